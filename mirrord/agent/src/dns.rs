@@ -1,10 +1,22 @@
 use std::{future, path::PathBuf, time::Duration};
 
 use futures::{stream::FuturesOrdered, StreamExt};
-use hickory_resolver::{system_conf::parse_resolv_conf, Hosts, Resolver};
+use hickory_resolver::{
+    config::LookupIpStrategy,
+    lookup::Lookup,
+    proto::{
+        rr::RecordType,
+        serialize::binary::{BinEncodable, BinEncoder},
+    },
+    system_conf::parse_resolv_conf,
+    Hosts, Resolver, TokioResolver,
+};
 use mirrord_protocol::{
-    dns::{DnsLookup, GetAddrInfoRequest, GetAddrInfoResponse},
-    DnsLookupError, RemoteResult, ResolveErrorKindInternal, ResponseError,
+    dns::{
+        AddressFamily, DnsLookup, DnsQueryRequest, DnsQueryResponse, DnsRecord,
+        GetAddrInfoRequestV2, GetAddrInfoResponse,
+    },
+    DaemonMessage, DnsLookupError, RemoteResult, ResolveErrorKindInternal, ResponseError,
 };
 use tokio::{
     fs,
@@ -21,10 +33,26 @@ use crate::{
     watched_task::TaskStatus,
 };
 
+/// Request handled by the [`DnsWorker`].
+#[derive(Debug)]
+pub(crate) enum DnsRequest {
+    /// Resolve IP addresses of a host.
+    GetAddrInfo(GetAddrInfoRequestV2),
+    /// Query records of any type.
+    Query(DnsQueryRequest),
+}
+
+/// Result of a [`DnsRequest`].
+#[derive(Debug)]
+enum DnsResponse {
+    GetAddrInfo(RemoteResult<DnsLookup>),
+    Query(RemoteResult<Vec<DnsRecord>>),
+}
+
 #[derive(Debug)]
 pub(crate) struct DnsCommand {
-    request: GetAddrInfoRequest,
-    response_tx: oneshot::Sender<RemoteResult<DnsLookup>>,
+    request: DnsRequest,
+    response_tx: oneshot::Sender<DnsResponse>,
 }
 
 /// Background task for resolving hostnames to IP addresses.
@@ -69,22 +97,19 @@ impl DnsWorker {
         }
     }
 
-    /// Reads `/etc/resolv.conf` and `/etc/hosts` files, then uses [`hickory_resolver::Resolver`] to
-    /// resolve address of the given `host`.
+    /// Reads `/etc/resolv.conf` and `/etc/hosts` files, then prepares a
+    /// [`hickory_resolver::Resolver`] that resolves addresses of the given `family`.
     ///
     /// # TODO
     ///
     /// We could probably cache results here.
     /// We cannot cache the [`Resolver`] itself, becaues the configuration in `etc` may change.
-    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
-    async fn do_lookup(
+    async fn build_resolver(
         etc_path: PathBuf,
-        host: String,
+        family: AddressFamily,
         attempts: usize,
         timeout: Duration,
-    ) -> RemoteResult<DnsLookup> {
-        // Prepares the `Resolver` after reading some `/etc` DNS files.
-        //
+    ) -> RemoteResult<TokioResolver> {
         // We care about logging these errors, at an `error!` level.
         let resolver: Result<_, ResponseError> = try {
             let resolv_conf_path = etc_path.join("resolv.conf");
@@ -98,7 +123,11 @@ impl DnsWorker {
                 hickory_resolver::config::ServerOrderingStrategy::UserProvidedOrder;
             options.timeout = timeout;
             options.attempts = attempts;
-            options.ip_strategy = hickory_resolver::config::LookupIpStrategy::Ipv4Only;
+            options.ip_strategy = match family {
+                AddressFamily::Ipv4Only => LookupIpStrategy::Ipv4Only,
+                AddressFamily::Ipv6Only => LookupIpStrategy::Ipv6Only,
+                AddressFamily::Both => LookupIpStrategy::Ipv4AndIpv6,
+            };
 
             let mut resolver = Resolver::tokio(config, options);
 
@@ -109,8 +138,20 @@ impl DnsWorker {
             resolver
         };
 
-        let lookup = resolver
-            .inspect_err(|fail| tracing::error!(?fail, "Failed to build DNS resolver"))?
+        resolver.inspect_err(|fail| tracing::error!(?fail, "Failed to build DNS resolver"))
+    }
+
+    /// Uses the resolver from [`Self::build_resolver`] to resolve address of the given `host`.
+    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
+    async fn do_lookup(
+        etc_path: PathBuf,
+        host: String,
+        family: AddressFamily,
+        attempts: usize,
+        timeout: Duration,
+    ) -> RemoteResult<DnsLookup> {
+        let lookup = Self::build_resolver(etc_path, family, attempts, timeout)
+            .await?
             .lookup_ip(host)
             .await
             .inspect(|lookup| tracing::trace!(?lookup, "Lookup finished"))?
@@ -119,6 +160,32 @@ impl DnsWorker {
         Ok(lookup)
     }
 
+    /// Uses the resolver from [`Self::build_resolver`] to query records of the given type.
+    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
+    async fn do_query(
+        etc_path: PathBuf,
+        name: String,
+        record_type: u16,
+        search: bool,
+        attempts: usize,
+        timeout: Duration,
+    ) -> RemoteResult<Vec<DnsRecord>> {
+        // The resolver does not apply the search domains to fully qualified names.
+        let name = if search || name.ends_with('.') {
+            name
+        } else {
+            format!("{name}.")
+        };
+
+        let lookup = Self::build_resolver(etc_path, AddressFamily::Both, attempts, timeout)
+            .await?
+            .lookup(name, RecordType::from(record_type))
+            .await
+            .inspect(|lookup| tracing::trace!(?lookup, "Query finished"))?;
+
+        Ok(lookup_into_records(&lookup))
+    }
+
     /// Handles the given [`DnsCommand`] in a separate [`tokio::task`].
     #[tracing::instrument(level = Level::TRACE, skip(self))]
     fn handle_message(&self, message: DnsCommand) {
@@ -126,7 +193,20 @@ impl DnsWorker {
         let timeout = self.timeout;
         let attempts = self.attempts;
         let lookup_future = async move {
-            let result = Self::do_lookup(etc_path, message.request.node, attempts, timeout).await;
+            let result = match message.request {
+                DnsRequest::GetAddrInfo(GetAddrInfoRequestV2 { node, family }) => {
+                    DnsResponse::GetAddrInfo(
+                        Self::do_lookup(etc_path, node, family, attempts, timeout).await,
+                    )
+                }
+                DnsRequest::Query(DnsQueryRequest {
+                    name,
+                    record_type,
+                    search,
+                }) => DnsResponse::Query(
+                    Self::do_query(etc_path, name, record_type, search, attempts, timeout).await,
+                ),
+            };
 
             if let Err(result) = message.response_tx.send(result) {
                 tracing::error!(?result, "Failed to send query response");
@@ -153,12 +233,40 @@ impl DnsWorker {
     }
 }
 
+/// Converts records of the given [`Lookup`] into [`DnsRecord`]s.
+///
+/// Record data is encoded without name compression, so that it can be copied as is into any DNS
+/// message. Records that fail to encode are skipped.
+fn lookup_into_records(lookup: &Lookup) -> Vec<DnsRecord> {
+    lookup
+        .records()
+        .iter()
+        .filter_map(|record| {
+            let mut data = Vec::new();
+            let mut encoder = BinEncoder::new(&mut data);
+            encoder.set_canonical_names(true);
+            record
+                .data()
+                .emit(&mut encoder)
+                .inspect_err(|error| tracing::debug!(%error, ?record, "Failed to encode record"))
+                .ok()?;
+
+            Some(DnsRecord {
+                name: record.name().to_string(),
+                record_type: record.record_type().into(),
+                ttl: record.ttl(),
+                data,
+            })
+        })
+        .collect()
+}
+
 pub(crate) struct DnsApi {
     task_status: TaskStatus,
     request_tx: Sender<DnsCommand>,
     /// [`DnsWorker`] processes all requests concurrently, so we use a combination of [`oneshot`]
     /// channels and [`FuturesOrdered`] to preserve order of responses.
-    responses: FuturesOrdered<oneshot::Receiver<RemoteResult<DnsLookup>>>,
+    responses: FuturesOrdered<oneshot::Receiver<DnsResponse>>,
 }
 
 impl DnsApi {
@@ -172,10 +280,7 @@ impl DnsApi {
 
     /// Schedules a new DNS request.
     /// Results of scheduled requests are available via [`Self::recv`] (order is preserved).
-    pub(crate) async fn make_request(
-        &mut self,
-        request: GetAddrInfoRequest,
-    ) -> Result<(), AgentError> {
+    pub(crate) async fn make_request(&mut self, request: DnsRequest) -> Result<(), AgentError> {
         let (response_tx, response_rx) = oneshot::channel();
 
         let command = DnsCommand {
@@ -194,12 +299,26 @@ impl DnsApi {
     /// Returns the result of the oldest outstanding DNS request issued with this struct (see
     /// [`Self::make_request`]).
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
-    pub(crate) async fn recv(&mut self) -> Result<GetAddrInfoResponse, AgentError> {
+    pub(crate) async fn recv(&mut self) -> Result<DaemonMessage, AgentError> {
         let Some(response) = self.responses.next().await else {
             return future::pending().await;
         };
 
-        let response = response?.map_err(|fail| match fail {
+        let message = match response? {
+            DnsResponse::GetAddrInfo(response) => DaemonMessage::GetAddrInfoResponse(
+                GetAddrInfoResponse(response.map_err(Self::into_lookup_error)),
+            ),
+            DnsResponse::Query(response) => DaemonMessage::DnsQueryResponse(DnsQueryResponse(
+                response.map_err(Self::into_lookup_error),
+            )),
+        };
+
+        Ok(message)
+    }
+
+    /// Converts any error from the [`DnsWorker`] into [`ResponseError::DnsLookup`].
+    fn into_lookup_error(fail: ResponseError) -> ResponseError {
+        match fail {
             ResponseError::RemoteIO(remote_ioerror) => ResponseError::DnsLookup(DnsLookupError {
                 kind: remote_ioerror.kind.into(),
             }),
@@ -207,8 +326,6 @@ impl DnsApi {
             _ => ResponseError::DnsLookup(DnsLookupError {
                 kind: ResolveErrorKindInternal::Unknown,
             }),
-        });
-
-        Ok(GetAddrInfoResponse(response))
+        }
     }
 }
@@ -10,10 +10,13 @@ use std::{
 };
 
 use client_connection::AgentTlsConnector;
-use dns::{DnsCommand, DnsWorker};
+use dns::{DnsCommand, DnsRequest, DnsWorker};
 use futures::TryFutureExt;
-use mirrord_protocol::{ClientMessage, DaemonMessage, GetEnvVarsRequest, LogMessage};
-use sniffer::tcp_capture::RawSocketTcpCapture;
+use mirrord_protocol::{
+    udp::{DaemonUdp, LayerUdp},
+    ClientMessage, DaemonMessage, GetEnvVarsRequest, LogMessage, ResponseError,
+};
+use sniffer::{tcp_capture::RawSocketTcpCapture, udp_capture::RawSocketUdpCapture};
 use tokio::{
     net::{TcpListener, TcpStream},
     process::Command,
@@ -36,12 +39,18 @@ use crate::{
     file::FileManager,
     outgoing::{TcpOutgoingApi, UdpOutgoingApi},
     runtime::get_container,
-    sniffer::{api::TcpSnifferApi, messages::SnifferCommand, TcpConnectionSniffer},
+    sniffer::{
+        api::{TcpSnifferApi, UdpSnifferApi},
+        messages::{SnifferCommand, UdpSnifferCommand},
+        udp::UdpDatagramSniffer,
+        TcpConnectionSniffer,
+    },
     steal::{
         ip_tables::{
-            new_iptables, IPTablesWrapper, SafeIpTables, IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL,
-            IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL_ENV, IPTABLE_MESH, IPTABLE_MESH_ENV,
-            IPTABLE_PREROUTING, IPTABLE_PREROUTING_ENV, IPTABLE_STANDARD, IPTABLE_STANDARD_ENV,
+            new_ip6tables, new_iptables, IPTablesWrapper, SafeIpTables,
+            IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL, IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL_ENV,
+            IPTABLE_MESH, IPTABLE_MESH_ENV, IPTABLE_PREROUTING, IPTABLE_PREROUTING_ENV,
+            IPTABLE_STANDARD, IPTABLE_STANDARD_ENV,
         },
         StealerCommand, TcpConnectionStealer, TcpStealerApi,
     },
@@ -186,6 +195,7 @@ impl<Command> Clone for BackgroundTask<Command> {
 #[derive(Clone)]
 struct BackgroundTasks {
     sniffer: BackgroundTask<SnifferCommand>,
+    udp_sniffer: BackgroundTask<UdpSnifferCommand>,
     stealer: BackgroundTask<StealerCommand>,
     dns: BackgroundTask<DnsCommand>,
 }
@@ -196,6 +206,7 @@ struct ClientConnectionHandler {
     file_manager: FileManager,
     connection: ClientConnection,
     tcp_sniffer_api: Option<TcpSnifferApi>,
+    udp_sniffer_api: Option<UdpSnifferApi>,
     tcp_stealer_api: Option<TcpStealerApi>,
     tcp_outgoing_api: TcpOutgoingApi,
     udp_outgoing_api: UdpOutgoingApi,
@@ -218,6 +229,8 @@ impl ClientConnectionHandler {
         let file_manager = FileManager::new(pid.or_else(|| state.ephemeral.then_some(1)));
 
         let tcp_sniffer_api = Self::create_sniffer_api(id, bg_tasks.sniffer, &mut connection).await;
+        let udp_sniffer_api =
+            Self::create_udp_sniffer_api(id, bg_tasks.udp_sniffer, &mut connection).await;
         let tcp_stealer_api =
             Self::create_stealer_api(id, bg_tasks.stealer, &mut connection).await?;
         let dns_api = Self::create_dns_api(bg_tasks.dns);
@@ -230,6 +243,7 @@ impl ClientConnectionHandler {
             file_manager,
             connection,
             tcp_sniffer_api,
+            udp_sniffer_api,
             tcp_stealer_api,
             tcp_outgoing_api,
             udp_outgoing_api,
@@ -269,6 +283,32 @@ impl ClientConnectionHandler {
         }
     }
 
+    async fn create_udp_sniffer_api(
+        id: ClientId,
+        task: BackgroundTask<UdpSnifferCommand>,
+        connection: &mut ClientConnection,
+    ) -> Option<UdpSnifferApi> {
+        if let BackgroundTask::Running(sniffer_status, sniffer_sender) = task {
+            match UdpSnifferApi::new(id, sniffer_sender, sniffer_status).await {
+                Ok(api) => Some(api),
+                Err(e) => {
+                    let message = format!("Failed to create UdpSnifferApi: {e}.");
+
+                    warn!(message);
+
+                    // Ignore message send error.
+                    let _ = connection
+                        .send(DaemonMessage::LogMessage(LogMessage::warn(message)))
+                        .await;
+
+                    None
+                }
+            }
+        } else {
+            None
+        }
+    }
+
     async fn create_stealer_api(
         id: ClientId,
         task: BackgroundTask<StealerCommand>,
@@ -350,6 +390,16 @@ impl ClientConnectionHandler {
                     },
                     Err(e) => break e,
                 },
+                message = async {
+                    if let Some(ref mut sniffer_api) = self.udp_sniffer_api {
+                        sniffer_api.recv().await
+                    } else {
+                        unreachable!()
+                    }
+                }, if self.udp_sniffer_api.is_some() => match message {
+                    Ok(message) => self.respond(DaemonMessage::Udp(message)).await?,
+                    Err(e) => break e,
+                },
                 message = async {
                     if let Some(ref mut stealer_api) = self.tcp_stealer_api {
                         stealer_api.recv().await
@@ -357,7 +407,7 @@ impl ClientConnectionHandler {
                         unreachable!()
                     }
                 }, if self.tcp_stealer_api.is_some() => match message {
-                    Ok(message) => self.respond(DaemonMessage::TcpSteal(message)).await?,
+                    Ok(message) => self.respond(message).await?,
                     Err(e) => break e,
                 },
                 message = self.tcp_outgoing_api.recv_from_task() => match message {
@@ -369,7 +419,7 @@ impl ClientConnectionHandler {
                     Err(e) => break e,
                 },
                 message = self.dns_api.recv() => match message {
-                    Ok(message) => self.respond(DaemonMessage::GetAddrInfoResponse(message)).await?,
+                    Ok(message) => self.respond(message).await?,
                     Err(e) => break e,
                 },
                 // message = self.vpn_api.daemon_message() => match message{
@@ -433,7 +483,19 @@ impl ClientConnectionHandler {
                     .await?
             }
             ClientMessage::GetAddrInfoRequest(request) => {
-                self.dns_api.make_request(request).await?;
+                self.dns_api
+                    .make_request(DnsRequest::GetAddrInfo(request.into()))
+                    .await?;
+            }
+            ClientMessage::GetAddrInfoRequestV2(request) => {
+                self.dns_api
+                    .make_request(DnsRequest::GetAddrInfo(request))
+                    .await?;
+            }
+            ClientMessage::DnsQuery(request) => {
+                self.dns_api
+                    .make_request(DnsRequest::Query(request))
+                    .await?;
             }
             ClientMessage::Ping => self.respond(DaemonMessage::Pong).await?,
             ClientMessage::Tcp(message) => {
@@ -481,6 +543,30 @@ impl ClientConnectionHandler {
                 unreachable!("VPN is not supported");
                 // self.vpn_api.layer_message(message).await?;
             }
+            ClientMessage::Udp(
+                message @ (LayerUdp::StealPortSubscribe(..)
+                | LayerUdp::StealPortUnsubscribe(..)
+                | LayerUdp::StealReply(..)),
+            ) => {
+                if let Some(tcp_stealer_api) = self.tcp_stealer_api.as_mut() {
+                    tcp_stealer_api.handle_udp_message(message).await?
+                } else {
+                    warn!("received udp steal request while not available");
+                    Err(AgentError::StealerNotRunning)?
+                }
+            }
+            ClientMessage::Udp(message) => match (&mut self.udp_sniffer_api, message) {
+                (Some(sniffer_api), message) => sniffer_api.handle_client_message(message).await?,
+                (None, LayerUdp::PortSubscribe(port)) => {
+                    warn!(port, "received udp sniffer request while not available");
+                    self.respond(DaemonMessage::Udp(DaemonUdp::SubscribeResult(Err(
+                        ResponseError::NotImplemented,
+                    ))))
+                    .await?
+                }
+                // Steal messages are handled above.
+                (None, _) => {}
+            },
         }
 
         Ok(true)
@@ -506,6 +592,7 @@ async fn start_agent(args: Args) -> Result<()> {
     let cancel_guard = cancellation_token.clone().drop_guard();
 
     let (sniffer_command_tx, sniffer_command_rx) = mpsc::channel::<SnifferCommand>(1000);
+    let (udp_sniffer_command_tx, udp_sniffer_command_rx) = mpsc::channel::<UdpSnifferCommand>(1000);
     let (stealer_command_tx, stealer_command_rx) = mpsc::channel::<StealerCommand>(1000);
     let (dns_command_tx, dns_command_rx) = mpsc::channel::<DnsCommand>(1000);
 
@@ -519,13 +606,13 @@ async fn start_agent(args: Args) -> Result<()> {
         // so we just check that initialization was successful
         // then decide whether to store the task or drop it
         // https://github.com/metalbear-co/mirrord/pull/2910
+        let network_interface = args.network_interface.clone();
         let (sniffer_init_tx, sniffer_init_rx) = tokio::sync::oneshot::channel::<bool>();
         let watched_task = WatchedTask::new(
             TcpConnectionSniffer::<RawSocketTcpCapture>::TASK_NAME,
             async move {
                 if let Ok(sniffer) =
-                    TcpConnectionSniffer::new(sniffer_command_rx, args.network_interface, is_mesh)
-                        .await
+                    TcpConnectionSniffer::new(sniffer_command_rx, network_interface, is_mesh).await
                 {
                     if let Err(error) = sniffer_init_tx.send(true) {
                         tracing::error!(%error, "Failed to send sniffer init result");
@@ -560,19 +647,75 @@ async fn start_agent(args: Args) -> Result<()> {
         }
     };
 
+    let (udp_sniffer_task, udp_sniffer_status) = if args.mode.is_targetless() {
+        (None, None)
+    } else {
+        let cancellation_token = cancellation_token.clone();
+        let is_mesh = args.is_mesh();
+        let network_interface = args.network_interface.clone();
+        // Same as with the TCP sniffer, failing to initialize the UDP sniffer only disables the
+        // feature.
+        let (sniffer_init_tx, sniffer_init_rx) = tokio::sync::oneshot::channel::<bool>();
+        let watched_task = WatchedTask::new(
+            UdpDatagramSniffer::<RawSocketUdpCapture>::TASK_NAME,
+            async move {
+                match UdpDatagramSniffer::new(udp_sniffer_command_rx, network_interface, is_mesh)
+                    .await
+                {
+                    Ok(sniffer) => {
+                        if let Err(error) = sniffer_init_tx.send(true) {
+                            tracing::error!(%error, "Failed to send UDP sniffer init result");
+                        };
+                        // will block from this point on
+                        let res = sniffer.start(cancellation_token).await;
+                        if let Err(err) = res {
+                            error!(%err, "UDP sniffer failed");
+                        }
+                    }
+                    Err(error) => {
+                        warn!(%error, "Failed to initialize UDP sniffer");
+                        if let Err(error) = sniffer_init_tx.send(false) {
+                            tracing::error!(%error, "Failed to send UDP sniffer init result");
+                        }
+                    }
+                }
+
+                Ok(())
+            },
+        );
+        let status = watched_task.status();
+        let task = run_thread_in_namespace(
+            watched_task.start(),
+            UdpDatagramSniffer::<RawSocketUdpCapture>::TASK_NAME.to_string(),
+            state.container_pid(),
+            "net",
+        );
+
+        match sniffer_init_rx.await {
+            Ok(true) => (Some(task), Some(status)),
+            Ok(false) => (None, None),
+            Err(error) => {
+                tracing::error!(%error, "unexpected error while waiting for UDP sniffer init");
+                (None, None)
+            }
+        }
+    };
+
     let (stealer_task, stealer_status) = if args.mode.is_targetless() {
         (None, None)
     } else {
         let cancellation_token = cancellation_token.clone();
         let watched_task = WatchedTask::new(
             TcpConnectionStealer::TASK_NAME,
-            TcpConnectionStealer::new(stealer_command_rx).and_then(|stealer| async move {
-                let res = stealer.start(cancellation_token).await;
-                if let Err(err) = res.as_ref() {
-                    error!("Stealer failed: {err}");
-                }
-                res
-            }),
+            TcpConnectionStealer::new(stealer_command_rx, state.container_pid()).and_then(
+                |stealer| async move {
+                    let res = stealer.start(cancellation_token).await;
+                    if let Err(err) = res.as_ref() {
+                        error!("Stealer failed: {err}");
+                    }
+                    res
+                },
+            ),
         );
         let status = watched_task.status();
         let task = run_thread_in_namespace(
@@ -606,6 +749,9 @@ async fn start_agent(args: Args) -> Result<()> {
         sniffer: sniffer_status
             .map(|status| BackgroundTask::Running(status, sniffer_command_tx))
             .unwrap_or(BackgroundTask::Disabled),
+        udp_sniffer: udp_sniffer_status
+            .map(|status| BackgroundTask::Running(status, udp_sniffer_command_tx))
+            .unwrap_or(BackgroundTask::Disabled),
         stealer: stealer_status
             .map(|status| BackgroundTask::Running(status, stealer_command_tx))
             .unwrap_or(BackgroundTask::Disabled),
@@ -689,6 +835,7 @@ async fn start_agent(args: Args) -> Result<()> {
 
     let BackgroundTasks {
         sniffer,
+        udp_sniffer,
         stealer,
         dns,
     } = bg_tasks;
@@ -702,6 +849,15 @@ async fn start_agent(args: Args) -> Result<()> {
         }
     }
 
+    if let (Some(udp_sniffer_task), BackgroundTask::Running(mut udp_sniffer_status, _)) =
+        (udp_sniffer_task, udp_sniffer)
+    {
+        udp_sniffer_task.join().map_err(|_| AgentError::JoinTask)?;
+        if let Some(err) = udp_sniffer_status.err().await {
+            error!("start_agent -> UDP sniffer task failed with error: {}", err);
+        }
+    }
+
     if let (Some(stealer_task), BackgroundTask::Running(mut stealer_status, _)) =
         (stealer_task, stealer)
     {
@@ -731,6 +887,15 @@ async fn clear_iptable_chain() -> Result<()> {
         .cleanup()
         .await?;
 
+    if std::env::var("MIRRORD_AGENT_SUPPORT_IPV6").is_ok_and(|val| val.to_lowercase() == "true") {
+        let ipt = new_ip6tables();
+
+        SafeIpTables::load(IPTablesWrapper::from(ipt), false)
+            .await?
+            .cleanup()
+            .await?;
+    }
+
     Ok(())
 }
 
@@ -5,8 +5,11 @@ use thiserror::Error;
 use tokio::sync::mpsc::{self, error::SendError};
 
 use crate::{
-    client_connection::TlsSetupError, namespace::NamespaceError, runtime,
-    sniffer::messages::SnifferCommand, steal::StealerCommand,
+    client_connection::TlsSetupError,
+    namespace::NamespaceError,
+    runtime,
+    sniffer::messages::SnifferCommand,
+    steal::{StealTlsError, StealerCommand},
 };
 
 #[derive(Debug, Error)]
@@ -70,6 +73,9 @@ pub(crate) enum AgentError {
     #[error("TLS setup failed: {0}")]
     TlsSetupError(#[from] TlsSetupError),
 
+    #[error("TLS setup for stolen connections failed: {0}")]
+    StealTls(#[from] StealTlsError),
+
     /// Child agent process spawned in `main` failed.
     #[error("Agent child process failed: {0}")]
     AgentFailed(ExitStatus),
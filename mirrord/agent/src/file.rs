@@ -1,18 +1,29 @@
 use std::{
     self,
     collections::{hash_map::Entry, HashMap, VecDeque},
-    fs::{read_link, File, OpenOptions, ReadDir},
+    fs::{read_link, File, OpenOptions, Permissions, ReadDir},
     io::{self, prelude::*, BufReader, SeekFrom},
     iter::{Enumerate, Peekable},
     ops::RangeInclusive,
-    os::unix::{fs::MetadataExt, prelude::FileExt},
+    os::unix::{
+        fs::{MetadataExt, PermissionsExt},
+        io::AsRawFd,
+        prelude::FileExt,
+    },
     path::{Path, PathBuf},
 };
 
 use faccess::{AccessMode, PathExt};
 use libc::DT_DIR;
-use mirrord_protocol::{file::*, FileRequest, FileResponse, RemoteResult, ResponseError};
+use mirrord_protocol::{
+    file::*, BlockedAction, FileRequest, FileResponse, RemoteResult, ResponseError,
+};
+use nix::sys::{
+    stat::{futimens, utimensat, UtimensatFlags},
+    time::TimeSpec,
+};
 use tracing::{error, trace, Level};
+use wildmatch::WildMatch;
 
 use crate::error::Result;
 
@@ -70,6 +81,8 @@ pub(crate) struct FileManager {
     dir_streams: HashMap<u64, Enumerate<ReadDir>>,
     getdents_streams: HashMap<u64, Peekable<GetDEnts64Stream>>,
     fds_iter: RangeInclusive<u64>,
+    /// Paths blocked by the mirrord policies, set with [`FsPolicyRequest`].
+    blocked_paths: Vec<BlockedPath>,
 }
 
 impl Default for FileManager {
@@ -80,10 +93,29 @@ impl Default for FileManager {
             dir_streams: Default::default(),
             getdents_streams: Default::default(),
             fds_iter: (0..=u64::MAX),
+            blocked_paths: Default::default(),
         }
     }
 }
 
+/// Returns the first of the `blocked` patterns that matches the given path (as seen from the
+/// target container).
+fn find_blocked<'a>(blocked: &'a [BlockedPath], path: &Path) -> Option<&'a BlockedPath> {
+    let path = path.to_string_lossy();
+    blocked
+        .iter()
+        .find(|blocked| WildMatch::new(&blocked.pattern).matches(&path))
+}
+
+/// Whether the directory entry at the given `host_path` (see [`resolve_path`]) is hidden by the
+/// `blocked` patterns.
+fn entry_blocked(blocked: &[BlockedPath], root_path: &Path, host_path: &Path) -> bool {
+    !blocked.is_empty()
+        && host_path
+            .strip_prefix(root_path)
+            .is_ok_and(|path| find_blocked(blocked, &Path::new("/").join(path)).is_some())
+}
+
 pub fn get_root_path_from_optional_pid(pid: Option<u64>) -> PathBuf {
     match pid {
         Some(pid) => PathBuf::from("/proc").join(pid.to_string()).join("root"),
@@ -149,6 +181,10 @@ impl FileManager {
     /// Executes the request and returns the response.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn handle_message(&mut self, request: FileRequest) -> Result<Option<FileResponse>> {
+        if let Some(response) = self.blocked_by_policy(&request) {
+            return Ok(Some(response));
+        }
+
         Ok(match request {
             FileRequest::Open(OpenFileRequest { path, open_options }) => {
                 // TODO: maybe not agent error on this?
@@ -258,9 +294,225 @@ impl FileManager {
                 pathname,
                 mode,
             }) => Some(FileResponse::MakeDir(self.mkdirat(dirfd, &pathname, mode))),
+            FileRequest::Unlink(UnlinkRequest { pathname }) => {
+                Some(FileResponse::Unlink(self.unlink(&pathname)))
+            }
+            FileRequest::UnlinkAt(UnlinkAtRequest {
+                dirfd,
+                pathname,
+                flags,
+            }) => Some(FileResponse::Unlink(self.unlinkat(dirfd, &pathname, flags))),
+            FileRequest::RemoveDir(RemoveDirRequest { pathname }) => {
+                Some(FileResponse::RemoveDir(self.rmdir(&pathname)))
+            }
+            FileRequest::Rename(RenameRequest { old_path, new_path }) => {
+                Some(FileResponse::Rename(self.rename(&old_path, &new_path)))
+            }
+            FileRequest::Ftruncate(FtruncateRequest { fd, length }) => {
+                Some(FileResponse::Truncate(self.ftruncate(fd, length)))
+            }
+            FileRequest::Truncate(TruncateRequest { path, length }) => {
+                Some(FileResponse::Truncate(self.truncate(&path, length)))
+            }
+            FileRequest::Chmod(ChmodRequest { path, mode }) => {
+                Some(FileResponse::Chmod(self.chmod(&path, mode)))
+            }
+            FileRequest::Fchmod(FchmodRequest { fd, mode }) => {
+                Some(FileResponse::Chmod(self.fchmod(fd, mode)))
+            }
+            FileRequest::Chown(ChownRequest { path, owner, group }) => {
+                Some(FileResponse::Chown(self.chown(&path, owner, group)))
+            }
+            FileRequest::Fchown(FchownRequest { fd, owner, group }) => {
+                Some(FileResponse::Chown(self.fchown(fd, owner, group)))
+            }
+            FileRequest::Utimensat(request) => {
+                Some(FileResponse::Utimensat(self.utimensat(request)))
+            }
+            FileRequest::Symlink(SymlinkRequest { target, link_path }) => {
+                Some(FileResponse::Symlink(self.symlink(&target, &link_path)))
+            }
+            FileRequest::Link(LinkRequest { old_path, new_path }) => {
+                Some(FileResponse::Link(self.link(&old_path, &new_path)))
+            }
+            FileRequest::Reopen(ReopenFileRequest {
+                fd,
+                path,
+                open_options,
+                position,
+            }) => Some(FileResponse::Open(self.reopen(
+                fd,
+                path,
+                open_options,
+                position,
+            ))),
+            FileRequest::FsPolicy(FsPolicyRequest { block }) => {
+                self.blocked_paths = block;
+                None
+            }
         })
     }
 
+    /// How many symlinks we follow when resolving a path, like `MAXSYMLINKS` in Linux.
+    const MAX_SYMLINKS: usize = 40;
+
+    /// Resolves all symlinks in the given `path` (as seen from the target container), like
+    /// `realpath`. Components that do not exist are kept as they are.
+    ///
+    /// With `follow_last == false`, the last component is kept as is, like in `lstat`.
+    fn canonical_path(&self, path: &Path, follow_last: bool) -> io::Result<PathBuf> {
+        if !follow_last && let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            return Ok(self.canonical_path(parent, true)?.join(name));
+        }
+
+        let mut current = Path::new("/").join(path);
+        for _ in 0..Self::MAX_SYMLINKS {
+            let resolved = resolve_path(&current, &self.root_path)?;
+            let resolved = Path::new("/").join(
+                resolved
+                    .strip_prefix(&self.root_path)
+                    .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+            );
+
+            if resolved == current {
+                return Ok(resolved);
+            }
+            current = resolved;
+        }
+
+        Err(io::Error::from_raw_os_error(libc::ELOOP))
+    }
+
+    /// Checks the given `path` (as seen from the target container) against the
+    /// [`Self::blocked_paths`].
+    ///
+    /// Both the path with all symlinks resolved and the path with the last symlink kept are
+    /// checked, so that a symlink cannot be used to reach a blocked path, and a blocked symlink
+    /// cannot be removed or replaced. Paths that cannot be resolved are blocked.
+    fn check_policy(&self, path: &Path) -> RemoteResult<()> {
+        let forbidden = |policy_name| ResponseError::Forbidden {
+            blocked_action: BlockedAction::FileAccess(path.to_path_buf()),
+            policy_name,
+        };
+
+        for follow_last in [false, true] {
+            let canonical = self
+                .canonical_path(path, follow_last)
+                .map_err(|_| forbidden(None))?;
+            if let Some(blocked) = find_blocked(&self.blocked_paths, &canonical) {
+                return Err(forbidden(Some(blocked.policy.clone())));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path (as seen from the target container) of the directory opened under the
+    /// given `fd`, if there is one.
+    fn dir_path(&self, fd: u64) -> Option<PathBuf> {
+        match self.open_files.get(&fd)? {
+            RemoteFile::Directory(path) => path
+                .strip_prefix(&self.root_path)
+                .ok()
+                .map(|path| Path::new("/").join(path)),
+            RemoteFile::File(..) => None,
+        }
+    }
+
+    /// Checks the remote paths used in the request against the [`Self::blocked_paths`].
+    ///
+    /// Returns the response for the client when the request is blocked.
+    fn blocked_by_policy(&self, request: &FileRequest) -> Option<FileResponse> {
+        if self.blocked_paths.is_empty() {
+            return None;
+        }
+
+        let relative =
+            |dirfd: Option<u64>, path: &Path| match dirfd.and_then(|fd| self.dir_path(fd)) {
+                Some(dir) if path.is_relative() => dir.join(path),
+                _ => path.to_path_buf(),
+            };
+
+        let (paths, response): (Vec<PathBuf>, fn(ResponseError) -> FileResponse) = match request {
+            FileRequest::Open(open) => (vec![open.path.clone()], |e| FileResponse::Open(Err(e))),
+            FileRequest::Reopen(reopen) => {
+                (vec![reopen.path.clone()], |e| FileResponse::Open(Err(e)))
+            }
+            FileRequest::OpenRelative(open) => {
+                (vec![relative(Some(open.relative_fd), &open.path)], |e| {
+                    FileResponse::Open(Err(e))
+                })
+            }
+            FileRequest::FdOpenDir(open) => match self.dir_path(open.remote_fd) {
+                Some(path) => (vec![path], |e| FileResponse::OpenDir(Err(e))),
+                None => return None,
+            },
+            FileRequest::Access(access) => (vec![access.pathname.clone()], |e| {
+                FileResponse::Access(Err(e))
+            }),
+            FileRequest::Xstat(xstat) => match &xstat.path {
+                Some(path) => (vec![relative(xstat.fd, path)], |e| {
+                    FileResponse::Xstat(Err(e))
+                }),
+                None => return None,
+            },
+            FileRequest::ReadLink(read_link) => (vec![read_link.path.clone()], |e| {
+                FileResponse::ReadLink(Err(e))
+            }),
+            FileRequest::MakeDir(make_dir) => (vec![make_dir.pathname.clone()], |e| {
+                FileResponse::MakeDir(Err(e))
+            }),
+            FileRequest::MakeDirAt(make_dir) => (
+                vec![relative(Some(make_dir.dirfd), &make_dir.pathname)],
+                |e| FileResponse::MakeDir(Err(e)),
+            ),
+            FileRequest::Unlink(unlink) => (vec![unlink.pathname.clone()], |e| {
+                FileResponse::Unlink(Err(e))
+            }),
+            FileRequest::UnlinkAt(unlink) => {
+                (vec![relative(Some(unlink.dirfd), &unlink.pathname)], |e| {
+                    FileResponse::Unlink(Err(e))
+                })
+            }
+            FileRequest::Rename(rename) => (
+                vec![rename.old_path.clone(), rename.new_path.clone()],
+                |e| FileResponse::Rename(Err(e)),
+            ),
+            FileRequest::RemoveDir(remove) => (vec![remove.pathname.clone()], |e| {
+                FileResponse::RemoveDir(Err(e))
+            }),
+            FileRequest::Truncate(truncate) => (vec![truncate.path.clone()], |e| {
+                FileResponse::Truncate(Err(e))
+            }),
+            FileRequest::Chmod(chmod) => {
+                (vec![chmod.path.clone()], |e| FileResponse::Chmod(Err(e)))
+            }
+            FileRequest::Chown(chown) => {
+                (vec![chown.path.clone()], |e| FileResponse::Chown(Err(e)))
+            }
+            FileRequest::Utimensat(utimensat) => match &utimensat.pathname {
+                Some(path) => (vec![relative(utimensat.dirfd, path)], |e| {
+                    FileResponse::Utimensat(Err(e))
+                }),
+                None => return None,
+            },
+            FileRequest::Symlink(symlink) => (vec![symlink.link_path.clone()], |e| {
+                FileResponse::Symlink(Err(e))
+            }),
+            FileRequest::Link(link) => (vec![link.old_path.clone(), link.new_path.clone()], |e| {
+                FileResponse::Link(Err(e))
+            }),
+            _ => return None,
+        };
+
+        // Paths relative to unknown descriptors are rejected by the operation itself.
+        paths
+            .iter()
+            .filter(|path| path.is_absolute())
+            .find_map(|path| self.check_policy(path).err())
+            .map(response)
+    }
+
     #[tracing::instrument(level = "trace")]
     pub fn new(pid: Option<u64>) -> Self {
         let root_path = get_root_path_from_optional_pid(pid);
@@ -299,6 +551,42 @@ impl FileManager {
         Ok(OpenFileResponse { fd })
     }
 
+    /// Opens the file under the given `fd`, that was handed out by the agent of a previous
+    /// intproxy connection.
+    ///
+    /// Descriptors allocated later are always greater than `fd`, even if this fails, so that the
+    /// layer never gets a descriptor that it already holds.
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn reopen(
+        &mut self,
+        fd: u64,
+        path: PathBuf,
+        open_options: OpenOptionsInternal,
+        position: u64,
+    ) -> RemoteResult<OpenFileResponse> {
+        if *self.fds_iter.start() <= fd {
+            self.fds_iter = fd.saturating_add(1)..=u64::MAX;
+        }
+
+        if self.open_files.contains_key(&fd) {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists).into());
+        }
+
+        let path = resolve_path(path, &self.root_path)?;
+        let mut file = OpenOptions::from(open_options).open(&path)?;
+
+        let remote_file = if file.metadata()?.is_dir() {
+            RemoteFile::Directory(path)
+        } else {
+            file.seek(SeekFrom::Start(position))?;
+            RemoteFile::File(file)
+        };
+
+        self.open_files.insert(fd, remote_file);
+
+        Ok(OpenFileResponse { fd })
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     fn open_relative(
         &mut self,
@@ -520,6 +808,295 @@ impl FileManager {
         }
     }
 
+    /// Resolves only the parent of `path` (see [`resolve_path`]), keeping the last component as
+    /// is.
+    ///
+    /// Used by operations that act on the directory entry itself (`unlink`, `rename`, `utimensat`
+    /// without following symlinks), so that we act on a symlink, and not the file it points to.
+    fn resolve_entry_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} has no file name"),
+            )
+        })?;
+        let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+
+        Ok(resolve_path(parent, &self.root_path)?.join(file_name))
+    }
+
+    /// Turns the host path of an open directory back into the path inside the container, so that
+    /// paths relative to it can go through [`resolve_path`] again.
+    fn container_path(&self, host_path: &Path) -> PathBuf {
+        Path::new("/").join(host_path.strip_prefix(&self.root_path).unwrap_or(host_path))
+    }
+
+    pub(crate) fn unlink(&mut self, path: &Path) -> RemoteResult<()> {
+        trace!("FileManager::unlink -> path {:#?}", path);
+
+        let path = self.resolve_entry_path(path)?;
+
+        Ok(std::fs::remove_file(path)?)
+    }
+
+    pub(crate) fn unlinkat(&mut self, dirfd: u64, path: &Path, flags: u32) -> RemoteResult<()> {
+        trace!(
+            "FileManager::unlinkat -> dirfd {:#?} | path {:#?} | flags {:#?}",
+            dirfd,
+            path,
+            flags
+        );
+
+        let relative_dir = self
+            .open_files
+            .get(&dirfd)
+            .ok_or(ResponseError::NotFound(dirfd))?;
+
+        if let RemoteFile::Directory(relative_dir) = relative_dir {
+            let path = self.resolve_entry_path(&self.container_path(relative_dir).join(path))?;
+
+            if flags & libc::AT_REMOVEDIR as u32 != 0 {
+                Ok(std::fs::remove_dir(path)?)
+            } else {
+                Ok(std::fs::remove_file(path)?)
+            }
+        } else {
+            Err(ResponseError::NotDirectory(dirfd))
+        }
+    }
+
+    pub(crate) fn rmdir(&mut self, path: &Path) -> RemoteResult<()> {
+        trace!("FileManager::rmdir -> path {:#?}", path);
+
+        let path = self.resolve_entry_path(path)?;
+
+        Ok(std::fs::remove_dir(path)?)
+    }
+
+    pub(crate) fn rename(&mut self, old_path: &Path, new_path: &Path) -> RemoteResult<()> {
+        trace!(
+            "FileManager::rename -> old_path {:#?} | new_path {:#?}",
+            old_path,
+            new_path
+        );
+
+        let old_path = self.resolve_entry_path(old_path)?;
+        let new_path = self.resolve_entry_path(new_path)?;
+
+        Ok(std::fs::rename(old_path, new_path)?)
+    }
+
+    pub(crate) fn ftruncate(&mut self, fd: u64, length: u64) -> RemoteResult<()> {
+        trace!(
+            "FileManager::ftruncate -> fd {:#?} | length {:#?}",
+            fd,
+            length
+        );
+
+        self.open_files
+            .get_mut(&fd)
+            .ok_or(ResponseError::NotFound(fd))
+            .and_then(|remote_file| {
+                if let RemoteFile::File(file) = remote_file {
+                    Ok(file.set_len(length)?)
+                } else {
+                    Err(ResponseError::NotFile(fd))
+                }
+            })
+    }
+
+    pub(crate) fn truncate(&mut self, path: &Path, length: u64) -> RemoteResult<()> {
+        trace!(
+            "FileManager::truncate -> path {:#?} | length {:#?}",
+            path,
+            length
+        );
+
+        let path = resolve_path(path, &self.root_path)?;
+        let file = OpenOptions::new().write(true).open(path)?;
+
+        Ok(file.set_len(length)?)
+    }
+
+    pub(crate) fn chmod(&mut self, path: &Path, mode: u32) -> RemoteResult<()> {
+        trace!("FileManager::chmod -> path {:#?} | mode {:#?}", path, mode);
+
+        let path = resolve_path(path, &self.root_path)?;
+
+        Ok(std::fs::set_permissions(
+            path,
+            Permissions::from_mode(mode),
+        )?)
+    }
+
+    pub(crate) fn fchmod(&mut self, fd: u64, mode: u32) -> RemoteResult<()> {
+        trace!("FileManager::fchmod -> fd {:#?} | mode {:#?}", fd, mode);
+
+        match self
+            .open_files
+            .get(&fd)
+            .ok_or(ResponseError::NotFound(fd))?
+        {
+            RemoteFile::File(file) => Ok(file.set_permissions(Permissions::from_mode(mode))?),
+            RemoteFile::Directory(path) => Ok(std::fs::set_permissions(
+                path,
+                Permissions::from_mode(mode),
+            )?),
+        }
+    }
+
+    pub(crate) fn chown(
+        &mut self,
+        path: &Path,
+        owner: Option<u32>,
+        group: Option<u32>,
+    ) -> RemoteResult<()> {
+        trace!(
+            "FileManager::chown -> path {:#?} | owner {:#?} | group {:#?}",
+            path,
+            owner,
+            group
+        );
+
+        let path = resolve_path(path, &self.root_path)?;
+
+        nix::unistd::chown(
+            &path,
+            owner.map(nix::unistd::Uid::from_raw),
+            group.map(nix::unistd::Gid::from_raw),
+        )
+        .map_err(|err| ResponseError::from(std::io::Error::from_raw_os_error(err as i32)))
+    }
+
+    pub(crate) fn fchown(
+        &mut self,
+        fd: u64,
+        owner: Option<u32>,
+        group: Option<u32>,
+    ) -> RemoteResult<()> {
+        trace!(
+            "FileManager::fchown -> fd {:#?} | owner {:#?} | group {:#?}",
+            fd,
+            owner,
+            group
+        );
+
+        let owner = owner.map(nix::unistd::Uid::from_raw);
+        let group = group.map(nix::unistd::Gid::from_raw);
+
+        let result = match self
+            .open_files
+            .get(&fd)
+            .ok_or(ResponseError::NotFound(fd))?
+        {
+            RemoteFile::File(file) => nix::unistd::fchown(file.as_raw_fd(), owner, group),
+            RemoteFile::Directory(path) => nix::unistd::chown(path, owner, group),
+        };
+
+        result.map_err(|err| ResponseError::from(std::io::Error::from_raw_os_error(err as i32)))
+    }
+
+    pub(crate) fn utimensat(&mut self, request: UtimensatRequest) -> RemoteResult<()> {
+        trace!("FileManager::utimensat -> request {:#?}", request);
+
+        let UtimensatRequest {
+            dirfd,
+            pathname,
+            access_time,
+            modification_time,
+            follow_symlink,
+        } = request;
+
+        let to_timespec = |time: FileTimeInternal| match time {
+            FileTimeInternal::Now => TimeSpec::UTIME_NOW,
+            FileTimeInternal::Omit => TimeSpec::UTIME_OMIT,
+            FileTimeInternal::Set {
+                seconds,
+                nanoseconds,
+            } => TimeSpec::new(seconds, nanoseconds),
+        };
+        let access_time = to_timespec(access_time);
+        let modification_time = to_timespec(modification_time);
+
+        let flags = if follow_symlink {
+            UtimensatFlags::FollowSymlink
+        } else {
+            UtimensatFlags::NoFollowSymlink
+        };
+
+        let relative_to = dirfd
+            .map(|dirfd| {
+                self.open_files
+                    .get(&dirfd)
+                    .ok_or(ResponseError::NotFound(dirfd))
+                    .map(|remote_file| (dirfd, remote_file))
+            })
+            .transpose()?;
+
+        let result = match (pathname, relative_to) {
+            (Some(pathname), _) if pathname.is_absolute() => {
+                // Like `lutimes`, without following the symlink the last component is kept as is,
+                // so that we update the symlink itself.
+                let path = if follow_symlink || pathname.file_name().is_none() {
+                    resolve_path(pathname, &self.root_path)?
+                } else {
+                    self.resolve_entry_path(&pathname)?
+                };
+                utimensat(None, &path, &access_time, &modification_time, flags)
+            }
+            (Some(pathname), Some((_, RemoteFile::Directory(relative_dir)))) => {
+                let pathname = self.container_path(relative_dir).join(pathname);
+                let path = if follow_symlink || pathname.file_name().is_none() {
+                    resolve_path(pathname, &self.root_path)?
+                } else {
+                    self.resolve_entry_path(&pathname)?
+                };
+                utimensat(None, &path, &access_time, &modification_time, flags)
+            }
+            (Some(_), Some((dirfd, RemoteFile::File(_)))) => {
+                return Err(ResponseError::NotDirectory(dirfd))
+            }
+            (None, Some((_, RemoteFile::File(file)))) => {
+                futimens(file.as_raw_fd(), &access_time, &modification_time)
+            }
+            (None, Some((_, RemoteFile::Directory(path)))) => {
+                utimensat(None, path, &access_time, &modification_time, flags)
+            }
+            (_, None) => {
+                return Err(ResponseError::from(io::Error::from(
+                    io::ErrorKind::InvalidInput,
+                )))
+            }
+        };
+
+        result.map_err(|err| ResponseError::from(std::io::Error::from_raw_os_error(err as i32)))
+    }
+
+    pub(crate) fn symlink(&mut self, target: &Path, link_path: &Path) -> RemoteResult<()> {
+        trace!(
+            "FileManager::symlink -> target {:#?} | link_path {:#?}",
+            target,
+            link_path
+        );
+
+        let link_path = self.resolve_entry_path(link_path)?;
+
+        Ok(std::os::unix::fs::symlink(target, link_path)?)
+    }
+
+    pub(crate) fn link(&mut self, old_path: &Path, new_path: &Path) -> RemoteResult<()> {
+        trace!(
+            "FileManager::link -> old_path {:#?} | new_path {:#?}",
+            old_path,
+            new_path
+        );
+
+        let old_path = self.resolve_entry_path(old_path)?;
+        let new_path = self.resolve_entry_path(new_path)?;
+
+        Ok(std::fs::hard_link(old_path, new_path)?)
+    }
+
     pub(crate) fn seek(&mut self, fd: u64, seek_from: SeekFrom) -> RemoteResult<SeekFileResponse> {
         trace!(
             "FileManager::seek -> fd {:#?} | seek_from {:#?}",
@@ -711,13 +1288,6 @@ impl FileManager {
         Ok(OpenDirResponse { fd })
     }
 
-    #[tracing::instrument(level = "trace", skip(self))]
-    pub(crate) fn get_dir_stream(&mut self, fd: u64) -> RemoteResult<&mut Enumerate<ReadDir>> {
-        self.dir_streams
-            .get_mut(&fd)
-            .ok_or(ResponseError::NotFound(fd))
-    }
-
     fn path_to_dir_entry_internal(
         path: &Path,
         position: u64,
@@ -777,8 +1347,17 @@ impl FileManager {
 
     #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
     pub(crate) fn read_dir(&mut self, fd: u64) -> RemoteResult<ReadDirResponse> {
-        let dir_stream = self.get_dir_stream(fd)?;
-        let result = if let Some(offset_entry_pair) = dir_stream.next() {
+        let dir_stream = self
+            .dir_streams
+            .get_mut(&fd)
+            .ok_or(ResponseError::NotFound(fd))?;
+        let next = dir_stream.find(|(_, entry)| {
+            !entry.as_ref().is_ok_and(|entry| {
+                entry_blocked(&self.blocked_paths, &self.root_path, &entry.path())
+            })
+        });
+
+        let result = if let Some(offset_entry_pair) = next {
             ReadDirResponse {
                 direntry: Some(offset_entry_pair.try_into()?),
             }
@@ -799,7 +1378,14 @@ impl FileManager {
         amount: usize,
     ) -> RemoteResult<ReadDirBatchResponse> {
         let result = self
-            .get_dir_stream(fd)?
+            .dir_streams
+            .get_mut(&fd)
+            .ok_or(ResponseError::NotFound(fd))?
+            .filter(|(_, entry)| {
+                !entry.as_ref().is_ok_and(|entry| {
+                    entry_blocked(&self.blocked_paths, &self.root_path, &entry.path())
+                })
+            })
             .take(amount)
             .map(DirEntryInternal::try_from)
             .try_collect::<Vec<_>>()
@@ -821,6 +1407,20 @@ impl FileManager {
     ) -> RemoteResult<GetDEnts64Response> {
         let mut result_size = 0u64;
 
+        let dir_path = match self.open_files.get(&fd) {
+            Some(RemoteFile::Directory(path)) => Some(path.clone()),
+            _ => None,
+        };
+        let blocked_paths = self.blocked_paths.clone();
+        let root_path = self.root_path.clone();
+        let blocked = |entry: &DirEntryInternal| {
+            entry.name != "."
+                && entry.name != ".."
+                && dir_path.as_ref().is_some_and(|dir| {
+                    entry_blocked(&blocked_paths, &root_path, &dir.join(&entry.name))
+                })
+        };
+
         // If this is the first call with this fd, the stream will be created, otherwise the
         // existing one is retrieved and we continue from where we stopped on the last call.
         let entry_results = self.get_or_create_getdents64_stream(fd)?;
@@ -853,6 +1453,10 @@ impl FileManager {
                 })
                 .transpose()?
             {
+                if blocked(&entry) {
+                    continue;
+                }
+
                 result_size += entry.get_d_reclen64() as u64;
                 entries.push(entry);
             }
@@ -865,3 +1469,153 @@ impl FileManager {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::{symlink, MetadataExt};
+
+    use super::*;
+
+    /// [`FileManager`] with the given container root, blocking `/secrets/*`.
+    fn file_manager(root: &Path) -> FileManager {
+        let mut manager = FileManager {
+            root_path: root.to_path_buf(),
+            ..Default::default()
+        };
+        let response = manager
+            .handle_message(FileRequest::FsPolicy(FsPolicyRequest {
+                block: vec![BlockedPath {
+                    pattern: "/secrets/*".into(),
+                    policy: "security".into(),
+                }],
+            }))
+            .unwrap();
+        assert!(response.is_none());
+        manager
+    }
+
+    fn open(manager: &mut FileManager, path: &str) -> RemoteResult<OpenFileResponse> {
+        match manager
+            .handle_message(FileRequest::Open(OpenFileRequest {
+                path: path.into(),
+                open_options: OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+            }))
+            .unwrap()
+        {
+            Some(FileResponse::Open(result)) => result,
+            other => panic!("unexpected response {other:?}"),
+        }
+    }
+
+    #[test]
+    fn policy_resolves_symlinks() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("secrets")).unwrap();
+        std::fs::create_dir_all(root.path().join("app")).unwrap();
+        std::fs::write(root.path().join("secrets/token"), "secret").unwrap();
+        std::fs::write(root.path().join("app/config"), "config").unwrap();
+        symlink("/secrets/token", root.path().join("app/token")).unwrap();
+        symlink("../secrets", root.path().join("app/secrets")).unwrap();
+
+        let mut manager = file_manager(root.path());
+
+        assert!(open(&mut manager, "/app/config").is_ok());
+        for path in ["/secrets/token", "/app/token", "/app/secrets/token"] {
+            let result = open(&mut manager, path);
+            assert!(
+                matches!(
+                    &result,
+                    Err(ResponseError::Forbidden { policy_name: Some(name), .. }) if name == "security"
+                ),
+                "{path}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn policy_hides_dir_entries() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("secrets")).unwrap();
+        std::fs::write(root.path().join("secrets/token"), "secret").unwrap();
+
+        let mut manager = file_manager(root.path());
+
+        let OpenFileResponse { fd } = open(&mut manager, "/secrets").unwrap();
+        let OpenDirResponse { fd } = manager.fdopen_dir(fd).unwrap();
+        let batch = manager.read_dir_batch(fd, 16).unwrap();
+        assert!(batch.dir_entries.is_empty(), "{batch:?}");
+    }
+
+    #[test]
+    fn unlinkat_stays_in_root() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        std::fs::write(temp.path().join("victim"), "victim").unwrap();
+
+        let mut manager = file_manager(&root);
+
+        let OpenFileResponse { fd } = open(&mut manager, "/app").unwrap();
+        assert!(manager.unlinkat(fd, Path::new("../../victim"), 0).is_err());
+        assert!(temp.path().join("victim").exists());
+    }
+
+    #[test]
+    fn utimensat_relative_stays_in_root() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        std::fs::write(temp.path().join("victim"), "victim").unwrap();
+
+        let mut manager = file_manager(&root);
+
+        let OpenFileResponse { fd } = open(&mut manager, "/app").unwrap();
+        for follow_symlink in [true, false] {
+            let result = manager.utimensat(UtimensatRequest {
+                dirfd: Some(fd),
+                pathname: Some("../../victim".into()),
+                access_time: FileTimeInternal::Omit,
+                modification_time: FileTimeInternal::Set {
+                    seconds: 1_000,
+                    nanoseconds: 0,
+                },
+                follow_symlink,
+            });
+            assert!(result.is_err());
+        }
+
+        let victim = std::fs::metadata(temp.path().join("victim")).unwrap();
+        assert_ne!(victim.mtime(), 1_000);
+    }
+
+    #[test]
+    fn utimensat_without_following_symlink() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("app")).unwrap();
+        std::fs::write(root.path().join("app/config"), "config").unwrap();
+        symlink("config", root.path().join("app/link")).unwrap();
+
+        let mut manager = file_manager(root.path());
+
+        manager
+            .utimensat(UtimensatRequest {
+                dirfd: None,
+                pathname: Some("/app/link".into()),
+                access_time: FileTimeInternal::Omit,
+                modification_time: FileTimeInternal::Set {
+                    seconds: 1_000,
+                    nanoseconds: 0,
+                },
+                follow_symlink: false,
+            })
+            .unwrap();
+
+        let link = std::fs::symlink_metadata(root.path().join("app/link")).unwrap();
+        let config = std::fs::metadata(root.path().join("app/config")).unwrap();
+        assert_eq!(link.mtime(), 1_000);
+        assert_ne!(config.mtime(), 1_000);
+    }
+}
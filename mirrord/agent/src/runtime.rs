@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-use bollard::{container::InspectContainerOptions, Docker, API_DEFAULT_VERSION};
+use bollard::{
+    container::InspectContainerOptions, models::ContainerInspectResponse, Docker,
+    API_DEFAULT_VERSION,
+};
 use containerd_client::{
     services::v1::{
         containers_client::ContainersClient, tasks_client::TasksClient, GetContainerRequest,
@@ -15,10 +18,14 @@ use tokio::net::UnixStream;
 use tonic::transport::{Endpoint, Uri};
 use tower::service_fn;
 
-use crate::{env::parse_raw_env, runtime::crio::CriOContainer};
+use crate::{
+    env::parse_raw_env,
+    runtime::{crio::CriOContainer, podman::PodmanContainer},
+};
 
 mod crio;
 mod error;
+mod podman;
 
 pub(crate) use error::{ContainerRuntimeError, ContainerRuntimeResult};
 
@@ -65,6 +72,7 @@ pub(crate) enum Container {
     Docker(DockerContainer),
     Containerd(ContainerdContainer),
     CriO(CriOContainer),
+    Podman(PodmanContainer),
     Ephemeral(EphemeralContainer),
 }
 
@@ -79,6 +87,9 @@ pub(crate) async fn get_container(
         )),
         "containerd" => Ok(Container::Containerd(ContainerdContainer { container_id })),
         "cri-o" => Ok(Container::CriO(CriOContainer::from_id(container_id))),
+        "podman" => Ok(Container::Podman(
+            PodmanContainer::from_id(container_id).await?,
+        )),
         other => Err(ContainerRuntimeError::unknown_runtime(other)),
     }
 }
@@ -112,6 +123,26 @@ impl DockerContainer {
     }
 }
 
+/// Extracts the [`ContainerInfo`] from the response of the Docker compatible inspect API, which
+/// is also served by Podman.
+fn container_info_from_inspect(
+    inspect_response: ContainerInspectResponse,
+) -> Result<ContainerInfo, &'static str> {
+    let pid = inspect_response
+        .state
+        .and_then(|state| state.pid)
+        .and_then(|pid| if pid > 0 { Some(pid as u64) } else { None })
+        .ok_or("pid not found in the runtime response")?;
+
+    let raw_env = inspect_response
+        .config
+        .and_then(|config| config.env)
+        .ok_or("env not found in the runtime response")?;
+    let env_vars = parse_raw_env(&raw_env);
+
+    Ok(ContainerInfo::new(pid, env_vars))
+}
+
 impl ContainerRuntime for DockerContainer {
     async fn get_info(&self) -> ContainerRuntimeResult<ContainerInfo> {
         let inspect_options = Some(InspectContainerOptions { size: false });
@@ -121,23 +152,7 @@ impl ContainerRuntime for DockerContainer {
             .await
             .map_err(ContainerRuntimeError::docker)?;
 
-        let pid = inspect_response
-            .state
-            .and_then(|state| state.pid)
-            .and_then(|pid| if pid > 0 { Some(pid as u64) } else { None })
-            .ok_or_else(|| {
-                ContainerRuntimeError::docker("pid not found in the runtime response")
-            })?;
-
-        let raw_env = inspect_response
-            .config
-            .and_then(|config| config.env)
-            .ok_or_else(|| {
-                ContainerRuntimeError::docker("env not found in the runtime response")
-            })?;
-        let env_vars = parse_raw_env(&raw_env);
-
-        Ok(ContainerInfo::new(pid, env_vars))
+        container_info_from_inspect(inspect_response).map_err(ContainerRuntimeError::docker)
     }
 }
 
@@ -1,46 +1,77 @@
+use std::path::{Path, PathBuf};
+
 use k8s_cri::v1::{runtime_service_client::RuntimeServiceClient, ContainerStatusRequest};
 use serde::Deserialize;
 use tokio::net::UnixStream;
-use tonic::transport::{Endpoint, Uri};
+use tonic::transport::{Channel, Endpoint, Uri};
 use tower::service_fn;
 use tracing::error;
 
 use super::ContainerRuntimeError;
-use crate::runtime::{error::ContainerRuntimeResult, ContainerInfo, ContainerRuntime};
+use crate::{
+    env::parse_raw_env,
+    runtime::{error::ContainerRuntimeResult, ContainerInfo, ContainerRuntime},
+};
+
+const CRIO_DEFAULT_SOCK_PATH: &str = "/host/run/crio/crio.sock";
+const CRIO_VAR_RUN_SOCK_PATH: &str = "/host/var/run/crio/crio.sock";
 
-static CRIO_DEFAULT_SOCK_PATH: &str = "/host/run/crio/crio.sock";
+/// Possible CRI-O socket paths, evaluated from left to right.
+const CRIO_SOCK_PATHS: [&str; 2] = [CRIO_DEFAULT_SOCK_PATH, CRIO_VAR_RUN_SOCK_PATH];
 
 #[derive(Debug, Clone)]
 pub(crate) struct CriOContainer {
     pub container_id: String,
 }
 
+/// The verbose info of the CRI `ContainerStatus` response, JSON encoded under the `info` key.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ContainerStatusInfo {
+    pid: Option<u64>,
+    runtime_spec: Option<RuntimeSpec>,
+}
+
+/// The parts of the OCI runtime spec of the container that we use.
+#[derive(Deserialize, Debug)]
+struct RuntimeSpec {
+    process: Option<RuntimeSpecProcess>,
+}
+
 #[derive(Deserialize, Debug)]
-struct ContainerStatus {
-    pid: u64,
+struct RuntimeSpecProcess {
+    #[serde(default)]
+    env: Vec<String>,
 }
 
 impl CriOContainer {
     pub fn from_id(container_id: String) -> Self {
         CriOContainer { container_id }
     }
-}
 
-impl ContainerRuntime for CriOContainer {
-    async fn get_info(&self) -> ContainerRuntimeResult<ContainerInfo> {
-        let channel = Endpoint::try_from("http://localhost")
+    /// Connects to the CRI-O runtime service at the given socket.
+    async fn connect(sock_path: &Path) -> ContainerRuntimeResult<Channel> {
+        let sock_path = sock_path.to_path_buf();
+
+        Endpoint::try_from("http://localhost")
             .map_err(ContainerRuntimeError::crio)?
-            .connect_with_connector(service_fn(move |_: Uri| async {
-                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
-                    UnixStream::connect(CRIO_DEFAULT_SOCK_PATH)
-                        .await
-                        .inspect_err(|err| error!("{err:?}"))?,
-                ))
+            .connect_with_connector(service_fn(move |_: Uri| {
+                let sock_path = sock_path.clone();
+                async move {
+                    Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                        UnixStream::connect(sock_path)
+                            .await
+                            .inspect_err(|err| error!("{err:?}"))?,
+                    ))
+                }
             }))
             .await
-            .map_err(ContainerRuntimeError::crio)?;
+            .map_err(ContainerRuntimeError::crio)
+    }
 
-        let mut client = RuntimeServiceClient::new(channel);
+    /// Gets the [`ContainerInfo`] from the CRI-O runtime service listening on `sock_path`.
+    async fn get_info_from(&self, sock_path: &Path) -> ContainerRuntimeResult<ContainerInfo> {
+        let mut client = RuntimeServiceClient::new(Self::connect(sock_path).await?);
 
         let status = client
             .container_status(ContainerStatusRequest {
@@ -51,23 +82,196 @@ impl ContainerRuntime for CriOContainer {
             .map_err(ContainerRuntimeError::crio)?
             .into_inner();
 
+        let info: Option<ContainerStatusInfo> = status
+            .info
+            .get("info")
+            .map(|info| serde_json::from_str(info))
+            .transpose()
+            .map_err(ContainerRuntimeError::crio)?;
+
         // Not sure if the `.get("pid")` logic works as on OpenShift
         // we observed that the `pid` exists in the `info` field which is JSON encoded.
         // for now we're adding a fallback
-        let pid: u64 = match status.info.get("pid") {
-            Some(val) => val.parse().map_err(|_| {
+        let pid: u64 = match (
+            status.info.get("pid"),
+            info.as_ref().and_then(|info| info.pid),
+        ) {
+            (Some(val), _) => val.parse().map_err(|_| {
                 ContainerRuntimeError::crio("failed to parse pid from the runtime response")
             })?,
-            None => {
-                let info_json = status.info.get("info").ok_or_else(|| {
-                    ContainerRuntimeError::crio("info not found in the runtime response status")
-                })?;
-                let info: ContainerStatus =
-                    serde_json::from_str(info_json).map_err(ContainerRuntimeError::crio)?;
-                info.pid
+            (None, Some(pid)) => pid,
+            (None, None) => {
+                return Err(ContainerRuntimeError::crio(
+                    "pid not found in the runtime response status",
+                ))
             }
         };
 
-        Ok(ContainerInfo::new(pid, Default::default()))
+        let raw_env = info
+            .and_then(|info| info.runtime_spec)
+            .and_then(|spec| spec.process)
+            .map(|process| process.env)
+            .ok_or_else(|| ContainerRuntimeError::crio("env not found in the runtime response"))?;
+
+        Ok(ContainerInfo::new(pid, parse_raw_env(&raw_env)))
+    }
+}
+
+impl ContainerRuntime for CriOContainer {
+    async fn get_info(&self) -> ContainerRuntimeResult<ContainerInfo> {
+        let sock_path = CRIO_SOCK_PATHS
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                ContainerRuntimeError::crio(format!(
+                    "CRI-O socket not found, looked in {CRIO_SOCK_PATHS:?}"
+                ))
+            })?;
+
+        self.get_info_from(&sock_path).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        convert::Infallible,
+        future::{self, Ready},
+        task::{Context, Poll},
+    };
+
+    use futures::{future::BoxFuture, stream};
+    use k8s_cri::v1::ContainerStatusResponse;
+    use tempfile::TempDir;
+    use tokio::net::UnixListener;
+    use tonic::{
+        body::BoxBody,
+        codec::ProstCodec,
+        server::{Grpc, NamedService, UnaryService},
+        transport::Server,
+        Request, Response, Status,
+    };
+    use tower::Service;
+
+    use super::*;
+
+    /// Stand-in for the CRI-O runtime service, answers the `ContainerStatus` requests for the
+    /// `container_id` with `info`.
+    #[derive(Clone)]
+    struct StandInRuntimeService {
+        container_id: &'static str,
+        info: HashMap<String, String>,
+    }
+
+    impl NamedService for StandInRuntimeService {
+        const NAME: &'static str = "runtime.v1.RuntimeService";
+    }
+
+    impl UnaryService<ContainerStatusRequest> for StandInRuntimeService {
+        type Response = ContainerStatusResponse;
+        type Future = Ready<Result<Response<Self::Response>, Status>>;
+
+        fn call(&mut self, request: Request<ContainerStatusRequest>) -> Self::Future {
+            let request = request.into_inner();
+
+            let response = if request.container_id != self.container_id {
+                Err(Status::not_found("container not found"))
+            } else if !request.verbose {
+                Ok(Response::new(ContainerStatusResponse::default()))
+            } else {
+                Ok(Response::new(ContainerStatusResponse {
+                    status: None,
+                    info: self.info.clone(),
+                }))
+            };
+
+            future::ready(response)
+        }
+    }
+
+    impl Service<http::Request<BoxBody>> for StandInRuntimeService {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            let service = self.clone();
+
+            Box::pin(async move {
+                let mut grpc = Grpc::new(ProstCodec::default());
+                Ok(grpc.unary(service, request).await)
+            })
+        }
+    }
+
+    /// Serves the [`StandInRuntimeService`] on a socket in a new temporary directory.
+    fn serve(info: HashMap<String, String>) -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let sock_path = dir.path().join("crio.sock");
+        let listener = UnixListener::bind(&sock_path).unwrap();
+
+        let incoming = stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _)| stream);
+            Some((stream, listener))
+        });
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(StandInRuntimeService {
+                    container_id: "crio-container",
+                    info,
+                })
+                .serve_with_incoming(incoming),
+        );
+
+        (dir, sock_path)
+    }
+
+    #[tokio::test]
+    async fn get_info_from_verbose_status() {
+        let info = serde_json::json!({
+            "sandboxID": "crio-sandbox",
+            "pid": 4242,
+            "runtimeSpec": {
+                "ociVersion": "1.0.2",
+                "process": {
+                    "args": ["/app"],
+                    "env": ["PATH=/usr/bin", "DATABASE_URL=postgres://db:5432", "EMPTY="]
+                }
+            }
+        });
+        let (_dir, sock_path) = serve(HashMap::from([("info".to_string(), info.to_string())]));
+
+        let info = CriOContainer::from_id("crio-container".into())
+            .get_info_from(&sock_path)
+            .await
+            .unwrap();
+
+        assert_eq!(info.pid, 4242);
+        assert_eq!(
+            info.env.get("DATABASE_URL").map(String::as_str),
+            Some("postgres://db:5432")
+        );
+        assert_eq!(info.env.get("EMPTY").map(String::as_str), Some(""));
+    }
+
+    #[tokio::test]
+    async fn get_info_missing_container() {
+        let (_dir, sock_path) = serve(HashMap::new());
+
+        let result = CriOContainer::from_id("other-container".into())
+            .get_info_from(&sock_path)
+            .await;
+
+        assert!(
+            matches!(&result, Err(ContainerRuntimeError::GetInfoError { runtime, .. }) if runtime == "cri-o"),
+            "{result:?}"
+        );
     }
 }
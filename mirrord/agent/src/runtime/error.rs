@@ -31,6 +31,13 @@ impl ContainerRuntimeError {
         }
     }
 
+    pub(crate) fn podman<E: ToString>(error: E) -> Self {
+        Self::GetInfoError {
+            runtime: "podman".into(),
+            error: error.to_string(),
+        }
+    }
+
     pub(crate) fn unknown_runtime<N: ToString>(name: N) -> Self {
         Self::UnknownRuntimeName(name.to_string())
     }
@@ -0,0 +1,167 @@
+use bollard::{container::InspectContainerOptions, Docker, API_DEFAULT_VERSION};
+
+use crate::runtime::{
+    container_info_from_inspect, error::ContainerRuntimeResult, ContainerInfo, ContainerRuntime,
+    ContainerRuntimeError,
+};
+
+const PODMAN_DEFAULT_SOCK_PATH: &str = "/host/run/podman/podman.sock";
+const PODMAN_VAR_RUN_SOCK_PATH: &str = "/host/var/run/podman/podman.sock";
+
+/// Possible Podman socket paths, evaluated from left to right.
+const PODMAN_SOCK_PATHS: [&str; 2] = [PODMAN_DEFAULT_SOCK_PATH, PODMAN_VAR_RUN_SOCK_PATH];
+
+/// Podman container, inspected through the Docker compatible API served on the Podman socket.
+#[derive(Debug, Clone)]
+pub(crate) struct PodmanContainer {
+    container_id: String,
+    client: Docker,
+}
+
+impl PodmanContainer {
+    pub(crate) async fn from_id(container_id: String) -> ContainerRuntimeResult<Self> {
+        Self::connect(container_id, &PODMAN_SOCK_PATHS).await
+    }
+
+    /// Connects to the first of the given Podman sockets that responds.
+    async fn connect(container_id: String, sock_paths: &[&str]) -> ContainerRuntimeResult<Self> {
+        for sock_path in sock_paths {
+            match Docker::connect_with_unix(sock_path, 10, API_DEFAULT_VERSION) {
+                Ok(client) if client.ping().await.is_ok() => {
+                    return Ok(PodmanContainer {
+                        container_id,
+                        client,
+                    })
+                }
+                _ => continue,
+            }
+        }
+
+        Err(ContainerRuntimeError::podman(format!(
+            "Podman socket not found, looked in {sock_paths:?}"
+        )))
+    }
+}
+
+impl ContainerRuntime for PodmanContainer {
+    async fn get_info(&self) -> ContainerRuntimeResult<ContainerInfo> {
+        let inspect_options = Some(InspectContainerOptions { size: false });
+        let inspect_response = self
+            .client
+            .inspect_container(&self.container_id, inspect_options)
+            .await
+            .map_err(ContainerRuntimeError::podman)?;
+
+        container_info_from_inspect(inspect_response).map_err(ContainerRuntimeError::podman)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{server::conn::http1, service::service_fn, Request, Response, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use tempfile::TempDir;
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    /// Stand-in for the Podman API, knows only the `podman-container`.
+    async fn handle(
+        request: Request<hyper::body::Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let path = request.uri().path();
+
+        let response = if path.ends_with("/_ping") {
+            Response::new(Full::from("OK"))
+        } else if path.ends_with("/containers/podman-container/json") {
+            let inspect = serde_json::json!({
+                "Id": "podman-container",
+                "State": { "Running": true, "Pid": 3131 },
+                "Config": { "Env": ["PATH=/usr/bin", "QUEUE_URL=amqp://queue:5672"] }
+            });
+
+            let mut response = Response::new(Full::from(inspect.to_string()));
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+            response
+        } else {
+            let mut response = Response::new(Full::from(r#"{"message":"no such container"}"#));
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            response
+        };
+
+        Ok(response)
+    }
+
+    /// Serves the stand-in Podman API on a socket in a new temporary directory.
+    fn serve() -> (TempDir, String) {
+        let dir = TempDir::new().unwrap();
+        let sock_path = dir.path().join("podman.sock");
+        let listener = UnixListener::bind(&sock_path).unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(
+                    http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service_fn(handle)),
+                );
+            }
+        });
+
+        (dir, sock_path.to_str().unwrap().to_owned())
+    }
+
+    #[tokio::test]
+    async fn get_info() {
+        let (dir, sock_path) = serve();
+        let missing = dir.path().join("missing.sock");
+
+        let container = PodmanContainer::connect(
+            "podman-container".into(),
+            &[missing.to_str().unwrap(), &sock_path],
+        )
+        .await
+        .unwrap();
+        let info = container.get_info().await.unwrap();
+
+        assert_eq!(info.pid, 3131);
+        assert_eq!(
+            info.env.get("QUEUE_URL").map(String::as_str),
+            Some("amqp://queue:5672")
+        );
+    }
+
+    #[tokio::test]
+    async fn get_info_missing_container() {
+        let (_dir, sock_path) = serve();
+
+        let container = PodmanContainer::connect("other-container".into(), &[&sock_path])
+            .await
+            .unwrap();
+        let result = container.get_info().await;
+
+        assert!(
+            matches!(&result, Err(ContainerRuntimeError::GetInfoError { runtime, .. }) if runtime == "podman"),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn no_socket() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing.sock");
+
+        assert!(
+            PodmanContainer::connect("podman-container".into(), &[missing.to_str().unwrap()])
+                .await
+                .is_err()
+        );
+    }
+}
@@ -32,6 +32,8 @@ use crate::{
 pub(crate) mod api;
 pub(crate) mod messages;
 pub(crate) mod tcp_capture;
+pub(crate) mod udp;
+pub(crate) mod udp_capture;
 
 #[derive(Debug, Eq, Copy, Clone)]
 pub(crate) struct TcpSessionIdentifier {
@@ -138,7 +140,7 @@ pub(crate) struct TcpConnectionSniffer<T> {
     sessions: TCPSessionMap,
 
     client_txs: HashMap<ClientId, Sender<SniffedConnection>>,
-    clients_closed: FuturesUnordered<ChannelClosedFuture<SniffedConnection>>,
+    clients_closed: FuturesUnordered<ChannelClosedFuture>,
 }
 
 impl<T> fmt::Debug for TcpConnectionSniffer<T> {
@@ -3,6 +3,7 @@ use std::ops::RangeInclusive;
 use futures::{stream::FuturesUnordered, StreamExt};
 use mirrord_protocol::{
     tcp::{DaemonTcp, LayerTcp, NewTcpConnection, TcpClose, TcpData},
+    udp::{DaemonUdp, LayerUdp, UdpDatagram},
     ConnectionId, LogMessage, Port,
 };
 use tokio::sync::{
@@ -14,7 +15,9 @@ use tokio_stream::{
     StreamMap, StreamNotifyClose,
 };
 
-use super::messages::{SniffedConnection, SnifferCommand, SnifferCommandInner};
+use super::messages::{
+    SniffedConnection, SniffedDatagram, SnifferCommand, SnifferCommandInner, UdpSnifferCommand,
+};
 use crate::{error::AgentError, util::ClientId, watched_task::TaskStatus};
 
 /// Interface used by clients to interact with the
@@ -184,3 +187,126 @@ impl TcpSnifferApi {
         }
     }
 }
+
+/// Interface used by clients to interact with the
+/// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer). Multiple instances of this struct
+/// operate on a single sniffer instance.
+pub(crate) struct UdpSnifferApi {
+    /// Id of the client using this struct.
+    client_id: ClientId,
+    /// Channel used to send commands to the
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+    sender: Sender<UdpSnifferCommand>,
+    /// Channel used to receive datagrams from the
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+    receiver: Receiver<SniffedDatagram>,
+    /// View on the sniffer task's status.
+    task_status: TaskStatus,
+    /// [`LayerUdp::PortSubscribe`] requests in progress.
+    subscriptions_in_progress: FuturesUnordered<oneshot::Receiver<Port>>,
+}
+
+impl UdpSnifferApi {
+    /// Capacity for channel that will be used by
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer) to send datagrams to this struct.
+    pub const DATAGRAM_CHANNEL_SIZE: usize = 512;
+
+    /// Create a new instance of this struct and connect it to a
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer) instance.
+    /// * `client_id` - id of the client using this struct
+    /// * `sniffer_sender` - channel used to send commands to the
+    ///   [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer)
+    /// * `task_status` - handle to the [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer) exit
+    ///   status
+    pub async fn new(
+        client_id: ClientId,
+        sniffer_sender: Sender<UdpSnifferCommand>,
+        mut task_status: TaskStatus,
+    ) -> Result<Self, AgentError> {
+        let (sender, receiver) = mpsc::channel(Self::DATAGRAM_CHANNEL_SIZE);
+
+        let command = SnifferCommand {
+            client_id,
+            command: SnifferCommandInner::NewClient(sender),
+        };
+        if sniffer_sender.send(command).await.is_err() {
+            return Err(task_status.unwrap_err().await);
+        }
+
+        Ok(Self {
+            client_id,
+            sender: sniffer_sender,
+            receiver,
+            task_status,
+            subscriptions_in_progress: Default::default(),
+        })
+    }
+
+    /// Send the given command to the connected
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+    async fn send_command(
+        &mut self,
+        command: SnifferCommandInner<SniffedDatagram>,
+    ) -> Result<(), AgentError> {
+        let command = SnifferCommand {
+            client_id: self.client_id,
+            command,
+        };
+
+        if self.sender.send(command).await.is_ok() {
+            Ok(())
+        } else {
+            Err(self.task_status.unwrap_err().await)
+        }
+    }
+
+    /// Return the next message from the connected
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+    pub async fn recv(&mut self) -> Result<DaemonUdp, AgentError> {
+        tokio::select! {
+            datagram = self.receiver.recv() => match datagram {
+                Some(datagram) => Ok(DaemonUdp::Datagram(UdpDatagram {
+                    remote_address: (*datagram.source.ip()).into(),
+                    source_port: datagram.source.port(),
+                    local_address: (*datagram.destination.ip()).into(),
+                    destination_port: datagram.destination.port(),
+                    bytes: datagram.bytes.into(),
+                })),
+
+                None => Err(self.task_status.unwrap_err().await),
+            },
+
+            Some(result) = self.subscriptions_in_progress.next() => match result {
+                Ok(port) => Ok(DaemonUdp::SubscribeResult(Ok(port))),
+                Err(..) => Err(self.task_status.unwrap_err().await),
+            }
+        }
+    }
+
+    /// Tansform the given message into a [`UdpSnifferCommand`] and pass it to the connected
+    /// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+    pub async fn handle_client_message(&mut self, message: LayerUdp) -> Result<(), AgentError> {
+        match message {
+            LayerUdp::PortSubscribe(port) => {
+                let (tx, rx) = oneshot::channel();
+                self.send_command(SnifferCommandInner::Subscribe(port, tx))
+                    .await?;
+                self.subscriptions_in_progress.push(rx);
+
+                Ok(())
+            }
+
+            LayerUdp::PortUnsubscribe(port) => {
+                self.send_command(SnifferCommandInner::UnsubscribePort(port))
+                    .await
+            }
+
+            message @ (LayerUdp::StealPortSubscribe(..)
+            | LayerUdp::StealPortUnsubscribe(..)
+            | LayerUdp::StealReply(..)) => {
+                tracing::warn!(?message, "Sniffer received a UDP steal message, ignoring");
+                Ok(())
+            }
+        }
+    }
+}
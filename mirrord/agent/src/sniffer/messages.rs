@@ -1,16 +1,22 @@
+use std::net::SocketAddrV4;
+
+use bytes::Bytes;
 use mirrord_protocol::Port;
 use tokio::sync::{broadcast, mpsc::Sender, oneshot};
 
 use super::TcpSessionIdentifier;
 use crate::util::ClientId;
 
-/// Commmand for [`TcpConnectionSniffer`](super::TcpConnectionSniffer).
+/// Commmand for [`TcpConnectionSniffer`](super::TcpConnectionSniffer) or
+/// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+///
+/// `T` is the type of items sent to the client, [`SniffedConnection`] or [`SniffedDatagram`].
 #[derive(Debug)]
-pub(crate) enum SnifferCommandInner {
+pub(crate) enum SnifferCommandInner<T = SniffedConnection> {
     /// New client wants to use the sniffer.
     NewClient(
-        /// For notyfing the client about new incoming connections.
-        Sender<SniffedConnection>,
+        /// For notyfing the client about new incoming connections or datagrams.
+        Sender<T>,
     ),
     /// Client wants to start receiving connections incoming to a specific port.
     Subscribe(
@@ -26,16 +32,21 @@ pub(crate) enum SnifferCommandInner {
     ),
 }
 
-/// Client's command for [`TcpConnectionSniffer`](super::TcpConnectionSniffer).
+/// Client's command for [`TcpConnectionSniffer`](super::TcpConnectionSniffer) or
+/// [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
 #[derive(Debug)]
-pub(crate) struct SnifferCommand {
+pub(crate) struct SnifferCommand<T = SniffedConnection> {
     /// Id of the client.
     pub client_id: ClientId,
     /// Actual command.
-    pub command: SnifferCommandInner,
+    pub command: SnifferCommandInner<T>,
 }
 
+/// Client's command for [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+pub(crate) type UdpSnifferCommand = SnifferCommand<SniffedDatagram>;
+
 /// New TCP connection picked up by [`TcpConnectionSniffer`](super::TcpConnectionSniffer).
+#[derive(Debug)]
 pub(crate) struct SniffedConnection {
     /// Parameters of this connection's TCP session.
     /// Can be used to create [`NewTcpConnection`](mirrord_protocol::tcp::NewTcpConnection).
@@ -43,3 +54,14 @@ pub(crate) struct SniffedConnection {
     /// For receiving data from this connection.
     pub data: broadcast::Receiver<Vec<u8>>,
 }
+
+/// UDP datagram picked up by [`UdpDatagramSniffer`](super::udp::UdpDatagramSniffer).
+#[derive(Debug, Clone)]
+pub(crate) struct SniffedDatagram {
+    /// Address of the peer that sent this datagram.
+    pub source: SocketAddrV4,
+    /// Address of the impersonated pod that received this datagram.
+    pub destination: SocketAddrV4,
+    /// Payload of this datagram.
+    pub bytes: Bytes,
+}
@@ -37,8 +37,25 @@ impl RawSocketTcpCapture {
     /// Returned instance initially uses a BPF filter that drops every packet.
     #[tracing::instrument(level = Level::DEBUG, err)]
     pub async fn new(network_interface: Option<String>, is_mesh: bool) -> Result<Self, AgentError> {
-        // Priority is whatever the user set as an option to mirrord, then we check if we're in a
-        // mesh to use `lo` interface, otherwise we try to get the appropriate interface.
+        let interface = Self::interface_name(network_interface, is_mesh).await?;
+
+        let capture = RawCapture::from_interface_name(&interface)?;
+        capture.set_filter(rawsocket::filter::build_drop_always())?;
+        capture
+            .ignore_outgoing()
+            .map_err(AgentError::PacketIgnoreOutgoing)?;
+        Ok(Self { inner: capture })
+    }
+
+    /// Determines the network interface that should be used for the raw OS socket.
+    ///
+    /// Priority is whatever the user set as an option to mirrord, then we check if we're in a
+    /// mesh to use `lo` interface, otherwise we try to get the appropriate interface.
+    #[tracing::instrument(level = Level::DEBUG, ret, err)]
+    pub(super) async fn interface_name(
+        network_interface: Option<String>,
+        is_mesh: bool,
+    ) -> io::Result<String> {
         let interface = match network_interface.or_else(|| is_mesh.then(|| "lo".to_string())) {
             Some(interface) => interface,
             None => Self::resolve_interface()
@@ -51,12 +68,7 @@ impl RawSocketTcpCapture {
             "Resolved raw capture interface"
         );
 
-        let capture = RawCapture::from_interface_name(&interface)?;
-        capture.set_filter(rawsocket::filter::build_drop_always())?;
-        capture
-            .ignore_outgoing()
-            .map_err(AgentError::PacketIgnoreOutgoing)?;
-        Ok(Self { inner: capture })
+        Ok(interface)
     }
 
     /// Connects to a remote address (`8.8.8.8:53`) so we can find which network interface to use.
@@ -0,0 +1,385 @@
+//! Incoming UDP traffic mirroring, see [`UdpDatagramSniffer`].
+
+use std::{collections::HashMap, fmt};
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use mirrord_protocol::Port;
+use tokio::{
+    select,
+    sync::mpsc::{error::TrySendError, Receiver, Sender},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::Level;
+
+use super::{
+    messages::{SniffedDatagram, SnifferCommand, SnifferCommandInner, UdpSnifferCommand},
+    udp_capture::{RawSocketUdpCapture, UdpCapture},
+};
+use crate::{
+    error::AgentError,
+    util::{ChannelClosedFuture, ClientId, Subscriptions},
+};
+
+/// Main struct implementing incoming UDP traffic mirroring feature.
+/// Utilizes [`UdpCapture`] for sniffing on incoming UDP datagrams and sends copies of them to all
+/// subscribed clients.
+///
+/// Can be easily used via [`UdpSnifferApi`](super::api::UdpSnifferApi).
+///
+/// # Notes on behavior under high load
+///
+/// Same as [`TcpConnectionSniffer`](super::TcpConnectionSniffer), this struct cannot apply any
+/// back pressure on the remote peers. Datagrams are distributed with the non-blocking
+/// [`Sender::try_send`] method, so if the client is not fast enough to pick them up, it will miss
+/// some of them.
+pub(crate) struct UdpDatagramSniffer<T> {
+    command_rx: Receiver<UdpSnifferCommand>,
+    udp_capture: T,
+
+    port_subscriptions: Subscriptions<Port, ClientId>,
+
+    client_txs: HashMap<ClientId, Sender<SniffedDatagram>>,
+    clients_closed: FuturesUnordered<ChannelClosedFuture>,
+}
+
+impl<T> fmt::Debug for UdpDatagramSniffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpDatagramSniffer")
+            .field("clients", &self.client_txs.keys())
+            .field("port_subscriptions", &self.port_subscriptions)
+            .finish()
+    }
+}
+
+impl UdpDatagramSniffer<RawSocketUdpCapture> {
+    /// Creates and prepares a new [`UdpDatagramSniffer`] that uses BPF filters to capture network
+    /// packets.
+    ///
+    /// Network interface is picked the same way as in
+    /// [`TcpConnectionSniffer::new`](super::TcpConnectionSniffer::new).
+    #[tracing::instrument(level = Level::TRACE, skip(command_rx), err)]
+    pub async fn new(
+        command_rx: Receiver<UdpSnifferCommand>,
+        network_interface: Option<String>,
+        is_mesh: bool,
+    ) -> Result<Self, AgentError> {
+        let udp_capture = RawSocketUdpCapture::new(network_interface, is_mesh).await?;
+
+        Ok(Self {
+            command_rx,
+            udp_capture,
+
+            port_subscriptions: Default::default(),
+
+            client_txs: HashMap::new(),
+            clients_closed: Default::default(),
+        })
+    }
+}
+
+impl<R> UdpDatagramSniffer<R>
+where
+    R: UdpCapture,
+{
+    pub const TASK_NAME: &'static str = "UdpSniffer";
+
+    /// Runs the sniffer loop, capturing datagrams.
+    #[tracing::instrument(level = Level::DEBUG, skip(cancel_token), err)]
+    pub async fn start(mut self, cancel_token: CancellationToken) -> Result<(), AgentError> {
+        loop {
+            select! {
+                command = self.command_rx.recv() => {
+                    let Some(command) = command else {
+                        tracing::debug!("command channel closed, exiting");
+                        break;
+                    };
+
+                    self.handle_command(command)?;
+                },
+
+                Some(client_id) = self.clients_closed.next() => {
+                    self.handle_client_closed(client_id)?;
+                }
+
+                result = self.udp_capture.next() => {
+                    self.handle_datagram(result?);
+                }
+
+                _ = cancel_token.cancelled() => {
+                    tracing::debug!("token cancelled, exiting");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the client with `client_id`, and also unsubscribes its ports.
+    /// Adjusts BPF filter if needed.
+    #[tracing::instrument(level = Level::TRACE, err)]
+    fn handle_client_closed(&mut self, client_id: ClientId) -> Result<(), AgentError> {
+        self.client_txs.remove(&client_id);
+
+        if self.port_subscriptions.remove_client(client_id) {
+            self.update_packet_filter()?;
+        }
+
+        Ok(())
+    }
+
+    /// Updates ports captured by [`Self::udp_capture`] to match state of
+    /// [`Self::port_subscriptions`].
+    #[tracing::instrument(level = Level::TRACE, err)]
+    fn update_packet_filter(&mut self) -> Result<(), AgentError> {
+        let ports = self.port_subscriptions.get_subscribed_topics();
+        self.udp_capture.set_ports(&ports)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = Level::TRACE, err)]
+    fn handle_command(&mut self, command: UdpSnifferCommand) -> Result<(), AgentError> {
+        match command {
+            SnifferCommand {
+                client_id,
+                command: SnifferCommandInner::NewClient(sender),
+            } => {
+                self.client_txs.insert(client_id, sender.clone());
+                self.clients_closed
+                    .push(ChannelClosedFuture::new(sender, client_id));
+            }
+
+            SnifferCommand {
+                client_id,
+                command: SnifferCommandInner::Subscribe(port, tx),
+            } => {
+                if self.port_subscriptions.subscribe(client_id, port) {
+                    self.update_packet_filter()?;
+                }
+
+                let _ = tx.send(port);
+            }
+
+            SnifferCommand {
+                client_id,
+                command: SnifferCommandInner::UnsubscribePort(port),
+            } => {
+                if self.port_subscriptions.unsubscribe(client_id, port) {
+                    self.update_packet_filter()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the datagram sniffed by [`Self::udp_capture`] to all clients subscribed to its
+    /// destination port.
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    fn handle_datagram(&mut self, datagram: SniffedDatagram) {
+        let Some(client_ids) = self
+            .port_subscriptions
+            .get_topic_subscribers(datagram.destination.port())
+        else {
+            return;
+        };
+
+        for client_id in client_ids {
+            let Some(client_tx) = self.client_txs.get(client_id) else {
+                tracing::error!(
+                    client_id,
+                    destination = %datagram.destination,
+                    source = %datagram.source,
+                    "Failed to find client while handling sniffed UDP datagram, this is a bug",
+                );
+
+                continue;
+            };
+
+            match client_tx.try_send(datagram.clone()) {
+                Ok(()) => {}
+
+                Err(TrySendError::Closed(..)) => {
+                    // Client closed.
+                    // State will be cleaned up when `self.clients_closed` picks it up.
+                }
+
+                Err(TrySendError::Full(..)) => {
+                    tracing::warn!(
+                        client_id,
+                        destination = %datagram.destination,
+                        source = %datagram.source,
+                        bytes = datagram.bytes.len(),
+                        "Client queue of sniffed UDP datagrams is full, dropping",
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use mirrord_protocol::udp::{DaemonUdp, LayerUdp, UdpDatagram};
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        sniffer::{api::UdpSnifferApi, udp_capture::test::UdpDatagramsChannel},
+        watched_task::{TaskStatus, WatchedTask},
+    };
+
+    struct TestSnifferSetup {
+        command_tx: Sender<UdpSnifferCommand>,
+        task_status: TaskStatus,
+        datagram_tx: Sender<SniffedDatagram>,
+        times_filter_changed: Arc<AtomicUsize>,
+        next_client_id: ClientId,
+    }
+
+    impl TestSnifferSetup {
+        async fn get_api(&mut self) -> UdpSnifferApi {
+            let client_id = self.next_client_id;
+            self.next_client_id += 1;
+            UdpSnifferApi::new(client_id, self.command_tx.clone(), self.task_status.clone())
+                .await
+                .unwrap()
+        }
+
+        fn times_filter_changed(&self) -> usize {
+            self.times_filter_changed.load(Ordering::Relaxed)
+        }
+
+        fn new() -> Self {
+            let (datagram_tx, datagram_rx) = mpsc::channel(128);
+            let (command_tx, command_rx) = mpsc::channel(16);
+            let times_filter_changed = Arc::new(AtomicUsize::default());
+
+            let sniffer = UdpDatagramSniffer {
+                command_rx,
+                udp_capture: UdpDatagramsChannel {
+                    times_filter_changed: times_filter_changed.clone(),
+                    receiver: datagram_rx,
+                },
+                port_subscriptions: Default::default(),
+                client_txs: Default::default(),
+                clients_closed: Default::default(),
+            };
+            let watched_task = WatchedTask::new(
+                UdpDatagramSniffer::<UdpDatagramsChannel>::TASK_NAME,
+                sniffer.start(CancellationToken::new()),
+            );
+            let task_status = watched_task.status();
+            tokio::spawn(watched_task.start());
+
+            Self {
+                command_tx,
+                task_status,
+                datagram_tx,
+                times_filter_changed,
+                next_client_id: 0,
+            }
+        }
+
+        async fn send_datagram(&self, destination_port: Port, bytes: &'static [u8]) {
+            self.datagram_tx
+                .send(SniffedDatagram {
+                    source: "1.1.1.1:3133".parse().unwrap(),
+                    destination: format!("127.0.0.1:{destination_port}").parse().unwrap(),
+                    bytes: bytes.into(),
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    fn expected_datagram(destination_port: Port, bytes: &[u8]) -> DaemonUdp {
+        DaemonUdp::Datagram(UdpDatagram {
+            remote_address: "1.1.1.1".parse().unwrap(),
+            source_port: 3133,
+            local_address: "127.0.0.1".parse().unwrap(),
+            destination_port,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    /// Simulates datagrams sent to two ports, only one matching client's subscription.
+    #[tokio::test]
+    async fn one_client() {
+        let mut setup = TestSnifferSetup::new();
+        let mut api = setup.get_api().await;
+
+        api.handle_client_message(LayerUdp::PortSubscribe(8125))
+            .await
+            .unwrap();
+        assert_eq!(
+            api.recv().await.unwrap(),
+            DaemonUdp::SubscribeResult(Ok(8125))
+        );
+        assert_eq!(setup.times_filter_changed(), 1);
+
+        setup.send_datagram(514, b"ignored").await;
+        setup.send_datagram(8125, b"metric:1|c").await;
+
+        assert_eq!(
+            api.recv().await.unwrap(),
+            expected_datagram(8125, b"metric:1|c")
+        );
+
+        api.handle_client_message(LayerUdp::PortUnsubscribe(8125))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(setup.times_filter_changed(), 2);
+        setup.send_datagram(8125, b"metric:2|c").await;
+
+        assert!(tokio::time::timeout(Duration::from_millis(100), api.recv())
+            .await
+            .is_err());
+    }
+
+    /// Verifies that the same datagram is delivered to all subscribed clients, and that closing
+    /// one of them removes its subscriptions.
+    #[tokio::test]
+    async fn two_clients() {
+        let mut setup = TestSnifferSetup::new();
+        let mut api_1 = setup.get_api().await;
+        let mut api_2 = setup.get_api().await;
+
+        for api in [&mut api_1, &mut api_2] {
+            api.handle_client_message(LayerUdp::PortSubscribe(514))
+                .await
+                .unwrap();
+            assert_eq!(
+                api.recv().await.unwrap(),
+                DaemonUdp::SubscribeResult(Ok(514))
+            );
+        }
+        assert_eq!(setup.times_filter_changed(), 1);
+
+        setup.send_datagram(514, b"<34>1 message").await;
+        for api in [&mut api_1, &mut api_2] {
+            assert_eq!(
+                api.recv().await.unwrap(),
+                expected_datagram(514, b"<34>1 message")
+            );
+        }
+
+        std::mem::drop(api_1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(setup.times_filter_changed(), 1); // api_2 still subscribes `514`
+
+        std::mem::drop(api_2);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(setup.times_filter_changed(), 2);
+    }
+}
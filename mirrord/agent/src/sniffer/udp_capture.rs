@@ -0,0 +1,286 @@
+use std::{
+    io::{self, Read},
+    mem,
+    net::SocketAddrV4,
+    os::fd::AsRawFd,
+};
+
+use bytes::Bytes;
+use mirrord_protocol::Port;
+use pnet::packet::{
+    ethernet::{EtherTypes, EthernetPacket},
+    ip::IpNextHeaderProtocols,
+    ipv4::Ipv4Packet,
+    udp::UdpPacket,
+    Packet,
+};
+use socket2::{Domain, Socket, Type};
+use tokio::io::unix::AsyncFd;
+use tracing::Level;
+
+use super::{messages::SniffedDatagram, tcp_capture::RawSocketTcpCapture};
+use crate::error::AgentError;
+
+/// Socket option for ignoring packets sent from the local host, not exported by [`libc`].
+const PACKET_IGNORE_OUTGOING: libc::c_int = 23;
+
+/// Size of the buffer used to receive Ethernet frames from the raw OS socket.
+///
+/// Big enough to hold the largest possible datagram sent on the loopback interface.
+const FRAME_BUFFER_SIZE: usize = u16::MAX as usize + 64;
+
+/// Trait for structs that are able to sniff incoming UDP datagrams.
+pub trait UdpCapture {
+    /// Limits captured datagrams to the ones sent to the given ports.
+    ///
+    /// An empty list of ports means that no datagrams should be captured.
+    fn set_ports(&mut self, ports: &[Port]) -> io::Result<()>;
+
+    /// Returns the next sniffed UDP datagram.
+    async fn next(&mut self) -> io::Result<SniffedDatagram>;
+}
+
+/// Implementor of [`UdpCapture`] that uses a raw `AF_PACKET` OS socket and a BPF filter.
+pub struct RawSocketUdpCapture {
+    /// Raw OS socket.
+    inner: AsyncFd<Socket>,
+    /// Buffer for the Ethernet frames received from [`Self::inner`].
+    buffer: Vec<u8>,
+}
+
+impl RawSocketUdpCapture {
+    /// Creates a new instance. `network_interface` and `mesh` will be used to determine correct
+    /// network interface for the raw OS socket, the same way as in [`RawSocketTcpCapture`].
+    ///
+    /// Returned instance initially uses a BPF filter that drops every packet.
+    #[tracing::instrument(level = Level::DEBUG, err)]
+    pub async fn new(network_interface: Option<String>, is_mesh: bool) -> Result<Self, AgentError> {
+        let interface = RawSocketTcpCapture::interface_name(network_interface, is_mesh).await?;
+        let interface_index =
+            nix::net::if_::if_nametoindex(interface.as_str()).map_err(io::Error::from)?;
+
+        // Protocol `0` means that the socket does not receive anything until we bind it, so no
+        // packets slip through before the filter is attached.
+        let socket = Socket::new(Domain::PACKET, Type::RAW, Some(0.into()))?;
+        socket.attach_filter(&build_udp_port_filter(&[]))?;
+        socket.set_nonblocking(true)?;
+
+        let one: libc::c_int = 1;
+        // SAFETY: the option value is a valid `c_int` that lives through the call.
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_PACKET,
+                PACKET_IGNORE_OUTGOING,
+                (&one as *const libc::c_int).cast(),
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(AgentError::PacketIgnoreOutgoing(io::Error::last_os_error()));
+        }
+
+        // SAFETY: all-zero is a valid `sockaddr_ll`.
+        let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as libc::c_ushort;
+        address.sll_protocol = (libc::ETH_P_ALL as u16).to_be();
+        address.sll_ifindex = interface_index as libc::c_int;
+        // SAFETY: the address is a valid `sockaddr_ll` that lives through the call.
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                (&address as *const libc::sockaddr_ll).cast(),
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            inner: AsyncFd::new(socket)?,
+            buffer: vec![0; FRAME_BUFFER_SIZE],
+        })
+    }
+
+    /// Extracts UDP datagram from the raw Ethernet packet given as bytes.
+    /// If the given Ethernet packet is not an IPv4 UDP datagram, returns [`None`].
+    #[tracing::instrument(skip(eth_packet), level = Level::TRACE, fields(bytes = %eth_packet.len()))]
+    fn get_udp_datagram(eth_packet: &[u8]) -> Option<SniffedDatagram> {
+        let eth_packet = EthernetPacket::new(eth_packet)?;
+        let ip_packet = match eth_packet.get_ethertype() {
+            EtherTypes::Ipv4 => Ipv4Packet::new(eth_packet.payload())?,
+            _ => return None,
+        };
+
+        let udp_packet = match ip_packet.get_next_level_protocol() {
+            IpNextHeaderProtocols::Udp => UdpPacket::new(ip_packet.payload())?,
+            _ => return None,
+        };
+
+        let datagram = SniffedDatagram {
+            source: SocketAddrV4::new(ip_packet.get_source(), udp_packet.get_source()),
+            destination: SocketAddrV4::new(
+                ip_packet.get_destination(),
+                udp_packet.get_destination(),
+            ),
+            bytes: Bytes::copy_from_slice(udp_packet.payload()),
+        };
+
+        tracing::trace!(?datagram, "Got UDP datagram");
+
+        Some(datagram)
+    }
+}
+
+impl UdpCapture for RawSocketUdpCapture {
+    fn set_ports(&mut self, ports: &[Port]) -> io::Result<()> {
+        self.inner
+            .get_ref()
+            .attach_filter(&build_udp_port_filter(ports))
+    }
+
+    async fn next(&mut self) -> io::Result<SniffedDatagram> {
+        loop {
+            let mut guard = self.inner.readable().await?;
+
+            let result = guard.try_io(|inner| {
+                let mut socket: &Socket = inner.get_ref();
+                socket.read(&mut self.buffer)
+            });
+            let Ok(result) = result else {
+                continue;
+            };
+
+            let datagram = self.buffer.get(..result?).and_then(Self::get_udp_datagram);
+            if let Some(datagram) = datagram {
+                break Ok(datagram);
+            }
+        }
+    }
+}
+
+/// Creates a single BPF statement.
+const fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+/// Creates a single BPF jump.
+const fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Builds a BPF program that accepts only unfragmented IPv4 UDP datagrams sent to one of the given
+/// ports. With no ports given, the program drops every packet.
+///
+/// Jump offsets in classic BPF are limited to [`u8`], so with a lot of ports the program accepts
+/// every UDP datagram and leaves the port matching to the sniffer.
+fn build_udp_port_filter(ports: &[Port]) -> Vec<libc::sock_filter> {
+    const MAX_FILTERED_PORTS: usize = 200;
+    const HEADER_CHECKS: usize = 8;
+
+    const ACCEPT: libc::sock_filter = bpf_stmt(libc::BPF_RET | libc::BPF_K, u32::MAX);
+    const DROP: libc::sock_filter = bpf_stmt(libc::BPF_RET | libc::BPF_K, 0);
+
+    if ports.is_empty() {
+        return vec![DROP];
+    }
+
+    let port_checks = if ports.len() > MAX_FILTERED_PORTS {
+        // Skip `DROP` and go straight to `ACCEPT`.
+        vec![bpf_stmt(libc::BPF_JMP | libc::BPF_JA, 1)]
+    } else {
+        ports
+            .iter()
+            .enumerate()
+            .map(|(index, port)| {
+                bpf_jump(
+                    libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                    u32::from(*port),
+                    (ports.len() - index) as u8,
+                    0,
+                )
+            })
+            .collect()
+    };
+
+    // `DROP` comes right after the port checks, offsets are counted from the next instruction.
+    let to_drop = |index: usize| (HEADER_CHECKS + port_checks.len() - index - 1) as u8;
+
+    let mut program = vec![
+        // Ethertype must be IPv4.
+        bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, 12),
+        bpf_jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            libc::ETH_P_IP as u32,
+            0,
+            to_drop(1),
+        ),
+        // IP protocol must be UDP.
+        bpf_stmt(libc::BPF_LD | libc::BPF_B | libc::BPF_ABS, 23),
+        bpf_jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            libc::IPPROTO_UDP as u32,
+            0,
+            to_drop(3),
+        ),
+        // Fragments other than the first one do not have the UDP header.
+        bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, 20),
+        bpf_jump(
+            libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K,
+            0x1fff,
+            to_drop(5),
+            0,
+        ),
+        // Load the IP header length, then the UDP destination port.
+        bpf_stmt(libc::BPF_LDX | libc::BPF_B | libc::BPF_MSH, 14),
+        bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_IND, 16),
+    ];
+
+    program.extend(port_checks);
+    program.extend([DROP, ACCEPT]);
+
+    program
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::sync::mpsc::Receiver;
+
+    use super::*;
+
+    /// Implementor of [`UdpCapture`] that returns datagrams received from an
+    /// [`mpsc`](tokio::sync::mpsc) channel.
+    pub struct UdpDatagramsChannel {
+        pub times_filter_changed: Arc<AtomicUsize>,
+        pub receiver: Receiver<SniffedDatagram>,
+    }
+
+    impl UdpCapture for UdpDatagramsChannel {
+        /// Ports are ignored, we don't want to execute BPF programs in tests.
+        fn set_ports(&mut self, _ports: &[Port]) -> io::Result<()> {
+            self.times_filter_changed.fetch_add(1, Ordering::Relaxed);
+
+            Ok(())
+        }
+
+        async fn next(&mut self) -> io::Result<SniffedDatagram> {
+            Ok(self.receiver.recv().await.expect("channel closed"))
+        }
+    }
+}
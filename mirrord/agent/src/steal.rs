@@ -1,5 +1,6 @@
 use mirrord_protocol::{
-    tcp::{DaemonTcp, HttpResponseFallback, StealType, TcpData},
+    tcp::{DaemonTcp, HttpResponseFallback, RequiredHeaderFilter, StealType, TcpData},
+    udp::{DaemonUdp, UdpReply},
     ConnectionId, Port,
 };
 use tokio::sync::mpsc::Sender;
@@ -13,9 +14,11 @@ mod http;
 pub mod ip_tables;
 mod orig_dst;
 mod subscriptions;
+mod tls;
 
 pub(crate) use api::TcpStealerApi;
 pub(crate) use connection::TcpConnectionStealer;
+pub(crate) use tls::StealTlsError;
 
 /// Commands from the agent that are passed down to the stealer worker, through [`TcpStealerApi`].
 ///
@@ -23,9 +26,9 @@ pub(crate) use connection::TcpConnectionStealer;
 /// work.
 #[derive(Debug)]
 enum Command {
-    /// Contains the channel that's used by the stealer worker to respond back to the agent
+    /// Contains the channels that are used by the stealer worker to respond back to the agent
     /// (stealer -> agent -> layer).
-    NewClient(Sender<DaemonTcp>, semver::Version),
+    NewClient(Sender<DaemonTcp>, Sender<DaemonUdp>, semver::Version),
 
     /// A layer wants to subscribe to this [`Port`].
     ///
@@ -54,6 +57,21 @@ enum Command {
     HttpResponse(HttpResponseFallback),
 
     SwitchProtocolVersion(semver::Version),
+
+    /// Sets the HTTP header filters required by the mirrord policies in the layer's steal
+    /// subscriptions.
+    RequireHeaderFilters(Vec<RequiredHeaderFilter>),
+
+    /// A layer wants to steal datagrams sent to this UDP [`Port`].
+    UdpPortSubscribe(Port),
+
+    /// A layer no longer wants to steal datagrams sent to this UDP [`Port`].
+    UdpPortUnsubscribe(Port),
+
+    /// Reply from the local process to a stolen UDP datagram.
+    ///
+    /// Agent sends it to the peer from the stolen port.
+    UdpReply(UdpReply),
 }
 
 /// Association between a client (identified by the `client_id`) and a [`Command`].
@@ -7,7 +7,8 @@ use mirrord_protocol::{
         ChunkedResponse, DaemonTcp, HttpResponse, HttpResponseFallback, InternalHttpResponse,
         LayerTcpSteal, ReceiverStreamBody, TcpData,
     },
-    RequestId,
+    udp::{DaemonUdp, LayerUdp},
+    DaemonMessage, RequestId,
 };
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio_stream::wrappers::ReceiverStream;
@@ -37,6 +38,9 @@ pub(crate) struct TcpStealerApi {
     /// This is where we get the messages that should be passed back to agent or layer.
     daemon_rx: Receiver<DaemonTcp>,
 
+    /// Channel that receives [`DaemonUdp`] messages from the stealer worker thread.
+    udp_daemon_rx: Receiver<DaemonUdp>,
+
     /// View on the stealer task's status.
     task_status: TaskStatus,
 
@@ -55,11 +59,12 @@ impl TcpStealerApi {
         protocol_version: semver::Version,
     ) -> Result<Self, AgentError> {
         let (daemon_tx, daemon_rx) = mpsc::channel(channel_size);
+        let (udp_daemon_tx, udp_daemon_rx) = mpsc::channel(channel_size);
 
         command_tx
             .send(StealerCommand {
                 client_id,
-                command: Command::NewClient(daemon_tx, protocol_version),
+                command: Command::NewClient(daemon_tx, udp_daemon_tx, protocol_version),
             })
             .await?;
 
@@ -67,6 +72,7 @@ impl TcpStealerApi {
             client_id,
             command_tx,
             daemon_rx,
+            udp_daemon_rx,
             task_status,
             response_body_txs: HashMap::new(),
         })
@@ -86,21 +92,22 @@ impl TcpStealerApi {
         }
     }
 
-    /// Helper function that passes the [`DaemonTcp`] messages we generated in the
-    /// [`TcpConnectionStealer`] task, back to the agent.
+    /// Helper function that passes the [`DaemonTcp`] and [`DaemonUdp`] messages we generated in
+    /// the [`TcpConnectionStealer`] task, back to the agent.
     ///
     /// Called in the `ClientConnectionHandler`.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub(crate) async fn recv(&mut self) -> Result<DaemonTcp> {
-        match self.daemon_rx.recv().await {
-            Some(msg) => {
+    pub(crate) async fn recv(&mut self) -> Result<DaemonMessage> {
+        tokio::select! {
+            Some(msg) = self.daemon_rx.recv() => {
                 if let DaemonTcp::Close(close) = &msg {
                     self.response_body_txs
                         .retain(|(key_id, _), _| *key_id != close.connection_id);
                 }
-                Ok(msg)
+                Ok(DaemonMessage::TcpSteal(msg))
             }
-            None => Err(self.task_status.unwrap_err().await),
+            Some(msg) = self.udp_daemon_rx.recv() => Ok(DaemonMessage::Udp(msg)),
+            else => Err(self.task_status.unwrap_err().await),
         }
     }
 
@@ -159,6 +166,27 @@ impl TcpStealerApi {
             .await
     }
 
+    /// Handles the UDP steal messages, that are passed from the agent, converting them to internal
+    /// stealer commands.
+    ///
+    /// [`LayerUdp::PortSubscribe`] and [`LayerUdp::PortUnsubscribe`] belong to the UDP sniffer and
+    /// are ignored here.
+    pub(crate) async fn handle_udp_message(&mut self, message: LayerUdp) -> Result<()> {
+        match message {
+            LayerUdp::StealPortSubscribe(port) => {
+                self.send_command(Command::UdpPortSubscribe(port)).await
+            }
+            LayerUdp::StealPortUnsubscribe(port) => {
+                self.send_command(Command::UdpPortUnsubscribe(port)).await
+            }
+            LayerUdp::StealReply(reply) => self.send_command(Command::UdpReply(reply)).await,
+            message @ (LayerUdp::PortSubscribe(..) | LayerUdp::PortUnsubscribe(..)) => {
+                tracing::warn!(?message, "Stealer received a UDP mirror message, ignoring");
+                Ok(())
+            }
+        }
+    }
+
     pub(crate) async fn handle_client_message(&mut self, message: LayerTcpSteal) -> Result<()> {
         match message {
             LayerTcpSteal::PortSubscribe(port_steal) => self.port_subscribe(port_steal).await,
@@ -168,6 +196,10 @@ impl TcpStealerApi {
                 self.connection_unsubscribe(connection_id).await
             }
             LayerTcpSteal::PortUnsubscribe(port) => self.port_unsubscribe(port).await,
+            LayerTcpSteal::RequiredHeaderFilters(required) => {
+                self.send_command(Command::RequireHeaderFilters(required))
+                    .await
+            }
             LayerTcpSteal::Data(tcp_data) => self.client_data(tcp_data).await,
             LayerTcpSteal::HttpResponse(response) => {
                 self.http_response(HttpResponseFallback::Fallback(response))
@@ -1,24 +1,22 @@
 use std::{
     collections::{HashMap, HashSet},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 
 use fancy_regex::Regex;
 use futures::{stream::FuturesUnordered, StreamExt};
 use http::Request;
 use http_body_util::BodyExt;
-use hyper::{
-    body::Incoming,
-    http::{header::UPGRADE, request::Parts},
-};
+use hyper::http::{header::UPGRADE, request::Parts};
 use mirrord_protocol::{
     body_chunks::{BodyExt as _, Frames},
     tcp::{
         ChunkedHttpBody, ChunkedHttpError, ChunkedRequest, DaemonTcp, HttpRequest,
         HttpResponseFallback, InternalHttpBody, InternalHttpBodyFrame, InternalHttpRequest,
-        StealType, TcpClose, TcpData, HTTP_CHUNKED_REQUEST_VERSION, HTTP_FILTERED_UPGRADE_VERSION,
-        HTTP_FRAMED_VERSION,
+        RequiredHeaderFilter, StealType, TcpClose, TcpData, HTTP_CHUNKED_REQUEST_VERSION,
+        HTTP_FILTERED_UPGRADE_VERSION, HTTP_FRAMED_VERSION,
     },
+    udp::{DaemonUdp, UdpDatagram, UdpReply},
     ConnectionId, Port,
     RemoteError::{BadHttpFilterExRegex, BadHttpFilterRegex},
     RequestId,
@@ -26,7 +24,7 @@ use mirrord_protocol::{
 use serde::Deserialize;
 use tokio::{
     net::TcpStream,
-    sync::mpsc::{Receiver, Sender},
+    sync::mpsc::{self, error::TrySendError, Receiver, Sender},
 };
 use tokio_util::sync::CancellationToken;
 use tracing::warn;
@@ -35,11 +33,13 @@ use crate::{
     error::{AgentError, Result},
     steal::{
         connections::{
-            ConnectionMessageIn, ConnectionMessageOut, StolenConnection, StolenConnections,
+            ConnectionMessageIn, ConnectionMessageOut, DynamicBody, StolenConnection,
+            StolenConnections,
         },
         http::HttpFilter,
         orig_dst,
-        subscriptions::{IpTablesRedirector, PortSubscriptions},
+        subscriptions::{IpTablesRedirector, PortSubscriptions, StolenDatagram},
+        tls::{StealTlsConfig, StealTlsHandler},
         Command, StealerCommand,
     },
     util::{ChannelClosedFuture, ClientId},
@@ -51,7 +51,7 @@ struct MatchedHttpRequest {
     connection_id: ConnectionId,
     port: Port,
     request_id: RequestId,
-    request: Request<Incoming>,
+    request: Request<DynamicBody>,
 }
 
 impl MatchedHttpRequest {
@@ -121,11 +121,17 @@ struct Client {
     /// For sending messages to client's [`TcpStealerApi`](super::api::TcpStealerApi).
     /// Comes to [`TcpConnectionStealer`] in [`Command::NewClient`].
     tx: Sender<DaemonTcp>,
+    /// For sending UDP messages to client's [`TcpStealerApi`](super::api::TcpStealerApi).
+    /// Comes to [`TcpConnectionStealer`] in [`Command::NewClient`].
+    udp_tx: Sender<DaemonUdp>,
     /// Clients [`mirrord_protocol`] verison.
     protocol_version: semver::Version,
     /// Client subscriptions to stolen connections.
     /// Used to unsubscribe when the client exits.
     subscribed_connections: HashSet<ConnectionId>,
+    /// HTTP header filters that the mirrord policies require in the client's subscriptions.
+    /// Comes to [`TcpConnectionStealer`] in [`Command::RequireHeaderFilters`].
+    required_header_filters: Vec<RequiredHeaderFilter>,
 }
 
 impl Client {
@@ -143,7 +149,7 @@ impl Client {
     ///
     /// # Why async?
     ///
-    /// This method spawns a [`tokio::task`] to read the [`Incoming`] body od the request without
+    /// This method spawns a [`tokio::task`] to read the body of the request without
     /// blocking the main [`TcpConnectionStealer`] loop.
     fn send_request_async(&self, request: MatchedHttpRequest) -> bool {
         if request.request.headers().contains_key(UPGRADE)
@@ -262,10 +268,15 @@ impl Client {
     }
 }
 
+/// Read from the `MIRRORD_AGENT_` prefixed env vars. The agent container only gets the
+/// variables that are set, e.g. `MIRRORD_AGENT_SUPPORT_IPV6` only when IPv6 is enabled, so every
+/// field has to be optional.
 #[derive(Deserialize, Debug, Default)]
+#[serde(default)]
 struct TcpStealerConfig {
     stealer_flush_connections: bool,
     pod_ips: Option<String>,
+    support_ipv6: bool,
 }
 
 /// Created once per agent during initialization.
@@ -285,26 +296,49 @@ pub(crate) struct TcpConnectionStealer {
     clients: HashMap<ClientId, Client>,
 
     /// [`Future`](std::future::Future)s that resolve when stealer clients close.
-    clients_closed: FuturesUnordered<ChannelClosedFuture<StealerCommand>>,
+    clients_closed: FuturesUnordered<ChannelClosedFuture>,
 
     /// Set of active connections stolen by [`Self::port_subscriptions`].
     connections: StolenConnections,
+
+    /// Receives datagrams stolen from the UDP ports redirected by [`Self::port_subscriptions`].
+    datagram_rx: Receiver<StolenDatagram>,
+
+    /// Terminates TLS on the stolen connections to the configured ports.
+    tls: Option<StealTlsHandler>,
 }
 
 impl TcpConnectionStealer {
     pub const TASK_NAME: &'static str = "Stealer";
 
+    /// Capacity of the channel for datagrams stolen from all redirected UDP ports.
+    const DATAGRAM_CHANNEL_SIZE: usize = 512;
+
     /// Initializes a new [`TcpConnectionStealer`], but doesn't start the actual work.
     /// You need to call [`TcpConnectionStealer::start`] to do so.
+    ///
+    /// `pid` is the process of the target container, used to read the TLS certificate (see
+    /// [`StealTlsHandler`]).
     #[tracing::instrument(level = "trace")]
-    pub(crate) async fn new(command_rx: Receiver<StealerCommand>) -> Result<Self, AgentError> {
+    pub(crate) async fn new(
+        command_rx: Receiver<StealerCommand>,
+        pid: Option<u64>,
+    ) -> Result<Self, AgentError> {
         let config = envy::prefixed("MIRRORD_AGENT_")
             .from_env::<TcpStealerConfig>()
             .unwrap_or_default();
+        let tls = StealTlsHandler::new(StealTlsConfig::from_env()?, pid)?;
+
+        let (datagram_tx, datagram_rx) = mpsc::channel(Self::DATAGRAM_CHANNEL_SIZE);
 
         let port_subscriptions = {
-            let redirector =
-                IpTablesRedirector::new(config.stealer_flush_connections, config.pod_ips).await?;
+            let redirector = IpTablesRedirector::new(
+                config.stealer_flush_connections,
+                config.pod_ips,
+                config.support_ipv6,
+                datagram_tx,
+            )
+            .await?;
 
             PortSubscriptions::new(redirector, 4)
         };
@@ -315,6 +349,8 @@ impl TcpConnectionStealer {
             clients: HashMap::with_capacity(8),
             clients_closed: Default::default(),
             connections: StolenConnections::with_capacity(8),
+            datagram_rx,
+            tls,
         })
     }
 
@@ -326,9 +362,11 @@ impl TcpConnectionStealer {
     ///
     /// 2. Accepting a new stolen connection based on the clients' port subscriptions;
     ///
-    /// 3. Receiving an update from one of the active stolen connections;
+    /// 3. Receiving a new stolen UDP datagram based on the clients' UDP port subscriptions;
     ///
-    /// 4. Handling the cancellation of the whole stealer thread (given `cancellation_token`).
+    /// 4. Receiving an update from one of the active stolen connections;
+    ///
+    /// 5. Handling the cancellation of the whole stealer thread (given `cancellation_token`).
     #[tracing::instrument(level = "trace", skip(self))]
     pub(crate) async fn start(
         mut self,
@@ -358,6 +396,8 @@ impl TcpConnectionStealer {
                     }
                 },
 
+                Some(datagram) = self.datagram_rx.recv() => self.incoming_datagram(datagram),
+
                 update = self.connections.wait() => self.handle_connection_update(update).await?,
 
                 _ = cancellation_token.cancelled() => {
@@ -391,6 +431,11 @@ impl TcpConnectionStealer {
             source: peer,
             destination: real_address,
             port_subscription,
+            tls: self
+                .tls
+                .as_ref()
+                .filter(|tls| tls.handles(real_address.port()))
+                .cloned(),
         };
 
         self.connections.manage(stolen_connection);
@@ -398,6 +443,61 @@ impl TcpConnectionStealer {
         Ok(())
     }
 
+    /// Handles a new UDP datagram that was stolen by [`Self::port_subscriptions`].
+    ///
+    /// Datagrams are distributed with the non-blocking [`Sender::try_send`] method, so that a slow
+    /// client does not stall the whole stealer.
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn incoming_datagram(&mut self, datagram: StolenDatagram) {
+        let Some(client) = self
+            .port_subscriptions
+            .get_udp(datagram.port)
+            .and_then(|client_id| self.clients.get(&client_id))
+        else {
+            // Same as with TCP connections, this *can* happen due to race conditions.
+            return;
+        };
+
+        let local_address = if datagram.peer.is_ipv6() {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        };
+
+        let message = DaemonUdp::Datagram(UdpDatagram {
+            remote_address: datagram.peer.ip(),
+            source_port: datagram.peer.port(),
+            local_address,
+            destination_port: datagram.port,
+            bytes: datagram.bytes,
+        });
+
+        if let Err(TrySendError::Full(..)) = client.udp_tx.try_send(message) {
+            tracing::warn!(
+                port = datagram.port,
+                peer = %datagram.peer,
+                "Client queue of stolen UDP datagrams is full, dropping",
+            );
+        }
+    }
+
+    /// Sends the given [`UdpReply`] to the peer, if the client still steals the port.
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn send_udp_reply(&mut self, client_id: ClientId, reply: UdpReply) {
+        if self.port_subscriptions.get_udp(reply.port) != Some(client_id) {
+            return;
+        }
+
+        let peer = SocketAddr::new(reply.remote_address, reply.remote_port);
+        if let Err(error) = self
+            .port_subscriptions
+            .send_datagram(reply.port, peer, &reply.bytes)
+            .await
+        {
+            tracing::warn!(?error, port = reply.port, %peer, "Failed to send a UDP reply");
+        }
+    }
+
     /// Handles an update from one of the connections in [`Self::connections`].
     #[tracing::instrument(level = "trace", skip(self))]
     async fn handle_connection_update(
@@ -536,9 +636,20 @@ impl TcpConnectionStealer {
 
     /// Helper function to handle [`Command::PortSubscribe`] messages.
     ///
-    /// Inserts a subscription into [`Self::port_subscriptions`].
+    /// Inserts a subscription into [`Self::port_subscriptions`]. Subscriptions that do not include
+    /// the [`Client::required_header_filters`] are rejected.
     #[tracing::instrument(level = "trace", skip(self))]
     async fn port_subscribe(&mut self, client_id: ClientId, port_steal: StealType) -> Result<()> {
+        let client = self.clients.get(&client_id).expect("client not found");
+        if let Err(error) = client
+            .required_header_filters
+            .iter()
+            .try_for_each(|required| required.check(&port_steal))
+        {
+            let _ = client.tx.send(DaemonTcp::SubscribeResult(Err(error))).await;
+            return Ok(());
+        }
+
         let spec = match port_steal {
             StealType::All(port) => Ok((port, None)),
             StealType::FilteredHttp(port, filter) => Regex::new(&format!("(?i){filter}"))
@@ -625,17 +736,24 @@ impl TcpConnectionStealer {
         let StealerCommand { client_id, command } = command;
 
         match command {
-            Command::NewClient(daemon_tx, protocol_version) => {
+            Command::NewClient(daemon_tx, udp_tx, protocol_version) => {
                 self.clients.insert(
                     client_id,
                     Client {
                         tx: daemon_tx,
+                        udp_tx,
                         protocol_version,
                         subscribed_connections: Default::default(),
+                        required_header_filters: Default::default(),
                     },
                 );
             }
 
+            Command::RequireHeaderFilters(required) => {
+                let client = self.clients.get_mut(&client_id).expect("client not found");
+                client.required_header_filters = required;
+            }
+
             Command::ConnectionUnsubscribe(connection_id) => {
                 self.clients
                     .get_mut(&client_id)
@@ -682,6 +800,30 @@ impl TcpConnectionStealer {
                 let client = self.clients.get_mut(&client_id).expect("client not found");
                 client.protocol_version = new_version;
             }
+
+            Command::UdpPortSubscribe(port) => {
+                // Datagrams are never filtered.
+                let blocked = self
+                    .clients
+                    .get(&client_id)
+                    .expect("client not found")
+                    .required_header_filters
+                    .iter()
+                    .try_for_each(|required| required.check(&StealType::All(port)));
+                let res = match blocked {
+                    Ok(()) => self.port_subscriptions.add_udp(client_id, port).await?,
+                    Err(error) => Err(error),
+                };
+
+                let client = self.clients.get(&client_id).expect("client not found");
+                let _ = client.udp_tx.send(DaemonUdp::SubscribeResult(res)).await;
+            }
+
+            Command::UdpPortUnsubscribe(port) => {
+                self.port_subscriptions.remove_udp(client_id, port).await?;
+            }
+
+            Command::UdpReply(reply) => self.send_udp_reply(client_id, reply).await,
         }
 
         Ok(())
@@ -695,7 +837,7 @@ mod test {
     use bytes::Bytes;
     use futures::{future::BoxFuture, FutureExt};
     use http::{Method, Request, Response, Version};
-    use http_body_util::{Empty, StreamBody};
+    use http_body_util::{combinators::BoxBody, Empty, StreamBody};
     use hyper::{
         body::{Frame, Incoming},
         service::Service,
@@ -712,7 +854,27 @@ mod test {
     };
     use tokio_stream::wrappers::ReceiverStream;
 
-    use crate::steal::connection::{Client, MatchedHttpRequest};
+    use crate::steal::connection::{Client, MatchedHttpRequest, TcpStealerConfig};
+
+    /// IPv4-only agents don't get `MIRRORD_AGENT_SUPPORT_IPV6`, which must not discard the rest
+    /// of the config.
+    #[test]
+    fn stealer_config_without_ipv6() {
+        let config = envy::prefixed("MIRRORD_AGENT_")
+            .from_iter::<_, TcpStealerConfig>([
+                (
+                    "MIRRORD_AGENT_STEALER_FLUSH_CONNECTIONS".to_string(),
+                    "true".to_string(),
+                ),
+                ("MIRRORD_AGENT_POD_IPS".to_string(), "10.0.0.7".to_string()),
+            ])
+            .unwrap();
+
+        assert!(config.stealer_flush_connections);
+        assert_eq!(config.pod_ips.as_deref(), Some("10.0.0.7"));
+        assert!(!config.support_ipv6);
+    }
+
     async fn prepare_dummy_service() -> (
         SocketAddr,
         Receiver<(Request<Incoming>, oneshot::Sender<Response<Empty<Bytes>>>)>,
@@ -790,8 +952,10 @@ mod test {
         let (client_tx, mut client_rx) = mpsc::channel::<DaemonTcp>(4);
         let client = Client {
             tx: client_tx,
+            udp_tx: mpsc::channel(1).0,
             protocol_version: "1.7.0".parse().unwrap(),
             subscribed_connections: Default::default(),
+            required_header_filters: Default::default(),
         };
 
         let (request, response_tx) = request_rx.recv().await.unwrap();
@@ -799,7 +963,7 @@ mod test {
             connection_id: 0,
             port: 80,
             request_id: 0,
-            request,
+            request: request.map(BoxBody::new),
         });
 
         // Verify that single-framed ChunkedRequest::Start requests are as expected, containing any
@@ -859,8 +1023,10 @@ mod test {
         let (client_tx, mut client_rx) = mpsc::channel::<DaemonTcp>(4);
         let client = Client {
             tx: client_tx,
+            udp_tx: mpsc::channel(1).0,
             protocol_version: "1.7.0".parse().unwrap(),
             subscribed_connections: Default::default(),
+            required_header_filters: Default::default(),
         };
 
         let (request, response_tx) = request_rx.recv().await.unwrap();
@@ -868,7 +1034,7 @@ mod test {
             connection_id: 0,
             port: 80,
             request_id: 0,
-            request,
+            request: request.map(BoxBody::new),
         });
 
         // Verify that ChunkedRequest::Start request is as expected
@@ -1,19 +1,26 @@
 //! Home for [`StolenConnections`] - manager for connections that were stolen based on active port
 //! subscriptions.
 
-use std::{collections::HashMap, fmt, io, net::SocketAddr, time::Duration};
+use std::{collections::HashMap, fmt, io, net::SocketAddr, sync::Arc, time::Duration};
 
-use hyper::{body::Incoming, Request, Response};
+use dashmap::DashMap;
+use hyper::{Request, Response};
 use mirrord_protocol::{tcp::NewTcpConnection, ConnectionId, Port, RequestId};
 use thiserror::Error;
 use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::TcpStream,
     sync::mpsc::{self, error::SendError, Receiver, Sender},
     task::JoinSet,
 };
 
-use self::{filtered::DynamicBody, unfiltered::UnfilteredStealTask};
-use super::{http::DefaultReversibleStream, subscriptions::PortSubscription};
+pub(crate) use self::filtered::DynamicBody;
+use self::unfiltered::UnfilteredStealTask;
+use super::{
+    http::{DefaultReversibleStream, HttpFilter},
+    subscriptions::PortSubscription,
+    tls::{OriginalDestination, StealTlsHandler},
+};
 use crate::{http::HttpVersion, steal::connections::filtered::FilteredStealTask, util::ClientId};
 
 mod filtered;
@@ -130,7 +137,7 @@ pub enum ConnectionMessageOut {
     Request {
         client_id: ClientId,
         connection_id: ConnectionId,
-        request: Request<Incoming>,
+        request: Request<DynamicBody>,
         id: RequestId,
         port: Port,
     },
@@ -380,6 +387,9 @@ pub struct StolenConnection {
     pub destination: SocketAddr,
     /// Subscription that triggered the steal.
     pub port_subscription: PortSubscription,
+    /// Present when TLS should be terminated on this connection (only when
+    /// [`PortSubscription::Filtered`] is in use).
+    pub tls: Option<StealTlsHandler>,
 }
 
 impl fmt::Debug for StolenConnection {
@@ -391,6 +401,7 @@ impl fmt::Debug for StolenConnection {
                 "filtered",
                 &matches!(self.port_subscription, PortSubscription::Filtered(..)),
             )
+            .field("tls", &self.tls.is_some())
             .finish()
     }
 }
@@ -474,29 +485,74 @@ impl ConnectionTask {
                 )
                 .await?;
 
-                let Some(http_version) = HttpVersion::new(stream.get_header()) else {
-                    tracing::trace!(
-                        "No HTTP version detected, proxying the connection transparently"
-                    );
-
-                    let mut outgoing_io = TcpStream::connect(self.connection.destination).await?;
-                    tokio::io::copy_bidirectional(&mut stream, &mut outgoing_io).await?;
+                match self.connection.tls {
+                    Some(tls) if StealTlsHandler::is_handshake(stream.get_header()) => {
+                        let (stream, destination) = tokio::time::timeout(
+                            Self::HTTP_DETECTION_TIMEOUT,
+                            tls.accept(stream, self.connection.destination),
+                        )
+                        .await
+                        .map_err(io::Error::from)??;
+                        let stream = DefaultReversibleStream::read_header(
+                            stream,
+                            Self::HTTP_DETECTION_TIMEOUT,
+                        )
+                        .await?;
+
+                        Self::run_filtered(
+                            self.connection_id,
+                            filters,
+                            destination,
+                            stream,
+                            self.tx,
+                            &mut self.rx,
+                        )
+                        .await
+                    }
+                    _ => {
+                        Self::run_filtered(
+                            self.connection_id,
+                            filters,
+                            OriginalDestination::new(self.connection.destination),
+                            stream,
+                            self.tx,
+                            &mut self.rx,
+                        )
+                        .await
+                    }
+                }
+            }
+        }
+    }
 
-                    return Ok(());
-                };
+    /// Detects the HTTP version on the given `stream` and runs a [`FilteredStealTask`].
+    ///
+    /// If no HTTP version is detected, proxies the connection to the original destination.
+    async fn run_filtered<IO>(
+        connection_id: ConnectionId,
+        filters: Arc<DashMap<ClientId, HttpFilter>>,
+        destination: OriginalDestination,
+        mut stream: DefaultReversibleStream<IO>,
+        tx: Sender<ConnectionMessageOut>,
+        rx: &mut Receiver<ConnectionMessageIn>,
+    ) -> Result<(), ConnectionTaskError>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let Some(http_version) = HttpVersion::new(stream.get_header()) else {
+            tracing::trace!("No HTTP version detected, proxying the connection transparently");
+
+            let mut outgoing_io = destination.connect().await?;
+            tokio::io::copy_bidirectional(&mut stream, &mut outgoing_io).await?;
+
+            return Ok(());
+        };
 
-                tracing::trace!(?http_version, "Detected HTTP version");
+        tracing::trace!(?http_version, %destination, "Detected HTTP version");
 
-                let task = FilteredStealTask::new(
-                    self.connection_id,
-                    filters,
-                    self.connection.destination,
-                    http_version,
-                    stream,
-                );
+        let task =
+            FilteredStealTask::new(connection_id, filters, destination, http_version, stream);
 
-                task.run(self.tx.clone(), &mut self.rx).await
-            }
-        }
+        task.run(tx, rx).await
     }
 }
@@ -1,11 +1,10 @@
-use std::{
-    collections::HashMap, future::Future, marker::PhantomData, net::SocketAddr, pin::Pin, sync::Arc,
-};
+use std::{collections::HashMap, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use dashmap::DashMap;
-use http::Version;
-use http_body_util::{combinators::BoxBody, BodyExt};
+use futures::{stream, StreamExt};
+use http::{header::CONTENT_LENGTH, Version};
+use http_body_util::{combinators::BoxBody, BodyExt, BodyStream, StreamBody};
 use hyper::{
     body::Incoming,
     client::conn::{http1, http2},
@@ -18,12 +17,12 @@ use hyper_util::rt::{TokioExecutor, TokioIo};
 use mirrord_protocol::{ConnectionId, RequestId};
 use tokio::{
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
-    net::TcpStream,
     sync::{
         mpsc::{self, Receiver, Sender},
         oneshot,
     },
     task::{self, JoinHandle},
+    time::Instant,
 };
 use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::Level;
@@ -31,7 +30,11 @@ use tracing::Level;
 use super::{ConnectionMessageIn, ConnectionMessageOut, ConnectionTaskError};
 use crate::{
     http::HttpVersion,
-    steal::{connections::unfiltered::UnfilteredStealTask, http::HttpFilter},
+    steal::{
+        connections::unfiltered::UnfilteredStealTask,
+        http::{BufferedBody, HttpFilter, FILTERED_BODY_TIMEOUT, MAX_FILTERED_BODY_SIZE},
+        tls::{OriginalDestination, OriginalDestinationStream},
+    },
     util::ClientId,
 };
 
@@ -40,17 +43,17 @@ pub type DynamicBody = BoxBody<Bytes, hyper::Error>;
 
 /// Incoming [`Request`] extracted from the HTTP connection in the [`FilteringService`].
 struct ExtractedRequest {
-    request: Request<Incoming>,
+    request: Request<DynamicBody>,
     response_tx: oneshot::Sender<RequestHandling>,
 }
 
 /// Response instruction for [`FilteringService`].
 /// Sent from [`FilteredStealTask`] in [`ExtractedRequest::response_tx`].
 enum RequestHandling {
-    /// The [`Request`] should be handled by the HTTP server running at the given address.
+    /// The [`Request`] should be handled by the HTTP server running at the given destination.
     LetThrough {
-        to: SocketAddr,
-        unchanged: Request<Incoming>,
+        to: OriginalDestination,
+        unchanged: Request<DynamicBody>,
     },
     /// The [`FilteringService`] should respond immediately with the given [`Response`]
     /// on behalf of the given stealer client.
@@ -87,6 +90,10 @@ struct FilteringService {
     /// For sending incoming requests to the [`FilteredStealTask`].
     requests_tx: Sender<ExtractedRequest>,
 
+    /// Same filters as in [`FilteredStealTask`], used only to check whether request bodies need
+    /// to be buffered (see [`HttpFilter::needs_body`]).
+    filters: Arc<DashMap<ClientId, HttpFilter>>,
+
     /// For recovering the upgraded connection in [`FilteredStealTask`].
     ///
     /// # Note
@@ -117,10 +124,10 @@ impl FilteringService {
     /// This method always creates a new TCP connection and preforms an HTTP handshake.
     /// Also, it does not retry the request upon failure.
     async fn send_request(
-        to: SocketAddr,
-        mut request: Request<Incoming>,
+        to: &OriginalDestination,
+        mut request: Request<DynamicBody>,
     ) -> Result<Response<Incoming>, Box<dyn std::error::Error>> {
-        let tcp_stream = TcpStream::connect(to).await.inspect_err(|error| {
+        let tcp_stream = to.connect().await.inspect_err(|error| {
             tracing::error!(?error, address = %to, "Failed connecting to request destination");
         })?;
 
@@ -191,12 +198,12 @@ impl FilteringService {
     )]
     async fn let_through(
         &self,
-        request: Request<Incoming>,
+        request: Request<DynamicBody>,
         on_upgrade: OnUpgrade,
-        to: SocketAddr,
+        to: OriginalDestination,
     ) -> Response<DynamicBody> {
         let version = request.version();
-        let mut response = Self::send_request(to, request)
+        let mut response = Self::send_request(&to, request)
             .await
             .map(|response| response.map(BoxBody::new))
             .unwrap_or_else(|_| {
@@ -271,6 +278,69 @@ impl FilteringService {
         }
     }
 
+    /// Prepares the given [`Request`] for matching with [`HttpFilter`]s.
+    ///
+    /// If any of the filters needs to inspect the body, reads the body frames until the end,
+    /// until [`MAX_FILTERED_BODY_SIZE`] is exceeded or until [`FILTERED_BODY_TIMEOUT`] elapses.
+    /// Bodies with a bigger `Content-Length` are not read at all. If the whole body was read, it's
+    /// stored in the [`BufferedBody`] extension. Either way, the returned [`Request`] has the
+    /// original body.
+    async fn buffer_body(
+        &self,
+        request: Request<Incoming>,
+    ) -> Result<Request<DynamicBody>, hyper::Error> {
+        if !self
+            .filters
+            .iter()
+            .any(|filter| filter.value().needs_body())
+        {
+            return Ok(request.map(BoxBody::new));
+        }
+
+        let (mut parts, mut body) = request.into_parts();
+
+        let too_big = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+            .is_some_and(|length| length > MAX_FILTERED_BODY_SIZE);
+        if too_big {
+            return Ok(Request::from_parts(parts, BoxBody::new(body)));
+        }
+
+        let deadline = Instant::now() + FILTERED_BODY_TIMEOUT;
+        let mut frames = Vec::new();
+        let mut data = BytesMut::new();
+        let complete = loop {
+            let Ok(frame) = tokio::time::timeout_at(deadline, body.frame()).await else {
+                break false;
+            };
+            let Some(frame) = frame.transpose()? else {
+                break true;
+            };
+
+            if let Some(chunk) = frame.data_ref() {
+                data.extend_from_slice(chunk);
+            }
+            frames.push(frame);
+
+            if data.len() > MAX_FILTERED_BODY_SIZE {
+                break false;
+            }
+        };
+
+        if complete {
+            parts.extensions.insert(BufferedBody(data.freeze()));
+        }
+
+        let body = stream::iter(frames.into_iter().map(Ok)).chain(BodyStream::new(body));
+
+        Ok(Request::from_parts(
+            parts,
+            BoxBody::new(StreamBody::new(body)),
+        ))
+    }
+
     /// Extracts [`OnUpgrade`] from the given [`Request`] and sends it to [`FilteredStealTask`].
     /// Waits on a dynamically created [`oneshot::channel`] for [`RequestHandling`] instruction.
     async fn handle_request(
@@ -280,6 +350,17 @@ impl FilteringService {
         let version = request.version();
         let on_upgrade = hyper::upgrade::on(&mut request);
 
+        let request = match self.buffer_body(request).await {
+            Ok(request) => request,
+            Err(error) => {
+                tracing::warn!(?error, "Failed to read the request body");
+                return Ok(Self::bad_gateway(
+                    version,
+                    "failed to read the request body",
+                ));
+            }
+        };
+
         let (response_tx, response_rx) = oneshot::channel();
         self.requests_tx
             .send(ExtractedRequest {
@@ -332,7 +413,7 @@ pub struct FilteredStealTask<T> {
     connection_id: ConnectionId,
     /// Original destination of the stolen connection. Used when passing through HTTP requests that
     /// don't not match any filter in [`Self::filters`].
-    original_destination: SocketAddr,
+    original_destination: OriginalDestination,
 
     /// Stealer client to [`HttpFilter`] mapping. Allows for routing HTTP requests to correct
     /// stealer clients.
@@ -389,7 +470,7 @@ where
     pub fn new(
         connection_id: ConnectionId,
         filters: Arc<DashMap<ClientId, HttpFilter>>,
-        original_destination: SocketAddr,
+        original_destination: OriginalDestination,
         http_version: HttpVersion,
         io: T,
     ) -> Self {
@@ -398,6 +479,7 @@ where
 
         let service = FilteringService {
             requests_tx,
+            filters: filters.clone(),
             upgrade_tx,
         };
 
@@ -568,7 +650,7 @@ where
     ) -> Result<(), ConnectionTaskError> {
         let Some(client_id) = self.match_request(&mut request.request) else {
             let _ = request.response_tx.send(RequestHandling::LetThrough {
-                to: self.original_destination,
+                to: self.original_destination.clone(),
                 unchanged: request.request,
             });
 
@@ -592,7 +674,7 @@ where
             connection_id: self.connection_id,
             request: request.request,
             id,
-            port: self.original_destination.port(),
+            port: self.original_destination.address().port(),
         })
         .await?;
 
@@ -751,7 +833,7 @@ where
                 }
 
                 let parts = upgraded
-                    .downcast::<TokioIo<TcpStream>>()
+                    .downcast::<TokioIo<OriginalDestinationStream>>()
                     .expect("IO type is known");
                 let mut http_server_io = parts.io.into_inner();
                 let http_server_read_buf = parts.read_buf;
@@ -810,6 +892,8 @@ where
 #[cfg(test)]
 mod test {
 
+    use std::net::SocketAddr;
+
     use bytes::BytesMut;
     use http::{
         header::{CONNECTION, UPGRADE},
@@ -817,7 +901,11 @@ mod test {
     };
     use http_body_util::Empty;
     use hyper::{client::conn::http1::SendRequest, service::service_fn};
-    use tokio::{io::AsyncReadExt, net::TcpListener, task::JoinSet};
+    use tokio::{
+        io::AsyncReadExt,
+        net::{TcpListener, TcpStream},
+        task::JoinSet,
+    };
 
     use super::*;
 
@@ -968,7 +1056,7 @@ mod test {
                 let task = FilteredStealTask::new(
                     Self::CONNECTION_ID,
                     filters_clone,
-                    original_address,
+                    OriginalDestination::new(original_address),
                     HttpVersion::V1,
                     server_stream,
                 );
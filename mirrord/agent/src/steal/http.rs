@@ -1,13 +1,16 @@
 //! Utils related to stealing with an HTTP filter.
 
+use tokio::net::TcpStream;
+
 use crate::http::HttpVersion;
 
 mod filter;
 mod reversible_stream;
 
-pub use filter::HttpFilter;
+pub use filter::{BufferedBody, HttpFilter, FILTERED_BODY_TIMEOUT, MAX_FILTERED_BODY_SIZE};
 
 pub(crate) use self::reversible_stream::ReversibleStream;
 
 /// Handy alias due to [`ReversibleStream`] being generic, avoiding value mismatches.
-pub(crate) type DefaultReversibleStream = ReversibleStream<{ HttpVersion::MINIMAL_HEADER_SIZE }>;
+pub(crate) type DefaultReversibleStream<IO = TcpStream> =
+    ReversibleStream<{ HttpVersion::MINIMAL_HEADER_SIZE }, IO>;
@@ -1,7 +1,35 @@
+use std::{str::FromStr, time::Duration};
+
+use bytes::Bytes;
 use fancy_regex::Regex;
 use hyper::Request;
+use jsonpath_rust::{path::config::JsonPathConfig, JsonPathInst};
+use mirrord_protocol::tcp::HttpBodyFilter;
+use thiserror::Error;
 use tracing::Level;
 
+/// Max size of a request body that can be inspected with [`HttpFilter::Body`].
+///
+/// Requests with bigger bodies never match [`HttpFilter::Body`].
+pub const MAX_FILTERED_BODY_SIZE: usize = 64 * 1024;
+
+/// How long we wait for a request body that can be inspected with [`HttpFilter::Body`].
+///
+/// Requests with bodies that take longer (e.g. streaming requests) never match
+/// [`HttpFilter::Body`].
+pub const FILTERED_BODY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Errors that can occur when building an [`HttpFilter`] from a
+/// [`mirrord_protocol::tcp::HttpFilter`].
+#[derive(Error, Debug)]
+pub enum HttpFilterError {
+    #[error(transparent)]
+    Regex(#[from] fancy_regex::Error),
+
+    #[error("invalid JSONPath query: {0}")]
+    JsonPath(String),
+}
+
 /// Currently supported filtering criterias.
 #[derive(Debug)]
 pub enum HttpFilter {
@@ -17,10 +45,28 @@ pub enum HttpFilter {
         /// Filters to use.
         filters: Vec<HttpFilter>,
     },
+    /// Method based filter, case-insensitive.
+    Method(String),
+    /// Query based filter.
+    /// This [`Regex`] should be used against each `key=value` pair from the query string (not
+    /// percent-decoded).
+    Query(Regex),
+    /// Body based filter, requires the body to be buffered in [`BufferedBody`].
+    Body(BodyFilter),
+}
+
+/// Body part of [`HttpFilter::Body`].
+#[derive(Debug)]
+pub enum BodyFilter {
+    /// This [`Regex`] should be used against the whole body.
+    Regex(Regex),
+    /// Values selected with the JSONPath `query` from the JSON body are matched with this
+    /// [`Regex`]. JSON strings are matched without quotes.
+    Json { query: JsonPathInst, matches: Regex },
 }
 
 impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
-    type Error = fancy_regex::Error;
+    type Error = HttpFilterError;
 
     fn try_from(filter: &mirrord_protocol::tcp::HttpFilter) -> Result<Self, Self::Error> {
         match filter {
@@ -38,11 +84,33 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
                     .collect::<Result<Vec<_>, _>>()?;
                 Ok(Self::Composite { all, filters })
             }
+            mirrord_protocol::tcp::HttpFilter::Method(method) => Ok(Self::Method(method.clone())),
+            mirrord_protocol::tcp::HttpFilter::Query(query) => {
+                Ok(Self::Query(Regex::new(&format!("(?i){query}"))?))
+            }
+            mirrord_protocol::tcp::HttpFilter::Body(body) => match &**body {
+                HttpBodyFilter::Regex(regex) => Ok(Self::Body(BodyFilter::Regex(Regex::new(
+                    &regex.to_string(),
+                )?))),
+                HttpBodyFilter::Json { query, matches } => Ok(Self::Body(BodyFilter::Json {
+                    query: JsonPathInst::from_str(query).map_err(HttpFilterError::JsonPath)?,
+                    matches: Regex::new(&matches.to_string())?,
+                })),
+            },
         }
     }
 }
 
 impl HttpFilter {
+    /// Checks whether this filter needs to inspect the request body, see [`BufferedBody`].
+    pub fn needs_body(&self) -> bool {
+        match self {
+            Self::Body(..) => true,
+            Self::Composite { filters, .. } => filters.iter().any(Self::needs_body),
+            Self::Header(..) | Self::Path(..) | Self::Method(..) | Self::Query(..) => false,
+        }
+    }
+
     /// Checks whether the given [`Request`] matches this filter.
     #[tracing::instrument(level = Level::TRACE, skip(request), ret(level = "DEBUG"))]
     pub fn matches<T>(&self, request: &mut Request<T>) -> bool {
@@ -110,10 +178,76 @@ impl HttpFilter {
                 all: false,
                 filters,
             } => filters.iter().any(|f| f.matches(request)),
+
+            Self::Method(method) => request.method().as_str().eq_ignore_ascii_case(method),
+
+            Self::Query(filter) => request
+                .uri()
+                .query()
+                .map(|query| {
+                    query.split('&').any(|pair| {
+                        filter
+                            .is_match(pair)
+                            .inspect_err(|error| {
+                                tracing::error!(pair, ?error, "Error while matching query");
+                            })
+                            .unwrap_or(false)
+                    })
+                })
+                .unwrap_or(false),
+
+            Self::Body(filter) => request
+                .extensions()
+                .get::<BufferedBody>()
+                .is_some_and(|BufferedBody(body)| filter.matches(body)),
         }
     }
 }
 
+impl BodyFilter {
+    /// Checks whether the given (complete) body matches this filter.
+    fn matches(&self, body: &Bytes) -> bool {
+        let Ok(body) = std::str::from_utf8(body) else {
+            return false;
+        };
+
+        match self {
+            Self::Regex(filter) => filter
+                .is_match(body)
+                .inspect_err(|error| tracing::error!(?error, "Error while matching body"))
+                .unwrap_or(false),
+
+            Self::Json { query, matches } => {
+                let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+                    return false;
+                };
+
+                query
+                    .find_slice(&json, JsonPathConfig::default())
+                    .iter()
+                    .any(|value| {
+                        let value = match &**value {
+                            serde_json::Value::String(value) => value.clone(),
+                            other => other.to_string(),
+                        };
+
+                        matches
+                            .is_match(&value)
+                            .inspect_err(|error| {
+                                tracing::error!(value, ?error, "Error while matching JSON value");
+                            })
+                            .unwrap_or(false)
+                    })
+            }
+        }
+    }
+}
+
+/// Complete body of a [`Request`], stored in [`Request::extensions`] when the body was small
+/// enough to be buffered (see [`MAX_FILTERED_BODY_SIZE`]). Used by [`HttpFilter::Body`].
+#[derive(Clone, Debug)]
+pub struct BufferedBody(pub Bytes);
+
 /// [`HeaderMap`](hyper::http::header::HeaderMap) entries formatted like `k: v` (format expected by
 /// [`HttpFilter::Header`]). Computed and cached in [`Request::extensions`] the first time
 /// [`HttpFilter::matches`] is called on the [`Request`].
@@ -138,9 +272,9 @@ impl NormalizedHeaders {
 #[cfg(test)]
 mod test {
     use hyper::Request;
-    use mirrord_protocol::tcp::{self, Filter};
+    use mirrord_protocol::tcp::{self, Filter, HttpBodyFilter};
 
-    use crate::steal::http::HttpFilter;
+    use crate::steal::http::{BufferedBody, HttpFilter};
 
     #[test]
     fn matching_all_filter() {
@@ -200,4 +334,87 @@ mod test {
         let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
         assert!(!filter.matches(&mut input));
     }
+
+    #[test]
+    fn matching_method_filter() {
+        let filter: HttpFilter =
+            TryFrom::try_from(&tcp::HttpFilter::Method("post".into())).unwrap();
+
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/path/to/v1")
+            .body(())
+            .unwrap();
+        assert!(filter.matches(&mut input));
+
+        let mut input = Request::builder()
+            .method("GET")
+            .uri("https://www.balconia.gov/api/path/to/v1")
+            .body(())
+            .unwrap();
+        assert!(!filter.matches(&mut input));
+    }
+
+    #[test]
+    fn matching_query_filter() {
+        let filter: HttpFilter = TryFrom::try_from(&tcp::HttpFilter::Query(
+            Filter::new("^tenant=foo$".to_string()).unwrap(),
+        ))
+        .unwrap();
+
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api?page=2&tenant=foo")
+            .body(())
+            .unwrap();
+        assert!(filter.matches(&mut input));
+
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api?page=2&tenant=foobar")
+            .body(())
+            .unwrap();
+        assert!(!filter.matches(&mut input));
+
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api/tenant=foo")
+            .body(())
+            .unwrap();
+        assert!(!filter.matches(&mut input));
+    }
+
+    #[test]
+    fn matching_body_filter() {
+        let regex_filter: HttpFilter = TryFrom::try_from(&tcp::HttpFilter::Body(Box::new(
+            HttpBodyFilter::Regex(Filter::new("\"tenant\": ?\"foo\"".to_string()).unwrap()),
+        )))
+        .unwrap();
+        let json_filter: HttpFilter =
+            TryFrom::try_from(&tcp::HttpFilter::Body(Box::new(HttpBodyFilter::Json {
+                query: "$.user.tenant".to_string(),
+                matches: Filter::new("^foo$".to_string()).unwrap(),
+            })))
+            .unwrap();
+
+        let body = r#"{"user": {"tenant": "foo", "id": 7}}"#;
+        let mut input = Request::builder()
+            .method("POST")
+            .extension(BufferedBody(body.into()))
+            .body(())
+            .unwrap();
+        assert!(regex_filter.matches(&mut input));
+        assert!(json_filter.matches(&mut input));
+
+        let body = r#"{"user": {"tenant": "foobar", "id": 7}}"#;
+        let mut input = Request::builder()
+            .method("POST")
+            .extension(BufferedBody(body.into()))
+            .body(())
+            .unwrap();
+        assert!(!regex_filter.matches(&mut input));
+        assert!(!json_filter.matches(&mut input));
+
+        // Body was not buffered (e.g. too big).
+        let mut input = Request::builder().method("POST").body(()).unwrap();
+        assert!(!regex_filter.matches(&mut input));
+        assert!(!json_filter.matches(&mut input));
+    }
 }
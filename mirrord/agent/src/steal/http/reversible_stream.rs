@@ -11,8 +11,9 @@ use tokio::{
     time::{Duration, Instant},
 };
 
-/// Wraps a [`TcpStream`] to allow a sort of _peek_ functionality, by reading the first bytes, but
-/// then keeping them for later reads.
+/// Wraps a [`TcpStream`] (or another IO stream, e.g. a TLS stream terminated by the agent) to allow
+/// a sort of _peek_ functionality, by reading the first bytes, but then keeping them for later
+/// reads.
 ///
 /// Very useful to the HTTP filter component on `stealer`, where we have to look at the first
 /// message on a [`TcpStream`] to try and identify if this connection is _talking_ HTTP.
@@ -20,8 +21,8 @@ use tokio::{
 /// Thanks [finomnis](https://stackoverflow.com/users/2902833/finomnis) for the help!
 // impl deref with pin
 #[derive(Debug)]
-pub(crate) struct ReversibleStream<const HEADER_SIZE: usize> {
-    stream: TcpStream,
+pub(crate) struct ReversibleStream<const HEADER_SIZE: usize, IO = TcpStream> {
+    stream: IO,
 
     header: [u8; HEADER_SIZE],
 
@@ -40,10 +41,13 @@ pub(crate) struct ReversibleStream<const HEADER_SIZE: usize> {
     num_forwarded: usize,
 }
 
-impl<const HEADER_SIZE: usize> ReversibleStream<HEADER_SIZE> {
+impl<const HEADER_SIZE: usize, IO> ReversibleStream<HEADER_SIZE, IO>
+where
+    IO: AsyncRead + Unpin,
+{
     /// Build a [`ReversibleStream`] from a [`TcpStream`], move on if not done within given timeout.
     /// Return an Error if there was an error while reading from the [`TcpStream`].
-    pub(crate) async fn read_header(stream: TcpStream, timeout: Duration) -> io::Result<Self> {
+    pub(crate) async fn read_header(stream: IO, timeout: Duration) -> io::Result<Self> {
         let mut this = Self {
             stream,
             header: [0; HEADER_SIZE],
@@ -100,7 +104,10 @@ impl<const HEADER_SIZE: usize> ReversibleStream<HEADER_SIZE> {
     }
 }
 
-impl<const HEADER_SIZE: usize> AsyncRead for ReversibleStream<HEADER_SIZE> {
+impl<const HEADER_SIZE: usize, IO> AsyncRead for ReversibleStream<HEADER_SIZE, IO>
+where
+    IO: AsyncRead + Unpin,
+{
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -121,7 +128,10 @@ impl<const HEADER_SIZE: usize> AsyncRead for ReversibleStream<HEADER_SIZE> {
     }
 }
 
-impl<const HEADER_SIZE: usize> AsyncWrite for ReversibleStream<HEADER_SIZE> {
+impl<const HEADER_SIZE: usize, IO> AsyncWrite for ReversibleStream<HEADER_SIZE, IO>
+where
+    IO: AsyncWrite + Unpin,
+{
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
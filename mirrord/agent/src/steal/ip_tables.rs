@@ -141,6 +141,18 @@ pub fn new_iptables() -> iptables::IPTables {
     .expect("IPTables initialization may not fail!")
 }
 
+/// wrapper around iptables::new that uses ip6tables nft or legacy based on env
+pub fn new_ip6tables() -> iptables::IPTables {
+    if let Ok(val) = std::env::var("MIRRORD_AGENT_NFTABLES")
+        && val.to_lowercase() == "true"
+    {
+        iptables::new_with_cmd("/usr/sbin/ip6tables-nft")
+    } else {
+        iptables::new_with_cmd("/usr/sbin/ip6tables-legacy")
+    }
+    .expect("IPTables initialization may not fail!")
+}
+
 impl Debug for IPTablesWrapper {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("IPTablesWrapper")
@@ -336,6 +348,32 @@ where
             .await
     }
 
+    /// Adds the UDP redirect rule to iptables.
+    ///
+    /// Used to redirect datagrams when mirrord incoming feature is set to `steal`.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub(super) async fn add_udp_redirect(
+        &self,
+        redirected_port: Port,
+        target_port: Port,
+    ) -> Result<()> {
+        self.redirect
+            .add_udp_redirect(redirected_port, target_port)
+            .await
+    }
+
+    /// Removes the UDP redirect rule from iptables.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub(super) async fn remove_udp_redirect(
+        &self,
+        redirected_port: Port,
+        target_port: Port,
+    ) -> Result<()> {
+        self.redirect
+            .remove_udp_redirect(redirected_port, target_port)
+            .await
+    }
+
     pub(crate) async fn cleanup(&self) -> Result<()> {
         self.redirect.unmount_entrypoint().await
     }
@@ -122,4 +122,19 @@ where
 
         Ok(())
     }
+
+    /// UDP has no connections to flush, stale conntrack entries are removed by the caller.
+    #[tracing::instrument(level = "trace", skip(self), ret)]
+    async fn add_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        self.inner
+            .add_udp_redirect(redirected_port, target_port)
+            .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), ret)]
+    async fn remove_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        self.inner
+            .remove_udp_redirect(redirected_port, target_port)
+            .await
+    }
 }
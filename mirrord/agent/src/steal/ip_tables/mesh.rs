@@ -125,6 +125,29 @@ where
 
         Ok(())
     }
+    /// Service meshes don't intercept incoming UDP traffic, so the `PREROUTING` redirect is used
+    /// regardless of the vendor.
+    async fn add_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        self.prerouting
+            .add_udp_redirect(redirected_port, target_port)
+            .await?;
+        self.output
+            .add_udp_redirect(redirected_port, target_port)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        self.prerouting
+            .remove_udp_redirect(redirected_port, target_port)
+            .await?;
+        self.output
+            .remove_udp_redirect(redirected_port, target_port)
+            .await?;
+
+        Ok(())
+    }
 }
 
 /// Extends the [`MeshVendor`] type with methods that are only relevant for the agent.
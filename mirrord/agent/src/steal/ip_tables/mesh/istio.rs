@@ -83,4 +83,25 @@ where
 
         Ok(())
     }
+    async fn add_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        self.prerouting
+            .add_udp_redirect(redirected_port, target_port)
+            .await?;
+        self.output
+            .add_udp_redirect(redirected_port, target_port)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        self.prerouting
+            .remove_udp_redirect(redirected_port, target_port)
+            .await?;
+        self.output
+            .remove_udp_redirect(redirected_port, target_port)
+            .await?;
+
+        Ok(())
+    }
 }
@@ -98,4 +98,24 @@ where
 
         Ok(())
     }
+
+    async fn add_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        let redirect_rule = format!(
+            "-o lo -m udp -p udp --dport {redirected_port} -j REDIRECT --to-ports {target_port}"
+        );
+
+        self.managed.add_rule(&redirect_rule)?;
+
+        Ok(())
+    }
+
+    async fn remove_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        let redirect_rule = format!(
+            "-o lo -m udp -p udp --dport {redirected_port} -j REDIRECT --to-ports {target_port}"
+        );
+
+        self.managed.remove_rule(&redirect_rule)?;
+
+        Ok(())
+    }
 }
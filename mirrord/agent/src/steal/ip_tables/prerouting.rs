@@ -71,6 +71,24 @@ where
 
         Ok(())
     }
+
+    async fn add_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        let redirect_rule =
+            format!("-m udp -p udp --dport {redirected_port} -j REDIRECT --to-ports {target_port}");
+
+        self.managed.add_rule(&redirect_rule)?;
+
+        Ok(())
+    }
+
+    async fn remove_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()> {
+        let redirect_rule =
+            format!("-m udp -p udp --dport {redirected_port} -j REDIRECT --to-ports {target_port}");
+
+        self.managed.remove_rule(&redirect_rule)?;
+
+        Ok(())
+    }
 }
 
 impl<IPT> Deref for PreroutingRedirect<IPT>
@@ -183,4 +201,41 @@ mod tests {
 
         assert!(prerouting.remove_redirect(69, 420).await.is_ok());
     }
+
+    #[tokio::test]
+    async fn add_and_remove_udp_redirect() {
+        let mut mock = MockIPTables::new();
+
+        mock.expect_create_chain()
+            .with(eq(IPTABLE_PREROUTING.as_str()))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_PREROUTING.as_str()),
+                eq("-m udp -p udp --dport 53 -j REDIRECT --to-ports 420"),
+                eq(1),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_remove_rule()
+            .with(
+                eq(IPTABLE_PREROUTING.as_str()),
+                eq("-m udp -p udp --dport 53 -j REDIRECT --to-ports 420"),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_chain()
+            .with(eq(IPTABLE_PREROUTING.as_str()))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let prerouting = PreroutingRedirect::create(Arc::new(mock)).expect("Unable to create");
+
+        assert!(prerouting.add_udp_redirect(53, 420).await.is_ok());
+        assert!(prerouting.remove_udp_redirect(53, 420).await.is_ok());
+    }
 }
@@ -15,4 +15,9 @@ pub(crate) trait Redirect {
     async fn add_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()>;
     /// Remove port redirection
     async fn remove_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()>;
+
+    /// Create UDP port redirection
+    async fn add_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()>;
+    /// Remove UDP port redirection
+    async fn remove_udp_redirect(&self, redirected_port: Port, target_port: Port) -> Result<()>;
 }
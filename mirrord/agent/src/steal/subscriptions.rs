@@ -1,20 +1,39 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
-    net::{Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     sync::Arc,
 };
 
 use dashmap::{mapref::entry::Entry as DashMapEntry, DashMap};
 use mirrord_protocol::{Port, RemoteResult, ResponseError};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::{
+    net::{TcpListener, TcpStream, UdpSocket},
+    process::Command,
+    sync::mpsc::Sender,
+    task::JoinHandle,
+};
 
 use super::{
     http::HttpFilter,
-    ip_tables::{new_iptables, IPTablesWrapper, SafeIpTables},
+    ip_tables::{new_ip6tables, new_iptables, IPTablesWrapper, SafeIpTables},
+};
+use crate::{
+    error::{AgentError, Result},
+    util::ClientId,
 };
-use crate::{error::AgentError, util::ClientId};
 
-/// For stealing incoming TCP connections.
+/// A UDP datagram stolen by a [`PortRedirector`].
+#[derive(Debug)]
+pub struct StolenDatagram {
+    /// Stolen port of the target.
+    pub port: Port,
+    /// Address of the peer that sent this datagram.
+    pub peer: SocketAddr,
+    /// Payload of this datagram.
+    pub bytes: Vec<u8>,
+}
+
+/// For stealing incoming TCP connections and UDP datagrams.
 #[async_trait::async_trait]
 pub trait PortRedirector {
     type Error;
@@ -45,27 +64,266 @@ pub trait PortRedirector {
     /// * [`TcpStream`] - redirected connection
     /// * [`SocketAddr`] - peer address
     async fn next_connection(&mut self) -> Result<(TcpStream, SocketAddr), Self::Error>;
+
+    /// Start stealing datagrams from the given UDP port.
+    ///
+    /// # Note
+    ///
+    /// Same as in [`PortRedirector::add_redirection`], implementations are free to do nothing or
+    /// return an [`Err`] when the redirection already exists.
+    async fn add_udp_redirection(&mut self, from: Port) -> Result<(), Self::Error>;
+
+    /// Stop stealing datagrams from the given UDP port.
+    async fn remove_udp_redirection(&mut self, from: Port) -> Result<(), Self::Error>;
+
+    /// Send a datagram to the given peer, from the given redirected UDP port.
+    ///
+    /// The datagram is silently dropped if the port is no longer redirected.
+    async fn send_datagram(
+        &mut self,
+        from: Port,
+        to: SocketAddr,
+        bytes: &[u8],
+    ) -> Result<(), Self::Error>;
 }
 
 /// Implementation of [`PortRedirector`] that manipulates iptables to steal connections by
 /// redirecting TCP packets to inner [`TcpListener`].
+///
+/// UDP datagrams are redirected to a separate [`UdpSocket`] for each stolen port. This way we know
+/// the original destination port, and replies sent from the socket go back to the peer from the
+/// original port (conntrack reverses the redirection).
 pub(crate) struct IpTablesRedirector {
-    /// For altering iptables rules.
-    iptables: Option<SafeIpTables<IPTablesWrapper>>,
     /// Whether exisiting connections should be flushed when adding new redirects.
     flush_connections: bool,
-    /// Port of [`IpTablesRedirector::listener`].
+    /// Redirects IPv4 traffic with iptables.
+    ipv4: FamilyRedirector,
+    /// Redirects IPv6 traffic with ip6tables, present only if IPv6 support was requested.
+    ipv6: Option<FamilyRedirector>,
+    /// Passed to the tasks reading from the sockets in [`FamilyRedirector::udp_redirects`].
+    datagram_tx: Sender<StolenDatagram>,
+}
+
+/// Socket receiving UDP datagrams redirected from a single port.
+struct UdpRedirect {
+    socket: Arc<UdpSocket>,
+    /// Task reading from [`UdpRedirect::socket`], aborted on drop.
+    reader: JoinHandle<()>,
+}
+
+impl UdpRedirect {
+    /// Opens a UDP socket on the unspecified address of the given family and a random port, and
+    /// starts reading from it.
+    async fn new(ipv6: bool, from: Port, datagram_tx: Sender<StolenDatagram>) -> Result<Self> {
+        let address = if ipv6 {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        };
+        let socket = Arc::new(UdpSocket::bind((address, 0)).await?);
+        let reader = tokio::spawn(Self::read_datagrams(from, socket.clone(), datagram_tx));
+
+        Ok(Self { socket, reader })
+    }
+
+    /// Forwards all datagrams received on the `socket` to the `datagram_tx`.
+    async fn read_datagrams(
+        port: Port,
+        socket: Arc<UdpSocket>,
+        datagram_tx: Sender<StolenDatagram>,
+    ) {
+        let mut buffer = vec![0; u16::MAX as usize];
+
+        loop {
+            let (received, peer) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(error) => {
+                    tracing::warn!(%error, port, "Failed to receive a stolen UDP datagram");
+                    break;
+                }
+            };
+
+            let datagram = StolenDatagram {
+                port,
+                peer,
+                bytes: buffer.get(..received).unwrap_or_default().to_vec(),
+            };
+
+            if datagram_tx.send(datagram).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for UdpRedirect {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Removes conntrack entries of the UDP flows to the given port.
+///
+/// Unlike TCP connections, UDP flows are usually long-lived (e.g. a DNS client reuses its port),
+/// and the nat table is consulted only for the first datagram of a flow. Without this, datagrams
+/// from existing flows would ignore a newly added redirection, or keep hitting a removed one.
+async fn flush_udp_conntrack(port: Port, ipv6: bool) {
+    let family = if ipv6 { "ipv6" } else { "ipv4" };
+
+    let result = Command::new("conntrack")
+        .args([
+            "-D",
+            "-f",
+            family,
+            "-p",
+            "udp",
+            "--dport",
+            &port.to_string(),
+        ])
+        .output()
+        .await;
+
+    match result {
+        // `conntrack` fails when there are no entries to delete.
+        Ok(output) if !output.status.success() => {
+            tracing::debug!(?output, port, "`conntrack` did not delete any UDP entries");
+        }
+        Ok(..) => {}
+        Err(error) => {
+            tracing::warn!(%error, port, "Failed to run `conntrack` to flush UDP entries");
+        }
+    }
+}
+
+/// Part of the [`IpTablesRedirector`] that handles a single IP family.
+struct FamilyRedirector {
+    /// For altering iptables (or ip6tables) rules.
+    iptables: Option<SafeIpTables<IPTablesWrapper>>,
+    /// Whether this redirector uses ip6tables.
+    ipv6: bool,
+    /// Port of [`FamilyRedirector::listener`].
     redirect_to: Port,
     /// Listener to which redirect all connections.
     listener: TcpListener,
-
+    /// Comma separated IPs of the target pod, from this redirector's family.
     pod_ips: Option<String>,
+    /// Sockets to which datagrams from the stolen UDP ports are redirected.
+    udp_redirects: HashMap<Port, UdpRedirect>,
+}
+
+impl FamilyRedirector {
+    /// Opens a TCP listener on the unspecified address of the given family and a random port.
+    async fn new(ipv6: bool, pod_ips: Option<String>) -> Result<Self, AgentError> {
+        let address = if ipv6 {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        };
+        let listener = TcpListener::bind((address, 0)).await?;
+        let redirect_to = listener.local_addr()?.port();
+
+        Ok(Self {
+            iptables: None,
+            ipv6,
+            redirect_to,
+            listener,
+            pod_ips,
+            udp_redirects: Default::default(),
+        })
+    }
+
+    /// Returns the [`SafeIpTables`] of this redirector, creating them if needed.
+    async fn iptables(
+        &mut self,
+        flush_connections: bool,
+    ) -> Result<&SafeIpTables<IPTablesWrapper>> {
+        let iptables = match &mut self.iptables {
+            Some(iptables) => iptables,
+            slot @ None => {
+                let iptables = if self.ipv6 {
+                    new_ip6tables()
+                } else {
+                    new_iptables()
+                };
+                let safe = SafeIpTables::create(
+                    iptables.into(),
+                    flush_connections,
+                    self.pod_ips.as_deref(),
+                )
+                .await?;
+                slot.insert(safe)
+            }
+        };
+
+        Ok(iptables)
+    }
+
+    async fn add_redirection(&mut self, from: Port, flush_connections: bool) -> Result<()> {
+        let redirect_to = self.redirect_to;
+        self.iptables(flush_connections)
+            .await?
+            .add_redirect(from, redirect_to)
+            .await
+    }
+
+    async fn add_udp_redirection(
+        &mut self,
+        from: Port,
+        flush_connections: bool,
+        datagram_tx: Sender<StolenDatagram>,
+    ) -> Result<()> {
+        let redirect = UdpRedirect::new(self.ipv6, from, datagram_tx).await?;
+        let redirect_to = redirect.socket.local_addr()?.port();
+
+        self.iptables(flush_connections)
+            .await?
+            .add_udp_redirect(from, redirect_to)
+            .await?;
+        self.udp_redirects.insert(from, redirect);
+
+        flush_udp_conntrack(from, self.ipv6).await;
+
+        Ok(())
+    }
+
+    async fn remove_udp_redirection(&mut self, from: Port) -> Result<()> {
+        let Some(redirect) = self.udp_redirects.remove(&from) else {
+            return Ok(());
+        };
+
+        if let Some(iptables) = self.iptables.as_ref() {
+            let redirect_to = redirect.socket.local_addr()?.port();
+            iptables.remove_udp_redirect(from, redirect_to).await?;
+            flush_udp_conntrack(from, self.ipv6).await;
+        }
+
+        Ok(())
+    }
+
+    async fn remove_redirection(&mut self, from: Port) -> Result<()> {
+        if let Some(iptables) = self.iptables.as_ref() {
+            iptables.remove_redirect(from, self.redirect_to).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        self.udp_redirects.clear();
+
+        if let Some(iptables) = self.iptables.take() {
+            iptables.cleanup().await?;
+        }
+
+        Ok(())
+    }
 }
 
 impl IpTablesRedirector {
     /// Create a new instance of this struct. Open an IPv4 TCP listener on an
     /// [`Ipv4Addr::UNSPECIFIED`] address and a random port. This listener will be used to accept
-    /// redirected connections.
+    /// redirected connections. If `support_ipv6` is set, does the same for IPv6 on an
+    /// [`Ipv6Addr::UNSPECIFIED`] address.
     ///
     /// # Note
     ///
@@ -75,63 +333,150 @@ impl IpTablesRedirector {
     ///
     /// * `flush_connections` - whether exisitng connections should be flushed when adding new
     ///   redirects
+    /// * `pod_ips` - comma separated IPs of the target pod, from both families
+    /// * `support_ipv6` - whether IPv6 connections should be redirected with ip6tables
+    /// * `datagram_tx` - where datagrams stolen from all redirected UDP ports are sent
     pub(crate) async fn new(
         flush_connections: bool,
         pod_ips: Option<String>,
+        support_ipv6: bool,
+        datagram_tx: Sender<StolenDatagram>,
     ) -> Result<Self, AgentError> {
-        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
-        let redirect_to = listener.local_addr()?.port();
+        let (pod_ips4, pod_ips6) = split_pod_ips(pod_ips.as_deref());
+
+        let ipv4 = FamilyRedirector::new(false, pod_ips4).await?;
+        let ipv6 = if support_ipv6 {
+            Some(FamilyRedirector::new(true, pod_ips6).await?)
+        } else {
+            None
+        };
 
         Ok(Self {
-            iptables: None,
             flush_connections,
-            redirect_to,
-            listener,
-            pod_ips,
+            ipv4,
+            ipv6,
+            datagram_tx,
         })
     }
 }
 
+/// Splits comma separated `pod_ips` into IPv4 and IPv6 lists, as iptables and ip6tables don't
+/// accept addresses from the other family.
+fn split_pod_ips(pod_ips: Option<&str>) -> (Option<String>, Option<String>) {
+    let (ipv4, ipv6): (Vec<_>, Vec<_>) = pod_ips
+        .into_iter()
+        .flat_map(|pod_ips| pod_ips.split(','))
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .partition(|ip| !ip.contains(':'));
+
+    let join = |ips: Vec<&str>| (!ips.is_empty()).then(|| ips.join(","));
+
+    (join(ipv4), join(ipv6))
+}
+
 #[async_trait::async_trait]
 impl PortRedirector for IpTablesRedirector {
     type Error = AgentError;
 
     async fn add_redirection(&mut self, from: Port) -> Result<(), Self::Error> {
-        let iptables = match self.iptables.as_ref() {
-            Some(iptables) => iptables,
-            None => {
-                let iptables = new_iptables();
-                let safe = SafeIpTables::create(
-                    iptables.into(),
-                    self.flush_connections,
-                    self.pod_ips.as_deref(),
-                )
-                .await?;
-                self.iptables.insert(safe)
-            }
-        };
+        self.ipv4
+            .add_redirection(from, self.flush_connections)
+            .await?;
+
+        if let Some(ipv6) = self.ipv6.as_mut() {
+            ipv6.add_redirection(from, self.flush_connections).await?;
+        }
 
-        iptables.add_redirect(from, self.redirect_to).await
+        Ok(())
     }
 
     async fn remove_redirection(&mut self, from: Port) -> Result<(), Self::Error> {
-        if let Some(iptables) = self.iptables.as_ref() {
-            iptables.remove_redirect(from, self.redirect_to).await?;
+        self.ipv4.remove_redirection(from).await?;
+
+        if let Some(ipv6) = self.ipv6.as_mut() {
+            ipv6.remove_redirection(from).await?;
         }
 
         Ok(())
     }
 
     async fn cleanup(&mut self) -> Result<(), Self::Error> {
-        if let Some(iptables) = self.iptables.take() {
-            iptables.cleanup().await?;
+        self.ipv4.cleanup().await?;
+
+        if let Some(ipv6) = self.ipv6.as_mut() {
+            ipv6.cleanup().await?;
         }
 
         Ok(())
     }
 
     async fn next_connection(&mut self) -> Result<(TcpStream, SocketAddr), Self::Error> {
-        self.listener.accept().await.map_err(Into::into)
+        match self.ipv6.as_ref() {
+            Some(ipv6) => tokio::select! {
+                accepted = self.ipv4.listener.accept() => accepted,
+                accepted = ipv6.listener.accept() => accepted,
+            },
+            None => self.ipv4.listener.accept().await,
+        }
+        .map_err(Into::into)
+    }
+
+    async fn add_udp_redirection(&mut self, from: Port) -> Result<(), Self::Error> {
+        self.ipv4
+            .add_udp_redirection(from, self.flush_connections, self.datagram_tx.clone())
+            .await?;
+
+        if let Some(ipv6) = self.ipv6.as_mut()
+            && let Err(error) = ipv6
+                .add_udp_redirection(from, self.flush_connections, self.datagram_tx.clone())
+                .await
+        {
+            // Don't leave the port stolen only from IPv4 peers.
+            if let Err(rollback_error) = self.ipv4.remove_udp_redirection(from).await {
+                tracing::warn!(
+                    from,
+                    %rollback_error,
+                    "Failed to remove the IPv4 UDP redirection after the IPv6 one failed"
+                );
+            }
+
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    async fn remove_udp_redirection(&mut self, from: Port) -> Result<(), Self::Error> {
+        self.ipv4.remove_udp_redirection(from).await?;
+
+        if let Some(ipv6) = self.ipv6.as_mut() {
+            ipv6.remove_udp_redirection(from).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_datagram(
+        &mut self,
+        from: Port,
+        to: SocketAddr,
+        bytes: &[u8],
+    ) -> Result<(), Self::Error> {
+        let family = if to.is_ipv6() {
+            self.ipv6.as_ref()
+        } else {
+            Some(&self.ipv4)
+        };
+
+        let Some(redirect) = family.and_then(|family| family.udp_redirects.get(&from)) else {
+            tracing::trace!(from, %to, "UDP port is no longer redirected, dropping the datagram");
+            return Ok(());
+        };
+
+        redirect.socket.send_to(bytes, to).await?;
+
+        Ok(())
     }
 }
 
@@ -141,6 +486,8 @@ pub struct PortSubscriptions<R: PortRedirector> {
     redirector: R,
     /// Maps ports to active subscriptions.
     subscriptions: HashMap<Port, PortSubscription>,
+    /// Maps UDP ports to the clients that steal them.
+    udp_subscriptions: HashMap<Port, ClientId>,
 }
 
 impl<R: PortRedirector> PortSubscriptions<R> {
@@ -155,6 +502,7 @@ impl<R: PortRedirector> PortSubscriptions<R> {
         Self {
             redirector,
             subscriptions: HashMap::with_capacity(initial_capacity),
+            udp_subscriptions: Default::default(),
         }
     }
 
@@ -246,7 +594,7 @@ impl<R: PortRedirector> PortSubscriptions<R> {
         if remove_redirect {
             self.redirector.remove_redirection(port).await?;
 
-            if self.subscriptions.is_empty() {
+            if self.is_empty() {
                 self.redirector.cleanup().await?;
             }
         }
@@ -254,6 +602,60 @@ impl<R: PortRedirector> PortSubscriptions<R> {
         Ok(())
     }
 
+    /// Try adding a new UDP subscription to this set.
+    ///
+    /// # Subscription clash rules
+    ///
+    /// A single UDP port may have only one subscription, as there are no filters for UDP.
+    ///
+    /// # Warning
+    ///
+    /// Same as in [`PortSubscriptions::add`].
+    pub async fn add_udp(
+        &mut self,
+        client_id: ClientId,
+        port: Port,
+    ) -> Result<RemoteResult<Port>, R::Error> {
+        match self.udp_subscriptions.entry(port) {
+            Entry::Occupied(..) => Ok(Err(ResponseError::PortAlreadyStolen(port))),
+            Entry::Vacant(e) => {
+                e.insert(client_id);
+                self.redirector.add_udp_redirection(port).await?;
+
+                Ok(Ok(port))
+            }
+        }
+    }
+
+    /// Remove a UDP subscription from this set, if it exists.
+    ///
+    /// # Warning
+    ///
+    /// Same as in [`PortSubscriptions::remove`].
+    pub async fn remove_udp(&mut self, client_id: ClientId, port: Port) -> Result<(), R::Error> {
+        let Entry::Occupied(e) = self.udp_subscriptions.entry(port) else {
+            return Ok(());
+        };
+
+        if *e.get() != client_id {
+            return Ok(());
+        }
+
+        e.remove();
+        self.redirector.remove_udp_redirection(port).await?;
+
+        if self.is_empty() {
+            self.redirector.cleanup().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether there are no TCP or UDP subscriptions in this set.
+    fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty() && self.udp_subscriptions.is_empty()
+    }
+
     /// Remove all client subscriptions from this set.
     ///
     /// # Params
@@ -276,6 +678,18 @@ impl<R: PortRedirector> PortSubscriptions<R> {
             self.remove(client_id, port).await?;
         }
 
+        let udp_ports = self
+            .udp_subscriptions
+            .iter()
+            .filter_map(|(port, subscribed_client)| {
+                (*subscribed_client == client_id).then_some(*port)
+            })
+            .collect::<Vec<_>>();
+
+        for port in udp_ports {
+            self.remove_udp(client_id, port).await?;
+        }
+
         Ok(())
     }
 
@@ -288,6 +702,21 @@ impl<R: PortRedirector> PortSubscriptions<R> {
     pub async fn next_connection(&mut self) -> Result<(TcpStream, SocketAddr), R::Error> {
         self.redirector.next_connection().await
     }
+
+    /// Return the client that steals the given UDP `port`.
+    pub fn get_udp(&self, port: Port) -> Option<ClientId> {
+        self.udp_subscriptions.get(&port).copied()
+    }
+
+    /// Call [`PortRedirector::send_datagram`] on the inner [`PortRedirector`].
+    pub async fn send_datagram(
+        &mut self,
+        from: Port,
+        to: SocketAddr,
+        bytes: &[u8],
+    ) -> Result<(), R::Error> {
+        self.redirector.send_datagram(from, to, bytes).await
+    }
 }
 
 /// Steal subscription for a port.
@@ -351,6 +780,7 @@ mod test {
     #[derive(Default)]
     struct DummyRedirector {
         redirections: HashSet<Port>,
+        udp_redirections: HashSet<Port>,
         dirty: bool,
     }
 
@@ -401,6 +831,7 @@ mod test {
 
         async fn cleanup(&mut self) -> Result<(), Self::Error> {
             self.redirections.clear();
+            self.udp_redirections.clear();
             self.dirty = false;
 
             Ok(())
@@ -409,6 +840,32 @@ mod test {
         async fn next_connection(&mut self) -> Result<(TcpStream, SocketAddr), Self::Error> {
             unimplemented!()
         }
+
+        async fn add_udp_redirection(&mut self, from: Port) -> Result<(), Self::Error> {
+            if self.udp_redirections.insert(from) {
+                self.dirty = true;
+                Ok(())
+            } else {
+                Err(from)
+            }
+        }
+
+        async fn remove_udp_redirection(&mut self, from: Port) -> Result<(), Self::Error> {
+            if self.udp_redirections.remove(&from) {
+                Ok(())
+            } else {
+                Err(from)
+            }
+        }
+
+        async fn send_datagram(
+            &mut self,
+            _from: Port,
+            _to: SocketAddr,
+            _bytes: &[u8],
+        ) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
     }
 
     fn dummy_filter() -> HttpFilter {
@@ -644,4 +1101,56 @@ mod test {
         let sub = subscriptions.get(81);
         assert!(sub.is_none(), "{sub:?}");
     }
+
+    #[tokio::test]
+    async fn udp_subscriptions() {
+        let redirector = DummyRedirector::default();
+        let mut subscriptions = PortSubscriptions::new(redirector, 8);
+
+        // TCP and UDP subscriptions of the same port don't clash.
+        subscriptions.add(0, 53, None).await.unwrap().unwrap();
+        subscriptions.add_udp(0, 53).await.unwrap().unwrap();
+        check_redirector!(subscriptions.redirector, 53);
+        assert_eq!(subscriptions.get_udp(53), Some(0));
+
+        // Another client cannot steal the same UDP port.
+        assert_eq!(
+            subscriptions.add_udp(1, 53).await.unwrap(),
+            Err(ResponseError::PortAlreadyStolen(53)),
+        );
+
+        // Another client cannot remove a UDP subscription it does not own.
+        subscriptions.remove_udp(1, 53).await.unwrap();
+        assert_eq!(subscriptions.get_udp(53), Some(0));
+
+        // Redirections are cleaned up only after the last subscription is gone.
+        subscriptions.remove(0, 53).await.unwrap();
+        assert!(subscriptions.redirector.dirty);
+        assert_eq!(
+            subscriptions.redirector.udp_redirections,
+            HashSet::from([53])
+        );
+
+        subscriptions.add_udp(0, 514).await.unwrap().unwrap();
+        subscriptions.remove_all(0).await.unwrap();
+        assert!(subscriptions.redirector.udp_redirections.is_empty());
+        assert!(!subscriptions.redirector.dirty);
+        assert_eq!(subscriptions.get_udp(53), None);
+    }
+
+    #[test]
+    fn split_pod_ips_by_family() {
+        assert_eq!(split_pod_ips(None), (None, None));
+        assert_eq!(
+            split_pod_ips(Some("10.0.0.1")),
+            (Some("10.0.0.1".to_string()), None)
+        );
+        assert_eq!(
+            split_pod_ips(Some("10.0.0.1,fd00::1,10.0.0.2")),
+            (
+                Some("10.0.0.1,10.0.0.2".to_string()),
+                Some("fd00::1".to_string())
+            )
+        );
+    }
 }
@@ -0,0 +1,431 @@
+//! TLS termination of stolen connections, which allows for applying
+//! [`HttpFilter`](super::http::HttpFilter)s to HTTPS traffic. See [`StealTlsHandler`].
+
+use std::{
+    collections::HashSet,
+    fmt, io,
+    io::BufReader,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use mirrord_protocol::Port;
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+use tokio_util::either::Either;
+
+/// TLS configuration of the stealer, read from the `MIRRORD_AGENT_STEAL_TLS_*` environment
+/// variables (set from `feature.network.incoming.tls`).
+#[derive(Deserialize, Debug, Default)]
+pub(super) struct StealTlsConfig {
+    /// Ports on which the TLS connections should be terminated.
+    #[serde(default)]
+    ports: Vec<Port>,
+    /// PEM-encoded certificate chain, from a Kubernetes Secret.
+    certificate_pem: Option<String>,
+    /// PEM-encoded private key, from a Kubernetes Secret.
+    key_pem: Option<String>,
+    /// Path to the PEM-encoded certificate chain in the target container.
+    certificate_path: Option<PathBuf>,
+    /// Path to the PEM-encoded private key in the target container.
+    key_path: Option<PathBuf>,
+}
+
+impl StealTlsConfig {
+    pub(super) fn from_env() -> Result<Self, StealTlsError> {
+        envy::prefixed("MIRRORD_AGENT_STEAL_TLS_")
+            .from_env()
+            .map_err(StealTlsError::Env)
+    }
+}
+
+/// Errors that can occur when preparing the [`StealTlsHandler`].
+#[derive(Debug, Error)]
+pub(crate) enum StealTlsError {
+    #[error("invalid TLS configuration in the environment: {0}")]
+    Env(#[source] envy::Error),
+
+    #[error("TLS ports were given without a certificate and a private key")]
+    MissingCertificate,
+
+    #[error("failed to read `{}`: {error}", .path.display())]
+    ReadFile {
+        path: PathBuf,
+        #[source]
+        error: io::Error,
+    },
+
+    #[error("failed to parse PEM data: {0}")]
+    Pem(#[source] io::Error),
+
+    #[error("no certificate found in the PEM data")]
+    NoCertificate,
+
+    #[error("no private key found in the PEM data")]
+    NoPrivateKey,
+
+    #[error("rustls failed: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// Terminates TLS on the stolen connections to the configured ports, so that the stealer can
+/// inspect the HTTP traffic.
+///
+/// Requests that are let through are encrypted again, see [`OriginalDestination`].
+#[derive(Clone)]
+pub(crate) struct StealTlsHandler {
+    ports: Arc<HashSet<Port>>,
+    acceptor: TlsAcceptor,
+    /// Base for the [`TlsConnector`]s that make the connections with the original destinations.
+    /// ALPN protocols are set per connection, see [`Self::accept`].
+    client_config: Arc<ClientConfig>,
+}
+
+impl StealTlsHandler {
+    /// First byte of a TLS record that contains a handshake message.
+    const HANDSHAKE_RECORD_TYPE: u8 = 0x16;
+
+    /// Creates a new handler from the given config, or returns [`None`] if there are no TLS ports.
+    ///
+    /// The certificate and key paths are resolved in the root of the target container, given as
+    /// `pid`.
+    pub(super) fn new(
+        config: StealTlsConfig,
+        pid: Option<u64>,
+    ) -> Result<Option<Self>, StealTlsError> {
+        if config.ports.is_empty() {
+            return Ok(None);
+        }
+
+        let root = match pid {
+            Some(pid) => PathBuf::from("/proc").join(pid.to_string()).join("root"),
+            None => PathBuf::from("/"),
+        };
+
+        let (certificate_pem, key_pem) = match config {
+            StealTlsConfig {
+                certificate_pem: Some(certificate),
+                key_pem: Some(key),
+                ..
+            } => (certificate.into_bytes(), key.into_bytes()),
+            StealTlsConfig {
+                certificate_path: Some(certificate),
+                key_path: Some(key),
+                ..
+            } => (
+                read_in_root(&root, &certificate)?,
+                read_in_root(&root, &key)?,
+            ),
+            _ => return Err(StealTlsError::MissingCertificate),
+        };
+
+        let certificates = rustls_pemfile::certs(&mut BufReader::new(certificate_pem.as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(StealTlsError::Pem)?;
+        if certificates.is_empty() {
+            return Err(StealTlsError::NoCertificate);
+        }
+        let key = rustls_pemfile::private_key(&mut BufReader::new(key_pem.as_slice()))
+            .map_err(StealTlsError::Pem)?
+            .ok_or(StealTlsError::NoPrivateKey)?;
+
+        // Same provider as the one installed in the agent's entrypoint.
+        let provider = Arc::new(crypto::aws_lc_rs::default_provider());
+
+        let mut server_config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certificates, key)?;
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let client_config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyServerCertificate(provider)))
+            .with_no_client_auth();
+
+        Ok(Some(Self {
+            ports: Arc::new(config.ports.into_iter().collect()),
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            client_config: Arc::new(client_config),
+        }))
+    }
+
+    /// Whether TLS should be terminated on connections to the given port.
+    pub(crate) fn handles(&self, port: Port) -> bool {
+        self.ports.contains(&port)
+    }
+
+    /// Whether the given first bytes of a connection start a TLS handshake.
+    pub(crate) fn is_handshake(header: &[u8]) -> bool {
+        header.first() == Some(&Self::HANDSHAKE_RECORD_TYPE)
+    }
+
+    /// Accepts a TLS connection from the stolen `stream`.
+    ///
+    /// Returns the decrypted stream and the [`OriginalDestination`] that will be reached with the
+    /// same server name and ALPN protocol as the ones requested by the client.
+    pub(crate) async fn accept<IO>(
+        &self,
+        stream: IO,
+        destination: SocketAddr,
+    ) -> io::Result<(server::TlsStream<IO>, OriginalDestination)>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let stream = self.acceptor.accept(stream).await?;
+        let (_, connection) = stream.get_ref();
+
+        let server_name = connection
+            .server_name()
+            .and_then(|name| ServerName::try_from(name.to_string()).ok())
+            .unwrap_or_else(|| ServerName::IpAddress(destination.ip().into()));
+
+        let mut client_config = ClientConfig::clone(&self.client_config);
+        client_config.alpn_protocols = connection
+            .alpn_protocol()
+            .map(|protocol| vec![protocol.to_vec()])
+            .unwrap_or_default();
+
+        tracing::trace!(
+            ?server_name,
+            alpn_protocols = ?client_config.alpn_protocols,
+            "Accepted a stolen TLS connection",
+        );
+
+        let destination = OriginalDestination {
+            address: destination,
+            tls: Some((TlsConnector::from(Arc::new(client_config)), server_name)),
+        };
+
+        Ok((stream, destination))
+    }
+}
+
+fn read_in_root(root: &Path, path: &Path) -> Result<Vec<u8>, StealTlsError> {
+    let full_path = root.join(path.strip_prefix("/").unwrap_or(path));
+    std::fs::read(&full_path).map_err(|error| StealTlsError::ReadFile {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// IO stream with the original destination of a stolen connection.
+pub(crate) type OriginalDestinationStream = Either<TcpStream, client::TlsStream<TcpStream>>;
+
+/// Original destination of a stolen connection.
+///
+/// If the agent terminated TLS on the stolen connection, the connection with the original
+/// destination is secured with TLS as well.
+#[derive(Clone)]
+pub(crate) struct OriginalDestination {
+    address: SocketAddr,
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+}
+
+impl OriginalDestination {
+    /// Destination reached with plain TCP.
+    pub(crate) fn new(address: SocketAddr) -> Self {
+        Self { address, tls: None }
+    }
+
+    pub(crate) fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Makes a new connection with this destination.
+    pub(crate) async fn connect(&self) -> io::Result<OriginalDestinationStream> {
+        let stream = TcpStream::connect(self.address).await?;
+
+        match &self.tls {
+            Some((connector, server_name)) => connector
+                .connect(server_name.clone(), stream)
+                .await
+                .map(Either::Right),
+            None => Ok(Either::Left(stream)),
+        }
+    }
+}
+
+impl fmt::Display for OriginalDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tls {
+            Some(..) => write!(f, "{} (TLS)", self.address),
+            None => self.address.fmt(f),
+        }
+    }
+}
+
+impl fmt::Debug for OriginalDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OriginalDestination")
+            .field("address", &self.address)
+            .field("server_name", &self.tls.as_ref().map(|(_, name)| name))
+            .finish()
+    }
+}
+
+/// Accepts any certificate presented by the original destination.
+///
+/// The original destination is the server running in the target container, which usually has a
+/// certificate issued for its public name, not for the address the agent uses.
+#[derive(Debug)]
+struct AnyServerCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyServerCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::rustls::{pki_types::PrivateKeyDer, RootCertStore};
+
+    use super::*;
+
+    /// Verifies that the [`StealTlsHandler`] terminates TLS from the client and encrypts the
+    /// traffic again towards the [`OriginalDestination`], keeping the SNI and the ALPN protocol.
+    #[tokio::test]
+    async fn terminate_and_encrypt_again() {
+        let cert = rcgen::generate_simple_self_signed(vec!["service.local".to_string()]).unwrap();
+
+        let handler = StealTlsHandler::new(
+            StealTlsConfig {
+                ports: vec![443],
+                certificate_pem: Some(cert.cert.pem()),
+                key_pem: Some(cert.key_pair.serialize_pem()),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(handler.handles(443));
+        assert!(!handler.handles(80));
+
+        let provider = Arc::new(crypto::aws_lc_rs::default_provider());
+
+        let mut server_config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.cert.der().clone()],
+                PrivateKeyDer::Pkcs8(cert.key_pair.serialize_der().into()),
+            )
+            .unwrap();
+        server_config.alpn_protocols = vec![b"h2".to_vec()];
+        let server_acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let server_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_address = server_listener.local_addr().unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.cert.der().clone()).unwrap();
+        let mut client_config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec()];
+        let client_connector = TlsConnector::from(Arc::new(client_config));
+        let stealer_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stealer_address = stealer_listener.local_addr().unwrap();
+
+        tokio::join!(
+            async {
+                let stream = TcpStream::connect(stealer_address).await.unwrap();
+                let mut stream = client_connector
+                    .connect(ServerName::try_from("service.local").unwrap(), stream)
+                    .await
+                    .unwrap();
+                stream.write_all(b"ping").await.unwrap();
+                let mut buf = [0; 4];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"pong");
+            },
+            async {
+                let (stream, _) = stealer_listener.accept().await.unwrap();
+                let (mut stream, destination) =
+                    handler.accept(stream, server_address).await.unwrap();
+                let mut buf = [0; 4];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ping");
+
+                let mut outgoing = destination.connect().await.unwrap();
+                outgoing.write_all(b"ping").await.unwrap();
+                outgoing.read_exact(&mut buf).await.unwrap();
+                stream.write_all(&buf).await.unwrap();
+                stream.flush().await.unwrap();
+            },
+            async {
+                let (stream, _) = server_listener.accept().await.unwrap();
+                let mut stream = server_acceptor.accept(stream).await.unwrap();
+                let (_, connection) = stream.get_ref();
+                assert_eq!(connection.server_name(), Some("service.local"));
+                assert_eq!(connection.alpn_protocol(), Some(b"h2".as_slice()));
+
+                let mut buf = [0; 4];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ping");
+                stream.write_all(b"pong").await.unwrap();
+                stream.flush().await.unwrap();
+            },
+        );
+    }
+}
@@ -162,27 +162,31 @@ pub(crate) fn enter_namespace(pid: Option<u64>, namespace: &str) -> Result<(), A
 }
 
 /// [`Future`] that resolves to [`ClientId`] when the client drops their [`mpsc::Receiver`].
-pub(crate) struct ChannelClosedFuture<T> {
-    tx: mpsc::Sender<T>,
+///
+/// Keeps the same [`mpsc::Sender::closed`] future between polls, so that the registered waker is
+/// not lost.
+pub(crate) struct ChannelClosedFuture {
+    closed: Pin<Box<dyn Future<Output = ()> + Send>>,
     client_id: ClientId,
 }
 
-impl<T> ChannelClosedFuture<T> {
-    pub(crate) fn new(tx: mpsc::Sender<T>, client_id: ClientId) -> Self {
-        Self { tx, client_id }
+impl ChannelClosedFuture {
+    pub(crate) fn new<T: 'static + Send>(tx: mpsc::Sender<T>, client_id: ClientId) -> Self {
+        Self {
+            closed: Box::pin(async move { tx.closed().await }),
+            client_id,
+        }
     }
 }
 
-impl<T> Future for ChannelClosedFuture<T> {
+impl Future for ChannelClosedFuture {
     type Output = ClientId;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let client_id = self.client_id;
-
-        let future = std::pin::pin!(self.get_mut().tx.closed());
-        std::task::ready!(future.poll(cx));
+        let this = self.get_mut();
+        std::task::ready!(this.closed.as_mut().poll(cx));
 
-        Poll::Ready(client_id)
+        Poll::Ready(this.client_id)
     }
 }
 
@@ -11,11 +11,12 @@ use std::{
 use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum, ValueHint};
 use clap_complete::Shell;
 use mirrord_config::{
+    config::template::ConfigVars,
     feature::env::{
         MIRRORD_OVERRIDE_ENV_FILE_ENV, MIRRORD_OVERRIDE_ENV_VARS_EXCLUDE_ENV,
         MIRRORD_OVERRIDE_ENV_VARS_INCLUDE_ENV,
     },
-    MIRRORD_CONFIG_FILE_ENV,
+    MIRRORD_CONFIG_FILE_ENV, MIRRORD_CONFIG_VARS_ENV, MIRRORD_PROFILE_ENV,
 };
 use mirrord_operator::setup::OperatorNamespace;
 use thiserror::Error;
@@ -98,6 +99,17 @@ pub(super) enum Commands {
     /// Run mirrord vpn
     #[command(hide = true)]
     Vpn(Box<VpnArgs>),
+
+    /// Send HTTP requests recorded with `feature.network.incoming.http_record` to a local
+    /// process.
+    Replay(Box<ReplayArgs>),
+
+    /// Show live events of a running mirrord session, e.g. stolen connections and remote files
+    /// opened by the application.
+    Inspect(Box<InspectArgs>),
+
+    /// Config commands, e.g. explain
+    Config(Box<ConfigArgs>),
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -207,6 +219,14 @@ pub(super) struct ExecParams {
     #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
     pub config_file: Option<PathBuf>,
 
+    /// Apply one of the profiles defined in the config file
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Set a variable for the config file template, e.g. `--config-var user=alice`
+    #[arg(long = "config-var", value_parser = config_var)]
+    pub config_vars: Vec<(String, String)>,
+
     /// Kube context to use from Kubeconfig
     #[arg(long)]
     pub context: Option<String>,
@@ -326,6 +346,15 @@ impl ExecParams {
             );
         }
 
+        if let Some(profile) = &self.profile {
+            envs.insert(MIRRORD_PROFILE_ENV.into(), profile.into());
+        }
+
+        envs.insert(
+            MIRRORD_CONFIG_VARS_ENV.into(),
+            config_vars_env(self.context.as_deref(), &self.config_vars)?.into(),
+        );
+
         if let Some(env_file) = &self.env_file {
             // Set canonicalized path to env file, in case forks/children are in different
             // working directories.
@@ -429,6 +458,14 @@ pub(super) struct PortForwardArgs {
     #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
     pub config_file: Option<PathBuf>,
 
+    /// Apply one of the profiles defined in the config file
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Set a variable for the config file template, e.g. `--config-var user=alice`
+    #[arg(long = "config-var", value_parser = config_var)]
+    pub config_vars: Vec<(String, String)>,
+
     /// Kube context to use from Kubeconfig
     #[arg(long)]
     pub context: Option<String>,
@@ -685,6 +722,25 @@ impl core::fmt::Display for SessionCommand {
     }
 }
 
+/// Parses a `name=value` config template variable.
+fn config_var(raw: &str) -> Result<(String, String), String> {
+    raw.split_once('=')
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected `name=value`, got `{raw}`"))
+}
+
+/// Resolves the config template variables and encodes them for [`MIRRORD_CONFIG_VARS_ENV`].
+///
+/// The built-in variables are resolved only here, the processes started by the CLI (including the
+/// layer) take them from the env. `kube_context` is the context selected with `--context`.
+pub(super) fn config_vars_env(
+    kube_context: Option<&str>,
+    vars: &[(String, String)],
+) -> Result<String, CliError> {
+    let vars = ConfigVars::resolve(kube_context, vars.iter().cloned());
+    Ok(serde_json::to_string(&vars)?)
+}
+
 /// Parses the operator session id from hex (without `0x` prefix) into `u64`.
 fn hex_id(raw: &str) -> Result<u64, String> {
     u64::from_str_radix(raw, 16)
@@ -738,6 +794,10 @@ pub(super) struct VerifyConfigArgs {
     #[arg(long)]
     pub(super) ide: bool,
 
+    /// Apply one of the profiles defined in the config file.
+    #[arg(long)]
+    pub(super) profile: Option<String>,
+
     /// Config file path.
     pub(super) path: PathBuf,
 }
@@ -762,6 +822,28 @@ pub(super) enum DiagnoseCommand {
         #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
         config_file: Option<PathBuf>,
     },
+
+    /// Check that the cluster is reachable, that the user has the RBAC permissions needed to run
+    /// the mirrord agent and that the agent image can be pulled.
+    Cluster(DiagnoseCheckArgs),
+
+    /// Check that the configured target can be resolved and accessed, and detect its container
+    /// runtime, service mesh and the iptables backend used on its node.
+    Target(DiagnoseCheckArgs),
+
+    /// Check that the mirrord layer can be extracted and injected into a trivial binary.
+    Layer(DiagnoseCheckArgs),
+}
+
+#[derive(Args, Debug)]
+pub(super) struct DiagnoseCheckArgs {
+    /// Specify config file to use
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
+    pub config_file: Option<PathBuf>,
+
+    /// Print the report as JSON.
+    #[arg(long)]
+    pub json: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum, serde::Serialize)]
@@ -878,6 +960,79 @@ pub(super) struct VpnArgs {
     pub resolver_path: PathBuf,
 }
 
+#[derive(Args, Debug)]
+pub(super) struct ReplayArgs {
+    /// Path to the file with the recorded requests.
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub file: PathBuf,
+
+    /// Address of the local process, e.g. `127.0.0.1:8080`.
+    #[arg(short, long)]
+    pub address: SocketAddr,
+
+    /// Replay only the requests that were sent to this remote port.
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Args, Debug)]
+pub(super) struct InspectArgs {
+    /// Path to the inspector socket of the session. Defaults to the most recently started
+    /// session.
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    pub socket: Option<PathBuf>,
+
+    /// Print the events as JSON lines.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub(super) struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+/// Commands for inspecting the mirrord config.
+#[derive(Subcommand, Debug)]
+pub(super) enum ConfigCommand {
+    /// Print the resolved config with the source of each value (default, config file or env
+    /// var), and explain which config rules apply to a file or an address.
+    Explain(ExplainConfigArgs),
+}
+
+#[derive(Args, Debug)]
+pub(super) struct ExplainConfigArgs {
+    /// Specify config file to use
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
+    pub config_file: Option<PathBuf>,
+
+    /// Apply one of the profiles defined in the config file.
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Set a variable for the config file template, e.g. `--config-var user=alice`
+    #[arg(long = "config-var", value_parser = config_var)]
+    pub config_vars: Vec<(String, String)>,
+
+    /// Explain how the file at this path is accessed.
+    #[arg(long)]
+    pub path: Option<String>,
+
+    /// Explain the file access for writing, instead of reading.
+    #[arg(long, requires = "path")]
+    pub write: bool,
+
+    /// Explain how outgoing connections to this address are routed, and how the incoming
+    /// traffic is handled when the application listens on it.
+    #[arg(long)]
+    pub address: Option<SocketAddr>,
+
+    /// Explain the outgoing routing for UDP, instead of TCP.
+    #[arg(long, requires = "address")]
+    pub udp: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
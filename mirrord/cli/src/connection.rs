@@ -4,7 +4,7 @@ use mirrord_analytics::Reporter;
 use mirrord_config::{target::Target, LayerConfig};
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
 use mirrord_kube::{
-    api::{kubernetes::KubernetesAPI, wrap_raw_connection},
+    api::{kubernetes::KubernetesAPI, multiplex::multiplex_connections, wrap_raw_connection},
     error::KubeApiError,
     resolved::ResolvedTarget,
 };
@@ -133,17 +133,21 @@ where
     }
 
     match (
-        // user in mutipod without operator
-        matches!(
-            config.target,
-            mirrord_config::target::TargetConfig {
-                path: Some(
-                    mirrord_config::target::Target::Deployment { .. }
-                        | mirrord_config::target::Target::Rollout(..)
-                ),
-                ..
-            }
-        ),
+        // user in mutipod without operator, and without an agent for each pod
+        !config.agent.multi_pod
+            && matches!(
+                config.target,
+                mirrord_config::target::TargetConfig {
+                    path: Some(
+                        mirrord_config::target::Target::Deployment { .. }
+                            | mirrord_config::target::Target::Rollout(..)
+                            | mirrord_config::target::Target::Service(..)
+                            | mirrord_config::target::Target::ReplicaSet(..)
+                            | mirrord_config::target::Target::DaemonSet(..)
+                    ),
+                    ..
+                }
+            ),
         // user using http filter(s) without operator
         config.feature.network.incoming.http_filter.is_filter_set(),
     ) {
@@ -171,6 +175,10 @@ where
         .inspect_err(|fail| tracing::debug!(?fail, "Failed to detect OpenShift!"))
         .ok();
 
+    if config.agent.multi_pod {
+        return create_and_connect_multi_pod(config, progress, &k8s_api).await;
+    }
+
     let agent_connect_info = tokio::time::timeout(
         Duration::from_secs(config.agent.startup_timeout),
         k8s_api.create_agent(progress, &config.target, Some(config), Default::default()),
@@ -194,6 +202,43 @@ where
     ))
 }
 
+/// Creates an agent for each pod of the target (`agent.multi_pod`), and merges the connections
+/// with [`multiplex_connections`].
+async fn create_and_connect_multi_pod<P>(
+    config: &LayerConfig,
+    progress: &mut P,
+    k8s_api: &KubernetesAPI,
+) -> CliResult<(AgentConnectInfo, AgentConnection)>
+where
+    P: Progress + Send + Sync,
+{
+    let agent_connect_infos = tokio::time::timeout(
+        Duration::from_secs(config.agent.startup_timeout),
+        k8s_api.create_agents(progress, &config.target, Some(config), Default::default()),
+    )
+    .await
+    .unwrap_or(Err(KubeApiError::AgentReadyTimeout))
+    .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateAgentFailed))?;
+
+    let mut connections = Vec::with_capacity(agent_connect_infos.len());
+    for agent_connect_info in &agent_connect_infos {
+        let stream = k8s_api
+            .create_connection(agent_connect_info.clone())
+            .await
+            .map_err(|error| {
+                CliError::friendlier_error_or_else(error, CliError::AgentConnectionFailed)
+            })?;
+        connections.push(wrap_raw_connection(stream));
+    }
+
+    let (sender, receiver) = multiplex_connections(connections);
+
+    Ok((
+        AgentConnectInfo::DirectKubernetesMultiPod(agent_connect_infos),
+        AgentConnection { sender, receiver },
+    ))
+}
+
 fn user_persistent_random_message_select() -> bool {
     mid::get("mirrord")
         .inspect_err(|error| tracing::error!(%error, "failed to obtain machine ID"))
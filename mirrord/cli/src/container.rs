@@ -17,7 +17,7 @@ use mirrord_config::{
         MIRRORD_INTPROXY_CLIENT_TLS_CERTIFICATE_ENV, MIRRORD_INTPROXY_CLIENT_TLS_KEY_ENV,
         MIRRORD_INTPROXY_CONTAINER_MODE_ENV,
     },
-    LayerConfig, MIRRORD_CONFIG_FILE_ENV,
+    LayerConfig, MIRRORD_CONFIG_FILE_ENV, MIRRORD_PROFILE_ENV,
 };
 use mirrord_progress::{JsonProgress, Progress, ProgressTracker, MIRRORD_PROGRESS_ENV};
 use tempfile::NamedTempFile;
@@ -355,6 +355,8 @@ pub(crate) async fn container_command(
 
     let composed_config_file = create_composed_config(&config)?;
     std::env::set_var(MIRRORD_CONFIG_FILE_ENV, composed_config_file.path());
+    // The selected profile is already applied in the composed config.
+    std::env::remove_var(MIRRORD_PROFILE_ENV);
 
     let mut sub_progress = progress.subtask("preparing to launch process");
 
@@ -520,6 +522,8 @@ pub(crate) async fn container_ext_command(
 
     let composed_config_file = create_composed_config(&config)?;
     std::env::set_var(MIRRORD_CONFIG_FILE_ENV, composed_config_file.path());
+    // The selected profile is already applied in the composed config.
+    std::env::remove_var(MIRRORD_PROFILE_ENV);
 
     let mut sub_progress = progress.subtask("preparing to launch process");
 
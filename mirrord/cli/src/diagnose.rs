@@ -1,20 +1,56 @@
 use std::{path::Path, time::Duration};
 
+use kube::Client;
 use mirrord_analytics::NullReporter;
 use mirrord_config::{
     config::{ConfigContext, MirrordConfig},
-    LayerFileConfig,
+    LayerConfig, LayerFileConfig,
 };
+use mirrord_kube::{api::kubernetes::KubernetesAPI, error::KubeApiError};
 use mirrord_progress::{Progress, ProgressTracker};
 use mirrord_protocol::{ClientMessage, DaemonMessage};
 use tokio::{sync::mpsc, time::Instant};
 use tracing::Level;
 
+use self::{
+    cluster::diagnose_cluster, layer::diagnose_layer, report::DiagnoseReport,
+    target::diagnose_target,
+};
 use crate::{
     connection::create_and_connect, util::remove_proxy_env, CliError, CliResult, DiagnoseArgs,
-    DiagnoseCommand,
+    DiagnoseCheckArgs, DiagnoseCommand,
 };
 
+mod cluster;
+mod layer;
+mod probe;
+mod report;
+mod target;
+
+/// Loads the [`LayerConfig`] for the `mirrord diagnose` commands, from the given file or from the
+/// environment.
+fn load_config(config: Option<&Path>) -> CliResult<LayerConfig> {
+    let mut cfg_context = ConfigContext::default();
+    let config = if let Some(path) = config {
+        LayerFileConfig::from_path(path)?.generate_config(&mut cfg_context)
+    } else {
+        LayerFileConfig::default().generate_config(&mut cfg_context)
+    }?;
+
+    if !config.use_proxy {
+        remove_proxy_env();
+    }
+
+    Ok(config)
+}
+
+/// Creates a Kubernetes [`Client`] for the `mirrord diagnose` checks.
+async fn kube_client(config: &LayerConfig) -> Result<Client, KubeApiError> {
+    KubernetesAPI::create(config)
+        .await
+        .map(|api| api.client().clone())
+}
+
 /// Sends a ping the connection and expects a pong.
 async fn ping(
     sender: &mpsc::Sender<ClientMessage>,
@@ -50,16 +86,7 @@ async fn ping(
 async fn diagnose_latency(config: Option<&Path>) -> CliResult<()> {
     let mut progress = ProgressTracker::from_env("mirrord network diagnosis");
 
-    let mut cfg_context = ConfigContext::default();
-    let config = if let Some(path) = config {
-        LayerFileConfig::from_path(path)?.generate_config(&mut cfg_context)
-    } else {
-        LayerFileConfig::default().generate_config(&mut cfg_context)
-    }?;
-
-    if !config.use_proxy {
-        remove_proxy_env();
-    }
+    let config = load_config(config)?;
 
     let mut analytics = NullReporter::default();
     let (_, mut connection) = create_and_connect(&config, &mut progress, &mut analytics).await?;
@@ -101,5 +128,22 @@ async fn diagnose_latency(config: Option<&Path>) -> CliResult<()> {
 pub(crate) async fn diagnose_command(args: DiagnoseArgs) -> CliResult<()> {
     match args.command {
         DiagnoseCommand::Latency { config_file } => diagnose_latency(config_file.as_deref()).await,
+        DiagnoseCommand::Cluster(DiagnoseCheckArgs { config_file, json }) => {
+            let config = load_config(config_file.as_deref())?;
+            let mut report = DiagnoseReport::new("cluster");
+            diagnose_cluster(&config, &mut report).await;
+            report.finish(json)
+        }
+        DiagnoseCommand::Target(DiagnoseCheckArgs { config_file, json }) => {
+            let config = load_config(config_file.as_deref())?;
+            let mut report = DiagnoseReport::new("target");
+            diagnose_target(&config, &mut report).await;
+            report.finish(json)
+        }
+        DiagnoseCommand::Layer(DiagnoseCheckArgs { config_file, json }) => {
+            let mut report = DiagnoseReport::new("layer");
+            diagnose_layer(config_file.as_deref(), &mut report).await;
+            report.finish(json)
+        }
     }
 }
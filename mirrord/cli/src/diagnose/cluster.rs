@@ -0,0 +1,276 @@
+use std::fmt;
+
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use kube::{api::PostParams, Api, Client};
+use mirrord_analytics::NullReporter;
+use mirrord_config::LayerConfig;
+use mirrord_operator::client::OperatorApi;
+use tracing::Level;
+
+use super::{
+    kube_client,
+    probe::{ProbeOutcome, ProbePod},
+    report::DiagnoseReport,
+};
+
+/// A Kubernetes permission required by mirrord, checked with a [`SelfSubjectAccessReview`].
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Permission {
+    pub verb: &'static str,
+    pub group: &'static str,
+    pub resource: &'static str,
+    pub subresource: Option<&'static str>,
+}
+
+impl Permission {
+    pub(super) const fn new(
+        verb: &'static str,
+        group: &'static str,
+        resource: &'static str,
+    ) -> Self {
+        Self {
+            verb,
+            group,
+            resource,
+            subresource: None,
+        }
+    }
+
+    pub(super) const fn with_subresource(self, subresource: &'static str) -> Self {
+        Self {
+            subresource: Some(subresource),
+            ..self
+        }
+    }
+
+    /// Asks the API server whether the current user has this permission in the given
+    /// `namespace`.
+    ///
+    /// Returns the reason given by the authorizer when the permission is denied.
+    #[tracing::instrument(level = Level::TRACE, skip(client), ret, err)]
+    async fn review(
+        &self,
+        client: &Client,
+        namespace: &str,
+    ) -> Result<Option<String>, kube::Error> {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    namespace: Some(namespace.to_string()),
+                    verb: Some(self.verb.to_string()),
+                    group: Some(self.group.to_string()),
+                    resource: Some(self.resource.to_string()),
+                    subresource: self.subresource.map(ToString::to_string),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let status = Api::<SelfSubjectAccessReview>::all(client.clone())
+            .create(&PostParams::default(), &review)
+            .await?
+            .status
+            .unwrap_or_default();
+
+        if status.allowed {
+            Ok(None)
+        } else {
+            Ok(Some(status.reason.unwrap_or_else(|| "denied".to_string())))
+        }
+    }
+
+    /// Checks all of the `permissions`, adding one entry per permission to the `report`.
+    ///
+    /// Returns `true` if all of the permissions were granted.
+    pub(super) async fn check_all(
+        permissions: &[Self],
+        client: &Client,
+        namespace: &str,
+        report: &mut DiagnoseReport,
+    ) -> bool {
+        let mut all_granted = true;
+
+        for permission in permissions {
+            let name = format!("permission: {permission}");
+
+            match permission.review(client, namespace).await {
+                Ok(None) => report.pass(name, format!("allowed in namespace `{namespace}`")),
+                Ok(Some(reason)) => {
+                    all_granted = false;
+                    report.fail(name, format!("denied in namespace `{namespace}`: {reason}"));
+                }
+                Err(error) => {
+                    all_granted = false;
+                    report.fail(name, format!("failed to review access: {error}"));
+                }
+            }
+        }
+
+        all_granted
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.verb, self.resource)?;
+
+        if let Some(subresource) = self.subresource {
+            write!(f, "/{subresource}")?;
+        }
+
+        if !self.group.is_empty() {
+            write!(f, ".{}", self.group)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Permissions needed to spawn the agent and connect to it without the operator.
+fn agent_permissions(config: &LayerConfig) -> Vec<Permission> {
+    let mut permissions = vec![
+        Permission::new("get", "", "pods"),
+        Permission::new("list", "", "pods"),
+        Permission::new("get", "", "pods").with_subresource("log"),
+        Permission::new("create", "", "pods").with_subresource("portforward"),
+    ];
+
+    if config.agent.ephemeral {
+        permissions
+            .push(Permission::new("patch", "", "pods").with_subresource("ephemeralcontainers"));
+    } else {
+        permissions.push(Permission::new("create", "batch", "jobs"));
+    }
+
+    permissions
+}
+
+/// Runs the `mirrord diagnose cluster` checks.
+#[tracing::instrument(level = Level::TRACE, skip_all)]
+pub(super) async fn diagnose_cluster(config: &LayerConfig, report: &mut DiagnoseReport) {
+    let client = match kube_client(config).await {
+        Ok(client) => client,
+        Err(error) => {
+            report.fail("kubernetes connection", error.to_string());
+            return;
+        }
+    };
+
+    match client.apiserver_version().await {
+        Ok(version) => report.pass(
+            "kubernetes connection",
+            format!("server version {}", version.git_version),
+        ),
+        Err(error) => {
+            report.fail("kubernetes connection", error.to_string());
+            return;
+        }
+    }
+
+    let operator = match OperatorApi::try_new(config, &mut NullReporter::default()).await {
+        Ok(Some(api)) => {
+            report.pass(
+                "operator",
+                format!(
+                    "mirrord operator {} found",
+                    api.operator().spec.operator_version
+                ),
+            );
+            config.operator != Some(false)
+        }
+        Ok(None) if config.operator == Some(true) => {
+            report.fail(
+                "operator",
+                "`operator` is enabled in the config, but the mirrord operator was not found",
+            );
+            false
+        }
+        Ok(None) => {
+            report.pass(
+                "operator",
+                "mirrord operator not found, mirrord will spawn agents directly",
+            );
+            false
+        }
+        Err(error) => {
+            report.fail("operator", error.to_string());
+            false
+        }
+    };
+
+    let namespace = config
+        .agent
+        .namespace
+        .as_deref()
+        .or(config.target.namespace.as_deref())
+        .unwrap_or(client.default_namespace())
+        .to_string();
+
+    if operator {
+        Permission::check_all(
+            &[Permission::new("proxy", "operator.metalbear.co", "targets")],
+            &client,
+            &namespace,
+            report,
+        )
+        .await;
+
+        report.skip(
+            "agent image",
+            "agents are spawned by the mirrord operator, using its own image",
+        );
+
+        return;
+    }
+
+    Permission::check_all(&agent_permissions(config), &client, &namespace, report).await;
+
+    let can_create_pods = Permission::check_all(
+        &[Permission::new("create", "", "pods")],
+        &client,
+        &namespace,
+        report,
+    )
+    .await;
+    if !can_create_pods {
+        report.skip(
+            "agent image",
+            "a pod is needed to check the agent image, but creating pods is not allowed",
+        );
+        return;
+    }
+
+    let probe = ProbePod {
+        agent: &config.agent,
+        namespace: Some(&namespace),
+        node_name: None,
+        command: vec!["./mirrord-agent".to_string(), "--help".to_string()],
+    };
+
+    let image = config.agent.image();
+    match probe.run(&client).await {
+        Ok(ProbeOutcome::Completed { exit_code: 0, .. }) => {
+            report.pass("agent image", format!("`{image}` was pulled and started"))
+        }
+        Ok(ProbeOutcome::Completed { exit_code, logs }) => report.fail(
+            "agent image",
+            format!("`{image}` was pulled, but exited with code {exit_code}: {logs}"),
+        ),
+        Ok(ProbeOutcome::ImagePullFailed(message)) => report.fail(
+            "agent image",
+            format!("failed to pull `{image}`: {message}"),
+        ),
+        Ok(ProbeOutcome::TimedOut) => report.fail(
+            "agent image",
+            format!(
+                "`{image}` did not start within {}s (`agent.startup_timeout`)",
+                config.agent.startup_timeout
+            ),
+        ),
+        Err(error) => report.fail("agent image", error.to_string()),
+    }
+}
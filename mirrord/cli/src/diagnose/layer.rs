@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use mirrord_progress::NullProgress;
+use tracing::Level;
+
+use super::report::DiagnoseReport;
+use crate::extract::extract_library;
+
+/// Runs the `mirrord diagnose layer` checks.
+#[tracing::instrument(level = Level::TRACE, skip(report))]
+pub(super) async fn diagnose_layer(config_file: Option<&Path>, report: &mut DiagnoseReport) {
+    let lib_path = match extract_library(None, &NullProgress, true) {
+        Ok(lib_path) => {
+            report.pass(
+                "layer extraction",
+                format!("extracted to `{}`", lib_path.display()),
+            );
+            lib_path
+        }
+        Err(error) => {
+            report.fail("layer extraction", error.to_string());
+            return;
+        }
+    };
+
+    check_injection(&lib_path, config_file, report).await;
+}
+
+/// Runs `sh` with the layer preloaded in trace only mode (no agent, no internal proxy), and checks
+/// that the layer was mapped into the process.
+#[cfg(target_os = "linux")]
+async fn check_injection(lib_path: &Path, config_file: Option<&Path>, report: &mut DiagnoseReport) {
+    use mirrord_config::MIRRORD_CONFIG_FILE_ENV;
+    use tokio::process::Command;
+
+    use crate::execution::INJECTION_ENV_VAR;
+
+    let lib_name = lib_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!(
+            "grep -q '{lib_name}' /proc/$$/maps && echo injected"
+        ))
+        .env(INJECTION_ENV_VAR, lib_path)
+        .env("MIRRORD_LAYER_TRACE_ONLY", "true");
+
+    if let Some(config_file) = config_file {
+        command.env(MIRRORD_CONFIG_FILE_ENV, config_file);
+    }
+
+    match command.output().await {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).trim() == "injected" => {
+            report.pass("layer injection", "the layer was loaded into `sh`")
+        }
+        Ok(output) if output.status.success() => report.fail(
+            "layer injection",
+            "`sh` ran, but the layer was not loaded into it",
+        ),
+        Ok(output) => report.fail(
+            "layer injection",
+            format!(
+                "`sh` failed with the layer loaded ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ),
+        Err(error) => report.fail("layer injection", format!("failed to run `sh`: {error}")),
+    }
+}
+
+/// On macOS system binaries are protected by SIP, so there's no trivial binary we can inject
+/// the layer into without patching it first.
+#[cfg(not(target_os = "linux"))]
+async fn check_injection(
+    _lib_path: &Path,
+    _config_file: Option<&Path>,
+    report: &mut DiagnoseReport,
+) {
+    report.skip(
+        "layer injection",
+        "the injection check is only supported on Linux",
+    );
+}
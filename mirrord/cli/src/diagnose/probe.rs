@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use k8s_openapi::api::core::v1::{
+    Capabilities, Container, ContainerState, LocalObjectReference, Pod, PodSpec, SecurityContext,
+    Toleration,
+};
+use kube::{
+    api::{DeleteParams, LogParams, ObjectMeta, PostParams},
+    Api, Client,
+};
+use mirrord_config::agent::AgentConfig;
+use rand::distributions::{Alphanumeric, DistString};
+use tokio::time::Instant;
+use tracing::Level;
+
+/// Container waiting reasons that mean the image will never be pulled.
+const IMAGE_PULL_ERRORS: [&str; 4] = [
+    "ErrImagePull",
+    "ImagePullBackOff",
+    "InvalidImageName",
+    "ErrImageNeverPull",
+];
+
+/// How the probe pod ended.
+#[derive(Debug)]
+pub(super) enum ProbeOutcome {
+    /// The container ran to completion.
+    Completed { exit_code: i32, logs: String },
+    /// The kubelet failed to pull the agent image.
+    ImagePullFailed(String),
+    /// The pod did not finish within `agent.startup_timeout`.
+    TimedOut,
+}
+
+/// Short-lived pod that runs a command in the mirrord agent image, used to check things that can
+/// only be seen from inside the cluster.
+///
+/// The pod mirrors the settings that we use for the agent (pull secrets, tolerations, node
+/// selector, sidecar injection opt-outs), so that what works here also works for the agent.
+pub(super) struct ProbePod<'a> {
+    pub agent: &'a AgentConfig,
+    pub namespace: Option<&'a str>,
+    /// When set, the pod is scheduled on this node, in the host network namespace, with the
+    /// capabilities needed to read the iptables rules.
+    pub node_name: Option<&'a str>,
+    pub command: Vec<String>,
+}
+
+impl ProbePod<'_> {
+    fn as_pod(&self, name: &str) -> Pod {
+        let agent = self.agent;
+
+        let image_pull_secrets = agent.image_pull_secrets.as_ref().map(|secrets| {
+            secrets
+                .iter()
+                .map(|secret| LocalObjectReference {
+                    name: secret.name.to_string(),
+                })
+                .collect()
+        });
+
+        let tolerations = agent.tolerations.clone().unwrap_or_else(|| {
+            vec![Toleration {
+                operator: Some("Exists".to_owned()),
+                ..Default::default()
+            }]
+        });
+
+        let security_context = self.node_name.map(|_| SecurityContext {
+            capabilities: Some(Capabilities {
+                add: Some(vec!["NET_ADMIN".to_string(), "NET_RAW".to_string()]),
+                ..Default::default()
+            }),
+            privileged: Some(agent.privileged),
+            ..Default::default()
+        });
+
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                annotations: Some(
+                    [
+                        ("sidecar.istio.io/inject".to_string(), "false".to_string()),
+                        ("linkerd.io/inject".to_string(), "disabled".to_string()),
+                    ]
+                    .into(),
+                ),
+                labels: Some(
+                    [
+                        (
+                            "kuma.io/sidecar-injection".to_string(),
+                            "disabled".to_string(),
+                        ),
+                        ("app".to_string(), "mirrord".to_string()),
+                    ]
+                    .into(),
+                ),
+                ..Default::default()
+            },
+            spec: Some(PodSpec {
+                restart_policy: Some("Never".to_string()),
+                image_pull_secrets,
+                tolerations: Some(tolerations),
+                node_selector: self
+                    .node_name
+                    .is_none()
+                    .then(|| agent.node_selector.clone().map(BTreeMap::from_iter))
+                    .flatten(),
+                node_name: self.node_name.map(ToString::to_string),
+                host_network: self.node_name.map(|_| true),
+                service_account_name: agent.service_account.clone(),
+                containers: vec![Container {
+                    name: "mirrord-diagnose".to_string(),
+                    image: Some(agent.image().to_string()),
+                    image_pull_policy: Some(agent.image_pull_policy.clone()),
+                    command: Some(self.command.clone()),
+                    security_context,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Creates the pod, waits until it completes (or fails to pull the image) and deletes it.
+    #[tracing::instrument(level = Level::TRACE, skip(self, client), ret, err)]
+    pub(super) async fn run(&self, client: &Client) -> Result<ProbeOutcome, kube::Error> {
+        let name = format!(
+            "mirrord-diagnose-{}",
+            Alphanumeric
+                .sample_string(&mut rand::thread_rng(), 10)
+                .to_lowercase()
+        );
+
+        let api: Api<Pod> = match self.namespace {
+            Some(namespace) => Api::namespaced(client.clone(), namespace),
+            None => Api::default_namespaced(client.clone()),
+        };
+
+        api.create(&PostParams::default(), &self.as_pod(&name))
+            .await?;
+
+        let outcome = Self::wait_for_completion(&api, &name, self.agent.startup_timeout).await;
+
+        if let Err(error) = api.delete(&name, &DeleteParams::background()).await {
+            tracing::warn!(%error, pod = name, "Failed to delete the diagnose pod");
+        }
+
+        outcome
+    }
+
+    async fn wait_for_completion(
+        api: &Api<Pod>,
+        name: &str,
+        timeout: u64,
+    ) -> Result<ProbeOutcome, kube::Error> {
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+
+        while Instant::now() < deadline {
+            let state = api
+                .get(name)
+                .await?
+                .status
+                .and_then(|status| status.container_statuses)
+                .and_then(|statuses| statuses.into_iter().next())
+                .and_then(|status| status.state);
+
+            match state {
+                Some(ContainerState {
+                    terminated: Some(terminated),
+                    ..
+                }) => {
+                    let logs = api.logs(name, &LogParams::default()).await?;
+
+                    return Ok(ProbeOutcome::Completed {
+                        exit_code: terminated.exit_code,
+                        logs,
+                    });
+                }
+
+                Some(ContainerState {
+                    waiting: Some(waiting),
+                    ..
+                }) if waiting
+                    .reason
+                    .as_deref()
+                    .is_some_and(|reason| IMAGE_PULL_ERRORS.contains(&reason)) =>
+                {
+                    return Ok(ProbeOutcome::ImagePullFailed(
+                        waiting.message.or(waiting.reason).unwrap_or_default(),
+                    ));
+                }
+
+                _ => tokio::time::sleep(Duration::from_secs(1)).await,
+            }
+        }
+
+        Ok(ProbeOutcome::TimedOut)
+    }
+}
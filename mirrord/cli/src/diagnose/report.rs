@@ -0,0 +1,179 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{CliError, CliResult};
+
+/// Outcome of a single [`Check`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum CheckStatus {
+    /// Everything is fine.
+    Pass,
+    /// mirrord will probably work, but something looks off.
+    Warn,
+    /// mirrord will not work until this is fixed.
+    Fail,
+    /// The check could not run, usually because a previous check failed.
+    Skip,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "PASS"),
+            Self::Warn => write!(f, "WARN"),
+            Self::Fail => write!(f, "FAIL"),
+            Self::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// Result of a single diagnose check.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(super) struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub details: String,
+}
+
+/// Structured result of a `mirrord diagnose` command, printed as text or JSON.
+#[derive(Debug, Serialize)]
+pub(super) struct DiagnoseReport {
+    /// Name of the suite, e.g. `cluster`.
+    suite: &'static str,
+    checks: Vec<Check>,
+}
+
+impl DiagnoseReport {
+    pub(super) fn new(suite: &'static str) -> Self {
+        Self {
+            suite,
+            checks: Default::default(),
+        }
+    }
+
+    fn push<N: Into<String>, D: Into<String>>(&mut self, name: N, status: CheckStatus, details: D) {
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            details: details.into(),
+        });
+    }
+
+    pub(super) fn pass<N: Into<String>, D: Into<String>>(&mut self, name: N, details: D) {
+        self.push(name, CheckStatus::Pass, details);
+    }
+
+    pub(super) fn warn<N: Into<String>, D: Into<String>>(&mut self, name: N, details: D) {
+        self.push(name, CheckStatus::Warn, details);
+    }
+
+    pub(super) fn fail<N: Into<String>, D: Into<String>>(&mut self, name: N, details: D) {
+        self.push(name, CheckStatus::Fail, details);
+    }
+
+    pub(super) fn skip<N: Into<String>, D: Into<String>>(&mut self, name: N, details: D) {
+        self.push(name, CheckStatus::Skip, details);
+    }
+
+    /// Number of checks with the [`CheckStatus::Fail`] status.
+    pub(super) fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count()
+    }
+
+    /// Prints the report to stdout, and returns [`CliError::DiagnoseChecksFailed`] if any of the
+    /// checks failed.
+    pub(super) fn finish(self, json: bool) -> CliResult<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(&self)?);
+        } else {
+            print!("{self}");
+        }
+
+        match self.failures() {
+            0 => Ok(()),
+            failed => Err(CliError::DiagnoseChecksFailed(self.suite, failed)),
+        }
+    }
+}
+
+impl fmt::Display for DiagnoseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "mirrord diagnose {}:", self.suite)?;
+
+        for Check {
+            name,
+            status,
+            details,
+        } in &self.checks
+        {
+            writeln!(f, "  [{status}] {name}: {details}")?;
+        }
+
+        writeln!(
+            f,
+            "{} checks, {} failed",
+            self.checks.len(),
+            self.failures()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report() -> DiagnoseReport {
+        let mut report = DiagnoseReport::new("cluster");
+        report.pass("kubernetes connection", "server version v1.31.0");
+        report.warn("operator", "not installed");
+        report.fail("permission: create jobs", "denied");
+        report.skip("agent image", "missing permissions");
+        report
+    }
+
+    #[test]
+    fn text_output() {
+        assert_eq!(
+            report().to_string(),
+            "mirrord diagnose cluster:\n  \
+            [PASS] kubernetes connection: server version v1.31.0\n  \
+            [WARN] operator: not installed\n  \
+            [FAIL] permission: create jobs: denied\n  \
+            [SKIP] agent image: missing permissions\n\
+            4 checks, 1 failed\n"
+        );
+    }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            serde_json::to_value(report()).unwrap(),
+            serde_json::json!({
+                "suite": "cluster",
+                "checks": [
+                    { "name": "kubernetes connection", "status": "pass", "details": "server version v1.31.0" },
+                    { "name": "operator", "status": "warn", "details": "not installed" },
+                    { "name": "permission: create jobs", "status": "fail", "details": "denied" },
+                    { "name": "agent image", "status": "skip", "details": "missing permissions" },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        assert!(matches!(
+            report().finish(true),
+            Err(CliError::DiagnoseChecksFailed("cluster", 1))
+        ));
+
+        let mut report = DiagnoseReport::new("layer");
+        report.pass("layer extraction", "ok");
+        assert!(report.finish(false).is_ok());
+    }
+}
@@ -0,0 +1,245 @@
+use mirrord_config::{target::Target, LayerConfig};
+use mirrord_kube::api::runtime::RuntimeDataProvider;
+use tracing::Level;
+
+use super::{
+    cluster::Permission,
+    kube_client,
+    probe::{ProbeOutcome, ProbePod},
+    report::{CheckStatus, DiagnoseReport},
+};
+
+/// Prints the number of rules seen by each iptables backend, see [`IptablesRules::parse`].
+const IPTABLES_SCRIPT: &str = r#"for backend in legacy nft; do
+    if command -v iptables-$backend-save > /dev/null; then
+        echo "$backend $(iptables-$backend-save 2> /dev/null | grep -c '^-A')"
+    else
+        echo "$backend missing"
+    fi
+done"#;
+
+/// Permissions needed to read the target resource and find its pods.
+fn target_permissions(target: &Target) -> Vec<Permission> {
+    let resource = match target {
+        Target::Deployment(..) => Permission::new("get", "apps", "deployments"),
+        Target::Pod(..) => Permission::new("get", "", "pods"),
+        Target::Rollout(..) => Permission::new("get", "argoproj.io", "rollouts"),
+        Target::Job(..) => Permission::new("get", "batch", "jobs"),
+        Target::CronJob(..) => Permission::new("get", "batch", "cronjobs"),
+        Target::StatefulSet(..) => Permission::new("get", "apps", "statefulsets"),
+        Target::Service(..) => Permission::new("get", "", "services"),
+        Target::ReplicaSet(..) => Permission::new("get", "apps", "replicasets"),
+        Target::DaemonSet(..) => Permission::new("get", "apps", "daemonsets"),
+        Target::Targetless => return Vec::new(),
+    };
+
+    vec![resource, Permission::new("list", "", "pods")]
+}
+
+/// Number of rules found on the target's node by each iptables backend, `None` when the backend
+/// is not available in the agent image.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct IptablesRules {
+    legacy: Option<usize>,
+    nft: Option<usize>,
+}
+
+impl IptablesRules {
+    /// Parses the output of [`IPTABLES_SCRIPT`].
+    fn parse(output: &str) -> Self {
+        let mut rules = Self::default();
+
+        for line in output.lines() {
+            let Some((backend, count)) = line.trim().split_once(' ') else {
+                continue;
+            };
+
+            let count = count.parse().ok();
+            match backend {
+                "legacy" => rules.legacy = count,
+                "nft" => rules.nft = count,
+                _ => {}
+            }
+        }
+
+        rules
+    }
+
+    /// Checks whether the backend selected with `agent.nftables` is the one used on the node.
+    fn check(&self, nftables: bool) -> (CheckStatus, String) {
+        let (selected, other, selected_name, other_name) = if nftables {
+            (self.nft, self.legacy, "iptables-nft", "iptables-legacy")
+        } else {
+            (self.legacy, self.nft, "iptables-legacy", "iptables-nft")
+        };
+
+        match (selected, other) {
+            (None, _) => (
+                CheckStatus::Fail,
+                format!("{selected_name} is not available in the agent image"),
+            ),
+            (Some(0), Some(other_rules)) if other_rules > 0 => (
+                CheckStatus::Warn,
+                format!(
+                    "the node has no {selected_name} rules, but {other_rules} {other_name} rules, \
+                    consider setting `agent.nftables` to `{}`",
+                    !nftables
+                ),
+            ),
+            (Some(rules), _) => (
+                CheckStatus::Pass,
+                format!("using {selected_name}, the node has {rules} rules"),
+            ),
+        }
+    }
+}
+
+/// Runs the `mirrord diagnose target` checks.
+#[tracing::instrument(level = Level::TRACE, skip_all)]
+pub(super) async fn diagnose_target(config: &LayerConfig, report: &mut DiagnoseReport) {
+    let target = match config.target.path.as_ref() {
+        None | Some(Target::Targetless) => {
+            report.skip("target", "no target in the config, running targetless");
+            return;
+        }
+        Some(target) => target,
+    };
+
+    let client = match kube_client(config).await {
+        Ok(client) => client,
+        Err(error) => {
+            report.fail("kubernetes connection", error.to_string());
+            return;
+        }
+    };
+
+    let namespace = config
+        .target
+        .namespace
+        .as_deref()
+        .unwrap_or(client.default_namespace())
+        .to_string();
+
+    if !Permission::check_all(&target_permissions(target), &client, &namespace, report).await {
+        report.skip("target", "missing permissions to read the target");
+        return;
+    }
+
+    let runtime_data = match target.runtime_data(&client, Some(&namespace)).await {
+        Ok(runtime_data) => {
+            report.pass(
+                "target",
+                format!(
+                    "`{target}` runs in pod `{}`, container `{}`{}, on node `{}`",
+                    runtime_data.pod_name,
+                    runtime_data.container_name,
+                    if runtime_data.guessed_container {
+                        " (guessed)"
+                    } else {
+                        ""
+                    },
+                    runtime_data.node_name
+                ),
+            );
+            runtime_data
+        }
+        Err(error) => {
+            report.fail("target", error.to_string());
+            return;
+        }
+    };
+
+    report.pass(
+        "container runtime",
+        format!(
+            "{} (container id `{}`)",
+            runtime_data.container_runtime, runtime_data.container_id
+        ),
+    );
+
+    match runtime_data.mesh {
+        Some(mesh) => report.pass("service mesh", format!("{mesh} detected")),
+        None => report.pass("service mesh", "no service mesh detected"),
+    }
+
+    let agent_namespace = config.agent.namespace.as_deref().unwrap_or(&namespace);
+    let can_create_pods = Permission::check_all(
+        &[Permission::new("create", "", "pods")],
+        &client,
+        agent_namespace,
+        report,
+    )
+    .await;
+    if !can_create_pods {
+        report.skip(
+            "iptables",
+            "a pod is needed to check iptables on the node, but creating pods is not allowed",
+        );
+        return;
+    }
+
+    let probe = ProbePod {
+        agent: &config.agent,
+        namespace: Some(agent_namespace),
+        node_name: Some(&runtime_data.node_name),
+        command: vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            IPTABLES_SCRIPT.to_string(),
+        ],
+    };
+
+    match probe.run(&client).await {
+        Ok(ProbeOutcome::Completed { exit_code: 0, logs }) => {
+            match IptablesRules::parse(&logs).check(config.agent.nftables) {
+                (CheckStatus::Pass, details) => report.pass("iptables", details),
+                (CheckStatus::Warn, details) => report.warn("iptables", details),
+                (_, details) => report.fail("iptables", details),
+            }
+        }
+        Ok(ProbeOutcome::Completed { exit_code, logs }) => report.fail(
+            "iptables",
+            format!("iptables check exited with code {exit_code}: {logs}"),
+        ),
+        Ok(ProbeOutcome::ImagePullFailed(message)) => report.fail(
+            "iptables",
+            format!("failed to pull the agent image: {message}"),
+        ),
+        Ok(ProbeOutcome::TimedOut) => report.fail(
+            "iptables",
+            format!(
+                "iptables check did not finish within {}s (`agent.startup_timeout`)",
+                config.agent.startup_timeout
+            ),
+        ),
+        Err(error) => report.fail("iptables", error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn parse_iptables_rules() {
+        assert_eq!(
+            IptablesRules::parse("legacy 3\nnft missing\n"),
+            IptablesRules {
+                legacy: Some(3),
+                nft: None,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case("legacy 12\nnft 0", false, CheckStatus::Pass)]
+    #[case("legacy 0\nnft 0", true, CheckStatus::Pass)]
+    #[case("legacy 0\nnft 40", false, CheckStatus::Warn)]
+    #[case("legacy 12\nnft 0", true, CheckStatus::Warn)]
+    #[case("legacy missing\nnft 40", false, CheckStatus::Fail)]
+    fn iptables_backend(#[case] output: &str, #[case] nftables: bool, #[case] status: CheckStatus) {
+        assert_eq!(IptablesRules::parse(output).check(nftables).0, status);
+    }
+}
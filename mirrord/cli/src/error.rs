@@ -2,9 +2,15 @@ use std::{ffi::NulError, net::SocketAddr, num::ParseIntError, path::PathBuf, str
 
 use kube::core::ErrorResponse;
 use miette::Diagnostic;
-use mirrord_config::config::ConfigError;
+use mirrord_config::{
+    config::ConfigError, feature::network::filter::ProtocolAndAddressFilterError,
+};
 use mirrord_console::error::ConsoleError;
-use mirrord_intproxy::{agent_conn::ConnectionTlsError, error::IntProxyError};
+use mirrord_intproxy::{
+    agent_conn::ConnectionTlsError,
+    error::IntProxyError,
+    proxies::{incoming::rewrite::HttpRewriteError, outgoing::mock::OutgoingMockError},
+};
 use mirrord_kube::error::KubeApiError;
 use mirrord_operator::client::error::{HttpError, OperatorApiError, OperatorOperation};
 use mirrord_vpn::error::VpnError;
@@ -145,6 +151,26 @@ pub(crate) enum InternalProxyError {
     #[error("Initial ping pong with the agent failed: {0}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
     InitialPingPongFailed(String),
+
+    #[error("Failed to open HTTP record file at `{0}`: {1}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    OpenHttpRecordFile(PathBuf, std::io::Error),
+
+    #[error("Invalid `feature.network.incoming.http_rewrite` config: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    HttpRewrite(#[from] HttpRewriteError),
+
+    #[error("Invalid `feature.network.outgoing.mock` config: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    OutgoingMock(#[from] OutgoingMockError),
+
+    #[error("Invalid `feature.network.outgoing.faults` config: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    OutgoingFaults(#[from] ProtocolAndAddressFilterError),
+
+    #[error("Failed to set up the inspector socket in `{0}`: {1}")]
+    #[diagnostic(help("The directory must be accessible only by the current user. You can also disable `internal_proxy.inspector`.{GENERAL_HELP}"))]
+    InspectorSetup(PathBuf, std::io::Error),
 }
 
 /// Errors that can occur when executing the `mirrord operator setup` command.
@@ -171,6 +197,10 @@ pub(crate) enum CliError {
     #[diagnostic(help("Please check that Kubernetes is configured correctly and test your connection with `kubectl get pods`.{GENERAL_HELP}"))]
     CreateKubeApiFailed(KubeApiError),
 
+    #[error("Failed to read the remote environment from the pod spec: {0}")]
+    #[diagnostic(help("Please check that the resource set in `feature.env.from_pod_spec` (or the target) exists, and that you can read its ConfigMaps and Secrets with `kubectl get configmaps,secrets`.{GENERAL_HELP}"))]
+    PodSpecEnvFailed(KubeApiError),
+
     #[error("Failed to list mirrord targets: {0}")]
     #[diagnostic(help("Please check that Kubernetes is configured correctly and test your connection with `kubectl get pods`.{GENERAL_HELP}"))]
     ListTargetsFailed(KubeApiError),
@@ -295,6 +325,26 @@ pub(crate) enum CliError {
     #[diagnostic(transparent)]
     InternalProxyError(#[from] InternalProxyError),
 
+    #[error("Failed to read recorded HTTP requests from `{0}`: {1}")]
+    #[diagnostic(help(
+        "The file should be recorded with `feature.network.incoming.http_record`.{GENERAL_HELP}"
+    ))]
+    ReplayFileRead(PathBuf, std::io::Error),
+
+    #[error("Failed to create HTTP client for replaying requests: {0}")]
+    #[diagnostic(help("{GENERAL_BUG}"))]
+    ReplayClient(reqwest::Error),
+
+    #[error("No running mirrord session found in `{0}`")]
+    #[diagnostic(help(
+        "Make sure that the session is running with `internal_proxy.inspector` enabled, or pass the socket path with `--socket`.{GENERAL_HELP}"
+    ))]
+    InspectorNotFound(PathBuf),
+
+    #[error("Failed to read events from the inspector socket `{0}`: {1}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    InspectorConnection(PathBuf, std::io::Error),
+
     /// Errors produced by `mirrord vpn` command.
     #[error(transparent)]
     #[diagnostic(help("{GENERAL_HELP}"))]
@@ -357,6 +407,10 @@ pub(crate) enum CliError {
     ))]
     PingPongFailed(String),
 
+    #[error("`mirrord diagnose {0}` found {1} failing check(s).")]
+    #[diagnostic(help("See the report above for details.{GENERAL_HELP}"))]
+    DiagnoseChecksFailed(&'static str, usize),
+
     #[error("Failed to prepare mirrord operator client certificate: {0}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
     OperatorClientCertError(String),
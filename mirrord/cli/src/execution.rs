@@ -4,17 +4,27 @@ use std::{
     time::Duration,
 };
 
+use kube::Client;
 use mirrord_analytics::{AnalyticsError, AnalyticsReporter, Reporter};
 use mirrord_config::{
-    config::ConfigError, feature::env::mapper::EnvVarsRemapper,
-    internal_proxy::MIRRORD_INTPROXY_CONNECT_TCP_ENV, LayerConfig,
+    config::ConfigError,
+    feature::{
+        env::{mapper::EnvVarsRemapper, FromPodSpecConfig},
+        network::incoming::{ServicePortMapping, MIRRORD_SERVICE_PORT_MAPPING_ENV},
+    },
+    internal_proxy::MIRRORD_INTPROXY_CONNECT_TCP_ENV,
+    target::Target,
+    LayerConfig,
 };
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
-use mirrord_operator::client::OperatorSession;
+use mirrord_kube::api::{
+    env::env_from_pod_spec, kubernetes::create_kube_config, service::service_port_mapping,
+};
+use mirrord_operator::client::{policy::ClientPolicy, OperatorSession};
 use mirrord_progress::Progress;
 use mirrord_protocol::{
-    tcp::HTTP_COMPOSITE_FILTER_VERSION, ClientMessage, DaemonMessage, EnvVars, GetEnvVarsRequest,
-    LogLevel,
+    tcp::{HTTP_COMPOSITE_FILTER_VERSION, HTTP_METHOD_QUERY_BODY_FILTER_VERSION},
+    ClientMessage, DaemonMessage, EnvVars, GetEnvVarsRequest, LogLevel,
 };
 #[cfg(target_os = "macos")]
 use mirrord_sip::sip_patch;
@@ -28,6 +38,7 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, trace, warn, Level};
+use wildmatch::WildMatch;
 
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 use crate::extract::extract_arm64;
@@ -196,36 +207,53 @@ impl MirrordExecution {
             .await
             .inspect_err(|_| analytics.set_error(AnalyticsError::AgentConnection))?;
 
-        if config.feature.network.incoming.http_filter.is_composite() {
+        let http_filter = &config.feature.network.incoming.http_filter;
+        if http_filter.is_composite() || http_filter.has_method_query_or_body() {
             let version = match &connect_info {
                 AgentConnectInfo::Operator(OperatorSession {
                     operator_protocol_version: Some(version),
                     ..
                 }) => Some(version.clone()),
-                AgentConnectInfo::DirectKubernetes(_) => {
+                AgentConnectInfo::DirectKubernetes(_)
+                | AgentConnectInfo::DirectKubernetesMultiPod(_) => {
                     Some(MirrordExecution::get_agent_version(&mut connection).await?)
                 }
                 _ => None,
             };
-            if !version
-                .map(|version| HTTP_COMPOSITE_FILTER_VERSION.matches(&version))
-                .unwrap_or(false)
+            if http_filter.is_composite()
+                && !version
+                    .as_ref()
+                    .map(|version| HTTP_COMPOSITE_FILTER_VERSION.matches(version))
+                    .unwrap_or(false)
             {
                 Err(ConfigError::Conflict(format!(
                     "Cannot use 'any_of' or 'all_of' HTTP filter types, protocol version used by mirrord-agent must match {}. Consider using a newer version of mirrord-agent",
                     *HTTP_COMPOSITE_FILTER_VERSION
                 )))?
             }
+            if http_filter.has_method_query_or_body()
+                && !version
+                    .as_ref()
+                    .map(|version| HTTP_METHOD_QUERY_BODY_FILTER_VERSION.matches(version))
+                    .unwrap_or(false)
+            {
+                Err(ConfigError::Conflict(format!(
+                    "Cannot use method, query or body HTTP filters, protocol version used by mirrord-agent must match {}. Consider using a newer version of mirrord-agent",
+                    *HTTP_METHOD_QUERY_BODY_FILTER_VERSION
+                )))?
+            }
         }
 
         let mut env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
             Default::default()
         } else {
-            Self::fetch_env_vars(config, &mut connection)
+            Self::fetch_env_vars(config, &mut connection, connect_info.policy(), progress)
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
         };
 
+        Self::add_service_port_mapping(config, &mut env_vars, progress).await;
+
         #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
         env_vars.insert(
             "MIRRORD_MACOS_ARM64_LIBRARY".to_string(),
@@ -380,11 +408,13 @@ impl MirrordExecution {
         let mut env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
             Default::default()
         } else {
-            Self::fetch_env_vars(config, &mut connection)
+            Self::fetch_env_vars(config, &mut connection, connect_info.policy(), progress)
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
         };
 
+        Self::add_service_port_mapping(config, &mut env_vars, progress).await;
+
         // stderr is inherited so we can see logs/errors.
         let mut proxy_command =
             Command::new(std::env::current_exe().map_err(CliError::CliPathError)?);
@@ -454,10 +484,15 @@ impl MirrordExecution {
     }
 
     /// Construct filter and retrieve remote environment from the connected agent using
-    /// `MirrordExecution::get_remote_env`.
-    async fn fetch_env_vars(
+    /// `MirrordExecution::get_remote_env`, or from the pod spec using
+    /// `MirrordExecution::get_pod_spec_env` when `feature.env.from_pod_spec` is set.
+    ///
+    /// Env vars excluded by the mirrord policies are removed from the remote environment.
+    async fn fetch_env_vars<P: Progress>(
         config: &LayerConfig,
         connection: &mut AgentConnection,
+        policy: Option<&ClientPolicy>,
+        progress: &P,
     ) -> CliResult<HashMap<String, String>> {
         let (env_vars_exclude, env_vars_include) = match (
             config
@@ -484,7 +519,17 @@ impl MirrordExecution {
             (None, None) => (HashSet::new(), HashSet::from(EnvVars("*".to_owned()))),
         };
 
-        let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
+        let from_pod_spec = config
+            .feature
+            .env
+            .from_pod_spec
+            .as_ref()
+            .filter(|from_pod_spec| from_pod_spec.is_enabled());
+
+        let mut env_vars = if let Some(from_pod_spec) = from_pod_spec {
+            Self::get_pod_spec_env(config, from_pod_spec, &env_vars_exclude, &env_vars_include)
+                .await?
+        } else if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
             let communication_timeout =
                 Duration::from_secs(config.agent.communication_timeout.unwrap_or(30).into());
 
@@ -498,6 +543,16 @@ impl MirrordExecution {
             Default::default()
         };
 
+        if let Some(policy) = policy {
+            let removed = policy.filter_env(&mut env_vars);
+            if !removed.is_empty() {
+                progress.warning(&format!(
+                    "Remote environment variables {} are hidden by a mirrord policy.",
+                    removed.join(", ")
+                ));
+            }
+        }
+
         if let Some(file) = &config.feature.env.env_file {
             let envs_from_file = envfile::EnvFile::new(file)
                 .map_err(|error| CliError::EnvFileAccessError(file.clone(), error))?
@@ -518,6 +573,95 @@ impl MirrordExecution {
         Ok(env_vars)
     }
 
+    /// For a `service` target, passes the `port` -> `targetPort` mapping of the service to the
+    /// layer in [`MIRRORD_SERVICE_PORT_MAPPING_ENV`].
+    ///
+    /// Failing to read the service is not fatal, the ports are just not mapped then.
+    async fn add_service_port_mapping<P: Progress>(
+        config: &LayerConfig,
+        env_vars: &mut HashMap<String, String>,
+        progress: &P,
+    ) {
+        let Some(target @ Target::Service(..)) = &config.target.path else {
+            return;
+        };
+
+        let result = async {
+            let client = create_kube_config(
+                config.accept_invalid_certificates,
+                config.kubeconfig.clone(),
+                config.kube_context.clone(),
+            )
+            .await
+            .and_then(|config| Client::try_from(config).map_err(From::from))?;
+
+            service_port_mapping(&client, target, config.target.namespace.as_deref()).await
+        }
+        .await;
+
+        match result.map(ServicePortMapping) {
+            Ok(mapping) if mapping.0.is_empty() => {}
+            Ok(mapping) => match serde_json::to_string(&mapping) {
+                Ok(mapping) => {
+                    env_vars.insert(MIRRORD_SERVICE_PORT_MAPPING_ENV.to_string(), mapping);
+                }
+                Err(error) => warn!(%error, "Failed to serialize the service port mapping"),
+            },
+            Err(error) => progress.warning(&format!(
+                "Failed to map the service ports to their target ports: {error}"
+            )),
+        }
+    }
+
+    /// Retrieve remote environment from the pod spec of the target, or of the resource set in
+    /// [`FromPodSpecConfig::Resource`], without asking the agent.
+    ///
+    /// Applies the `env_vars_filter` and `env_vars_select` patterns here, as the agent would.
+    #[tracing::instrument(level = Level::TRACE, skip(config))]
+    async fn get_pod_spec_env(
+        config: &LayerConfig,
+        from_pod_spec: &FromPodSpecConfig,
+        env_vars_filter: &HashSet<String>,
+        env_vars_select: &HashSet<String>,
+    ) -> CliResult<HashMap<String, String>> {
+        let target = match from_pod_spec.resource()? {
+            Some(target) => target,
+            None => config.target.path.clone().unwrap_or(Target::Targetless),
+        };
+
+        let client = create_kube_config(
+            config.accept_invalid_certificates,
+            config.kubeconfig.clone(),
+            config.kube_context.clone(),
+        )
+        .await
+        .and_then(|config| Client::try_from(config).map_err(From::from))
+        .map_err(|error| {
+            CliError::friendlier_error_or_else(error, CliError::CreateKubeApiFailed)
+        })?;
+
+        let mut env_vars = env_from_pod_spec(&client, &target, config.target.namespace.as_deref())
+            .await
+            .map_err(|error| {
+                CliError::friendlier_error_or_else(error, CliError::PodSpecEnvFailed)
+            })?;
+
+        let exclude = env_vars_filter
+            .iter()
+            .map(|pattern| WildMatch::new(pattern))
+            .collect::<Vec<_>>();
+        let include = env_vars_select
+            .iter()
+            .map(|pattern| WildMatch::new(pattern))
+            .collect::<Vec<_>>();
+        env_vars.retain(|name, _| {
+            !exclude.iter().any(|pattern| pattern.matches(name))
+                && (include.is_empty() || include.iter().any(|pattern| pattern.matches(name)))
+        });
+
+        Ok(env_vars)
+    }
+
     /// Retrieve remote environment from the connected agent.
     #[tracing::instrument(level = Level::TRACE, skip_all)]
     async fn get_remote_env(
@@ -0,0 +1,413 @@
+//! Implementation of the `mirrord config explain` command, which prints the resolved
+//! [`LayerConfig`] with the source of each value, and explains which config rules apply to a
+//! file path or a socket address.
+
+use std::{collections::HashMap, fmt, net::SocketAddr};
+
+use mirrord_config::{
+    config::ConfigContext,
+    feature::{
+        fs::{
+            filter::{FileAccess, FileFilter, FileFilterRule},
+            FsConfig,
+        },
+        network::{
+            filter::{AddressFilter, ProtocolAndAddressFilter, ProtocolFilter},
+            incoming::{IgnoredPort, IncomingConfig, IncomingMode, IncomingPort},
+            outgoing::{OutgoingConfig, OutgoingFilterConfig},
+        },
+    },
+    LayerConfig, MIRRORD_CONFIG_FILE_ENV, MIRRORD_CONFIG_VARS_ENV, MIRRORD_PROFILE_ENV,
+};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::Level;
+
+use crate::{
+    config::{config_vars_env, ConfigArgs, ConfigCommand, ExplainConfigArgs},
+    CliResult,
+};
+
+/// Top level config keys that are resolved when the config file is loaded, and are always empty
+/// in the [`LayerConfig`].
+const RESOLVED_KEYS: [&str; 2] = ["extends", "profiles"];
+
+/// Where the value of a config field comes from.
+#[derive(Debug, PartialEq, Eq)]
+enum ValueSource<'a> {
+    /// Set with an env variable, which takes precedence over the config file.
+    Env(&'static str),
+    /// Set in a config file, or in one of its profiles.
+    File(&'a str),
+    Default,
+}
+
+impl<'a> ValueSource<'a> {
+    fn of(context: &'a ConfigContext, path: &str) -> Self {
+        if let Some(env) = context.env_source(path) {
+            Self::Env(env)
+        } else if let Some(file) = context.sources().file(path) {
+            Self::File(file)
+        } else {
+            Self::Default
+        }
+    }
+}
+
+impl fmt::Display for ValueSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env(env) => write!(f, "env {env}"),
+            Self::File(file) => write!(f, "file {file}"),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Collects the leaf values of the serialized config, keyed by their path, e.g.
+/// `feature.network.incoming.mode`.
+fn flatten(value: Value, path: String, values: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (key, value) in fields {
+                let path = if path.is_empty() {
+                    key
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten(value, path, values);
+            }
+        }
+        value => values.push((path, value)),
+    }
+}
+
+/// Formats the config values, one per line, together with their [`ValueSource`].
+fn describe_values(config: &LayerConfig, context: &ConfigContext) -> CliResult<Vec<String>> {
+    let mut values = Vec::new();
+    flatten(serde_json::to_value(config)?, String::new(), &mut values);
+
+    Ok(values
+        .into_iter()
+        .filter(|(path, _)| !RESOLVED_KEYS.contains(&path.as_str()))
+        .map(|(path, value)| {
+            let source = ValueSource::of(context, &path);
+            format!("{path} = {value}  ({source})")
+        })
+        .collect())
+}
+
+/// Serializes a config enum the same way it's written in the config file.
+fn config_value<T: Serialize>(value: T) -> String {
+    serde_json::to_string(&value).unwrap_or_default()
+}
+
+/// Explains the [`FileFilter`] decision for the file at `path`.
+fn explain_file(config: &FsConfig, path: &str, write: bool) -> String {
+    let filter = FileFilter::new(config.clone());
+    let (access, rule) = filter.check(path, write);
+
+    let access = match access {
+        FileAccess::Remote => "accessed in the target",
+        FileAccess::Local => "accessed locally",
+        FileAccess::NotFound => "reported as not found",
+    };
+
+    let patterns = match rule {
+        FileFilterRule::NotFound => Some("`feature.fs.not_found`"),
+        FileFilterRule::ReadWrite => Some("`feature.fs.read_write`"),
+        FileFilterRule::ReadOnly => Some("`feature.fs.read_only`"),
+        FileFilterRule::Local => Some("`feature.fs.local`"),
+        FileFilterRule::DefaultNotFound => Some("the default not found paths"),
+        FileFilterRule::DefaultReadOnly => Some("the default remote read only paths"),
+        FileFilterRule::DefaultLocal => Some("the default local paths"),
+        FileFilterRule::Mode(..) => None,
+    };
+
+    let rule = match patterns {
+        Some(patterns) => format!(
+            "matched `{}` from {patterns}",
+            filter.matched_pattern(rule, path).unwrap_or_default()
+        ),
+        None => format!("`feature.fs.mode` is {}", config_value(config.mode)),
+    };
+
+    let operation = if write { "writing" } else { "reading" };
+    format!("{path} ({operation}): {access}, {rule}")
+}
+
+/// Explains whether an outgoing connection to `address` goes through the target, following the
+/// `feature.network.outgoing` rules.
+fn explain_outgoing(config: &OutgoingConfig, address: SocketAddr, udp: bool) -> String {
+    let protocol = if udp { "udp" } else { "tcp" };
+    let prefix = format!("outgoing {protocol}://{address}");
+
+    if (udp && !config.udp) || (!udp && !config.tcp) {
+        return format!("{prefix}: local, `feature.network.outgoing.{protocol}` is disabled");
+    }
+
+    if config.ignore_localhost && address.ip().is_loopback() {
+        return format!("{prefix}: local, `feature.network.outgoing.ignore_localhost` is set");
+    }
+
+    let (filters, filter_is_local) = match &config.filter {
+        None => return format!("{prefix}: remote, `feature.network.outgoing.filter` is not set"),
+        Some(OutgoingFilterConfig::Remote(filters)) => (filters, false),
+        Some(OutgoingFilterConfig::Local(filters)) => (filters, true),
+    };
+
+    let (matched, unmatched) = if filter_is_local {
+        ("local", "remote")
+    } else {
+        ("remote", "local")
+    };
+
+    let protocol = if udp {
+        ProtocolFilter::Udp
+    } else {
+        ProtocolFilter::Tcp
+    };
+    let parsed = filters
+        .iter()
+        .filter_map(|filter| Some((filter, filter.parse::<ProtocolAndAddressFilter>().ok()?)))
+        .collect::<Vec<_>>();
+
+    // Names are resolved with the cluster DNS, so they can't be matched here.
+    let matched_filter = parsed
+        .iter()
+        .find(|(_, parsed)| parsed.matches(address, protocol, &Default::default()));
+    if let Some((filter, _)) = matched_filter {
+        return format!(
+            "{prefix}: {matched}, matched `{filter}` from `feature.network.outgoing.filter.{matched}`"
+        );
+    }
+
+    // Name filters that would match if the name resolved to this address in the cluster.
+    let name_filters = parsed
+        .iter()
+        .filter_map(|(filter, parsed)| {
+            let AddressFilter::Name(name, _) = &parsed.address else {
+                return None;
+            };
+            let resolved_names = HashMap::from([(name.clone(), vec![address.ip()])]);
+            parsed
+                .matches(address, protocol, &resolved_names)
+                .then(|| format!("`{filter}`"))
+        })
+        .collect::<Vec<_>>();
+
+    if name_filters.is_empty() {
+        format!(
+            "{prefix}: {unmatched}, nothing matched in `feature.network.outgoing.filter.{matched}`"
+        )
+    } else {
+        format!(
+            "{prefix}: {unmatched}, nothing matched in `feature.network.outgoing.filter.{matched}`, \
+            unless {} resolves to {} with the cluster DNS",
+            name_filters.join(" or "),
+            address.ip()
+        )
+    }
+}
+
+/// Explains how the incoming traffic is handled when the application listens on `address`,
+/// following the `feature.network.incoming` rules.
+fn explain_incoming(config: &IncomingConfig, address: SocketAddr) -> String {
+    let prefix = format!("incoming {address}");
+    let port = address.port();
+
+    if config.mode == IncomingMode::Off {
+        return format!("{prefix}: local only, `feature.network.incoming.mode` is \"off\"");
+    }
+
+    let (remote_port, filtered) = match config.tcp_port(address) {
+        IncomingPort::Remote {
+            remote_port,
+            filtered,
+        } => (remote_port, filtered),
+        IncomingPort::Ignored(IgnoredPort::Localhost) => {
+            return format!(
+                "{prefix}: local only, `feature.network.incoming.ignore_localhost` is set"
+            );
+        }
+        IncomingPort::Ignored(IgnoredPort::IgnorePorts) => {
+            return format!(
+                "{prefix}: local only, {port} is in `feature.network.incoming.ignore_ports`"
+            );
+        }
+        IncomingPort::Ignored(IgnoredPort::PortZero) => {
+            return format!("{prefix}: local only, port 0 is never subscribed");
+        }
+        IncomingPort::Ignored(IgnoredPort::NotInPorts(remote_port)) => {
+            return format!(
+                "{prefix}: local only, {remote_port} is not in `feature.network.incoming.ports`"
+            );
+        }
+        IncomingPort::Ignored(IgnoredPort::NotInHttpFilterPorts(remote_port)) => {
+            return format!(
+                "{prefix}: local only, an HTTP filter is set and {remote_port} is not in \
+                `feature.network.incoming.http_filter.ports` or `feature.network.incoming.ports`"
+            );
+        }
+    };
+
+    let remote = if remote_port == port {
+        format!("remote port {remote_port}")
+    } else {
+        format!("remote port {remote_port} (from `feature.network.incoming.port_mapping`)")
+    };
+
+    match config.mode {
+        IncomingMode::Steal if filtered => format!(
+            "{prefix}: steals the HTTP requests matching `feature.network.incoming.http_filter` \
+            from {remote}"
+        ),
+        IncomingMode::Steal => format!("{prefix}: steals all traffic from {remote}"),
+        _ => format!("{prefix}: mirrors the traffic of {remote}"),
+    }
+}
+
+/// Prints the resolved config, and explains the rules for the given path and address.
+#[tracing::instrument(level = Level::TRACE, ret)]
+fn explain_config(args: ExplainConfigArgs) -> CliResult<()> {
+    if let Some(config_file) = &args.config_file {
+        std::env::set_var(MIRRORD_CONFIG_FILE_ENV, config_file);
+    }
+    if let Some(profile) = &args.profile {
+        std::env::set_var(MIRRORD_PROFILE_ENV, profile);
+    }
+    std::env::set_var(
+        MIRRORD_CONFIG_VARS_ENV,
+        config_vars_env(None, &args.config_vars)?,
+    );
+
+    let (config, mut context) = LayerConfig::from_env_with_warnings()?;
+    // The config is explained even if it's invalid, to help with fixing it.
+    let verified = config.verify(&mut context);
+
+    for line in describe_values(&config, &context)? {
+        println!("{line}");
+    }
+
+    for warning in context.get_warnings() {
+        println!("warning: {warning}");
+    }
+
+    if let Err(error) = verified {
+        println!("error: {error}");
+    }
+
+    if let Some(path) = &args.path {
+        println!();
+        println!("{}", explain_file(&config.feature.fs, path, args.write));
+    }
+
+    if let Some(address) = args.address {
+        println!();
+        println!(
+            "{}",
+            explain_outgoing(&config.feature.network.outgoing, address, args.udp)
+        );
+        println!(
+            "{}",
+            explain_incoming(&config.feature.network.incoming, address)
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn config_command(args: ConfigArgs) -> CliResult<()> {
+    match args.command {
+        ConfigCommand::Explain(args) => explain_config(args),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_config::{
+        config::{ConfigContext, MirrordConfig},
+        feature::{
+            fs::FsModeConfig,
+            network::incoming::{IncomingConfig, IncomingFileConfig},
+        },
+        util::VecOrSingle,
+    };
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("/etc/hosts", false, "/etc/hosts (reading): accessed in the target, matched `^/etc/hosts$` from the default remote read only paths")]
+    #[case("/app/data.db", true, "/app/data.db (writing): accessed in the target, matched `^/app/` from `feature.fs.read_write`")]
+    #[case(
+        "/srv/out",
+        true,
+        "/srv/out (writing): accessed locally, `feature.fs.mode` is \"read\""
+    )]
+    fn file(#[case] path: &str, #[case] write: bool, #[case] expected: &str) {
+        let config = FsConfig {
+            mode: FsModeConfig::Read,
+            read_write: Some(VecOrSingle::Single("^/app/".into())),
+            ..Default::default()
+        };
+
+        assert_eq!(explain_file(&config, path, write), expected);
+    }
+
+    #[rstest]
+    #[case("10.0.0.1:5432", false, "outgoing tcp://10.0.0.1:5432: local, matched `10.0.0.0/8:5432` from `feature.network.outgoing.filter.local`")]
+    #[case("10.0.0.1:80", false, "outgoing tcp://10.0.0.1:80: remote, nothing matched in `feature.network.outgoing.filter.local`")]
+    #[case("192.168.0.1:5432", false, "outgoing tcp://192.168.0.1:5432: remote, nothing matched in `feature.network.outgoing.filter.local`, unless `tcp://db.default:5432` resolves to 192.168.0.1 with the cluster DNS")]
+    #[case("1.1.1.1:53", true, "outgoing udp://1.1.1.1:53: local, matched `udp://:53` from `feature.network.outgoing.filter.local`")]
+    #[case(
+        "127.0.0.1:6379",
+        false,
+        "outgoing tcp://127.0.0.1:6379: local, `feature.network.outgoing.ignore_localhost` is set"
+    )]
+    fn outgoing(#[case] address: SocketAddr, #[case] udp: bool, #[case] expected: &str) {
+        let config = OutgoingConfig {
+            tcp: true,
+            udp: true,
+            ignore_localhost: true,
+            filter: Some(OutgoingFilterConfig::Local(VecOrSingle::Multiple(vec![
+                "10.0.0.0/8:5432".into(),
+                "udp://:53".into(),
+                "tcp://db.default:5432".into(),
+            ]))),
+            ..Default::default()
+        };
+
+        assert_eq!(explain_outgoing(&config, address, udp), expected);
+    }
+
+    #[rstest]
+    #[case("0.0.0.0:8080", "incoming 0.0.0.0:8080: steals the HTTP requests matching `feature.network.incoming.http_filter` from remote port 80 (from `feature.network.incoming.port_mapping`)")]
+    #[case(
+        "0.0.0.0:9000",
+        "incoming 0.0.0.0:9000: steals all traffic from remote port 9000"
+    )]
+    #[case(
+        "0.0.0.0:9999",
+        "incoming 0.0.0.0:9999: local only, 9999 is not in `feature.network.incoming.ports`"
+    )]
+    #[case(
+        "0.0.0.0:4000",
+        "incoming 0.0.0.0:4000: local only, 4000 is in `feature.network.incoming.ignore_ports`"
+    )]
+    fn incoming(#[case] address: SocketAddr, #[case] expected: &str) {
+        let config: IncomingConfig =
+            serde_json::from_value::<IncomingFileConfig>(serde_json::json!({
+                "mode": "steal",
+                "port_mapping": [[8080, 80]],
+                "ignore_ports": [4000],
+                "ports": [9000],
+                "http_filter": { "header_filter": "x-user: me", "ports": [80] }
+            }))
+            .unwrap()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(explain_incoming(&config, address), expected);
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::HashMap;
 
 use mirrord_analytics::{AnalyticsError, AnalyticsReporter, Reporter};
-use mirrord_config::{LayerConfig, MIRRORD_CONFIG_FILE_ENV};
+use mirrord_config::{LayerConfig, MIRRORD_CONFIG_FILE_ENV, MIRRORD_CONFIG_VARS_ENV};
 use mirrord_progress::{JsonProgress, Progress, ProgressTracker};
 
-use crate::{config::ExtensionExecArgs, error::CliError, execution::MirrordExecution, CliResult};
+use crate::{
+    config::{config_vars_env, ExtensionExecArgs},
+    error::CliError,
+    execution::MirrordExecution,
+    CliResult,
+};
 
 /// Actually facilitate execution after all preparations were complete
 async fn mirrord_exec<P>(
@@ -57,6 +62,11 @@ pub(crate) async fn extension_exec(args: ExtensionExecArgs, watch: drain::Watch)
         std::env::set_var("MIRRORD_IMPERSONATED_TARGET", target.clone());
         env.insert("MIRRORD_IMPERSONATED_TARGET".into(), target.to_string());
     }
+    // Resolve the config template variables here, so that the application renders the config the
+    // same way.
+    let config_vars = config_vars_env(None, &[])?;
+    std::env::set_var(MIRRORD_CONFIG_VARS_ENV, &config_vars);
+    env.insert(MIRRORD_CONFIG_VARS_ENV.into(), config_vars);
     let (config, mut context) = LayerConfig::from_env_with_warnings()?;
 
     let mut analytics = AnalyticsReporter::only_error(config.telemetry, Default::default(), watch);
@@ -0,0 +1,205 @@
+//! Implementation of the `mirrord inspect` command, which shows the live events of a running
+//! mirrord session, served by the [`Inspector`] of its internal proxy.
+
+use std::{fs, io, path::PathBuf};
+
+use mirrord_intproxy::inspector::{Inspector, InspectorEvent, InspectorRecord};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::UnixStream,
+};
+use tracing::Level;
+
+use crate::{config::InspectArgs, CliError, CliResult};
+
+/// Connects to the socket of the most recently started session in [`Inspector::socket_dir`].
+///
+/// Removes the sockets left behind by sessions that are no longer running.
+async fn connect_latest() -> CliResult<(PathBuf, UnixStream)> {
+    let dir = Inspector::socket_dir();
+
+    let mut sockets = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect::<Vec<_>>();
+    sockets.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    for (_, path) in sockets {
+        match UnixStream::connect(&path).await {
+            Ok(stream) => return Ok((path, stream)),
+            Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+                let _ = fs::remove_file(&path);
+            }
+            Err(error) => {
+                tracing::debug!(%error, ?path, "Failed to connect to an inspector socket");
+            }
+        }
+    }
+
+    Err(CliError::InspectorNotFound(dir))
+}
+
+/// Formats the UTC time of day of the given timestamp (milliseconds since the UNIX epoch).
+fn time_of_day(timestamp: u64) -> String {
+    let millis = timestamp % 1000;
+    let seconds = timestamp / 1000 % 86_400;
+
+    format!(
+        "{:02}:{:02}:{:02}.{millis:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn describe(event: &InspectorEvent) -> String {
+    match event {
+        InspectorEvent::LayerConnected {
+            layer_id,
+            parent_id: None,
+        } => format!("layer {layer_id} connected"),
+        InspectorEvent::LayerConnected {
+            layer_id,
+            parent_id: Some(parent_id),
+        } => format!("layer {layer_id} connected, forked from layer {parent_id}"),
+        InspectorEvent::PortSubscribed {
+            layer_id,
+            port,
+            steal,
+            filter,
+        } => {
+            let mode = if *steal { "stealing" } else { "mirroring" };
+            match filter {
+                Some(filter) => {
+                    format!("layer {layer_id} {mode} port {port} with filter `{filter}`")
+                }
+                None => format!("layer {layer_id} {mode} port {port}"),
+            }
+        }
+        InspectorEvent::IncomingConnection {
+            connection_id,
+            stolen,
+            source,
+            destination_port,
+        } => {
+            let mode = if *stolen { "stolen" } else { "mirrored" };
+            format!("{mode} connection {connection_id} from {source} to port {destination_port}")
+        }
+        InspectorEvent::HttpRequest {
+            connection_id,
+            port,
+            method,
+            uri,
+            filter,
+            ..
+        } => {
+            let description = format!(
+                "stolen HTTP request {method} {uri} on port {port} (connection {connection_id})"
+            );
+            match filter {
+                Some(filter) => format!("{description} matched filter `{filter}`"),
+                None => description,
+            }
+        }
+        InspectorEvent::FileOpened { layer_id, path } => {
+            format!("layer {layer_id} opened remote file {}", path.display())
+        }
+        InspectorEvent::DnsQuery { layer_id, name } => {
+            format!("layer {layer_id} resolved `{name}` with the remote DNS")
+        }
+        InspectorEvent::OutgoingConnect {
+            layer_id,
+            remote_address,
+            udp,
+        } => {
+            let protocol = if *udp { "udp" } else { "tcp" };
+            format!(
+                "layer {layer_id} connecting to {protocol}://{remote_address} through the target"
+            )
+        }
+    }
+}
+
+/// Prints the events from the inspector socket until the session ends.
+#[tracing::instrument(level = Level::TRACE, ret)]
+pub(crate) async fn inspect_command(args: InspectArgs) -> CliResult<()> {
+    let (path, stream) = match args.socket {
+        Some(path) => {
+            let stream = UnixStream::connect(&path)
+                .await
+                .map_err(|error| CliError::InspectorConnection(path.clone(), error))?;
+            (path, stream)
+        }
+        None => connect_latest().await?,
+    };
+
+    if !args.json {
+        eprintln!(
+            "Showing events of the session at `{}`, press Ctrl+C to stop.",
+            path.display()
+        );
+    }
+
+    let mut lines = BufReader::new(stream).lines();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|error| CliError::InspectorConnection(path.clone(), error))?
+    {
+        if args.json {
+            println!("{line}");
+            continue;
+        }
+
+        match serde_json::from_str::<InspectorRecord>(&line) {
+            Ok(record) => println!(
+                "{} UTC  {}",
+                time_of_day(record.timestamp),
+                describe(&record.event)
+            ),
+            Err(error) => tracing::debug!(%error, line, "Skipping an unknown inspector event"),
+        }
+    }
+
+    if !args.json {
+        eprintln!("The session has ended.");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(0, "00:00:00.000")]
+    #[case(1_700_000_123_456, "22:15:23.456")]
+    fn format_time_of_day(#[case] timestamp: u64, #[case] expected: &str) {
+        assert_eq!(time_of_day(timestamp), expected);
+    }
+
+    #[test]
+    fn describe_http_request() {
+        let event = InspectorEvent::HttpRequest {
+            connection_id: 3,
+            request_id: 0,
+            port: 80,
+            method: "GET".into(),
+            uri: "/api/v1".into(),
+            filter: Some("header=x-user: me".into()),
+        };
+
+        assert_eq!(
+            describe(&event),
+            "stolen HTTP request GET /api/v1 on port 80 (connection 3) matched filter `header=x-user: me`"
+        );
+    }
+}
@@ -20,10 +20,15 @@ use std::{
 };
 
 use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, Reporter};
-use mirrord_config::LayerConfig;
+use mirrord_config::{LayerConfig, MIRRORD_CONFIG_FILE_ENV};
 use mirrord_intproxy::{
-    agent_conn::{AgentConnectInfo, AgentConnection},
+    agent_conn::{AgentConnectInfo, AgentConnection, AgentReconnect},
     error::IntProxyError,
+    inspector::Inspector,
+    proxies::{
+        incoming::{recorder::HttpRecorder, rewrite::HttpRewrites},
+        outgoing::{faults::OutgoingFaults, mock::OutgoingMocks},
+    },
     IntProxy,
 };
 use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, LogMessage};
@@ -127,7 +132,26 @@ pub(crate) async fn proxy(
     // **before** this happens to ensure that the agent does not prematurely exit.
     // We also perform initial ping pong round to ensure that k8s runtime actually made connection
     // with the agent (it's a must, because port forwarding may be done lazily).
-    let agent_conn = connect_and_ping(&config, agent_connect_info, &mut analytics).await?;
+    let agent_conn = connect_and_ping(&config, agent_connect_info.clone(), &mut analytics).await?;
+
+    let http_recorder = config
+        .feature
+        .network
+        .incoming
+        .http_record
+        .as_deref()
+        .map(|path| {
+            HttpRecorder::create(path)
+                .map_err(|error| InternalProxyError::OpenHttpRecordFile(path.to_owned(), error))
+        })
+        .transpose()?;
+    let http_rewrites = HttpRewrites::new(&config.feature.network.incoming.http_rewrite)?;
+    let config_file = env::var_os(MIRRORD_CONFIG_FILE_ENV).map(PathBuf::from);
+    let outgoing_mocks = OutgoingMocks::new(
+        &config.feature.network.outgoing.mock,
+        config_file.as_deref(),
+    )?;
+    let outgoing_faults = OutgoingFaults::new(&config.feature.network.outgoing.faults)?;
 
     // Let it assign address for us then print it for the user.
     let listener = create_listen_socket(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), listen_port))
@@ -141,17 +165,44 @@ pub(crate) async fn proxy(
     let first_connection_timeout = Duration::from_secs(config.internal_proxy.start_idle_timeout);
     let consecutive_connection_timeout = Duration::from_secs(config.internal_proxy.idle_timeout);
 
-    IntProxy::new_with_connection(
+    let mut intproxy = IntProxy::new_with_connection(
         agent_conn,
         listener,
         config.experimental.readonly_file_buffer,
     )
-    .run(first_connection_timeout, consecutive_connection_timeout)
-    .await
-    .map_err(InternalProxyError::from)
-    .inspect_err(|error| {
-        tracing::error!(%error, "Internal proxy encountered an error, exiting");
-    })
+    .with_http_rewrites(http_rewrites)
+    .with_outgoing_mocks(outgoing_mocks)
+    .with_outgoing_faults(outgoing_faults);
+    if let Some(http_recorder) = http_recorder {
+        intproxy = intproxy.with_http_recorder(http_recorder);
+    }
+    if let Some(policy) = agent_connect_info
+        .as_ref()
+        .and_then(AgentConnectInfo::policy)
+    {
+        intproxy = intproxy.with_policy(policy.clone());
+    }
+    if config.internal_proxy.reconnect {
+        let timeout = Duration::from_secs(config.internal_proxy.reconnect_timeout);
+        intproxy = intproxy.with_reconnect(AgentReconnect::new(
+            config.clone(),
+            agent_connect_info,
+            timeout,
+        ));
+    }
+    if config.internal_proxy.inspector {
+        let inspector = Inspector::bind()
+            .map_err(|error| InternalProxyError::InspectorSetup(Inspector::socket_dir(), error))?;
+        intproxy = intproxy.with_inspector(inspector);
+    }
+
+    intproxy
+        .run(first_connection_timeout, consecutive_connection_timeout)
+        .await
+        .map_err(InternalProxyError::from)
+        .inspect_err(|error| {
+            tracing::error!(%error, "Internal proxy encountered an error, exiting");
+        })
 }
 
 /// Creates a connection with the agent and handles one round of ping pong.
@@ -30,7 +30,8 @@ use mirrord_config::{
             incoming::IncomingMode,
         },
     },
-    LayerConfig, LayerFileConfig, MIRRORD_CONFIG_FILE_ENV,
+    LayerConfig, LayerFileConfig, MIRRORD_CONFIG_FILE_ENV, MIRRORD_CONFIG_VARS_ENV,
+    MIRRORD_PROFILE_ENV,
 };
 use mirrord_intproxy::agent_conn::{AgentConnection, AgentConnectionError};
 use mirrord_kube::api::kubernetes::{create_kube_config, seeker::KubeResourceSeeker};
@@ -53,12 +54,15 @@ mod container;
 mod diagnose;
 mod error;
 mod execution;
+mod explain_config;
 mod extension;
 mod external_proxy;
 mod extract;
+mod inspect;
 mod internal_proxy;
 mod operator;
 pub mod port_forward;
+mod replay;
 mod teams;
 mod util;
 mod verify_config;
@@ -576,6 +580,15 @@ async fn port_forward(args: &PortForwardArgs, watch: drain::Watch) -> CliResult<
         std::env::set_var("MIRRORD_CONFIG_FILE", config_file);
     }
 
+    if let Some(profile) = &args.profile {
+        std::env::set_var(MIRRORD_PROFILE_ENV, profile);
+    }
+
+    std::env::set_var(
+        MIRRORD_CONFIG_VARS_ENV,
+        config::config_vars_env(args.context.as_deref(), &args.config_vars)?,
+    );
+
     let (config, mut context) = LayerConfig::from_env_with_warnings()?;
 
     let mut analytics = AnalyticsReporter::new(config.telemetry, ExecutionKind::PortForward, watch);
@@ -587,6 +600,7 @@ async fn port_forward(args: &PortForwardArgs, watch: drain::Watch) -> CliResult<
     }
     let (connection_info, connection) =
         create_and_connect(&config, &mut progress, &mut analytics).await?;
+    let policy = connection_info.policy().cloned().unwrap_or_default();
 
     // errors from AgentConnection::new get mapped to CliError manually to prevent unreadably long
     // error print-outs
@@ -622,6 +636,7 @@ async fn port_forward(args: &PortForwardArgs, watch: drain::Watch) -> CliResult<
                     connection_2,
                     rev_port_mappings,
                     config.feature.network.incoming,
+                    policy,
                 )
                 .await?;
                 port_forward.run().await.map_err(|error| error.into())
@@ -702,6 +717,9 @@ fn main() -> miette::Result<()> {
             Commands::ExternalProxy { port } => external_proxy::proxy(port, watch).await?,
             Commands::PortForward(args) => port_forward(&args, watch).await?,
             Commands::Vpn(args) => vpn::vpn_command(*args).await?,
+            Commands::Replay(args) => replay::replay_command(*args).await?,
+            Commands::Inspect(args) => inspect::inspect_command(*args).await?,
+            Commands::Config(args) => explain_config::config_command(*args).await?,
         };
 
         Ok(())
@@ -5,30 +5,29 @@ use std::{
 };
 
 use futures::StreamExt;
-use mirrord_config::feature::network::incoming::{
-    http_filter::{HttpFilterConfig, InnerFilter},
-    IncomingConfig,
-};
+use mirrord_config::feature::network::incoming::IncomingConfig;
 use mirrord_intproxy::{
     background_tasks::{BackgroundTasks, TaskError, TaskSender, TaskUpdate},
     error::IntProxyError,
     main_tasks::{MainTaskId, ProxyMessage, ToLayer},
     proxies::incoming::{
-        port_subscription_ext::PortSubscriptionExt, IncomingProxy, IncomingProxyError,
-        IncomingProxyMessage,
+        port_subscription_ext::PortSubscriptionExt,
+        rewrite::{HttpRewriteError, HttpRewrites},
+        IncomingProxy, IncomingProxyError, IncomingProxyMessage,
     },
 };
 use mirrord_intproxy_protocol::{
-    IncomingRequest, IncomingResponse, LayerId, PortSubscribe, PortSubscription,
-    ProxyToLayerMessage,
+    http_filter::steal_http_filter, IncomingRequest, IncomingResponse, LayerId, PortSubscribe,
+    PortSubscription, ProxyToLayerMessage,
 };
+use mirrord_operator::client::policy::ClientPolicy;
 use mirrord_protocol::{
     dns::{DnsLookup, GetAddrInfoRequest, GetAddrInfoResponse, LookupRecord},
     outgoing::{
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
         LayerClose, LayerConnect, LayerWrite, SocketAddress,
     },
-    tcp::{Filter, HttpFilter, LayerTcp, LayerTcpSteal, StealType},
+    tcp::{HttpFilter, LayerTcp, LayerTcpSteal, StealType},
     ClientMessage, ConnectionId, DaemonMessage, LogLevel, Port, ResponseError,
     CLIENT_READY_FOR_LOGS,
 };
@@ -448,14 +447,29 @@ impl ReversePortForwarder {
         agent_connection: AgentConnection,
         mappings: HashMap<RemotePort, LocalPort>,
         network_config: IncomingConfig,
+        policy: ClientPolicy,
     ) -> Result<Self, PortForwardError> {
+        // construct IncomingMode from config file
+        let incoming_mode = IncomingMode::new(&network_config);
+
+        // we subscribe with the agent directly in `run`, so the policy has to be checked here
+        for &remote in mappings.keys() {
+            if let PortSubscription::Steal(steal_type) = incoming_mode.subscription(remote) {
+                policy.check_steal(&steal_type).map_err(|error| {
+                    IntProxyError::from(IncomingProxyError::SubscriptionFailed(error))
+                })?;
+            }
+        }
+
         // setup IncomingProxy
         let mut background_tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError> =
             Default::default();
-        let incoming =
-            background_tasks.register(IncomingProxy::default(), MainTaskId::IncomingProxy, 512);
-        // construct IncomingMode from config file
-        let incoming_mode = IncomingMode::new(&network_config);
+        let http_rewrites = HttpRewrites::new(&network_config.http_rewrite)?;
+        let incoming = background_tasks.register(
+            IncomingProxy::new(None, http_rewrites, policy),
+            MainTaskId::IncomingProxy,
+            512,
+        );
         for (i, (&remote, &local)) in mappings.iter().enumerate() {
             // send subscription to incoming proxy
             let subscription = incoming_mode.subscription(remote);
@@ -883,74 +897,13 @@ impl IncomingMode {
 
         let ports = { http_filter_config.ports.iter().copied().collect() };
 
-        // Matching all fields to make this check future-proof.
-        let filter = match http_filter_config {
-            HttpFilterConfig {
-                path_filter: Some(path),
-                header_filter: None,
-                all_of: None,
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::Filter(HttpFilter::Path(
-                Filter::new(path.into()).expect("invalid filter expression"),
-            )),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: Some(header),
-                all_of: None,
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::Filter(HttpFilter::Header(
-                Filter::new(header.into()).expect("invalid filter expression"),
-            )),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: None,
-                all_of: Some(filters),
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::Filter(Self::make_composite_filter(true, filters)),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: None,
-                all_of: None,
-                any_of: Some(filters),
-                ports: _ports,
-            } => StealHttpFilter::Filter(Self::make_composite_filter(false, filters)),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: None,
-                all_of: None,
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::None,
-
-            _ => panic!("multiple HTTP filters specified, this is a bug"),
-        };
+        let filter = steal_http_filter(http_filter_config)
+            .map(StealHttpFilter::Filter)
+            .unwrap_or(StealHttpFilter::None);
 
         Self::Steal(StealHttpSettings { filter, ports })
     }
 
-    fn make_composite_filter(all: bool, filters: &[InnerFilter]) -> HttpFilter {
-        let filters = filters
-            .iter()
-            .map(|filter| match filter {
-                InnerFilter::Path { path } => {
-                    HttpFilter::Path(Filter::new(path.clone()).expect("invalid filter expression"))
-                }
-                InnerFilter::Header { header } => HttpFilter::Header(
-                    Filter::new(header.clone()).expect("invalid filter expression"),
-                ),
-            })
-            .collect();
-
-        HttpFilter::Composite { all, filters }
-    }
-
     /// Returns [`PortSubscription`] request to be used for the given port.
     fn subscription(&self, port: Port) -> PortSubscription {
         let Self::Steal(steal) = self else {
@@ -1015,6 +968,9 @@ pub enum PortForwardError {
 
     #[error("failed to subscribe to remote port: `{0}`")]
     SubscriptionError(ResponseError),
+
+    #[error("invalid HTTP rewrite rules: `{0}`")]
+    HttpRewrite(#[from] HttpRewriteError),
 }
 
 impl From<mpsc::error::SendError<ClientMessage>> for PortForwardError {
@@ -1330,10 +1286,14 @@ mod test {
         let network_config = IncomingConfig::default();
 
         tokio::spawn(async move {
-            let mut port_forwarder =
-                ReversePortForwarder::new(agent_connection, mappings, network_config)
-                    .await
-                    .unwrap();
+            let mut port_forwarder = ReversePortForwarder::new(
+                agent_connection,
+                mappings,
+                network_config,
+                Default::default(),
+            )
+            .await
+            .unwrap();
             port_forwarder.run().await.unwrap()
         });
 
@@ -1423,10 +1383,14 @@ mod test {
         };
 
         tokio::spawn(async move {
-            let mut port_forwarder =
-                ReversePortForwarder::new(agent_connection, mappings, network_config)
-                    .await
-                    .unwrap();
+            let mut port_forwarder = ReversePortForwarder::new(
+                agent_connection,
+                mappings,
+                network_config,
+                Default::default(),
+            )
+            .await
+            .unwrap();
             port_forwarder.run().await.unwrap()
         });
 
@@ -1534,10 +1498,14 @@ mod test {
         let network_config = IncomingConfig::default();
 
         tokio::spawn(async move {
-            let mut port_forwarder =
-                ReversePortForwarder::new(agent_connection, mappings, network_config)
-                    .await
-                    .unwrap();
+            let mut port_forwarder = ReversePortForwarder::new(
+                agent_connection,
+                mappings,
+                network_config,
+                Default::default(),
+            )
+            .await
+            .unwrap();
             port_forwarder.run().await.unwrap()
         });
 
@@ -1677,10 +1645,14 @@ mod test {
         network_config.http_filter.header_filter = Some("header: value".to_string());
 
         tokio::spawn(async move {
-            let mut port_forwarder =
-                ReversePortForwarder::new(agent_connection, mappings, network_config)
-                    .await
-                    .unwrap();
+            let mut port_forwarder = ReversePortForwarder::new(
+                agent_connection,
+                mappings,
+                network_config,
+                Default::default(),
+            )
+            .await
+            .unwrap();
             port_forwarder.run().await.unwrap()
         });
 
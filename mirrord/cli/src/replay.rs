@@ -0,0 +1,160 @@
+//! Implementation of the `mirrord replay` command, which sends HTTP requests recorded with
+//! `feature.network.incoming.http_record` to a local process.
+
+use std::net::SocketAddr;
+
+use mirrord_intproxy::proxies::incoming::recorder::RecordedExchange;
+use mirrord_progress::{Progress, ProgressTracker};
+use reqwest::{
+    header::{CONTENT_LENGTH, TRANSFER_ENCODING},
+    redirect::Policy,
+    Client, Request, Version,
+};
+use tracing::Level;
+
+use crate::{config::ReplayArgs, CliError, CliResult};
+
+/// Builds a [`Request`] that sends the recorded request to the process listening on the given
+/// `address`.
+///
+/// The body is sent as a whole, so the original framing headers are dropped.
+fn build_request(
+    client: &Client,
+    address: SocketAddr,
+    exchange: &RecordedExchange,
+) -> reqwest::Result<Request> {
+    let recorded = &exchange.request;
+    let path = recorded
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+
+    let mut headers = recorded.headers.clone();
+    headers.remove(CONTENT_LENGTH);
+    headers.remove(TRANSFER_ENCODING);
+
+    client
+        .request(recorded.method.clone(), format!("http://{address}{path}"))
+        .headers(headers)
+        .body(recorded.body.0.clone())
+        .build()
+}
+
+/// Sends all requests from the recorded file to the local process, one at a time, and reports the
+/// statuses of the responses next to the recorded ones.
+#[tracing::instrument(level = Level::TRACE, ret)]
+pub(crate) async fn replay_command(args: ReplayArgs) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord replay");
+
+    let exchanges = RecordedExchange::read_all(&args.file)
+        .map_err(|error| CliError::ReplayFileRead(args.file.clone(), error))?;
+
+    let http1_client = Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .map_err(CliError::ReplayClient)?;
+    let http2_client = Client::builder()
+        .redirect(Policy::none())
+        .http2_prior_knowledge()
+        .build()
+        .map_err(CliError::ReplayClient)?;
+
+    let mut sent = 0;
+    let mut mismatched = 0;
+    for exchange in exchanges
+        .iter()
+        .filter(|exchange| args.port.is_none_or(|port| port == exchange.port))
+    {
+        let client = if exchange.request.version == Version::HTTP_2 {
+            &http2_client
+        } else {
+            &http1_client
+        };
+        let description = format!("{} {}", exchange.request.method, exchange.request.uri);
+
+        let result = match build_request(client, args.address, exchange) {
+            Ok(request) => client.execute(request).await,
+            Err(error) => Err(error),
+        };
+        sent += 1;
+
+        match result {
+            Ok(response) if response.status() == exchange.response.status => {
+                progress.info(&format!("{description} -> {}", response.status()));
+            }
+            Ok(response) => {
+                mismatched += 1;
+                progress.warning(&format!(
+                    "{description} -> {}, recorded {}",
+                    response.status(),
+                    exchange.response.status
+                ));
+            }
+            Err(error) => {
+                mismatched += 1;
+                progress.warning(&format!("{description} failed: {error}"));
+            }
+        }
+    }
+
+    progress.success(Some(&format!(
+        "replayed {sent} requests, {mismatched} did not get the recorded status"
+    )));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_intproxy::proxies::incoming::recorder::RecordedBody;
+    use mirrord_protocol::tcp::{InternalHttpRequest, InternalHttpResponse};
+    use reqwest::{Method, StatusCode};
+
+    use super::*;
+
+    #[test]
+    fn request_to_local_address() {
+        let exchange = RecordedExchange {
+            connection_id: 0,
+            request_id: 0,
+            port: 80,
+            request: InternalHttpRequest {
+                method: Method::PUT,
+                uri: "http://remote.svc/items/1?force=true".parse().unwrap(),
+                headers: [
+                    ("x-user", "me"),
+                    ("content-length", "100"),
+                    ("transfer-encoding", "chunked"),
+                ]
+                .into_iter()
+                .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+                .collect(),
+                version: Version::HTTP_11,
+                body: RecordedBody(b"item".to_vec()),
+            },
+            response: InternalHttpResponse {
+                status: StatusCode::OK,
+                version: Version::HTTP_11,
+                headers: Default::default(),
+                body: Default::default(),
+            },
+        };
+
+        let request =
+            build_request(&Client::new(), "127.0.0.1:8080".parse().unwrap(), &exchange).unwrap();
+
+        assert_eq!(request.method(), Method::PUT);
+        assert_eq!(
+            request.url().as_str(),
+            "http://127.0.0.1:8080/items/1?force=true"
+        );
+        assert_eq!(request.headers().get("x-user").unwrap(), "me");
+        assert!(request.headers().get(CONTENT_LENGTH).is_none());
+        assert!(request.headers().get(TRANSFER_ENCODING).is_none());
+        assert_eq!(
+            request.body().and_then(|body| body.as_bytes()),
+            Some(b"item".as_slice())
+        );
+    }
+}
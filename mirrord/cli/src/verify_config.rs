@@ -7,9 +7,11 @@ use mirrord_config::{
     config::{ConfigContext, MirrordConfig},
     feature::FeatureConfig,
     target::{
-        cron_job::CronJobTarget, deployment::DeploymentTarget, job::JobTarget, pod::PodTarget,
-        rollout::RolloutTarget, stateful_set::StatefulSetTarget, Target, TargetConfig,
+        cron_job::CronJobTarget, daemon_set::DaemonSetTarget, deployment::DeploymentTarget,
+        job::JobTarget, pod::PodTarget, replica_set::ReplicaSetTarget, rollout::RolloutTarget,
+        service::ServiceTarget, stateful_set::StatefulSetTarget, Target, TargetConfig,
     },
+    MIRRORD_PROFILE_ENV,
 };
 use serde::Serialize;
 
@@ -43,6 +45,15 @@ enum VerifiedTarget {
 
     #[serde(untagged)]
     StatefulSet(StatefulSetTarget),
+
+    #[serde(untagged)]
+    Service(ServiceTarget),
+
+    #[serde(untagged)]
+    ReplicaSet(ReplicaSetTarget),
+
+    #[serde(untagged)]
+    DaemonSet(DaemonSetTarget),
 }
 
 impl From<Target> for VerifiedTarget {
@@ -54,6 +65,9 @@ impl From<Target> for VerifiedTarget {
             Target::Job(target) => Self::Job(target),
             Target::CronJob(target) => Self::CronJob(target),
             Target::StatefulSet(target) => Self::StatefulSet(target),
+            Target::Service(target) => Self::Service(target),
+            Target::ReplicaSet(target) => Self::ReplicaSet(target),
+            Target::DaemonSet(target) => Self::DaemonSet(target),
             Target::Targetless => Self::Targetless,
         }
     }
@@ -69,6 +83,9 @@ impl From<VerifiedTarget> for TargetType {
             VerifiedTarget::Job(_) => TargetType::Job,
             VerifiedTarget::CronJob(_) => TargetType::CronJob,
             VerifiedTarget::StatefulSet(_) => TargetType::StatefulSet,
+            VerifiedTarget::Service(_) => TargetType::Service,
+            VerifiedTarget::ReplicaSet(_) => TargetType::ReplicaSet,
+            VerifiedTarget::DaemonSet(_) => TargetType::DaemonSet,
         }
     }
 }
@@ -99,6 +116,9 @@ enum TargetType {
     Job,
     CronJob,
     StatefulSet,
+    Service,
+    ReplicaSet,
+    DaemonSet,
 }
 
 impl core::fmt::Display for TargetType {
@@ -111,6 +131,9 @@ impl core::fmt::Display for TargetType {
             TargetType::Job => "job",
             TargetType::CronJob => "cronjob",
             TargetType::StatefulSet => "statefulset",
+            TargetType::Service => "service",
+            TargetType::ReplicaSet => "replicaset",
+            TargetType::DaemonSet => "daemonset",
         };
 
         f.write_str(stringifed)
@@ -127,13 +150,20 @@ impl TargetType {
             Self::Job,
             Self::CronJob,
             Self::StatefulSet,
+            Self::Service,
+            Self::ReplicaSet,
+            Self::DaemonSet,
         ]
         .into_iter()
     }
 
     fn compatible_with(&self, config: &FeatureConfig) -> bool {
         match self {
-            Self::Targetless | Self::Rollout => !config.copy_target.enabled,
+            Self::Targetless
+            | Self::Rollout
+            | Self::Service
+            | Self::ReplicaSet
+            | Self::DaemonSet => !config.copy_target.enabled,
             Self::Pod => !(config.copy_target.enabled && config.copy_target.scale_down),
             Self::Job | Self::CronJob => config.copy_target.enabled,
             Self::Deployment | Self::StatefulSet => true,
@@ -202,12 +232,16 @@ enum VerifiedConfig {
 /// }
 /// ```
 pub(super) async fn verify_config(
-    VerifyConfigArgs { ide, path }: VerifyConfigArgs,
+    VerifyConfigArgs { ide, profile, path }: VerifyConfigArgs,
 ) -> CliResult<()> {
     let mut config_context = ConfigContext::new(ide);
 
-    let layer_config = LayerFileConfig::from_path(path)
-        .and_then(|config| config.generate_config(&mut config_context))
+    let profile = profile.or_else(|| std::env::var(MIRRORD_PROFILE_ENV).ok());
+    let layer_config = LayerFileConfig::from_path_with_sources(path, profile.as_deref())
+        .and_then(|(config, sources)| {
+            config_context = ConfigContext::new(ide).with_sources(sources);
+            config.generate_config(&mut config_context)
+        })
         .and_then(|config| {
             config.verify(&mut config_context)?;
             Ok(config)
@@ -134,7 +134,14 @@ impl ConfigField {
 
         // Rest of flow is irrelevant for nested config.
         if flags.nested {
-            return quote! { #ident: self.#ident.unwrap_or_default().generate_config(context)? };
+            return quote! {
+                #ident: {
+                    context.enter_section(stringify!(#ident));
+                    let generated = self.#ident.unwrap_or_default().generate_config(context);
+                    context.exit_section();
+                    generated?
+                }
+            };
         }
 
         let mut impls = Vec::new();
@@ -149,7 +156,7 @@ impl ConfigField {
 
         if let Some(lit) = flags.deprecated.as_ref() {
             layers.push(
-                quote! { .layer(|next| crate::config::deprecated::Deprecated::new(#lit, next)) },
+                quote! { .layer(|next| crate::config::deprecated::Deprecated::new(stringify!(#ident), #lit, next)) },
             );
         }
 
@@ -177,10 +184,15 @@ impl ConfigField {
             .into_iter()
             .reduce(|acc, impl_| quote! { #acc.or(#impl_) });
 
+        let record_env = flags
+            .env
+            .as_ref()
+            .map(|EnvFlag(flag)| quote! { context.record_env_source(stringify!(#ident), #flag); });
+
         if layers.is_empty() {
-            quote! { #ident: #impls .source_value(context).transpose()?#unwrapper }
+            quote! { #ident: { #record_env #impls .source_value(context).transpose()?#unwrapper } }
         } else {
-            quote! { #ident: #impls #(#layers),* .source_value(context).transpose()?#unwrapper }
+            quote! { #ident: { #record_env #impls #(#layers),* .source_value(context).transpose()?#unwrapper } }
         }
     }
 }
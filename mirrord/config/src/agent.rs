@@ -350,6 +350,21 @@ pub struct AgentConfig {
     /// ```
     pub service_account: Option<String>,
 
+    /// ### agent.multi_pod {#agent-multi_pod}
+    ///
+    /// When the target has multiple pods (e.g. a deployment with several replicas), spawns one
+    /// agent per ready pod, instead of a single agent for the first pod.
+    ///
+    /// Incoming traffic is then mirrored or stolen from all of the pods, while everything else
+    /// (files, env, DNS, outgoing traffic) goes through the agent of the first pod.
+    ///
+    /// Only used when running without the mirrord operator, which handles multi-pod targets on
+    /// its own.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_AGENT_MULTI_POD", default = false)]
+    pub multi_pod: bool,
+
     /// <!--${internal}-->
     /// Create an agent that returns an error after accepting the first client. For testing
     /// purposes. Only supported with job agents (not with ephemeral agents).
@@ -452,6 +467,7 @@ impl MirrordConfig for AgentImageFileConfig {
 impl CollectAnalytics for &AgentConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("ephemeral", self.ephemeral);
+        analytics.add("multi_pod", self.multi_pod);
     }
 }
 
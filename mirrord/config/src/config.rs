@@ -1,13 +1,15 @@
 pub mod deprecated;
+pub mod file;
 pub mod from_env;
 pub mod source;
+pub mod template;
 pub mod unstable;
 
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
 
 use thiserror::Error;
 
-use crate::feature::split_queues::QueueSplittingVerificationError;
+use crate::{config::file::ConfigSources, feature::split_queues::QueueSplittingVerificationError};
 
 /// <!--${internal}-->
 /// Error that would be returned from [MirrordConfig::generate_config]
@@ -44,6 +46,14 @@ pub enum ConfigError {
     #[error("mirrord-config: Unsupported configuration file format!")]
     UnsupportedFormat,
 
+    #[error("mirrord-config: config file `{0}` extends itself through the `extends` chain!")]
+    ExtendsCycle(PathBuf),
+
+    #[error(
+        "mirrord-config: profile `{0}` not found in the config file, available profiles: [{1}]"
+    )]
+    ProfileNotFound(String, String),
+
     #[error("Invalid FS mode `{0}`!")]
     InvalidFsMode(String),
 
@@ -116,6 +126,15 @@ pub struct ConfigContext {
     ///
     /// Some _target_ related errors become warning when `ide == true`.
     warnings: Vec<String>,
+
+    /// Which config files set the config values, mentioned in the warnings about these values.
+    sources: ConfigSources,
+
+    /// Path of the config section that is being generated, e.g. `["feature", "network"]`.
+    section: Vec<&'static str>,
+
+    /// Env variables that set the config values, keyed by the value path.
+    env_sources: BTreeMap<String, &'static str>,
 }
 
 impl ConfigContext {
@@ -126,10 +145,80 @@ impl ConfigContext {
         }
     }
 
+    pub fn with_sources(mut self, sources: ConfigSources) -> Self {
+        self.sources = sources;
+        self
+    }
+
     pub fn add_warning(&mut self, warning: String) {
         self.warnings.push(warning);
     }
 
+    /// Adds a warning about the value of `field` in the current config section.
+    ///
+    /// When the value comes from another config file than the one that was loaded (through
+    /// `extends` or a profile), the warning mentions that file.
+    pub fn add_value_warning(&mut self, field: &str, warning: String) {
+        let path = self.value_path(field);
+
+        let sources = self.sources.get(&path);
+        if sources.is_empty() {
+            self.add_warning(warning);
+        } else {
+            self.add_warning(format!(
+                "{warning} (`{path}` comes from {})",
+                sources.join(" and ")
+            ));
+        }
+    }
+
+    /// Returns the [`ConfigSources`] set with [`ConfigContext::with_sources`].
+    pub fn sources(&self) -> &ConfigSources {
+        &self.sources
+    }
+
+    /// Returns the env variable that set the value at `path` (or one of its parents).
+    pub fn env_source(&self, path: &str) -> Option<&'static str> {
+        let mut current = Some(path);
+        while let Some(path) = current {
+            if let Some(env) = self.env_sources.get(path) {
+                return Some(env);
+            }
+
+            current = path.rsplit_once('.').map(|(parent, _)| parent);
+        }
+
+        None
+    }
+
+    /// Called before generating `field` of the current config section, records `env` as the
+    /// source of the value if the env variable is set.
+    pub(crate) fn record_env_source(&mut self, field: &str, env: &'static str) {
+        if std::env::var_os(env).is_some() {
+            self.env_sources.insert(self.value_path(field), env);
+        }
+    }
+
+    /// Returns the full path of `field` in the current config section.
+    fn value_path(&self, field: &str) -> String {
+        self.section
+            .iter()
+            .copied()
+            .chain(std::iter::once(field))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Called when starting to generate the nested config section `name`.
+    pub(crate) fn enter_section(&mut self, name: &'static str) {
+        self.section.push(name);
+    }
+
+    /// Called when done generating the current nested config section.
+    pub(crate) fn exit_section(&mut self) {
+        self.section.pop();
+    }
+
     pub fn get_warnings(&self) -> &Vec<String> {
         &self.warnings
     }
@@ -1,11 +1,11 @@
 use crate::config::{source::MirrordConfigSource, ConfigContext, Result};
 
 #[derive(Clone)]
-pub struct Deprecated<T>(String, T);
+pub struct Deprecated<T>(&'static str, String, T);
 
 impl<T> Deprecated<T> {
-    pub fn new(message: &'static str, inner: T) -> Self {
-        Deprecated(message.to_owned(), inner)
+    pub fn new(field: &'static str, message: &'static str, inner: T) -> Self {
+        Deprecated(field, message.to_owned(), inner)
     }
 }
 
@@ -16,8 +16,8 @@ where
     type Value = T::Value;
 
     fn source_value(self, context: &mut ConfigContext) -> Option<Result<Self::Value>> {
-        self.1.source_value(context).inspect(|_| {
-            context.add_warning(self.0.to_string());
+        self.2.source_value(context).inspect(|_| {
+            context.add_value_warning(self.0, self.1.to_string());
         })
     }
 }
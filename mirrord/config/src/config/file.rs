@@ -0,0 +1,514 @@
+//! Loading of the config files, with the `extends` and `profiles` keys resolved, see
+//! [`LayerFileConfig::from_path`](crate::LayerFileConfig::from_path).
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+use tera::Tera;
+
+use crate::config::{template, ConfigError, Result};
+
+/// Path to the config file that the config file is based on, relative to the config file.
+const EXTENDS_KEY: &str = "extends";
+
+/// Named partial configs that can be applied on top of the config file.
+const PROFILES_KEY: &str = "profiles";
+
+/// Which config files set the config values, keyed by the value path, e.g.
+/// `feature.network.incoming.mode`.
+///
+/// Used to point the [`ConfigContext`](super::ConfigContext) warnings to the right file when the
+/// config is composed from multiple files or profiles.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+    /// Description of the file that was loaded.
+    root: String,
+    values: BTreeMap<String, String>,
+}
+
+impl ConfigSources {
+    /// Records that the value at `path` was set by `source`, replacing the sources of the values
+    /// it overrides.
+    fn insert(&mut self, path: String, source: &str) {
+        let nested_prefix = format!("{path}.");
+        self.values.retain(|key, _| {
+            !key.starts_with(&nested_prefix) && !path.starts_with(&format!("{key}."))
+        });
+        self.values.insert(path, source.to_owned());
+    }
+
+    /// Records the sources of all values in the given (partial) config.
+    fn insert_all(&mut self, config: &Map<String, Value>, prefix: &str, source: &str) {
+        for (key, value) in config {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{prefix}.{key}")
+            };
+
+            match value {
+                Value::Object(nested) if !nested.is_empty() => {
+                    self.insert_all(nested, &path, source)
+                }
+                _ => self.insert(path, source),
+            }
+        }
+    }
+
+    /// Returns the file that set the value at `path` (or one of its parents).
+    pub fn file(&self, path: &str) -> Option<&str> {
+        let mut current = Some(path);
+        while let Some(path) = current {
+            if let Some(source) = self.values.get(path) {
+                return Some(source);
+            }
+
+            current = path.rsplit_once('.').map(|(parent, _)| parent);
+        }
+
+        None
+    }
+
+    /// Returns the sources of the value at `path` (or of the values nested in it), apart from the
+    /// file that was loaded.
+    pub fn get(&self, path: &str) -> Vec<&str> {
+        if let Some(source) = self.file(path) {
+            return (source != self.root)
+                .then_some(source)
+                .into_iter()
+                .collect();
+        }
+
+        let nested_prefix = format!("{path}.");
+        let mut sources = self
+            .values
+            .range(nested_prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&nested_prefix))
+            .map(|(_, source)| source.as_str())
+            .filter(|source| *source != self.root)
+            .collect::<Vec<_>>();
+        sources.sort_unstable();
+        sources.dedup();
+
+        sources
+    }
+}
+
+/// Loads the config file at `path` together with the files it extends, and applies the given
+/// profile on top.
+pub(crate) fn load(path: &Path, profile: Option<&str>) -> Result<(Value, ConfigSources)> {
+    let mut sources = ConfigSources {
+        root: path.display().to_string(),
+        ..Default::default()
+    };
+
+    let template_context = template::template_context(path)?;
+    let mut config = load_extended(path, &template_context, &mut vec![], &mut sources)?;
+
+    let profiles = match config.remove(PROFILES_KEY) {
+        None | Some(Value::Null) => Map::new(),
+        Some(Value::Object(profiles)) => profiles,
+        Some(other) => {
+            return Err(ConfigError::InvalidValue {
+                name: PROFILES_KEY,
+                provided: other.to_string(),
+                error: "expected a map of profile names to configs".into(),
+            })
+        }
+    };
+
+    let (profile_sources, values): (BTreeMap<_, _>, _) = std::mem::take(&mut sources.values)
+        .into_iter()
+        .partition(|(path, _)| path.split('.').next() == Some(PROFILES_KEY));
+    sources.values = values;
+
+    if let Some(name) = profile {
+        let profile = match profiles.get(name) {
+            Some(Value::Object(profile)) => profile.clone(),
+            Some(other) => {
+                return Err(ConfigError::InvalidValue {
+                    name: PROFILES_KEY,
+                    provided: other.to_string(),
+                    error: format!("profile `{name}` is not a config").into(),
+                })
+            }
+            None => {
+                return Err(ConfigError::ProfileNotFound(
+                    name.to_owned(),
+                    profiles.keys().cloned().collect::<Vec<_>>().join(", "),
+                ))
+            }
+        };
+
+        let profile_prefix = format!("{PROFILES_KEY}.{name}.");
+        for (path, source) in profile_sources {
+            if let Some(path) = path.strip_prefix(&profile_prefix) {
+                sources.insert(path.to_owned(), &format!("{source}, profile `{name}`"));
+            }
+        }
+
+        merge(&mut config, profile, "")?;
+    }
+
+    Ok((Value::Object(config), sources))
+}
+
+/// Loads the config file at `path` merged on top of the files it extends.
+///
+/// `chain` holds the files that extend this one, to detect cycles.
+fn load_extended(
+    path: &Path,
+    template_context: &tera::Context,
+    chain: &mut Vec<PathBuf>,
+    sources: &mut ConfigSources,
+) -> Result<Map<String, Value>> {
+    let canonical = path.canonicalize()?;
+    if chain.contains(&canonical) {
+        return Err(ConfigError::ExtendsCycle(path.to_owned()));
+    }
+    chain.push(canonical);
+
+    let mut config = match parse(path, template_context)? {
+        Value::Null => Map::new(),
+        Value::Object(config) => config,
+        other => {
+            return Err(ConfigError::InvalidValue {
+                name: "config file",
+                provided: other.to_string(),
+                error: "expected a map of config values".into(),
+            })
+        }
+    };
+
+    let mut merged = match config.remove(EXTENDS_KEY) {
+        None | Some(Value::Null) => Map::new(),
+        Some(Value::String(base)) => {
+            let base = path.parent().unwrap_or(Path::new("")).join(base);
+            load_extended(&base, template_context, chain, sources)?
+        }
+        Some(other) => {
+            return Err(ConfigError::InvalidValue {
+                name: EXTENDS_KEY,
+                provided: other.to_string(),
+                error: "expected a path to a config file".into(),
+            })
+        }
+    };
+
+    sources.insert_all(&config, "", &path.display().to_string());
+    merge(&mut merged, config, "")?;
+
+    Ok(merged)
+}
+
+/// Renders the config file template and parses it according to the file extension.
+fn parse(path: &Path, template_context: &tera::Context) -> Result<Value> {
+    let mut template_engine = Tera::default();
+    template_engine.add_template_file(path, Some("main"))?;
+    let rendered = template_engine.render("main", template_context)?;
+
+    match path.extension().and_then(|os_val| os_val.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&rendered)?),
+        Some("toml") => Ok(toml::from_str(&rendered)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(&rendered)?),
+        _ => Err(ConfigError::UnsupportedFormat),
+    }
+}
+
+/// Merges `overrides` into `base`. Nested maps are merged, all other values are replaced.
+///
+/// `path` is the path of `base` in the config, e.g. `feature.network`. When only one of the values
+/// is a map, the other one is expanded to its map form first (see [`expand_shorthand`]), so that
+/// e.g. `"incoming": "steal"` merged with `"incoming": { "http_filter": ... }` keeps the steal
+/// mode.
+fn merge(base: &mut Map<String, Value>, overrides: Map<String, Value>, path: &str) -> Result<()> {
+    for (key, value) in overrides {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+
+        match (base.get_mut(&key), value) {
+            (Some(Value::Object(base)), Value::Object(value)) => merge(base, value, &path)?,
+            (Some(base), value) if base.is_object() != value.is_object() => {
+                let expanded_base = expand_shorthand(&path, base.take())?;
+                match (expanded_base, expand_shorthand(&path, value)?) {
+                    (Value::Object(mut expanded_base), Value::Object(value)) => {
+                        merge(&mut expanded_base, value, &path)?;
+                        *base = Value::Object(expanded_base);
+                    }
+                    (_, value) => *base = value,
+                }
+            }
+            (Some(base), value) => *base = value,
+            (None, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Expands the shorthand form of the config value at `path` to the equivalent map, e.g.
+/// `"fs": "write"` to `"fs": { "mode": "write" }`.
+///
+/// `true` toggles expand to an empty map (enabled with the defaults). Maps, `false` toggles and
+/// `null`s are returned as they are, as the latter two can only replace the other value. Any other
+/// value has no map form, and can't be merged with a map.
+fn expand_shorthand(path: &str, value: Value) -> Result<Value> {
+    let expanded = match (path, value) {
+        (_, value @ (Value::Object(..) | Value::Bool(false) | Value::Null)) => return Ok(value),
+        (_, Value::Bool(true)) => Map::new(),
+        ("feature.fs" | "feature.network.incoming", Value::String(mode)) => {
+            Map::from_iter([("mode".to_owned(), Value::String(mode))])
+        }
+        ("target", Value::String(target)) => {
+            Map::from_iter([("path".to_owned(), Value::String(target))])
+        }
+        (_, value) => {
+            return Err(ConfigError::Conflict(format!(
+                "`{path}` is `{value}` in one config file and a map in another, use the map form \
+                 in both"
+            )))
+        }
+    };
+
+    Ok(Value::Object(expanded))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        config::{ConfigContext, MirrordConfig},
+        feature::{fs::FsModeConfig, network::incoming::IncomingMode},
+        util::testing::with_env_vars,
+        LayerFileConfig,
+    };
+
+    /// Writes the config files into a new temporary directory.
+    fn config_dir(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (name, contents) in files {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    const BASE: &str = r#"
+    {
+        "agent": { "namespace": "mirrord", "ttl": 30 },
+        "feature": {
+            "fs": "write",
+            "network": {
+                "incoming": "steal",
+                "dns": { "filter": { "local": ["example.com"] } }
+            }
+        },
+        "profiles": {
+            "mirror": { "feature": { "network": { "incoming": "mirror" } } }
+        }
+    }
+    "#;
+
+    const SERVICE: &str = r#"
+extends: base.json
+target: deployment/service
+agent:
+  ttl: 60
+feature:
+  fs: read
+profiles:
+  local-fs:
+    feature:
+      fs: local
+"#;
+
+    #[test]
+    fn extends_and_profiles() {
+        let dir = config_dir(&[("base.json", BASE), ("service.yaml", SERVICE)]);
+        let base = dir.path().join("base.json").display().to_string();
+        let service = dir.path().join("service.yaml");
+
+        let (config, sources) =
+            LayerFileConfig::from_path_with_sources(&service, Some("mirror")).unwrap();
+        let config = config
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(config.agent.namespace.as_deref(), Some("mirrord"));
+        assert_eq!(config.agent.ttl, 60);
+        assert_eq!(config.feature.fs.mode, FsModeConfig::Read);
+        assert_eq!(config.feature.network.incoming.mode, IncomingMode::Mirror);
+        assert!(config.extends.is_none());
+        assert!(config.profiles.is_none());
+
+        assert_eq!(sources.get("agent.namespace"), vec![base.as_str()]);
+        assert!(sources.get("agent.ttl").is_empty());
+        assert_eq!(
+            sources.file("agent.ttl"),
+            Some(service.display().to_string().as_str())
+        );
+        assert_eq!(sources.file("agent.image"), None);
+        assert_eq!(
+            sources.get("feature.network.incoming"),
+            vec![format!("{base}, profile `mirror`")]
+        );
+        assert_eq!(
+            sources.get("feature.network.dns.filter.local"),
+            vec![base.as_str()]
+        );
+
+        let (config, _) =
+            LayerFileConfig::from_path_with_sources(&service, Some("local-fs")).unwrap();
+        let config = config
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+        assert_eq!(config.feature.fs.mode, FsModeConfig::Local);
+        assert_eq!(config.feature.network.incoming.mode, IncomingMode::Steal);
+
+        assert!(matches!(
+            LayerFileConfig::from_path_with_sources(&service, Some("remote")),
+            Err(ConfigError::ProfileNotFound(profile, available))
+                if profile == "remote" && available == "local-fs, mirror"
+        ));
+    }
+
+    #[test]
+    fn warnings_mention_source_file() {
+        let dir = config_dir(&[("base.json", BASE), ("service.yaml", SERVICE)]);
+        let base = dir.path().join("base.json").display().to_string();
+
+        let (config, sources) =
+            LayerFileConfig::from_path_with_sources(dir.path().join("service.yaml"), None).unwrap();
+        let mut context = ConfigContext::default().with_sources(sources);
+        config.generate_config(&mut context).unwrap();
+
+        let warning = context
+            .get_warnings()
+            .iter()
+            .find(|warning| warning.contains("DnsConfig.filter"))
+            .unwrap();
+        assert!(
+            warning.ends_with(&format!("(`feature.network.dns.filter` comes from {base})")),
+            "{warning}"
+        );
+    }
+
+    #[test]
+    fn env_sources() {
+        let dir = config_dir(&[("base.json", BASE)]);
+
+        with_env_vars(vec![("MIRRORD_AGENT_TTL", Some("90"))], || {
+            let (config, sources) =
+                LayerFileConfig::from_path_with_sources(dir.path().join("base.json"), None)
+                    .unwrap();
+            let mut context = ConfigContext::default().with_sources(sources);
+            let config = config.generate_config(&mut context).unwrap();
+
+            assert_eq!(config.agent.ttl, 90);
+            assert_eq!(context.env_source("agent.ttl"), Some("MIRRORD_AGENT_TTL"));
+            assert_eq!(context.env_source("agent.namespace"), None);
+            assert!(context.sources().file("agent.namespace").is_some());
+        });
+    }
+
+    #[test]
+    fn template_vars_in_extended_file() {
+        let dir = config_dir(&[
+            (
+                "base.json",
+                r#"{ "agent": { "namespace": "{{ user }}-{{ team }}" } }"#,
+            ),
+            ("service.json", r#"{ "extends": "base.json" }"#),
+            ("service.vars.yaml", "user: alice\nteam: payments\n"),
+        ]);
+
+        let config = LayerFileConfig::from_path_with_sources(dir.path().join("service.json"), None)
+            .unwrap()
+            .0
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(config.agent.namespace.as_deref(), Some("alice-payments"));
+    }
+
+    #[test]
+    fn extends_cycle() {
+        let dir = config_dir(&[
+            ("a.json", r#"{ "extends": "b.toml" }"#),
+            ("b.toml", r#"extends = "a.json""#),
+        ]);
+
+        assert!(matches!(
+            load(&dir.path().join("a.json"), None),
+            Err(ConfigError::ExtendsCycle(..))
+        ));
+    }
+
+    #[test]
+    fn shorthand_merged_with_map() {
+        let dir = config_dir(&[
+            (
+                "base.json",
+                r#"
+                {
+                    "agent": { "image": "repo/agent:1" },
+                    "feature": { "network": { "incoming": "steal" } },
+                    "target": "pod/api"
+                }
+                "#,
+            ),
+            (
+                "service.json",
+                r#"
+                {
+                    "extends": "base.json",
+                    "feature": {
+                        "network": { "incoming": { "http_filter": { "header_filter": "x-user: me" } } }
+                    },
+                    "target": { "namespace": "staging" }
+                }
+                "#,
+            ),
+            (
+                "image.json",
+                r#"{ "extends": "base.json", "agent": { "image": { "tag": "2" } } }"#,
+            ),
+        ]);
+
+        let config = LayerFileConfig::from_path_with_sources(dir.path().join("service.json"), None)
+            .unwrap()
+            .0
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(config.feature.network.incoming.mode, IncomingMode::Steal);
+        assert_eq!(
+            config
+                .feature
+                .network
+                .incoming
+                .http_filter
+                .header_filter
+                .as_deref(),
+            Some("x-user: me")
+        );
+        assert_eq!(config.target.namespace.as_deref(), Some("staging"));
+        assert!(config.target.path.is_some());
+
+        assert!(matches!(
+            load(&dir.path().join("image.json"), None),
+            Err(ConfigError::Conflict(..))
+        ));
+    }
+}
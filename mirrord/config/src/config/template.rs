@@ -0,0 +1,237 @@
+//! Variables available in the config file templates, see [`template_context`].
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{ConfigError, Result},
+    MIRRORD_CONFIG_VARS_ENV,
+};
+
+/// The config template variables resolved in the CLI, passed to the other mirrord processes (e.g.
+/// the layer) in [`MIRRORD_CONFIG_VARS_ENV`] as JSON.
+///
+/// The built-in variables are kept apart from the `--config-var` ones, as the sidecar vars file
+/// goes between them, see [`template_context`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigVars {
+    /// The built-in `user`, `git_branch` and `kube_context` variables.
+    pub builtin: Map<String, Value>,
+    /// The variables set with `--config-var`.
+    pub vars: Map<String, Value>,
+}
+
+impl ConfigVars {
+    /// Resolves the built-in variables and adds the given `--config-var`s.
+    ///
+    /// `kube_context` is the context selected with `--context`, it takes precedence over
+    /// `MIRRORD_KUBE_CONTEXT` and the kubeconfig `current-context`.
+    pub fn resolve<I>(kube_context: Option<&str>, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        Self {
+            builtin: builtin_vars(kube_context),
+            vars: vars
+                .into_iter()
+                .map(|(name, value)| (name, Value::String(value)))
+                .collect(),
+        }
+    }
+
+    /// Reads the variables from [`MIRRORD_CONFIG_VARS_ENV`], if it's set.
+    fn from_env() -> Result<Option<Self>> {
+        let Ok(raw) = env::var(MIRRORD_CONFIG_VARS_ENV) else {
+            return Ok(None);
+        };
+
+        serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|error| ConfigError::InvalidValue {
+                name: MIRRORD_CONFIG_VARS_ENV,
+                provided: raw,
+                error: error.into(),
+            })
+    }
+}
+
+/// Builds the [`tera::Context`] used to render the config file at `config_path` and the files it
+/// extends.
+///
+/// Later sources override the earlier ones:
+///
+/// 1. the built-in `user`, `git_branch` and `kube_context` variables, when they can be determined;
+/// 2. the variables from the sidecar vars file, see [`sidecar_vars_path`];
+/// 3. the variables set with `--config-var` in the CLI.
+///
+/// The built-in and `--config-var` variables are taken from [`MIRRORD_CONFIG_VARS_ENV`], where the
+/// CLI puts them, so that every mirrord process renders the config the same way. The built-in ones
+/// are resolved here only when the env is not set, e.g. when the config is loaded without the CLI.
+pub(crate) fn template_context(config_path: &Path) -> Result<tera::Context> {
+    let ConfigVars { builtin, vars } =
+        ConfigVars::from_env()?.unwrap_or_else(|| ConfigVars::resolve(None, []));
+
+    let mut context = builtin;
+
+    if let Some(path) = sidecar_vars_path(config_path) {
+        context.extend(parse_vars(&path)?);
+    }
+
+    context.extend(vars);
+
+    Ok(tera::Context::from_serialize(context)?)
+}
+
+fn builtin_vars(kube_context: Option<&str>) -> Map<String, Value> {
+    let user = env::var("USER").or_else(|_| env::var("USERNAME")).ok();
+    let git_branch = env::current_dir().ok().and_then(|dir| git_branch(&dir));
+    let kube_context = kube_context
+        .map(ToOwned::to_owned)
+        .or_else(|| env::var("MIRRORD_KUBE_CONTEXT").ok())
+        .or_else(current_kube_context);
+
+    [
+        ("user", user),
+        ("git_branch", git_branch),
+        ("kube_context", kube_context),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name.to_owned(), Value::String(value?))))
+    .collect()
+}
+
+/// Returns the branch checked out in the git repository containing `dir`.
+///
+/// Reads the `HEAD` file instead of running `git`, as the config is also loaded inside of the
+/// user application.
+fn git_branch(dir: &Path) -> Option<String> {
+    let git_dir = dir.ancestors().find_map(|dir| {
+        let dot_git = dir.join(".git");
+
+        if dot_git.is_dir() {
+            Some(dot_git)
+        } else if dot_git.is_file() {
+            // Worktrees and submodules have a `.git` file pointing to the actual git dir.
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let git_dir = contents.trim().strip_prefix("gitdir:")?.trim();
+            Some(dir.join(git_dir))
+        } else {
+            None
+        }
+    })?;
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(ToOwned::to_owned)
+}
+
+/// Returns the `current-context` from the kubeconfig files listed in `KUBECONFIG`, or from
+/// `~/.kube/config`.
+fn current_kube_context() -> Option<String> {
+    let paths = match env::var_os("KUBECONFIG") {
+        Some(paths) => env::split_paths(&paths).collect::<Vec<_>>(),
+        None => vec![PathBuf::from(env::var_os("HOME")?).join(".kube/config")],
+    };
+
+    paths.into_iter().find_map(|path| {
+        let kubeconfig =
+            serde_yaml::from_str::<serde_yaml::Value>(&fs::read_to_string(path).ok()?).ok()?;
+        kubeconfig
+            .get("current-context")?
+            .as_str()
+            .filter(|context| !context.is_empty())
+            .map(ToOwned::to_owned)
+    })
+}
+
+/// Returns the path of the sidecar vars file of the config file, if it exists.
+///
+/// For `mirrord.json`, the sidecar is `mirrord.vars.json` (or `.toml`, `.yaml`, `.yml`) in the
+/// same directory.
+fn sidecar_vars_path(config_path: &Path) -> Option<PathBuf> {
+    let stem = config_path.file_stem()?.to_str()?;
+
+    ["json", "toml", "yaml", "yml"]
+        .into_iter()
+        .map(|extension| config_path.with_file_name(format!("{stem}.vars.{extension}")))
+        .find(|path| path.is_file())
+}
+
+fn parse_vars(path: &Path) -> Result<Map<String, Value>> {
+    let contents = fs::read_to_string(path)?;
+
+    let vars = match path.extension().and_then(|os_val| os_val.to_str()) {
+        Some("json") => serde_json::from_str(&contents)?,
+        Some("toml") => toml::from_str(&contents)?,
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents)?,
+        _ => return Err(ConfigError::UnsupportedFormat),
+    };
+
+    match vars {
+        Value::Null => Ok(Map::new()),
+        Value::Object(vars) => Ok(vars),
+        other => Err(ConfigError::InvalidValue {
+            name: "config vars file",
+            provided: other.to_string(),
+            error: "expected a map of variable names to values".into(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn git_branch_from_head() {
+        let dir = TempDir::new().unwrap();
+        let nested = dir.path().join("service/src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+
+        fs::write(
+            dir.path().join(".git/HEAD"),
+            "ref: refs/heads/feature/login\n",
+        )
+        .unwrap();
+        assert_eq!(git_branch(&nested).as_deref(), Some("feature/login"));
+
+        fs::write(dir.path().join(".git/HEAD"), "3f2a1b8c\n").unwrap();
+        assert_eq!(git_branch(&nested), None);
+    }
+
+    #[test]
+    fn sidecar_vars() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("mirrord.json");
+        assert_eq!(sidecar_vars_path(&config_path), None);
+
+        fs::write(dir.path().join("mirrord.vars.toml"), "user = \"alice\"\n").unwrap();
+        let vars_path = sidecar_vars_path(&config_path).unwrap();
+        assert_eq!(
+            parse_vars(&vars_path).unwrap().get("user"),
+            Some(&Value::String("alice".into()))
+        );
+    }
+
+    #[test]
+    fn config_vars_roundtrip() {
+        let vars = ConfigVars::resolve(Some("staging"), [("user".to_owned(), "bob".to_owned())]);
+        assert_eq!(
+            vars.builtin.get("kube_context"),
+            Some(&Value::String("staging".into()))
+        );
+        assert_eq!(vars.vars.get("user"), Some(&Value::String("bob".into())));
+
+        let encoded = serde_json::to_string(&vars).unwrap();
+        assert_eq!(serde_json::from_str::<ConfigVars>(&encoded).unwrap(), vars);
+    }
+}
@@ -18,10 +18,13 @@ where
 
     fn source_value(self, context: &mut ConfigContext) -> Option<Result<Self::Value>> {
         self.2.source_value(context).inspect(|_| {
-            context.add_warning(format!(
-                "Warning: field {}.{} is marked as unstable. Please note API may change",
-                self.0, self.1
-            ));
+            context.add_value_warning(
+                self.1,
+                format!(
+                    "Warning: field {}.{} is marked as unstable. Please note API may change",
+                    self.0, self.1
+                ),
+            );
         })
     }
 }
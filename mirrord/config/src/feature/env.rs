@@ -3,10 +3,11 @@ use std::{collections::HashMap, path::PathBuf};
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     config::{from_env::FromEnv, source::MirrordConfigSource, ConfigContext, Result},
+    target::Target,
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
@@ -138,6 +139,59 @@ pub struct EnvConfig {
     /// `LOG_FILE_VERBOSITY: info` => `LOG_FILE_VERBOSITY: debug`
     /// `DATA_1234: common-value` => `DATA_1234: magic-value`
     pub mapping: Option<HashMap<String, String>>,
+
+    /// ### feature.env.from_pod_spec {#feature-env-from_pod_spec}
+    ///
+    /// Reads the remote environment from the pod spec of the target container through the
+    /// Kubernetes API, instead of reading it from the running process.
+    ///
+    /// The `env` and `envFrom` of the container are resolved, including the referenced
+    /// `ConfigMap`s and `Secret`s, so the target doesn't have to be running (e.g. a crashing pod).
+    /// Only variables set in the pod spec are loaded, variables set by the container image are
+    /// not. As with the kubelet, only the `data` of `ConfigMap`s is used, and `binaryData` is
+    /// ignored.
+    ///
+    /// The resources are read from the CLI with your own Kubernetes credentials, not by the agent,
+    /// so you need RBAC permissions to `get` the target, and the `ConfigMap`s and `Secret`s it
+    /// references.
+    ///
+    /// Can be set to one of the options:
+    ///
+    /// 1. `true` - reads the pod spec of the [`target`](#target-path);
+    /// 2. a target path, like `"deployment/api/container/app"` - reads the pod spec of this
+    ///    resource, which can be used with a targetless run.
+    ///
+    /// [`include`](#feature-env-include), [`exclude`](#feature-env-exclude),
+    /// [`mapping`](#feature-env-mapping) and [`override`](#feature-env-override) apply to these
+    /// variables as well.
+    ///
+    /// Cannot be used with [`load_from_process`](#feature-env-load_from_process).
+    pub from_pod_spec: Option<FromPodSpecConfig>,
+}
+
+/// See [`EnvConfig::from_pod_spec`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum FromPodSpecConfig {
+    /// Read the pod spec of the mirrord target, when `true`.
+    Target(bool),
+
+    /// Read the pod spec of the resource with this target path.
+    Resource(String),
+}
+
+impl FromPodSpecConfig {
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Target(false))
+    }
+
+    /// Parses the [`Target`] of [`FromPodSpecConfig::Resource`], `None` means the mirrord target.
+    pub fn resource(&self) -> Result<Option<Target>> {
+        match self {
+            Self::Target(..) => Ok(None),
+            Self::Resource(path) => path.parse().map(Some),
+        }
+    }
 }
 
 impl MirrordToggleableConfig for EnvFileConfig {
@@ -157,6 +211,7 @@ impl MirrordToggleableConfig for EnvFileConfig {
                 .source_value(context)
                 .transpose()?,
             mapping: None,
+            from_pod_spec: None,
         })
     }
 }
@@ -192,6 +247,12 @@ impl CollectAnalytics for &EnvConfig {
                 .unwrap_or_default(),
         );
         analytics.add("env_file_used", self.env_file.is_some());
+        analytics.add(
+            "env_from_pod_spec",
+            self.from_pod_spec
+                .as_ref()
+                .is_some_and(FromPodSpecConfig::is_enabled),
+        );
         analytics.add(
             "env_mapping_count",
             self.mapping
@@ -265,4 +326,25 @@ mod tests {
             },
         );
     }
+
+    #[rstest]
+    #[case(r#"true"#, true, None)]
+    #[case(r#"false"#, false, None)]
+    #[case(
+        r#""deployment/api/container/app""#,
+        true,
+        Some("deployment/api/container/app")
+    )]
+    fn from_pod_spec(#[case] value: &str, #[case] enabled: bool, #[case] resource: Option<&str>) {
+        let from_pod_spec: FromPodSpecConfig = serde_json::from_str(value).unwrap();
+
+        assert_eq!(from_pod_spec.is_enabled(), enabled);
+        assert_eq!(
+            from_pod_spec.resource().unwrap(),
+            resource.map(|path| path.parse::<Target>().unwrap())
+        );
+        assert!(FromPodSpecConfig::Resource("api".to_string())
+            .resource()
+            .is_err());
+    }
 }
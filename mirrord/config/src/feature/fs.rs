@@ -4,9 +4,8 @@
 //! 1. [`FsUserConfig::Simple`]: controls only the option for enabling read-only, read-write, or
 //!    disable file operations;
 //!
-//! 2. [`FsUserConfig::Advanced`]: All of the above, plus allows setting up
-//!    `mirrord_layer::file::filter::FileFilter` to control which files should be opened locally or
-//!    remotely.
+//! 2. [`FsUserConfig::Advanced`]: All of the above, plus allows setting up [`filter::FileFilter`]
+//!    to control which files should be opened locally or remotely.
 use schemars::JsonSchema;
 use serde::Deserialize;
 
@@ -19,6 +18,7 @@ use crate::{
 };
 
 pub mod advanced;
+pub mod filter;
 pub mod mode;
 
 /// ## feature.fs {#fs}
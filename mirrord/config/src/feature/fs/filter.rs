@@ -0,0 +1,294 @@
+//! Controls which files are ignored (opened locally) by mirrord file operations.
+//!
+//! There are 2 ways of setting this up:
+//!
+//! 1. no configuration (default): will bypass file operations for file paths and types that match
+//!    [`generate_local_set`];
+//!
+//! 2. Using the overrides for `read_only`, `read_write` and `local`.
+use std::env;
+
+use regex::{RegexSet, RegexSetBuilder};
+
+use crate::{
+    feature::fs::{FsConfig, FsModeConfig},
+    util::VecOrSingle,
+};
+
+mod not_found_by_default;
+mod read_local_by_default;
+mod read_remote_by_default;
+
+/// List of files that mirrord should use locally, as they probably exist only in the local user
+/// machine, or are system configuration files (that could break the process if we used the remote
+/// version).
+///
+/// You most likely do **NOT** want to include any of these, but if have a reason to do so, then
+/// setting any of the overrides - `MIRRORD_FILE_X_PATTERN` allows you to override this list.
+fn generate_local_set() -> RegexSet {
+    // To handle the problem of injecting `open` and friends into project runners (like in a call to
+    // `node app.js`, or `cargo run app`), we're ignoring files from the current working directory.
+    read_local_by_default::regex_set_builder()
+        .case_insensitive(true)
+        .build()
+        .expect("Building local path regex set failed")
+}
+
+/// List of files that mirrord should use remotely read only
+fn generate_remote_ro_set() -> RegexSet {
+    let patterns = read_remote_by_default::PATHS;
+    RegexSetBuilder::new(patterns)
+        .case_insensitive(true)
+        .build()
+        .expect("Building remote readonly path regex set failed")
+}
+
+fn generate_not_found_set() -> RegexSet {
+    let Ok(home) = env::var("HOME") else {
+        tracing::warn!("Unable to resolve $HOME directory, generating empty not-found set");
+        return Default::default();
+    };
+
+    let home_clean = regex::escape(home.trim_end_matches('/'));
+
+    let patterns = not_found_by_default::PATHS
+        .into_iter()
+        .map(|cloud_dir| format!("^{home_clean}/{cloud_dir}"));
+
+    RegexSetBuilder::new(patterns)
+        .case_insensitive(true)
+        .build()
+        .expect("Building not found path regex set failed")
+}
+
+/// How a file is accessed, decided by the [`FileFilter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileAccess {
+    /// The file is accessed in the remote target.
+    Remote,
+    /// The file is accessed locally.
+    Local,
+    /// The file is reported as not found.
+    NotFound,
+}
+
+/// The rule of the [`FileFilter`] that decided how a file is accessed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFilterRule {
+    /// A pattern from [`FsConfig::not_found`].
+    NotFound,
+    /// A pattern from [`FsConfig::read_write`].
+    ReadWrite,
+    /// A pattern from [`FsConfig::read_only`].
+    ReadOnly,
+    /// A pattern from [`FsConfig::local`].
+    Local,
+    /// One of the paths that are not found by default.
+    DefaultNotFound,
+    /// One of the paths that are read remotely by default.
+    DefaultReadOnly,
+    /// One of the paths that are read locally by default.
+    DefaultLocal,
+    /// No pattern matched, or the mode is [`FsModeConfig::Local`].
+    Mode(FsModeConfig),
+}
+
+#[derive(Debug)]
+pub struct FileFilter {
+    read_only: RegexSet,
+    read_write: RegexSet,
+    local: RegexSet,
+    not_found: RegexSet,
+    default_local: RegexSet,
+    default_remote_ro: RegexSet,
+    default_not_found: RegexSet,
+    mode: FsModeConfig,
+}
+
+impl FileFilter {
+    fn make_regex_set(patterns: Option<VecOrSingle<String>>) -> Result<RegexSet, regex::Error> {
+        RegexSetBuilder::new(patterns.as_deref().map(<[_]>::to_vec).unwrap_or_default())
+            .case_insensitive(true)
+            .build()
+    }
+
+    /// Initializes a `FileFilter` based on the user configuration.
+    ///
+    /// The filter first checks if the user specified any include/exclude regexes. (This will be
+    /// removed) If path matches include, it continues to check if the path has specific
+    /// behavior, if not, it checks if the path matches the default exclude list.
+    /// If not, it does the default behavior set by user (default is read only remote).
+    #[tracing::instrument(level = "trace")]
+    pub fn new(fs_config: FsConfig) -> Self {
+        let FsConfig {
+            read_write,
+            read_only,
+            local,
+            mode,
+            not_found,
+            ..
+        } = fs_config;
+
+        let read_write =
+            Self::make_regex_set(read_write).expect("building read-write regex set failed");
+        let read_only =
+            Self::make_regex_set(read_only).expect("building read-only regex set failed");
+        let local = Self::make_regex_set(local).expect("building local path regex set failed");
+        let not_found =
+            Self::make_regex_set(not_found).expect("building not-found regex set failed");
+
+        let default_local = generate_local_set();
+        let default_remote_ro = generate_remote_ro_set();
+        let default_not_found = generate_not_found_set();
+
+        Self {
+            read_only,
+            read_write,
+            local,
+            not_found,
+            default_local,
+            default_remote_ro,
+            default_not_found,
+            mode,
+        }
+    }
+
+    /// Decides how the file at `text` is accessed, and whether it's accessed for writing.
+    ///
+    /// Returns the [`FileFilterRule`] that made the decision.
+    pub fn check(&self, text: &str, write: bool) -> (FileAccess, FileFilterRule) {
+        match self.mode {
+            FsModeConfig::Local => (FileAccess::Local, FileFilterRule::Mode(self.mode)),
+            _ if self.not_found.is_match(text) => (FileAccess::NotFound, FileFilterRule::NotFound),
+            _ if self.read_write.is_match(text) => (FileAccess::Remote, FileFilterRule::ReadWrite),
+            _ if self.read_only.is_match(text) => {
+                if write {
+                    (FileAccess::Local, FileFilterRule::ReadOnly)
+                } else {
+                    (FileAccess::Remote, FileFilterRule::ReadOnly)
+                }
+            }
+            _ if self.local.is_match(text) => (FileAccess::Local, FileFilterRule::Local),
+            _ if self.default_not_found.is_match(text) => {
+                (FileAccess::NotFound, FileFilterRule::DefaultNotFound)
+            }
+            _ if self.default_remote_ro.is_match(text) && !write => {
+                (FileAccess::Remote, FileFilterRule::DefaultReadOnly)
+            }
+            _ if self.default_local.is_match(text) => {
+                (FileAccess::Local, FileFilterRule::DefaultLocal)
+            }
+            FsModeConfig::LocalWithOverrides => {
+                (FileAccess::Local, FileFilterRule::Mode(self.mode))
+            }
+            FsModeConfig::Write => (FileAccess::Remote, FileFilterRule::Mode(self.mode)),
+            FsModeConfig::Read if write => (FileAccess::Local, FileFilterRule::Mode(self.mode)),
+            FsModeConfig::Read => (FileAccess::Remote, FileFilterRule::Mode(self.mode)),
+        }
+    }
+
+    /// Returns the first pattern of the given `rule` that matches `text`.
+    pub fn matched_pattern(&self, rule: FileFilterRule, text: &str) -> Option<&str> {
+        let set = match rule {
+            FileFilterRule::NotFound => &self.not_found,
+            FileFilterRule::ReadWrite => &self.read_write,
+            FileFilterRule::ReadOnly => &self.read_only,
+            FileFilterRule::Local => &self.local,
+            FileFilterRule::DefaultNotFound => &self.default_not_found,
+            FileFilterRule::DefaultReadOnly => &self.default_remote_ro,
+            FileFilterRule::DefaultLocal => &self.default_local,
+            FileFilterRule::Mode(..) => return None,
+        };
+
+        let index = set.matches(text).into_iter().next()?;
+        set.patterns().get(index).map(String::as_str)
+    }
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self::new(FsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    /// Sanity test for empty [`RegexSet`] behaviour.
+    #[test]
+    fn empty_regex_set() {
+        let set = FileFilter::make_regex_set(None).unwrap();
+        assert!(!set.is_match("/path/to/some/file"));
+    }
+
+    #[rstest]
+    #[case(
+        FsModeConfig::Read,
+        "/pain/local/test.a",
+        false,
+        FileAccess::Local,
+        FileFilterRule::Local,
+        Some(r"/pain/local.*\.a")
+    )]
+    #[case(
+        FsModeConfig::Read,
+        "/pain/read_only/test.a",
+        true,
+        FileAccess::Local,
+        FileFilterRule::ReadOnly,
+        Some(r"/pain/read_only.*\.a")
+    )]
+    #[case(
+        FsModeConfig::Write,
+        "/etc/resolv.conf",
+        false,
+        FileAccess::Remote,
+        FileFilterRule::DefaultReadOnly,
+        Some(r"^/etc/resolv.conf$")
+    )]
+    #[case(
+        FsModeConfig::Write,
+        "/etc/resolv.conf",
+        true,
+        FileAccess::Local,
+        FileFilterRule::DefaultLocal,
+        Some(r"^/etc(/|$)")
+    )]
+    #[case(
+        FsModeConfig::Read,
+        "/a/test.a",
+        true,
+        FileAccess::Local,
+        FileFilterRule::Mode(FsModeConfig::Read),
+        None
+    )]
+    #[case(
+        FsModeConfig::Local,
+        "/pain/read_only/test.a",
+        false,
+        FileAccess::Local,
+        FileFilterRule::Mode(FsModeConfig::Local),
+        None
+    )]
+    fn check_with_rule(
+        #[case] mode: FsModeConfig,
+        #[case] path: &str,
+        #[case] write: bool,
+        #[case] access: FileAccess,
+        #[case] rule: FileFilterRule,
+        #[case] pattern: Option<&str>,
+    ) {
+        let file_filter = FileFilter::new(FsConfig {
+            read_only: Some(VecOrSingle::Single(r"/pain/read_only.*\.a".to_string())),
+            local: Some(VecOrSingle::Single(r"/pain/local.*\.a".to_string())),
+            mode,
+            ..Default::default()
+        });
+
+        assert_eq!(file_filter.check(path, write), (access, rule));
+        assert_eq!(file_filter.matched_pattern(rule, path), pattern);
+    }
+}
@@ -6,7 +6,7 @@ use serde::Serialize;
 
 use self::{incoming::*, outgoing::*};
 use crate::{
-    config::{ConfigContext, ConfigError},
+    config::{from_env::FromEnv, source::MirrordConfigSource, ConfigContext, ConfigError},
     util::MirrordToggleableConfig,
 };
 
@@ -47,7 +47,8 @@ pub mod outgoing;
 ///         "filter": {
 ///           "local": ["1.1.1.0/24:1337", "1.1.5.0/24", "google.com"]
 ///         }
-///       }
+///       },
+///       "ipv6": false
 ///     }
 ///   }
 /// }
@@ -67,6 +68,18 @@ pub struct NetworkConfig {
     /// ### feature.network.dns {#feature-network-dns}
     #[config(toggleable, nested)]
     pub dns: DnsConfig,
+
+    /// ### feature.network.ipv6 {#feature-network-ipv6}
+    ///
+    /// Enables IPv6 support for the hooked sockets: binding, listening and connecting over IPv6,
+    /// and resolving IPv6 addresses in remote DNS.
+    ///
+    /// Turn it on if your application opens IPv6 sockets (e.g. listens on `::`) and runs in a
+    /// dual-stack cluster.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_ENABLE_IPV6", default = false)]
+    pub ipv6: bool,
 }
 
 impl MirrordToggleableConfig for NetworkFileConfig {
@@ -75,6 +88,9 @@ impl MirrordToggleableConfig for NetworkFileConfig {
             incoming: IncomingFileConfig::disabled_config(context)?,
             dns: DnsFileConfig::disabled_config(context)?,
             outgoing: OutgoingFileConfig::disabled_config(context)?,
+            ipv6: FromEnv::new("MIRRORD_ENABLE_IPV6")
+                .source_value(context)
+                .unwrap_or(Ok(false))?,
         })
     }
 }
@@ -84,6 +100,7 @@ impl CollectAnalytics for &NetworkConfig {
         analytics.add("incoming", &self.incoming);
         analytics.add("outgoing", &self.outgoing);
         analytics.add("dns", &self.dns);
+        analytics.add("ipv6", self.ipv6);
     }
 }
 
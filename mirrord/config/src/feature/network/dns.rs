@@ -105,7 +105,8 @@ impl DnsConfig {
     pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
         let filters = match &self.filter {
             Some(..) if !self.enabled => {
-                context.add_warning(
+                context.add_value_warning(
+                    "feature.network.dns.filter",
                     "Remote DNS resolution is disabled, provided DNS filter will be ignored"
                         .to_string(),
                 );
@@ -113,14 +114,16 @@ impl DnsConfig {
             }
             None => return Ok(()),
             Some(DnsFilterConfig::Local(filters)) if filters.is_empty() => {
-                context.add_warning(
+                context.add_value_warning(
+                    "feature.network.dns.filter",
                     "Local DNS filter is empty, all DNS resolution will be done remotely"
                         .to_string(),
                 );
                 return Ok(());
             }
             Some(DnsFilterConfig::Remote(filters)) if filters.is_empty() => {
-                context.add_warning(
+                context.add_value_warning(
+                    "feature.network.dns.filter",
                     "Remote DNS filter is empty, all DNS resolution will be done locally"
                         .to_string(),
                 );
@@ -1,4 +1,6 @@
 use std::{
+    collections::HashMap,
+    convert::Infallible,
     net::{IpAddr, SocketAddr},
     num::ParseIntError,
     str::FromStr,
@@ -198,6 +200,61 @@ impl From<nom::Err<nom::error::Error<&str>>> for ProtocolAndAddressFilterError {
     }
 }
 
+impl ProtocolAndAddressFilter {
+    /// Checks if a connection to `address` matches this filter.
+    ///
+    /// `protocol` is the protocol of the connection, either [`ProtocolFilter::Tcp`] or
+    /// [`ProtocolFilter::Udp`].
+    ///
+    /// The name of an [`AddressFilter::Name`] is resolved with `resolve` only when the protocol and
+    /// the port match. The name should be resolved with the remote DNS (unless the user disabled
+    /// it), so that it matches the same addresses as in the cluster.
+    pub fn matches_with<E, F>(
+        &self,
+        address: SocketAddr,
+        protocol: ProtocolFilter,
+        resolve: F,
+    ) -> Result<bool, E>
+    where
+        F: FnOnce(&str) -> Result<Vec<IpAddr>, E>,
+    {
+        if let (ProtocolFilter::Tcp, ProtocolFilter::Udp)
+        | (ProtocolFilter::Udp, ProtocolFilter::Tcp) = (self.protocol, protocol)
+        {
+            return Ok(false);
+        }
+
+        let port = self.address.port();
+        if port != 0 && port != address.port() {
+            return Ok(false);
+        }
+
+        match &self.address {
+            AddressFilter::Name(name, _) => Ok(resolve(name)?.contains(&address.ip())),
+            AddressFilter::Socket(filter) => {
+                Ok(filter.ip().is_unspecified() || filter.ip() == address.ip())
+            }
+            AddressFilter::Subnet(net, _) => Ok(net.contains(&address.ip())),
+            AddressFilter::Port(..) => Ok(true),
+        }
+    }
+
+    /// [`ProtocolAndAddressFilter::matches_with`] names that were already resolved.
+    ///
+    /// An [`AddressFilter::Name`] matches only the addresses it resolved to in `resolved_names`.
+    pub fn matches(
+        &self,
+        address: SocketAddr,
+        protocol: ProtocolFilter,
+        resolved_names: &HashMap<String, Vec<IpAddr>>,
+    ) -> bool {
+        self.matches_with(address, protocol, |name| {
+            Ok::<_, Infallible>(resolved_names.get(name).cloned().unwrap_or_default())
+        })
+        .unwrap_or_else(|never| match never {})
+    }
+}
+
 impl FromStr for ProtocolAndAddressFilter {
     type Err = ProtocolAndAddressFilterError;
 
@@ -456,4 +513,39 @@ mod tests {
     fn invalid_filters(#[case] input: &'static str) {
         ProtocolAndAddressFilter::from_str(input).unwrap();
     }
+
+    #[rstest]
+    #[case("tcp://10.0.0.0/16", ProtocolFilter::Tcp, true)]
+    #[case("tcp://10.0.0.0/16", ProtocolFilter::Udp, false)]
+    #[case(":8080", ProtocolFilter::Udp, true)]
+    #[case(":80", ProtocolFilter::Tcp, false)]
+    #[case("0.0.0.0:8080", ProtocolFilter::Tcp, true)]
+    #[case("payments.default:8080", ProtocolFilter::Tcp, true)]
+    #[case("users.default:8080", ProtocolFilter::Tcp, false)]
+    #[case("localhost:8080", ProtocolFilter::Tcp, false)]
+    fn filter_matching(
+        #[case] filter: &str,
+        #[case] protocol: ProtocolFilter,
+        #[case] matches: bool,
+    ) {
+        let resolved_names = HashMap::from([
+            (
+                "payments.default".to_string(),
+                vec!["10.0.1.1".parse().unwrap()],
+            ),
+            (
+                "users.default".to_string(),
+                vec!["10.0.2.2".parse().unwrap()],
+            ),
+        ]);
+
+        assert_eq!(
+            ProtocolAndAddressFilter::from_str(filter).unwrap().matches(
+                "10.0.1.1:8080".parse().unwrap(),
+                protocol,
+                &resolved_names
+            ),
+            matches
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashSet, fmt, str::FromStr};
+use std::{collections::HashSet, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
 
 use bimap::BiMap;
 use mirrord_analytics::{AnalyticValue, Analytics, CollectAnalytics};
@@ -15,8 +15,42 @@ use crate::{
 };
 
 pub mod http_filter;
+pub mod http_rewrite;
+pub mod tls;
 
 use http_filter::*;
+use http_rewrite::HttpRewriteConfig;
+use tls::IncomingTlsConfig;
+
+/// <!--${internal}-->
+/// Set by the CLI for `service` targets, see [`ServicePortMapping`].
+pub const MIRRORD_SERVICE_PORT_MAPPING_ENV: &str = "MIRRORD_SERVICE_PORT_MAPPING";
+
+/// <!--${internal}-->
+/// `(port, targetPort)` pairs of a `service` target, resolved by the CLI from the service spec and
+/// passed to the layer as JSON in [`MIRRORD_SERVICE_PORT_MAPPING_ENV`].
+///
+/// They are added to [`IncomingConfig::port_mapping`], without replacing the mappings set by the
+/// user.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServicePortMapping(pub Vec<(u16, u16)>);
+
+impl ServicePortMapping {
+    /// Adds the pairs to the `port_mapping`, skipping the ports that are already mapped.
+    fn extend(self, port_mapping: &mut BiMap<u16, u16>) {
+        for (port, target_port) in self.0 {
+            let _ = port_mapping.insert_no_overwrite(port, target_port);
+        }
+    }
+}
+
+impl FromStr for ServicePortMapping {
+    type Err = serde_json::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(value)
+    }
+}
 
 /// ## incoming (network)
 ///
@@ -87,7 +121,12 @@ impl MirrordConfig for IncomingFileConfig {
     type Generated = IncomingConfig;
 
     fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated> {
-        let config = match self {
+        let service_port_mapping: Option<ServicePortMapping> =
+            FromEnv::new(MIRRORD_SERVICE_PORT_MAPPING_ENV)
+                .source_value(context)
+                .transpose()?;
+
+        let mut config = match self {
             IncomingFileConfig::Simple(mode) => IncomingConfig {
                 mode: FromEnv::new("MIRRORD_AGENT_TCP_STEAL_TRAFFIC")
                     .or(mode)
@@ -136,9 +175,16 @@ impl MirrordConfig for IncomingFileConfig {
                     .transpose()?
                     .unwrap_or_default(),
                 ports: advanced.ports.map(|ports| ports.into_iter().collect()),
+                http_record: advanced.http_record,
+                http_rewrite: advanced.http_rewrite.unwrap_or_default(),
+                tls: advanced.tls,
             },
         };
 
+        if let Some(service_port_mapping) = service_port_mapping {
+            service_port_mapping.extend(&mut config.port_mapping);
+        }
+
         Ok(config)
     }
 }
@@ -308,6 +354,29 @@ pub struct IncomingAdvancedFileConfig {
     ///
     /// Mutually exclusive with [`ignore_ports`](###ignore_ports).
     pub ports: Option<Vec<u16>>,
+
+    /// ### http_record
+    ///
+    /// Path of a file to which the HTTP requests and responses handled by mirrord are appended,
+    /// one JSON object per line. Mirrored and unfiltered stolen traffic is recorded when it's
+    /// HTTP/1.
+    pub http_record: Option<PathBuf>,
+
+    /// ### http_rewrite
+    ///
+    /// Rewrites applied to the stolen HTTP requests and the responses to them.
+    ///
+    /// See [`feature.network.incoming.http_rewrite`](#feature-network-incoming-http_rewrite) for
+    /// details.
+    pub http_rewrite: Option<HttpRewriteConfig>,
+
+    /// ### tls
+    ///
+    /// Lets the agent terminate TLS on the given ports, so that HTTP filters work with HTTPS
+    /// traffic.
+    ///
+    /// See [`feature.network.incoming.tls`](#feature-network-incoming-tls) for details.
+    pub tls: Option<IncomingTlsConfig>,
 }
 
 fn serialize_bi_map<S>(map: &BiMap<u16, u16>, serializer: S) -> Result<S::Ok, S::Error>
@@ -400,6 +469,8 @@ pub struct IncomingConfig {
     /// This is useful when you want to mirror/steal a port to a different port on the remote
     /// machine. For example, your local process listens on port `9333` and the container listens
     /// on port `80`. You'd use `[[9333, 80]]`
+    ///
+    /// With a `service` target, the service ports are mapped to their `targetPort`s by default.
     #[serde(serialize_with = "serialize_bi_map")]
     pub port_mapping: BiMap<u16, u16>,
 
@@ -451,6 +522,39 @@ pub struct IncomingConfig {
     /// Mutually exclusive with
     /// [`feature.network.incoming.ignore_ports`](#feature-network-ignore_ports).
     pub ports: Option<HashSet<u16>>,
+
+    /// #### feature.network.incoming.http_record {#feature-network-incoming-http_record}
+    ///
+    /// Path of a file to which the HTTP requests and responses handled by mirrord are appended,
+    /// one JSON object per line. The recorded file can be sent again to a local process with
+    /// `mirrord replay`.
+    ///
+    /// Traffic stolen with an [`http_filter`](#feature-network-incoming-http-filter) is always
+    /// recorded. Mirrored traffic, and traffic stolen without an HTTP filter, is recorded when
+    /// it's HTTP/1.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "http_filter": {
+    ///           "header_filter": "x-debug: .*"
+    ///         },
+    ///         "http_record": "/tmp/mirrord-http.jsonl"
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub http_record: Option<PathBuf>,
+
+    /// #### feature.network.incoming.http_rewrite {#feature-network-incoming-http_rewrite}
+    pub http_rewrite: HttpRewriteConfig,
+
+    /// #### feature.network.incoming.tls {#feature-network-incoming-tls}
+    pub tls: Option<IncomingTlsConfig>,
 }
 
 impl IncomingConfig {
@@ -461,6 +565,95 @@ impl IncomingConfig {
     pub fn is_steal(&self) -> bool {
         matches!(self.mode, IncomingMode::Steal)
     }
+
+    /// Verifies the [`IncomingTlsConfig`], if there is one.
+    pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
+        self.tls
+            .as_ref()
+            .map(IncomingTlsConfig::verify)
+            .transpose()?;
+
+        Ok(())
+    }
+
+    /// Checks the rules that apply to all sockets bound by the application on `address`:
+    /// `ignore_localhost` and `ignore_ports`.
+    pub fn ignored_address(&self, address: SocketAddr) -> Option<IgnoredPort> {
+        if self.ignore_localhost && address.ip().is_loopback() {
+            Some(IgnoredPort::Localhost)
+        } else if self.ignore_ports.contains(&address.port()) {
+            Some(IgnoredPort::IgnorePorts)
+        } else {
+            None
+        }
+    }
+
+    /// Decides what happens with the traffic of a TCP port bound by the application on
+    /// `address`.
+    ///
+    /// Used by mirrord-layer when the application binds a TCP socket, and by
+    /// `mirrord config explain`. [`IncomingMode::Off`] is not checked here, as the layer handles
+    /// it only when the application starts listening.
+    pub fn tcp_port(&self, address: SocketAddr) -> IncomingPort {
+        if let Some(ignored) = self.ignored_address(address) {
+            return IncomingPort::Ignored(ignored);
+        }
+
+        if address.port() == 0 {
+            return IncomingPort::Ignored(IgnoredPort::PortZero);
+        }
+
+        // The `ports` rules apply to the remote port, see
+        // https://github.com/metalbear-co/mirrord/issues/2397
+        let remote_port = self
+            .port_mapping
+            .get_by_left(&address.port())
+            .copied()
+            .unwrap_or(address.port());
+
+        let http_filter_used = self.is_steal() && self.http_filter.is_filter_set();
+        let filtered = http_filter_used && self.http_filter.ports.contains(&remote_port);
+
+        match &self.ports {
+            Some(ports) if !filtered && !ports.contains(&remote_port) => {
+                IncomingPort::Ignored(IgnoredPort::NotInPorts(remote_port))
+            }
+            None if http_filter_used && !filtered => {
+                IncomingPort::Ignored(IgnoredPort::NotInHttpFilterPorts(remote_port))
+            }
+            _ => IncomingPort::Remote {
+                remote_port,
+                filtered,
+            },
+        }
+    }
+}
+
+/// What happens with the traffic of a port bound by the application, see
+/// [`IncomingConfig::tcp_port`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncomingPort {
+    /// The port is bound only locally.
+    Ignored(IgnoredPort),
+    /// The traffic of the `remote_port` is mirrored or stolen. With `filtered`, only the HTTP
+    /// requests matching [`IncomingConfig::http_filter`] are stolen.
+    Remote { remote_port: u16, filtered: bool },
+}
+
+/// Why a port bound by the application is bound only locally, see [`IncomingPort::Ignored`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoredPort {
+    /// The address is a loopback, and [`IncomingConfig::ignore_localhost`] is set.
+    Localhost,
+    /// The port is in [`IncomingConfig::ignore_ports`].
+    IgnorePorts,
+    /// Port `0` is never subscribed.
+    PortZero,
+    /// The remote port is not in [`IncomingConfig::ports`].
+    NotInPorts(u16),
+    /// An HTTP filter is set, and the remote port is neither in [`HttpFilterConfig::ports`], nor
+    /// in [`IncomingConfig::ports`].
+    NotInHttpFilterPorts(u16),
 }
 
 /// Allows selecting between mirrorring or stealing traffic.
@@ -617,3 +810,79 @@ impl CollectAnalytics for &IncomingConfig {
         analytics.add("http", &self.http_filter);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn service_port_mapping_keeps_user_mapping() {
+        let mut port_mapping = BiMap::from_iter([(80, 9000)]);
+
+        "[[80, 8080], [443, 8443]]"
+            .parse::<ServicePortMapping>()
+            .unwrap()
+            .extend(&mut port_mapping);
+
+        assert_eq!(port_mapping, BiMap::from_iter([(80, 9000), (443, 8443)]));
+    }
+
+    #[test]
+    fn tcp_port() {
+        let mut config = IncomingConfig {
+            mode: IncomingMode::Steal,
+            ignore_localhost: true,
+            ignore_ports: HashSet::from([9999]),
+            ports: Some(HashSet::from([8081])),
+            port_mapping: BiMap::from_iter([(3000, 8080), (3001, 8081), (3002, 8082)]),
+            http_filter: HttpFilterConfig {
+                header_filter: Some("x-user: me".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cases = [
+            (
+                "127.0.0.1:3000",
+                IncomingPort::Ignored(IgnoredPort::Localhost),
+            ),
+            (
+                "0.0.0.0:9999",
+                IncomingPort::Ignored(IgnoredPort::IgnorePorts),
+            ),
+            ("0.0.0.0:0", IncomingPort::Ignored(IgnoredPort::PortZero)),
+            (
+                "0.0.0.0:3000",
+                IncomingPort::Remote {
+                    remote_port: 8080,
+                    filtered: true,
+                },
+            ),
+            (
+                "0.0.0.0:3001",
+                IncomingPort::Remote {
+                    remote_port: 8081,
+                    filtered: false,
+                },
+            ),
+            (
+                "0.0.0.0:3002",
+                IncomingPort::Ignored(IgnoredPort::NotInPorts(8082)),
+            ),
+        ];
+        for (address, expected) in cases {
+            assert_eq!(
+                config.tcp_port(address.parse().unwrap()),
+                expected,
+                "{address}"
+            );
+        }
+
+        config.ports = None;
+        assert_eq!(
+            config.tcp_port("0.0.0.0:3001".parse().unwrap()),
+            IncomingPort::Ignored(IgnoredPort::NotInHttpFilterPorts(8081))
+        );
+    }
+}
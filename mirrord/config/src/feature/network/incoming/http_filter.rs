@@ -1,5 +1,6 @@
 use std::{collections::HashSet, ops::Deref, str::FromStr};
 
+use jsonpath_rust::JsonPathInst;
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
@@ -55,6 +56,19 @@ use crate::{
 /// ```
 /// Setting this filter will make mirrord only steal requests to URIs that do not start with
 /// "/health/".
+///
+/// Requests can also be filtered by method, query parameters or body:
+/// ```json
+/// {
+///   "all_of": [
+///     { "method": "POST" },
+///     { "query": "^tenant=foo$" },
+///     { "body": { "json_path": "$.user.tenant", "matches": "^foo$" } }
+///   ]
+/// }
+/// ```
+/// Setting this filter will make mirrord only steal `POST` requests with the `tenant=foo` query
+/// parameter and a JSON body where `user.tenant` is `"foo"`.
 #[derive(MirrordConfig, Default, PartialEq, Eq, Clone, Debug, Serialize)]
 #[config(map_to = "HttpFilterFileConfig", derive = "JsonSchema")]
 #[cfg_attr(test, config(derive = "PartialEq, Eq"))]
@@ -81,6 +95,34 @@ pub struct HttpFilterConfig {
     #[config(env = "MIRRORD_HTTP_PATH_FILTER")]
     pub path_filter: Option<String>,
 
+    /// ##### feature.network.incoming.http_filter.method_filter {#feature-network-incoming-http-method-filter}
+    ///
+    ///
+    /// Steal only requests with this HTTP method, e.g. `"POST"`.
+    ///
+    /// Case-insensitive.
+    #[config(env = "MIRRORD_HTTP_METHOD_FILTER")]
+    pub method_filter: Option<String>,
+
+    /// ##### feature.network.incoming.http_filter.query_filter {#feature-network-incoming-http-query-filter}
+    ///
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    ///
+    /// Case-insensitive. Matched against each `key=value` pair of the query string, as it
+    /// appears in the URI (not percent-decoded). If any pair matches, the request is stolen.
+    #[config(env = "MIRRORD_HTTP_QUERY_FILTER")]
+    pub query_filter: Option<String>,
+
+    /// ##### feature.network.incoming.http_filter.body_filter {#feature-network-incoming-http-body-filter}
+    ///
+    /// Filter requests by their body, see [`BodyFilter`].
+    ///
+    /// Only bodies up to 64KiB are inspected, requests with bigger bodies are never stolen by
+    /// this filter.
+    pub body_filter: Option<BodyFilter>,
+
     /// #### feature.network.incoming.http_filter.all_of {#feature-network-incoming-http_filter-all_of}
     ///
     /// Messages must match all of the specified filters.
@@ -109,6 +151,9 @@ impl HttpFilterConfig {
     pub fn is_filter_set(&self) -> bool {
         self.header_filter.is_some()
             || self.path_filter.is_some()
+            || self.method_filter.is_some()
+            || self.query_filter.is_some()
+            || self.body_filter.is_some()
             || self.all_of.is_some()
             || self.any_of.is_some()
     }
@@ -117,9 +162,72 @@ impl HttpFilterConfig {
         self.all_of.is_some() || self.any_of.is_some()
     }
 
+    /// Whether a method, query or body filter is used, either directly or inside a composite
+    /// filter.
+    pub fn has_method_query_or_body(&self) -> bool {
+        self.method_filter.is_some()
+            || self.query_filter.is_some()
+            || self.body_filter.is_some()
+            || [self.all_of.as_ref(), self.any_of.as_ref()]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|filter| {
+                    matches!(
+                        filter,
+                        InnerFilter::Method { .. }
+                            | InnerFilter::Query { .. }
+                            | InnerFilter::Body { .. }
+                    )
+                })
+    }
+
     pub fn get_filtered_ports(&self) -> Option<&[u16]> {
         self.is_filter_set().then(|| &*self.ports.0)
     }
+
+    /// Checks that all regexes and JSONPath queries in the filter are valid.
+    pub fn verify(&self) -> Result<(), ConfigError> {
+        const HEADER: &str = "feature.network.incoming.http_filter.header_filter";
+        const PATH: &str = "feature.network.incoming.http_filter.path_filter";
+        const QUERY: &str = "feature.network.incoming.http_filter.query_filter";
+        const BODY: &str = "feature.network.incoming.http_filter.body_filter";
+        const ALL_OF: &str = "feature.network.incoming.http_filter.all_of";
+        const ANY_OF: &str = "feature.network.incoming.http_filter.any_of";
+
+        for (name, regex) in [
+            (HEADER, &self.header_filter),
+            (PATH, &self.path_filter),
+            (QUERY, &self.query_filter),
+        ] {
+            if let Some(regex) = regex {
+                verify_regex(name, regex)?;
+            }
+        }
+
+        if let Some(body) = &self.body_filter {
+            body.verify(BODY)?;
+        }
+
+        for (name, filters) in [(ALL_OF, &self.all_of), (ANY_OF, &self.any_of)] {
+            for filter in filters.iter().flatten() {
+                filter.verify(name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the HTTP filter `regex` is valid.
+fn verify_regex(name: &'static str, regex: &str) -> Result<(), ConfigError> {
+    fancy_regex::Regex::new(regex)
+        .map(drop)
+        .map_err(|error| ConfigError::InvalidValue {
+            name,
+            provided: regex.to_string(),
+            error: error.into(),
+        })
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
@@ -144,6 +252,81 @@ pub enum InnerFilter {
     /// Case-insensitive. Tries to find match in the path (without query) and path+query.
     /// If any of the two matches, the request is stolen.
     Path { path: String },
+
+    /// ##### feature.network.incoming.inner_filter.method_filter {#feature-network-incoming-inner-method-filter}
+    ///
+    ///
+    /// Steal only requests with this HTTP method, e.g. `"POST"`.
+    ///
+    /// Case-insensitive.
+    Method { method: String },
+
+    /// ##### feature.network.incoming.inner_filter.query_filter {#feature-network-incoming-inner-query-filter}
+    ///
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    ///
+    /// Case-insensitive. Matched against each `key=value` pair of the query string, as it
+    /// appears in the URI (not percent-decoded). If any pair matches, the request is stolen.
+    Query { query: String },
+
+    /// ##### feature.network.incoming.inner_filter.body_filter {#feature-network-incoming-inner-body-filter}
+    ///
+    /// Filter requests by their body, see [`BodyFilter`].
+    Body { body: BodyFilter },
+}
+
+impl InnerFilter {
+    /// Checks that the regexes and JSONPath queries in this filter are valid.
+    fn verify(&self, name: &'static str) -> Result<(), ConfigError> {
+        match self {
+            Self::Header { header: regex }
+            | Self::Path { path: regex }
+            | Self::Query { query: regex } => verify_regex(name, regex),
+            Self::Method { .. } => Ok(()),
+            Self::Body { body } => body.verify(name),
+        }
+    }
+}
+
+/// Filter for the HTTP request body.
+///
+/// Only bodies up to 64KiB are inspected, requests with bigger bodies never match.
+///
+/// Either a regex matched against the whole body:
+/// ```json
+/// { "regex": "\"tenant\": ?\"foo\"" }
+/// ```
+///
+/// Or a [JSONPath](https://goessner.net/articles/JsonPath/) query evaluated on a JSON body,
+/// with the selected values matched against a regex (JSON strings are matched without quotes):
+/// ```json
+/// { "json_path": "$.user.tenant", "matches": "^foo$" }
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BodyFilter {
+    Json { json_path: String, matches: String },
+    Regex { regex: String },
+}
+
+impl BodyFilter {
+    /// Checks that the regex and the JSONPath query of this filter are valid.
+    fn verify(&self, name: &'static str) -> Result<(), ConfigError> {
+        match self {
+            Self::Regex { regex } => verify_regex(name, regex),
+            Self::Json { json_path, matches } => {
+                JsonPathInst::from_str(json_path).map_err(|error| ConfigError::InvalidValue {
+                    name,
+                    provided: json_path.clone(),
+                    error: error.into(),
+                })?;
+
+                verify_regex(name, matches)
+            }
+        }
+    }
 }
 
 /// <!--${internal}-->
@@ -167,6 +350,16 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
             .source_value(context)
             .transpose()?;
 
+        let method_filter = FromEnv::new("MIRRORD_HTTP_METHOD_FILTER")
+            .source_value(context)
+            .transpose()?;
+
+        let query_filter = FromEnv::new("MIRRORD_HTTP_QUERY_FILTER")
+            .source_value(context)
+            .transpose()?;
+
+        let body_filter = None;
+
         let all_of = None;
         let any_of = None;
 
@@ -178,6 +371,9 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
         Ok(Self::Generated {
             header_filter,
             path_filter,
+            method_filter,
+            query_filter,
+            body_filter,
             all_of,
             any_of,
             ports,
@@ -223,6 +419,41 @@ impl CollectAnalytics for &HttpFilterConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("header_filter", self.header_filter.is_some());
         analytics.add("path_filter", self.path_filter.is_some());
+        analytics.add("method_filter", self.method_filter.is_some());
+        analytics.add("query_filter", self.query_filter.is_some());
+        analytics.add("body_filter", self.body_filter.is_some());
         analytics.add("ports", self.ports.len());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(BodyFilter::Json { json_path: "$.user.tenant".into(), matches: "^foo$".into() }, true)]
+    #[case(BodyFilter::Json { json_path: "$.user[".into(), matches: "^foo$".into() }, false)]
+    #[case(BodyFilter::Json { json_path: "$.user".into(), matches: "(foo".into() }, false)]
+    #[case(BodyFilter::Regex { regex: "tenant".into() }, true)]
+    #[case(BodyFilter::Regex { regex: "(tenant".into() }, false)]
+    fn verify_body_filter(#[case] body: BodyFilter, #[case] valid: bool) {
+        let direct = HttpFilterConfig {
+            body_filter: Some(body.clone()),
+            ..Default::default()
+        };
+        assert_eq!(direct.verify().is_ok(), valid);
+
+        let composite = HttpFilterConfig {
+            any_of: Some(vec![
+                InnerFilter::Method {
+                    method: "POST".into(),
+                },
+                InnerFilter::Body { body },
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(composite.verify().is_ok(), valid);
+    }
+}
@@ -0,0 +1,112 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Rewrites applied to the stolen HTTP traffic, before requests reach the local application and
+/// before responses are sent back to the remote peer.
+///
+/// Rules are applied in the given order. Header names are case-insensitive.
+///
+/// For example, to strip the auth header, mark the requests as debug ones, and serve `/api/v1`
+/// from the local `/` (responses get an extra header):
+/// ```json
+/// {
+///   "request": [
+///     { "remove_header": "authorization" },
+///     { "set_header": "x-debug", "value": "1" },
+///     { "set_header": "host", "value": "localhost" },
+///     { "replace_path_prefix": "/api/v1", "with": "/" }
+///   ],
+///   "response": [
+///     { "set_header": "x-served-by", "value": "mirrord" }
+///   ]
+/// }
+/// ```
+///
+/// Only does something when [`feature.network.incoming.mode`](#feature-network-incoming-mode) is
+/// set as `"steal"` with an [`http_filter`](#feature-network-incoming-http-filter), as otherwise
+/// mirrord does not parse the HTTP traffic.
+#[derive(Default, PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpRewriteConfig {
+    /// ##### feature.network.incoming.http_rewrite.request {#feature-network-incoming-http_rewrite-request}
+    ///
+    /// Rules applied to the requests.
+    #[serde(default)]
+    pub request: Vec<HttpRewriteRule>,
+
+    /// ##### feature.network.incoming.http_rewrite.response {#feature-network-incoming-http_rewrite-response}
+    ///
+    /// Rules applied to the responses. Path rules are not allowed here.
+    #[serde(default)]
+    pub response: Vec<HttpRewriteRule>,
+}
+
+impl HttpRewriteConfig {
+    pub fn is_empty(&self) -> bool {
+        self.request.is_empty() && self.response.is_empty()
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HttpRewriteRule {
+    /// ##### feature.network.incoming.http_rewrite_rule.remove_header {#feature-network-incoming-http_rewrite_rule-remove_header}
+    ///
+    /// Removes all values of the header.
+    RemoveHeader { remove_header: String },
+
+    /// ##### feature.network.incoming.http_rewrite_rule.set_header {#feature-network-incoming-http_rewrite_rule-set_header}
+    ///
+    /// Replaces all values of the header with the given `value`, or adds the header if it's
+    /// missing.
+    SetHeader { set_header: String, value: String },
+
+    /// ##### feature.network.incoming.http_rewrite_rule.replace_path_prefix {#feature-network-incoming-http_rewrite_rule-replace_path_prefix}
+    ///
+    /// Replaces the prefix of the request path with the value of `with`. Requests with paths
+    /// that do not start with the prefix are not changed. The prefix matches whole path segments
+    /// only, e.g. `/api` matches `/api/items`, but not `/apiary`. The query is preserved.
+    ReplacePathPrefix {
+        replace_path_prefix: String,
+        with: String,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_rules() {
+        let config: HttpRewriteConfig = serde_json::from_str(
+            r#"{
+                "request": [
+                    { "remove_header": "authorization" },
+                    { "set_header": "x-debug", "value": "1" },
+                    { "replace_path_prefix": "/api", "with": "/" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            HttpRewriteConfig {
+                request: vec![
+                    HttpRewriteRule::RemoveHeader {
+                        remove_header: "authorization".into()
+                    },
+                    HttpRewriteRule::SetHeader {
+                        set_header: "x-debug".into(),
+                        value: "1".into()
+                    },
+                    HttpRewriteRule::ReplacePathPrefix {
+                        replace_path_prefix: "/api".into(),
+                        with: "/".into()
+                    },
+                ],
+                response: vec![],
+            }
+        );
+    }
+}
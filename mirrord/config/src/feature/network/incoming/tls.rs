@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// Lets the mirrord-agent terminate TLS on the given ports, so that
+/// [`http_filter`](#feature-network-incoming-http-filter) can be applied to HTTPS traffic.
+///
+/// Requests that match the filter are sent to the local application decrypted. Requests that do
+/// not match are encrypted again and sent to their original destination.
+///
+/// The certificate and the private key are taken either from a Kubernetes Secret of type
+/// `kubernetes.io/tls`, or from PEM files in the target container:
+/// ```json
+/// {
+///   "ports": [443],
+///   "secret": "my-service-tls"
+/// }
+/// ```
+/// ```json
+/// {
+///   "ports": [443],
+///   "certificate_path": "/etc/tls/tls.crt",
+///   "key_path": "/etc/tls/tls.key"
+/// }
+/// ```
+///
+/// Only does something when [`feature.network.incoming.mode`](#feature-network-incoming-mode) is
+/// set as `"steal"` with an [`http_filter`](#feature-network-incoming-http-filter).
+#[derive(Default, PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IncomingTlsConfig {
+    /// ##### feature.network.incoming.tls.ports {#feature-network-incoming-tls-ports}
+    ///
+    /// Ports on which the remote application accepts TLS connections.
+    pub ports: Vec<u16>,
+
+    /// ##### feature.network.incoming.tls.secret {#feature-network-incoming-tls-secret}
+    ///
+    /// Name of a Kubernetes Secret of type `kubernetes.io/tls` that holds the certificate and the
+    /// private key (`tls.crt` and `tls.key`).
+    ///
+    /// The Secret has to be in the namespace in which the agent is created.
+    ///
+    /// Mutually exclusive with
+    /// [`certificate_path`](#feature-network-incoming-tls-certificate_path) and
+    /// [`key_path`](#feature-network-incoming-tls-key_path).
+    pub secret: Option<String>,
+
+    /// ##### feature.network.incoming.tls.certificate_path {#feature-network-incoming-tls-certificate_path}
+    ///
+    /// Path to the PEM-encoded certificate chain, inside of the target container.
+    pub certificate_path: Option<PathBuf>,
+
+    /// ##### feature.network.incoming.tls.key_path {#feature-network-incoming-tls-key_path}
+    ///
+    /// Path to the PEM-encoded private key, inside of the target container.
+    pub key_path: Option<PathBuf>,
+}
+
+impl IncomingTlsConfig {
+    /// Checks that the certificate is given exactly once, either as a Secret or as a pair of
+    /// paths.
+    pub fn verify(&self) -> Result<(), ConfigError> {
+        if self.ports.is_empty() {
+            return Err(ConfigError::Conflict(
+                "`feature.network.incoming.tls.ports` cannot be empty".to_string(),
+            ));
+        }
+
+        match (&self.secret, &self.certificate_path, &self.key_path) {
+            (Some(..), None, None) | (None, Some(..), Some(..)) => Ok(()),
+            (Some(..), ..) => Err(ConfigError::Conflict(
+                "cannot use both `secret` and file paths in `feature.network.incoming.tls`"
+                    .to_string(),
+            )),
+            (None, ..) => Err(ConfigError::Conflict(
+                "`feature.network.incoming.tls` requires either `secret`, or both \
+                `certificate_path` and `key_path`"
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(r#"{ "ports": [443], "secret": "tls" }"#, true)]
+    #[case(
+        r#"{ "ports": [443], "certificate_path": "/a", "key_path": "/b" }"#,
+        true
+    )]
+    #[case(r#"{ "ports": [], "secret": "tls" }"#, false)]
+    #[case(r#"{ "ports": [443], "certificate_path": "/a" }"#, false)]
+    #[case(r#"{ "ports": [443], "secret": "tls", "key_path": "/b" }"#, false)]
+    fn verify(#[case] config: &str, #[case] valid: bool) {
+        let config: IncomingTlsConfig = serde_json::from_str(config).unwrap();
+        assert_eq!(config.verify().is_ok(), valid);
+    }
+}
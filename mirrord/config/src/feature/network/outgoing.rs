@@ -5,12 +5,16 @@ use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use self::{faults::OutgoingFaultsConfig, mock::OutgoingMockConfig};
 use super::filter::ProtocolAndAddressFilter;
 use crate::{
     config::{from_env::FromEnv, source::MirrordConfigSource, ConfigContext, ConfigError},
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
+pub mod faults;
+pub mod mock;
+
 /// List of addresses/ports/subnets that should be sent through either the remote pod or local app,
 /// depending how you set this up with either `remote` or `local`.
 ///
@@ -133,6 +137,64 @@ pub struct OutgoingConfig {
     /// to happen locally on your machine.
     #[config(unstable, env = "MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS")]
     pub unix_streams: Option<VecOrSingle<String>>,
+
+    /// #### feature.network.outgoing.mock {#feature.network.outgoing.mock}
+    ///
+    /// HTTP responses served locally for requests to the given remote services, useful when a
+    /// dependency does not exist yet, or is flaky. Requests that don't match any of the mocks are
+    /// sent through the target as usual.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "outgoing": {
+    ///         "mock": [
+    ///           {
+    ///             "address": "users.default:8080",
+    ///             "method": "GET",
+    ///             "path": "/v1/users",
+    ///             "response": "./mocks/users.json"
+    ///           }
+    ///         ]
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// See [`feature.network.outgoing.mock`](#feature-network-outgoing-mock-address) for the
+    /// fields of a single mock.
+    #[config(default)]
+    pub mock: Vec<OutgoingMockConfig>,
+
+    /// #### feature.network.outgoing.faults {#feature.network.outgoing.faults}
+    ///
+    /// Latency, bandwidth limits, resets and connect failures injected into the outgoing
+    /// connections made through the target.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "outgoing": {
+    ///         "faults": {
+    ///           "seed": 42,
+    ///           "rules": [
+    ///             { "filter": "tcp://payments.default:8080", "latency_ms": 200 },
+    ///             { "filter": "10.0.0.0/16", "connect_failure_percent": 10 }
+    ///           ]
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// See [`feature.network.outgoing.faults`](#feature-network-outgoing-faults-rules-filter) for
+    /// the fields of a single rule.
+    #[config(default)]
+    pub faults: OutgoingFaultsConfig,
 }
 
 impl MirrordToggleableConfig for OutgoingFileConfig {
@@ -165,6 +227,9 @@ impl CollectAnalytics for &OutgoingConfig {
                 .unwrap_or_default(),
         );
 
+        analytics.add("mock", self.mock.len());
+        analytics.add("faults", self.faults.rules.len());
+
         if let Some(filter) = self.filter.as_ref() {
             match filter {
                 OutgoingFilterConfig::Remote(value) => {
@@ -180,6 +245,43 @@ impl CollectAnalytics for &OutgoingConfig {
 
 impl OutgoingConfig {
     pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
+        if let Some(mock) = self.mock.iter().find(|mock| mock.host_and_port().is_none()) {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.outgoing.mock.address",
+                provided: mock.address.clone(),
+                error: "expected `host:port`".into(),
+            });
+        }
+
+        for rule in &self.faults.rules {
+            if let Err(error) = rule.filter.parse::<ProtocolAndAddressFilter>() {
+                return Err(ConfigError::InvalidValue {
+                    name: "feature.network.outgoing.faults.rules.filter",
+                    provided: rule.filter.clone(),
+                    error: Box::new(error),
+                });
+            }
+
+            for (name, percent) in [
+                (
+                    "feature.network.outgoing.faults.rules.reset_percent",
+                    rule.reset_percent,
+                ),
+                (
+                    "feature.network.outgoing.faults.rules.connect_failure_percent",
+                    rule.connect_failure_percent,
+                ),
+            ] {
+                if percent > 100 {
+                    return Err(ConfigError::InvalidValue {
+                        name,
+                        provided: percent.to_string(),
+                        error: "expected a value between 0 and 100".into(),
+                    });
+                }
+            }
+        }
+
         let filters = match self.filter.as_ref() {
             None => return Ok(()),
             Some(OutgoingFilterConfig::Local(filters)) => filters.deref(),
@@ -206,6 +308,10 @@ impl OutgoingConfig {
 mod tests {
     use rstest::rstest;
 
+    use super::{
+        faults::{OutgoingFaultRule, OutgoingFaultsConfig},
+        OutgoingConfig,
+    };
     use crate::{
         config::{ConfigContext, MirrordConfig},
         feature::network::OutgoingFileConfig,
@@ -267,4 +373,33 @@ mod tests {
             },
         );
     }
+
+    #[rstest]
+    #[case("tcp://payments.default:8080", 1, 10, true)]
+    #[case("udp://10.0.0.0/16", 0, 100, true)]
+    #[case("tcp://10.0.0.0/x", 0, 0, false)]
+    #[case(":8080", 101, 0, false)]
+    #[case(":8080", 0, 200, false)]
+    fn verify_faults(
+        #[case] filter: &str,
+        #[case] reset_percent: u8,
+        #[case] connect_failure_percent: u8,
+        #[case] valid: bool,
+    ) {
+        let config = OutgoingConfig {
+            faults: OutgoingFaultsConfig {
+                seed: None,
+                rules: vec![OutgoingFaultRule {
+                    filter: filter.to_string(),
+                    latency_ms: 100,
+                    bandwidth: None,
+                    reset_percent,
+                    connect_failure_percent,
+                }],
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(config.verify(&mut ConfigContext::default()).is_ok(), valid);
+    }
 }
@@ -0,0 +1,75 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Faults injected into outgoing connections made through the target, useful for testing how the
+/// application deals with slow or unreliable dependencies.
+///
+/// ```json
+/// {
+///   "seed": 42,
+///   "rules": [
+///     {
+///       "filter": "tcp://payments.default:8080",
+///       "latency_ms": 200,
+///       "bandwidth": 65536,
+///       "reset_percent": 1,
+///       "connect_failure_percent": 10
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Default, PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutgoingFaultsConfig {
+    /// ##### feature.network.outgoing.faults.seed {#feature-network-outgoing-faults-seed}
+    ///
+    /// Seed for the random faults. With a seed set, the same sequence of outgoing connections is
+    /// affected by the same faults in every run.
+    ///
+    /// Random when not set.
+    pub seed: Option<u64>,
+
+    /// ##### feature.network.outgoing.faults.rules {#feature-network-outgoing-faults-rules}
+    ///
+    /// Faults to inject, the first rule whose filter matches the connection is used.
+    #[serde(default)]
+    pub rules: Vec<OutgoingFaultRule>,
+}
+
+/// Faults injected into the outgoing connections matching the filter.
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutgoingFaultRule {
+    /// ###### feature.network.outgoing.faults.rules.filter {#feature-network-outgoing-faults-rules-filter}
+    ///
+    /// Connections affected by this rule, in the same format as the
+    /// [`feature.network.outgoing.filter`](#feature.network.outgoing.filter) values, e.g.
+    /// `tcp://payments.default:8080` or `10.0.0.0/16`.
+    ///
+    /// Names are resolved with the cluster DNS, through the agent. Connections wait until the
+    /// names are resolved when the session starts.
+    pub filter: String,
+
+    /// ###### feature.network.outgoing.faults.rules.latency_ms {#feature-network-outgoing-faults-rules-latency_ms}
+    ///
+    /// Delay added to the data sent in each direction, in milliseconds.
+    #[serde(default)]
+    pub latency_ms: u64,
+
+    /// ###### feature.network.outgoing.faults.rules.bandwidth {#feature-network-outgoing-faults-rules-bandwidth}
+    ///
+    /// Limit for the data sent in each direction, in bytes per second.
+    pub bandwidth: Option<u64>,
+
+    /// ###### feature.network.outgoing.faults.rules.reset_percent {#feature-network-outgoing-faults-rules-reset_percent}
+    ///
+    /// Chance (`0`-`100`) that the connection is reset when relaying each chunk of data.
+    #[serde(default)]
+    pub reset_percent: u8,
+
+    /// ###### feature.network.outgoing.faults.rules.connect_failure_percent {#feature-network-outgoing-faults-rules-connect_failure_percent}
+    ///
+    /// Chance (`0`-`100`) that the connection is refused.
+    #[serde(default)]
+    pub connect_failure_percent: u8,
+}
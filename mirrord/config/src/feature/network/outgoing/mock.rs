@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A canned HTTP response served locally by mirrord for requests to a remote service, instead of
+/// connecting to the service through the target.
+///
+/// For example, to answer `GET http://users.default:8080/v1/users` with the contents of a local
+/// file:
+/// ```json
+/// {
+///   "address": "users.default:8080",
+///   "method": "GET",
+///   "path": "/v1/users",
+///   "response": "./mocks/users.json",
+///   "headers": { "content-type": "application/json" }
+/// }
+/// ```
+///
+/// Only plain HTTP/1 traffic can be mocked. Connections to a mocked port are sent through the
+/// target only after the application sends its first request (if it does not match any mock),
+/// so don't mock ports of protocols in which the server speaks first.
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutgoingMockConfig {
+    /// ##### feature.network.outgoing.mock.address {#feature-network-outgoing-mock-address}
+    ///
+    /// `host:port` of the mocked service. The host is either an IP address, or a name. A name is
+    /// resolved with the remote DNS, and matches connections to the resolved addresses, and only
+    /// the requests with the same `Host` header.
+    ///
+    /// When the name does not resolve with the remote DNS (e.g. the service does not exist yet),
+    /// the application's DNS lookups of the name get a placeholder address, and connections to
+    /// that address are mocked.
+    pub address: String,
+
+    /// ##### feature.network.outgoing.mock.method {#feature-network-outgoing-mock-method}
+    ///
+    /// HTTP method of the mocked requests, case-insensitive. Requests with any method are mocked
+    /// when not set.
+    pub method: Option<String>,
+
+    /// ##### feature.network.outgoing.mock.path {#feature-network-outgoing-mock-path}
+    ///
+    /// Path of the mocked requests, e.g. `/v1/users`. The query is ignored when matching.
+    pub path: String,
+
+    /// ##### feature.network.outgoing.mock.response {#feature-network-outgoing-mock-response}
+    ///
+    /// Path of the file with the body of the mocked response. A relative path is resolved against
+    /// the directory of the config file.
+    pub response: PathBuf,
+
+    /// ##### feature.network.outgoing.mock.status {#feature-network-outgoing-mock-status}
+    ///
+    /// Status code of the mocked response.
+    ///
+    /// Defaults to `200`.
+    #[serde(default = "default_status")]
+    pub status: u16,
+
+    /// ##### feature.network.outgoing.mock.headers {#feature-network-outgoing-mock-headers}
+    ///
+    /// Headers of the mocked response. `content-length` is always set by mirrord.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl OutgoingMockConfig {
+    /// Splits [`OutgoingMockConfig::address`] into the host and the port.
+    ///
+    /// Returns [`None`] if the address has no valid port.
+    pub fn host_and_port(&self) -> Option<(&str, u16)> {
+        let (host, port) = self.address.rsplit_once(':')?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+
+        Some((host, port.parse().ok()?))
+    }
+
+    /// Returns [`OutgoingMockConfig::response`], resolved against the directory of the
+    /// `config_file` if it's relative.
+    pub fn response_path(&self, config_file: Option<&Path>) -> PathBuf {
+        match config_file.and_then(Path::parent) {
+            Some(config_dir) if self.response.is_relative() => config_dir.join(&self.response),
+            _ => self.response.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("users.default:8080", Some(("users.default", 8080)))]
+    #[case("10.0.0.1:80", Some(("10.0.0.1", 80)))]
+    #[case("[::1]:80", Some(("::1", 80)))]
+    #[case("users.default", None)]
+    #[case("users.default:http", None)]
+    fn host_and_port(#[case] address: &str, #[case] expected: Option<(&str, u16)>) {
+        let mock: OutgoingMockConfig = serde_json::from_value(serde_json::json!({
+            "address": address,
+            "path": "/",
+            "response": "response.json",
+        }))
+        .unwrap();
+
+        assert_eq!(mock.status, 200);
+        assert_eq!(mock.host_and_port(), expected);
+    }
+
+    #[rstest]
+    #[case("response.json", Some("/app/mirrord.json"), "/app/response.json")]
+    #[case(
+        "/mocks/response.json",
+        Some("/app/mirrord.json"),
+        "/mocks/response.json"
+    )]
+    #[case("response.json", None, "response.json")]
+    fn response_path(
+        #[case] response: &str,
+        #[case] config_file: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let mock: OutgoingMockConfig = serde_json::from_value(serde_json::json!({
+            "address": "users.default:8080",
+            "path": "/",
+            "response": response,
+        }))
+        .unwrap();
+
+        assert_eq!(
+            mock.response_path(config_file.map(Path::new)),
+            Path::new(expected)
+        );
+    }
+}
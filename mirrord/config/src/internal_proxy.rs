@@ -113,6 +113,48 @@ pub struct InternalProxyConfig {
     /// Set the log file destination for the internal proxy.
     pub log_destination: Option<String>,
 
+    /// ### internal_proxy.reconnect {#internal_proxy-reconnect}
+    ///
+    /// When the connection with the agent drops (e.g. the port forwarding is reset), the internal
+    /// proxy makes a new one instead of exiting. If the agent is gone, and the agent was created
+    /// by mirrord (without the operator), a new agent is created.
+    ///
+    /// Port subscriptions are sent to the new agent again, and remote files opened by the
+    /// application are reopened. The application sees only a brief stall, but connections that
+    /// were open at the time (incoming and outgoing) are closed. File operations that were in
+    /// flight and are not safe to repeat (e.g. writes, renames or unlinks) fail with `EIO`, as do
+    /// reads from directories opened before the reconnection.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "reconnect": true
+    ///   }
+    /// }
+    /// ```
+    #[config(default = false)]
+    pub reconnect: bool,
+
+    /// ### internal_proxy.reconnect_timeout {#internal_proxy-reconnect_timeout}
+    ///
+    /// How much time (in seconds) the internal proxy keeps trying to reconnect to the agent, when
+    /// [`internal_proxy.reconnect`](#internal_proxy-reconnect) is enabled.
+    #[config(default = 60)]
+    pub reconnect_timeout: u64,
+
+    /// ### internal_proxy.inspector {#internal_proxy-inspector}
+    ///
+    /// When enabled, the internal proxy serves a live stream of the session events (stolen
+    /// connections and HTTP requests, remote files, DNS queries and outgoing connections) on a
+    /// unix socket, which can be viewed with `mirrord inspect`.
+    ///
+    /// The socket is placed in `$XDG_RUNTIME_DIR/mirrord-inspect`, or in a per-user directory in
+    /// the temp dir, and is accessible only by the current user.
+    ///
+    /// Defaults to `false`.
+    #[config(default = false)]
+    pub inspector: bool,
+
     /// <!--${internal}-->
     ///
     /// This informs the intproxy that it's running inside a continer and should not detach io
@@ -23,7 +23,7 @@ use std::{
     path::Path,
 };
 
-use config::{ConfigContext, ConfigError, MirrordConfig};
+use config::{file::ConfigSources, ConfigContext, ConfigError, MirrordConfig};
 use experimental::ExperimentalConfig;
 use feature::{env::mapper::EnvVarsRemapper, network::outgoing::OutgoingFilterConfig};
 use mirrord_analytics::CollectAnalytics;
@@ -31,7 +31,6 @@ use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::Serialize;
 use target::Target;
-use tera::Tera;
 use tracing::warn;
 
 use crate::{
@@ -43,6 +42,13 @@ use crate::{
 /// Env variable to load config from file (json, yaml and toml supported).
 pub static MIRRORD_CONFIG_FILE_ENV: &str = "MIRRORD_CONFIG_FILE";
 
+/// Env variable to select one of the [`LayerConfig::profiles`] of the config file.
+pub static MIRRORD_PROFILE_ENV: &str = "MIRRORD_PROFILE";
+
+/// Env variable with the config template variables resolved in the CLI, see
+/// [`ConfigVars`](config::template::ConfigVars).
+pub static MIRRORD_CONFIG_VARS_ENV: &str = "MIRRORD_CONFIG_VARS";
+
 /// mirrord allows for a high degree of customization when it comes to which features you want to
 /// enable, and how they should function.
 ///
@@ -50,8 +56,15 @@ pub static MIRRORD_CONFIG_FILE_ENV: &str = "MIRRORD_CONFIG_FILE";
 /// configuration at all.
 ///
 /// The configuration supports templating using the [Tera](https://keats.github.io/tera/docs/) template engine.
-/// Currently we don't provide additional values to the context, if you have anything you want us to
-/// provide please let us know.
+/// The templates can use these variables:
+///
+/// - `user`, `git_branch` (of the repository in the current directory) and `kube_context` (the one
+///   selected with `--context`, or the current one), when they can be determined;
+/// - the variables from the sidecar vars file, e.g. `mirrord.vars.json` (or `.toml`, `.yaml`) for
+///   the `mirrord.json` config file;
+/// - the variables passed with `--config-var name=value` in the CLI.
+///
+/// Later sources override the earlier ones.
 ///
 /// To use a configuration file in the CLI, use the `-f <CONFIG_PATH>` flag.
 /// Or if using VSCode Extension or JetBrains plugin, simply create a `.mirrord/mirrord.json` file
@@ -86,6 +99,24 @@ pub static MIRRORD_CONFIG_FILE_ENV: &str = "MIRRORD_CONFIG_FILE";
 /// }
 /// ```
 ///
+/// ### `config.json` with template variables {#root-basic-template-variables}
+///
+/// ```json
+/// {
+///   "target": "deployment/my-service",
+///   "feature": {
+///     "network": {
+///       "incoming": {
+///         "mode": "steal",
+///         "http_filter": {
+///           "header_filter": "X-User: {{ user }}"
+///         }
+///       }
+///     }
+///   }
+/// }
+/// ```
+///
 /// ### Complete `config.json` {#root-complete}
 ///
 ///  Don't use this example as a starting point, it's just here to show you all the available
@@ -322,6 +353,44 @@ pub struct LayerConfig {
     /// ## experimental {#root-experimental}
     #[config(nested)]
     pub experimental: ExperimentalConfig,
+
+    /// ## extends {#root-extends}
+    ///
+    /// Path to a config file that this config file is based on, relative to this config file.
+    ///
+    /// The config files are deep-merged: nested objects are merged, and all other values
+    /// (including arrays) from this config file replace the ones from the extended file.
+    /// Shorthands merged with an object are expanded first, e.g. `"incoming": "steal"` merged with
+    /// `"incoming": { "http_filter": ... }` keeps the `steal` mode. The extended file may extend
+    /// another config file.
+    ///
+    /// ```json
+    /// {
+    ///   "extends": "../team/mirrord.json",
+    ///   "target": "deployment/my-service"
+    /// }
+    /// ```
+    pub extends: Option<String>,
+
+    /// ## profiles {#root-profiles}
+    ///
+    /// Named partial configs, one of which can be deep-merged on top of the config file with the
+    /// `--profile` argument or the `MIRRORD_PROFILE` environment variable.
+    ///
+    /// ```json
+    /// {
+    ///   "target": "deployment/my-service",
+    ///   "profiles": {
+    ///     "steal": {
+    ///       "feature": { "network": { "incoming": "steal" } }
+    ///     },
+    ///     "staging": {
+    ///       "target": { "namespace": "staging" }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub profiles: Option<HashMap<String, serde_json::Value>>,
 }
 
 impl LayerConfig {
@@ -331,7 +400,11 @@ impl LayerConfig {
     pub fn from_env_with_warnings() -> Result<(Self, ConfigContext), ConfigError> {
         let mut cfg_context = ConfigContext::default();
         if let Ok(path) = std::env::var(MIRRORD_CONFIG_FILE_ENV) {
-            LayerFileConfig::from_path(path)?.generate_config(&mut cfg_context)
+            let profile = std::env::var(MIRRORD_PROFILE_ENV).ok();
+            let (config, sources) =
+                LayerFileConfig::from_path_with_sources(path, profile.as_deref())?;
+            cfg_context = cfg_context.with_sources(sources);
+            config.generate_config(&mut cfg_context)
         } else {
             LayerFileConfig::default().generate_config(&mut cfg_context)
         }
@@ -356,7 +429,8 @@ impl LayerConfig {
     ///   selected after `verify-config` is run.
     pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
         if self.agent.ephemeral && self.agent.namespace.is_some() {
-            context.add_warning(
+            context.add_value_warning(
+                "agent.namespace",
                 "Agent namespace is ignored when using an ephemeral container for the agent."
                     .to_string(),
             );
@@ -367,7 +441,8 @@ impl LayerConfig {
             Some(OutgoingFilterConfig::Remote(_))
         ) && !self.feature.network.dns.enabled
         {
-            context.add_warning(
+            context.add_value_warning(
+                "feature.network.outgoing.filter",
                 "The mirrord outgoing traffic filter includes host names to be connected remotely, \
                 but the remote DNS feature is disabled, so the addresses of these hosts will be \
                 resolved locally. Consider enabling the remote DNS resolution feature.".to_string(),
@@ -378,6 +453,9 @@ impl LayerConfig {
         let used_filters = [
             http_filter.path_filter.is_some(),
             http_filter.header_filter.is_some(),
+            http_filter.method_filter.is_some(),
+            http_filter.query_filter.is_some(),
+            http_filter.body_filter.is_some(),
             http_filter.all_of.is_some(),
             http_filter.any_of.is_some(),
         ]
@@ -400,6 +478,8 @@ impl LayerConfig {
             ))?;
         }
 
+        http_filter.verify()?;
+
         if !self.feature.network.incoming.ignore_ports.is_empty()
             && self.feature.network.incoming.ports.is_some()
         {
@@ -483,7 +563,8 @@ impl LayerConfig {
             }
 
             if !self.feature.network.incoming.is_steal() {
-                context.add_warning(
+                context.add_value_warning(
+                    "feature.copy_target",
                     "Using copy target feature without steal mode \
                     may result in unreturned responses in cluster \
                     because the underlying app instance is not copied \
@@ -512,7 +593,8 @@ impl LayerConfig {
             .iter()
             .any(|(to, from)| to == from)
         {
-            context.add_warning(
+            context.add_value_warning(
+                "feature.network.incoming.port_mapping",
                 "The feature.network.incoming.port_mapping mirrord configuration field \
                 contains a mapping of a local port to the same remote port. \
                 A mapping is only necessary when the local application is listening on \
@@ -533,12 +615,49 @@ impl LayerConfig {
             EnvVarsRemapper::new(env_vars_mapping, HashMap::new())?;
         }
 
+        if let Some(from_pod_spec) = self
+            .feature
+            .env
+            .from_pod_spec
+            .as_ref()
+            .filter(|from_pod_spec| from_pod_spec.is_enabled())
+        {
+            if self.feature.env.load_from_process.unwrap_or_default() {
+                return Err(ConfigError::Conflict(
+                    "cannot use both `from_pod_spec` and `load_from_process` for environment \
+                    variables"
+                        .to_string(),
+                ));
+            }
+
+            match from_pod_spec.resource()? {
+                Some(Target::Targetless) => {
+                    return Err(ConfigError::Conflict(
+                        "`feature.env.from_pod_spec` needs a resource with a pod spec, not \
+                        `targetless`"
+                            .to_string(),
+                    ));
+                }
+                None if self.target.path.is_none() && !context.ide => {
+                    return Err(ConfigError::Conflict(
+                        "`feature.env.from_pod_spec` is not compatible with a targetless agent, \
+                        please either specify a target or set `from_pod_spec` to the resource to \
+                        read the environment from, e.g. `deployment/my-app`."
+                            .to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
         self.feature.network.dns.verify(context)?;
+        self.feature.network.incoming.verify(context)?;
         self.feature.network.outgoing.verify(context)?;
         self.feature.split_queues.verify(context)?;
 
         if self.experimental.readlink {
-            context.add_warning(
+            context.add_value_warning(
+                "experimental.readlink",
                 "experimental.readlink config has been deprecated, and `readlink` is now\
                     enabled by default! You may remove it from your config."
                     .into(),
@@ -563,20 +682,31 @@ impl CollectAnalytics for &LayerConfig {
 }
 
 impl LayerFileConfig {
+    /// Loads the config file, together with the files it [`extends`](LayerConfig::extends).
+    ///
+    /// Applies the profile selected with [`MIRRORD_PROFILE_ENV`], if any.
     pub fn from_path<P>(path: P) -> Result<Self, ConfigError>
     where
         P: AsRef<Path>,
     {
-        let mut template_engine = Tera::default();
-        template_engine.add_template_file(path.as_ref(), Some("main"))?;
-        let rendered = template_engine.render("main", &tera::Context::new())?;
-
-        match path.as_ref().extension().and_then(|os_val| os_val.to_str()) {
-            Some("json") => Ok(serde_json::from_str::<Self>(&rendered)?),
-            Some("toml") => Ok(toml::from_str::<Self>(&rendered)?),
-            Some("yaml" | "yml") => Ok(serde_yaml::from_str::<Self>(&rendered)?),
-            _ => Err(ConfigError::UnsupportedFormat),
-        }
+        let profile = std::env::var(MIRRORD_PROFILE_ENV).ok();
+        Self::from_path_with_sources(path, profile.as_deref()).map(|(config, _)| config)
+    }
+
+    /// Loads the config file, together with the files it [`extends`](LayerConfig::extends), and
+    /// applies the given profile.
+    ///
+    /// Also returns the [`ConfigSources`], to be used with [`ConfigContext::with_sources`].
+    pub fn from_path_with_sources<P>(
+        path: P,
+        profile: Option<&str>,
+    ) -> Result<(Self, ConfigSources), ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        let (config, sources) = config::file::load(path.as_ref(), profile)?;
+
+        Ok((serde_json::from_value(config)?, sources))
     }
 }
 
@@ -869,6 +999,9 @@ mod tests {
                             listen_ports: None,
                             on_concurrent_steal: None,
                             ports: None,
+                            http_record: None,
+                            http_rewrite: None,
+                            tls: None,
                         }),
                     ))),
                     outgoing: Some(ToggleableConfig::Config(OutgoingFileConfig {
@@ -876,6 +1009,7 @@ mod tests {
                         udp: Some(false),
                         ..Default::default()
                     })),
+                    ipv6: None,
                 })),
                 copy_target: None,
                 hostname: None,
@@ -890,6 +1024,8 @@ mod tests {
             internal_proxy: None,
             use_proxy: None,
             experimental: None,
+            extends: None,
+            profiles: None,
         };
 
         assert_eq!(config, expect);
@@ -2,9 +2,12 @@ use core::fmt;
 use std::str::FromStr;
 
 use cron_job::CronJobTarget;
+use daemon_set::DaemonSetTarget;
 use mirrord_analytics::CollectAnalytics;
+use replica_set::ReplicaSetTarget;
 use schemars::{gen::SchemaGenerator, schema::SchemaObject, JsonSchema};
 use serde::{Deserialize, Serialize};
+use service::ServiceTarget;
 use stateful_set::StatefulSetTarget;
 
 use self::{deployment::DeploymentTarget, job::JobTarget, pod::PodTarget, rollout::RolloutTarget};
@@ -18,10 +21,13 @@ use crate::{
 };
 
 pub mod cron_job;
+pub mod daemon_set;
 pub mod deployment;
 pub mod job;
 pub mod pod;
+pub mod replica_set;
 pub mod rollout;
+pub mod service;
 pub mod stateful_set;
 
 #[derive(Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
@@ -115,6 +121,11 @@ pub struct TargetConfig {
     /// - `container/{sample-container}`;
     /// - `containername/{sample-container}`.
     /// - `job/{sample-job}` (only when [`copy_target`](#feature-copy_target) is enabled).
+    /// - `service/{sample-service}`, which targets a ready pod selected by the service. The
+    ///   service ports are mapped to their `targetPort`s, so your application can listen on the
+    ///   service ports (see [`port_mapping`](#feature-network-incoming-port_mapping));
+    /// - `replicaset/{sample-replicaset}`;
+    /// - `daemonset/{sample-daemonset}`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<Target>,
 
@@ -187,6 +198,9 @@ mirrord-layer failed to parse the provided target!
     >> job/<job-name>[/container/container-name]
     >> cronjob/<cronjob-name>[/container/container-name]
     >> statefulset/<statefulset-name>[/container/container-name]
+    >> service/<service-name>[/container/container-name]
+    >> replicaset/<replicaset-name>[/container/container-name]
+    >> daemonset/<daemonset-name>[/container/container-name]
 
 - Note:
     >> specifying container name is optional, defaults to the first container in the provided pod/deployment target.
@@ -211,6 +225,9 @@ mirrord-layer failed to parse the provided target!
 /// - `job/{sample-job}`;
 /// - `cronjob/{sample-cronjob}`;
 /// - `statefulset/{sample-statefulset}`;
+/// - `service/{sample-service}`;
+/// - `replicaset/{sample-replicaset}`;
+/// - `daemonset/{sample-daemonset}`;
 #[warn(clippy::wildcard_enum_match_arm)]
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, JsonSchema)]
 #[serde(untagged, deny_unknown_fields)]
@@ -247,6 +264,21 @@ pub enum Target {
     /// Only supported when `copy_target` is enabled.
     StatefulSet(stateful_set::StatefulSetTarget),
 
+    /// <!--${internal}-->
+    /// Targets a ready pod selected by a
+    /// [Service](https://kubernetes.io/docs/concepts/services-networking/service/).
+    Service(service::ServiceTarget),
+
+    /// <!--${internal}-->
+    /// Targets a
+    /// [ReplicaSet](https://kubernetes.io/docs/concepts/workloads/controllers/replicaset/).
+    ReplicaSet(replica_set::ReplicaSetTarget),
+
+    /// <!--${internal}-->
+    /// Targets a
+    /// [DaemonSet](https://kubernetes.io/docs/concepts/workloads/controllers/daemonset/).
+    DaemonSet(daemon_set::DaemonSetTarget),
+
     /// <!--${internal}-->
     /// Spawn a new pod.
     Targetless,
@@ -269,6 +301,9 @@ impl FromStr for Target {
             Some("job") => job::JobTarget::from_split(&mut split).map(Target::Job),
             Some("cronjob") => cron_job::CronJobTarget::from_split(&mut split).map(Target::CronJob),
             Some("statefulset") => stateful_set::StatefulSetTarget::from_split(&mut split).map(Target::StatefulSet),
+            Some("service") => service::ServiceTarget::from_split(&mut split).map(Target::Service),
+            Some("replicaset") => replica_set::ReplicaSetTarget::from_split(&mut split).map(Target::ReplicaSet),
+            Some("daemonset") => daemon_set::DaemonSetTarget::from_split(&mut split).map(Target::DaemonSet),
             _ => Err(ConfigError::InvalidTarget(format!(
                 "Provided target: {target} is unsupported. Did you remember to add a prefix, e.g. pod/{target}? \n{FAIL_PARSE_DEPLOYMENT_OR_POD}",
             ))),
@@ -286,6 +321,9 @@ impl Target {
             Target::Job(target) => target.job.clone(),
             Target::CronJob(target) => target.cron_job.clone(),
             Target::StatefulSet(target) => target.stateful_set.clone(),
+            Target::Service(target) => target.service.clone(),
+            Target::ReplicaSet(target) => target.replica_set.clone(),
+            Target::DaemonSet(target) => target.daemon_set.clone(),
             Target::Targetless => {
                 unreachable!("this shouldn't happen - called from operator on a flow where it's not targetless.")
             }
@@ -361,6 +399,9 @@ impl_target_display!(RolloutTarget, rollout, "rollout");
 impl_target_display!(JobTarget, job, "job");
 impl_target_display!(CronJobTarget, cron_job, "cronjob");
 impl_target_display!(StatefulSetTarget, stateful_set, "statefulset");
+impl_target_display!(ServiceTarget, service, "service");
+impl_target_display!(ReplicaSetTarget, replica_set, "replicaset");
+impl_target_display!(DaemonSetTarget, daemon_set, "daemonset");
 
 impl fmt::Display for Target {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -372,6 +413,9 @@ impl fmt::Display for Target {
             Target::Job(target) => target.fmt(f),
             Target::CronJob(target) => target.fmt(f),
             Target::StatefulSet(target) => target.fmt(f),
+            Target::Service(target) => target.fmt(f),
+            Target::ReplicaSet(target) => target.fmt(f),
+            Target::DaemonSet(target) => target.fmt(f),
         }
     }
 }
@@ -386,6 +430,9 @@ impl TargetDisplay for Target {
             Target::Job(target) => target.type_(),
             Target::CronJob(target) => target.type_(),
             Target::StatefulSet(target) => target.type_(),
+            Target::Service(target) => target.type_(),
+            Target::ReplicaSet(target) => target.type_(),
+            Target::DaemonSet(target) => target.type_(),
         }
     }
 
@@ -398,6 +445,9 @@ impl TargetDisplay for Target {
             Target::Job(target) => target.name(),
             Target::CronJob(target) => target.name(),
             Target::StatefulSet(target) => target.name(),
+            Target::Service(target) => target.name(),
+            Target::ReplicaSet(target) => target.name(),
+            Target::DaemonSet(target) => target.name(),
         }
     }
 
@@ -410,6 +460,9 @@ impl TargetDisplay for Target {
             Target::Job(target) => target.container(),
             Target::CronJob(target) => target.container(),
             Target::StatefulSet(target) => target.container(),
+            Target::Service(target) => target.container(),
+            Target::ReplicaSet(target) => target.container(),
+            Target::DaemonSet(target) => target.container(),
         }
     }
 }
@@ -426,6 +479,9 @@ bitflags::bitflags! {
         const JOB = 32;
         const CRON_JOB = 64;
         const STATEFUL_SET = 128;
+        const SERVICE = 256;
+        const REPLICA_SET = 512;
+        const DAEMON_SET = 1024;
     }
 }
 
@@ -473,6 +529,24 @@ impl CollectAnalytics for &TargetConfig {
                         flags |= TargetAnalyticFlags::CONTAINER;
                     }
                 }
+                Target::Service(target) => {
+                    flags |= TargetAnalyticFlags::SERVICE;
+                    if target.container.is_some() {
+                        flags |= TargetAnalyticFlags::CONTAINER;
+                    }
+                }
+                Target::ReplicaSet(target) => {
+                    flags |= TargetAnalyticFlags::REPLICA_SET;
+                    if target.container.is_some() {
+                        flags |= TargetAnalyticFlags::CONTAINER;
+                    }
+                }
+                Target::DaemonSet(target) => {
+                    flags |= TargetAnalyticFlags::DAEMON_SET;
+                    if target.container.is_some() {
+                        flags |= TargetAnalyticFlags::CONTAINER;
+                    }
+                }
                 Target::Targetless => {
                     // Targetless is essentially 0, so no need to set any flags.
                 }
@@ -545,6 +619,28 @@ mod tests {
             namespace: None
         }
     )] // Rollout specified.
+    #[case(
+        Some("service/foo/container/bar"),
+        None,
+        TargetConfig{
+            path: Some(Target::Service(ServiceTarget {
+                service: "foo".to_string(),
+                container: Some("bar".to_string())
+            })),
+            namespace: None
+        }
+    )] // Service and container specified.
+    #[case(
+        Some("daemonset/foo"),
+        Some("baz"),
+        TargetConfig{
+            path: Some(Target::DaemonSet(DaemonSetTarget {
+                daemon_set: "foo".to_string(),
+                container: None
+            })),
+            namespace: Some("baz".to_string())
+        }
+    )] // DaemonSet and namespace specified.
     fn default(
         #[case] path_env: Option<&str>,
         #[case] namespace_env: Option<&str>,
@@ -615,6 +711,21 @@ mod tests {
             namespace: None
         }
     )]
+    // advanced variant of file config, with a replica set object as path.
+    #[case(
+        r#"{
+            "path": {
+                "replica_set": "my-cool-replica-set"
+            }
+        }"#,
+        TargetConfig{
+            path: Some(Target::ReplicaSet(ReplicaSetTarget {
+                replica_set: "my-cool-replica-set".to_string(),
+                container: None
+            })),
+            namespace: None
+        }
+    )]
     fn parse_target_config_from_json(
         #[case] config_json_string: &str,
         #[case] mut expected_target_config: TargetConfig,
@@ -0,0 +1,140 @@
+//! Conversion of the `feature.network.incoming.http_filter` config into the [`HttpFilter`] used
+//! with the `steal` feature, shared by the layer and `mirrord port-forward`.
+
+use mirrord_config::feature::network::incoming::http_filter::{
+    BodyFilter, HttpFilterConfig, InnerFilter,
+};
+use mirrord_protocol::tcp::{Filter, HttpBodyFilter, HttpFilter};
+
+/// Returns the [`HttpFilter`] described by the config, or [`None`] if no filter is set.
+///
+/// # Panics
+///
+/// When the config was not verified, and contains multiple filters or invalid regexes.
+pub fn steal_http_filter(config: &HttpFilterConfig) -> Option<HttpFilter> {
+    // Matching all fields to make this check future-proof.
+    let filter = match config {
+        HttpFilterConfig {
+            path_filter: Some(path),
+            header_filter: None,
+            method_filter: None,
+            query_filter: None,
+            body_filter: None,
+            all_of: None,
+            any_of: None,
+            ports: _ports,
+        } => HttpFilter::Path(make_filter(path)),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: Some(header),
+            method_filter: None,
+            query_filter: None,
+            body_filter: None,
+            all_of: None,
+            any_of: None,
+            ports: _ports,
+        } => HttpFilter::Header(make_filter(header)),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: None,
+            method_filter: Some(method),
+            query_filter: None,
+            body_filter: None,
+            all_of: None,
+            any_of: None,
+            ports: _ports,
+        } => HttpFilter::Method(method.clone()),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: None,
+            method_filter: None,
+            query_filter: Some(query),
+            body_filter: None,
+            all_of: None,
+            any_of: None,
+            ports: _ports,
+        } => HttpFilter::Query(make_filter(query)),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: None,
+            method_filter: None,
+            query_filter: None,
+            body_filter: Some(body),
+            all_of: None,
+            any_of: None,
+            ports: _ports,
+        } => make_body_filter(body),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: None,
+            method_filter: None,
+            query_filter: None,
+            body_filter: None,
+            all_of: Some(filters),
+            any_of: None,
+            ports: _ports,
+        } => make_composite_filter(true, filters),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: None,
+            method_filter: None,
+            query_filter: None,
+            body_filter: None,
+            all_of: None,
+            any_of: Some(filters),
+            ports: _ports,
+        } => make_composite_filter(false, filters),
+
+        HttpFilterConfig {
+            path_filter: None,
+            header_filter: None,
+            method_filter: None,
+            query_filter: None,
+            body_filter: None,
+            all_of: None,
+            any_of: None,
+            ports: _ports,
+        } => return None,
+
+        _ => panic!("multiple HTTP filters specified, this is a bug"),
+    };
+
+    Some(filter)
+}
+
+fn make_filter(regex: &str) -> Filter {
+    Filter::new(regex.to_string()).expect("invalid filter expression")
+}
+
+fn make_composite_filter(all: bool, filters: &[InnerFilter]) -> HttpFilter {
+    let filters = filters
+        .iter()
+        .map(|filter| match filter {
+            InnerFilter::Path { path } => HttpFilter::Path(make_filter(path)),
+            InnerFilter::Header { header } => HttpFilter::Header(make_filter(header)),
+            InnerFilter::Method { method } => HttpFilter::Method(method.clone()),
+            InnerFilter::Query { query } => HttpFilter::Query(make_filter(query)),
+            InnerFilter::Body { body } => make_body_filter(body),
+        })
+        .collect();
+
+    HttpFilter::Composite { all, filters }
+}
+
+fn make_body_filter(filter: &BodyFilter) -> HttpFilter {
+    let filter = match filter {
+        BodyFilter::Regex { regex } => HttpBodyFilter::Regex(make_filter(regex)),
+        BodyFilter::Json { json_path, matches } => HttpBodyFilter::Json {
+            query: json_path.clone(),
+            matches: make_filter(matches),
+        },
+    };
+
+    HttpFilter::Body(Box::new(filter))
+}
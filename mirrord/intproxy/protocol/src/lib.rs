@@ -10,7 +10,7 @@ use std::{
 
 use bincode::{Decode, Encode};
 use mirrord_protocol::{
-    dns::{GetAddrInfoRequest, GetAddrInfoResponse},
+    dns::{DnsQueryRequest, DnsQueryResponse, GetAddrInfoRequestV2, GetAddrInfoResponse},
     file::*,
     outgoing::SocketAddress,
     tcp::StealType,
@@ -19,6 +19,8 @@ use mirrord_protocol::{
 
 #[cfg(feature = "codec")]
 pub mod codec;
+#[cfg(feature = "config")]
+pub mod http_filter;
 mod macros;
 
 /// An identifier for a message sent from the layer to the internal proxy.
@@ -44,13 +46,18 @@ pub enum LayerToProxyMessage {
     /// A file operation request.
     File(FileRequest),
     /// A DNS request.
-    GetAddrInfo(GetAddrInfoRequest),
+    ///
+    /// The internal proxy downgrades it to [`mirrord_protocol::dns::GetAddrInfoRequest`] when the
+    /// agent does not support [`GetAddrInfoRequestV2`].
+    GetAddrInfo(GetAddrInfoRequestV2),
     /// A request to initiate a new outgoing connection.
     OutgoingConnect(OutgoingConnectRequest),
     /// Requests related to incoming connections.
     Incoming(IncomingRequest),
     /// Fetch environment variables from the target.
     GetEnv(GetEnvVarsRequest),
+    /// A DNS query for records of any type.
+    DnsQuery(DnsQueryRequest),
 }
 
 /// Layer process information
@@ -136,7 +143,14 @@ pub enum IncomingRequest {
     PortUnsubscribe(PortUnsubscribe),
     /// A request made by the layer when it accepts a connection on the socket that is listening
     /// for mirrored connections.
+    ///
+    /// Also made when the layer receives a datagram on the socket bound with
+    /// [`UdpPortSubscribe`].
     ConnMetadata(ConnMetadataRequest),
+    /// A request made by the layer when it binds a UDP socket to receive mirrored datagrams.
+    UdpPortSubscribe(UdpPortSubscribe),
+    /// A request made by the layer when it closes the UDP socket receiving mirrored datagrams.
+    UdpPortUnsubscribe(UdpPortUnsubscribe),
 }
 
 /// A request for additional metadata for accepted connection.
@@ -202,6 +216,30 @@ pub struct PortUnsubscribe {
     pub listening_on: SocketAddr,
 }
 
+/// A request to start proxying incoming UDP datagrams.
+///
+/// For each datagram sent to the remote port, the internal proxy will send a copy to the local
+/// address specified in `listening_on`. When stealing, the internal proxy will also send the
+/// replies back to the remote peers.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct UdpPortSubscribe {
+    /// Local address to which the layer's socket is bound.
+    pub listening_on: SocketAddr,
+    /// Port on the remote pod.
+    pub port: Port,
+    /// Whether the datagrams should be stolen instead of mirrored.
+    pub steal: bool,
+}
+
+/// A request to stop proxying incoming UDP datagrams.
+#[derive(Encode, Decode, Debug, PartialEq, Eq)]
+pub struct UdpPortUnsubscribe {
+    /// Port on the remote pod that layer mirrored.
+    pub port: Port,
+    /// Local address to which the layer's socket was bound.
+    pub listening_on: SocketAddr,
+}
+
 /// Messages sent by the internal proxy and handled by the layer.
 #[derive(Encode, Decode, Debug, PartialEq, Eq)]
 pub enum ProxyToLayerMessage {
@@ -210,7 +248,7 @@ pub enum ProxyToLayerMessage {
     NewSession(LayerId),
     /// A response to layer's [`FileRequest`].
     File(FileResponse),
-    /// A response to layer's [`GetAddrInfoRequest`].
+    /// A response to layer's [`GetAddrInfoRequestV2`].
     GetAddrInfo(GetAddrInfoResponse),
     /// A response to layer's [`OutgoingConnectRequest`].
     OutgoingConnect(RemoteResult<OutgoingConnectResponse>),
@@ -218,6 +256,8 @@ pub enum ProxyToLayerMessage {
     Incoming(IncomingResponse),
     /// A response to layer's [`LayerToProxyMessage::GetEnv`].
     GetEnv(RemoteResult<HashMap<String, String>>),
+    /// A response to layer's [`DnsQueryRequest`].
+    DnsQuery(DnsQueryResponse),
 }
 
 /// A response to layer's [`IncomingRequest`].
@@ -231,6 +271,8 @@ pub enum IncomingResponse {
     PortSubscribe(RemoteResult<()>),
     /// A response to layers' [`ConnMetadataRequest`].
     ConnMetadata(ConnMetadataResponse),
+    /// A response to layer's [`UdpPortSubscribe`].
+    UdpPortSubscribe(RemoteResult<()>),
 }
 
 /// A response to layer's [`OutgoingConnectRequest`].
@@ -324,6 +366,97 @@ impl_request!(
     res_path = ProxyToLayerMessage::File => FileResponse::MakeDir,
 );
 
+impl_request!(
+    req = UnlinkRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Unlink,
+    res_path = ProxyToLayerMessage::File => FileResponse::Unlink,
+);
+
+impl_request!(
+    req = UnlinkAtRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::UnlinkAt,
+    res_path = ProxyToLayerMessage::File => FileResponse::Unlink,
+);
+
+impl_request!(
+    req = RenameRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Rename,
+    res_path = ProxyToLayerMessage::File => FileResponse::Rename,
+);
+
+impl_request!(
+    req = RemoveDirRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::RemoveDir,
+    res_path = ProxyToLayerMessage::File => FileResponse::RemoveDir,
+);
+
+impl_request!(
+    req = FtruncateRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Ftruncate,
+    res_path = ProxyToLayerMessage::File => FileResponse::Truncate,
+);
+
+impl_request!(
+    req = TruncateRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Truncate,
+    res_path = ProxyToLayerMessage::File => FileResponse::Truncate,
+);
+
+impl_request!(
+    req = ChmodRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Chmod,
+    res_path = ProxyToLayerMessage::File => FileResponse::Chmod,
+);
+
+impl_request!(
+    req = FchmodRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Fchmod,
+    res_path = ProxyToLayerMessage::File => FileResponse::Chmod,
+);
+
+impl_request!(
+    req = ChownRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Chown,
+    res_path = ProxyToLayerMessage::File => FileResponse::Chown,
+);
+
+impl_request!(
+    req = FchownRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Fchown,
+    res_path = ProxyToLayerMessage::File => FileResponse::Chown,
+);
+
+impl_request!(
+    req = UtimensatRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Utimensat,
+    res_path = ProxyToLayerMessage::File => FileResponse::Utimensat,
+);
+
+impl_request!(
+    req = SymlinkRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Symlink,
+    res_path = ProxyToLayerMessage::File => FileResponse::Symlink,
+);
+
+impl_request!(
+    req = LinkRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Link,
+    res_path = ProxyToLayerMessage::File => FileResponse::Link,
+);
+
 impl_request!(
     req = SeekFileRequest,
     res = RemoteResult<SeekFileResponse>,
@@ -407,12 +540,19 @@ impl_request!(
 );
 
 impl_request!(
-    req = GetAddrInfoRequest,
+    req = GetAddrInfoRequestV2,
     res = GetAddrInfoResponse,
     req_path = LayerToProxyMessage::GetAddrInfo,
     res_path = ProxyToLayerMessage::GetAddrInfo,
 );
 
+impl_request!(
+    req = DnsQueryRequest,
+    res = DnsQueryResponse,
+    req_path = LayerToProxyMessage::DnsQuery,
+    res_path = ProxyToLayerMessage::DnsQuery,
+);
+
 impl_request!(
     req = OutgoingConnectRequest,
     res = RemoteResult<OutgoingConnectResponse>,
@@ -432,6 +572,18 @@ impl_request!(
     req_path = LayerToProxyMessage::Incoming => IncomingRequest::PortUnsubscribe,
 );
 
+impl_request!(
+    req = UdpPortSubscribe,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::Incoming => IncomingRequest::UdpPortSubscribe,
+    res_path = ProxyToLayerMessage::Incoming => IncomingResponse::UdpPortSubscribe,
+);
+
+impl_request!(
+    req = UdpPortUnsubscribe,
+    req_path = LayerToProxyMessage::Incoming => IncomingRequest::UdpPortUnsubscribe,
+);
+
 impl_request!(
     req = ConnMetadataRequest,
     res = ConnMetadataResponse,
@@ -8,19 +8,25 @@ use std::{
     net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
-use mirrord_analytics::Reporter;
+use exponential_backoff::Backoff;
+use mirrord_analytics::{NullReporter, Reporter};
 use mirrord_config::LayerConfig;
 use mirrord_kube::{
     api::{
         kubernetes::{AgentKubernetesConnectInfo, KubernetesAPI},
+        multiplex::multiplex_connections,
         wrap_raw_connection,
     },
     error::KubeApiError,
 };
-use mirrord_operator::client::{error::OperatorApiError, OperatorApi, OperatorSession};
-use mirrord_protocol::{ClientMessage, DaemonMessage};
+use mirrord_operator::client::{
+    error::OperatorApiError, policy::ClientPolicy, OperatorApi, OperatorSession,
+};
+use mirrord_progress::NullProgress;
+use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, LogMessage};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::{
@@ -29,6 +35,7 @@ use tokio::{
         mpsc,
         mpsc::{Receiver, Sender},
     },
+    time::{self, Instant},
 };
 use tokio_rustls::TlsConnector;
 use tracing::Level;
@@ -59,6 +66,24 @@ pub enum AgentConnectionError {
     NoConnectionMethod,
 }
 
+/// Errors that can occur when [`AgentReconnect`] tries to replace the agent connection.
+#[derive(Error, Debug)]
+pub enum AgentReconnectError {
+    #[error("{0}")]
+    Connection(#[from] AgentConnectionError),
+
+    /// The agent did not respond to the ping sent over the new connection.
+    #[error("ping pong with the agent failed: {0}")]
+    PingPong(String),
+
+    /// We failed to connect for the whole timeout.
+    #[error("failed to reconnect to the agent in {timeout:?}, last error: {last_error}")]
+    Timeout {
+        timeout: Duration,
+        last_error: String,
+    },
+}
+
 #[derive(Error, Debug)]
 pub enum ConnectionTlsError {
     #[error("could not open pem data from {0}, error: {1}")]
@@ -95,6 +120,22 @@ pub enum AgentConnectInfo {
     Operator(OperatorSession),
     /// Connect directly to the agent by name and port using k8s port forward.
     DirectKubernetes(AgentKubernetesConnectInfo),
+    /// Connect directly to multiple agents, one per target pod (`agent.multi_pod`), and merge
+    /// the connections with [`multiplex_connections`]. The first agent is the primary one.
+    DirectKubernetesMultiPod(Vec<AgentKubernetesConnectInfo>),
+}
+
+impl AgentConnectInfo {
+    /// Parts of the mirrord policies that should be enforced by the client, available only when
+    /// connecting through the operator.
+    pub fn policy(&self) -> Option<&ClientPolicy> {
+        match self {
+            Self::Operator(session) => Some(&session.policy),
+            Self::ExternalProxy(..)
+            | Self::DirectKubernetes(..)
+            | Self::DirectKubernetesMultiPod(..) => None,
+        }
+    }
 }
 
 /// Handles logic of the `proxy <-> agent` connection as a [`BackgroundTask`].
@@ -168,6 +209,23 @@ impl AgentConnection {
                 wrap_raw_connection(stream)
             }
 
+            Some(AgentConnectInfo::DirectKubernetesMultiPod(connect_infos)) => {
+                let k8s_api = KubernetesAPI::create(config)
+                    .await
+                    .map_err(AgentConnectionError::Kube)?;
+
+                let mut connections = Vec::with_capacity(connect_infos.len());
+                for connect_info in connect_infos {
+                    let stream = k8s_api
+                        .create_connection(connect_info)
+                        .await
+                        .map_err(AgentConnectionError::Kube)?;
+                    connections.push(wrap_raw_connection(stream));
+                }
+
+                multiplex_connections(connections)
+            }
+
             None => {
                 let address = config
                     .connect_tcp
@@ -192,6 +250,170 @@ impl AgentConnection {
     async fn send(&self, msg: ClientMessage) -> Result<(), AgentChannelError> {
         self.agent_tx.send(msg).await.map_err(|_| AgentChannelError)
     }
+
+    /// Handles one round of ping pong, to make sure that the connection actually works (port
+    /// forwarding may be done lazily).
+    async fn ping(&mut self) -> Result<(), AgentReconnectError> {
+        self.send(ClientMessage::Ping)
+            .await
+            .map_err(|_| AgentReconnectError::PingPong("agent closed connection".to_string()))?;
+
+        loop {
+            match self.agent_rx.recv().await {
+                Some(DaemonMessage::Pong) => break Ok(()),
+                Some(DaemonMessage::LogMessage(LogMessage {
+                    level: LogLevel::Error,
+                    message,
+                })) => {
+                    tracing::error!("agent log: {message}");
+                }
+                Some(DaemonMessage::LogMessage(LogMessage {
+                    level: LogLevel::Warn,
+                    message,
+                })) => {
+                    tracing::warn!("agent log: {message}");
+                }
+                Some(DaemonMessage::Close(reason)) => {
+                    break Err(AgentReconnectError::PingPong(format!(
+                        "agent closed connection with message: {reason}"
+                    )));
+                }
+                Some(message) => {
+                    break Err(AgentReconnectError::PingPong(format!(
+                        "agent sent an unexpected message: {message:?}"
+                    )));
+                }
+                None => {
+                    break Err(AgentReconnectError::PingPong(
+                        "agent unexpectedly closed connection".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Creates a new [`AgentConnection`] when the previous one is lost, enabled with
+/// `internal_proxy.reconnect`.
+///
+/// Connects to the agent using the same [`AgentConnectInfo`] that was used for the first
+/// connection. When the agent was created by the CLI ([`AgentConnectInfo::DirectKubernetes`]) and
+/// it does not respond after [`Self::ATTEMPTS_BEFORE_NEW_AGENT`] attempts, a new agent is created
+/// for the same target (or new agents for all of the target's pods, with
+/// [`AgentConnectInfo::DirectKubernetesMultiPod`]).
+///
+/// Operator sessions are only resumed, the operator is responsible for the agents.
+pub struct AgentReconnect {
+    config: LayerConfig,
+    connect_info: Option<AgentConnectInfo>,
+    timeout: Duration,
+}
+
+impl AgentReconnect {
+    /// How many failed attempts to connect with the old agent we make before creating a new one.
+    const ATTEMPTS_BEFORE_NEW_AGENT: u32 = 3;
+
+    /// How long we wait for a single connection attempt (together with ping pong).
+    const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Creates a new instance that gives up when it fails to connect for the whole `timeout`.
+    pub fn new(
+        config: LayerConfig,
+        connect_info: Option<AgentConnectInfo>,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            config,
+            connect_info,
+            timeout,
+        }
+    }
+
+    /// Retries with a [`Backoff`] until we get a working [`AgentConnection`], or until the
+    /// timeout.
+    #[tracing::instrument(level = Level::DEBUG, skip(self), err)]
+    pub async fn reconnect(&mut self) -> Result<AgentConnection, AgentReconnectError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut backoffs =
+            Backoff::new(u32::MAX, Duration::from_millis(500), Duration::from_secs(5))
+                .into_iter()
+                .flatten();
+        let mut failed_attempts = 0;
+
+        loop {
+            let attempt_deadline = deadline.min(Instant::now() + Self::ATTEMPT_TIMEOUT);
+            let create_agent = failed_attempts == Self::ATTEMPTS_BEFORE_NEW_AGENT;
+
+            let error =
+                match time::timeout_at(attempt_deadline, self.try_connect(create_agent)).await {
+                    Ok(Ok(connection)) => break Ok(connection),
+                    Ok(Err(error)) => error.to_string(),
+                    Err(..) => "connection attempt timed out".to_string(),
+                };
+
+            failed_attempts += 1;
+            tracing::warn!(%error, failed_attempts, "Failed to reconnect to the agent");
+
+            let backoff = backoffs.next().unwrap_or(Duration::from_secs(5));
+            if Instant::now() + backoff >= deadline {
+                break Err(AgentReconnectError::Timeout {
+                    timeout: self.timeout,
+                    last_error: error,
+                });
+            }
+
+            time::sleep(backoff).await;
+        }
+    }
+
+    async fn try_connect(
+        &mut self,
+        create_agent: bool,
+    ) -> Result<AgentConnection, AgentReconnectError> {
+        if create_agent && let Some(AgentConnectInfo::DirectKubernetes(..)) = &self.connect_info {
+            tracing::info!("Creating a new agent");
+
+            let connect_info = KubernetesAPI::create(&self.config)
+                .await
+                .map_err(AgentConnectionError::Kube)?
+                .create_agent(
+                    &mut NullProgress,
+                    &self.config.target,
+                    Some(&self.config),
+                    None,
+                )
+                .await
+                .map_err(AgentConnectionError::Kube)?;
+            self.connect_info = Some(AgentConnectInfo::DirectKubernetes(connect_info));
+        } else if create_agent
+            && let Some(AgentConnectInfo::DirectKubernetesMultiPod(..)) = &self.connect_info
+        {
+            tracing::info!("Creating new agents");
+
+            let connect_infos = KubernetesAPI::create(&self.config)
+                .await
+                .map_err(AgentConnectionError::Kube)?
+                .create_agents(
+                    &mut NullProgress,
+                    &self.config.target,
+                    Some(&self.config),
+                    None,
+                )
+                .await
+                .map_err(AgentConnectionError::Kube)?;
+            self.connect_info = Some(AgentConnectInfo::DirectKubernetesMultiPod(connect_infos));
+        }
+
+        let mut connection = AgentConnection::new(
+            &self.config,
+            self.connect_info.clone(),
+            &mut NullReporter::default(),
+        )
+        .await?;
+        connection.ping().await?;
+
+        Ok(connection)
+    }
 }
 
 /// This error occurs when the [`AgentConnection`] fails to communicate with the inner
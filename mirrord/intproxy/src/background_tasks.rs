@@ -145,6 +145,27 @@ where
         Some(msg)
     }
 
+    /// Aborts all registered tasks and deregisters them, without producing
+    /// [`TaskUpdate::Finished`]s.
+    pub fn clear(&mut self) {
+        for handle in self.handles.values() {
+            handle.abort();
+        }
+
+        self.handles.clear();
+        self.streams.clear();
+    }
+
+    /// Aborts the task with the given id and deregisters it, without producing a
+    /// [`TaskUpdate::Finished`]. Does nothing if there is no such task.
+    pub fn abort(&mut self, id: &Id) {
+        if let Some(handle) = self.handles.remove(id) {
+            handle.abort();
+        }
+
+        self.streams.remove(id);
+    }
+
     /// Waits for all registered tasks to finish and returns their results.
     /// This method does not signalize the tasks to finish. Instead, one should drop all
     /// [`TaskSender`]s first.
@@ -205,4 +226,11 @@ impl<T: BackgroundTask> TaskSender<T> {
     pub async fn send<M: Into<T::MessageIn>>(&self, msg: M) {
         let _ = self.0.send(msg.into()).await;
     }
+
+    /// Attempt to send a message to the task without waiting.
+    /// The message is dropped when the channel is full.
+    /// Returns whether the message was sent.
+    pub fn try_send<M: Into<T::MessageIn>>(&self, msg: M) -> bool {
+        self.0.try_send(msg.into()).is_ok()
+    }
 }
@@ -5,7 +5,7 @@ use mirrord_protocol::DaemonMessage;
 use thiserror::Error;
 
 use crate::{
-    agent_conn::{AgentChannelError, AgentConnectionError},
+    agent_conn::{AgentChannelError, AgentConnectionError, AgentReconnectError},
     layer_initializer::LayerInitializerError,
     ping_pong::PingPongError,
     proxies::{
@@ -30,6 +30,8 @@ pub enum IntProxyError {
 
     #[error("connecting with agent failed: {0}")]
     AgentConnection(#[from] AgentConnectionError),
+    #[error("reconnecting with agent failed: {0}")]
+    AgentReconnect(#[from] AgentReconnectError),
     #[error("agent closed connection with error: {0}")]
     AgentFailed(String),
     #[error(transparent)]
@@ -56,6 +58,8 @@ pub enum IntProxyError {
     IncomingProxy(#[from] IncomingProxyError),
     #[error("files proxy failed: {0}")]
     FilesProxy(#[from] FilesProxyError),
+    #[error("inspector failed: {0}")]
+    Inspector(#[from] io::Error),
 }
 
 pub type Result<T> = core::result::Result<T, IntProxyError>;
@@ -0,0 +1,475 @@
+//! Live stream of the session events for `mirrord inspect`, see [`Inspector`].
+
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    net::SocketAddr,
+    os::unix::fs::{DirBuilderExt, MetadataExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use mirrord_intproxy_protocol::{
+    IncomingRequest, LayerId, LayerToProxyMessage, NetProtocol, PortSubscription,
+};
+use mirrord_protocol::{
+    file::OpenFileRequest,
+    tcp::{ChunkedRequest, DaemonTcp, HttpRequest, StealType},
+    ConnectionId, DaemonMessage, FileRequest, Port, RequestId,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UnixListener, UnixStream},
+    sync::broadcast::{self, error::RecvError},
+};
+use tracing::Level;
+
+use crate::{
+    background_tasks::{BackgroundTask, MessageBus},
+    main_tasks::ProxyMessage,
+};
+
+/// An event in the mirrord session, reported to `mirrord inspect`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InspectorEvent {
+    /// A new layer instance connected to the internal proxy.
+    LayerConnected {
+        layer_id: u64,
+        parent_id: Option<u64>,
+    },
+    /// The application subscribed to a port on the target.
+    PortSubscribed {
+        layer_id: u64,
+        port: Port,
+        steal: bool,
+        /// HTTP filter of the subscription, if any.
+        filter: Option<String>,
+    },
+    /// A new incoming connection was stolen or mirrored.
+    IncomingConnection {
+        connection_id: ConnectionId,
+        stolen: bool,
+        source: SocketAddr,
+        destination_port: Port,
+    },
+    /// A stolen HTTP request, matching the filter of its port.
+    HttpRequest {
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        port: Port,
+        method: String,
+        uri: String,
+        /// Filled by the [`Inspector`] from the last [`InspectorEvent::PortSubscribed`] for the
+        /// port.
+        filter: Option<String>,
+    },
+    /// The application opened a remote file.
+    FileOpened { layer_id: u64, path: PathBuf },
+    /// The application resolved a name with the remote DNS.
+    DnsQuery { layer_id: u64, name: String },
+    /// The application made an outgoing connection through the target.
+    OutgoingConnect {
+        layer_id: u64,
+        remote_address: String,
+        udp: bool,
+    },
+}
+
+impl InspectorEvent {
+    /// Returns the event reported for the given message from the agent, if any.
+    pub(crate) fn from_agent(message: &DaemonMessage) -> Option<Self> {
+        let (message, stolen) = match message {
+            DaemonMessage::Tcp(message) => (message, false),
+            DaemonMessage::TcpSteal(message) => (message, true),
+            _ => return None,
+        };
+
+        match message {
+            DaemonTcp::NewConnection(connection) => Some(Self::IncomingConnection {
+                connection_id: connection.connection_id,
+                stolen,
+                source: SocketAddr::new(connection.remote_address, connection.source_port),
+                destination_port: connection.destination_port,
+            }),
+            DaemonTcp::HttpRequest(request) => Some(Self::from_http_request(request)),
+            DaemonTcp::HttpRequestFramed(request) => Some(Self::from_http_request(request)),
+            DaemonTcp::HttpRequestChunked(ChunkedRequest::Start(request)) => {
+                Some(Self::from_http_request(request))
+            }
+            _ => None,
+        }
+    }
+
+    fn from_http_request<B>(request: &HttpRequest<B>) -> Self {
+        Self::HttpRequest {
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+            port: request.port,
+            method: request.internal_request.method.to_string(),
+            uri: request.internal_request.uri.to_string(),
+            filter: None,
+        }
+    }
+
+    /// Returns the event reported for the given message from the layer, if any.
+    pub(crate) fn from_layer(
+        LayerId(layer_id): LayerId,
+        message: &LayerToProxyMessage,
+    ) -> Option<Self> {
+        match message {
+            LayerToProxyMessage::File(FileRequest::Open(OpenFileRequest { path, .. })) => {
+                Some(Self::FileOpened {
+                    layer_id,
+                    path: path.clone(),
+                })
+            }
+            LayerToProxyMessage::GetAddrInfo(request) => Some(Self::DnsQuery {
+                layer_id,
+                name: request.node.clone(),
+            }),
+            LayerToProxyMessage::DnsQuery(request) => Some(Self::DnsQuery {
+                layer_id,
+                name: request.name.clone(),
+            }),
+            LayerToProxyMessage::OutgoingConnect(request) => Some(Self::OutgoingConnect {
+                layer_id,
+                remote_address: request.remote_address.to_string(),
+                udp: request.protocol == NetProtocol::Datagrams,
+            }),
+            LayerToProxyMessage::Incoming(IncomingRequest::PortSubscribe(subscribe)) => {
+                let (port, steal, filter) = match &subscribe.subscription {
+                    PortSubscription::Mirror(port) => (*port, false, None),
+                    PortSubscription::Steal(StealType::All(port)) => (*port, true, None),
+                    PortSubscription::Steal(StealType::FilteredHttp(port, filter)) => {
+                        (*port, true, Some(format!("header={filter}")))
+                    }
+                    PortSubscription::Steal(StealType::FilteredHttpEx(port, filter)) => {
+                        (*port, true, Some(filter.to_string()))
+                    }
+                };
+
+                Some(Self::PortSubscribed {
+                    layer_id,
+                    port,
+                    steal,
+                    filter,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A single line of the stream served by the [`Inspector`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InspectorRecord {
+    /// Milliseconds since the UNIX epoch.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: InspectorEvent,
+}
+
+/// Serves a stream of [`InspectorRecord`]s, as JSON lines, to every client that connects to its
+/// unix socket. Run as a [`BackgroundTask`].
+///
+/// Clients receive only the events that happen after they connect. Slow clients miss events
+/// instead of slowing down the internal proxy, and so are the events that arrive when the
+/// [`Inspector`] itself falls behind.
+pub struct Inspector {
+    listener: UnixListener,
+    path: PathBuf,
+    /// HTTP filters of the stolen ports, from [`InspectorEvent::PortSubscribed`].
+    filters: HashMap<Port, Option<String>>,
+}
+
+impl Inspector {
+    /// How many records can wait for a single client.
+    const CLIENT_BUFFER: usize = 1024;
+
+    /// Directory with the sockets of all running internal proxies of the current user.
+    ///
+    /// Placed in `$XDG_RUNTIME_DIR` when it's set, and in a per-user directory in the temp dir
+    /// otherwise.
+    pub fn socket_dir() -> PathBuf {
+        match env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("mirrord-inspect"),
+            // SAFETY: `getuid` is always successful.
+            _ => env::temp_dir().join(format!("mirrord-inspect-{}", unsafe { libc::getuid() })),
+        }
+    }
+
+    /// Binds a socket for this process in [`Self::socket_dir`].
+    pub fn bind() -> io::Result<Self> {
+        Self::bind_in(&Self::socket_dir())
+    }
+
+    /// Binds a socket for this process in the given directory, creating it if needed.
+    ///
+    /// Fails when the directory exists, but is not a directory private to the current user.
+    fn bind_in(dir: &Path) -> io::Result<Self> {
+        match fs::DirBuilder::new().mode(0o700).create(dir) {
+            Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+            _ => {}
+        }
+
+        let metadata = fs::symlink_metadata(dir)?;
+        // SAFETY: `getuid` is always successful.
+        let uid = unsafe { libc::getuid() };
+        if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is not a directory accessible only by the current user",
+                    dir.display()
+                ),
+            ));
+        }
+
+        Self::bind_at(dir.join(format!("{}.sock", std::process::id())))
+    }
+
+    /// Binds a socket at the given path, replacing a stale socket file.
+    pub fn bind_at(path: PathBuf) -> io::Result<Self> {
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+            _ => {}
+        }
+
+        Ok(Self {
+            listener: UnixListener::bind(&path)?,
+            path,
+            filters: Default::default(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wraps the event in a new [`InspectorRecord`], filling in the filter of
+    /// [`InspectorEvent::HttpRequest`]s.
+    fn record(&mut self, mut event: InspectorEvent) -> InspectorRecord {
+        match &mut event {
+            InspectorEvent::PortSubscribed {
+                port,
+                steal: true,
+                filter,
+                ..
+            } => {
+                self.filters.insert(*port, filter.clone());
+            }
+            InspectorEvent::HttpRequest { port, filter, .. } => {
+                *filter = self.filters.get(port).cloned().flatten();
+            }
+            _ => {}
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            .try_into()
+            .unwrap_or(u64::MAX);
+
+        InspectorRecord { timestamp, event }
+    }
+
+    /// Writes the records to the client until it disconnects.
+    #[tracing::instrument(level = Level::TRACE, skip_all)]
+    async fn serve_client(
+        mut stream: UnixStream,
+        mut records: broadcast::Receiver<InspectorRecord>,
+    ) {
+        loop {
+            let record = match records.recv().await {
+                Ok(record) => record,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!(skipped, "Inspector client is lagging behind");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            let Ok(mut line) = serde_json::to_vec(&record) else {
+                continue;
+            };
+            line.push(b'\n');
+
+            if stream.write_all(&line).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for Inspector {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl BackgroundTask for Inspector {
+    type Error = io::Error;
+    type MessageIn = InspectorEvent;
+    type MessageOut = ProxyMessage;
+
+    async fn run(mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
+        let (tx, _) = broadcast::channel(Self::CLIENT_BUFFER);
+
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, _) = accepted?;
+                    tokio::spawn(Self::serve_client(stream, tx.subscribe()));
+                }
+
+                event = message_bus.recv() => match event {
+                    Some(event) => {
+                        let _ = tx.send(self.record(event));
+                    }
+                    None => break Ok(()),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{os::unix::fs::PermissionsExt, time::Duration};
+
+    use mirrord_intproxy_protocol::PortSubscribe;
+    use mirrord_protocol::tcp::{Filter, HttpFilter, InternalHttpRequest, NewTcpConnection};
+    use tempfile::TempDir;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    use super::*;
+    use crate::background_tasks::BackgroundTasks;
+
+    fn subscribe(port: Port, filter: &str) -> LayerToProxyMessage {
+        LayerToProxyMessage::Incoming(IncomingRequest::PortSubscribe(PortSubscribe {
+            listening_on: "127.0.0.1:8080".parse().unwrap(),
+            subscription: PortSubscription::Steal(StealType::FilteredHttpEx(
+                port,
+                HttpFilter::Header(Filter::new(filter.to_string()).unwrap()),
+            )),
+        }))
+    }
+
+    fn request(port: Port) -> DaemonMessage {
+        DaemonMessage::TcpSteal(DaemonTcp::HttpRequest(HttpRequest {
+            internal_request: InternalHttpRequest {
+                method: "GET".parse().unwrap(),
+                uri: "/api/v1".parse().unwrap(),
+                headers: Default::default(),
+                version: Default::default(),
+                body: vec![],
+            },
+            connection_id: 3,
+            request_id: 0,
+            port,
+        }))
+    }
+
+    #[test]
+    fn events_from_messages() {
+        let connection = DaemonMessage::Tcp(DaemonTcp::NewConnection(NewTcpConnection {
+            connection_id: 1,
+            remote_address: "10.0.0.1".parse().unwrap(),
+            destination_port: 80,
+            source_port: 5555,
+            local_address: "10.0.0.2".parse().unwrap(),
+        }));
+        assert_eq!(
+            InspectorEvent::from_agent(&connection),
+            Some(InspectorEvent::IncomingConnection {
+                connection_id: 1,
+                stolen: false,
+                source: "10.0.0.1:5555".parse().unwrap(),
+                destination_port: 80,
+            })
+        );
+
+        assert_eq!(
+            InspectorEvent::from_layer(LayerId(2), &subscribe(80, "x-user: me")),
+            Some(InspectorEvent::PortSubscribed {
+                layer_id: 2,
+                port: 80,
+                steal: true,
+                filter: Some("header=x-user: me".into()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn socket_dir_must_be_private() {
+        let parent = TempDir::new().unwrap();
+        let dir = parent.path().join("mirrord-inspect");
+
+        let inspector = Inspector::bind_in(&dir).unwrap();
+        assert!(inspector.path().starts_with(&dir));
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        std::mem::drop(inspector);
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        let error = Inspector::bind_in(&dir).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+
+        let link = parent.path().join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        let error = Inspector::bind_in(&link).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn stream_records_to_client() {
+        let dir = TempDir::new().unwrap();
+        let inspector = Inspector::bind_at(dir.path().join("inspector.sock")).unwrap();
+        let path = inspector.path().to_path_buf();
+
+        let mut tasks: BackgroundTasks<(), ProxyMessage, io::Error> = Default::default();
+        let tx = tasks.register(inspector, (), 8);
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        // Give the inspector time to accept the client.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let events = [
+            InspectorEvent::from_layer(LayerId(0), &subscribe(80, "x-user: me")),
+            InspectorEvent::from_agent(&request(80)),
+            InspectorEvent::from_agent(&request(81)),
+        ];
+        for event in events.into_iter().flatten() {
+            tx.send(event).await;
+        }
+
+        let mut filters = vec![];
+        for _ in 0..3 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let record: InspectorRecord = serde_json::from_str(&line).unwrap();
+            match record.event {
+                InspectorEvent::PortSubscribed { filter, .. } => filters.push(filter),
+                InspectorEvent::HttpRequest { filter, uri, .. } => {
+                    assert_eq!(uri, "/api/v1");
+                    filters.push(filter);
+                }
+                other => panic!("unexpected event {other:?}"),
+            }
+        }
+        assert_eq!(
+            filters,
+            [
+                Some("header=x-user: me".to_string()),
+                Some("header=x-user: me".to_string()),
+                None,
+            ]
+        );
+
+        std::mem::drop(tx);
+        tasks.results().await;
+        assert!(!path.exists());
+    }
+}
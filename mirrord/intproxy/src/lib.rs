@@ -1,33 +1,47 @@
 #![feature(map_try_insert, let_chains)]
 #![warn(clippy::indexing_slicing)]
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use background_tasks::{BackgroundTasks, TaskSender, TaskUpdate};
 use error::UnexpectedAgentMessage;
+use futures::{
+    future::{BoxFuture, OptionFuture},
+    FutureExt,
+};
+use inspector::{Inspector, InspectorEvent};
 use layer_conn::LayerConnection;
 use layer_initializer::LayerInitializer;
-use main_tasks::{FromLayer, LayerForked, MainTaskId, ProxyMessage, ToLayer};
+use main_tasks::{ConnectionRefresh, FromLayer, LayerForked, MainTaskId, ProxyMessage, ToLayer};
 use mirrord_intproxy_protocol::{LayerId, LayerToProxyMessage, LocalMessage};
+use mirrord_operator::client::policy::ClientPolicy;
 use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, CLIENT_READY_FOR_LOGS};
-use ping_pong::{AgentSentPong, PingPong};
+use ping_pong::{PingPong, PingPongError, PingPongMessage};
 use proxies::{
     files::{FilesProxy, FilesProxyMessage},
-    incoming::{IncomingProxy, IncomingProxyMessage},
-    outgoing::{OutgoingProxy, OutgoingProxyMessage},
+    incoming::{
+        recorder::HttpRecorder, rewrite::HttpRewrites, IncomingProxy, IncomingProxyMessage,
+    },
+    outgoing::{faults::OutgoingFaults, mock::OutgoingMocks, OutgoingProxy, OutgoingProxyMessage},
     simple::{SimpleProxy, SimpleProxyMessage},
 };
 use tokio::{net::TcpListener, time};
 use tracing::Level;
 
 use crate::{
-    agent_conn::AgentConnection, background_tasks::TaskError, error::IntProxyError,
+    agent_conn::{AgentConnection, AgentReconnect, AgentReconnectError},
+    background_tasks::TaskError,
+    error::IntProxyError,
     main_tasks::LayerClosed,
 };
 
 pub mod agent_conn;
 pub mod background_tasks;
 pub mod error;
+pub mod inspector;
 mod layer_conn;
 mod layer_initializer;
 pub mod main_tasks;
@@ -36,6 +50,11 @@ pub mod proxies;
 mod remote_resources;
 mod request_queue;
 
+/// An in-progress [`AgentReconnect::reconnect`], returns the [`AgentReconnect`] back with the
+/// result.
+type ReconnectFuture =
+    BoxFuture<'static, (AgentReconnect, Result<AgentConnection, AgentReconnectError>)>;
+
 /// [`TaskSender`]s for main background tasks. See [`MainTaskId`].
 struct TaskTxs {
     layers: HashMap<LayerId, TaskSender<LayerConnection>>,
@@ -46,6 +65,7 @@ struct TaskTxs {
     incoming: TaskSender<IncomingProxy>,
     ping_pong: TaskSender<PingPong>,
     files: TaskSender<FilesProxy>,
+    inspector: Option<TaskSender<Inspector>>,
 }
 
 /// This struct contains logic for proxying between multiple layer instances and one agent.
@@ -53,18 +73,32 @@ struct TaskTxs {
 ///
 /// Utilizes multiple [`BackgroundTask`](background_tasks::BackgroundTask)s to split logic of
 /// different mirrod features (e.g. file operations and incoming traffic).
+///
+/// When configured [`IntProxy::with_reconnect`], replaces the agent connection when it fails. See
+/// [`ConnectionRefresh`] for details.
+///
+/// The background tasks are started in [`IntProxy::run`].
 pub struct IntProxy {
-    any_connection_accepted: bool,
-    background_tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError>,
-    task_txs: TaskTxs,
+    agent_conn: AgentConnection,
+    listener: TcpListener,
+    file_buffer_size: u64,
+    /// Records the incoming HTTP traffic.
+    http_recorder: Option<HttpRecorder>,
+    /// Applied to the stolen HTTP requests and responses.
+    http_rewrites: HttpRewrites,
+    /// Enforced on the file, outgoing and env requests of the layers.
+    policy: ClientPolicy,
+    /// Outgoing HTTP requests matching these are answered locally.
+    outgoing_mocks: OutgoingMocks,
+    /// Injected into the outgoing connections.
+    outgoing_faults: OutgoingFaults,
+    /// Used to replace the agent connection when it fails.
+    reconnect: Option<AgentReconnect>,
+    /// Streams the session events to its clients.
+    inspector: Option<Inspector>,
 }
 
 impl IntProxy {
-    /// Size of channels used to communicate with main tasks (see [`MainTaskId`]).
-    const CHANNEL_SIZE: usize = 512;
-    /// How long can the agent connection remain silent.
-    const PING_INTERVAL: Duration = Duration::from_secs(30);
-
     /// Creates a new [`IntProxy`] using existing [`AgentConnection`].
     /// The returned instance will accept connections from the layers using the given
     /// [`TcpListener`].
@@ -73,6 +107,114 @@ impl IntProxy {
         listener: TcpListener,
         file_buffer_size: u64,
     ) -> Self {
+        Self {
+            agent_conn,
+            listener,
+            file_buffer_size,
+            http_recorder: None,
+            http_rewrites: Default::default(),
+            policy: Default::default(),
+            outgoing_mocks: Default::default(),
+            outgoing_faults: Default::default(),
+            reconnect: None,
+            inspector: None,
+        }
+    }
+
+    /// Enables replacing the agent connection with the given [`AgentReconnect`] when it fails.
+    pub fn with_reconnect(mut self, reconnect: AgentReconnect) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+
+    /// Enables streaming the session events to the clients of the given [`Inspector`].
+    pub fn with_inspector(mut self, inspector: Inspector) -> Self {
+        self.inspector = Some(inspector);
+        self
+    }
+
+    /// Enables recording the incoming HTTP traffic with the given [`HttpRecorder`].
+    pub fn with_http_recorder(mut self, http_recorder: HttpRecorder) -> Self {
+        self.http_recorder = Some(http_recorder);
+        self
+    }
+
+    /// Applies the given [`HttpRewrites`] to the stolen HTTP requests and responses.
+    pub fn with_http_rewrites(mut self, http_rewrites: HttpRewrites) -> Self {
+        self.http_rewrites = http_rewrites;
+        self
+    }
+
+    /// Enforces the given [`ClientPolicy`] on the file, outgoing and env requests of the layers.
+    pub fn with_policy(mut self, policy: ClientPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Answers the outgoing HTTP requests matching the given [`OutgoingMocks`] locally.
+    pub fn with_outgoing_mocks(mut self, outgoing_mocks: OutgoingMocks) -> Self {
+        self.outgoing_mocks = outgoing_mocks;
+        self
+    }
+
+    /// Injects the given [`OutgoingFaults`] into the outgoing connections.
+    pub fn with_outgoing_faults(mut self, outgoing_faults: OutgoingFaults) -> Self {
+        self.outgoing_faults = outgoing_faults;
+        self
+    }
+
+    /// Runs main event loop of this proxy.
+    /// Expects to accept the first layer connection within the given `first_timeout`.
+    /// Exits after `idle_timeout` when there are no more layer connections.
+    pub async fn run(
+        self,
+        first_timeout: Duration,
+        idle_timeout: Duration,
+    ) -> Result<(), IntProxyError> {
+        RunningIntProxy::new(self)
+            .run(first_timeout, idle_timeout)
+            .await
+    }
+}
+
+/// [`IntProxy`] with its background tasks started.
+struct RunningIntProxy {
+    any_connection_accepted: bool,
+    background_tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError>,
+    task_txs: TaskTxs,
+    /// Used to replace the agent connection when it fails.
+    reconnect: Option<AgentReconnect>,
+    /// Tasks that did not yet acknowledge the last [`ConnectionRefresh::Start`].
+    refreshing: HashSet<MainTaskId>,
+    /// The reconnect that is in progress. Polled in the main loop, so that we keep serving the
+    /// layers in the meantime.
+    reconnecting: Option<ReconnectFuture>,
+    /// Messages sent to the agent by tasks that already acknowledged
+    /// [`ConnectionRefresh::Start`], waiting for the new agent connection.
+    agent_backlog: Vec<ClientMessage>,
+}
+
+impl RunningIntProxy {
+    /// Size of channels used to communicate with main tasks (see [`MainTaskId`]).
+    const CHANNEL_SIZE: usize = 512;
+    /// How long can the agent connection remain silent.
+    const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+    /// Starts the background tasks of the given [`IntProxy`].
+    fn new(proxy: IntProxy) -> Self {
+        let IntProxy {
+            agent_conn,
+            listener,
+            file_buffer_size,
+            http_recorder,
+            http_rewrites,
+            policy,
+            outgoing_mocks,
+            outgoing_faults,
+            reconnect,
+            inspector,
+        } = proxy;
+
         let mut background_tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError> =
             Default::default();
 
@@ -89,25 +231,28 @@ impl IntProxy {
             Self::CHANNEL_SIZE,
         );
         let simple = background_tasks.register(
-            SimpleProxy::default(),
+            SimpleProxy::new(policy.clone(), outgoing_mocks.placeholders().clone()),
             MainTaskId::SimpleProxy,
             Self::CHANNEL_SIZE,
         );
         let outgoing = background_tasks.register(
-            OutgoingProxy::default(),
+            OutgoingProxy::new(policy.clone(), outgoing_mocks, outgoing_faults),
             MainTaskId::OutgoingProxy,
             Self::CHANNEL_SIZE,
         );
         let incoming = background_tasks.register(
-            IncomingProxy::default(),
+            IncomingProxy::new(http_recorder, http_rewrites, policy.clone()),
             MainTaskId::IncomingProxy,
             Self::CHANNEL_SIZE,
         );
         let files = background_tasks.register(
-            FilesProxy::new(file_buffer_size),
+            FilesProxy::new(file_buffer_size, policy),
             MainTaskId::FilesProxy,
             Self::CHANNEL_SIZE,
         );
+        let inspector = inspector.map(|inspector| {
+            background_tasks.register(inspector, MainTaskId::Inspector, Self::CHANNEL_SIZE)
+        });
 
         Self {
             any_connection_accepted: false,
@@ -121,14 +266,30 @@ impl IntProxy {
                 incoming,
                 ping_pong,
                 files,
+                inspector,
             },
+            reconnect,
+            refreshing: Default::default(),
+            reconnecting: None,
+            agent_backlog: Default::default(),
         }
     }
 
-    /// Runs main event loop of this proxy.
-    /// Expects to accept the first layer connection within the given `first_timeout`.
-    /// Exits after `idle_timeout` when there are no more layer connections.
-    pub async fn run(
+    /// Sends the event to the [`Inspector`], if it's enabled.
+    ///
+    /// The event is dropped when the [`Inspector`] falls behind, so that it never slows down the
+    /// proxy.
+    fn inspect(&self, event: Option<InspectorEvent>) {
+        if let Some(inspector) = &self.task_txs.inspector
+            && let Some(event) = event
+            && !inspector.try_send(event)
+        {
+            tracing::trace!("inspector is falling behind, dropping a session event");
+        }
+    }
+
+    /// See [`IntProxy::run`].
+    async fn run(
         mut self,
         first_timeout: Duration,
         idle_timeout: Duration,
@@ -146,6 +307,12 @@ impl IntProxy {
                     self.handle_task_update(task_id, task_update).await?;
                 }
 
+                Some((reconnect, result)) = OptionFuture::from(self.reconnecting.as_mut()) => {
+                    self.reconnecting = None;
+                    self.reconnect = Some(reconnect);
+                    self.finish_reconnect(result?).await;
+                }
+
                 _ = time::sleep(first_timeout), if !self.any_connection_accepted => {
                     if !self.any_connection_accepted {
                         return Err(IntProxyError::ConnectionAcceptTimeout);
@@ -171,9 +338,9 @@ impl IntProxy {
         Ok(())
     }
 
-    /// Routes a [`ProxyMessage`] to the correct background task.
+    /// Routes a [`ProxyMessage`] from the task `from` to the correct background task.
     /// [`ProxyMessage::NewLayer`] is handled here, as an exception.
-    async fn handle(&mut self, msg: ProxyMessage) -> Result<(), IntProxyError> {
+    async fn handle(&mut self, from: MainTaskId, msg: ProxyMessage) -> Result<(), IntProxyError> {
         match msg {
             ProxyMessage::NewLayer(new_layer) => {
                 self.any_connection_accepted = true;
@@ -185,6 +352,11 @@ impl IntProxy {
                 );
                 self.task_txs.layers.insert(new_layer.id, tx);
 
+                self.inspect(Some(InspectorEvent::LayerConnected {
+                    layer_id: new_layer.id.0,
+                    parent_id: new_layer.parent_id.map(|LayerId(id)| id),
+                }));
+
                 if let Some(parent) = new_layer.parent_id {
                     let msg = LayerForked {
                         child: new_layer.id,
@@ -203,7 +375,30 @@ impl IntProxy {
             }
             ProxyMessage::FromAgent(msg) => self.handle_agent_message(msg).await?,
             ProxyMessage::FromLayer(msg) => self.handle_layer_message(msg).await?,
-            ProxyMessage::ToAgent(msg) => self.task_txs.agent.send(msg).await,
+            ProxyMessage::ToAgent(msg) => {
+                if self.refreshing.contains(&from) {
+                    tracing::debug!(?msg, %from, "Dropping a message to the previous agent connection");
+                } else if self.reconnecting.is_some() {
+                    self.agent_backlog.push(msg);
+                } else {
+                    self.task_txs.agent.send(msg).await;
+                }
+            }
+            ProxyMessage::ConnectionRefreshAck => {
+                self.refreshing.remove(&from);
+            }
+            ProxyMessage::ResolveName(name) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::ResolveName(name))
+                    .await;
+            }
+            ProxyMessage::NameResolved(name, addresses) => {
+                self.task_txs
+                    .outgoing
+                    .send(OutgoingProxyMessage::NameResolved(name, addresses))
+                    .await;
+            }
             ProxyMessage::ToLayer(msg) => {
                 let ToLayer {
                     message,
@@ -247,6 +442,33 @@ impl IntProxy {
 
                 self.task_txs.layers.remove(&LayerId(id));
             }
+            (MainTaskId::Inspector, TaskUpdate::Finished(res)) => {
+                tracing::warn!(
+                    ?res,
+                    "inspector finished, session events are no longer available"
+                );
+                self.task_txs.inspector = None;
+            }
+            (MainTaskId::AgentConnection, TaskUpdate::Finished(Err(TaskError::Error(error))))
+                if self.reconnect.is_some() =>
+            {
+                tracing::warn!(%error, "agent connection failed, reconnecting");
+                self.start_reconnect().await;
+            }
+            (
+                MainTaskId::PingPong,
+                TaskUpdate::Finished(Err(TaskError::Error(IntProxyError::PingPong(
+                    PingPongError::PongTimeout,
+                )))),
+            ) if self.reconnect.is_some() => {
+                tracing::warn!("agent did not respond to ping in time, reconnecting");
+                self.task_txs.ping_pong = self.background_tasks.register(
+                    PingPong::new(Self::PING_INTERVAL),
+                    MainTaskId::PingPong,
+                    Self::CHANNEL_SIZE,
+                );
+                self.start_reconnect().await;
+            }
             (task_id, TaskUpdate::Finished(res)) => match res {
                 Ok(()) => {
                     tracing::error!("task {task_id} finished unexpectedly");
@@ -261,18 +483,111 @@ impl IntProxy {
                     return Err(IntProxyError::TaskPanic(task_id));
                 }
             },
-            (_, TaskUpdate::Message(msg)) => self.handle(msg).await?,
+            (task_id, TaskUpdate::Message(msg)) => self.handle(task_id, msg).await?,
         }
 
         Ok(())
     }
 
+    /// Starts replacing the failed agent connection with a new one, in the background (see
+    /// [`RunningIntProxy::finish_reconnect`]). The old connection is dropped.
+    ///
+    /// The proxies are notified with [`ConnectionRefresh::Start`] before we start reconnecting,
+    /// and with [`ConnectionRefresh::End`] when the new connection is ready.
+    #[tracing::instrument(level = Level::DEBUG, skip(self))]
+    async fn start_reconnect(&mut self) {
+        let Some(mut reconnect) = self.reconnect.take() else {
+            return;
+        };
+
+        self.background_tasks.abort(&MainTaskId::AgentConnection);
+
+        self.refreshing.extend([
+            MainTaskId::SimpleProxy,
+            MainTaskId::OutgoingProxy,
+            MainTaskId::IncomingProxy,
+            MainTaskId::FilesProxy,
+            MainTaskId::PingPong,
+        ]);
+        self.notify_connection_refresh(ConnectionRefresh::Start)
+            .await;
+
+        self.reconnecting = Some(
+            async move {
+                let result = reconnect.reconnect().await;
+                (reconnect, result)
+            }
+            .boxed(),
+        );
+    }
+
+    /// Registers the new agent connection and flushes the messages that were sent to the agent
+    /// during the reconnect.
+    #[tracing::instrument(level = Level::DEBUG, skip_all)]
+    async fn finish_reconnect(&mut self, agent_conn: AgentConnection) {
+        tracing::info!("Reconnected to the agent");
+
+        self.task_txs.agent = self.background_tasks.register(
+            agent_conn,
+            MainTaskId::AgentConnection,
+            Self::CHANNEL_SIZE,
+        );
+        self.task_txs
+            .agent
+            .send(ClientMessage::SwitchProtocolVersion(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await;
+
+        for message in std::mem::take(&mut self.agent_backlog) {
+            self.task_txs.agent.send(message).await;
+        }
+
+        self.notify_connection_refresh(ConnectionRefresh::End).await;
+    }
+
+    async fn notify_connection_refresh(&mut self, refresh: ConnectionRefresh) {
+        self.task_txs
+            .simple
+            .send(SimpleProxyMessage::ConnectionRefresh(refresh))
+            .await;
+        self.task_txs
+            .outgoing
+            .send(OutgoingProxyMessage::ConnectionRefresh(refresh))
+            .await;
+        self.task_txs
+            .incoming
+            .send(IncomingProxyMessage::ConnectionRefresh(refresh))
+            .await;
+        self.task_txs
+            .files
+            .send(FilesProxyMessage::ConnectionRefresh(refresh))
+            .await;
+        self.task_txs
+            .ping_pong
+            .send(PingPongMessage::ConnectionRefresh(refresh))
+            .await;
+    }
+
     /// Routes most messages from the agent to the correct background task.
     /// Some messages are handled here.
     #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
     async fn handle_agent_message(&mut self, message: DaemonMessage) -> Result<(), IntProxyError> {
+        if self.task_txs.inspector.is_some() {
+            self.inspect(InspectorEvent::from_agent(&message));
+        }
+
         match message {
-            DaemonMessage::Pong => self.task_txs.ping_pong.send(AgentSentPong).await,
+            DaemonMessage::Pong => {
+                self.task_txs
+                    .ping_pong
+                    .send(PingPongMessage::AgentSentPong)
+                    .await
+            }
+            DaemonMessage::Close(reason) if self.reconnect.is_some() => {
+                tracing::warn!(%reason, "agent closed the connection, reconnecting");
+                self.start_reconnect().await;
+            }
             DaemonMessage::Close(reason) => return Err(IntProxyError::AgentFailed(reason)),
             DaemonMessage::TcpOutgoing(msg) => {
                 self.task_txs
@@ -310,6 +625,12 @@ impl IntProxy {
                     .send(IncomingProxyMessage::AgentSteal(msg))
                     .await
             }
+            DaemonMessage::Udp(msg) => {
+                self.task_txs
+                    .incoming
+                    .send(IncomingProxyMessage::AgentUdp(msg))
+                    .await
+            }
             DaemonMessage::SwitchProtocolVersionResponse(protocol_version) => {
                 if CLIENT_READY_FOR_LOGS.matches(&protocol_version) {
                     self.task_txs.agent.send(ClientMessage::ReadyForLogs).await;
@@ -320,6 +641,13 @@ impl IntProxy {
                     .send(FilesProxyMessage::ProtocolVersion(protocol_version.clone()))
                     .await;
 
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::ProtocolVersion(
+                        protocol_version.clone(),
+                    ))
+                    .await;
+
                 self.task_txs
                     .incoming
                     .send(IncomingProxyMessage::AgentProtocolVersion(protocol_version))
@@ -335,6 +663,12 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvRes(res))
                     .await
             }
+            DaemonMessage::DnsQueryResponse(res) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::DnsQueryRes(res))
+                    .await
+            }
             other => {
                 return Err(IntProxyError::UnexpectedAgentMessage(
                     UnexpectedAgentMessage(other),
@@ -347,13 +681,17 @@ impl IntProxy {
 
     /// Routes a message from the layer to the correct background task.
     #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
-    async fn handle_layer_message(&self, message: FromLayer) -> Result<(), IntProxyError> {
+    async fn handle_layer_message(&mut self, message: FromLayer) -> Result<(), IntProxyError> {
         let FromLayer {
             message_id,
             layer_id,
             message,
         } = message;
 
+        if self.task_txs.inspector.is_some() {
+            self.inspect(InspectorEvent::from_layer(layer_id, &message));
+        }
+
         match message {
             LayerToProxyMessage::File(req) => {
                 self.task_txs
@@ -389,6 +727,12 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvReq(message_id, layer_id, req))
                     .await
             }
+            LayerToProxyMessage::DnsQuery(req) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::DnsQueryReq(message_id, layer_id, req))
+                    .await
+            }
             other => return Err(IntProxyError::UnexpectedLayerMessage(other)),
         }
 
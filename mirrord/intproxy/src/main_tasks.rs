@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, net::IpAddr};
 
 use mirrord_intproxy_protocol::{LayerId, LayerToProxyMessage, MessageId, ProxyToLayerMessage};
 use mirrord_protocol::{ClientMessage, DaemonMessage};
@@ -19,6 +19,16 @@ pub enum ProxyMessage {
     FromLayer(FromLayer),
     /// New layer instance to serve.
     NewLayer(NewLayer),
+    /// Sent by a background task when it handles [`ConnectionRefresh::Start`]. Messages to the
+    /// agent that the task produced before this one are dropped, as they were meant for the
+    /// previous agent connection.
+    ConnectionRefreshAck,
+    /// Sent by the [`OutgoingProxy`](crate::proxies::outgoing::OutgoingProxy) when it needs a
+    /// name from its filters resolved with the remote DNS.
+    ResolveName(String),
+    /// Sent by the [`SimpleProxy`](crate::proxies::simple::SimpleProxy) with the addresses of a
+    /// [`ProxyMessage::ResolveName`]. Empty if the resolution failed.
+    NameResolved(String, Vec<IpAddr>),
 }
 
 #[cfg(test)]
@@ -106,6 +116,7 @@ pub enum MainTaskId {
     PingPong,
     AgentConnection,
     FilesProxy,
+    Inspector,
     LayerConnection(LayerId),
 }
 
@@ -120,6 +131,7 @@ impl fmt::Display for MainTaskId {
             Self::LayerConnection(id) => write!(f, "LAYER_CONNECTION {}", id.0),
             Self::IncomingProxy => f.write_str("INCOMING_PROXY"),
             Self::FilesProxy => f.write_str("FILES_PROXY"),
+            Self::Inspector => f.write_str("INSPECTOR"),
         }
     }
 }
@@ -136,3 +148,19 @@ pub struct LayerForked {
 pub struct LayerClosed {
     pub id: LayerId,
 }
+
+/// Notification about the agent connection being replaced with a new one, when
+/// `internal_proxy.reconnect` is enabled. Useful to the background tasks that talk with the agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionRefresh {
+    /// The agent connection failed and the proxy is making a new one.
+    ///
+    /// The tasks should reply with [`ProxyMessage::ConnectionRefreshAck`], and then send the
+    /// requests that were not answered by the agent once again, as they may have never reached
+    /// it. Requests that are not safe to repeat should fail instead. Everything sent after the
+    /// ack goes to the new agent connection, and is held in the proxy until that connection is
+    /// ready.
+    Start,
+    /// The new agent connection is ready.
+    End,
+}
@@ -13,6 +13,7 @@ use tokio::time::{self, Interval, MissedTickBehavior};
 
 use crate::{
     background_tasks::{BackgroundTask, MessageBus},
+    main_tasks::ConnectionRefresh,
     ProxyMessage,
 };
 
@@ -27,9 +28,15 @@ pub enum PingPongError {
     PongTimeout,
 }
 
-/// Notification about a [`DeamonMessage::Pong`](mirrord_protocol::DaemonMessage::Pong) received
-/// from the agent.
-pub struct AgentSentPong;
+/// Messages consumed by [`PingPong`].
+pub enum PingPongMessage {
+    /// Notification about a [`DeamonMessage::Pong`](mirrord_protocol::DaemonMessage::Pong)
+    /// received from the agent.
+    AgentSentPong,
+    /// The agent connection is being replaced. No pings are sent between
+    /// [`ConnectionRefresh::Start`] and [`ConnectionRefresh::End`].
+    ConnectionRefresh(ConnectionRefresh),
+}
 
 /// Encapsulates logic of the ping pong mechanism on the proxy side.
 /// Run as a [`BackgroundTask`].
@@ -38,6 +45,8 @@ pub struct PingPong {
     ticker: Interval,
     /// Whether this struct awaits for a pong from the agent.
     awaiting_pong: bool,
+    /// Whether the agent connection is being replaced, see [`ConnectionRefresh`].
+    refreshing: bool,
 }
 
 impl PingPong {
@@ -53,13 +62,14 @@ impl PingPong {
         Self {
             ticker,
             awaiting_pong: false,
+            refreshing: false,
         }
     }
 }
 
 impl BackgroundTask for PingPong {
     type Error = PingPongError;
-    type MessageIn = AgentSentPong;
+    type MessageIn = PingPongMessage;
     type MessageOut = ProxyMessage;
 
     /// Pings the agent with a frequency configured in [`PingPong::new`].
@@ -69,7 +79,7 @@ impl BackgroundTask for PingPong {
     async fn run(mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
         loop {
             tokio::select! {
-                _ = self.ticker.tick() => {
+                _ = self.ticker.tick(), if !self.refreshing => {
                     if self.awaiting_pong {
                         tracing::error!("pong timeout");
                         break Err(PingPongError::PongTimeout);
@@ -85,14 +95,25 @@ impl BackgroundTask for PingPong {
                         tracing::trace!("message bus closed, exiting");
                         break Ok(())
                     },
-                    (Some(AgentSentPong), true) => {
+                    (Some(PingPongMessage::AgentSentPong), true) => {
                         tracing::trace!("agent responded to ping");
                         self.awaiting_pong = false;
                     },
-                    (Some(AgentSentPong), false) => {
+                    (Some(PingPongMessage::AgentSentPong), false) => {
                         tracing::error!("agent sent an unexpected pong");
                         break Err(PingPongError::UnmatchedPong)
                     },
+                    (Some(PingPongMessage::ConnectionRefresh(ConnectionRefresh::Start)), _) => {
+                        tracing::trace!("agent connection refresh started, pausing pings");
+                        self.refreshing = true;
+                        self.awaiting_pong = false;
+                        message_bus.send(ProxyMessage::ConnectionRefreshAck).await;
+                    },
+                    (Some(PingPongMessage::ConnectionRefresh(ConnectionRefresh::End)), _) => {
+                        tracing::trace!("agent connection refresh finished, resuming pings");
+                        self.refreshing = false;
+                        self.ticker.reset();
+                    },
                 },
             }
         }
@@ -1,24 +1,31 @@
 use core::fmt;
-use std::{collections::HashMap, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    vec,
+};
 
 use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
+use mirrord_operator::client::policy::ClientPolicy;
 use mirrord_protocol::{
     file::{
-        CloseDirRequest, CloseFileRequest, DirEntryInternal, ReadDirBatchRequest, ReadDirResponse,
-        ReadFileResponse, ReadLimitedFileRequest, SeekFromInternal, MKDIR_VERSION,
-        READDIR_BATCH_VERSION, READLINK_VERSION,
+        CloseDirRequest, CloseFileRequest, DirEntryInternal, FsPolicyRequest, OpenOptionsInternal,
+        ReadDirBatchRequest, ReadDirResponse, ReadFileResponse, ReadLimitedFileRequest,
+        ReopenFileRequest, SeekFromInternal, FS_POLICY_VERSION, LINK_VERSION, MKDIR_VERSION,
+        READDIR_BATCH_VERSION, READLINK_VERSION, REMOVE_RENAME_VERSION, REOPEN_VERSION,
+        SET_METADATA_VERSION, TRUNCATE_VERSION,
     },
     ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError,
-    ResponseError,
+    RemoteResult, ResponseError,
 };
-use semver::Version;
+use semver::{Version, VersionReq};
 use thiserror::Error;
 use tracing::Level;
 
 use crate::{
     background_tasks::{BackgroundTask, MessageBus},
     error::UnexpectedAgentMessage,
-    main_tasks::{LayerClosed, LayerForked, ProxyMessage, ToLayer},
+    main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ProxyMessage, ToLayer},
     remote_resources::RemoteResources,
     request_queue::RequestQueue,
 };
@@ -36,6 +43,8 @@ pub enum FilesProxyMessage {
     LayerForked(LayerForked),
     /// Layer instance closed.
     LayerClosed(LayerClosed),
+    /// The agent connection is being replaced.
+    ConnectionRefresh(ConnectionRefresh),
 }
 
 /// Error that can occur in [`FilesProxy`].
@@ -123,6 +132,29 @@ enum AdditionalRequestData {
     Other,
 }
 
+/// A request sent to the agent, saved by [`FilesProxy`] in its [`RequestQueue`] until the agent
+/// responds.
+///
+/// Requests that were not answered are sent again on [`ConnectionRefresh::Start`].
+#[derive(Debug)]
+struct QueuedRequest {
+    request: FileRequest,
+    additional_data: AdditionalRequestData,
+}
+
+/// Remote file opened by the layer, with everything that is needed to open it again in a new
+/// agent connection (see [`ConnectionRefresh`]).
+#[derive(Debug)]
+struct OpenedFile {
+    /// Path of the file in the remote target.
+    path: PathBuf,
+    open_options: OpenOptionsInternal,
+    /// Position of the file descriptor in the file, as reported by the agent.
+    ///
+    /// Not updated for buffered files, as we use only [`FileRequest::ReadLimited`] to read them.
+    position: u64,
+}
+
 /// For handling all file operations.
 /// Run as a [`BackgroundTask`].
 ///
@@ -147,6 +179,17 @@ enum AdditionalRequestData {
 ///    buffer. If it's not possible, we proceed as in point 1
 /// 4. To solve problems with descriptor offset, we only use [`FileRequest::ReadLimited`] to read
 ///    buffered files. Descriptor offset value is maintained in this proxy.
+///
+/// # Agent reconnection
+///
+/// When the agent connection is replaced ([`ConnectionRefresh`]), this proxy opens the remote
+/// files again with [`FileRequest::Reopen`], under the same descriptors and at the same positions.
+/// Then it sends again the requests that were not answered by the previous agent, but only those
+/// that are safe to repeat. The other ones (e.g. [`FileRequest::Write`] or [`FileRequest::Rename`])
+/// may have been already handled by the previous agent, so they fail with `EIO`.
+///
+/// Directory streams are not restored. Reading from a directory opened before the reconnection
+/// fails with `EIO`.
 pub struct FilesProxy {
     /// [`mirrord_protocol`] version negotiated with the agent.
     /// Determines whether we can use some messages, like [`FileRequest::ReadDirBatch`] or
@@ -157,11 +200,13 @@ pub struct FilesProxy {
     /// If equal to 0, this proxy does not buffer files.
     file_buffer_size: u64,
 
-    /// Stores metadata of outstanding requests.
-    request_queue: RequestQueue<AdditionalRequestData>,
+    /// Stores outstanding requests.
+    request_queue: RequestQueue<QueuedRequest>,
 
     /// For tracking remote file descriptors across layer instances (forks).
     remote_files: RemoteResources<u64>,
+    /// For reopening remote files, see [`ConnectionRefresh`].
+    opened_files: HashMap<u64, OpenedFile>,
     /// Locally stored data of buffered files.
     buffered_files: HashMap<u64, BufferedFileData>,
 
@@ -169,6 +214,11 @@ pub struct FilesProxy {
     remote_dirs: RemoteResources<u64>,
     /// Locally stored data of buffered directories.
     buffered_dirs: HashMap<u64, BufferedDirData>,
+    /// Remote directory descriptors from the previous agent connection, see [`ConnectionRefresh`].
+    stale_dirs: HashSet<u64>,
+
+    /// Used to reject requests for remote paths blocked by the mirrord policies.
+    policy: ClientPolicy,
 }
 
 impl fmt::Debug for FilesProxy {
@@ -194,7 +244,10 @@ impl FilesProxy {
     ///
     /// `file_buffer_size` sets size of the readonly files buffer.
     /// Size 0 disables buffering.
-    pub fn new(file_buffer_size: u64) -> Self {
+    ///
+    /// Requests for paths blocked by the `policy` are answered with
+    /// [`ResponseError::Forbidden`], without reaching the agent.
+    pub fn new(file_buffer_size: u64, policy: ClientPolicy) -> Self {
         Self {
             protocol_version: Default::default(),
             file_buffer_size,
@@ -202,10 +255,14 @@ impl FilesProxy {
             request_queue: Default::default(),
 
             remote_files: Default::default(),
+            opened_files: Default::default(),
             buffered_files: Default::default(),
 
             remote_dirs: Default::default(),
             buffered_dirs: Default::default(),
+            stale_dirs: Default::default(),
+
+            policy,
         }
     }
 
@@ -231,6 +288,7 @@ impl FilesProxy {
     async fn layer_closed(&mut self, closed: LayerClosed, message_bus: &mut MessageBus<Self>) {
         for fd in self.remote_files.remove_all(closed.id) {
             self.buffered_files.remove(&fd);
+            self.opened_files.remove(&fd);
             message_bus
                 .send(ProxyMessage::ToAgent(ClientMessage::FileRequest(
                     FileRequest::Close(CloseFileRequest { fd }),
@@ -240,6 +298,9 @@ impl FilesProxy {
 
         for remote_fd in self.remote_dirs.remove_all(closed.id) {
             self.buffered_dirs.remove(&remote_fd);
+            if self.stale_dirs.remove(&remote_fd) {
+                continue;
+            }
             message_bus
                 .send(ProxyMessage::ToAgent(ClientMessage::FileRequest(
                     FileRequest::CloseDir(CloseDirRequest { remote_fd }),
@@ -248,9 +309,346 @@ impl FilesProxy {
         }
     }
 
-    #[tracing::instrument(level = Level::TRACE)]
-    fn protocol_version(&mut self, version: Version) {
+    /// Sends the `fs` restrictions of the [`ClientPolicy`] to the agent, if it supports
+    /// [`FsPolicyRequest`]. This happens again with every new agent connection.
+    #[tracing::instrument(level = Level::TRACE, skip(message_bus))]
+    async fn protocol_version(&mut self, version: Version, message_bus: &mut MessageBus<Self>) {
         self.protocol_version.replace(version);
+
+        let block = self.policy.blocked_paths();
+        if self.agent_enforces_policy() && !block.is_empty() {
+            message_bus
+                .send(ClientMessage::FileRequest(FileRequest::FsPolicy(
+                    FsPolicyRequest { block },
+                )))
+                .await;
+        }
+    }
+
+    /// Saves the request in the [`RequestQueue`] and sends it to the agent.
+    async fn send_request(
+        &mut self,
+        message_id: MessageId,
+        layer_id: LayerId,
+        request: FileRequest,
+        additional_data: AdditionalRequestData,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        self.request_queue.push_back_with_data(
+            message_id,
+            layer_id,
+            QueuedRequest {
+                request: request.clone(),
+                additional_data,
+            },
+        );
+        message_bus
+            .send(ProxyMessage::ToAgent(ClientMessage::FileRequest(request)))
+            .await;
+    }
+
+    /// Remembers the file opened with the given `request`, so that it can be reopened later.
+    fn file_opened(&mut self, fd: u64, request: &FileRequest) {
+        let (path, open_options) = match request {
+            FileRequest::Open(open) => (open.path.clone(), open.open_options),
+            FileRequest::OpenRelative(open) => {
+                let Some(parent) = self.opened_files.get(&open.relative_fd) else {
+                    return;
+                };
+                (parent.path.join(&open.path), open.open_options)
+            }
+            _ => return,
+        };
+
+        self.opened_files.insert(
+            fd,
+            OpenedFile {
+                path,
+                open_options,
+                position: 0,
+            },
+        );
+    }
+
+    /// Error for the requests that fail because of the agent reconnection.
+    fn refresh_error() -> ResponseError {
+        ResponseError::RemoteIO(RemoteIOError {
+            raw_os_error: Some(5), // EIO
+            kind: ErrorKindInternal::Other,
+        })
+    }
+
+    /// Returns the error response for a request that was not answered by the previous agent and
+    /// is not safe to send again, as the previous agent may have already handled it.
+    fn refresh_failure(request: &FileRequest) -> Option<FileResponse> {
+        let error = Self::refresh_error;
+
+        let response = match request {
+            FileRequest::Open(open) if open.open_options.create_new => {
+                FileResponse::Open(Err(error()))
+            }
+            FileRequest::OpenRelative(open) if open.open_options.create_new => {
+                FileResponse::Open(Err(error()))
+            }
+            FileRequest::Seek(seek) if matches!(seek.seek_from, SeekFromInternal::Current(..)) => {
+                FileResponse::Seek(Err(error()))
+            }
+            FileRequest::Write(..) => FileResponse::Write(Err(error())),
+            FileRequest::Unlink(..) | FileRequest::UnlinkAt(..) => {
+                FileResponse::Unlink(Err(error()))
+            }
+            FileRequest::Rename(..) => FileResponse::Rename(Err(error())),
+            FileRequest::RemoveDir(..) => FileResponse::RemoveDir(Err(error())),
+            FileRequest::MakeDir(..) | FileRequest::MakeDirAt(..) => {
+                FileResponse::MakeDir(Err(error()))
+            }
+            FileRequest::Symlink(..) => FileResponse::Symlink(Err(error())),
+            FileRequest::Link(..) => FileResponse::Link(Err(error())),
+            // Directory streams are not restored.
+            FileRequest::ReadDir(..) | FileRequest::ReadDirBatch(..) => {
+                FileResponse::ReadDir(Err(error()))
+            }
+            _ => return None,
+        };
+
+        Some(response)
+    }
+
+    /// Opens the remote files in the new agent connection, and sends it the requests that were
+    /// not answered by the previous one. Requests that are not safe to repeat fail, see
+    /// [`Self::refresh_failure`].
+    ///
+    /// Reopened files are never truncated or created. Directory streams become stale.
+    #[tracing::instrument(level = Level::TRACE, skip_all)]
+    async fn connection_refresh_started(&mut self, message_bus: &mut MessageBus<Self>) {
+        message_bus.send(ProxyMessage::ConnectionRefreshAck).await;
+
+        self.stale_dirs.extend(self.remote_dirs.iter().copied());
+        self.buffered_dirs.clear();
+
+        let mut queued_requests = std::mem::take(&mut self.request_queue);
+        while let Some((message_id, layer_id, queued)) = queued_requests.pop_front_with_data() {
+            match Self::refresh_failure(&queued.request) {
+                Some(response) => {
+                    tracing::warn!(
+                        request = ?queued.request,
+                        "Request was not answered before the agent reconnection and it is not safe to repeat it"
+                    );
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            layer_id,
+                            message: ProxyToLayerMessage::File(response),
+                        })
+                        .await;
+                }
+                None => self
+                    .request_queue
+                    .push_back_with_data(message_id, layer_id, queued),
+            }
+        }
+
+        let reopen_supported = self
+            .protocol_version
+            .as_ref()
+            .is_some_and(|version| REOPEN_VERSION.matches(version));
+        let reopens = if reopen_supported {
+            self.opened_files
+                .iter()
+                .map(|(fd, file)| {
+                    FileRequest::Reopen(ReopenFileRequest {
+                        fd: *fd,
+                        path: file.path.clone(),
+                        open_options: OpenOptionsInternal {
+                            truncate: false,
+                            create: false,
+                            create_new: false,
+                            ..file.open_options
+                        },
+                        position: file.position,
+                    })
+                })
+                .collect::<Vec<_>>()
+        } else {
+            if !self.opened_files.is_empty() {
+                tracing::warn!(
+                    "The agent does not support reopening files, \
+                    remote files opened before the reconnection can no longer be used"
+                );
+            }
+            vec![]
+        };
+
+        for request in &reopens {
+            message_bus
+                .send(ClientMessage::FileRequest(request.clone()))
+                .await;
+        }
+
+        for queued in self.request_queue.iter() {
+            message_bus
+                .send(ClientMessage::FileRequest(queued.request.clone()))
+                .await;
+        }
+
+        // Reopened files come first, as they were sent first. Message and layer ids are not
+        // used for them.
+        for request in reopens.into_iter().rev() {
+            self.request_queue.push_front_with_data(
+                Default::default(),
+                LayerId(Default::default()),
+                QueuedRequest {
+                    request,
+                    additional_data: Default::default(),
+                },
+            );
+        }
+    }
+
+    /// Sends the given [`FileRequest`] to the agent, but only if the negotiated
+    /// [`mirrord_protocol`] version matches `version_req`.
+    ///
+    /// Otherwise, responds to the layer with [`ResponseError::NotImplemented`], wrapped in the
+    /// given `response` variant, so that the layer can fall back to the local operation.
+    async fn versioned_request(
+        &mut self,
+        request: FileRequest,
+        version_req: &VersionReq,
+        response: fn(RemoteResult<()>) -> FileResponse,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let supported = self
+            .protocol_version
+            .as_ref()
+            .is_some_and(|version| version_req.matches(version));
+
+        if supported {
+            self.send_request(
+                message_id,
+                layer_id,
+                request,
+                Default::default(),
+                message_bus,
+            )
+            .await;
+        } else {
+            message_bus
+                .send(ToLayer {
+                    message_id,
+                    message: ProxyToLayerMessage::File(response(Err(
+                        ResponseError::NotImplemented,
+                    ))),
+                    layer_id,
+                })
+                .await;
+        }
+    }
+
+    /// Checks the remote paths used in the request against the [`ClientPolicy`].
+    ///
+    /// Returns the response for the layer when the request is blocked. Paths relative to a
+    /// directory descriptor are resolved only if we know the path of the directory, otherwise
+    /// they are blocked when the agent does not enforce the policy itself (see
+    /// [`Self::agent_enforces_policy`]). Symlinks are resolved only by the agent.
+    fn blocked_by_policy(&self, request: &FileRequest) -> Option<FileResponse> {
+        let resolve = |dirfd: u64, path: &Path| match self.opened_files.get(&dirfd) {
+            Some(dir) if path.is_relative() => dir.path.join(path),
+            _ => path.to_path_buf(),
+        };
+
+        let (paths, response): (Vec<PathBuf>, fn(ResponseError) -> FileResponse) = match request {
+            FileRequest::Open(open) => (vec![open.path.clone()], |e| FileResponse::Open(Err(e))),
+            FileRequest::OpenRelative(open) => (vec![resolve(open.relative_fd, &open.path)], |e| {
+                FileResponse::Open(Err(e))
+            }),
+            // The descriptor was opened with a request that was already checked.
+            FileRequest::FdOpenDir(open) => match self.opened_files.get(&open.remote_fd) {
+                Some(dir) => (vec![dir.path.clone()], |e| FileResponse::OpenDir(Err(e))),
+                None => return None,
+            },
+            FileRequest::Access(access) => (vec![access.pathname.clone()], |e| {
+                FileResponse::Access(Err(e))
+            }),
+            FileRequest::Xstat(xstat) => match (&xstat.path, xstat.fd) {
+                (Some(path), Some(fd)) => {
+                    (vec![resolve(fd, path)], |e| FileResponse::Xstat(Err(e)))
+                }
+                (Some(path), None) => (vec![path.clone()], |e| FileResponse::Xstat(Err(e))),
+                (None, _) => return None,
+            },
+            FileRequest::ReadLink(read_link) => (vec![read_link.path.clone()], |e| {
+                FileResponse::ReadLink(Err(e))
+            }),
+            FileRequest::MakeDir(make_dir) => (vec![make_dir.pathname.clone()], |e| {
+                FileResponse::MakeDir(Err(e))
+            }),
+            FileRequest::MakeDirAt(make_dir) => {
+                (vec![resolve(make_dir.dirfd, &make_dir.pathname)], |e| {
+                    FileResponse::MakeDir(Err(e))
+                })
+            }
+            FileRequest::Unlink(unlink) => (vec![unlink.pathname.clone()], |e| {
+                FileResponse::Unlink(Err(e))
+            }),
+            FileRequest::UnlinkAt(unlink) => (vec![resolve(unlink.dirfd, &unlink.pathname)], |e| {
+                FileResponse::Unlink(Err(e))
+            }),
+            FileRequest::Rename(rename) => (
+                vec![rename.old_path.clone(), rename.new_path.clone()],
+                |e| FileResponse::Rename(Err(e)),
+            ),
+            FileRequest::RemoveDir(remove) => (vec![remove.pathname.clone()], |e| {
+                FileResponse::RemoveDir(Err(e))
+            }),
+            FileRequest::Truncate(truncate) => (vec![truncate.path.clone()], |e| {
+                FileResponse::Truncate(Err(e))
+            }),
+            FileRequest::Chmod(chmod) => {
+                (vec![chmod.path.clone()], |e| FileResponse::Chmod(Err(e)))
+            }
+            FileRequest::Chown(chown) => {
+                (vec![chown.path.clone()], |e| FileResponse::Chown(Err(e)))
+            }
+            FileRequest::Utimensat(utimensat) => match (&utimensat.pathname, utimensat.dirfd) {
+                (Some(path), Some(dirfd)) => (vec![resolve(dirfd, path)], |e| {
+                    FileResponse::Utimensat(Err(e))
+                }),
+                (Some(path), None) => (vec![path.clone()], |e| FileResponse::Utimensat(Err(e))),
+                (None, _) => return None,
+            },
+            FileRequest::Symlink(symlink) => (
+                vec![symlink.target.clone(), symlink.link_path.clone()],
+                |e| FileResponse::Symlink(Err(e)),
+            ),
+            FileRequest::Link(link) => (vec![link.old_path.clone(), link.new_path.clone()], |e| {
+                FileResponse::Link(Err(e))
+            }),
+            _ => return None,
+        };
+
+        let agent_enforces_policy = self.agent_enforces_policy();
+        paths
+            .iter()
+            .find_map(|path| {
+                if path.is_absolute() {
+                    self.policy.check_file_access(path).err()
+                } else if agent_enforces_policy {
+                    None
+                } else {
+                    self.policy.check_unresolved_file_access(path).err()
+                }
+            })
+            .map(response)
+    }
+
+    /// Whether the agent enforces the `fs` restrictions of the [`ClientPolicy`], see
+    /// [`FsPolicyRequest`].
+    fn agent_enforces_policy(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| FS_POLICY_VERSION.matches(version))
     }
 
     // #[tracing::instrument(level = Level::TRACE, skip(message_bus))]
@@ -261,11 +659,23 @@ impl FilesProxy {
         message_id: MessageId,
         message_bus: &mut MessageBus<Self>,
     ) {
+        if let Some(response) = self.blocked_by_policy(&request) {
+            message_bus
+                .send(ToLayer {
+                    message_id,
+                    message: ProxyToLayerMessage::File(response),
+                    layer_id,
+                })
+                .await;
+            return;
+        }
+
         match request {
             // Should trigger remote close only when the fd is closed in all layer instances.
             FileRequest::Close(close) => {
                 if self.remote_files.remove(layer_id, close.fd) {
                     self.buffered_files.remove(&close.fd);
+                    self.opened_files.remove(&close.fd);
                     message_bus
                         .send(ClientMessage::FileRequest(FileRequest::Close(close)))
                         .await;
@@ -273,9 +683,13 @@ impl FilesProxy {
             }
 
             // Should trigger remote close only when the fd is closed in all layer instances.
+            // Stale directories are already gone with the previous agent connection.
             FileRequest::CloseDir(close) => {
                 if self.remote_dirs.remove(layer_id, close.remote_fd) {
                     self.buffered_dirs.remove(&close.remote_fd);
+                    if self.stale_dirs.remove(&close.remote_fd) {
+                        return;
+                    }
                     message_bus
                         .send(ClientMessage::FileRequest(FileRequest::CloseDir(close)))
                         .await;
@@ -287,11 +701,14 @@ impl FilesProxy {
                 let additional_data = (self.buffer_reads() && open.open_options.is_read_only())
                     .then_some(AdditionalRequestData::OpenBuffered)
                     .unwrap_or_default();
-                self.request_queue
-                    .push_back_with_data(message_id, layer_id, additional_data);
-                message_bus
-                    .send(ClientMessage::FileRequest(FileRequest::Open(open)))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    FileRequest::Open(open),
+                    additional_data,
+                    message_bus,
+                )
+                .await;
             }
 
             // May require storing additional data in the request queue.
@@ -299,11 +716,14 @@ impl FilesProxy {
                 let additional_data = (self.buffer_reads() && open.open_options.is_read_only())
                     .then_some(AdditionalRequestData::OpenBuffered)
                     .unwrap_or_default();
-                self.request_queue
-                    .push_back_with_data(message_id, layer_id, additional_data);
-                message_bus
-                    .send(ClientMessage::FileRequest(FileRequest::OpenRelative(open)))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    FileRequest::OpenRelative(open),
+                    additional_data,
+                    message_bus,
+                )
+                .await;
             }
 
             // Try to use local buffer if possible.
@@ -332,32 +752,32 @@ impl FilesProxy {
                             requested_amount: read.buffer_size,
                             update_fd_position: true,
                         };
-                        self.request_queue.push_back_with_data(
+                        let request = FileRequest::ReadLimited(ReadLimitedFileRequest {
+                            remote_fd: read.remote_fd,
+                            buffer_size: std::cmp::max(read.buffer_size, self.file_buffer_size),
+                            start_from: data.fd_position,
+                        });
+                        self.send_request(
                             message_id,
                             layer_id,
+                            request,
                             additional_data,
-                        );
-                        message_bus
-                            .send(ClientMessage::FileRequest(FileRequest::ReadLimited(
-                                ReadLimitedFileRequest {
-                                    remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
-                                    start_from: data.fd_position,
-                                },
-                            )))
-                            .await;
+                            message_bus,
+                        )
+                        .await;
                     }
                 }
 
                 // File is not buffered.
                 None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send(ClientMessage::FileRequest(FileRequest::Read(read)))
-                        .await;
+                    self.send_request(
+                        message_id,
+                        layer_id,
+                        FileRequest::Read(read),
+                        Default::default(),
+                        message_bus,
+                    )
+                    .await;
                 }
             },
 
@@ -386,35 +806,48 @@ impl FilesProxy {
                             requested_amount: read.buffer_size,
                             update_fd_position: false,
                         };
-                        self.request_queue.push_back_with_data(
+                        let request = FileRequest::ReadLimited(ReadLimitedFileRequest {
+                            remote_fd: read.remote_fd,
+                            buffer_size: std::cmp::max(read.buffer_size, self.file_buffer_size),
+                            start_from: read.start_from,
+                        });
+                        self.send_request(
                             message_id,
                             layer_id,
+                            request,
                             additional_data,
-                        );
-                        message_bus
-                            .send(ClientMessage::FileRequest(FileRequest::ReadLimited(
-                                ReadLimitedFileRequest {
-                                    remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
-                                    start_from: read.start_from,
-                                },
-                            )))
-                            .await;
+                            message_bus,
+                        )
+                        .await;
                     }
                 }
 
                 // File is not buffered.
                 None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send(ClientMessage::FileRequest(FileRequest::ReadLimited(read)))
-                        .await;
+                    self.send_request(
+                        message_id,
+                        layer_id,
+                        FileRequest::ReadLimited(read),
+                        Default::default(),
+                        message_bus,
+                    )
+                    .await;
                 }
             },
 
+            // Directory was opened with the previous agent connection.
+            FileRequest::ReadDir(read_dir) if self.stale_dirs.contains(&read_dir.remote_fd) => {
+                message_bus
+                    .send(ToLayer {
+                        message_id,
+                        layer_id,
+                        message: ProxyToLayerMessage::File(FileResponse::ReadDir(Err(
+                            Self::refresh_error(),
+                        ))),
+                    })
+                    .await;
+            }
+
             // Try to use local buffer if possible.
             FileRequest::ReadDir(read_dir) => match self.buffered_dirs.get_mut(&read_dir.remote_fd)
             {
@@ -433,24 +866,31 @@ impl FilesProxy {
                             })
                             .await;
                     } else {
-                        self.request_queue.push_back(message_id, layer_id);
-                        message_bus
-                            .send(ClientMessage::FileRequest(FileRequest::ReadDirBatch(
-                                ReadDirBatchRequest {
-                                    remote_fd: read_dir.remote_fd,
-                                    amount: Self::READDIR_BATCH_SIZE,
-                                },
-                            )))
-                            .await;
+                        let request = FileRequest::ReadDirBatch(ReadDirBatchRequest {
+                            remote_fd: read_dir.remote_fd,
+                            amount: Self::READDIR_BATCH_SIZE,
+                        });
+                        self.send_request(
+                            message_id,
+                            layer_id,
+                            request,
+                            Default::default(),
+                            message_bus,
+                        )
+                        .await;
                     }
                 }
 
                 // Directory is not buffered.
                 None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send(ClientMessage::FileRequest(FileRequest::ReadDir(read_dir)))
-                        .await;
+                    self.send_request(
+                        message_id,
+                        layer_id,
+                        FileRequest::ReadDir(read_dir),
+                        Default::default(),
+                        message_bus,
+                    )
+                    .await;
                 }
             },
 
@@ -462,9 +902,7 @@ impl FilesProxy {
                     .is_some_and(|version| READLINK_VERSION.matches(version));
 
                 if supported {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send(ProxyMessage::ToAgent(ClientMessage::FileRequest(req)))
+                    self.send_request(message_id, layer_id, req, Default::default(), message_bus)
                         .await;
                 } else {
                     message_bus
@@ -515,41 +953,140 @@ impl FilesProxy {
                         _ => AdditionalRequestData::Other,
                     };
 
-                self.request_queue
-                    .push_back_with_data(message_id, layer_id, additional_data);
-                message_bus
-                    .send(ClientMessage::FileRequest(FileRequest::Seek(seek)))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    FileRequest::Seek(seek),
+                    additional_data,
+                    message_bus,
+                )
+                .await;
             }
 
             FileRequest::MakeDir(_) | FileRequest::MakeDirAt(_) => {
-                let supported = self
-                    .protocol_version
-                    .as_ref()
-                    .is_some_and(|version| MKDIR_VERSION.matches(version));
+                self.versioned_request(
+                    request,
+                    &MKDIR_VERSION,
+                    FileResponse::MakeDir,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
 
-                if supported {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send(ProxyMessage::ToAgent(ClientMessage::FileRequest(request)))
-                        .await;
-                } else {
-                    let file_response = FileResponse::MakeDir(Err(ResponseError::NotImplemented));
+            FileRequest::Unlink(_) | FileRequest::UnlinkAt(_) => {
+                self.versioned_request(
+                    request,
+                    &REMOVE_RENAME_VERSION,
+                    FileResponse::Unlink,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
 
-                    message_bus
-                        .send(ToLayer {
-                            message_id,
-                            message: ProxyToLayerMessage::File(file_response),
-                            layer_id,
-                        })
-                        .await;
-                }
+            FileRequest::Rename(_) => {
+                self.versioned_request(
+                    request,
+                    &REMOVE_RENAME_VERSION,
+                    FileResponse::Rename,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::RemoveDir(_) => {
+                self.versioned_request(
+                    request,
+                    &REMOVE_RENAME_VERSION,
+                    FileResponse::RemoveDir,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::Ftruncate(_) | FileRequest::Truncate(_) => {
+                self.versioned_request(
+                    request,
+                    &TRUNCATE_VERSION,
+                    FileResponse::Truncate,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::Chmod(_) | FileRequest::Fchmod(_) => {
+                self.versioned_request(
+                    request,
+                    &SET_METADATA_VERSION,
+                    FileResponse::Chmod,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::Chown(_) | FileRequest::Fchown(_) => {
+                self.versioned_request(
+                    request,
+                    &SET_METADATA_VERSION,
+                    FileResponse::Chown,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::Utimensat(_) => {
+                self.versioned_request(
+                    request,
+                    &SET_METADATA_VERSION,
+                    FileResponse::Utimensat,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::Symlink(_) => {
+                self.versioned_request(
+                    request,
+                    &LINK_VERSION,
+                    FileResponse::Symlink,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
+
+            FileRequest::Link(_) => {
+                self.versioned_request(
+                    request,
+                    &LINK_VERSION,
+                    FileResponse::Link,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
             }
 
             // Doesn't require any special logic.
             other => {
-                self.request_queue.push_back(message_id, layer_id);
-                message_bus.send(ClientMessage::FileRequest(other)).await;
+                self.send_request(message_id, layer_id, other, Default::default(), message_bus)
+                    .await;
             }
         }
     }
@@ -563,16 +1100,22 @@ impl FilesProxy {
         match response {
             // Update file maps.
             FileResponse::Open(Ok(open)) => {
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, queued) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(DaemonMessage::File(FileResponse::Open(Ok(
                             open.clone()
                         ))))
                     })?;
 
+                if let FileRequest::Reopen(reopen) = &queued.request {
+                    tracing::debug!(?reopen, "Remote file reopened");
+                    return Ok(());
+                }
+
                 self.remote_files.add(layer_id, open.fd);
+                self.file_opened(open.fd, &queued.request);
 
-                if matches!(additional_data, AdditionalRequestData::OpenBuffered) {
+                if matches!(queued.additional_data, AdditionalRequestData::OpenBuffered) {
                     self.buffered_files.insert(open.fd, Default::default());
                 }
 
@@ -593,6 +1136,8 @@ impl FilesProxy {
                     ))))
                 })?;
 
+                // The new agent may reuse a descriptor of a stale directory.
+                self.stale_dirs.remove(&open.fd);
                 self.remote_dirs.add(layer_id, open.fd);
 
                 if self.buffer_dirs() {
@@ -610,7 +1155,7 @@ impl FilesProxy {
 
             // If the file is buffered, update `files_data`.
             FileResponse::ReadLimited(Ok(read)) => {
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, queued) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(DaemonMessage::File(FileResponse::ReadLimited(Ok(
                             read.clone(),
@@ -621,7 +1166,7 @@ impl FilesProxy {
                     fd,
                     requested_amount,
                     update_fd_position,
-                } = additional_data
+                } = queued.additional_data
                 else {
                     // This file is not buffered.
                     message_bus
@@ -678,14 +1223,20 @@ impl FilesProxy {
 
             // If the file is buffered, update `files_data`.
             FileResponse::Seek(Ok(seek)) => {
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, queued) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(DaemonMessage::File(FileResponse::Seek(Ok(
                             seek.clone()
                         ))))
                     })?;
 
-                if let AdditionalRequestData::SeekBuffered { fd } = additional_data {
+                if let FileRequest::Seek(request) = &queued.request {
+                    if let Some(file) = self.opened_files.get_mut(&request.fd) {
+                        file.position = seek.result_offset;
+                    }
+                }
+
+                if let AdditionalRequestData::SeekBuffered { fd } = queued.additional_data {
                     let Some(data) = self.buffered_files.get_mut(&fd) else {
                         // File must have been closed from other thread in user application.
                         message_bus
@@ -749,12 +1300,37 @@ impl FilesProxy {
                     .await;
             }
 
-            // Doesn't require any special logic.
+            // Only keeps track of remote file positions.
             other => {
-                let (message_id, layer_id) = self
+                let (message_id, layer_id, queued) = self
                     .request_queue
-                    .pop_front()
+                    .pop_front_with_data()
                     .ok_or_else(|| UnexpectedAgentMessage(DaemonMessage::File(other.clone())))?;
+
+                match (&queued.request, &other) {
+                    (FileRequest::Reopen(reopen), FileResponse::Open(Err(error))) => {
+                        tracing::warn!(
+                            %error,
+                            fd = reopen.fd,
+                            path = %reopen.path.display(),
+                            "Failed to reopen a remote file after reconnecting to the agent",
+                        );
+                        self.opened_files.remove(&reopen.fd);
+                        return Ok(());
+                    }
+                    (FileRequest::Read(request), FileResponse::Read(Ok(read))) => {
+                        if let Some(file) = self.opened_files.get_mut(&request.remote_fd) {
+                            file.position += read.read_amount;
+                        }
+                    }
+                    (FileRequest::Write(request), FileResponse::Write(Ok(write))) => {
+                        if let Some(file) = self.opened_files.get_mut(&request.fd) {
+                            file.position += write.written_amount;
+                        }
+                    }
+                    _ => {}
+                }
+
                 message_bus
                     .send(ToLayer {
                         message_id,
@@ -790,7 +1366,13 @@ impl BackgroundTask for FilesProxy {
                     self.layer_closed(closed, message_bus).await;
                 }
                 FilesProxyMessage::LayerForked(forked) => self.layer_forked(forked),
-                FilesProxyMessage::ProtocolVersion(version) => self.protocol_version(version),
+                FilesProxyMessage::ProtocolVersion(version) => {
+                    self.protocol_version(version, message_bus).await
+                }
+                FilesProxyMessage::ConnectionRefresh(ConnectionRefresh::Start) => {
+                    self.connection_refresh_started(message_bus).await;
+                }
+                FilesProxyMessage::ConnectionRefresh(ConnectionRefresh::End) => {}
             }
         }
 
@@ -807,10 +1389,11 @@ mod tests {
     use mirrord_intproxy_protocol::{LayerId, ProxyToLayerMessage};
     use mirrord_protocol::{
         file::{
-            FdOpenDirRequest, OpenDirResponse, OpenFileRequest, OpenFileResponse,
+            CloseDirRequest, FdOpenDirRequest, OpenDirResponse, OpenFileRequest, OpenFileResponse,
             OpenOptionsInternal, ReadDirBatchRequest, ReadDirBatchResponse, ReadDirRequest,
             ReadDirResponse, ReadFileRequest, ReadFileResponse, ReadLimitedFileRequest,
-            SeekFileRequest, SeekFileResponse, SeekFromInternal,
+            ReopenFileRequest, SeekFileRequest, SeekFileResponse, SeekFromInternal, UnlinkRequest,
+            WriteFileRequest,
         },
         ClientMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError, ResponseError,
     };
@@ -821,7 +1404,7 @@ mod tests {
     use crate::{
         background_tasks::{BackgroundTasks, TaskSender, TaskUpdate},
         error::IntProxyError,
-        main_tasks::{MainTaskId, ProxyMessage, ToLayer},
+        main_tasks::{ConnectionRefresh, MainTaskId, ProxyMessage, ToLayer},
     };
 
     /// Sets up a [`TaskSender`] and [`BackgroundTasks`] for a functioning [`FilesProxy`].
@@ -840,7 +1423,7 @@ mod tests {
             Default::default();
 
         let proxy = tasks.register(
-            FilesProxy::new(file_buffer_size),
+            FilesProxy::new(file_buffer_size, Default::default()),
             MainTaskId::FilesProxy,
             32,
         );
@@ -1370,4 +1953,206 @@ mod tests {
             .unwrap_proxy_to_layer_message();
         assert_eq!(update, ProxyToLayerMessage::File(seek_response),);
     }
+
+    #[rstest]
+    #[case(Version::new(1, 14, 0), false)]
+    #[case(Version::new(1, 15, 0), true)]
+    #[tokio::test]
+    async fn unlink_request_version(#[case] version: Version, #[case] supported: bool) {
+        let (proxy, mut tasks) = setup_proxy(version, 0).await;
+
+        let request = FileRequest::Unlink(UnlinkRequest {
+            pathname: PathBuf::from("/tmp/file"),
+        });
+        proxy
+            .send(FilesProxyMessage::FileReq(0xbad, LayerId(0xa55), request))
+            .await;
+        let (_, update) = tasks.next().await.unzip();
+
+        if supported {
+            assert!(
+                matches!(
+                    update,
+                    Some(TaskUpdate::Message(ProxyMessage::ToAgent(
+                        ClientMessage::FileRequest(FileRequest::Unlink(UnlinkRequest { .. }))
+                    )))
+                ),
+                "Mismatched message for `UnlinkRequest` {update:?}!"
+            );
+        } else {
+            assert!(
+                matches!(
+                    update,
+                    Some(TaskUpdate::Message(ProxyMessage::ToLayer(ToLayer {
+                        message_id: 0xbad,
+                        layer_id: LayerId(0xa55),
+                        message: ProxyToLayerMessage::File(FileResponse::Unlink(Err(
+                            ResponseError::NotImplemented
+                        )))
+                    })))
+                ),
+                "Mismatched message for `UnlinkResponse` {update:?}!"
+            );
+        }
+    }
+
+    /// Verifies that the remote file is reopened at the correct position, and that the unanswered
+    /// request is sent again, when the agent connection is replaced.
+    #[tokio::test]
+    async fn reopen_on_connection_refresh() {
+        let (proxy, mut tasks) = setup_proxy(mirrord_protocol::VERSION.clone(), 0).await;
+
+        let fd = open_file(&proxy, &mut tasks, false).await;
+        make_read_request(&proxy, &mut tasks, fd, 10, None).await;
+        respond_to_read_request(&proxy, &mut tasks, vec![0; 10], false).await;
+
+        let pending_read = FileRequest::Read(ReadFileRequest {
+            remote_fd: fd,
+            buffer_size: 5,
+        });
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0),
+                pending_read.clone(),
+            ))
+            .await;
+        tasks.next().await.unwrap().1.unwrap_message();
+
+        proxy
+            .send(FilesProxyMessage::ConnectionRefresh(
+                ConnectionRefresh::Start,
+            ))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ConnectionRefreshAck,
+        );
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToAgent(ClientMessage::FileRequest(FileRequest::Reopen(
+                ReopenFileRequest {
+                    fd,
+                    path: PathBuf::from("/some/path"),
+                    open_options: OpenOptionsInternal {
+                        read: true,
+                        write: true,
+                        ..Default::default()
+                    },
+                    position: 10,
+                }
+            ))),
+        );
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToAgent(ClientMessage::FileRequest(pending_read)),
+        );
+        proxy
+            .send(FilesProxyMessage::ConnectionRefresh(ConnectionRefresh::End))
+            .await;
+
+        // Response to the reopen is not sent to the layer.
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::Open(Ok(
+                OpenFileResponse { fd },
+            ))))
+            .await;
+        let update = respond_to_read_request(&proxy, &mut tasks, vec![1; 5], false).await;
+        assert_eq!(
+            update,
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0xbad,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                    bytes: vec![1; 5],
+                    read_amount: 5,
+                }))),
+            }),
+        );
+    }
+
+    /// Requests that are not safe to repeat and directory streams fail after the agent
+    /// reconnection.
+    #[tokio::test]
+    async fn fail_unsafe_requests_on_connection_refresh() {
+        let (proxy, mut tasks) = setup_proxy(mirrord_protocol::VERSION.clone(), 0).await;
+
+        prepare_dir(&proxy, &mut tasks).await;
+
+        let pending_write = FileRequest::Write(WriteFileRequest {
+            fd: 0xdad,
+            write_bytes: vec![0; 5],
+        });
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                pending_write,
+            ))
+            .await;
+        tasks.next().await.unwrap().1.unwrap_message();
+
+        proxy
+            .send(FilesProxyMessage::ConnectionRefresh(
+                ConnectionRefresh::Start,
+            ))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ConnectionRefreshAck,
+        );
+
+        let eio = ResponseError::RemoteIO(RemoteIOError {
+            raw_os_error: Some(5),
+            kind: ErrorKindInternal::Other,
+        });
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0xbad,
+                layer_id: LayerId(0xa55),
+                message: ProxyToLayerMessage::File(FileResponse::Write(Err(eio.clone()))),
+            }),
+        );
+        proxy
+            .send(FilesProxyMessage::ConnectionRefresh(ConnectionRefresh::End))
+            .await;
+
+        let read_dir = FileRequest::ReadDir(ReadDirRequest { remote_fd: 0xdad });
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                read_dir.clone(),
+            ))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0xbad,
+                layer_id: LayerId(0xa55),
+                message: ProxyToLayerMessage::File(FileResponse::ReadDir(Err(eio))),
+            }),
+        );
+
+        // Stale directory is not closed in the new agent.
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                FileRequest::CloseDir(CloseDirRequest { remote_fd: 0xdad }),
+            ))
+            .await;
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                read_dir.clone(),
+            ))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToAgent(ClientMessage::FileRequest(read_dir)),
+        );
+    }
 }
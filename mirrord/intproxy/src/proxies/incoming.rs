@@ -4,6 +4,7 @@ use std::{
     collections::{hash_map::Entry, HashMap},
     fmt, io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
 };
 
 use bytes::Bytes;
@@ -14,15 +15,18 @@ use hyper::body::Frame;
 use mirrord_intproxy_protocol::{
     ConnMetadataRequest, ConnMetadataResponse, IncomingRequest, IncomingResponse, LayerId,
     MessageId, PortSubscribe, PortSubscription, PortUnsubscribe, ProxyToLayerMessage,
+    UdpPortSubscribe,
 };
+use mirrord_operator::client::policy::ClientPolicy;
 use mirrord_protocol::{
     body_chunks::BodyExt,
     tcp::{
         ChunkedHttpBody, ChunkedHttpError, ChunkedRequest, ChunkedResponse, DaemonTcp, HttpRequest,
         HttpRequestFallback, HttpResponse, HttpResponseFallback, InternalHttpBodyFrame,
         InternalHttpRequest, InternalHttpResponse, LayerTcpSteal, NewTcpConnection,
-        ReceiverStreamBody, StreamingBody, TcpData,
+        ReceiverStreamBody, StealType, StreamingBody, TcpData, STEAL_POLICY_VERSION,
     },
+    udp::{DaemonUdp, UDP_MIRROR_VERSION, UDP_STEAL_VERSION},
     ClientMessage, ConnectionId, RequestId, ResponseError,
 };
 use thiserror::Error;
@@ -36,18 +40,24 @@ use tracing::{debug, Level};
 use self::{
     interceptor::{Interceptor, InterceptorError, MessageOut},
     port_subscription_ext::PortSubscriptionExt,
+    recorder::HttpRecorder,
+    rewrite::HttpRewrites,
     subscriptions::SubscriptionsManager,
+    udp::UdpSubscriptions,
 };
 use crate::{
     background_tasks::{BackgroundTask, BackgroundTasks, MessageBus, TaskSender, TaskUpdate},
-    main_tasks::{LayerClosed, LayerForked, ToLayer},
+    main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ToLayer},
     ProxyMessage,
 };
 
 mod http;
 mod interceptor;
 pub mod port_subscription_ext;
+pub mod recorder;
+pub mod rewrite;
 mod subscriptions;
+mod udp;
 
 /// Creates and binds a new [`TcpSocket`].
 /// The socket has the same IP version and address as the given `addr`.
@@ -108,8 +118,11 @@ pub enum IncomingProxyMessage {
     LayerClosed(LayerClosed),
     AgentMirror(DaemonTcp),
     AgentSteal(DaemonTcp),
+    AgentUdp(DaemonUdp),
     /// Agent responded to [`ClientMessage::SwitchProtocolVersion`].
     AgentProtocolVersion(semver::Version),
+    /// The agent connection is being replaced.
+    ConnectionRefresh(ConnectionRefresh),
 }
 
 /// Handle for an [`Interceptor`].
@@ -160,10 +173,20 @@ impl MetadataStore {
 ///
 /// Incoming connections are created by the agent either explicitly ([`NewTcpConnection`] message)
 /// or implicitly ([`HttpRequest`]).
+///
+/// Incoming UDP datagrams are handled separately, in [`UdpSubscriptions`].
+///
+/// HTTP requests and responses can be written to a file with an optional [`HttpRecorder`], and
+/// modified by the [`Interceptor`]s with [`HttpRewrites`].
+///
+/// When the agent connection is replaced ([`ConnectionRefresh`]), all ports are subscribed again
+/// and the intercepted connections are dropped, as they exist only in the previous agent.
 #[derive(Default)]
 pub struct IncomingProxy {
     /// Active port subscriptions for all layers.
     subscriptions: SubscriptionsManager,
+    /// Active UDP port subscriptions for all layers.
+    udp_subscriptions: UdpSubscriptions,
     /// [`TaskSender`]s for active [`Interceptor`]s.
     interceptors: HashMap<InterceptorId, InterceptorHandle>,
     /// For receiving updates from [`Interceptor`]s.
@@ -176,6 +199,12 @@ pub struct IncomingProxy {
     response_body_rxs: StreamMap<(ConnectionId, RequestId), StreamNotifyClose<ReceiverStreamBody>>,
     /// Version of [`mirrord_protocol`] negotiated with the agent.
     agent_protocol_version: Option<semver::Version>,
+    /// For recording HTTP traffic, enabled with `feature.network.incoming.http_record`.
+    recorder: Option<HttpRecorder>,
+    /// Shared with all [`Interceptor`]s.
+    http_rewrites: Arc<HttpRewrites>,
+    /// Used to reject steal subscriptions with filters that do not satisfy the mirrord policies.
+    policy: ClientPolicy,
 }
 
 impl IncomingProxy {
@@ -184,7 +213,34 @@ impl IncomingProxy {
     // TODO: Update outdated documentation. RawInterceptor, HttpInterceptor do not exist
     const CHANNEL_SIZE: usize = 512;
 
+    /// Creates a new instance that writes HTTP traffic to the given [`HttpRecorder`] and applies
+    /// the given [`HttpRewrites`].
+    pub fn new(
+        recorder: Option<HttpRecorder>,
+        http_rewrites: HttpRewrites,
+        policy: ClientPolicy,
+    ) -> Self {
+        Self {
+            recorder,
+            http_rewrites: Arc::new(http_rewrites),
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Passes the response sent to the agent to the [`HttpRecorder`], if there is one.
+    async fn record_response(&mut self, message: &ClientMessage) {
+        if let (Some(recorder), ClientMessage::TcpSteal(message)) =
+            (self.recorder.as_mut(), message)
+        {
+            recorder.response(message).await;
+        }
+    }
+
     /// Tries to register the new subscription in the [`SubscriptionsManager`].
+    ///
+    /// Steal subscriptions blocked by the [`ClientPolicy`] are rejected without reaching the
+    /// agent.
     #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
     async fn handle_port_subscribe(
         &mut self,
@@ -193,6 +249,22 @@ impl IncomingProxy {
         subscribe: PortSubscribe,
         message_bus: &mut MessageBus<Self>,
     ) {
+        if let PortSubscription::Steal(steal_type) = &subscribe.subscription
+            && let Err(error) = self.policy.check_steal(steal_type)
+        {
+            tracing::warn!(%error, "Port subscribe blocked by policy");
+            message_bus
+                .send(ToLayer {
+                    message_id,
+                    layer_id,
+                    message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Err(
+                        error,
+                    ))),
+                })
+                .await;
+            return;
+        }
+
         let msg = self
             .subscriptions
             .layer_subscribed(layer_id, message_id, subscribe);
@@ -202,6 +274,81 @@ impl IncomingProxy {
         }
     }
 
+    /// Tries to register the new subscription in the [`UdpSubscriptions`].
+    ///
+    /// Responds with [`ResponseError::NotImplemented`] if the agent does not support incoming UDP
+    /// traffic in the requested mode.
+    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
+    async fn handle_udp_port_subscribe(
+        &mut self,
+        message_id: MessageId,
+        layer_id: LayerId,
+        subscribe: UdpPortSubscribe,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let required_version = if subscribe.steal {
+            &*UDP_STEAL_VERSION
+        } else {
+            &*UDP_MIRROR_VERSION
+        };
+        let supported = self
+            .agent_protocol_version
+            .as_ref()
+            .is_some_and(|version| required_version.matches(version));
+
+        // UDP traffic is never filtered.
+        let blocked = subscribe
+            .steal
+            .then(|| self.policy.check_steal(&StealType::All(subscribe.port)))
+            .and_then(Result::err);
+
+        let msg = if let Some(error) = blocked {
+            Some(ProxyMessage::ToLayer(ToLayer {
+                message_id,
+                layer_id,
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(Err(
+                    error,
+                ))),
+            }))
+        } else if supported {
+            self.udp_subscriptions
+                .layer_subscribed(layer_id, message_id, subscribe)
+        } else {
+            Some(ProxyMessage::ToLayer(ToLayer {
+                message_id,
+                layer_id,
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(Err(
+                    ResponseError::NotImplemented,
+                ))),
+            }))
+        };
+
+        if let Some(msg) = msg {
+            message_bus.send(msg).await;
+        }
+    }
+
+    /// Handles UDP messages from the agent.
+    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
+    async fn handle_agent_udp_message(
+        &mut self,
+        message: DaemonUdp,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Result<(), IncomingProxyError> {
+        match message {
+            DaemonUdp::SubscribeResult(result) => {
+                for msg in self.udp_subscriptions.agent_responded(result) {
+                    message_bus.send(msg).await;
+                }
+            }
+            DaemonUdp::Datagram(datagram) => {
+                self.udp_subscriptions.handle_datagram(datagram).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Tries to unregister the subscription from the [`SubscriptionsManager`].
     #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
     async fn handle_port_unsubscribe(
@@ -247,6 +394,7 @@ impl IncomingProxy {
                         interceptor_socket,
                         subscription.listening_on,
                         self.agent_protocol_version.clone(),
+                        self.http_rewrites.clone(),
                     ),
                     id,
                     Self::CHANNEL_SIZE,
@@ -284,8 +432,14 @@ impl IncomingProxy {
                 for key in keys.iter() {
                     self.response_body_rxs.remove(key);
                 }
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.connection_closed(close.connection_id);
+                }
             }
             DaemonTcp::Data(data) => {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.raw_request_data(data.connection_id, &data.bytes);
+                }
                 if let Some(interceptor) = self.interceptors.get(&InterceptorId(data.connection_id))
                 {
                     interceptor.tx.send(data.bytes).await;
@@ -297,6 +451,9 @@ impl IncomingProxy {
                 }
             }
             DaemonTcp::HttpRequest(req) => {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.request(&req);
+                }
                 let req = HttpRequestFallback::Fallback(req);
                 let interceptor = self.get_interceptor_for_http_request(&req)?;
                 if let Some(interceptor) = interceptor {
@@ -304,6 +461,9 @@ impl IncomingProxy {
                 }
             }
             DaemonTcp::HttpRequestFramed(req) => {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.request_framed(&req).await;
+                }
                 let req = HttpRequestFallback::Framed(req);
                 let interceptor = self.get_interceptor_for_http_request(&req)?;
                 if let Some(interceptor) = interceptor {
@@ -311,6 +471,9 @@ impl IncomingProxy {
                 }
             }
             DaemonTcp::HttpRequestChunked(req) => {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.request_chunked(&req);
+                }
                 match req {
                     ChunkedRequest::Start(req) => {
                         let (tx, rx) = mpsc::channel::<InternalHttpBodyFrame>(128);
@@ -385,6 +548,10 @@ impl IncomingProxy {
 
                 let id = InterceptorId(connection_id);
 
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.raw_connection(connection_id, destination_port);
+                }
+
                 self.metadata_store.expect(
                     ConnMetadataRequest {
                         listener_address: subscription.listening_on,
@@ -402,6 +569,7 @@ impl IncomingProxy {
                         interceptor_socket,
                         subscription.listening_on,
                         self.agent_protocol_version.clone(),
+                        self.http_rewrites.clone(),
                     ),
                     id,
                     Self::CHANNEL_SIZE,
@@ -430,6 +598,7 @@ impl IncomingProxy {
     fn handle_layer_fork(&mut self, msg: LayerForked) {
         let LayerForked { child, parent } = msg;
         self.subscriptions.layer_forked(parent, child);
+        self.udp_subscriptions.layer_forked(parent, child);
     }
 
     async fn handle_layer_close(&mut self, msg: LayerClosed, message_bus: &MessageBus<Self>) {
@@ -438,6 +607,50 @@ impl IncomingProxy {
         for msg in msgs {
             message_bus.send(msg).await;
         }
+
+        for msg in self.udp_subscriptions.layer_closed(msg.id) {
+            message_bus.send(msg).await;
+        }
+    }
+
+    /// Drops all intercepted connections and subscribes all ports in the new agent connection.
+    #[tracing::instrument(level = Level::TRACE, skip_all)]
+    async fn connection_refresh_started(&mut self, message_bus: &mut MessageBus<Self>) {
+        message_bus.send(ProxyMessage::ConnectionRefreshAck).await;
+
+        self.send_required_header_filters(message_bus).await;
+
+        self.interceptors.clear();
+        self.background_tasks.clear();
+        self.metadata_store = Default::default();
+        self.request_body_txs.clear();
+        self.response_body_rxs.clear();
+
+        let subscriptions = self
+            .subscriptions
+            .agent_subscriptions()
+            .chain(self.udp_subscriptions.agent_subscriptions());
+        for msg in subscriptions {
+            message_bus.send(msg).await;
+        }
+    }
+
+    /// Sends the header filters required by the [`ClientPolicy`] to the agent, if it supports
+    /// [`LayerTcpSteal::RequiredHeaderFilters`]. Must be sent before the subscriptions.
+    async fn send_required_header_filters(&self, message_bus: &mut MessageBus<Self>) {
+        let required = self.policy.required_header_filters();
+        let supported = self
+            .agent_protocol_version
+            .as_ref()
+            .is_some_and(|version| STEAL_POLICY_VERSION.matches(version));
+
+        if supported && !required.is_empty() {
+            message_bus
+                .send(ClientMessage::TcpSteal(
+                    LayerTcpSteal::RequiredHeaderFilters(required),
+                ))
+                .await;
+        }
     }
 
     fn get_subscription(&self, interceptor_id: InterceptorId) -> Option<&PortSubscription> {
@@ -456,59 +669,58 @@ impl BackgroundTask for IncomingProxy {
     async fn run(mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
         loop {
             tokio::select! {
-                Some(((connection_id, request_id), stream_item)) = self.response_body_rxs.next() => match stream_item {
-                    Some(Ok(frame)) => {
-                        let int_frame = InternalHttpBodyFrame::from(frame);
-                        let res = ChunkedResponse::Body(ChunkedHttpBody {
-                            frames: vec![int_frame],
+                Some(((connection_id, request_id), stream_item)) = self.response_body_rxs.next() => {
+                    let res = match stream_item {
+                        Some(Ok(frame)) => ChunkedResponse::Body(ChunkedHttpBody {
+                            frames: vec![InternalHttpBodyFrame::from(frame)],
                             is_last: false,
                             connection_id,
                             request_id,
-                        });
-                        message_bus
-                            .send(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
-                                res,
-                            )))
-                            .await;
-                    },
-                    Some(Err(error)) => {
-                        debug!(%error, "Error while reading streamed response body");
-                        let res = ChunkedResponse::Error(ChunkedHttpError {connection_id, request_id});
-                        message_bus
-                            .send(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
-                                res,
-                            )))
-                            .await;
-                        self.response_body_rxs.remove(&(connection_id, request_id));
-                    },
-                    None => {
-                        let res = ChunkedResponse::Body(ChunkedHttpBody {
-                            frames: vec![],
-                            is_last: true,
-                            connection_id,
-                            request_id,
-                        });
-                        message_bus
-                            .send(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
-                                res,
-                            )))
-                            .await;
-                        self.response_body_rxs.remove(&(connection_id, request_id));
-                    }
+                        }),
+                        Some(Err(error)) => {
+                            debug!(%error, "Error while reading streamed response body");
+                            self.response_body_rxs.remove(&(connection_id, request_id));
+                            ChunkedResponse::Error(ChunkedHttpError {connection_id, request_id})
+                        },
+                        None => {
+                            self.response_body_rxs.remove(&(connection_id, request_id));
+                            ChunkedResponse::Body(ChunkedHttpBody {
+                                frames: vec![],
+                                is_last: true,
+                                connection_id,
+                                request_id,
+                            })
+                        }
+                    };
+
+                    let msg = ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(res));
+                    self.record_response(&msg).await;
+                    message_bus.send(msg).await;
                 },
 
+                msg = self.udp_subscriptions.next_reply() => message_bus.send(msg).await,
+
                 msg = message_bus.recv() => match msg {
                     None => {
                         tracing::trace!("message bus closed, exiting");
+                        if let Some(recorder) = &self.recorder {
+                            recorder.flush().await;
+                        }
                         break Ok(());
                     },
                     Some(IncomingProxyMessage::LayerRequest(message_id, layer_id, req)) => match req {
                         IncomingRequest::PortSubscribe(subscribe) => self.handle_port_subscribe(message_id, layer_id, subscribe, message_bus).await,
                         IncomingRequest::PortUnsubscribe(unsubscribe) => self.handle_port_unsubscribe(layer_id, unsubscribe, message_bus).await,
                         IncomingRequest::ConnMetadata(req) => {
-                            let res = self.metadata_store.get(req);
+                            let res = self.udp_subscriptions.metadata(&req).unwrap_or_else(|| self.metadata_store.get(req));
                             message_bus.send(ToLayer { message_id, layer_id, message: ProxyToLayerMessage::Incoming(IncomingResponse::ConnMetadata(res))  }).await;
                         }
+                        IncomingRequest::UdpPortSubscribe(subscribe) => self.handle_udp_port_subscribe(message_id, layer_id, subscribe, message_bus).await,
+                        IncomingRequest::UdpPortUnsubscribe(unsubscribe) => {
+                            if let Some(msg) = self.udp_subscriptions.layer_unsubscribed(layer_id, unsubscribe) {
+                                message_bus.send(msg).await;
+                            }
+                        }
                     },
                     Some(IncomingProxyMessage::AgentMirror(msg)) => {
                         self.handle_agent_message(msg, message_bus).await?;
@@ -516,11 +728,19 @@ impl BackgroundTask for IncomingProxy {
                     Some(IncomingProxyMessage::AgentSteal(msg)) => {
                         self.handle_agent_message(msg, message_bus).await?;
                     }
+                    Some(IncomingProxyMessage::AgentUdp(msg)) => {
+                        self.handle_agent_udp_message(msg, message_bus).await?;
+                    }
                     Some(IncomingProxyMessage::LayerClosed(msg)) => self.handle_layer_close(msg, message_bus).await,
                     Some(IncomingProxyMessage::LayerForked(msg)) => self.handle_layer_fork(msg),
                     Some(IncomingProxyMessage::AgentProtocolVersion(version)) => {
                         self.agent_protocol_version.replace(version);
+                        self.send_required_header_filters(message_bus).await;
+                    }
+                    Some(IncomingProxyMessage::ConnectionRefresh(ConnectionRefresh::Start)) => {
+                        self.connection_refresh_started(message_bus).await;
                     }
+                    Some(IncomingProxyMessage::ConnectionRefresh(ConnectionRefresh::End)) => {}
                 },
 
                 Some(task_update) = self.background_tasks.next() => match task_update {
@@ -535,9 +755,15 @@ impl BackgroundTask for IncomingProxy {
                         }
 
                         self.request_body_txs.retain(|(connection_id, _), _| *connection_id != id.0);
+                        if let Some(recorder) = self.recorder.as_mut() {
+                            recorder.connection_closed(id.0);
+                        }
                     },
 
                     (id, TaskUpdate::Message(msg)) => {
+                        if let (Some(recorder), MessageOut::Raw(bytes)) = (self.recorder.as_mut(), &msg) {
+                            recorder.raw_response_data(id.0, bytes);
+                        }
                         let Some(PortSubscription::Steal(_)) = self.get_subscription(id) else {
                             continue;
                         };
@@ -561,6 +787,7 @@ impl BackgroundTask for IncomingProxy {
                                 }
                             }
                         };
+                        self.record_response(&msg).await;
                         message_bus.send(msg).await;
                     },
                 },
@@ -5,6 +5,7 @@ use std::{
     error::Error,
     io::{self, ErrorKind},
     net::SocketAddr,
+    sync::Arc,
     time::Duration,
 };
 
@@ -24,7 +25,7 @@ use tokio::{
 };
 use tracing::Level;
 
-use super::http::HttpSender;
+use super::{http::HttpSender, rewrite::HttpRewrites};
 use crate::{
     background_tasks::{BackgroundTask, MessageBus},
     proxies::incoming::http::RETRY_ON_RESET_ATTEMPTS,
@@ -131,6 +132,8 @@ pub struct Interceptor {
     peer: SocketAddr,
     /// Version of [`mirrord_protocol`] negotiated with the agent.
     agent_protocol_version: Option<semver::Version>,
+    /// Rewrites of HTTP requests and responses.
+    rewrites: Arc<HttpRewrites>,
 }
 
 impl Interceptor {
@@ -144,11 +147,13 @@ impl Interceptor {
         socket: TcpSocket,
         peer: SocketAddr,
         agent_protocol_version: Option<semver::Version>,
+        rewrites: Arc<HttpRewrites>,
     ) -> Self {
         Self {
             socket,
             peer,
             agent_protocol_version,
+            rewrites,
         }
     }
 }
@@ -192,6 +197,7 @@ impl BackgroundTask for Interceptor {
             sender,
             peer: self.peer,
             agent_protocol_version: self.agent_protocol_version.clone(),
+            rewrites: self.rewrites.clone(),
         };
         let (response, on_upgrade) = http_conn.send(request).await.inspect_err(|fail| {
             tracing::error!(?fail, "Failed getting a filtered http response!")
@@ -235,6 +241,9 @@ struct HttpConnection {
     /// Determines which variant of [`LayerTcpSteal`](mirrord_protocol::tcp::LayerTcpSteal)
     /// we use when sending HTTP responses.
     agent_protocol_version: Option<semver::Version>,
+    /// Applied to the requests before they're sent to the server, and to the responses before
+    /// they're converted to [`HttpResponseFallback`].
+    rewrites: Arc<HttpRewrites>,
 }
 
 impl HttpConnection {
@@ -317,6 +326,8 @@ impl HttpConnection {
             }
 
             Ok(mut res) => {
+                self.rewrites.rewrite_response(res.headers_mut());
+
                 let upgrade = if res.status() == StatusCode::SWITCHING_PROTOCOLS {
                     Some(hyper::upgrade::on(&mut res))
                 } else {
@@ -385,8 +396,14 @@ impl HttpConnection {
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
     async fn send(
         &mut self,
-        request: HttpRequestFallback,
+        mut request: HttpRequestFallback,
     ) -> InterceptorResult<(HttpResponseFallback, Option<OnUpgrade>)> {
+        // Retried streamed requests come back from the [`IncomingProxy`](super::IncomingProxy),
+        // and were already rewritten.
+        if !matches!(request, HttpRequestFallback::Streamed { retries: 1.., .. }) {
+            self.rewrites.rewrite_request(&mut request);
+        }
+
         let min = Duration::from_millis(10);
         let max = Duration::from_millis(250);
 
@@ -688,6 +705,7 @@ mod test {
                     socket,
                     local_destination,
                     Some(mirrord_protocol::VERSION.clone()),
+                    Default::default(),
                 ),
                 (),
                 8,
@@ -771,6 +789,7 @@ mod test {
             socket,
             local_destination,
             Some(mirrord_protocol::VERSION.clone()),
+            Default::default(),
         );
         let sender = tasks.register(interceptor, (), 8);
 
@@ -0,0 +1,1022 @@
+//! Recording of the HTTP traffic handled by the [`IncomingProxy`](super::IncomingProxy), see
+//! [`HttpRecorder`].
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use base64::prelude::*;
+use http_body_util::BodyExt;
+use hyper::{
+    header::{HeaderName, HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING},
+    HeaderMap, Method, StatusCode, Uri, Version,
+};
+use mirrord_protocol::{
+    tcp::{
+        ChunkedRequest, ChunkedResponse, HttpRequest, InternalHttpBody, InternalHttpBodyFrame,
+        InternalHttpRequest, InternalHttpResponse, LayerTcpSteal,
+    },
+    ConnectionId, Port, RequestId,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tokio::sync::{mpsc, oneshot};
+use tracing::Level;
+
+/// Size of the channel between the [`HttpRecorder`] and its writer task. Exchanges recorded when
+/// the channel is full are dropped.
+const WRITER_CHANNEL_SIZE: usize = 1024;
+
+/// How many bytes of a single raw HTTP message we buffer before giving up on recording the
+/// connection.
+const MAX_RAW_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How many bytes of a raw HTTP message head we buffer before giving up on recording the
+/// connection.
+const MAX_RAW_HEAD_SIZE: usize = 64 * 1024;
+
+/// How many headers a raw HTTP message can have.
+const MAX_RAW_HEADERS: usize = 128;
+
+/// Body of a recorded HTTP message.
+///
+/// Serialized as a base64 string, so that binary bodies survive the round trip through JSON.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordedBody(pub Vec<u8>);
+
+impl RecordedBody {
+    /// Appends data from the given frames, trailers are ignored.
+    fn extend<'a, I: IntoIterator<Item = &'a InternalHttpBodyFrame>>(&mut self, frames: I) {
+        for frame in frames {
+            if let InternalHttpBodyFrame::Data(data) = frame {
+                self.0.extend_from_slice(data);
+            }
+        }
+    }
+
+    async fn from_framed(body: &InternalHttpBody) -> Self {
+        match body.clone().collect().await {
+            Ok(collected) => Self(collected.to_bytes().into()),
+            Err(infallible) => match infallible {},
+        }
+    }
+}
+
+impl Serialize for RecordedBody {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordedBody {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(encoded)
+            .map(Self)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A single HTTP request received from the agent, together with the response given by the user
+/// application.
+///
+/// [`HttpRecorder`] writes these as JSON lines, and `mirrord replay` reads them back.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecordedExchange {
+    pub connection_id: ConnectionId,
+    pub request_id: RequestId,
+    /// Remote port the request was sent to.
+    pub port: Port,
+    pub request: InternalHttpRequest<RecordedBody>,
+    pub response: InternalHttpResponse<RecordedBody>,
+}
+
+impl RecordedExchange {
+    /// Reads all exchanges from a file written by [`HttpRecorder`].
+    pub fn read_all(path: &Path) -> io::Result<Vec<Self>> {
+        BufReader::new(File::open(path)?)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|line| serde_json::from_str(&line?).map_err(io::Error::from))
+            .collect()
+    }
+}
+
+/// An exchange that is not yet complete.
+struct PendingExchange {
+    port: Port,
+    request: InternalHttpRequest<RecordedBody>,
+    /// Present when the response head was already sent, but the body is still streamed.
+    response: Option<InternalHttpResponse<RecordedBody>>,
+}
+
+/// Writes every HTTP request/response pair that passes through the
+/// [`IncomingProxy`](super::IncomingProxy) to a file, one [`RecordedExchange`] JSON per line.
+///
+/// HTTP traffic parsed by the agent (stolen with an HTTP filter) is recorded from the protocol
+/// messages. Connections that reach the intproxy as raw TCP data (mirrored, or stolen without an
+/// HTTP filter) are recorded when they carry HTTP/1, see [`RawConnection`].
+///
+/// The file is written in a blocking task (see [`write_exchanges`]), so that the file IO does not
+/// stall the [`IncomingProxy`](super::IncomingProxy). Failures to write are logged and do not
+/// affect the traffic.
+pub struct HttpRecorder {
+    writer: mpsc::Sender<WriterMessage>,
+    pending: HashMap<(ConnectionId, RequestId), PendingExchange>,
+    raw_connections: HashMap<ConnectionId, RawConnection>,
+}
+
+impl HttpRecorder {
+    /// Opens the file at the given `path` for appending recorded exchanges, and starts the writer
+    /// task.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        let (writer, rx) = mpsc::channel(WRITER_CHANNEL_SIZE);
+        tokio::task::spawn_blocking(move || write_exchanges(BufWriter::new(file), rx));
+
+        Ok(Self {
+            writer,
+            pending: Default::default(),
+            raw_connections: Default::default(),
+        })
+    }
+
+    /// Waits until all exchanges recorded so far are written to the file.
+    pub(super) async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.writer.send(WriterMessage::Flush(tx)).await.is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    fn request_started<B>(&mut self, request: &HttpRequest<B>, body: RecordedBody) {
+        let InternalHttpRequest {
+            method,
+            uri,
+            headers,
+            version,
+            ..
+        } = &request.internal_request;
+
+        self.pending.insert(
+            (request.connection_id, request.request_id),
+            PendingExchange {
+                port: request.port,
+                request: InternalHttpRequest {
+                    method: method.clone(),
+                    uri: uri.clone(),
+                    headers: headers.clone(),
+                    version: *version,
+                    body,
+                },
+                response: None,
+            },
+        );
+    }
+
+    /// Records a request with the whole body.
+    pub(super) fn request(&mut self, request: &HttpRequest<Vec<u8>>) {
+        let body = RecordedBody(request.internal_request.body.clone());
+        self.request_started(request, body);
+    }
+
+    /// Records a request with the whole body, received in frames.
+    pub(super) async fn request_framed(&mut self, request: &HttpRequest<InternalHttpBody>) {
+        let body = RecordedBody::from_framed(&request.internal_request.body).await;
+        self.request_started(request, body);
+    }
+
+    /// Records a part of a streamed request.
+    pub(super) fn request_chunked(&mut self, request: &ChunkedRequest) {
+        match request {
+            ChunkedRequest::Start(request) => {
+                let mut body = RecordedBody::default();
+                body.extend(&request.internal_request.body);
+                self.request_started(request, body);
+            }
+            ChunkedRequest::Body(body) => {
+                if let Some(pending) = self.pending.get_mut(&(body.connection_id, body.request_id))
+                {
+                    pending.request.body.extend(&body.frames);
+                }
+            }
+            ChunkedRequest::Error(error) => {
+                self.pending
+                    .remove(&(error.connection_id, error.request_id));
+            }
+        }
+    }
+
+    /// Records a response sent to the agent. Writes the exchange to the file when the response is
+    /// complete.
+    pub(super) async fn response(&mut self, message: &LayerTcpSteal) {
+        match message {
+            LayerTcpSteal::HttpResponse(response) => {
+                let key = (response.connection_id, response.request_id);
+                let body = RecordedBody(response.internal_response.body.clone());
+                self.response_started(key, &response.internal_response, body);
+                self.finish(key);
+            }
+            LayerTcpSteal::HttpResponseFramed(response) => {
+                let key = (response.connection_id, response.request_id);
+                let body = RecordedBody::from_framed(&response.internal_response.body).await;
+                self.response_started(key, &response.internal_response, body);
+                self.finish(key);
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Start(response)) => {
+                let mut body = RecordedBody::default();
+                body.extend(&response.internal_response.body);
+                self.response_started(
+                    (response.connection_id, response.request_id),
+                    &response.internal_response,
+                    body,
+                );
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Body(body)) => {
+                let key = (body.connection_id, body.request_id);
+                if let Some(response) = self
+                    .pending
+                    .get_mut(&key)
+                    .and_then(|pending| pending.response.as_mut())
+                {
+                    response.body.extend(&body.frames);
+                }
+
+                if body.is_last {
+                    self.finish(key);
+                }
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Error(error)) => {
+                self.pending
+                    .remove(&(error.connection_id, error.request_id));
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts recording a connection that reaches the intproxy as raw TCP data, see
+    /// [`HttpRecorder::raw_request_data`] and [`HttpRecorder::raw_response_data`].
+    pub(super) fn raw_connection(&mut self, connection_id: ConnectionId, port: Port) {
+        self.raw_connections
+            .insert(connection_id, RawConnection::new(connection_id, port));
+    }
+
+    /// Records data sent to the user application on a raw connection.
+    pub(super) fn raw_request_data(&mut self, connection_id: ConnectionId, bytes: &[u8]) {
+        let Some(connection) = self.raw_connections.get_mut(&connection_id) else {
+            return;
+        };
+
+        if connection.request_data(bytes).is_err() {
+            tracing::trace!(connection_id, "Stopped recording a raw connection");
+            self.raw_connections.remove(&connection_id);
+        }
+    }
+
+    /// Records data sent by the user application on a raw connection. Writes the exchanges to
+    /// the file when their responses are complete.
+    pub(super) fn raw_response_data(&mut self, connection_id: ConnectionId, bytes: &[u8]) {
+        self.raw_responses(connection_id, bytes, false);
+    }
+
+    fn raw_responses(&mut self, connection_id: ConnectionId, bytes: &[u8], eof: bool) {
+        let Some(connection) = self.raw_connections.get_mut(&connection_id) else {
+            return;
+        };
+
+        let mut exchanges = vec![];
+        let result = connection.response_data(bytes, eof, &mut exchanges);
+        for exchange in exchanges {
+            self.write(exchange);
+        }
+
+        if result.is_err() {
+            tracing::trace!(connection_id, "Stopped recording a raw connection");
+            self.raw_connections.remove(&connection_id);
+        }
+    }
+
+    /// Drops all incomplete exchanges of the given connection.
+    ///
+    /// A raw connection may still have a response with a body that ends with the connection,
+    /// which is written to the file.
+    pub(super) fn connection_closed(&mut self, connection_id: ConnectionId) {
+        self.pending.retain(|(id, _), _| *id != connection_id);
+
+        self.raw_responses(connection_id, &[], true);
+        self.raw_connections.remove(&connection_id);
+    }
+
+    fn response_started<B>(
+        &mut self,
+        key: (ConnectionId, RequestId),
+        head: &InternalHttpResponse<B>,
+        body: RecordedBody,
+    ) {
+        if let Some(pending) = self.pending.get_mut(&key) {
+            pending.response = Some(InternalHttpResponse {
+                status: head.status,
+                version: head.version,
+                headers: head.headers.clone(),
+                body,
+            });
+        }
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    fn finish(&mut self, key: (ConnectionId, RequestId)) {
+        let Some(PendingExchange {
+            port,
+            request,
+            response: Some(response),
+        }) = self.pending.remove(&key)
+        else {
+            return;
+        };
+
+        let exchange = RecordedExchange {
+            connection_id: key.0,
+            request_id: key.1,
+            port,
+            request,
+            response,
+        };
+
+        self.write(exchange);
+    }
+
+    /// Passes the exchange to the writer task, or drops it if the task does not keep up.
+    fn write(&self, exchange: RecordedExchange) {
+        if let Err(error) = self
+            .writer
+            .try_send(WriterMessage::Exchange(Box::new(exchange)))
+        {
+            tracing::warn!(%error, "Dropped a recorded HTTP exchange");
+        }
+    }
+}
+
+/// Messages consumed by the writer task of the [`HttpRecorder`].
+enum WriterMessage {
+    Exchange(Box<RecordedExchange>),
+    /// Flush the file and notify the sender.
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the exchanges received from the [`HttpRecorder`] to the file, until the recorder is
+/// dropped.
+///
+/// Blocking, meant to be run with [`tokio::task::spawn_blocking`]. The file is flushed whenever
+/// there are no more exchanges waiting.
+fn write_exchanges(mut output: BufWriter<File>, mut rx: mpsc::Receiver<WriterMessage>) {
+    while let Some(message) = rx.blocking_recv() {
+        let result = match message {
+            WriterMessage::Exchange(exchange) => serde_json::to_writer(&mut output, &exchange)
+                .map_err(io::Error::from)
+                .and_then(|()| output.write_all(b"\n"))
+                .and_then(|()| {
+                    if rx.is_empty() {
+                        output.flush()
+                    } else {
+                        Ok(())
+                    }
+                }),
+            WriterMessage::Flush(done) => {
+                let result = output.flush();
+                let _ = done.send(());
+                result
+            }
+        };
+
+        if let Err(error) = result {
+            tracing::warn!(%error, "Failed to write a recorded HTTP exchange");
+        }
+    }
+}
+
+/// The rest of a raw connection can't be recorded: it's not HTTP/1, it was upgraded to another
+/// protocol, or a message is too big.
+#[derive(Debug)]
+struct RawHttpError;
+
+/// HTTP/1 traffic of a connection that reaches the intproxy as raw TCP data, parsed from the
+/// bytes sent in both directions.
+///
+/// Responses are matched with the requests in order, and the requests get consecutive
+/// [`RequestId`]s.
+struct RawConnection {
+    connection_id: ConnectionId,
+    port: Port,
+    /// Data sent to the user application.
+    requests: RawStream<InternalHttpRequest<RecordedBody>>,
+    /// Data sent by the user application.
+    responses: RawStream<InternalHttpResponse<RecordedBody>>,
+    /// Parsed requests that wait for their responses.
+    pending: VecDeque<(RequestId, InternalHttpRequest<RecordedBody>)>,
+    next_request_id: RequestId,
+}
+
+impl RawConnection {
+    fn new(connection_id: ConnectionId, port: Port) -> Self {
+        Self {
+            connection_id,
+            port,
+            requests: Default::default(),
+            responses: Default::default(),
+            pending: Default::default(),
+            next_request_id: 0,
+        }
+    }
+
+    fn request_data(&mut self, bytes: &[u8]) -> Result<(), RawHttpError> {
+        self.requests.buf.extend_from_slice(bytes);
+
+        while let Some((mut request, body)) = self.requests.next_message(false, parse_request)? {
+            request.body = body;
+            self.pending.push_back((self.next_request_id, request));
+            self.next_request_id += 1;
+        }
+
+        self.requests.check_size()
+    }
+
+    /// Parses the responses, and adds the completed exchanges to `exchanges`.
+    ///
+    /// With `eof`, the connection is closed, which ends a response body without a length.
+    fn response_data(
+        &mut self,
+        bytes: &[u8],
+        eof: bool,
+        exchanges: &mut Vec<RecordedExchange>,
+    ) -> Result<(), RawHttpError> {
+        self.responses.buf.extend_from_slice(bytes);
+
+        while !self.responses.is_empty() {
+            let Some((_, request)) = self.pending.front() else {
+                // A response without a request.
+                return Err(RawHttpError);
+            };
+            let method = request.method.clone();
+
+            let Some((mut response, body)) = self
+                .responses
+                .next_message(eof, |head| parse_response(head, &method))?
+            else {
+                break;
+            };
+            response.body = body;
+
+            let upgraded = response.status == StatusCode::SWITCHING_PROTOCOLS
+                || (method == Method::CONNECT && response.status.is_success());
+            if response.status.is_informational() && !upgraded {
+                continue;
+            }
+
+            let Some((request_id, request)) = self.pending.pop_front() else {
+                return Err(RawHttpError);
+            };
+            exchanges.push(RecordedExchange {
+                connection_id: self.connection_id,
+                request_id,
+                port: self.port,
+                request,
+                response,
+            });
+
+            if upgraded {
+                return Err(RawHttpError);
+            }
+        }
+
+        self.responses.check_size()
+    }
+}
+
+/// Data sent in one direction of a [`RawConnection`], parsed incrementally into messages of type
+/// `M`.
+///
+/// The parsing progress is kept between the calls to [`RawStream::next_message`], so that every
+/// byte is scanned once, no matter how the data is split.
+struct RawStream<M> {
+    /// Data that is not parsed yet.
+    buf: Vec<u8>,
+    /// Length of the prefix of `buf` that is known not to contain the end of the message head.
+    head_scanned: usize,
+    /// Message with a parsed head, waiting for the rest of its body.
+    current: Option<(M, BodyDecoder, RecordedBody)>,
+}
+
+impl<M> Default for RawStream<M> {
+    fn default() -> Self {
+        Self {
+            buf: Default::default(),
+            head_scanned: 0,
+            current: None,
+        }
+    }
+}
+
+impl<M> RawStream<M> {
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty() && self.current.is_none()
+    }
+
+    /// Returns the next complete message and its body, or [`None`] if more data is needed.
+    ///
+    /// `parse_head` is called once the whole message head is buffered, and once with the
+    /// beginning of the head, so that we quickly give up on connections that are not HTTP/1.
+    ///
+    /// With `eof`, the connection is closed, which ends a body without a length.
+    fn next_message<F>(
+        &mut self,
+        eof: bool,
+        parse_head: F,
+    ) -> Result<Option<(M, RecordedBody)>, RawHttpError>
+    where
+        F: FnOnce(&[u8]) -> Result<Option<(M, BodyLength, usize)>, RawHttpError>,
+    {
+        if self.current.is_none() {
+            // The head ends with an empty line, `\r\n` or just `\n`.
+            let start = self.head_scanned.saturating_sub(2);
+            let head_complete = (start..self.buf.len()).any(|position| {
+                matches!(
+                    self.buf.get(position..),
+                    Some([b'\n', b'\n', ..] | [b'\n', b'\r', b'\n', ..])
+                )
+            });
+
+            let first_look = self.head_scanned == 0;
+            self.head_scanned = self.buf.len();
+            if !head_complete && !first_look {
+                return Ok(None);
+            }
+
+            let Some((message, length, head_len)) = parse_head(&self.buf)? else {
+                return Ok(None);
+            };
+            self.buf.drain(..head_len);
+            self.head_scanned = 0;
+            self.current = Some((message, length.into(), RecordedBody::default()));
+        }
+
+        let Some((_, decoder, body)) = self.current.as_mut() else {
+            return Ok(None);
+        };
+        if !decoder.decode(&mut self.buf, body, eof)? {
+            return Ok(None);
+        }
+
+        Ok(self
+            .current
+            .take()
+            .map(|(message, _, body)| (message, body)))
+    }
+
+    /// Fails if the message being parsed is too big to be buffered.
+    fn check_size(&self) -> Result<(), RawHttpError> {
+        let too_big = match &self.current {
+            None => self.buf.len() > MAX_RAW_HEAD_SIZE,
+            Some((_, _, body)) => body.0.len() + self.buf.len() > MAX_RAW_MESSAGE_SIZE,
+        };
+
+        if too_big {
+            Err(RawHttpError)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Parses the head of an HTTP/1 request from the beginning of `buf`. Returns the request without
+/// the body, how to read the body, and the length of the head in `buf`, or [`None`] if the head is
+/// not complete yet.
+fn parse_request(
+    buf: &[u8],
+) -> Result<Option<(InternalHttpRequest<RecordedBody>, BodyLength, usize)>, RawHttpError> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_RAW_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+    let head_len = match request.parse(buf).map_err(|_| RawHttpError)? {
+        httparse::Status::Complete(head_len) => head_len,
+        httparse::Status::Partial => return Ok(None),
+    };
+
+    let method = request
+        .method
+        .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+        .ok_or(RawHttpError)?;
+    let uri = request
+        .path
+        .and_then(|path| path.parse::<Uri>().ok())
+        .ok_or(RawHttpError)?;
+    let version = http_version(request.version)?;
+    let headers = header_map(request.headers)?;
+
+    let body_length = BodyLength::from_headers(&headers)?.unwrap_or(BodyLength::Fixed(0));
+
+    let request = InternalHttpRequest {
+        method,
+        uri,
+        headers,
+        version,
+        body: Default::default(),
+    };
+
+    Ok(Some((request, body_length, head_len)))
+}
+
+/// Parses the head of an HTTP/1 response to a request with the given `method` from the beginning
+/// of `buf`. Returns the response without the body, how to read the body, and the length of the
+/// head in `buf`, or [`None`] if the head is not complete yet.
+fn parse_response(
+    buf: &[u8],
+    method: &Method,
+) -> Result<Option<(InternalHttpResponse<RecordedBody>, BodyLength, usize)>, RawHttpError> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_RAW_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+    let head_len = match response.parse(buf).map_err(|_| RawHttpError)? {
+        httparse::Status::Complete(head_len) => head_len,
+        httparse::Status::Partial => return Ok(None),
+    };
+
+    let status = response
+        .code
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or(RawHttpError)?;
+    let version = http_version(response.version)?;
+    let headers = header_map(response.headers)?;
+
+    let body_length = if *method == Method::HEAD
+        || status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        BodyLength::Fixed(0)
+    } else {
+        BodyLength::from_headers(&headers)?.unwrap_or(BodyLength::UntilClose)
+    };
+
+    let response = InternalHttpResponse {
+        status,
+        version,
+        headers,
+        body: Default::default(),
+    };
+
+    Ok(Some((response, body_length, head_len)))
+}
+
+fn http_version(minor: Option<u8>) -> Result<Version, RawHttpError> {
+    match minor {
+        Some(0) => Ok(Version::HTTP_10),
+        Some(1) => Ok(Version::HTTP_11),
+        _ => Err(RawHttpError),
+    }
+}
+
+fn header_map(headers: &[httparse::Header]) -> Result<HeaderMap, RawHttpError> {
+    headers
+        .iter()
+        .map(|header| {
+            let name = HeaderName::from_bytes(header.name.as_bytes()).map_err(|_| RawHttpError)?;
+            let value = HeaderValue::from_bytes(header.value).map_err(|_| RawHttpError)?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// How the end of a raw HTTP message body is determined.
+enum BodyLength {
+    Fixed(usize),
+    Chunked,
+    /// The body ends when the connection is closed.
+    UntilClose,
+}
+
+impl BodyLength {
+    /// Reads the length from the `Transfer-Encoding` and `Content-Length` headers, if any of them
+    /// is present.
+    fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, RawHttpError> {
+        let chunked = headers
+            .get_all(TRANSFER_ENCODING)
+            .iter()
+            .last()
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim().to_ascii_lowercase().ends_with("chunked"));
+        if chunked {
+            return Ok(Some(Self::Chunked));
+        }
+
+        headers
+            .get(CONTENT_LENGTH)
+            .map(|value| {
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse().ok())
+                    .map(Self::Fixed)
+                    .ok_or(RawHttpError)
+            })
+            .transpose()
+    }
+}
+
+/// Progress of reading a raw HTTP message body.
+enum BodyDecoder {
+    /// This many bytes of the body are left.
+    Fixed(usize),
+    /// The body ends when the connection is closed.
+    UntilClose,
+    Chunked(ChunkedState),
+}
+
+/// Progress of decoding a body with the chunked transfer coding.
+enum ChunkedState {
+    /// Expecting the size line of the next chunk.
+    Size,
+    /// This many bytes of the current chunk are left.
+    Data(u64),
+    /// Expecting the line break after the chunk data.
+    DataEnd,
+    /// Skipping the trailers, until an empty line.
+    Trailers,
+}
+
+impl From<BodyLength> for BodyDecoder {
+    fn from(length: BodyLength) -> Self {
+        match length {
+            BodyLength::Fixed(len) => Self::Fixed(len),
+            BodyLength::Chunked => Self::Chunked(ChunkedState::Size),
+            BodyLength::UntilClose => Self::UntilClose,
+        }
+    }
+}
+
+impl BodyDecoder {
+    /// Moves the body data from the beginning of `buf` to `body`. Returns whether the body is
+    /// complete.
+    ///
+    /// With `eof`, the connection is closed, which ends [`BodyDecoder::UntilClose`].
+    fn decode(
+        &mut self,
+        buf: &mut Vec<u8>,
+        body: &mut RecordedBody,
+        eof: bool,
+    ) -> Result<bool, RawHttpError> {
+        let mut position = 0;
+        let result = self.decode_at(buf, &mut position, body, eof);
+        buf.drain(..position);
+        result
+    }
+
+    fn decode_at(
+        &mut self,
+        buf: &[u8],
+        position: &mut usize,
+        body: &mut RecordedBody,
+        eof: bool,
+    ) -> Result<bool, RawHttpError> {
+        loop {
+            let rest = buf.get(*position..).unwrap_or_default();
+
+            match self {
+                Self::Fixed(left) => {
+                    let data = rest.get(..*left).unwrap_or(rest);
+                    body.0.extend_from_slice(data);
+                    *position += data.len();
+                    *left -= data.len();
+                    return Ok(*left == 0);
+                }
+                Self::UntilClose => {
+                    body.0.extend_from_slice(rest);
+                    *position += rest.len();
+                    return Ok(eof);
+                }
+                Self::Chunked(ChunkedState::Size) => {
+                    let (size_len, size) =
+                        match httparse::parse_chunk_size(rest).map_err(|_| RawHttpError)? {
+                            httparse::Status::Complete(chunk_size) => chunk_size,
+                            httparse::Status::Partial => return Ok(false),
+                        };
+                    *position += size_len;
+                    *self = if size == 0 {
+                        Self::Chunked(ChunkedState::Trailers)
+                    } else {
+                        Self::Chunked(ChunkedState::Data(size))
+                    };
+                }
+                Self::Chunked(ChunkedState::Data(left)) => {
+                    let len = usize::try_from(*left).unwrap_or(usize::MAX);
+                    let data = rest.get(..len).unwrap_or(rest);
+                    body.0.extend_from_slice(data);
+                    *position += data.len();
+                    *left -= u64::try_from(data.len()).map_err(|_| RawHttpError)?;
+                    if *left > 0 {
+                        return Ok(false);
+                    }
+                    *self = Self::Chunked(ChunkedState::DataEnd);
+                }
+                Self::Chunked(ChunkedState::DataEnd) => match rest.get(..2) {
+                    None => return Ok(false),
+                    Some(b"\r\n") => {
+                        *position += 2;
+                        *self = Self::Chunked(ChunkedState::Size);
+                    }
+                    Some(..) => return Err(RawHttpError),
+                },
+                // Trailers are skipped.
+                Self::Chunked(ChunkedState::Trailers) => {
+                    let Some(line_len) = rest.windows(2).position(|window| window == b"\r\n")
+                    else {
+                        return Ok(false);
+                    };
+                    *position += line_len + 2;
+
+                    if line_len == 0 {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::tcp::{ChunkedHttpBody, HttpResponse};
+
+    use super::*;
+
+    fn request(body: Vec<u8>) -> HttpRequest<Vec<u8>> {
+        HttpRequest {
+            internal_request: InternalHttpRequest {
+                method: Method::POST,
+                uri: "/api/items?id=1".parse().unwrap(),
+                headers: [("x-user", "me")]
+                    .into_iter()
+                    .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+                    .collect(),
+                version: Version::HTTP_11,
+                body,
+            },
+            connection_id: 0,
+            request_id: 1,
+            port: 80,
+        }
+    }
+
+    /// Records one complete exchange and one with a streamed response, and reads them back.
+    #[tokio::test]
+    async fn record_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traffic.jsonl");
+        let mut recorder = HttpRecorder::create(&path).unwrap();
+
+        recorder.request(&request(b"\x00binary".to_vec()));
+        recorder
+            .response(&LayerTcpSteal::HttpResponse(HttpResponse {
+                port: 80,
+                connection_id: 0,
+                request_id: 1,
+                internal_response: InternalHttpResponse {
+                    status: StatusCode::CREATED,
+                    version: Version::HTTP_11,
+                    headers: Default::default(),
+                    body: b"created".to_vec(),
+                },
+            }))
+            .await;
+
+        let mut streamed = request(b"hello".to_vec());
+        streamed.request_id = 2;
+        recorder.request(&streamed);
+        recorder
+            .response(&LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Start(
+                HttpResponse {
+                    port: 80,
+                    connection_id: 0,
+                    request_id: 2,
+                    internal_response: InternalHttpResponse {
+                        status: StatusCode::OK,
+                        version: Version::HTTP_11,
+                        headers: Default::default(),
+                        body: vec![InternalHttpBodyFrame::Data(b"first ".to_vec())],
+                    },
+                },
+            )))
+            .await;
+        recorder.flush().await;
+        assert_eq!(RecordedExchange::read_all(&path).unwrap().len(), 1);
+        recorder
+            .response(&LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Body(
+                ChunkedHttpBody {
+                    frames: vec![InternalHttpBodyFrame::Data(b"second".to_vec())],
+                    is_last: true,
+                    connection_id: 0,
+                    request_id: 2,
+                },
+            )))
+            .await;
+        recorder.flush().await;
+
+        let exchanges = RecordedExchange::read_all(&path).unwrap();
+        let [first, second] = exchanges.as_slice() else {
+            panic!("unexpected number of exchanges: {exchanges:?}");
+        };
+
+        assert_eq!(first.request.method, Method::POST);
+        assert_eq!(first.request.uri, "/api/items?id=1");
+        assert_eq!(first.request.headers.get("x-user").unwrap(), "me");
+        assert_eq!(first.request.body.0, b"\x00binary");
+        assert_eq!(first.response.status, StatusCode::CREATED);
+        assert_eq!(first.response.body.0, b"created");
+
+        assert_eq!(second.request_id, 2);
+        assert_eq!(second.response.body.0, b"first second");
+    }
+
+    /// Records HTTP/1 exchanges from the raw data of a mirrored connection: split and pipelined
+    /// requests, a chunked response, and a response that ends with the connection.
+    #[tokio::test]
+    async fn record_raw_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traffic.jsonl");
+        let mut recorder = HttpRecorder::create(&path).unwrap();
+
+        recorder.raw_connection(3, 8080);
+        recorder.raw_request_data(3, b"POST /items HTTP/1.1\r\nContent-Le");
+        recorder.raw_request_data(
+            3,
+            b"ngth: 5\r\n\r\nhelloGET /items HTTP/1.1\r\nHost: api\r\n\r\n",
+        );
+        recorder.raw_response_data(
+            3,
+            b"HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nab",
+        );
+        recorder.flush().await;
+        assert!(RecordedExchange::read_all(&path).unwrap().is_empty());
+
+        recorder.raw_response_data(3, b"c\r\n0\r\n\r\nHTTP/1.0 200 OK\r\n\r\nall items");
+        recorder.connection_closed(3);
+        recorder.flush().await;
+
+        let exchanges = RecordedExchange::read_all(&path).unwrap();
+        let [first, second] = exchanges.as_slice() else {
+            panic!("unexpected number of exchanges: {exchanges:?}");
+        };
+
+        assert_eq!(
+            (first.connection_id, first.request_id, first.port),
+            (3, 0, 8080)
+        );
+        assert_eq!(first.request.method, Method::POST);
+        assert_eq!(first.request.body.0, b"hello");
+        assert_eq!(first.response.status, StatusCode::CREATED);
+        assert_eq!(first.response.body.0, b"abc");
+
+        assert_eq!(second.request_id, 1);
+        assert_eq!(second.request.headers.get("host").unwrap(), "api");
+        assert_eq!(second.response.version, Version::HTTP_10);
+        assert_eq!(second.response.body.0, b"all items");
+    }
+
+    /// Records HTTP/1 exchanges from raw data that arrives one byte at a time.
+    #[tokio::test]
+    async fn record_raw_connection_byte_by_byte() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traffic.jsonl");
+        let mut recorder = HttpRecorder::create(&path).unwrap();
+
+        recorder.raw_connection(3, 8080);
+        for byte in b"PUT /items HTTP/1.1\nTransfer-Encoding: chunked\n\n5\r\nhello\r\n0\r\n\r\n" {
+            recorder.raw_request_data(3, &[*byte]);
+        }
+        for byte in b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok" {
+            recorder.raw_response_data(3, &[*byte]);
+        }
+        recorder.flush().await;
+
+        let exchanges = RecordedExchange::read_all(&path).unwrap();
+        let [exchange] = exchanges.as_slice() else {
+            panic!("unexpected number of exchanges: {exchanges:?}");
+        };
+        assert_eq!(exchange.request.method, Method::PUT);
+        assert_eq!(exchange.request.body.0, b"hello");
+        assert_eq!(exchange.response.body.0, b"ok");
+    }
+
+    /// Stops recording a raw connection that does not carry HTTP/1.
+    #[tokio::test]
+    async fn raw_connection_not_http() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traffic.jsonl");
+        let mut recorder = HttpRecorder::create(&path).unwrap();
+
+        recorder.raw_connection(3, 5432);
+        recorder.raw_request_data(3, b"\x00\x00\x00\x08\x04\xd2\x16\x2f");
+        assert!(recorder.raw_connections.is_empty());
+    }
+}
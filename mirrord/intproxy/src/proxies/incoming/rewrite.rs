@@ -0,0 +1,269 @@
+//! Rewrites of the stolen HTTP traffic, configured with `feature.network.incoming.http_rewrite`.
+//! See [`HttpRewrites`].
+
+use hyper::{
+    header::{HeaderName, HeaderValue, InvalidHeaderName, InvalidHeaderValue},
+    HeaderMap, Uri,
+};
+use mirrord_config::feature::network::incoming::http_rewrite::{
+    HttpRewriteConfig, HttpRewriteRule,
+};
+use mirrord_protocol::tcp::{HttpRequestFallback, InternalHttpRequest};
+use thiserror::Error;
+
+/// Errors that can occur when preparing [`HttpRewrites`] from the config.
+#[derive(Error, Debug)]
+pub enum HttpRewriteError {
+    #[error("invalid header name `{0}` in HTTP rewrite rule: {1}")]
+    HeaderName(String, InvalidHeaderName),
+    #[error("invalid value of header `{0}` in HTTP rewrite rule: {1}")]
+    HeaderValue(String, InvalidHeaderValue),
+    #[error("HTTP response rewrite rules cannot replace path prefixes")]
+    PathInResponse,
+}
+
+/// Parsed [`HttpRewriteRule`].
+#[derive(Debug)]
+enum Rule {
+    RemoveHeader(HeaderName),
+    SetHeader(HeaderName, HeaderValue),
+    ReplacePathPrefix { prefix: String, with: String },
+}
+
+impl TryFrom<&HttpRewriteRule> for Rule {
+    type Error = HttpRewriteError;
+
+    fn try_from(rule: &HttpRewriteRule) -> Result<Self, Self::Error> {
+        let header_name = |name: &str| {
+            HeaderName::try_from(name)
+                .map_err(|error| HttpRewriteError::HeaderName(name.to_string(), error))
+        };
+
+        match rule {
+            HttpRewriteRule::RemoveHeader { remove_header } => {
+                Ok(Self::RemoveHeader(header_name(remove_header)?))
+            }
+            HttpRewriteRule::SetHeader { set_header, value } => {
+                let value = HeaderValue::try_from(value)
+                    .map_err(|error| HttpRewriteError::HeaderValue(set_header.clone(), error))?;
+                Ok(Self::SetHeader(header_name(set_header)?, value))
+            }
+            HttpRewriteRule::ReplacePathPrefix {
+                replace_path_prefix,
+                with,
+            } => Ok(Self::ReplacePathPrefix {
+                prefix: replace_path_prefix.clone(),
+                with: with.clone(),
+            }),
+        }
+    }
+}
+
+/// Rewrites applied by the [`Interceptor`](super::interceptor::Interceptor)s to the requests
+/// before they're sent to the user application, and to the responses before they're sent back to
+/// the agent.
+#[derive(Default, Debug)]
+pub struct HttpRewrites {
+    request: Vec<Rule>,
+    response: Vec<Rule>,
+}
+
+impl HttpRewrites {
+    /// Parses the rules from the config, failing on invalid header names and values.
+    pub fn new(config: &HttpRewriteConfig) -> Result<Self, HttpRewriteError> {
+        let request = config
+            .request
+            .iter()
+            .map(Rule::try_from)
+            .collect::<Result<_, _>>()?;
+        let response = config
+            .response
+            .iter()
+            .map(Rule::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if response
+            .iter()
+            .any(|rule| matches!(rule, Rule::ReplacePathPrefix { .. }))
+        {
+            return Err(HttpRewriteError::PathInResponse);
+        }
+
+        Ok(Self { request, response })
+    }
+
+    /// Applies the request rules to the given request.
+    pub(super) fn rewrite_request(&self, request: &mut HttpRequestFallback) {
+        if self.request.is_empty() {
+            return;
+        }
+
+        match request {
+            HttpRequestFallback::Framed(request) => {
+                self.rewrite_internal(&mut request.internal_request)
+            }
+            HttpRequestFallback::Fallback(request) => {
+                self.rewrite_internal(&mut request.internal_request)
+            }
+            HttpRequestFallback::Streamed { request, .. } => {
+                self.rewrite_internal(&mut request.internal_request)
+            }
+        }
+    }
+
+    fn rewrite_internal<B>(&self, request: &mut InternalHttpRequest<B>) {
+        for rule in &self.request {
+            match rule {
+                Rule::ReplacePathPrefix { prefix, with } => {
+                    if let Some(uri) = replace_path_prefix(&request.uri, prefix, with) {
+                        request.uri = uri;
+                    }
+                }
+                header_rule => apply_header_rule(header_rule, &mut request.headers),
+            }
+        }
+    }
+
+    /// Applies the response rules to the headers of a response.
+    pub(super) fn rewrite_response(&self, headers: &mut HeaderMap) {
+        for rule in &self.response {
+            apply_header_rule(rule, headers);
+        }
+    }
+}
+
+fn apply_header_rule(rule: &Rule, headers: &mut HeaderMap) {
+    match rule {
+        Rule::RemoveHeader(name) => {
+            headers.remove(name);
+        }
+        Rule::SetHeader(name, value) => {
+            headers.insert(name, value.clone());
+        }
+        Rule::ReplacePathPrefix { .. } => {}
+    }
+}
+
+/// Returns the `uri` with the path `prefix` replaced, or [`None`] if the path does not start with
+/// the prefix.
+///
+/// The prefix matches whole path segments only, so `/api` matches `/api` and `/api/items`, but
+/// not `/apiary`.
+fn replace_path_prefix(uri: &Uri, prefix: &str, with: &str) -> Option<Uri> {
+    let rest = uri
+        .path()
+        .strip_prefix(prefix)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'))?;
+
+    let mut path_and_query = with.trim_end_matches('/').to_string();
+    if !rest.is_empty() && !rest.starts_with('/') {
+        path_and_query.push('/');
+    }
+    path_and_query.push_str(rest);
+    if !path_and_query.starts_with('/') {
+        path_and_query.insert(0, '/');
+    }
+    if let Some(query) = uri.query() {
+        path_and_query.push('?');
+        path_and_query.push_str(query);
+    }
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .inspect_err(|error| {
+                tracing::warn!(%error, %uri, "Failed to replace the prefix of the request path")
+            })
+            .ok()?,
+    );
+
+    Uri::from_parts(parts).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("/api/v1/items?id=1", "/api/v1", "/", "/items?id=1")]
+    #[case("/api/v1", "/api/v1", "/", "/")]
+    #[case("/api/v1/", "/api/v1", "/v2", "/v2/")]
+    #[case("/items", "/", "/api/", "/api/items")]
+    #[case(
+        "http://remote.svc/api/items",
+        "/api",
+        "/v2",
+        "http://remote.svc/v2/items"
+    )]
+    fn path_prefix(
+        #[case] uri: &str,
+        #[case] prefix: &str,
+        #[case] with: &str,
+        #[case] expected: &str,
+    ) {
+        let uri = uri.parse().unwrap();
+        assert_eq!(
+            replace_path_prefix(&uri, prefix, with).unwrap().to_string(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("/health", "/api")]
+    #[case("/apiary", "/api")]
+    #[case("/api/v10/items", "/api/v1")]
+    fn path_without_prefix(#[case] uri: &str, #[case] prefix: &str) {
+        assert!(replace_path_prefix(&uri.parse().unwrap(), prefix, "/v2").is_none());
+    }
+
+    #[test]
+    fn header_rules() {
+        let rewrites = HttpRewrites::new(&HttpRewriteConfig {
+            request: vec![],
+            response: vec![
+                HttpRewriteRule::RemoveHeader {
+                    remove_header: "Server".into(),
+                },
+                HttpRewriteRule::SetHeader {
+                    set_header: "x-debug".into(),
+                    value: "1".into(),
+                },
+            ],
+        })
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("server", "local".parse().unwrap());
+        headers.append("x-debug", "0".parse().unwrap());
+        headers.append("x-debug", "2".parse().unwrap());
+        rewrites.rewrite_response(&mut headers);
+
+        assert!(headers.get("server").is_none());
+        assert_eq!(headers.get_all("x-debug").iter().collect::<Vec<_>>(), ["1"]);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let config = |rule| HttpRewriteConfig {
+            request: vec![],
+            response: vec![rule],
+        };
+
+        assert!(matches!(
+            HttpRewrites::new(&config(HttpRewriteRule::RemoveHeader {
+                remove_header: "bad header".into()
+            })),
+            Err(HttpRewriteError::HeaderName(..))
+        ));
+        assert!(matches!(
+            HttpRewrites::new(&config(HttpRewriteRule::ReplacePathPrefix {
+                replace_path_prefix: "/".into(),
+                with: "/api".into()
+            })),
+            Err(HttpRewriteError::PathInResponse)
+        ));
+    }
+}
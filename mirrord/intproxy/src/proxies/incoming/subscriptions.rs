@@ -276,6 +276,19 @@ impl SubscriptionsManager {
         }
     }
 
+    /// Returns messages that subscribe all ports again, for a new agent connection.
+    ///
+    /// Subscriptions that were already confirmed stay confirmed.
+    pub fn agent_subscriptions(&self) -> impl Iterator<Item = ClientMessage> + '_ {
+        self.subscriptions.values().map(|subscription| {
+            subscription
+                .active_source
+                .request
+                .subscription
+                .agent_subscribe()
+        })
+    }
+
     /// Notifies this struct about layer closing.
     /// Returns messages to be sent to the agent.
     pub fn layer_closed(&mut self, layer_id: LayerId) -> Vec<ClientMessage> {
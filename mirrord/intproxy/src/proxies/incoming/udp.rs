@@ -0,0 +1,869 @@
+//! Handles the incoming UDP traffic part of the `incoming` feature.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use mirrord_intproxy_protocol::{
+    ConnMetadataRequest, ConnMetadataResponse, IncomingResponse, LayerId, MessageId,
+    ProxyToLayerMessage, UdpPortSubscribe, UdpPortUnsubscribe,
+};
+use mirrord_protocol::{
+    udp::{LayerUdp, UdpDatagram, UdpReply},
+    ClientMessage, Port, RemoteResult, ResponseError,
+};
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{self, Receiver, Sender},
+    task::JoinHandle,
+    time::Instant,
+};
+use tracing::Level;
+
+use crate::{
+    main_tasks::{ProxyMessage, ToLayer},
+    remote_resources::RemoteResources,
+};
+
+/// Returns an address that can be used as a destination for datagrams sent to a socket bound to
+/// the given `addr`.
+///
+/// # Exception
+///
+/// If the given `addr` is unspecified, returns localhost.
+fn destination_for(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED) => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), addr.port())
+        }
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED) => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+/// Represents a source of UDP subscription - a `bind` call in the layer.
+#[derive(Debug)]
+struct Source {
+    layer: LayerId,
+    message: MessageId,
+    listening_on: SocketAddr,
+}
+
+/// Socket used to deliver datagrams from a single remote peer to the layer.
+#[derive(Debug)]
+struct Peer {
+    socket: Arc<UdpSocket>,
+    /// Sent to the layer in [`ConnMetadataResponse`].
+    metadata: ConnMetadataResponse,
+    /// When stealing, task that reads the layer's replies from [`Peer::socket`].
+    /// Aborted on drop.
+    reader: Option<JoinHandle<()>>,
+    /// When we last delivered a datagram from this peer, or sent a reply to it.
+    last_active: Instant,
+}
+
+impl Peer {
+    /// Forwards all replies received on the `socket` to the `reply_tx`.
+    async fn read_replies(
+        socket: Arc<UdpSocket>,
+        remote_source: SocketAddr,
+        port: Port,
+        reply_tx: Sender<UdpReply>,
+    ) {
+        let mut buffer = vec![0; u16::MAX as usize];
+
+        loop {
+            let received = match socket.recv(&mut buffer).await {
+                Ok(received) => received,
+                Err(error) => {
+                    tracing::debug!(%error, %remote_source, "Failed to receive a UDP reply");
+                    break;
+                }
+            };
+
+            let reply = UdpReply {
+                remote_address: remote_source.ip(),
+                remote_port: remote_source.port(),
+                port,
+                bytes: buffer.get(..received).unwrap_or_default().to_vec(),
+            };
+
+            if reply_tx.send(reply).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            reader.abort();
+        }
+    }
+}
+
+/// Represents a UDP port subscription in the agent.
+#[derive(Debug)]
+struct Subscription {
+    /// All sources of this subscription, the last one is active.
+    sources: Vec<Source>,
+    /// Whether this subscription is confirmed.
+    confirmed: bool,
+    /// Whether the datagrams are stolen. If so, layer's replies are sent back to the peers.
+    steal: bool,
+    /// Sockets delivering datagrams to the active source, by the address of the remote peer.
+    ///
+    /// Each peer gets its own socket, so that the layer can tell the peers apart.
+    peers: HashMap<SocketAddr, Peer>,
+}
+
+impl Subscription {
+    fn active_source(&self) -> Option<&Source> {
+        self.sources.last()
+    }
+
+    /// Removes the [`Peer`]s that were not active for at least `idle`, returns the addresses of
+    /// their sockets.
+    fn evict_peers(&mut self, idle: Duration, now: Instant) -> Vec<SocketAddr> {
+        let mut evicted = vec![];
+        self.peers.retain(|_, peer| {
+            if now.duration_since(peer.last_active) < idle {
+                return true;
+            }
+
+            evicted.extend(peer.socket.local_addr().ok());
+            false
+        });
+
+        evicted
+    }
+
+    /// Makes room for a new [`Peer`] when the subscription has [`UdpSubscriptions::MAX_PEERS`],
+    /// by removing the least recently active one. Returns the address of its socket.
+    ///
+    /// Only a peer that was not active for [`UdpSubscriptions::MIN_EVICTION_IDLE`] can be
+    /// removed, otherwise returns [`None`] and there's no room.
+    fn evict_least_active(&mut self, now: Instant) -> Option<SocketAddr> {
+        let (remote_source, _) = self
+            .peers
+            .iter()
+            .min_by_key(|(_, peer)| peer.last_active)
+            .filter(|(_, peer)| {
+                now.duration_since(peer.last_active) >= UdpSubscriptions::MIN_EVICTION_IDLE
+            })?;
+        let remote_source = *remote_source;
+
+        self.peers
+            .remove(&remote_source)
+            .and_then(|peer| peer.socket.local_addr().ok())
+    }
+}
+
+/// Manages UDP port subscriptions across all connected layers and delivers mirrored or stolen
+/// datagrams.
+///
+/// Like [`SubscriptionsManager`](super::subscriptions::SubscriptionsManager), subsequent
+/// subscriptions of the same port take precedence over previous ones.
+///
+/// Datagrams are delivered to the layer from a separate socket for each remote peer. The layer can
+/// retrieve the original peer address with a [`ConnMetadataRequest`]. When stealing, whatever the
+/// layer sends to this socket is sent back to the remote peer.
+///
+/// The sockets (and the tasks reading the replies) are closed after
+/// [`UdpSubscriptions::PEER_IDLE_TIMEOUT`], and a subscription has at most
+/// [`UdpSubscriptions::MAX_PEERS`] of them. The layer caches the peer addresses for a shorter
+/// time, so it doesn't use the address of a closed socket, which could be bound again for another
+/// peer.
+pub struct UdpSubscriptions {
+    remote_ports: RemoteResources<(Port, SocketAddr)>,
+    subscriptions: HashMap<Port, Subscription>,
+    /// Addresses of the sockets in [`Subscription::peers`], mapped to the remote peer address.
+    peer_addresses: HashMap<SocketAddr, (Port, SocketAddr)>,
+    /// Passed to the [`Peer`] tasks reading the layer's replies.
+    reply_tx: Sender<UdpReply>,
+    /// Receives the layer's replies from all [`Peer`]s.
+    reply_rx: Receiver<UdpReply>,
+    /// When to evict the idle [`Peer`]s, see [`UdpSubscriptions::next_reply`].
+    next_eviction: Instant,
+    max_peers: usize,
+}
+
+impl Default for UdpSubscriptions {
+    fn default() -> Self {
+        let (reply_tx, reply_rx) = mpsc::channel(Self::REPLY_CHANNEL_SIZE);
+
+        Self {
+            remote_ports: Default::default(),
+            subscriptions: Default::default(),
+            peer_addresses: Default::default(),
+            reply_tx,
+            reply_rx,
+            next_eviction: Instant::now() + Self::PEER_IDLE_TIMEOUT,
+            max_peers: Self::MAX_PEERS,
+        }
+    }
+}
+
+impl UdpSubscriptions {
+    /// Capacity of the channel for the layer's replies to stolen datagrams.
+    const REPLY_CHANNEL_SIZE: usize = 512;
+
+    /// [`Peer`]s are closed when they were not active for this long.
+    const PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Maximum number of [`Peer`]s in a single subscription.
+    const MAX_PEERS: usize = 256;
+
+    /// To make room for a new [`Peer`], we close the least recently active one only if it was not
+    /// active for this long. Longer than the time the layer caches the peer addresses.
+    const MIN_EVICTION_IDLE: Duration = Duration::from_secs(30);
+
+    /// Registers a new port subscription in this struct.
+    /// Optionally returns a message to be sent.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub fn layer_subscribed(
+        &mut self,
+        layer_id: LayerId,
+        message_id: MessageId,
+        request: UdpPortSubscribe,
+    ) -> Option<ProxyMessage> {
+        self.remote_ports
+            .add(layer_id, (request.port, request.listening_on));
+
+        let source = Source {
+            layer: layer_id,
+            message: message_id,
+            listening_on: request.listening_on,
+        };
+
+        match self.subscriptions.entry(request.port) {
+            Entry::Occupied(mut e) => {
+                let subscription = e.get_mut();
+                subscription.sources.push(source);
+
+                subscription.confirmed.then(|| {
+                    ProxyMessage::ToLayer(ToLayer {
+                        message_id,
+                        layer_id,
+                        message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(
+                            Ok(()),
+                        )),
+                    })
+                })
+            }
+            Entry::Vacant(e) => {
+                e.insert(Subscription {
+                    sources: vec![source],
+                    confirmed: false,
+                    steal: request.steal,
+                    peers: Default::default(),
+                });
+
+                let message = if request.steal {
+                    LayerUdp::StealPortSubscribe(request.port)
+                } else {
+                    LayerUdp::PortSubscribe(request.port)
+                };
+
+                Some(ProxyMessage::ToAgent(ClientMessage::Udp(message)))
+            }
+        }
+    }
+
+    /// Removes the given source from the subscription of the given port.
+    /// If this source is the last one, returns a message to be sent to the agent.
+    fn remove_source(&mut self, port: Port, listening_on: SocketAddr) -> Option<ClientMessage> {
+        let Entry::Occupied(mut e) = self.subscriptions.entry(port) else {
+            return None;
+        };
+
+        let subscription = e.get_mut();
+        let position = subscription
+            .sources
+            .iter()
+            .rposition(|source| source.listening_on == listening_on)?;
+        subscription.sources.remove(position);
+
+        if !subscription.sources.is_empty() {
+            return None;
+        }
+
+        let subscription = e.remove();
+        for peer in subscription.peers.values() {
+            if let Ok(address) = peer.socket.local_addr() {
+                self.peer_addresses.remove(&address);
+            }
+        }
+
+        let message = if subscription.steal {
+            LayerUdp::StealPortUnsubscribe(port)
+        } else {
+            LayerUdp::PortUnsubscribe(port)
+        };
+
+        Some(ClientMessage::Udp(message))
+    }
+
+    /// Unregisters a subscription from this struct.
+    /// Optionally returns a message to be sent to the agent.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub fn layer_unsubscribed(
+        &mut self,
+        layer_id: LayerId,
+        request: UdpPortUnsubscribe,
+    ) -> Option<ClientMessage> {
+        let closed_in_all_forks = self
+            .remote_ports
+            .remove(layer_id, (request.port, request.listening_on));
+        if !closed_in_all_forks {
+            return None;
+        }
+
+        self.remove_source(request.port, request.listening_on)
+    }
+
+    /// Notifies this struct that the layer with `child` id was forked from the layer with `parent`
+    /// id.
+    pub fn layer_forked(&mut self, parent: LayerId, child: LayerId) {
+        self.remote_ports.clone_all(parent, child);
+    }
+
+    /// Returns messages that subscribe all ports again, for a new agent connection.
+    ///
+    /// Subscriptions that were already confirmed stay confirmed.
+    pub fn agent_subscriptions(&self) -> impl Iterator<Item = ClientMessage> + '_ {
+        self.subscriptions.iter().map(|(port, subscription)| {
+            let message = if subscription.steal {
+                LayerUdp::StealPortSubscribe(*port)
+            } else {
+                LayerUdp::PortSubscribe(*port)
+            };
+
+            ClientMessage::Udp(message)
+        })
+    }
+
+    /// Notifies this struct that the layer with the given id was closed.
+    /// Returns messages to be sent to the agent.
+    pub fn layer_closed(&mut self, layer_id: LayerId) -> Vec<ClientMessage> {
+        let to_close = self.remote_ports.remove_all(layer_id).collect::<Vec<_>>();
+
+        to_close
+            .into_iter()
+            .filter_map(|(port, listening_on)| self.remove_source(port, listening_on))
+            .collect()
+    }
+
+    /// Notifies this struct about agent's response to [`LayerUdp::PortSubscribe`] or
+    /// [`LayerUdp::StealPortSubscribe`]. Returns messages to be sent to the layers.
+    ///
+    /// [`ResponseError::PortAlreadyStolen`] rejects only the subscription of the given port. Any
+    /// other error means that the agent is not able to handle UDP traffic at all, so it rejects
+    /// all pending subscriptions.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub fn agent_responded(&mut self, result: RemoteResult<Port>) -> Vec<ToLayer> {
+        let response = |source: &Source, result: RemoteResult<()>| ToLayer {
+            message_id: source.message,
+            layer_id: source.layer,
+            message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(result)),
+        };
+
+        match result {
+            Ok(port) => {
+                let Some(subscription) = self
+                    .subscriptions
+                    .get_mut(&port)
+                    .filter(|subscription| !subscription.confirmed)
+                else {
+                    return vec![];
+                };
+
+                subscription.confirmed = true;
+                subscription
+                    .sources
+                    .iter()
+                    .map(|source| response(source, Ok(())))
+                    .collect()
+            }
+
+            Err(error) => {
+                let rejected_ports = self
+                    .subscriptions
+                    .iter()
+                    .filter(|(port, subscription)| match &error {
+                        ResponseError::PortAlreadyStolen(stolen) => {
+                            *port == stolen && !subscription.confirmed
+                        }
+                        _ => !subscription.confirmed,
+                    })
+                    .map(|(port, _)| *port)
+                    .collect::<Vec<_>>();
+
+                let mut responses = vec![];
+                for port in rejected_ports {
+                    let Some(subscription) = self.subscriptions.remove(&port) else {
+                        continue;
+                    };
+
+                    for source in subscription.sources {
+                        self.remote_ports
+                            .remove(source.layer, (port, source.listening_on));
+                        responses.push(response(&source, Err(error.clone())));
+                    }
+                }
+
+                responses
+            }
+        }
+    }
+
+    /// Sends the given datagram to the active source of the subscription.
+    ///
+    /// When stealing, replies sent by the layer to the delivering socket are later returned from
+    /// [`UdpSubscriptions::next_reply`].
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    pub async fn handle_datagram(&mut self, datagram: UdpDatagram) -> io::Result<()> {
+        let Some(subscription) = self.subscriptions.get_mut(&datagram.destination_port) else {
+            tracing::trace!(
+                "received a datagram for port {} that is no longer mirrored",
+                datagram.destination_port
+            );
+            return Ok(());
+        };
+
+        let Some(destination) = subscription
+            .active_source()
+            .map(|source| destination_for(source.listening_on))
+        else {
+            return Ok(());
+        };
+
+        let remote_source = SocketAddr::new(datagram.remote_address, datagram.source_port);
+        let now = Instant::now();
+
+        if !subscription.peers.contains_key(&remote_source)
+            && subscription.peers.len() >= self.max_peers
+        {
+            let Some(evicted) = subscription.evict_least_active(now) else {
+                tracing::warn!(
+                    %remote_source,
+                    port = datagram.destination_port,
+                    "Too many active UDP peers, dropping a datagram"
+                );
+                return Ok(());
+            };
+            self.peer_addresses.remove(&evicted);
+        }
+
+        let peer = match subscription.peers.entry(remote_source) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let bind_address = SocketAddr::new(destination.ip(), 0);
+                let socket = Arc::new(UdpSocket::bind(bind_address).await?);
+                self.peer_addresses.insert(
+                    socket.local_addr()?,
+                    (datagram.destination_port, remote_source),
+                );
+
+                let reader = subscription.steal.then(|| {
+                    tokio::spawn(Peer::read_replies(
+                        socket.clone(),
+                        remote_source,
+                        datagram.destination_port,
+                        self.reply_tx.clone(),
+                    ))
+                });
+
+                e.insert(Peer {
+                    socket,
+                    metadata: ConnMetadataResponse {
+                        remote_source,
+                        local_address: datagram.local_address,
+                    },
+                    reader,
+                    last_active: now,
+                })
+            }
+        };
+        peer.last_active = now;
+
+        if let Err(error) = peer.socket.send_to(&datagram.bytes, destination).await {
+            // Layer's socket might have been closed in the meantime.
+            tracing::debug!(%error, %destination, "Failed to deliver a UDP datagram");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the next reply that the layer sent to a stolen datagram, as a message to be sent to
+    /// the agent.
+    ///
+    /// Also periodically closes the [`Peer`]s that were idle for
+    /// [`UdpSubscriptions::PEER_IDLE_TIMEOUT`], so this should be polled all the time.
+    ///
+    /// Cancel safe.
+    pub async fn next_reply(&mut self) -> ClientMessage {
+        loop {
+            tokio::select! {
+                reply = self.reply_rx.recv() => {
+                    // We hold a sender in `self.reply_tx`, so the channel is never closed.
+                    let reply = reply.expect("reply channel closed unexpectedly");
+
+                    if let Some(peer) = self.subscriptions.get_mut(&reply.port).and_then(|subscription| {
+                        subscription
+                            .peers
+                            .get_mut(&SocketAddr::new(reply.remote_address, reply.remote_port))
+                    }) {
+                        peer.last_active = Instant::now();
+                    }
+
+                    break ClientMessage::Udp(LayerUdp::StealReply(reply));
+                }
+
+                _ = tokio::time::sleep_until(self.next_eviction) => self.evict_idle_peers(),
+            }
+        }
+    }
+
+    /// Closes the [`Peer`]s that were idle for [`UdpSubscriptions::PEER_IDLE_TIMEOUT`].
+    fn evict_idle_peers(&mut self) {
+        let now = Instant::now();
+
+        for subscription in self.subscriptions.values_mut() {
+            for address in subscription.evict_peers(Self::PEER_IDLE_TIMEOUT, now) {
+                self.peer_addresses.remove(&address);
+            }
+        }
+
+        self.next_eviction = now + Self::PEER_IDLE_TIMEOUT / 2;
+    }
+
+    /// Returns the original peer of the datagram received by the layer, if the datagram was
+    /// delivered by this struct.
+    pub fn metadata(&self, request: &ConnMetadataRequest) -> Option<ConnMetadataResponse> {
+        let (port, remote_source) = self.peer_addresses.get(&request.peer_address)?;
+
+        self.subscriptions
+            .get(port)?
+            .peers
+            .get(remote_source)
+            .map(|peer| peer.metadata.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn datagram(port: Port, bytes: &[u8]) -> UdpDatagram {
+        UdpDatagram {
+            remote_address: "1.2.3.4".parse().unwrap(),
+            source_port: 3133,
+            local_address: "10.0.0.1".parse().unwrap(),
+            destination_port: port,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    /// Verifies that a confirmed subscription delivers datagrams to the layer socket, and that the
+    /// original peer can be retrieved with [`ConnMetadataRequest`].
+    #[tokio::test]
+    async fn datagram_delivered_with_metadata() {
+        let layer_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let listening_on = layer_socket.local_addr().unwrap();
+        let mut subscriptions = UdpSubscriptions::default();
+
+        let message = subscriptions.layer_subscribed(
+            LayerId(0),
+            1,
+            UdpPortSubscribe {
+                listening_on,
+                port: 8125,
+                steal: false,
+            },
+        );
+        assert!(matches!(
+            message,
+            Some(ProxyMessage::ToAgent(ClientMessage::Udp(
+                LayerUdp::PortSubscribe(8125)
+            )))
+        ));
+
+        assert_eq!(
+            subscriptions.agent_responded(Ok(8125)),
+            vec![ToLayer {
+                message_id: 1,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(Ok(()))),
+            }]
+        );
+
+        subscriptions
+            .handle_datagram(datagram(8125, b"metric:1|c"))
+            .await
+            .unwrap();
+
+        let mut buffer = [0; 64];
+        let (received, peer_address) = layer_socket.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(buffer.get(..received), Some(b"metric:1|c".as_slice()));
+
+        let metadata = subscriptions
+            .metadata(&ConnMetadataRequest {
+                listener_address: listening_on,
+                peer_address,
+            })
+            .unwrap();
+        assert_eq!(metadata.remote_source, "1.2.3.4:3133".parse().unwrap());
+        assert_eq!(
+            metadata.local_address,
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+
+        let message = subscriptions.layer_unsubscribed(
+            LayerId(0),
+            UdpPortUnsubscribe {
+                port: 8125,
+                listening_on,
+            },
+        );
+        assert_eq!(
+            message,
+            Some(ClientMessage::Udp(LayerUdp::PortUnsubscribe(8125)))
+        );
+        assert!(subscriptions
+            .metadata(&ConnMetadataRequest {
+                listener_address: listening_on,
+                peer_address,
+            })
+            .is_none());
+    }
+
+    /// Verifies that replies to stolen datagrams are sent back to the original peer.
+    #[tokio::test]
+    async fn reply_to_stolen_datagram() {
+        let layer_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let listening_on = layer_socket.local_addr().unwrap();
+        let mut subscriptions = UdpSubscriptions::default();
+
+        let message = subscriptions.layer_subscribed(
+            LayerId(0),
+            1,
+            UdpPortSubscribe {
+                listening_on,
+                port: 53,
+                steal: true,
+            },
+        );
+        assert!(matches!(
+            message,
+            Some(ProxyMessage::ToAgent(ClientMessage::Udp(
+                LayerUdp::StealPortSubscribe(53)
+            )))
+        ));
+        subscriptions.agent_responded(Ok(53));
+
+        subscriptions
+            .handle_datagram(datagram(53, b"query"))
+            .await
+            .unwrap();
+
+        let mut buffer = [0; 64];
+        let (received, peer_address) = layer_socket.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(buffer.get(..received), Some(b"query".as_slice()));
+
+        layer_socket
+            .send_to(b"response", peer_address)
+            .await
+            .unwrap();
+        assert_eq!(
+            subscriptions.next_reply().await,
+            ClientMessage::Udp(LayerUdp::StealReply(UdpReply {
+                remote_address: "1.2.3.4".parse().unwrap(),
+                remote_port: 3133,
+                port: 53,
+                bytes: b"response".to_vec(),
+            }))
+        );
+
+        assert_eq!(
+            subscriptions.layer_closed(LayerId(0)),
+            vec![ClientMessage::Udp(LayerUdp::StealPortUnsubscribe(53))]
+        );
+    }
+
+    /// Verifies that idle peers are closed, and that a subscription has a limited number of peers.
+    #[tokio::test]
+    async fn peers_are_evicted() {
+        let layer_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let listening_on = layer_socket.local_addr().unwrap();
+        let mut subscriptions = UdpSubscriptions {
+            max_peers: 2,
+            ..Default::default()
+        };
+
+        subscriptions.layer_subscribed(
+            LayerId(0),
+            1,
+            UdpPortSubscribe {
+                listening_on,
+                port: 53,
+                steal: true,
+            },
+        );
+        subscriptions.agent_responded(Ok(53));
+
+        let idle_for = |subscriptions: &mut UdpSubscriptions, source_port, idle| {
+            let peer = subscriptions
+                .subscriptions
+                .get_mut(&53)
+                .unwrap()
+                .peers
+                .get_mut(&SocketAddr::new("1.2.3.4".parse().unwrap(), source_port))
+                .unwrap();
+            peer.last_active = Instant::now().checked_sub(idle).unwrap();
+        };
+        let datagram_from = |source_port| UdpDatagram {
+            source_port,
+            ..datagram(53, b"query")
+        };
+
+        subscriptions
+            .handle_datagram(datagram_from(1))
+            .await
+            .unwrap();
+        subscriptions
+            .handle_datagram(datagram_from(2))
+            .await
+            .unwrap();
+        idle_for(&mut subscriptions, 1, UdpSubscriptions::MIN_EVICTION_IDLE);
+
+        // The first peer is idle for long enough to make room for the third one.
+        subscriptions
+            .handle_datagram(datagram_from(3))
+            .await
+            .unwrap();
+        // No room for the fourth one.
+        subscriptions
+            .handle_datagram(datagram_from(4))
+            .await
+            .unwrap();
+
+        let mut buffer = [0; 64];
+        let mut peers = vec![];
+        for _ in 0..3 {
+            let (_, peer_address) = layer_socket.recv_from(&mut buffer).await.unwrap();
+            peers.push(peer_address);
+        }
+        let remote_ports = |subscriptions: &UdpSubscriptions| {
+            peers
+                .iter()
+                .map(|peer_address| {
+                    subscriptions
+                        .metadata(&ConnMetadataRequest {
+                            listener_address: listening_on,
+                            peer_address: *peer_address,
+                        })
+                        .map(|metadata| metadata.remote_source.port())
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(remote_ports(&subscriptions), [None, Some(2), Some(3)]);
+
+        // The task reading the replies sent to the evicted peer is gone.
+        layer_socket
+            .send_to(b"response", peers.first().unwrap())
+            .await
+            .unwrap();
+        assert!(tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            subscriptions.next_reply()
+        )
+        .await
+        .is_err());
+
+        // Replies keep the peer active.
+        idle_for(&mut subscriptions, 2, UdpSubscriptions::PEER_IDLE_TIMEOUT);
+        idle_for(&mut subscriptions, 3, UdpSubscriptions::PEER_IDLE_TIMEOUT);
+        layer_socket
+            .send_to(b"response", peers.last().unwrap())
+            .await
+            .unwrap();
+        subscriptions.next_reply().await;
+
+        subscriptions.evict_idle_peers();
+        assert_eq!(remote_ports(&subscriptions), [None, None, Some(3)]);
+    }
+
+    /// Verifies that [`ResponseError::PortAlreadyStolen`] rejects only the subscription of the
+    /// given port.
+    #[test]
+    fn port_already_stolen() {
+        let mut subscriptions = UdpSubscriptions::default();
+
+        for (message_id, port) in [(1, 53), (2, 514)] {
+            subscriptions.layer_subscribed(
+                LayerId(0),
+                message_id,
+                UdpPortSubscribe {
+                    listening_on: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port),
+                    port,
+                    steal: true,
+                },
+            );
+        }
+
+        assert_eq!(
+            subscriptions.agent_responded(Err(ResponseError::PortAlreadyStolen(53))),
+            vec![ToLayer {
+                message_id: 1,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(Err(
+                    ResponseError::PortAlreadyStolen(53)
+                ))),
+            }]
+        );
+        assert_eq!(subscriptions.agent_responded(Ok(514)).len(), 1);
+    }
+
+    /// Verifies that an agent error rejects pending subscriptions.
+    #[test]
+    fn agent_rejected() {
+        let mut subscriptions = UdpSubscriptions::default();
+        let listening_on = "127.0.0.1:8125".parse().unwrap();
+
+        subscriptions.layer_subscribed(
+            LayerId(0),
+            1,
+            UdpPortSubscribe {
+                listening_on,
+                port: 8125,
+                steal: false,
+            },
+        );
+
+        assert_eq!(
+            subscriptions.agent_responded(Err(ResponseError::NotImplemented)),
+            vec![ToLayer {
+                message_id: 1,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::UdpPortSubscribe(Err(
+                    ResponseError::NotImplemented
+                ))),
+            }]
+        );
+
+        // Nothing left to unsubscribe in the agent.
+        assert!(subscriptions.layer_closed(LayerId(0)).is_empty());
+        assert_eq!(
+            subscriptions.agent_responded(Ok(8125)),
+            Vec::<ToLayer>::new()
+        );
+    }
+}
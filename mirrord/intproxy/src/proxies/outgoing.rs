@@ -1,29 +1,43 @@
 //! Handles the logic of the `outgoing` feature.
 
-use std::{collections::HashMap, fmt, io};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
 use mirrord_intproxy_protocol::{
     LayerId, MessageId, NetProtocol, OutgoingConnectRequest, OutgoingConnectResponse,
     ProxyToLayerMessage,
 };
+use mirrord_operator::client::policy::ClientPolicy;
 use mirrord_protocol::{
-    outgoing::{tcp::DaemonTcpOutgoing, udp::DaemonUdpOutgoing, DaemonConnect, DaemonRead},
-    ConnectionId, DaemonMessage, RemoteResult, ResponseError,
+    outgoing::{
+        tcp::DaemonTcpOutgoing, udp::DaemonUdpOutgoing, DaemonConnect, DaemonRead, SocketAddress,
+    },
+    ClientMessage, ConnectionId, DaemonMessage, RemoteResult, ResponseError,
 };
 use thiserror::Error;
 use tracing::Level;
 
-use self::interceptor::Interceptor;
+use self::{
+    faults::{ConnectionFaults, OutgoingFaults},
+    interceptor::Interceptor,
+    mock::{MockInterceptor, MockInterceptorMessage, MockInterceptorUpdate, OutgoingMocks},
+};
 use crate::{
     background_tasks::{BackgroundTask, BackgroundTasks, MessageBus, TaskSender, TaskUpdate},
     error::UnexpectedAgentMessage,
-    main_tasks::ToLayer,
+    main_tasks::{ConnectionRefresh, ToLayer},
     proxies::outgoing::net_protocol_ext::NetProtocolExt,
     request_queue::RequestQueue,
     ProxyMessage,
 };
 
+pub mod faults;
 mod interceptor;
+pub mod mock;
 mod net_protocol_ext;
 
 /// Errors that can occur when handling the `outgoing` feature.
@@ -63,6 +77,33 @@ impl fmt::Display for InterceptorId {
     }
 }
 
+/// Id of a single [`MockInterceptor`] task.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MockId(u64);
+
+/// State of a connection intercepted by a [`MockInterceptor`].
+struct MockConnection {
+    tx: TaskSender<MockInterceptor>,
+    remote_address: SocketAddress,
+    /// Id of the layer's [`OutgoingConnectRequest`], reused when the connection is made through
+    /// the agent.
+    message_id: MessageId,
+    layer_id: LayerId,
+    /// Set when the connection is made through the agent.
+    connection_id: Option<ConnectionId>,
+}
+
+/// Connection request sent to the agent.
+#[derive(Clone, Debug)]
+struct ConnectRequest {
+    message: ClientMessage,
+    /// Set when the request was made by a [`MockInterceptor`], after the layer's
+    /// [`OutgoingConnectRequest`] was already answered.
+    mock: Option<MockId>,
+    /// Faults to inject into the connection once it's made.
+    faults: ConnectionFaults,
+}
+
 /// Handles logic and state of the `outgoing` feature.
 /// Run as a [`BackgroundTask`].
 ///
@@ -78,24 +119,67 @@ impl fmt::Display for InterceptorId {
 /// 6. The proxy passes the data between the agent and the [`Interceptor`] task.
 /// 7. If the layer closes the connection, the [`Interceptor`] exits and the proxy notifies the
 ///    agent. If the agent closes the connection, the proxy shuts down the [`Interceptor`].
+///
+/// When the agent connection is replaced ([`ConnectionRefresh`]), all [`Interceptor`]s are shut
+/// down, as their connections exist only in the previous agent.
+///
+/// # Mocks
+///
+/// TCP connections to addresses that might be mocked ([`OutgoingMocks::intercepts`]) are not sent
+/// to the agent right away. Instead, the proxy answers the layer immediately and starts a
+/// [`MockInterceptor`] task, which serves the mocked responses. The connection is made through the
+/// agent only when the [`MockInterceptor`] gets a request that doesn't match any mock.
+///
+/// # Faults
+///
+/// Before a connection request is sent to the agent, [`OutgoingFaults`] decide whether it should
+/// fail right away, and which faults the [`Interceptor`] should inject into the connection.
+/// Names in the [`OutgoingFaults`] filters are resolved with the remote DNS when the proxy starts
+/// ([`ProxyMessage::ResolveName`]), and the connection requests wait until that's done.
+/// The same goes for the host names of the [`OutgoingMocks`].
 #[derive(Default)]
 pub struct OutgoingProxy {
     /// For [`OutgoingConnectRequest`]s related to [`NetProtocol::Datagrams`].
-    datagrams_reqs: RequestQueue,
+    datagrams_reqs: RequestQueue<ConnectRequest>,
     /// For [`OutgoingConnectRequest`]s related to [`NetProtocol::Stream`].
-    stream_reqs: RequestQueue,
+    stream_reqs: RequestQueue<ConnectRequest>,
     /// [`TaskSender`]s for active [`Interceptor`] tasks.
     txs: HashMap<InterceptorId, TaskSender<Interceptor>>,
     /// For managing [`Interceptor`] tasks.
     background_tasks: BackgroundTasks<InterceptorId, Vec<u8>, io::Error>,
+    /// Used to reject connections to destinations blocked by the mirrord policies.
+    policy: ClientPolicy,
+    /// From `feature.network.outgoing.mock`.
+    mocks: Arc<OutgoingMocks>,
+    /// Active [`MockInterceptor`] tasks.
+    mock_connections: HashMap<MockId, MockConnection>,
+    /// For [`MockInterceptor`]s whose connections were made through the agent.
+    mock_ids: HashMap<ConnectionId, MockId>,
+    /// For managing [`MockInterceptor`] tasks.
+    mock_tasks: BackgroundTasks<MockId, MockInterceptorUpdate, io::Error>,
+    next_mock_id: u64,
+    /// From `feature.network.outgoing.faults`.
+    faults: OutgoingFaults,
+    /// Connection requests waiting for the [`OutgoingFaults::names`] and [`OutgoingMocks::names`]
+    /// to be resolved.
+    waiting_for_names: Vec<(MessageId, LayerId, OutgoingConnectRequest)>,
 }
 
 impl OutgoingProxy {
     /// Used when registering new [`Interceptor`] tasks in the [`BackgroundTasks`] struct.
     const CHANNEL_SIZE: usize = 512;
 
+    pub fn new(policy: ClientPolicy, mocks: OutgoingMocks, faults: OutgoingFaults) -> Self {
+        Self {
+            policy,
+            mocks: Arc::new(mocks),
+            faults,
+            ..Default::default()
+        }
+    }
+
     /// Retrieves correct [`RequestQueue`] for the given [`NetProtocol`].
-    fn queue(&mut self, protocol: NetProtocol) -> &mut RequestQueue {
+    fn queue(&mut self, protocol: NetProtocol) -> &mut RequestQueue<ConnectRequest> {
         match protocol {
             NetProtocol::Datagrams => &mut self.datagrams_reqs,
             NetProtocol::Stream => &mut self.stream_reqs,
@@ -121,6 +205,16 @@ impl OutgoingProxy {
             protocol,
         };
 
+        if let Some(mock) = self
+            .mock_ids
+            .get(&connection_id)
+            .filter(|_| protocol == NetProtocol::Stream)
+            .and_then(|mock_id| self.mock_connections.get(mock_id))
+        {
+            mock.tx.send(MockInterceptorMessage::Data(bytes)).await;
+            return Ok(());
+        }
+
         let Some(interceptor) = self.txs.get(&id) else {
             tracing::trace!(
                 "{id} does not exist, received data for connection that is already closed"
@@ -143,17 +237,24 @@ impl OutgoingProxy {
         protocol: NetProtocol,
         message_bus: &mut MessageBus<Self>,
     ) -> Result<(), OutgoingProxyError> {
-        let (message_id, layer_id) = self.queue(protocol).pop_front().ok_or_else(|| {
-            let message = match protocol {
-                NetProtocol::Datagrams => {
-                    DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Connect(connect.clone()))
-                }
-                NetProtocol::Stream => {
-                    DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::Connect(connect.clone()))
-                }
-            };
-            UnexpectedAgentMessage(message)
-        })?;
+        let (message_id, layer_id, request) =
+            self.queue(protocol).pop_front_with_data().ok_or_else(|| {
+                let message = match protocol {
+                    NetProtocol::Datagrams => {
+                        DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Connect(connect.clone()))
+                    }
+                    NetProtocol::Stream => {
+                        DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::Connect(connect.clone()))
+                    }
+                };
+                UnexpectedAgentMessage(message)
+            })?;
+
+        if let Some(mock_id) = request.mock {
+            self.handle_mock_connect_response(mock_id, connect, message_bus)
+                .await;
+            return Ok(());
+        }
 
         let connect = match connect {
             Ok(connect) => connect,
@@ -185,7 +286,7 @@ impl OutgoingProxy {
         };
 
         let interceptor = self.background_tasks.register(
-            Interceptor::new(prepared_socket),
+            Interceptor::new(prepared_socket, request.faults),
             id,
             Self::CHANNEL_SIZE,
         );
@@ -205,7 +306,145 @@ impl OutgoingProxy {
         Ok(())
     }
 
+    /// Handles agent's response to a connection request made by a [`MockInterceptor`].
+    async fn handle_mock_connect_response(
+        &mut self,
+        mock_id: MockId,
+        connect: RemoteResult<DaemonConnect>,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let connect = match connect {
+            Ok(connect) => connect,
+            Err(error) => {
+                tracing::warn!(
+                    ?mock_id,
+                    %error,
+                    "Agent failed to make a connection for an unmatched mock request"
+                );
+                self.mock_connections.remove(&mock_id);
+                return;
+            }
+        };
+
+        match self.mock_connections.get_mut(&mock_id) {
+            Some(mock) => {
+                mock.connection_id = Some(connect.connection_id);
+                self.mock_ids.insert(connect.connection_id, mock_id);
+                mock.tx.send(MockInterceptorMessage::Connected).await;
+            }
+            None => {
+                let msg = NetProtocol::Stream.wrap_agent_close(connect.connection_id);
+                message_bus.send(ProxyMessage::ToAgent(msg)).await;
+            }
+        }
+    }
+
+    /// Prepares a local socket and registers a new [`MockInterceptor`] task for this connection.
+    /// Replies to the layer's request with the requested `remote_ip` as the in-cluster address.
+    async fn intercept_mocked(
+        &mut self,
+        message_id: MessageId,
+        layer_id: LayerId,
+        remote_ip: SocketAddr,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Result<(), OutgoingProxyError> {
+        let remote_address = SocketAddress::Ip(remote_ip);
+        let prepared_socket = NetProtocol::Stream
+            .prepare_socket(remote_address.clone())
+            .await?;
+        let layer_address = prepared_socket.local_address()?;
+
+        let mock_id = MockId(self.next_mock_id);
+        self.next_mock_id += 1;
+
+        let tx = self.mock_tasks.register(
+            MockInterceptor::new(prepared_socket, remote_ip, self.mocks.clone()),
+            mock_id,
+            Self::CHANNEL_SIZE,
+        );
+        self.mock_connections.insert(
+            mock_id,
+            MockConnection {
+                tx,
+                remote_address,
+                message_id,
+                layer_id,
+                connection_id: None,
+            },
+        );
+
+        message_bus
+            .send(ToLayer {
+                message: ProxyToLayerMessage::OutgoingConnect(Ok(OutgoingConnectResponse {
+                    layer_address,
+                    in_cluster_address: SocketAddress::Ip(remote_ip),
+                })),
+                message_id,
+                layer_id,
+            })
+            .await;
+
+        Ok(())
+    }
+
+    /// Handles an update from one of the [`MockInterceptor`] tasks.
+    async fn handle_mock_update(
+        &mut self,
+        mock_id: MockId,
+        update: TaskUpdate<MockInterceptorUpdate, io::Error>,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        match update {
+            TaskUpdate::Message(MockInterceptorUpdate::Passthrough) => {
+                let Some(mock) = self.mock_connections.get(&mock_id) else {
+                    return;
+                };
+
+                let message = NetProtocol::Stream.wrap_agent_connect(mock.remote_address.clone());
+                self.stream_reqs.push_back_with_data(
+                    mock.message_id,
+                    mock.layer_id,
+                    ConnectRequest {
+                        message: message.clone(),
+                        mock: Some(mock_id),
+                        faults: Default::default(),
+                    },
+                );
+                message_bus.send(ProxyMessage::ToAgent(message)).await;
+            }
+
+            TaskUpdate::Message(MockInterceptorUpdate::Data(bytes)) => {
+                if let Some(connection_id) = self
+                    .mock_connections
+                    .get(&mock_id)
+                    .and_then(|mock| mock.connection_id)
+                {
+                    let msg = NetProtocol::Stream.wrap_agent_write(connection_id, bytes);
+                    message_bus.send(ProxyMessage::ToAgent(msg)).await;
+                }
+            }
+
+            TaskUpdate::Finished(res) => {
+                tracing::trace!(?mock_id, ?res, "mock interceptor finished");
+
+                if let Some(connection_id) = self
+                    .mock_connections
+                    .remove(&mock_id)
+                    .and_then(|mock| mock.connection_id)
+                {
+                    self.mock_ids.remove(&connection_id);
+                    let msg = NetProtocol::Stream.wrap_agent_close(connection_id);
+                    message_bus.send(ProxyMessage::ToAgent(msg)).await;
+                }
+            }
+        }
+    }
+
     /// Saves the layer's request id and sends the connection request to the agent.
+    ///
+    /// Requests to destinations blocked by the [`ClientPolicy`] are answered with an error right
+    /// away, and so are the requests failed by the [`OutgoingFaults`]. TCP connections to
+    /// addresses that might be mocked are handled locally, see [`Self::intercept_mocked`].
     #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
     async fn handle_connect_request(
         &mut self,
@@ -213,12 +452,107 @@ impl OutgoingProxy {
         session_id: LayerId,
         request: OutgoingConnectRequest,
         message_bus: &mut MessageBus<Self>,
-    ) {
-        self.queue(request.protocol)
-            .push_back(message_id, session_id);
+    ) -> Result<(), OutgoingProxyError> {
+        if let Err(error) = self.policy.check_outgoing(&request.remote_address) {
+            message_bus
+                .send(ToLayer {
+                    message_id,
+                    layer_id: session_id,
+                    message: ProxyToLayerMessage::OutgoingConnect(Err(error)),
+                })
+                .await;
+            return Ok(());
+        }
+
+        if self.faults.is_resolving() || self.mocks.is_resolving() {
+            self.waiting_for_names
+                .push((message_id, session_id, request));
+            return Ok(());
+        }
+
+        if let (NetProtocol::Stream, SocketAddress::Ip(remote_ip)) =
+            (request.protocol, &request.remote_address)
+            && self.mocks.intercepts(*remote_ip)
+        {
+            return self
+                .intercept_mocked(message_id, session_id, *remote_ip, message_bus)
+                .await;
+        }
+
+        let faults = match self
+            .faults
+            .connection_faults(&request.remote_address, request.protocol)
+        {
+            Ok(faults) => faults,
+            Err(error) => {
+                message_bus
+                    .send(ToLayer {
+                        message_id,
+                        layer_id: session_id,
+                        message: ProxyToLayerMessage::OutgoingConnect(Err(error)),
+                    })
+                    .await;
+                return Ok(());
+            }
+        };
 
         let msg = request.protocol.wrap_agent_connect(request.remote_address);
+        self.queue(request.protocol).push_back_with_data(
+            message_id,
+            session_id,
+            ConnectRequest {
+                message: msg.clone(),
+                mock: None,
+                faults,
+            },
+        );
+
         message_bus.send(ProxyMessage::ToAgent(msg)).await;
+
+        Ok(())
+    }
+
+    /// Saves the remote DNS resolution result for one of the [`OutgoingFaults::names`] or
+    /// [`OutgoingMocks::names`], and handles the connection requests that were waiting for it.
+    async fn name_resolved(
+        &mut self,
+        name: String,
+        addresses: Vec<IpAddr>,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Result<(), OutgoingProxyError> {
+        if self.mocks.names().contains(&name) {
+            Arc::make_mut(&mut self.mocks).name_resolved(name.clone(), addresses.clone());
+        }
+        self.faults.name_resolved(name, addresses);
+        if self.faults.is_resolving() || self.mocks.is_resolving() {
+            return Ok(());
+        }
+
+        for (message_id, layer_id, request) in std::mem::take(&mut self.waiting_for_names) {
+            self.handle_connect_request(message_id, layer_id, request, message_bus)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Shuts down all [`Interceptor`]s and [`MockInterceptor`]s, and sends connection requests
+    /// that were not answered yet to the new agent connection.
+    #[tracing::instrument(level = Level::TRACE, skip_all)]
+    async fn connection_refresh_started(&mut self, message_bus: &mut MessageBus<Self>) {
+        message_bus.send(ProxyMessage::ConnectionRefreshAck).await;
+
+        self.txs.clear();
+        self.background_tasks.clear();
+        self.mock_connections.clear();
+        self.mock_ids.clear();
+        self.mock_tasks.clear();
+
+        for request in self.datagrams_reqs.iter().chain(self.stream_reqs.iter()) {
+            message_bus
+                .send(ProxyMessage::ToAgent(request.message.clone()))
+                .await;
+        }
     }
 }
 
@@ -227,6 +561,10 @@ pub enum OutgoingProxyMessage {
     AgentStream(DaemonTcpOutgoing),
     AgentDatagrams(DaemonUdpOutgoing),
     LayerConnect(OutgoingConnectRequest, MessageId, LayerId),
+    /// The agent connection is being replaced.
+    ConnectionRefresh(ConnectionRefresh),
+    /// Remote DNS resolution result for a [`ProxyMessage::ResolveName`].
+    NameResolved(String, Vec<IpAddr>),
 }
 
 impl BackgroundTask for OutgoingProxy {
@@ -235,6 +573,12 @@ impl BackgroundTask for OutgoingProxy {
     type MessageOut = ProxyMessage;
 
     async fn run(mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
+        let mut names = self.faults.names();
+        names.extend(self.mocks.names());
+        for name in names {
+            message_bus.send(ProxyMessage::ResolveName(name)).await;
+        }
+
         loop {
             tokio::select! {
                 msg = message_bus.recv() => match msg {
@@ -246,6 +590,9 @@ impl BackgroundTask for OutgoingProxy {
                         DaemonTcpOutgoing::Close(close) => {
                             let id = InterceptorId { connection_id: close, protocol: NetProtocol::Stream};
                             self.txs.remove(&id);
+                            if let Some(mock_id) = self.mock_ids.remove(&close) {
+                                self.mock_connections.remove(&mock_id);
+                            }
                         },
                         DaemonTcpOutgoing::Read(read) => self.handle_agent_read(read, NetProtocol::Stream).await?,
                         DaemonTcpOutgoing::Connect(connect) => self.handle_connect_response(connect, NetProtocol::Stream, message_bus).await?,
@@ -263,9 +610,14 @@ impl BackgroundTask for OutgoingProxy {
                         session_id,
                         req,
                         message_bus
-                    ).await,
+                    ).await?,
+                    Some(OutgoingProxyMessage::ConnectionRefresh(ConnectionRefresh::Start)) => self.connection_refresh_started(message_bus).await,
+                    Some(OutgoingProxyMessage::ConnectionRefresh(ConnectionRefresh::End)) => {},
+                    Some(OutgoingProxyMessage::NameResolved(name, addresses)) => self.name_resolved(name, addresses, message_bus).await?,
                 },
 
+                Some((mock_id, update)) = self.mock_tasks.next() => self.handle_mock_update(mock_id, update, message_bus).await,
+
                 Some(task_update) = self.background_tasks.next() => match task_update {
                     (id, TaskUpdate::Message(bytes)) => {
                         let msg = id.protocol.wrap_agent_write(id.connection_id, bytes);
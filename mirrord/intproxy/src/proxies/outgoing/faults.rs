@@ -0,0 +1,354 @@
+//! Fault injection for outgoing connections, configured with `feature.network.outgoing.faults`.
+//! See [`OutgoingFaults`] and [`ConnectionFaults`].
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::IpAddr,
+    time::Duration,
+};
+
+use mirrord_config::feature::network::{
+    filter::{
+        AddressFilter, ProtocolAndAddressFilter, ProtocolAndAddressFilterError, ProtocolFilter,
+    },
+    outgoing::faults::{OutgoingFaultRule, OutgoingFaultsConfig},
+};
+use mirrord_intproxy_protocol::NetProtocol;
+use mirrord_protocol::{outgoing::SocketAddress, ErrorKindInternal, RemoteIOError, ResponseError};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::time::Instant;
+
+/// Parsed [`OutgoingFaultRule`].
+#[derive(Debug)]
+struct FaultRule {
+    filter: ProtocolAndAddressFilter,
+    config: OutgoingFaultRule,
+}
+
+/// Decides which faults are injected into the outgoing connections.
+///
+/// All random decisions are made with a single [`StdRng`], seeded with
+/// [`OutgoingFaultsConfig::seed`] when it's set. The generator is used only for connections that
+/// match one of the rules, so the same sequence of such connections gets the same faults in every
+/// run.
+///
+/// Names in the [`AddressFilter::Name`] filters are resolved with the remote DNS, like the names
+/// in `feature.network.outgoing.filter`. The results are passed to
+/// [`OutgoingFaults::name_resolved`], and a name matches nothing until then.
+#[derive(Debug)]
+pub struct OutgoingFaults {
+    rules: Vec<FaultRule>,
+    rng: StdRng,
+    /// Remote DNS resolution results for the [`AddressFilter::Name`] filters.
+    resolved_names: HashMap<String, Vec<IpAddr>>,
+}
+
+impl Default for OutgoingFaults {
+    fn default() -> Self {
+        Self {
+            rules: Default::default(),
+            rng: StdRng::from_entropy(),
+            resolved_names: Default::default(),
+        }
+    }
+}
+
+impl OutgoingFaults {
+    pub fn new(config: &OutgoingFaultsConfig) -> Result<Self, ProtocolAndAddressFilterError> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                Ok(FaultRule {
+                    filter: rule.filter.parse()?,
+                    config: rule.clone(),
+                })
+            })
+            .collect::<Result<_, ProtocolAndAddressFilterError>>()?;
+
+        Ok(Self {
+            rules,
+            rng: config
+                .seed
+                .map(StdRng::seed_from_u64)
+                .unwrap_or_else(StdRng::from_entropy),
+            resolved_names: Default::default(),
+        })
+    }
+
+    /// Returns the names from the [`AddressFilter::Name`] filters, which should be resolved with
+    /// the remote DNS.
+    pub fn names(&self) -> HashSet<String> {
+        self.rules
+            .iter()
+            .filter_map(|rule| match &rule.filter.address {
+                AddressFilter::Name(name, _) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Saves the remote DNS resolution result for one of the [`OutgoingFaults::names`].
+    pub fn name_resolved(&mut self, name: String, addresses: Vec<IpAddr>) {
+        self.resolved_names.insert(name, addresses);
+    }
+
+    /// Returns whether some of the [`OutgoingFaults::names`] were not resolved yet.
+    pub fn is_resolving(&self) -> bool {
+        self.rules.iter().any(|rule| {
+            matches!(
+                &rule.filter.address,
+                AddressFilter::Name(name, _) if !self.resolved_names.contains_key(name)
+            )
+        })
+    }
+
+    /// Decides the faults for a new outgoing connection.
+    ///
+    /// Returns an [`Err`] if the connection should be refused.
+    pub fn connection_faults(
+        &mut self,
+        remote_address: &SocketAddress,
+        protocol: NetProtocol,
+    ) -> Result<ConnectionFaults, ResponseError> {
+        let SocketAddress::Ip(remote_address) = remote_address else {
+            return Ok(Default::default());
+        };
+        let protocol = match protocol {
+            NetProtocol::Stream => ProtocolFilter::Tcp,
+            NetProtocol::Datagrams => ProtocolFilter::Udp,
+        };
+
+        let Some(rule) = self.rules.iter().find(|rule| {
+            rule.filter
+                .matches(*remote_address, protocol, &self.resolved_names)
+        }) else {
+            return Ok(Default::default());
+        };
+
+        if self.rng.gen_range(0..100) < rule.config.connect_failure_percent {
+            tracing::debug!(%remote_address, "Injecting a connect failure");
+            return Err(ResponseError::RemoteIO(RemoteIOError {
+                raw_os_error: Some(libc::ECONNREFUSED),
+                kind: ErrorKindInternal::ConnectionRefused,
+            }));
+        }
+
+        Ok(ConnectionFaults {
+            latency: Duration::from_millis(rule.config.latency_ms),
+            bandwidth: rule.config.bandwidth,
+            reset_percent: rule.config.reset_percent,
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+        })
+    }
+}
+
+/// Faults injected into a single outgoing connection, see [`OutgoingFaults::connection_faults`].
+///
+/// The default value injects no faults.
+#[derive(Clone, Debug)]
+pub struct ConnectionFaults {
+    latency: Duration,
+    /// Bytes per second.
+    bandwidth: Option<u64>,
+    reset_percent: u8,
+    rng: StdRng,
+}
+
+impl Default for ConnectionFaults {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            bandwidth: None,
+            reset_percent: 0,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+}
+
+impl ConnectionFaults {
+    /// Returns a new [`FaultyLink`] for one direction of the connection.
+    pub fn link(&self) -> FaultyLink {
+        FaultyLink {
+            latency: self.latency,
+            bandwidth: self.bandwidth,
+            queue: Default::default(),
+            busy_until: Instant::now(),
+        }
+    }
+
+    /// Decides whether the connection should be reset before relaying the next chunk of data.
+    pub fn should_reset(&mut self) -> bool {
+        self.reset_percent > 0 && self.rng.gen_range(0..100) < self.reset_percent
+    }
+}
+
+/// Data relayed in one direction of a connection, released after the latency and at the pace
+/// allowed by the bandwidth of the [`ConnectionFaults`].
+pub struct FaultyLink {
+    latency: Duration,
+    bandwidth: Option<u64>,
+    /// Chunks of data with their release times.
+    queue: VecDeque<(Instant, Vec<u8>)>,
+    /// When the link is done transmitting the previous chunks.
+    busy_until: Instant,
+}
+
+impl FaultyLink {
+    /// How many chunks can wait in the queue before we stop accepting more.
+    const MAX_QUEUED: usize = 64;
+
+    pub fn has_room(&self) -> bool {
+        self.queue.len() < Self::MAX_QUEUED
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn push(&mut self, bytes: Vec<u8>) {
+        let transmit_time = self
+            .bandwidth
+            .map(|bandwidth| Duration::from_secs_f64(bytes.len() as f64 / bandwidth.max(1) as f64))
+            .unwrap_or_default();
+
+        self.busy_until = self.busy_until.max(Instant::now()) + transmit_time;
+        self.queue
+            .push_back((self.busy_until + self.latency, bytes));
+    }
+
+    /// Waits until the next chunk of data can be released.
+    ///
+    /// Returns [`None`] immediately if the queue is empty.
+    ///
+    /// Cancel safe.
+    pub async fn next(&mut self) -> Option<Vec<u8>> {
+        let release_at = self.queue.front()?.0;
+        tokio::time::sleep_until(release_at).await;
+        self.queue.pop_front().map(|(_, bytes)| bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn faults(rules: &[(&str, u8)]) -> OutgoingFaults {
+        OutgoingFaults::new(&OutgoingFaultsConfig {
+            seed: Some(7),
+            rules: rules
+                .iter()
+                .map(|(filter, connect_failure_percent)| OutgoingFaultRule {
+                    filter: filter.to_string(),
+                    latency_ms: 10,
+                    bandwidth: None,
+                    reset_percent: 0,
+                    connect_failure_percent: *connect_failure_percent,
+                })
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case("tcp://10.0.0.0/16", NetProtocol::Stream, true)]
+    #[case("tcp://10.0.0.0/16", NetProtocol::Datagrams, false)]
+    #[case(":8080", NetProtocol::Datagrams, true)]
+    #[case(":80", NetProtocol::Stream, false)]
+    #[case("10.0.0.1", NetProtocol::Stream, true)]
+    #[case("localhost:8080", NetProtocol::Stream, false)]
+    #[test]
+    fn rule_matching(#[case] filter: &str, #[case] protocol: NetProtocol, #[case] matches: bool) {
+        let mut faults = faults(&[(filter, 100)]);
+        let address = SocketAddress::Ip("10.0.0.1:8080".parse().unwrap());
+
+        assert_eq!(
+            faults.connection_faults(&address, protocol).is_err(),
+            matches
+        );
+    }
+
+    /// Names that exist only in the cluster DNS match the addresses resolved by the agent.
+    #[test]
+    fn cluster_name_matching() {
+        let mut faults = faults(&[("tcp://payments.default:8080", 100)]);
+        let payments = SocketAddress::Ip("10.0.0.1:8080".parse().unwrap());
+        let other = SocketAddress::Ip("10.0.0.2:8080".parse().unwrap());
+
+        assert_eq!(
+            faults.names(),
+            HashSet::from(["payments.default".to_string()])
+        );
+        assert!(faults.is_resolving());
+        assert!(faults
+            .connection_faults(&payments, NetProtocol::Stream)
+            .is_ok());
+
+        faults.name_resolved(
+            "payments.default".to_string(),
+            vec!["10.0.0.1".parse().unwrap()],
+        );
+
+        assert!(!faults.is_resolving());
+        assert!(faults
+            .connection_faults(&payments, NetProtocol::Stream)
+            .is_err());
+        assert!(faults
+            .connection_faults(&other, NetProtocol::Stream)
+            .is_ok());
+        assert!(faults
+            .connection_faults(&payments, NetProtocol::Datagrams)
+            .is_ok());
+    }
+
+    #[test]
+    fn seeded_faults_are_deterministic() {
+        let address = SocketAddress::Ip("10.0.0.1:8080".parse().unwrap());
+        let mut runs = vec![];
+
+        for _ in 0..2 {
+            let mut faults = faults(&[(":8080", 50)]);
+            let mut run = vec![];
+            for _ in 0..32 {
+                run.push(
+                    faults
+                        .connection_faults(&address, NetProtocol::Stream)
+                        .is_err(),
+                );
+            }
+            runs.push(run);
+        }
+
+        let (first, second) = (runs.first().unwrap(), runs.last().unwrap());
+        assert_eq!(first, second);
+        assert!(first.contains(&true));
+        assert!(first.contains(&false));
+    }
+
+    #[tokio::test]
+    async fn link_latency_and_bandwidth() {
+        let mut link = ConnectionFaults {
+            latency: Duration::from_millis(20),
+            bandwidth: Some(10_000),
+            ..Default::default()
+        }
+        .link();
+
+        let start = Instant::now();
+        link.push(vec![0; 500]);
+        link.push(vec![0; 500]);
+
+        let first = link.queue.front().unwrap().0;
+        let second = link.queue.back().unwrap().0;
+        assert!(first >= start + Duration::from_millis(70));
+        assert_eq!(second - first, Duration::from_millis(50));
+
+        link.next().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(70));
+        link.next().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(120));
+        assert!(link.next().await.is_none());
+    }
+}
@@ -5,7 +5,7 @@ use std::io;
 
 use crate::{
     background_tasks::{BackgroundTask, MessageBus},
-    proxies::outgoing::net_protocol_ext::PreparedSocket,
+    proxies::outgoing::{faults::ConnectionFaults, net_protocol_ext::PreparedSocket},
 };
 
 /// Manages a single intercepted connection.
@@ -13,13 +13,15 @@ use crate::{
 /// to manage individual connections.
 pub struct Interceptor {
     socket: PreparedSocket,
+    faults: ConnectionFaults,
 }
 
 impl Interceptor {
     /// Creates a new instance. This instance will use the provided [`PreparedSocket`] to accept the
-    /// layer's connection and manage it.
-    pub fn new(socket: PreparedSocket) -> Self {
-        Self { socket }
+    /// layer's connection and manage it, injecting the given [`ConnectionFaults`] into the relayed
+    /// traffic.
+    pub fn new(socket: PreparedSocket, faults: ConnectionFaults) -> Self {
+        Self { socket, faults }
     }
 }
 
@@ -40,15 +42,28 @@ impl BackgroundTask for Interceptor {
     /// 2. A 0-sized read received from the [`MessageBus`] is treated as a shutdown on the agent
     ///    side. Connection with the peer is shut down as well.
     ///
-    /// 3. This implementation exits only when an error is encountered or the [`MessageBus`] is
-    ///    closed.
-    async fn run(self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
+    /// 3. This implementation exits only when an error is encountered, the [`MessageBus`] is
+    ///    closed, or the [`ConnectionFaults`] decide to reset the connection.
+    ///
+    /// 4. Data is relayed in both directions through
+    ///    [`FaultyLink`](crate::proxies::outgoing::faults::FaultyLink)s, which delay it according
+    ///    to the [`ConnectionFaults`].
+    async fn run(mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
         let mut connected_socket = self.socket.accept().await?;
         let mut reading_closed = false;
+        let mut agent_closed = false;
+
+        let mut to_agent = self.faults.link();
+        let mut to_layer = self.faults.link();
 
         loop {
+            if agent_closed && to_layer.is_empty() {
+                tracing::trace!("outgoing interceptor -> no more messages from the agent, exiting");
+                break Ok(());
+            }
+
             tokio::select! {
-                read = connected_socket.receive(), if !reading_closed => match read {
+                read = connected_socket.receive(), if !reading_closed && to_agent.has_room() => match read {
                     Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                         continue;
                     },
@@ -58,23 +73,35 @@ impl BackgroundTask for Interceptor {
                             tracing::trace!("outgoing interceptor -> layer shutdown, sending a 0-sized read to inform the agent");
                             reading_closed = true;
                         }
-                        message_bus.send(bytes).await
+                        to_agent.push(bytes);
                     },
                 },
 
-                msg = message_bus.recv() => match msg {
-                    Some(bytes) => {
-                        if bytes.is_empty() {
-                            tracing::trace!("outgoing interceptor -> agent shutdown, shutting down connection with layer");
-                            connected_socket.shutdown().await?;
-                        } else {
-                            connected_socket.send(&bytes).await?;
-                        }
+                msg = message_bus.recv(), if !agent_closed && to_layer.has_room() => match msg {
+                    Some(bytes) => to_layer.push(bytes),
+                    None => agent_closed = true,
+                },
+
+                Some(bytes) = to_agent.next() => {
+                    if self.faults.should_reset() {
+                        tracing::debug!("outgoing interceptor -> injecting a connection reset");
+                        connected_socket.reset()?;
+                        break Ok(());
                     }
 
-                    None => {
-                        tracing::trace!("outgoing interceptor -> no more messages from the agent, exiting");
-                        break Ok(())
+                    message_bus.send(bytes).await;
+                },
+
+                Some(bytes) = to_layer.next() => {
+                    if bytes.is_empty() {
+                        tracing::trace!("outgoing interceptor -> agent shutdown, shutting down connection with layer");
+                        connected_socket.shutdown().await?;
+                    } else if self.faults.should_reset() {
+                        tracing::debug!("outgoing interceptor -> injecting a connection reset");
+                        connected_socket.reset()?;
+                        break Ok(());
+                    } else {
+                        connected_socket.send(&bytes).await?;
                     }
                 },
             }
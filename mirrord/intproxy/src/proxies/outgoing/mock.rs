@@ -0,0 +1,638 @@
+//! Local mock responses for outgoing HTTP requests, configured with
+//! `feature.network.outgoing.mock`. See [`OutgoingMocks`] and [`MockInterceptor`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use hyper::StatusCode;
+use mirrord_config::feature::network::outgoing::mock::OutgoingMockConfig;
+use thiserror::Error;
+
+use crate::{
+    background_tasks::{BackgroundTask, MessageBus},
+    proxies::outgoing::net_protocol_ext::{ConnectedSocket, PreparedSocket},
+};
+
+/// Errors that can occur when preparing [`OutgoingMocks`] from the config.
+#[derive(Error, Debug)]
+pub enum OutgoingMockError {
+    #[error("invalid mock address `{0}`, expected `host:port`")]
+    Address(String),
+    #[error("invalid mock response status `{0}`")]
+    Status(u16),
+    #[error("failed to read mock response file `{0}`: {1}")]
+    ReadResponse(PathBuf, io::Error),
+}
+
+/// Host of a mocked service.
+#[derive(Debug, Clone)]
+enum MockHost {
+    /// Matched against the address the application connects to.
+    Ip(IpAddr),
+    /// Matched against the addresses resolved from the name with the remote DNS, and the `Host`
+    /// header of the requests.
+    Name(String),
+}
+
+/// Parsed [`OutgoingMockConfig`].
+#[derive(Debug, Clone)]
+struct OutgoingMock {
+    host: MockHost,
+    port: u16,
+    /// Uppercase.
+    method: Option<String>,
+    path: String,
+    /// Whole serialized HTTP/1.1 response.
+    response: Vec<u8>,
+}
+
+impl OutgoingMock {
+    fn new(
+        config: &OutgoingMockConfig,
+        config_file: Option<&Path>,
+    ) -> Result<Self, OutgoingMockError> {
+        let (host, port) = config
+            .host_and_port()
+            .ok_or_else(|| OutgoingMockError::Address(config.address.clone()))?;
+        let host = match host.parse() {
+            Ok(ip) => MockHost::Ip(ip),
+            Err(..) => MockHost::Name(host.to_ascii_lowercase()),
+        };
+
+        let status = StatusCode::from_u16(config.status)
+            .map_err(|_| OutgoingMockError::Status(config.status))?;
+        let response_path = config.response_path(config_file);
+        let body = std::fs::read(&response_path)
+            .map_err(|error| OutgoingMockError::ReadResponse(response_path, error))?;
+
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\ncontent-length: {}\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default(),
+            body.len()
+        );
+        for (name, value) in &config.headers {
+            if !name.eq_ignore_ascii_case("content-length") {
+                let _ = write!(head, "{name}: {value}\r\n");
+            }
+        }
+        head.push_str("\r\n");
+
+        let mut response = head.into_bytes();
+        response.extend(body);
+
+        Ok(Self {
+            host,
+            port,
+            method: config.method.as_deref().map(str::to_ascii_uppercase),
+            path: config.path.clone(),
+            response,
+        })
+    }
+
+    /// Whether requests sent on connections to the `remote_address` can match this mock.
+    ///
+    /// A [`MockHost::Name`] matches only the addresses in `resolved_names`.
+    fn matches_address(
+        &self,
+        remote_address: SocketAddr,
+        resolved_names: &HashMap<String, Vec<IpAddr>>,
+    ) -> bool {
+        self.port == remote_address.port()
+            && match &self.host {
+                MockHost::Ip(ip) => *ip == remote_address.ip(),
+                MockHost::Name(name) => resolved_names
+                    .get(name)
+                    .is_some_and(|addresses| addresses.contains(&remote_address.ip())),
+            }
+    }
+
+    fn matches_request(
+        &self,
+        remote_address: SocketAddr,
+        request: &RequestHead,
+        resolved_names: &HashMap<String, Vec<IpAddr>>,
+    ) -> bool {
+        let host_matches = match &self.host {
+            MockHost::Ip(..) => true,
+            MockHost::Name(name) => request.host.as_deref() == Some(name.as_str()),
+        };
+
+        self.matches_address(remote_address, resolved_names)
+            && host_matches
+            && self
+                .method
+                .as_deref()
+                .is_none_or(|method| method == request.method)
+            && self.path == request.path
+    }
+}
+
+/// Mocks from `feature.network.outgoing.mock`, with the responses loaded into memory.
+///
+/// Host names of the mocks are resolved with the remote DNS, like the names in
+/// `feature.network.outgoing.faults`. The results are passed to [`OutgoingMocks::name_resolved`],
+/// and a name matches nothing until then.
+///
+/// Names that don't resolve (e.g. the mocked service does not exist yet) get a placeholder address
+/// instead, see [`OutgoingMocks::placeholders`].
+#[derive(Default, Debug, Clone)]
+pub struct OutgoingMocks {
+    mocks: Vec<OutgoingMock>,
+    /// Remote DNS resolution results for the [`MockHost::Name`]s.
+    resolved_names: HashMap<String, Vec<IpAddr>>,
+    /// Placeholder addresses for the [`MockHost::Name`]s.
+    placeholders: HashMap<String, IpAddr>,
+}
+
+impl OutgoingMocks {
+    /// Placeholder addresses are taken from this benchmarking range (RFC 2544), which should not
+    /// be used by any real service.
+    const PLACEHOLDER_PREFIX: [u8; 2] = [198, 18];
+
+    /// Parses the mocks from the config and reads the response files.
+    ///
+    /// Relative response paths are resolved against the directory of the `config_file`.
+    pub fn new(
+        config: &[OutgoingMockConfig],
+        config_file: Option<&Path>,
+    ) -> Result<Self, OutgoingMockError> {
+        let mocks = config
+            .iter()
+            .map(|mock| OutgoingMock::new(mock, config_file))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut names = mocks
+            .iter()
+            .filter_map(|mock| match &mock.host {
+                MockHost::Name(name) => Some(name.clone()),
+                MockHost::Ip(..) => None,
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        let placeholders = names
+            .into_iter()
+            .zip(1..=u16::MAX)
+            .map(|(name, index)| {
+                let [prefix_0, prefix_1] = Self::PLACEHOLDER_PREFIX;
+                let [index_0, index_1] = index.to_be_bytes();
+                let ip = Ipv4Addr::new(prefix_0, prefix_1, index_0, index_1);
+                (name, IpAddr::V4(ip))
+            })
+            .collect();
+
+        Ok(Self {
+            mocks,
+            resolved_names: Default::default(),
+            placeholders,
+        })
+    }
+
+    /// Returns the placeholder addresses of the [`OutgoingMocks::names`].
+    ///
+    /// When a name does not resolve with the remote DNS, the application's lookups of the name
+    /// should get the placeholder, which is then mocked like a resolved address.
+    pub fn placeholders(&self) -> &HashMap<String, IpAddr> {
+        &self.placeholders
+    }
+
+    /// Returns the host names of the mocks, which should be resolved with the remote DNS.
+    pub fn names(&self) -> HashSet<String> {
+        self.mocks
+            .iter()
+            .filter_map(|mock| match &mock.host {
+                MockHost::Name(name) => Some(name.clone()),
+                MockHost::Ip(..) => None,
+            })
+            .collect()
+    }
+
+    /// Saves the remote DNS resolution result for one of the [`OutgoingMocks::names`].
+    ///
+    /// If the name did not resolve, its placeholder address is used instead.
+    pub fn name_resolved(&mut self, name: String, mut addresses: Vec<IpAddr>) {
+        if addresses.is_empty() {
+            addresses.extend(self.placeholders.get(&name));
+        }
+        self.resolved_names.insert(name, addresses);
+    }
+
+    /// Returns whether some of the [`OutgoingMocks::names`] were not resolved yet.
+    pub fn is_resolving(&self) -> bool {
+        self.mocks.iter().any(|mock| {
+            matches!(
+                &mock.host,
+                MockHost::Name(name) if !self.resolved_names.contains_key(name)
+            )
+        })
+    }
+
+    /// Whether TCP connections to the given address should be handled by a [`MockInterceptor`].
+    pub fn intercepts(&self, remote_address: SocketAddr) -> bool {
+        self.mocks
+            .iter()
+            .any(|mock| mock.matches_address(remote_address, &self.resolved_names))
+    }
+
+    /// Returns the response of the first mock matching the request.
+    fn response(&self, remote_address: SocketAddr, request: &RequestHead) -> Option<&[u8]> {
+        self.mocks
+            .iter()
+            .find(|mock| mock.matches_request(remote_address, request, &self.resolved_names))
+            .map(|mock| mock.response.as_slice())
+    }
+}
+
+/// Length of an HTTP request body.
+#[derive(Debug, PartialEq, Eq)]
+enum BodyLength {
+    Fixed(usize),
+    Chunked,
+}
+
+/// Parsed head of an HTTP/1 request.
+#[derive(Debug, PartialEq, Eq)]
+struct RequestHead {
+    /// Uppercase.
+    method: String,
+    /// Without the query.
+    path: String,
+    /// Lowercase, without the port.
+    host: Option<String>,
+    /// Length of the head, including the empty line that ends it.
+    len: usize,
+    body: BodyLength,
+}
+
+/// The application sent something that is not an HTTP/1 request.
+#[derive(Debug, PartialEq, Eq)]
+struct NotHttp;
+
+impl RequestHead {
+    /// Longest request head we're willing to buffer.
+    const MAX_LEN: usize = 64 * 1024;
+
+    /// Longest method we expect to see.
+    const MAX_METHOD_LEN: usize = 16;
+
+    /// Parses the head of the request at the start of `bytes`.
+    ///
+    /// Returns [`None`] if more bytes are needed.
+    fn parse(bytes: &[u8]) -> Result<Option<Self>, NotHttp> {
+        let mut method = bytes.iter().take_while(|byte| **byte != b' ');
+        if method.clone().count() > Self::MAX_METHOD_LEN || !method.all(u8::is_ascii_uppercase) {
+            return Err(NotHttp);
+        }
+
+        let Some(len) = bytes
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|position| position + 4)
+        else {
+            return if bytes.len() > Self::MAX_LEN {
+                Err(NotHttp)
+            } else {
+                Ok(None)
+            };
+        };
+
+        let head = std::str::from_utf8(bytes.get(..len).ok_or(NotHttp)?).map_err(|_| NotHttp)?;
+        let mut lines = head.split("\r\n");
+
+        let mut request_line = lines.next().ok_or(NotHttp)?.split(' ');
+        let (Some(method), Some(target), Some(version), None) = (
+            request_line.next(),
+            request_line.next(),
+            request_line.next(),
+            request_line.next(),
+        ) else {
+            return Err(NotHttp);
+        };
+        if !version.starts_with("HTTP/1.") {
+            return Err(NotHttp);
+        }
+
+        let mut host = None;
+        let mut body = BodyLength::Fixed(0);
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':').ok_or(NotHttp)?;
+            let value = value.trim();
+
+            if name.eq_ignore_ascii_case("host") {
+                let name = match value.rsplit_once(':') {
+                    Some((name, port)) if port.parse::<u16>().is_ok() => name,
+                    _ => value,
+                };
+                host = Some(name.to_ascii_lowercase());
+            } else if name.eq_ignore_ascii_case("content-length") {
+                body = BodyLength::Fixed(value.parse().map_err(|_| NotHttp)?);
+            } else if name.eq_ignore_ascii_case("transfer-encoding")
+                && value.to_ascii_lowercase().ends_with("chunked")
+            {
+                body = BodyLength::Chunked;
+            }
+        }
+
+        Ok(Some(Self {
+            method: method.to_string(),
+            path: target.split('?').next().unwrap_or_default().to_string(),
+            host,
+            len,
+            body,
+        }))
+    }
+
+    /// Returns the length of the whole request (head and body) at the start of `bytes`, or
+    /// [`None`] if more bytes are needed.
+    fn request_len(&self, bytes: &[u8]) -> Option<usize> {
+        match self.body {
+            BodyLength::Fixed(body_len) => {
+                Some(self.len + body_len).filter(|len| *len <= bytes.len())
+            }
+            BodyLength::Chunked => bytes
+                .get(self.len..)?
+                .windows(5)
+                .position(|window| window == b"0\r\n\r\n")
+                .map(|position| self.len + position + 5),
+        }
+    }
+}
+
+/// Messages consumed by the [`MockInterceptor`].
+pub enum MockInterceptorMessage {
+    /// The connection through the agent, requested with [`MockInterceptorUpdate::Passthrough`],
+    /// is ready.
+    Connected,
+    /// Data from the agent.
+    Data(Vec<u8>),
+}
+
+/// Messages produced by the [`MockInterceptor`].
+pub enum MockInterceptorUpdate {
+    /// The application sent a request that does not match any mock, and the connection should go
+    /// through the agent from now on.
+    Passthrough,
+    /// Data for the agent.
+    Data(Vec<u8>),
+}
+
+/// Manages a single intercepted connection to a mocked address, see [`OutgoingMocks::intercepts`].
+///
+/// Answers the requests matching the mocks locally. When the application sends a request that
+/// doesn't match any of the mocks, asks the [`OutgoingProxy`](super::OutgoingProxy) for a
+/// connection through the agent, and then proxies bytes like the
+/// [`Interceptor`](super::interceptor::Interceptor) does, starting with the unmatched request.
+pub struct MockInterceptor {
+    socket: PreparedSocket,
+    remote_address: SocketAddr,
+    mocks: Arc<OutgoingMocks>,
+}
+
+impl MockInterceptor {
+    pub fn new(
+        socket: PreparedSocket,
+        remote_address: SocketAddr,
+        mocks: Arc<OutgoingMocks>,
+    ) -> Self {
+        Self {
+            socket,
+            remote_address,
+            mocks,
+        }
+    }
+
+    /// Serves the mocked requests, returns the buffered bytes that should be sent to the agent,
+    /// or [`None`] if the application closed the connection.
+    async fn serve_mocks(
+        mocks: &OutgoingMocks,
+        remote_address: SocketAddr,
+        connected_socket: &mut ConnectedSocket,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let mut buffer = Vec::new();
+
+        loop {
+            match RequestHead::parse(&buffer) {
+                Ok(Some(head)) => {
+                    let Some(response) = mocks.response(remote_address, &head) else {
+                        return Ok(Some(buffer));
+                    };
+
+                    if let Some(request_len) = head.request_len(&buffer) {
+                        tracing::debug!(?head, "Serving a mocked response");
+                        buffer.drain(..request_len);
+                        connected_socket.send(response).await?;
+                        continue;
+                    }
+                }
+                Ok(None) => {}
+                Err(NotHttp) => return Ok(Some(buffer)),
+            }
+
+            let bytes = connected_socket.receive().await?;
+            if bytes.is_empty() {
+                return Ok(None);
+            }
+            buffer.extend(bytes);
+        }
+    }
+}
+
+impl BackgroundTask for MockInterceptor {
+    type Error = io::Error;
+    type MessageIn = MockInterceptorMessage;
+    type MessageOut = MockInterceptorUpdate;
+
+    async fn run(self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
+        let mut connected_socket = self.socket.accept().await?;
+
+        let Some(buffered) =
+            Self::serve_mocks(&self.mocks, self.remote_address, &mut connected_socket).await?
+        else {
+            return Ok(());
+        };
+
+        message_bus.send(MockInterceptorUpdate::Passthrough).await;
+        loop {
+            match message_bus.recv().await {
+                Some(MockInterceptorMessage::Connected) => break,
+                Some(MockInterceptorMessage::Data(..)) => {}
+                None => return Ok(()),
+            }
+        }
+        message_bus
+            .send(MockInterceptorUpdate::Data(buffered))
+            .await;
+
+        let mut reading_closed = false;
+        loop {
+            tokio::select! {
+                read = connected_socket.receive(), if !reading_closed => {
+                    let bytes = read?;
+                    reading_closed = bytes.is_empty();
+                    message_bus.send(MockInterceptorUpdate::Data(bytes)).await;
+                },
+
+                msg = message_bus.recv() => match msg {
+                    Some(MockInterceptorMessage::Data(bytes)) if bytes.is_empty() => {
+                        connected_socket.shutdown().await?;
+                    }
+                    Some(MockInterceptorMessage::Data(bytes)) => connected_socket.send(&bytes).await?,
+                    Some(MockInterceptorMessage::Connected) => {}
+                    None => break Ok(()),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[rstest]
+    #[case(b"GET /v1/users?page=2 HTTP/1.1\r\nHost: Users.default:8080\r\n\r\n", Ok(Some(RequestHead {
+        method: "GET".into(),
+        path: "/v1/users".into(),
+        host: Some("users.default".into()),
+        len: 59,
+        body: BodyLength::Fixed(0),
+    })))]
+    #[case(b"POST / HTTP/1.1\r\ncontent-length: 4\r\n\r\nbody", Ok(Some(RequestHead {
+        method: "POST".into(),
+        path: "/".into(),
+        host: None,
+        len: 38,
+        body: BodyLength::Fixed(4),
+    })))]
+    #[case(b"GET / HTTP/1.1\r\nHost: users", Ok(None))]
+    #[case(b"", Ok(None))]
+    #[case(b"\x16\x03\x01\x02\x00\x01", Err(NotHttp))]
+    #[case(b"GET / SSH-2.0\r\n\r\n", Err(NotHttp))]
+    fn parse_request_head(
+        #[case] bytes: &[u8],
+        #[case] expected: Result<Option<RequestHead>, NotHttp>,
+    ) {
+        assert_eq!(RequestHead::parse(bytes), expected);
+    }
+
+    #[test]
+    fn chunked_request_len() {
+        let request =
+            b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n4\r\nbody\r\n0\r\n\r\nGET";
+        let head = RequestHead::parse(request).unwrap().unwrap();
+
+        assert_eq!(head.body, BodyLength::Chunked);
+        assert_eq!(head.request_len(request), Some(request.len() - 3));
+        assert_eq!(head.request_len(&request[..50]), None);
+    }
+
+    #[test]
+    fn mock_matching() {
+        let body = NamedTempFile::new().unwrap();
+        std::fs::write(body.path(), "[]").unwrap();
+
+        let mut mocks = OutgoingMocks::new(
+            &[OutgoingMockConfig {
+                address: "users.default:8080".into(),
+                method: Some("get".into()),
+                path: "/v1/users".into(),
+                response: body.path().to_path_buf(),
+                status: 200,
+                headers: BTreeMap::from([("content-type".into(), "application/json".into())]),
+            }],
+            None,
+        )
+        .unwrap();
+
+        let remote_address = "10.0.0.1:8080".parse().unwrap();
+        assert_eq!(mocks.names(), HashSet::from(["users.default".to_string()]));
+        assert!(mocks.is_resolving());
+        assert!(!mocks.intercepts(remote_address));
+
+        mocks.name_resolved(
+            "users.default".to_string(),
+            vec!["10.0.0.1".parse().unwrap()],
+        );
+        assert!(!mocks.is_resolving());
+        assert!(mocks.intercepts(remote_address));
+        assert!(!mocks.intercepts("10.0.0.1:80".parse().unwrap()));
+        assert!(!mocks.intercepts("10.0.0.2:8080".parse().unwrap()));
+
+        let request = |method: &str, path: &str| {
+            RequestHead::parse(
+                format!("{method} {path} HTTP/1.1\r\nhost: users.default:8080\r\n\r\n").as_bytes(),
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        assert_eq!(
+            mocks.response(remote_address, &request("GET", "/v1/users")),
+            Some(
+                b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\ncontent-type: application/json\r\n\r\n[]"
+                    .as_slice()
+            )
+        );
+        assert_eq!(
+            mocks.response(remote_address, &request("POST", "/v1/users")),
+            None
+        );
+        assert_eq!(
+            mocks.response(remote_address, &request("GET", "/v1/orders")),
+            None
+        );
+    }
+
+    /// A name that does not resolve remotely is mocked on its placeholder address.
+    #[test]
+    fn unresolved_name_placeholder() {
+        let config_dir = tempfile::tempdir().unwrap();
+        std::fs::write(config_dir.path().join("orders.json"), "[]").unwrap();
+
+        let mock = |address: &str| OutgoingMockConfig {
+            address: address.into(),
+            method: None,
+            path: "/v1/orders".into(),
+            response: "orders.json".into(),
+            status: 200,
+            headers: Default::default(),
+        };
+        let mut mocks = OutgoingMocks::new(
+            &[mock("orders.default:8080"), mock("users.default:8080")],
+            Some(&config_dir.path().join("mirrord.json")),
+        )
+        .unwrap();
+
+        let placeholder: IpAddr = "198.18.0.1".parse().unwrap();
+        assert_eq!(
+            mocks.placeholders(),
+            &HashMap::from([
+                ("orders.default".to_string(), placeholder),
+                ("users.default".to_string(), "198.18.0.2".parse().unwrap()),
+            ])
+        );
+
+        mocks.name_resolved("orders.default".to_string(), vec![]);
+        assert!(mocks.intercepts(SocketAddr::new(placeholder, 8080)));
+
+        let request =
+            RequestHead::parse(b"GET /v1/orders HTTP/1.1\r\nhost: orders.default\r\n\r\n")
+                .unwrap()
+                .unwrap();
+        assert!(mocks
+            .response(SocketAddr::new(placeholder, 8080), &request)
+            .is_some());
+    }
+}
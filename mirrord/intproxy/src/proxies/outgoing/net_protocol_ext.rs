@@ -5,6 +5,7 @@ use std::{
     env, io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::PathBuf,
+    time::Duration,
 };
 
 use bytes::BytesMut;
@@ -256,4 +257,16 @@ impl ConnectedSocket {
             InnerConnectedSocket::UdpSocket(..) => Ok(()),
         }
     }
+    /// Resets the connection, so that the peer gets a `RST` instead of a `FIN` when this socket is
+    /// dropped.
+    ///
+    /// # Note
+    ///
+    /// This is a no-op for UDP and unix sockets.
+    pub fn reset(&mut self) -> io::Result<()> {
+        match &self.inner {
+            InnerConnectedSocket::TcpStream(stream) => stream.set_linger(Some(Duration::ZERO)),
+            InnerConnectedSocket::UnixStream(..) | InnerConnectedSocket::UdpSocket(..) => Ok(()),
+        }
+    }
 }
@@ -1,43 +1,184 @@
 //! The most basic proxying logic. Handles cases when the only job to do in the internal proxy is to
 //! pass requests and responses between the layer and the agent.
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+};
 
 use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
+use mirrord_operator::client::policy::ClientPolicy;
 use mirrord_protocol::{
-    dns::{GetAddrInfoRequest, GetAddrInfoResponse},
-    ClientMessage, DaemonMessage, GetEnvVarsRequest, RemoteResult,
+    dns::{
+        AddressFamily, DnsLookup, DnsQueryRequest, DnsQueryResponse, GetAddrInfoRequest,
+        GetAddrInfoRequestV2, GetAddrInfoResponse, LookupRecord, ADDRINFO_V2_VERSION,
+        DNS_QUERY_VERSION,
+    },
+    ClientMessage, DaemonMessage, GetEnvVarsRequest, RemoteResult, ResponseError,
 };
+use semver::Version;
 use thiserror::Error;
 
 use crate::{
     background_tasks::{BackgroundTask, MessageBus},
     error::UnexpectedAgentMessage,
-    main_tasks::ToLayer,
+    main_tasks::{ConnectionRefresh, ToLayer},
     request_queue::RequestQueue,
     ProxyMessage,
 };
 
 #[derive(Debug)]
 pub enum SimpleProxyMessage {
-    AddrInfoReq(MessageId, LayerId, GetAddrInfoRequest),
+    AddrInfoReq(MessageId, LayerId, GetAddrInfoRequestV2),
     AddrInfoRes(GetAddrInfoResponse),
     GetEnvReq(MessageId, LayerId, GetEnvVarsRequest),
     GetEnvRes(RemoteResult<HashMap<String, String>>),
+    DnsQueryReq(MessageId, LayerId, DnsQueryRequest),
+    DnsQueryRes(DnsQueryResponse),
+    /// The [`OutgoingProxy`](super::outgoing::OutgoingProxy) needs the name resolved with the
+    /// remote DNS, see [`ProxyMessage::NameResolved`].
+    ResolveName(String),
+    /// Protocol version was negotiated with the agent.
+    ProtocolVersion(Version),
+    /// The agent connection is being replaced.
+    ConnectionRefresh(ConnectionRefresh),
 }
 
 #[derive(Error, Debug)]
 #[error(transparent)]
 pub struct SimpleProxyError(#[from] UnexpectedAgentMessage);
 
+/// Who waits for a [`GetAddrInfoResponse`].
+#[derive(Debug)]
+enum AddrInfoRequester {
+    Layer(MessageId, LayerId),
+    /// Name from the [`OutgoingProxy`](super::outgoing::OutgoingProxy) filters, see
+    /// [`SimpleProxyMessage::ResolveName`].
+    OutgoingProxy(String),
+}
+
 /// For passing messages between the layer and the agent without custom internal logic.
 /// Run as a [`BackgroundTask`].
+///
+/// The [`RequestQueue`]s keep the messages sent to the agent, so that they can be sent again on
+/// [`ConnectionRefresh::Start`].
 #[derive(Default)]
 pub struct SimpleProxy {
-    /// For [`GetAddrInfoRequest`]s.
-    addr_info_reqs: RequestQueue,
+    /// For [`GetAddrInfoRequest`]s, made by the layer or by the
+    /// [`OutgoingProxy`](super::outgoing::OutgoingProxy).
+    addr_info_reqs: VecDeque<(AddrInfoRequester, ClientMessage)>,
+    /// From [`SimpleProxyMessage::ResolveName`]s that came before the [`mirrord_protocol`]
+    /// version was negotiated, so that we know which request to use.
+    unsent_names: Vec<String>,
     /// For [`GetEnvVarsRequest`]s.
-    get_env_reqs: RequestQueue,
+    get_env_reqs: RequestQueue<ClientMessage>,
+    /// For [`DnsQueryRequest`]s.
+    dns_query_reqs: RequestQueue<ClientMessage>,
+    /// [`mirrord_protocol`] version negotiated with the agent.
+    /// Determines whether we can use [`GetAddrInfoRequestV2`] and [`DnsQueryRequest`].
+    protocol_version: Option<Version>,
+    /// Used to hide env vars excluded by the mirrord policies from [`GetEnvVarsRequest`]
+    /// responses.
+    policy: ClientPolicy,
+    /// Addresses returned to the layer for the mocked names that don't resolve with the remote
+    /// DNS, see [`OutgoingMocks::placeholders`](super::outgoing::mock::OutgoingMocks::placeholders).
+    mock_placeholders: HashMap<String, IpAddr>,
+}
+
+impl SimpleProxy {
+    pub fn new(policy: ClientPolicy, mock_placeholders: HashMap<String, IpAddr>) -> Self {
+        Self {
+            policy,
+            mock_placeholders,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces a failed lookup of a mocked name with its placeholder address (IPv4 only).
+    fn mock_placeholder(
+        &self,
+        request: &ClientMessage,
+        response: GetAddrInfoResponse,
+    ) -> GetAddrInfoResponse {
+        let node = match request {
+            ClientMessage::GetAddrInfoRequest(request) => &request.node,
+            ClientMessage::GetAddrInfoRequestV2(request)
+                if request.family != AddressFamily::Ipv6Only =>
+            {
+                &request.node
+            }
+            _ => return response,
+        };
+
+        match self.mock_placeholders.get(&node.to_ascii_lowercase()) {
+            Some(ip) if response.0.is_err() => {
+                GetAddrInfoResponse(Ok(DnsLookup(vec![LookupRecord {
+                    name: node.clone(),
+                    ip: *ip,
+                }])))
+            }
+            _ => response,
+        }
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for [`GetAddrInfoRequestV2`].
+    fn addr_info_v2(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| ADDRINFO_V2_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for [`DnsQueryRequest`].
+    fn dns_query(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| DNS_QUERY_VERSION.matches(version))
+    }
+
+    /// Sends the [`GetAddrInfoRequest`] to the agent, using the [`GetAddrInfoRequestV2`] when
+    /// the agent supports it.
+    async fn send_addr_info_req(
+        &mut self,
+        requester: AddrInfoRequester,
+        req: GetAddrInfoRequestV2,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        // Plain IPv4 lookups don't need the new message, which keeps us compatible
+        // with agents and mocks that only know the old one.
+        let message = if req.family != AddressFamily::Ipv4Only && self.addr_info_v2() {
+            ClientMessage::GetAddrInfoRequestV2(req)
+        } else {
+            ClientMessage::GetAddrInfoRequest(GetAddrInfoRequest::from(req))
+        };
+        self.addr_info_reqs.push_back((requester, message.clone()));
+        message_bus.send(message).await;
+    }
+
+    /// Sends a request for the name from the [`OutgoingProxy`](super::outgoing::OutgoingProxy)
+    /// filters, asking for both IPv4 and IPv6 addresses.
+    async fn resolve_name(&mut self, name: String, message_bus: &mut MessageBus<Self>) {
+        let req = GetAddrInfoRequestV2 {
+            node: name.clone(),
+            family: AddressFamily::Both,
+        };
+        self.send_addr_info_req(AddrInfoRequester::OutgoingProxy(name), req, message_bus)
+            .await;
+    }
+
+    /// Sends all requests that were not answered yet to the new agent connection.
+    async fn connection_refresh_started(&self, message_bus: &mut MessageBus<Self>) {
+        message_bus.send(ProxyMessage::ConnectionRefreshAck).await;
+
+        let pending = self
+            .addr_info_reqs
+            .iter()
+            .map(|(_, message)| message)
+            .chain(self.get_env_reqs.iter())
+            .chain(self.dns_query_reqs.iter());
+        for message in pending {
+            message_bus.send(message.clone()).await;
+        }
+    }
 }
 
 impl BackgroundTask for SimpleProxy {
@@ -51,43 +192,122 @@ impl BackgroundTask for SimpleProxy {
 
             match msg {
                 SimpleProxyMessage::AddrInfoReq(message_id, session_id, req) => {
-                    self.addr_info_reqs.push_back(message_id, session_id);
-                    message_bus
-                        .send(ClientMessage::GetAddrInfoRequest(req))
-                        .await;
+                    let requester = AddrInfoRequester::Layer(message_id, session_id);
+                    self.send_addr_info_req(requester, req, message_bus).await;
                 }
                 SimpleProxyMessage::AddrInfoRes(res) => {
-                    let (message_id, layer_id) =
+                    let (requester, request) =
                         self.addr_info_reqs.pop_front().ok_or_else(|| {
                             UnexpectedAgentMessage(DaemonMessage::GetAddrInfoResponse(res.clone()))
                         })?;
+
+                    match requester {
+                        AddrInfoRequester::Layer(message_id, layer_id) => {
+                            message_bus
+                                .send(ToLayer {
+                                    message_id,
+                                    message: ProxyToLayerMessage::GetAddrInfo(
+                                        self.mock_placeholder(&request, res),
+                                    ),
+                                    layer_id,
+                                })
+                                .await;
+                        }
+                        AddrInfoRequester::OutgoingProxy(name) => {
+                            let addresses = match res.0 {
+                                Ok(lookup) => {
+                                    lookup.0.into_iter().map(|record| record.ip).collect()
+                                }
+                                Err(error) => {
+                                    tracing::warn!(
+                                        name,
+                                        %error,
+                                        "Failed to resolve a name from the outgoing filters"
+                                    );
+                                    Vec::<IpAddr>::new()
+                                }
+                            };
+                            message_bus
+                                .send(ProxyMessage::NameResolved(name, addresses))
+                                .await;
+                        }
+                    }
+                }
+                SimpleProxyMessage::ResolveName(name) => {
+                    if self.protocol_version.is_some() {
+                        self.resolve_name(name, message_bus).await;
+                    } else {
+                        self.unsent_names.push(name);
+                    }
+                }
+                SimpleProxyMessage::GetEnvReq(message_id, layer_id, req) => {
+                    let message = ClientMessage::GetEnvVarsRequest(req);
+                    self.get_env_reqs
+                        .push_back_with_data(message_id, layer_id, message.clone());
+                    message_bus.send(message).await;
+                }
+                SimpleProxyMessage::GetEnvRes(mut res) => {
+                    let (message_id, layer_id) =
+                        self.get_env_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(DaemonMessage::GetEnvVarsResponse(res.clone()))
+                        })?;
+                    if let Ok(env) = res.as_mut() {
+                        self.policy.filter_env(env);
+                    }
                     message_bus
                         .send(ToLayer {
                             message_id,
-                            message: ProxyToLayerMessage::GetAddrInfo(res),
+                            message: ProxyToLayerMessage::GetEnv(res),
                             layer_id,
                         })
-                        .await;
+                        .await
                 }
-                SimpleProxyMessage::GetEnvReq(message_id, layer_id, req) => {
-                    self.get_env_reqs.push_back(message_id, layer_id);
-                    message_bus
-                        .send(ClientMessage::GetEnvVarsRequest(req))
-                        .await;
+                SimpleProxyMessage::DnsQueryReq(message_id, layer_id, req) => {
+                    if self.dns_query() {
+                        let message = ClientMessage::DnsQuery(req);
+                        self.dns_query_reqs.push_back_with_data(
+                            message_id,
+                            layer_id,
+                            message.clone(),
+                        );
+                        message_bus.send(message).await;
+                    } else {
+                        // The layer falls back to the local resolver.
+                        message_bus
+                            .send(ToLayer {
+                                message_id,
+                                message: ProxyToLayerMessage::DnsQuery(DnsQueryResponse(Err(
+                                    ResponseError::NotImplemented,
+                                ))),
+                                layer_id,
+                            })
+                            .await;
+                    }
                 }
-                SimpleProxyMessage::GetEnvRes(res) => {
+                SimpleProxyMessage::DnsQueryRes(res) => {
                     let (message_id, layer_id) =
-                        self.get_env_reqs.pop_front().ok_or_else(|| {
-                            UnexpectedAgentMessage(DaemonMessage::GetEnvVarsResponse(res.clone()))
+                        self.dns_query_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(DaemonMessage::DnsQueryResponse(res.clone()))
                         })?;
                     message_bus
                         .send(ToLayer {
                             message_id,
-                            message: ProxyToLayerMessage::GetEnv(res),
+                            message: ProxyToLayerMessage::DnsQuery(res),
                             layer_id,
                         })
                         .await
                 }
+                SimpleProxyMessage::ProtocolVersion(version) => {
+                    self.protocol_version.replace(version);
+
+                    for name in std::mem::take(&mut self.unsent_names) {
+                        self.resolve_name(name, message_bus).await;
+                    }
+                }
+                SimpleProxyMessage::ConnectionRefresh(ConnectionRefresh::Start) => {
+                    self.connection_refresh_started(message_bus).await;
+                }
+                SimpleProxyMessage::ConnectionRefresh(ConnectionRefresh::End) => {}
             }
         }
 
@@ -96,3 +316,218 @@ impl BackgroundTask for SimpleProxy {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::IpAddr};
+
+    use mirrord_intproxy_protocol::{LayerId, ProxyToLayerMessage};
+    use mirrord_protocol::{
+        dns::{
+            AddressFamily, DnsLookup, DnsQueryRequest, DnsQueryResponse, GetAddrInfoRequest,
+            GetAddrInfoRequestV2, GetAddrInfoResponse, LookupRecord,
+        },
+        ClientMessage, ResponseError,
+    };
+    use rstest::rstest;
+    use semver::Version;
+
+    use super::{SimpleProxy, SimpleProxyMessage};
+    use crate::{
+        background_tasks::{BackgroundTasks, TaskUpdate},
+        error::IntProxyError,
+        main_tasks::{MainTaskId, ProxyMessage, ToLayer},
+    };
+
+    /// Verifies that [`GetAddrInfoRequestV2`] is downgraded for agents that don't support it.
+    #[rstest]
+    #[case::old_agent(Version::new(1, 13, 0), false)]
+    #[case::new_agent(Version::new(1, 14, 0), true)]
+    #[tokio::test]
+    async fn addr_info_request_version(#[case] protocol_version: Version, #[case] v2: bool) {
+        let mut tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError> =
+            Default::default();
+        let proxy = tasks.register(SimpleProxy::default(), MainTaskId::SimpleProxy, 32);
+
+        proxy
+            .send(SimpleProxyMessage::ProtocolVersion(protocol_version))
+            .await;
+
+        let request = GetAddrInfoRequestV2 {
+            node: "some.service".to_string(),
+            family: AddressFamily::Both,
+        };
+        proxy
+            .send(SimpleProxyMessage::AddrInfoReq(
+                0xbad,
+                LayerId(0xa55),
+                request.clone(),
+            ))
+            .await;
+
+        let (_, update) = tasks.next().await.unzip();
+        let expected = if v2 {
+            ClientMessage::GetAddrInfoRequestV2(request)
+        } else {
+            ClientMessage::GetAddrInfoRequest(GetAddrInfoRequest {
+                node: "some.service".to_string(),
+            })
+        };
+
+        assert!(
+            matches!(
+                update,
+                Some(TaskUpdate::Message(ProxyMessage::ToAgent(ref message))) if *message == expected
+            ),
+            "Mismatched message for `GetAddrInfoRequest` {update:?}!"
+        );
+    }
+
+    /// Verifies that names from the outgoing filters are resolved once the protocol version is
+    /// known, and that the results go back to the outgoing proxy instead of a layer.
+    #[tokio::test]
+    async fn resolve_outgoing_filter_name() {
+        let mut tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError> =
+            Default::default();
+        let proxy = tasks.register(SimpleProxy::default(), MainTaskId::SimpleProxy, 32);
+
+        proxy
+            .send(SimpleProxyMessage::ResolveName(
+                "payments.default".to_string(),
+            ))
+            .await;
+        proxy
+            .send(SimpleProxyMessage::ProtocolVersion(Version::new(1, 14, 0)))
+            .await;
+
+        let (_, update) = tasks.next().await.unzip();
+        let expected = ClientMessage::GetAddrInfoRequestV2(GetAddrInfoRequestV2 {
+            node: "payments.default".to_string(),
+            family: AddressFamily::Both,
+        });
+        assert!(
+            matches!(
+                update,
+                Some(TaskUpdate::Message(ProxyMessage::ToAgent(ref message))) if *message == expected
+            ),
+            "Mismatched message for `ResolveName` {update:?}!"
+        );
+
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        proxy
+            .send(SimpleProxyMessage::AddrInfoRes(GetAddrInfoResponse(Ok(
+                DnsLookup(vec![LookupRecord {
+                    name: "payments.default.svc.cluster.local".to_string(),
+                    ip,
+                }]),
+            ))))
+            .await;
+
+        let (_, update) = tasks.next().await.unzip();
+        assert!(
+            matches!(
+                update,
+                Some(TaskUpdate::Message(ProxyMessage::NameResolved(ref name, ref addresses)))
+                    if name == "payments.default" && *addresses == [ip]
+            ),
+            "Mismatched message for `GetAddrInfoResponse` {update:?}!"
+        );
+    }
+
+    /// Verifies that the layer gets the placeholder address of a mocked name that does not
+    /// resolve with the remote DNS.
+    #[tokio::test]
+    async fn mock_placeholder() {
+        let mut tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError> =
+            Default::default();
+        let placeholder: IpAddr = "198.18.0.1".parse().unwrap();
+        let proxy = tasks.register(
+            SimpleProxy::new(
+                Default::default(),
+                HashMap::from([("orders.default".to_string(), placeholder)]),
+            ),
+            MainTaskId::SimpleProxy,
+            32,
+        );
+
+        proxy
+            .send(SimpleProxyMessage::ProtocolVersion(Version::new(1, 14, 0)))
+            .await;
+        proxy
+            .send(SimpleProxyMessage::AddrInfoReq(
+                0xbad,
+                LayerId(0xa55),
+                GetAddrInfoRequestV2 {
+                    node: "orders.default".to_string(),
+                    family: AddressFamily::Both,
+                },
+            ))
+            .await;
+        let _ = tasks.next().await;
+
+        proxy
+            .send(SimpleProxyMessage::AddrInfoRes(GetAddrInfoResponse(Err(
+                ResponseError::NotFound(0),
+            ))))
+            .await;
+
+        let (_, update) = tasks.next().await.unzip();
+        assert!(
+            matches!(
+                update,
+                Some(TaskUpdate::Message(ProxyMessage::ToLayer(ToLayer {
+                    message: ProxyToLayerMessage::GetAddrInfo(GetAddrInfoResponse(Ok(ref lookup))),
+                    ..
+                }))) if lookup.0.iter().map(|record| record.ip).eq([placeholder])
+            ),
+            "Mismatched message for `GetAddrInfoResponse` {update:?}!"
+        );
+    }
+
+    /// Verifies that [`DnsQueryRequest`] is rejected without reaching agents that don't support
+    /// it.
+    #[rstest]
+    #[case::old_agent(Version::new(1, 22, 0), false)]
+    #[case::new_agent(Version::new(1, 23, 0), true)]
+    #[tokio::test]
+    async fn dns_query_version(#[case] protocol_version: Version, #[case] supported: bool) {
+        let mut tasks: BackgroundTasks<MainTaskId, ProxyMessage, IntProxyError> =
+            Default::default();
+        let proxy = tasks.register(SimpleProxy::default(), MainTaskId::SimpleProxy, 32);
+
+        proxy
+            .send(SimpleProxyMessage::ProtocolVersion(protocol_version))
+            .await;
+
+        let request = DnsQueryRequest {
+            name: "_grpc._tcp.some.service".to_string(),
+            record_type: 33,
+            search: true,
+        };
+        proxy
+            .send(SimpleProxyMessage::DnsQueryReq(
+                0xbad,
+                LayerId(0xa55),
+                request.clone(),
+            ))
+            .await;
+
+        let (_, update) = tasks.next().await.unzip();
+        let expected = if supported {
+            ProxyMessage::ToAgent(ClientMessage::DnsQuery(request))
+        } else {
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0xbad,
+                layer_id: LayerId(0xa55),
+                message: ProxyToLayerMessage::DnsQuery(DnsQueryResponse(Err(
+                    ResponseError::NotImplemented,
+                ))),
+            })
+        };
+
+        assert!(
+            matches!(update, Some(TaskUpdate::Message(ref message)) if *message == expected),
+            "Mismatched message for `DnsQueryRequest` {update:?}!"
+        );
+    }
+}
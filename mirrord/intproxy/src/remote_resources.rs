@@ -107,6 +107,11 @@ where
             })
     }
 
+    /// Returns an [`Iterator`] over all resources held by any layer instance.
+    pub(crate) fn iter(&self) -> impl '_ + Iterator<Item = &T> {
+        self.counts.keys()
+    }
+
     /// Adds the given resource to the layer instance with the given [`LayerId`].
     ///
     /// Used when the layer opens a resource, e.g. with
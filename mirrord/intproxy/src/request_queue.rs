@@ -69,4 +69,16 @@ impl<T: fmt::Debug> RequestQueue<T> {
     pub fn pop_front_with_data(&mut self) -> Option<(MessageId, LayerId, T)> {
         self.inner.pop_front()
     }
+
+    /// Save the request at the front of this queue, for requests that have to be answered before
+    /// the ones already in the queue.
+    #[tracing::instrument(level = Level::TRACE)]
+    pub fn push_front_with_data(&mut self, message_id: MessageId, layer_id: LayerId, data: T) {
+        self.inner.push_front((message_id, layer_id, data));
+    }
+
+    /// Returns the data of all requests in this queue, starting from the front.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.inner.iter().map(|(_, _, data)| data)
+    }
 }
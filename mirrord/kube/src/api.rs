@@ -5,8 +5,11 @@ use tokio::sync::mpsc;
 use tracing::Instrument;
 
 pub mod container;
+pub mod env;
 pub mod kubernetes;
+pub mod multiplex;
 pub mod runtime;
+pub mod service;
 
 const CONNECTION_CHANNEL_SIZE: usize = 1000;
 
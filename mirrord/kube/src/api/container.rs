@@ -1,7 +1,7 @@
 use std::{collections::HashSet, sync::LazyLock};
 
 use k8s_openapi::api::core::v1::{ContainerStatus, Pod};
-use mirrord_config::agent::AgentConfig;
+use mirrord_config::{agent::AgentConfig, feature::network::incoming::tls::IncomingTlsConfig};
 use mirrord_progress::Progress;
 use mirrord_protocol::MeshVendor;
 use rand::{
@@ -44,10 +44,19 @@ pub struct ContainerParams {
     /// the agent container.
     pub tls_cert: Option<String>,
     pub pod_ips: Option<String>,
+    /// Whether the agent should steal IPv6 traffic as well (`feature.network.ipv6`).
+    pub support_ipv6: bool,
+    /// TLS termination on stolen ports (`feature.network.incoming.tls`).
+    pub steal_tls: Option<IncomingTlsConfig>,
 }
 
 impl ContainerParams {
-    pub fn new(tls_cert: Option<String>, pod_ips: Option<String>) -> ContainerParams {
+    pub fn new(
+        tls_cert: Option<String>,
+        pod_ips: Option<String>,
+        support_ipv6: bool,
+        steal_tls: Option<IncomingTlsConfig>,
+    ) -> ContainerParams {
         let port: u16 = rand::thread_rng().gen_range(30000..=65535);
         let gid: u16 = rand::thread_rng().gen_range(3000..u16::MAX);
 
@@ -64,6 +73,8 @@ impl ContainerParams {
             port,
             tls_cert,
             pod_ips,
+            support_ipv6,
+            steal_tls,
         }
     }
 }
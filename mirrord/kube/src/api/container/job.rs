@@ -247,6 +247,8 @@ mod test {
             gid: 13,
             tls_cert: None,
             pod_ips: None,
+            support_ipv6: false,
+            steal_tls: None,
         };
 
         let update = JobVariant::new(&agent, &params).as_update();
@@ -336,6 +338,8 @@ mod test {
             gid: 13,
             tls_cert: None,
             pod_ips: None,
+            support_ipv6: false,
+            steal_tls: None,
         };
 
         let update = JobTargetedVariant::new(
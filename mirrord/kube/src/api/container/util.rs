@@ -1,7 +1,7 @@
 use std::sync::LazyLock;
 
 use futures::{AsyncBufReadExt, TryStreamExt};
-use k8s_openapi::api::core::v1::{EnvVar, Pod, Toleration};
+use k8s_openapi::api::core::v1::{EnvVar, EnvVarSource, Pod, SecretKeySelector, Toleration};
 use kube::{api::LogParams, Api};
 use mirrord_config::agent::{AgentConfig, LinuxCapability};
 use mirrord_protocol::{AGENT_NETWORK_INTERFACE_ENV, AGENT_OPERATOR_CERT_ENV};
@@ -67,6 +67,59 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &&ContainerParams) -> Vec<E
         env.push(("MIRRORD_AGENT_POD_IPS".to_string(), pod_ips));
     }
 
+    if params.support_ipv6 {
+        env.push(("MIRRORD_AGENT_SUPPORT_IPV6".to_string(), "true".to_string()));
+    }
+
+    if let Some(tls) = params.steal_tls.as_ref() {
+        let ports = tls
+            .ports
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        env.push(("MIRRORD_AGENT_STEAL_TLS_PORTS".to_string(), ports));
+
+        if let Some(path) = tls.certificate_path.as_ref() {
+            env.push((
+                "MIRRORD_AGENT_STEAL_TLS_CERTIFICATE_PATH".to_string(),
+                path.to_string_lossy().into_owned(),
+            ));
+        }
+        if let Some(path) = tls.key_path.as_ref() {
+            env.push((
+                "MIRRORD_AGENT_STEAL_TLS_KEY_PATH".to_string(),
+                path.to_string_lossy().into_owned(),
+            ));
+        }
+    }
+
+    // The certificate and the key are injected by Kubernetes, so that they don't show in the
+    // agent's spec.
+    let secret_env = params
+        .steal_tls
+        .as_ref()
+        .and_then(|tls| tls.secret.as_ref())
+        .into_iter()
+        .flat_map(|secret| {
+            [
+                ("MIRRORD_AGENT_STEAL_TLS_CERTIFICATE_PEM", "tls.crt"),
+                ("MIRRORD_AGENT_STEAL_TLS_KEY_PEM", "tls.key"),
+            ]
+            .map(|(name, key)| EnvVar {
+                name: name.to_string(),
+                value_from: Some(EnvVarSource {
+                    secret_key_ref: Some(SecretKeySelector {
+                        name: secret.clone(),
+                        key: key.to_string(),
+                        optional: None,
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        });
+
     env.into_iter()
         .chain(
             params
@@ -79,6 +132,7 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &&ContainerParams) -> Vec<E
             value: Some(value),
             ..Default::default()
         })
+        .chain(secret_env)
         .collect::<Vec<_>>()
 }
 
@@ -138,6 +192,11 @@ pub(super) async fn wait_for_agent_startup(
 
 #[cfg(test)]
 mod test {
+    use mirrord_config::{
+        agent::AgentFileConfig,
+        config::{ConfigContext, MirrordConfig},
+        feature::network::incoming::tls::IncomingTlsConfig,
+    };
     use rstest::rstest;
 
     use super::*;
@@ -150,4 +209,43 @@ mod test {
 
         assert_eq!(captures.get(2).map(|c| c.as_str()), version);
     }
+
+    /// Verifies that the TLS certificate and key from a Secret are not put in the agent's spec.
+    #[test]
+    fn steal_tls_secret_env() {
+        let agent = AgentFileConfig::default()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+        let params = ContainerParams {
+            name: "foobar".to_string(),
+            port: 3000,
+            gid: 13,
+            tls_cert: None,
+            pod_ips: None,
+            support_ipv6: false,
+            steal_tls: Some(IncomingTlsConfig {
+                ports: vec![443, 8443],
+                secret: Some("service-tls".to_string()),
+                ..Default::default()
+            }),
+        };
+
+        let env = agent_env(&agent, &&params);
+        let find = |name: &str| env.iter().find(|var| var.name == name).unwrap();
+
+        assert_eq!(
+            find("MIRRORD_AGENT_STEAL_TLS_PORTS").value.as_deref(),
+            Some("443,8443")
+        );
+
+        let key = find("MIRRORD_AGENT_STEAL_TLS_KEY_PEM");
+        assert!(key.value.is_none());
+        let secret_ref = key
+            .value_from
+            .as_ref()
+            .and_then(|source| source.secret_key_ref.as_ref())
+            .unwrap();
+        assert_eq!(secret_ref.name, "service-tls");
+        assert_eq!(secret_ref.key, "tls.key");
+    }
 }
@@ -0,0 +1,654 @@
+//! Resolves the environment of a container from its pod spec, without looking at the running
+//! process, see [`env_from_pod_spec`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use k8s_openapi::api::core::v1::{
+    ConfigMap, Container, EnvVarSource, Pod, PodTemplateSpec, Secret,
+};
+use kube::{api::ListParams, Api, Client};
+use mirrord_config::target::Target;
+use tracing::Level;
+
+use crate::{
+    api::{container::SKIP_NAMES, kubernetes::get_k8s_resource_api},
+    error::{KubeApiError, Result},
+    resolved::{ResolvedResource, ResolvedTarget},
+};
+
+/// Data of the [`ConfigMap`]s and [`Secret`]s referenced by the environment of a container,
+/// by name. Resources that don't exist are missing from the maps.
+#[derive(Default, Debug)]
+struct EnvSources {
+    config_maps: HashMap<String, BTreeMap<String, String>>,
+    secrets: HashMap<String, BTreeMap<String, String>>,
+}
+
+/// Resolves the environment variables of the `target` container, as defined in its pod spec.
+///
+/// Reads the `env` and `envFrom` of the container, fetching the referenced [`ConfigMap`]s and
+/// [`Secret`]s. As the target does not have to be running, workloads use their pod template,
+/// which means that `fieldRef`s to the pod status (and name, in most cases) can't be resolved.
+/// Such variables, and the ones referencing container resources, are left out.
+///
+/// A [`Target::Service`] uses the first pod selected by the service, ready or not.
+///
+/// Like the kubelet, only the `data` of [`ConfigMap`]s is used, `binaryData` is ignored.
+///
+/// The resources are read with the given `client`, which needs permission to get the referenced
+/// [`ConfigMap`]s and [`Secret`]s.
+///
+/// Only the names of the resolved variables are logged, as the values may come from [`Secret`]s.
+#[tracing::instrument(level = Level::DEBUG, skip(client), err)]
+pub async fn env_from_pod_spec(
+    client: &Client,
+    target: &Target,
+    namespace: Option<&str>,
+) -> Result<HashMap<String, String>> {
+    let resolved = ResolvedTarget::new(client, target, namespace).await?;
+    let pod = pod_from_target(client, &resolved, namespace).await?;
+    let container = choose_container(&pod, resolved.container())?;
+    let sources = fetch_sources(client, container, namespace).await?;
+
+    let env = resolve_env(&pod, container, &sources)?;
+    tracing::debug!(names = ?env.keys().collect::<Vec<_>>(), "Resolved env from the pod spec.");
+
+    Ok(env)
+}
+
+/// Returns the [`Pod`] of the `target`, or a [`Pod`] built from the pod template of the target.
+pub(crate) async fn pod_from_target(
+    client: &Client,
+    target: &ResolvedTarget<false>,
+    namespace: Option<&str>,
+) -> Result<Pod> {
+    let template = match target {
+        ResolvedTarget::Pod(ResolvedResource { resource, .. }) => return Ok(resource.clone()),
+        ResolvedTarget::Deployment(ResolvedResource { resource, .. }) => resource
+            .spec
+            .as_ref()
+            .map(|spec| spec.template.clone())
+            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec"))?,
+        ResolvedTarget::Rollout(ResolvedResource { resource, .. }) => {
+            resource.get_pod_template(client).await?.into_owned()
+        }
+        ResolvedTarget::Job(ResolvedResource { resource, .. }) => resource
+            .spec
+            .as_ref()
+            .map(|spec| spec.template.clone())
+            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec"))?,
+        ResolvedTarget::CronJob(ResolvedResource { resource, .. }) => resource
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.job_template.spec.as_ref())
+            .map(|spec| spec.template.clone())
+            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec.jobTemplate.spec"))?,
+        ResolvedTarget::StatefulSet(ResolvedResource { resource, .. }) => resource
+            .spec
+            .as_ref()
+            .map(|spec| spec.template.clone())
+            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec"))?,
+        ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => resource
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.clone())
+            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec.template"))?,
+        ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }) => resource
+            .spec
+            .as_ref()
+            .map(|spec| spec.template.clone())
+            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec"))?,
+        ResolvedTarget::Service(ResolvedResource { resource, .. }) => {
+            let selector = resource
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.selector.as_ref())
+                .ok_or_else(|| KubeApiError::missing_field(resource, ".spec.selector"))?;
+
+            let list_params = ListParams {
+                label_selector: Some(
+                    selector
+                        .iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                ..Default::default()
+            };
+
+            return get_k8s_resource_api::<Pod>(client, namespace)
+                .list(&list_params)
+                .await?
+                .items
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    KubeApiError::invalid_state(resource, "no pods matching labels found")
+                });
+        }
+        ResolvedTarget::Targetless(..) => {
+            return Err(KubeApiError::PodSpecEnv(
+                "a targetless run has no pod spec".to_string(),
+            ))
+        }
+    };
+
+    let PodTemplateSpec { metadata, spec } = template;
+    let mut metadata = metadata.unwrap_or_default();
+    metadata.namespace = target.namespace().map(ToOwned::to_owned);
+
+    Ok(Pod {
+        metadata,
+        spec,
+        status: None,
+    })
+}
+
+/// Picks the container named `container_name`, or the first container that is not a known
+/// sidecar, like [`choose_container`](crate::api::container::choose_container) does for running
+/// pods.
+fn choose_container<'a>(pod: &'a Pod, container_name: Option<&str>) -> Result<&'a Container> {
+    let containers = pod
+        .spec
+        .as_ref()
+        .map(|spec| spec.containers.as_slice())
+        .unwrap_or_default();
+
+    let container = match container_name {
+        Some(name) => containers.iter().find(|container| container.name == name),
+        None => containers
+            .iter()
+            .find(|container| !SKIP_NAMES.contains(container.name.as_str()))
+            .or_else(|| containers.first()),
+    };
+
+    container.ok_or_else(|| match container_name {
+        Some(name) => KubeApiError::PodSpecEnv(format!("container `{name}` not found")),
+        None => KubeApiError::PodSpecEnv("no containers found".to_string()),
+    })
+}
+
+/// Fetches all [`ConfigMap`]s and [`Secret`]s referenced by the environment of the `container`.
+async fn fetch_sources(
+    client: &Client,
+    container: &Container,
+    namespace: Option<&str>,
+) -> Result<EnvSources> {
+    let mut config_map_names = Vec::new();
+    let mut secret_names = Vec::new();
+
+    for env_from in container.env_from.iter().flatten() {
+        config_map_names.extend(env_from.config_map_ref.as_ref().map(|r| &r.name));
+        secret_names.extend(env_from.secret_ref.as_ref().map(|r| &r.name));
+    }
+
+    for value_from in container
+        .env
+        .iter()
+        .flatten()
+        .filter_map(|var| var.value_from.as_ref())
+    {
+        config_map_names.extend(value_from.config_map_key_ref.as_ref().map(|r| &r.name));
+        secret_names.extend(value_from.secret_key_ref.as_ref().map(|r| &r.name));
+    }
+
+    let mut sources = EnvSources::default();
+
+    let config_map_api: Api<ConfigMap> = get_k8s_resource_api(client, namespace);
+    for name in config_map_names {
+        if sources.config_maps.contains_key(name) {
+            continue;
+        }
+
+        if let Some(config_map) = config_map_api.get_opt(name).await? {
+            sources
+                .config_maps
+                .insert(name.clone(), config_map.data.unwrap_or_default());
+        }
+    }
+
+    let secret_api: Api<Secret> = get_k8s_resource_api(client, namespace);
+    for name in secret_names {
+        if sources.secrets.contains_key(name) {
+            continue;
+        }
+
+        if let Some(secret) = secret_api.get_opt(name).await? {
+            let data = secret
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, value)| (key, String::from_utf8_lossy(&value.0).into_owned()))
+                .collect();
+            sources.secrets.insert(name.clone(), data);
+        }
+    }
+
+    Ok(sources)
+}
+
+/// Resolves the environment of the `container` the same way the kubelet does: `envFrom` sources
+/// come first, in order, and are overridden by the `env` entries. `$(VAR)` references in `env`
+/// values are expanded with the variables defined before them.
+///
+/// Fails when a required (not `optional`) [`ConfigMap`], [`Secret`], or key is missing, as the
+/// container would not start either.
+fn resolve_env(
+    pod: &Pod,
+    container: &Container,
+    sources: &EnvSources,
+) -> Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+
+    for env_from in container.env_from.iter().flatten() {
+        let prefix = env_from.prefix.as_deref().unwrap_or_default();
+
+        let data = match (&env_from.config_map_ref, &env_from.secret_ref) {
+            (Some(config_map_ref), _) => source_data(
+                &sources.config_maps,
+                "ConfigMap",
+                &config_map_ref.name,
+                config_map_ref.optional,
+            )?,
+            (None, Some(secret_ref)) => source_data(
+                &sources.secrets,
+                "Secret",
+                &secret_ref.name,
+                secret_ref.optional,
+            )?,
+            (None, None) => None,
+        };
+
+        env.extend(
+            data.into_iter()
+                .flatten()
+                .map(|(key, value)| (format!("{prefix}{key}"), value.clone())),
+        );
+    }
+
+    for var in container.env.iter().flatten() {
+        let value = match (&var.value, &var.value_from) {
+            (Some(value), _) => Some(expand(value, &env)),
+            (None, Some(value_from)) => value_from_source(pod, value_from, sources)?,
+            (None, None) => Some(String::new()),
+        };
+
+        match value {
+            Some(value) => {
+                env.insert(var.name.clone(), value);
+            }
+            None => {
+                tracing::debug!(name = var.name, "Cannot resolve env var from the pod spec.");
+            }
+        }
+    }
+
+    Ok(env)
+}
+
+/// Returns the data of the `kind` resource `name`, `None` when it's missing but `optional`.
+fn source_data<'a>(
+    data: &'a HashMap<String, BTreeMap<String, String>>,
+    kind: &str,
+    name: &str,
+    optional: Option<bool>,
+) -> Result<Option<&'a BTreeMap<String, String>>> {
+    match data.get(name) {
+        Some(data) => Ok(Some(data)),
+        None if optional.unwrap_or_default() => Ok(None),
+        None => Err(KubeApiError::PodSpecEnv(format!(
+            "{kind} `{name}` not found"
+        ))),
+    }
+}
+
+/// Resolves an `env[].valueFrom`, `None` when it can't be resolved from the pod spec.
+fn value_from_source(
+    pod: &Pod,
+    value_from: &EnvVarSource,
+    sources: &EnvSources,
+) -> Result<Option<String>> {
+    let (data, kind, name, key, optional) =
+        match (&value_from.config_map_key_ref, &value_from.secret_key_ref) {
+            (Some(selector), _) => (
+                &sources.config_maps,
+                "ConfigMap",
+                &selector.name,
+                &selector.key,
+                selector.optional,
+            ),
+            (None, Some(selector)) => (
+                &sources.secrets,
+                "Secret",
+                &selector.name,
+                &selector.key,
+                selector.optional,
+            ),
+            (None, None) => {
+                return Ok(value_from
+                    .field_ref
+                    .as_ref()
+                    .and_then(|field_ref| field_value(pod, &field_ref.field_path)))
+            }
+        };
+
+    let Some(data) = source_data(data, kind, name, optional)? else {
+        return Ok(None);
+    };
+
+    match data.get(key) {
+        Some(value) => Ok(Some(value.clone())),
+        None if optional.unwrap_or_default() => Ok(None),
+        None => Err(KubeApiError::PodSpecEnv(format!(
+            "key `{key}` not found in {kind} `{name}`"
+        ))),
+    }
+}
+
+/// Returns the value of a `fieldRef`, using the same field paths as the downward API.
+fn field_value(pod: &Pod, field_path: &str) -> Option<String> {
+    let metadata = &pod.metadata;
+
+    if let Some(key) = field_path
+        .strip_prefix("metadata.labels['")
+        .and_then(|rest| rest.strip_suffix("']"))
+    {
+        return metadata.labels.as_ref()?.get(key).cloned();
+    }
+
+    if let Some(key) = field_path
+        .strip_prefix("metadata.annotations['")
+        .and_then(|rest| rest.strip_suffix("']"))
+    {
+        return metadata.annotations.as_ref()?.get(key).cloned();
+    }
+
+    match field_path {
+        "metadata.name" => metadata.name.clone(),
+        "metadata.namespace" => metadata.namespace.clone(),
+        "metadata.uid" => metadata.uid.clone(),
+        "spec.nodeName" => pod.spec.as_ref()?.node_name.clone(),
+        "spec.serviceAccountName" => pod.spec.as_ref()?.service_account_name.clone(),
+        "status.hostIP" => pod.status.as_ref()?.host_ip.clone(),
+        "status.podIP" => pod.status.as_ref()?.pod_ip.clone(),
+        "status.podIPs" => pod.status.as_ref()?.pod_ips.as_ref().map(|ips| {
+            ips.iter()
+                .map(|pod_ip| pod_ip.ip.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        }),
+        _ => None,
+    }
+}
+
+/// Expands the `$(VAR)` references in `value` with the variables in `env`.
+///
+/// `$$` escapes a `$`, and references to unknown variables are left as they are.
+fn expand(value: &str, env: &HashMap<String, String>) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(position) = rest.find('$') {
+        expanded.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('(')
+            .and_then(|reference| reference.split_once(')'))
+        {
+            match env.get(name) {
+                Some(value) => expanded.push_str(value),
+                None => {
+                    expanded.push_str("$(");
+                    expanded.push_str(name);
+                    expanded.push(')');
+                }
+            }
+            rest = after;
+        } else {
+            expanded.push('$');
+        }
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod test {
+    use k8s_openapi::api::core::v1::{
+        ConfigMapEnvSource, ConfigMapKeySelector, EnvFromSource, EnvVar, ObjectFieldSelector,
+        PodSpec, SecretEnvSource, SecretKeySelector,
+    };
+    use kube::api::ObjectMeta;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn var(name: &str, value: &str) -> EnvVar {
+        EnvVar {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+            value_from: None,
+        }
+    }
+
+    fn var_from(name: &str, value_from: EnvVarSource) -> EnvVar {
+        EnvVar {
+            name: name.to_string(),
+            value: None,
+            value_from: Some(value_from),
+        }
+    }
+
+    fn sources() -> EnvSources {
+        EnvSources {
+            config_maps: HashMap::from([(
+                "app-config".to_string(),
+                BTreeMap::from([
+                    ("LOG_LEVEL".to_string(), "info".to_string()),
+                    ("DB_HOST".to_string(), "db.svc".to_string()),
+                ]),
+            )]),
+            secrets: HashMap::from([(
+                "app-secret".to_string(),
+                BTreeMap::from([("password".to_string(), "hunter2".to_string())]),
+            )]),
+        }
+    }
+
+    fn pod(container: Container) -> Pod {
+        Pod {
+            metadata: ObjectMeta {
+                namespace: Some("prod".to_string()),
+                labels: Some(BTreeMap::from([("app".to_string(), "api".to_string())])),
+                ..Default::default()
+            },
+            spec: Some(PodSpec {
+                containers: vec![container],
+                ..Default::default()
+            }),
+            status: None,
+        }
+    }
+
+    #[test]
+    fn resolve_env_from_spec() {
+        let container = Container {
+            name: "app".to_string(),
+            env_from: Some(vec![
+                EnvFromSource {
+                    config_map_ref: Some(ConfigMapEnvSource {
+                        name: "app-config".to_string(),
+                        optional: None,
+                    }),
+                    ..Default::default()
+                },
+                EnvFromSource {
+                    secret_ref: Some(SecretEnvSource {
+                        name: "app-secret".to_string(),
+                        optional: None,
+                    }),
+                    prefix: Some("SECRET_".to_string()),
+                    ..Default::default()
+                },
+                EnvFromSource {
+                    config_map_ref: Some(ConfigMapEnvSource {
+                        name: "missing-config".to_string(),
+                        optional: Some(true),
+                    }),
+                    ..Default::default()
+                },
+            ]),
+            env: Some(vec![
+                var("LOG_LEVEL", "debug"),
+                var_from(
+                    "DB_PASSWORD",
+                    EnvVarSource {
+                        secret_key_ref: Some(SecretKeySelector {
+                            name: "app-secret".to_string(),
+                            key: "password".to_string(),
+                            optional: None,
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                var("DB_URL", "postgres://$(DB_HOST):5432?level=$(LOG_LEVEL)"),
+                var_from(
+                    "NAMESPACE",
+                    EnvVarSource {
+                        field_ref: Some(ObjectFieldSelector {
+                            field_path: "metadata.namespace".to_string(),
+                            api_version: None,
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                var_from(
+                    "APP",
+                    EnvVarSource {
+                        field_ref: Some(ObjectFieldSelector {
+                            field_path: "metadata.labels['app']".to_string(),
+                            api_version: None,
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                var_from(
+                    "POD_IP",
+                    EnvVarSource {
+                        field_ref: Some(ObjectFieldSelector {
+                            field_path: "status.podIP".to_string(),
+                            api_version: None,
+                        }),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let pod = pod(container);
+        let container = choose_container(&pod, None).unwrap();
+
+        let env = resolve_env(&pod, container, &sources()).unwrap();
+
+        assert_eq!(
+            env,
+            HashMap::from([
+                ("LOG_LEVEL".to_string(), "debug".to_string()),
+                ("DB_HOST".to_string(), "db.svc".to_string()),
+                ("SECRET_password".to_string(), "hunter2".to_string()),
+                ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+                (
+                    "DB_URL".to_string(),
+                    "postgres://db.svc:5432?level=debug".to_string()
+                ),
+                ("NAMESPACE".to_string(), "prod".to_string()),
+                ("APP".to_string(), "api".to_string()),
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::missing_config_map(ConfigMapKeySelector {
+        name: "missing-config".to_string(),
+        key: "LOG_LEVEL".to_string(),
+        optional: None,
+    }, false)]
+    #[case::missing_key(ConfigMapKeySelector {
+        name: "app-config".to_string(),
+        key: "MISSING".to_string(),
+        optional: None,
+    }, false)]
+    #[case::optional_missing_key(ConfigMapKeySelector {
+        name: "app-config".to_string(),
+        key: "MISSING".to_string(),
+        optional: Some(true),
+    }, true)]
+    fn resolve_env_missing_reference(#[case] selector: ConfigMapKeySelector, #[case] ok: bool) {
+        let container = Container {
+            name: "app".to_string(),
+            env: Some(vec![var_from(
+                "VALUE",
+                EnvVarSource {
+                    config_map_key_ref: Some(selector),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let pod = pod(container);
+        let container = choose_container(&pod, Some("app")).unwrap();
+
+        let result = resolve_env(&pod, container, &sources());
+
+        assert_eq!(result.is_ok(), ok, "{result:?}");
+        if let Ok(env) = result {
+            assert!(env.is_empty());
+        }
+    }
+
+    #[test]
+    fn choose_container_skips_sidecars() {
+        let pod = Pod {
+            spec: Some(PodSpec {
+                containers: vec![
+                    Container {
+                        name: "istio-proxy".to_string(),
+                        ..Default::default()
+                    },
+                    Container {
+                        name: "app".to_string(),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(choose_container(&pod, None).unwrap().name, "app");
+        assert_eq!(
+            choose_container(&pod, Some("istio-proxy")).unwrap().name,
+            "istio-proxy"
+        );
+        assert!(choose_container(&pod, Some("other")).is_err());
+    }
+
+    #[rstest]
+    #[case("$(HOST):$(PORT)", "db.svc:5432")]
+    #[case("$$(HOST)", "$(HOST)")]
+    #[case("$(MISSING)/$(HOST", "$(MISSING)/$(HOST")]
+    #[case("cost: 5$", "cost: 5$")]
+    fn expand_references(#[case] value: &str, #[case] expanded: &str) {
+        let env = HashMap::from([
+            ("HOST".to_string(), "db.svc".to_string()),
+            ("PORT".to_string(), "5432".to_string()),
+        ]);
+
+        assert_eq!(expand(value, &env), expanded);
+    }
+}
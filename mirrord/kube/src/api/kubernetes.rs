@@ -8,6 +8,7 @@ use kube::{
 };
 use mirrord_config::{
     agent::AgentConfig,
+    feature::network::incoming::tls::IncomingTlsConfig,
     target::{Target, TargetConfig},
     LayerConfig,
 };
@@ -161,11 +162,15 @@ impl KubernetesAPI {
     /// * `tls_cert` - value for
     ///   [`AGENT_OPERATOR_CERT_ENV`](mirrord_protocol::AGENT_OPERATOR_CERT_ENV), for creating an
     ///   agent from the operator. In usage from this repo this is always `None`.
+    /// * `support_ipv6` - whether the agent should also steal IPv6 traffic
+    /// * `steal_tls` - TLS termination on stolen ports
     #[tracing::instrument(level = "trace", skip(self), ret, err)]
     pub async fn create_agent_params(
         &self,
         target: &TargetConfig,
         tls_cert: Option<String>,
+        support_ipv6: bool,
+        steal_tls: Option<IncomingTlsConfig>,
     ) -> Result<(ContainerParams, Option<RuntimeData>), KubeApiError> {
         let runtime_data = match target.path.as_ref().unwrap_or(&Target::Targetless) {
             Target::Targetless => None,
@@ -175,8 +180,19 @@ impl KubernetesAPI {
                 .into(),
         };
 
+        let params =
+            Self::container_params(runtime_data.as_ref(), tls_cert, support_ipv6, steal_tls);
+
+        Ok((params, runtime_data))
+    }
+
+    fn container_params(
+        runtime_data: Option<&RuntimeData>,
+        tls_cert: Option<String>,
+        support_ipv6: bool,
+        steal_tls: Option<IncomingTlsConfig>,
+    ) -> ContainerParams {
         let pod_ips = runtime_data
-            .as_ref()
             .filter(|runtime_data| !runtime_data.pod_ips.is_empty())
             .map(|runtime_data| {
                 runtime_data
@@ -187,9 +203,7 @@ impl KubernetesAPI {
                     .join(",")
             });
 
-        let params = ContainerParams::new(tls_cert, pod_ips);
-
-        Ok((params, runtime_data))
+        ContainerParams::new(tls_cert, pod_ips, support_ipv6, steal_tls)
     }
 
     /// # Params
@@ -209,7 +223,91 @@ impl KubernetesAPI {
     where
         P: Progress + Send + Sync,
     {
-        let (params, runtime_data) = self.create_agent_params(target, tls_cert).await?;
+        let support_ipv6 = config
+            .map(|config| config.feature.network.ipv6)
+            .unwrap_or_default();
+        let steal_tls = config.and_then(|config| config.feature.network.incoming.tls.clone());
+        let (params, runtime_data) = self
+            .create_agent_params(target, tls_cert, support_ipv6, steal_tls)
+            .await?;
+
+        self.spawn_agent(progress, config, params, runtime_data)
+            .await
+    }
+
+    /// Creates one agent for each ready pod of the target (`agent.multi_pod`), see
+    /// [`RuntimeDataProvider::all_runtime_data`].
+    ///
+    /// The first agent is the one that handles everything that is not incoming traffic, so it's
+    /// the one created for the same pod [`KubernetesAPI::create_agent`] would pick.
+    ///
+    /// For a targetless run this creates a single agent.
+    #[tracing::instrument(level = "trace", skip(self, progress))]
+    pub async fn create_agents<P>(
+        &self,
+        progress: &mut P,
+        target: &TargetConfig,
+        config: Option<&LayerConfig>,
+        tls_cert: Option<String>,
+    ) -> Result<Vec<AgentKubernetesConnectInfo>, KubeApiError>
+    where
+        P: Progress + Send + Sync,
+    {
+        let path = match target.path.as_ref() {
+            None | Some(Target::Targetless) => {
+                return self
+                    .create_agent(progress, target, config, tls_cert)
+                    .await
+                    .map(|connect_info| vec![connect_info]);
+            }
+            Some(path) => path,
+        };
+
+        let support_ipv6 = config
+            .map(|config| config.feature.network.ipv6)
+            .unwrap_or_default();
+        let steal_tls = config.and_then(|config| config.feature.network.incoming.tls.clone());
+
+        let all_runtime_data = path
+            .all_runtime_data(&self.client, target.namespace.as_deref())
+            .await?;
+        if all_runtime_data.len() > 1 {
+            progress.info(&format!(
+                "target has {} ready pods, spawning one agent for each",
+                all_runtime_data.len()
+            ));
+        }
+
+        let mut agents = Vec::with_capacity(all_runtime_data.len());
+        for runtime_data in all_runtime_data {
+            let params = Self::container_params(
+                Some(&runtime_data),
+                tls_cert.clone(),
+                support_ipv6,
+                steal_tls.clone(),
+            );
+
+            agents.push(
+                self.spawn_agent(progress, config, params, Some(runtime_data))
+                    .await?,
+            );
+        }
+
+        Ok(agents)
+    }
+
+    /// Spawns the agent container with the given `params`, next to the target described by the
+    /// `runtime_data` (or targetless).
+    async fn spawn_agent<P>(
+        &self,
+        progress: &mut P,
+        config: Option<&LayerConfig>,
+        params: ContainerParams,
+        runtime_data: Option<RuntimeData>,
+    ) -> Result<AgentKubernetesConnectInfo, KubeApiError>
+    where
+        P: Progress + Send + Sync,
+    {
         if let Some(RuntimeData {
             guessed_container: true,
             container_name,
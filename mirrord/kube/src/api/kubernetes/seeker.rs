@@ -4,9 +4,9 @@ use async_stream::stream;
 use futures::{stream, Stream, StreamExt, TryStreamExt};
 use k8s_openapi::{
     api::{
-        apps::v1::{Deployment, StatefulSet},
+        apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
         batch::v1::{CronJob, Job},
-        core::v1::Pod,
+        core::v1::{Pod, Service},
     },
     Metadata, NamespaceResourceScope,
 };
@@ -28,23 +28,29 @@ pub struct KubeResourceSeeker<'a> {
 
 impl KubeResourceSeeker<'_> {
     /// Returns all resource types that don't require the operator to operate ie. [`Pod`],
-    /// [`Deployment`] and [`Rollout`]
+    /// [`Deployment`], [`Rollout`], [`Service`], [`ReplicaSet`] and [`DaemonSet`]
     pub async fn all_open_source(&self) -> Result<Vec<String>> {
-        let (pods, deployments, rollouts) = tokio::try_join!(
+        let (pods, deployments, rollouts, services, replicasets, daemonsets) = tokio::try_join!(
             self.pods(),
             self.deployments(),
-            self.simple_list_resource::<Rollout>("rollout")
+            self.simple_list_resource::<Rollout>("rollout"),
+            self.services(),
+            self.replicasets(),
+            self.daemonsets(),
         )?;
 
         Ok(pods
             .into_iter()
             .chain(deployments)
             .chain(rollouts)
+            .chain(services)
+            .chain(replicasets)
+            .chain(daemonsets)
             .collect())
     }
 
     /// Returns all resource types ie. [`Pod`], [`Deployment`], [`Rollout`], [`Job`], [`CronJob`],
-    /// and [`StatefulSet`]
+    /// [`StatefulSet`], [`Service`], [`ReplicaSet`] and [`DaemonSet`]
     pub async fn all(&self) -> Result<Vec<String>> {
         let (pods, deployments, rollouts, jobs, cronjobs, statefulsets) = tokio::try_join!(
             self.pods(),
@@ -54,11 +60,16 @@ impl KubeResourceSeeker<'_> {
             self.simple_list_resource::<CronJob>("cronjob"),
             self.simple_list_resource::<StatefulSet>("statefulset"),
         )?;
+        let (services, replicasets, daemonsets) =
+            tokio::try_join!(self.services(), self.replicasets(), self.daemonsets())?;
 
         Ok(deployments
             .into_iter()
             .chain(rollouts)
             .chain(statefulsets)
+            .chain(replicasets)
+            .chain(daemonsets)
+            .chain(services)
             .chain(cronjobs)
             .chain(jobs)
             .chain(pods)
@@ -136,6 +147,85 @@ impl KubeResourceSeeker<'_> {
             .await
     }
 
+    /// The list of services that have a pod selector, services without one can't be targeted.
+    async fn services(&self) -> Result<Vec<String>> {
+        fn has_selector(service: &Service) -> bool {
+            service
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.selector.as_ref())
+                .is_some_and(|selector| !selector.is_empty())
+        }
+
+        self.list_resource::<Service>(None)
+            .filter(|response| std::future::ready(response.is_ok()))
+            .try_filter(|service| std::future::ready(has_selector(service)))
+            .try_filter_map(|service| {
+                std::future::ready(Ok(service
+                    .metadata
+                    .name
+                    .map(|name| format!("service/{name}"))))
+            })
+            .try_collect()
+            .await
+    }
+
+    /// The list of replicasets that have at least 1 available replica, and are not owned by a
+    /// [`Deployment`] (those are listed as the deployment itself).
+    async fn replicasets(&self) -> Result<Vec<String>> {
+        fn check_replicaset(replicaset: &ReplicaSet) -> bool {
+            let owned_by_deployment = replicaset
+                .metadata
+                .owner_references
+                .iter()
+                .flatten()
+                .any(|owner| owner.kind == "Deployment");
+
+            let available = replicaset
+                .status
+                .as_ref()
+                .map(|status| status.available_replicas >= Some(1))
+                .unwrap_or(false);
+
+            available && !owned_by_deployment
+        }
+
+        self.list_resource::<ReplicaSet>(None)
+            .filter(|response| std::future::ready(response.is_ok()))
+            .try_filter(|replicaset| std::future::ready(check_replicaset(replicaset)))
+            .try_filter_map(|replicaset| {
+                std::future::ready(Ok(replicaset
+                    .metadata
+                    .name
+                    .map(|name| format!("replicaset/{name}"))))
+            })
+            .try_collect()
+            .await
+    }
+
+    /// The list of daemonsets that have at least 1 available pod.
+    async fn daemonsets(&self) -> Result<Vec<String>> {
+        fn check_daemonset(daemonset: &DaemonSet) -> bool {
+            daemonset
+                .status
+                .as_ref()
+                .map(|status| status.number_available >= Some(1))
+                .unwrap_or(false)
+        }
+
+        self.list_resource::<DaemonSet>(None)
+            .filter(|response| std::future::ready(response.is_ok()))
+            .try_filter(|daemonset| std::future::ready(check_daemonset(daemonset)))
+            .try_filter_map(|daemonset| {
+                std::future::ready(Ok(daemonset
+                    .metadata
+                    .name
+                    .map(|name| format!("daemonset/{name}"))))
+            })
+            .try_collect()
+            .await
+    }
+
     /// Helper to get the list of a resource type ([`Pod`], [`Deployment`], [`Rollout`], [`Job`],
     /// [`CronJob`], [`StatefulSet`], or whatever satisfies `R`) through the kube api.
     fn list_resource<'s, R>(
@@ -0,0 +1,584 @@
+//! Merges connections with multiple agents (one per target pod, see `agent.multi_pod`) into a
+//! single connection, see [`multiplex_connections`].
+//!
+//! Incoming traffic subscriptions are sent to all of the agents, so that traffic is mirrored or
+//! stolen from every pod. Everything else (files, env, DNS, outgoing traffic) is handled only by
+//! the first (primary) agent.
+//! The client gets one subscription result, and it's an error if any of the agents failed to
+//! subscribe.
+//!
+//! Each agent numbers its incoming connections independently, so the [`ConnectionId`]s are
+//! namespaced with the index of the agent before they reach the client, and stripped back before
+//! client messages are routed to the agent that owns the connection.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use mirrord_protocol::{
+    tcp::{ChunkedRequest, ChunkedResponse, DaemonTcp, LayerTcp, LayerTcpSteal},
+    udp::{DaemonUdp, LayerUdp},
+    ClientMessage, ConnectionId, DaemonMessage, Port, RemoteResult,
+};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use super::CONNECTION_CHANNEL_SIZE;
+
+/// Agent index is stored in the highest bits of the [`ConnectionId`]s seen by the client.
+///
+/// Agents assign [`ConnectionId`]s sequentially from 0, so the lower 48 bits are more than
+/// enough.
+const AGENT_INDEX_SHIFT: u32 = 48;
+
+const AGENT_CONNECTION_ID_MASK: ConnectionId = (1 << AGENT_INDEX_SHIFT) - 1;
+
+/// Index of the agent that handles everything other than incoming traffic.
+const PRIMARY_AGENT: usize = 0;
+
+/// How long we remember which agent stole the datagrams from a UDP peer, after the last one.
+const UDP_PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Prefixes the `connection_id` assigned by the agent with the agent's index.
+fn namespace_connection_id(agent: usize, connection_id: ConnectionId) -> ConnectionId {
+    ((agent as ConnectionId) << AGENT_INDEX_SHIFT) | (connection_id & AGENT_CONNECTION_ID_MASK)
+}
+
+/// Reverse of [`namespace_connection_id`], returns the agent's index and the `connection_id`
+/// assigned by the agent.
+fn split_connection_id(connection_id: ConnectionId) -> (usize, ConnectionId) {
+    (
+        (connection_id >> AGENT_INDEX_SHIFT) as usize,
+        connection_id & AGENT_CONNECTION_ID_MASK,
+    )
+}
+
+fn daemon_tcp_connection_id(message: &mut DaemonTcp) -> Option<&mut ConnectionId> {
+    match message {
+        DaemonTcp::SubscribeResult(..) => None,
+        DaemonTcp::NewConnection(connection) => Some(&mut connection.connection_id),
+        DaemonTcp::Data(data) => Some(&mut data.connection_id),
+        DaemonTcp::Close(close) => Some(&mut close.connection_id),
+        DaemonTcp::HttpRequest(request) => Some(&mut request.connection_id),
+        DaemonTcp::HttpRequestFramed(request) => Some(&mut request.connection_id),
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::Start(request)) => {
+            Some(&mut request.connection_id)
+        }
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(body)) => Some(&mut body.connection_id),
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::Error(error)) => {
+            Some(&mut error.connection_id)
+        }
+    }
+}
+
+fn client_connection_id(message: &mut ClientMessage) -> Option<&mut ConnectionId> {
+    match message {
+        ClientMessage::Tcp(LayerTcp::ConnectionUnsubscribe(connection_id)) => Some(connection_id),
+        ClientMessage::TcpSteal(message) => match message {
+            LayerTcpSteal::PortSubscribe(..)
+            | LayerTcpSteal::PortUnsubscribe(..)
+            | LayerTcpSteal::RequiredHeaderFilters(..) => None,
+            LayerTcpSteal::ConnectionUnsubscribe(connection_id) => Some(connection_id),
+            LayerTcpSteal::Data(data) => Some(&mut data.connection_id),
+            LayerTcpSteal::HttpResponse(response) => Some(&mut response.connection_id),
+            LayerTcpSteal::HttpResponseFramed(response) => Some(&mut response.connection_id),
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Start(response)) => {
+                Some(&mut response.connection_id)
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Body(body)) => {
+                Some(&mut body.connection_id)
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Error(error)) => {
+                Some(&mut error.connection_id)
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Whether the message should reach all of the agents: incoming traffic subscriptions, and
+/// messages that affect the whole agent session.
+fn is_broadcast(message: &ClientMessage) -> bool {
+    matches!(
+        message,
+        ClientMessage::Tcp(LayerTcp::PortSubscribe(..) | LayerTcp::PortUnsubscribe(..))
+            | ClientMessage::TcpSteal(
+                LayerTcpSteal::PortSubscribe(..)
+                    | LayerTcpSteal::PortUnsubscribe(..)
+                    | LayerTcpSteal::RequiredHeaderFilters(..)
+            )
+            | ClientMessage::Udp(
+                LayerUdp::PortSubscribe(..)
+                    | LayerUdp::PortUnsubscribe(..)
+                    | LayerUdp::StealPortSubscribe(..)
+                    | LayerUdp::StealPortUnsubscribe(..)
+            )
+            | ClientMessage::Ping
+            | ClientMessage::SwitchProtocolVersion(..)
+            | ClientMessage::ReadyForLogs
+            | ClientMessage::PauseTargetRequest(..)
+            | ClientMessage::Close
+    )
+}
+
+/// Where a [`ClientMessage`] should be sent.
+#[derive(Debug, PartialEq, Eq)]
+enum Destination {
+    All,
+    Agent(usize),
+}
+
+/// Kinds of subscriptions, each agent answers the subscriptions of one kind in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum SubscriptionKind {
+    Mirror,
+    Steal,
+    Udp,
+}
+
+/// Routing state of [`multiplex_connections`].
+struct Router {
+    /// Which agent stole the last datagram from a peer, and when, so that we can send the replies
+    /// back through the same agent.
+    ///
+    /// Keyed by the peer's address, peer's port and the stolen port. Entries are removed when
+    /// the port is unsubscribed, or after [`UDP_PEER_IDLE_TIMEOUT`].
+    udp_peers: HashMap<(IpAddr, Port, Port), (usize, Instant)>,
+    /// When we last removed the idle entries from [`Router::udp_peers`].
+    udp_peers_swept_at: Instant,
+    /// Subscription results that wait for the other agents' results, one queue per agent.
+    ///
+    /// The client gets one result per subscription, after every agent answers it. See
+    /// [`Router::subscribe_result`].
+    subscribe_results: HashMap<SubscriptionKind, Vec<VecDeque<RemoteResult<Port>>>>,
+    agents: usize,
+}
+
+impl Router {
+    fn new(agents: usize) -> Self {
+        Self {
+            udp_peers: Default::default(),
+            udp_peers_swept_at: Instant::now(),
+            subscribe_results: Default::default(),
+            agents,
+        }
+    }
+
+    /// Saves the `result` of a subscription from the `agent`. Returns the result for the client
+    /// when all of the agents have answered the subscription: the first error, or the primary
+    /// agent's result.
+    fn subscribe_result(
+        &mut self,
+        kind: SubscriptionKind,
+        agent: usize,
+        result: RemoteResult<Port>,
+    ) -> Option<RemoteResult<Port>> {
+        if let Err(error) = &result {
+            tracing::warn!(agent, %error, "Agent failed to subscribe to a port");
+        }
+
+        let queues = self
+            .subscribe_results
+            .entry(kind)
+            .or_insert_with(|| vec![Default::default(); self.agents]);
+        queues.get_mut(agent)?.push_back(result);
+
+        if queues.iter().any(VecDeque::is_empty) {
+            return None;
+        }
+
+        let results = queues
+            .iter_mut()
+            .filter_map(VecDeque::pop_front)
+            .collect::<Vec<_>>();
+        results
+            .iter()
+            .find(|result| result.is_err())
+            .or(results.first())
+            .cloned()
+    }
+
+    /// Removes the entries of [`Router::udp_peers`] for the stolen `port`, or the idle ones.
+    fn sweep_udp_peers(&mut self, port: Option<Port>) {
+        let now = Instant::now();
+        if port.is_none() && now.duration_since(self.udp_peers_swept_at) < UDP_PEER_IDLE_TIMEOUT {
+            return;
+        }
+
+        self.udp_peers
+            .retain(|(_, _, stolen_port), (_, last_seen)| {
+                port != Some(*stolen_port) && now.duration_since(*last_seen) < UDP_PEER_IDLE_TIMEOUT
+            });
+        self.udp_peers_swept_at = now;
+    }
+
+    fn route_client_message(&mut self, mut message: ClientMessage) -> (Destination, ClientMessage) {
+        if let Some(connection_id) = client_connection_id(&mut message) {
+            let (agent, agent_connection_id) = split_connection_id(*connection_id);
+            *connection_id = agent_connection_id;
+            return (Destination::Agent(agent), message);
+        }
+
+        if let ClientMessage::Udp(LayerUdp::StealReply(reply)) = &message {
+            let agent = self
+                .udp_peers
+                .get(&(reply.remote_address, reply.remote_port, reply.port))
+                .map(|(agent, _)| *agent)
+                .unwrap_or(PRIMARY_AGENT);
+            return (Destination::Agent(agent), message);
+        }
+
+        if let ClientMessage::Udp(LayerUdp::StealPortUnsubscribe(port)) = &message {
+            self.sweep_udp_peers(Some(*port));
+        }
+
+        if is_broadcast(&message) {
+            (Destination::All, message)
+        } else {
+            (Destination::Agent(PRIMARY_AGENT), message)
+        }
+    }
+
+    /// Returns [`None`] when the message should not reach the client.
+    ///
+    /// Responses to broadcast requests are taken only from the primary agent, so that the client
+    /// gets exactly one response for each request. Subscription results are merged, see
+    /// [`Router::subscribe_result`].
+    fn route_daemon_message(
+        &mut self,
+        agent: usize,
+        message: DaemonMessage,
+    ) -> Option<DaemonMessage> {
+        match message {
+            DaemonMessage::Tcp(DaemonTcp::SubscribeResult(result)) => self
+                .subscribe_result(SubscriptionKind::Mirror, agent, result)
+                .map(|result| DaemonMessage::Tcp(DaemonTcp::SubscribeResult(result))),
+            DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(result)) => self
+                .subscribe_result(SubscriptionKind::Steal, agent, result)
+                .map(|result| DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(result))),
+            DaemonMessage::Udp(DaemonUdp::SubscribeResult(result)) => self
+                .subscribe_result(SubscriptionKind::Udp, agent, result)
+                .map(|result| DaemonMessage::Udp(DaemonUdp::SubscribeResult(result))),
+
+            DaemonMessage::Pong
+            | DaemonMessage::SwitchProtocolVersionResponse(..)
+            | DaemonMessage::PauseTarget(..)
+                if agent != PRIMARY_AGENT =>
+            {
+                None
+            }
+
+            DaemonMessage::Tcp(mut message) => {
+                if let Some(connection_id) = daemon_tcp_connection_id(&mut message) {
+                    *connection_id = namespace_connection_id(agent, *connection_id);
+                }
+                Some(DaemonMessage::Tcp(message))
+            }
+
+            DaemonMessage::TcpSteal(mut message) => {
+                if let Some(connection_id) = daemon_tcp_connection_id(&mut message) {
+                    *connection_id = namespace_connection_id(agent, *connection_id);
+                }
+                Some(DaemonMessage::TcpSteal(message))
+            }
+
+            DaemonMessage::Udp(DaemonUdp::Datagram(datagram)) => {
+                self.sweep_udp_peers(None);
+                self.udp_peers.insert(
+                    (
+                        datagram.remote_address,
+                        datagram.source_port,
+                        datagram.destination_port,
+                    ),
+                    (agent, Instant::now()),
+                );
+                Some(DaemonMessage::Udp(DaemonUdp::Datagram(datagram)))
+            }
+
+            message => Some(message),
+        }
+    }
+}
+
+/// Merges connections with multiple agents into one, as if there was a single agent on the other
+/// side. The first connection belongs to the primary agent, see the module docs.
+///
+/// The merged connection is closed when any of the agent connections is closed.
+#[tracing::instrument(level = "trace", skip_all, fields(agents = connections.len()))]
+pub fn multiplex_connections(
+    connections: Vec<(mpsc::Sender<ClientMessage>, mpsc::Receiver<DaemonMessage>)>,
+) -> (mpsc::Sender<ClientMessage>, mpsc::Receiver<DaemonMessage>) {
+    let (in_tx, mut in_rx) = mpsc::channel(CONNECTION_CHANNEL_SIZE);
+    let (out_tx, out_rx) = mpsc::channel(CONNECTION_CHANNEL_SIZE);
+    let (merged_tx, mut merged_rx) = mpsc::channel(CONNECTION_CHANNEL_SIZE);
+
+    let mut agent_txs = Vec::with_capacity(connections.len());
+    for (agent, (agent_tx, mut agent_rx)) in connections.into_iter().enumerate() {
+        agent_txs.push(agent_tx);
+
+        let merged_tx = merged_tx.clone();
+        tokio::spawn(
+            async move {
+                while let Some(message) = agent_rx.recv().await {
+                    if merged_tx.send((agent, Some(message))).await.is_err() {
+                        return;
+                    }
+                }
+
+                let _ = merged_tx.send((agent, None)).await;
+            }
+            .in_current_span(),
+        );
+    }
+    drop(merged_tx);
+
+    tokio::spawn(
+        async move {
+            let mut router = Router::new(agent_txs.len());
+
+            loop {
+                tokio::select! {
+                    message = in_rx.recv() => {
+                        let Some(message) = message else {
+                            tracing::trace!("No more client messages, disconnecting");
+                            break;
+                        };
+
+                        let sent = match router.route_client_message(message) {
+                            (Destination::All, message) => {
+                                let mut sent = true;
+                                for agent_tx in &agent_txs {
+                                    sent &= agent_tx.send(message.clone()).await.is_ok();
+                                }
+                                sent
+                            }
+                            (Destination::Agent(agent), message) => match agent_txs.get(agent) {
+                                Some(agent_tx) => agent_tx.send(message).await.is_ok(),
+                                None => {
+                                    tracing::warn!(agent, ?message, "Message for an unknown agent, dropping");
+                                    true
+                                }
+                            },
+                        };
+
+                        if !sent {
+                            tracing::error!("Failed to send client message");
+                            break;
+                        }
+                    }
+
+                    message = merged_rx.recv() => match message {
+                        Some((agent, Some(message))) => {
+                            let Some(message) = router.route_daemon_message(agent, message) else {
+                                continue;
+                            };
+
+                            if let Err(error) = out_tx.send(message).await {
+                                tracing::error!(?error, "Failed to send agent message");
+                                break;
+                            }
+                        }
+                        Some((agent, None)) => {
+                            tracing::error!(agent, "Connection with the agent closed, disconnecting");
+                            break;
+                        }
+                        None => break,
+                    },
+                }
+            }
+        }
+        .in_current_span(),
+    );
+
+    (in_tx, out_rx)
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use mirrord_protocol::{
+        tcp::{NewTcpConnection, StealType, TcpData},
+        udp::{UdpDatagram, UdpReply},
+        GetEnvVarsRequest, ResponseError,
+    };
+
+    use super::*;
+
+    #[test]
+    fn connection_id_namespacing() {
+        let namespaced = namespace_connection_id(3, 17);
+
+        assert_ne!(namespaced, namespace_connection_id(0, 17));
+        assert_eq!(split_connection_id(namespaced), (3, 17));
+        assert_eq!(namespace_connection_id(PRIMARY_AGENT, 17), 17);
+    }
+
+    #[test]
+    fn connection_ids_are_routed_back_to_their_agent() {
+        let mut router = Router::new(3);
+
+        let new_connection = router
+            .route_daemon_message(
+                2,
+                DaemonMessage::TcpSteal(DaemonTcp::NewConnection(NewTcpConnection {
+                    connection_id: 5,
+                    remote_address: Ipv4Addr::LOCALHOST.into(),
+                    destination_port: 80,
+                    source_port: 3000,
+                    local_address: Ipv4Addr::LOCALHOST.into(),
+                })),
+            )
+            .unwrap();
+        let DaemonMessage::TcpSteal(DaemonTcp::NewConnection(NewTcpConnection {
+            connection_id,
+            ..
+        })) = new_connection
+        else {
+            panic!("unexpected message: {new_connection:?}");
+        };
+        assert_eq!(connection_id, namespace_connection_id(2, 5));
+
+        let (destination, message) =
+            router.route_client_message(ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
+                connection_id,
+                bytes: b"hello".to_vec(),
+            })));
+        assert_eq!(destination, Destination::Agent(2));
+        assert_eq!(
+            message,
+            ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
+                connection_id: 5,
+                bytes: b"hello".to_vec(),
+            }))
+        );
+    }
+
+    #[test]
+    fn subscriptions_are_broadcast() {
+        let mut router = Router::new(3);
+
+        let (destination, _) = router.route_client_message(ClientMessage::TcpSteal(
+            LayerTcpSteal::PortSubscribe(StealType::All(80)),
+        ));
+        assert_eq!(destination, Destination::All);
+
+        let (destination, _) =
+            router.route_client_message(ClientMessage::GetEnvVarsRequest(GetEnvVarsRequest {
+                env_vars_filter: Default::default(),
+                env_vars_select: Default::default(),
+            }));
+        assert_eq!(destination, Destination::Agent(PRIMARY_AGENT));
+
+        assert_eq!(router.route_daemon_message(1, DaemonMessage::Pong), None);
+    }
+
+    #[test]
+    fn subscribe_results_are_merged() {
+        let mut router = Router::new(3);
+        let result = |result| DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(result));
+        let stolen = || Err(ResponseError::PortAlreadyStolen(80));
+
+        // First subscription fails in one of the other agents.
+        assert_eq!(
+            router.route_daemon_message(PRIMARY_AGENT, result(Ok(80))),
+            None
+        );
+        assert_eq!(router.route_daemon_message(2, result(stolen())), None);
+
+        // Second subscription is answered by agent 1 before it answers the first one.
+        assert_eq!(router.route_daemon_message(2, result(Ok(81))), None);
+        assert_eq!(
+            router.route_daemon_message(1, result(Ok(80))),
+            Some(result(stolen()))
+        );
+        assert_eq!(
+            router.route_daemon_message(PRIMARY_AGENT, result(Ok(81))),
+            None
+        );
+        assert_eq!(
+            router.route_daemon_message(1, result(Ok(81))),
+            Some(result(Ok(81)))
+        );
+
+        // Other kinds of subscriptions are merged separately.
+        assert_eq!(
+            router.route_daemon_message(1, DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(82)))),
+            None
+        );
+    }
+
+    #[test]
+    fn udp_replies_go_through_the_stealing_agent() {
+        let mut router = Router::new(3);
+        let peer = Ipv4Addr::new(10, 0, 0, 1).into();
+
+        router
+            .route_daemon_message(
+                1,
+                DaemonMessage::Udp(DaemonUdp::Datagram(UdpDatagram {
+                    remote_address: peer,
+                    source_port: 5000,
+                    local_address: Ipv4Addr::LOCALHOST.into(),
+                    destination_port: 53,
+                    bytes: vec![],
+                })),
+            )
+            .unwrap();
+
+        let reply = |remote_port| {
+            ClientMessage::Udp(LayerUdp::StealReply(UdpReply {
+                remote_address: peer,
+                remote_port,
+                port: 53,
+                bytes: vec![],
+            }))
+        };
+
+        assert_eq!(
+            router.route_client_message(reply(5000)).0,
+            Destination::Agent(1)
+        );
+        assert_eq!(
+            router.route_client_message(reply(5001)).0,
+            Destination::Agent(PRIMARY_AGENT)
+        );
+    }
+
+    #[test]
+    fn udp_peers_are_evicted() {
+        let mut router = Router::new(3);
+        let datagram = |remote_address: IpAddr, destination_port| {
+            DaemonMessage::Udp(DaemonUdp::Datagram(UdpDatagram {
+                remote_address,
+                source_port: 5000,
+                local_address: Ipv4Addr::LOCALHOST.into(),
+                destination_port,
+                bytes: vec![],
+            }))
+        };
+
+        router.route_daemon_message(1, datagram(Ipv4Addr::new(10, 0, 0, 1).into(), 53));
+        router.route_daemon_message(1, datagram(Ipv4Addr::new(10, 0, 0, 2).into(), 54));
+        assert_eq!(router.udp_peers.len(), 2);
+
+        router.route_client_message(ClientMessage::Udp(LayerUdp::StealPortUnsubscribe(53)));
+        assert_eq!(
+            router.udp_peers.keys().copied().collect::<Vec<_>>(),
+            [(Ipv4Addr::new(10, 0, 0, 2).into(), 5000, 54)]
+        );
+
+        let long_ago = Instant::now() - UDP_PEER_IDLE_TIMEOUT * 2;
+        router.udp_peers_swept_at = long_ago;
+        for (_, last_seen) in router.udp_peers.values_mut() {
+            *last_seen = long_ago;
+        }
+        router.route_daemon_message(2, datagram(Ipv4Addr::new(10, 0, 0, 3).into(), 54));
+        assert_eq!(
+            router.udp_peers.keys().copied().collect::<Vec<_>>(),
+            [(Ipv4Addr::new(10, 0, 0, 3).into(), 5000, 54)]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     collections::BTreeMap,
     convert::Infallible,
     fmt::{self, Display, Formatter},
-    net::Ipv4Addr,
+    net::IpAddr,
     ops::FromResidual,
     str::FromStr,
 };
@@ -28,10 +28,13 @@ use crate::{
 };
 
 pub mod cron_job;
+pub mod daemon_set;
 pub mod deployment;
 pub mod job;
 pub mod pod;
+pub mod replica_set;
 pub mod rollout;
+pub mod service;
 pub mod stateful_set;
 
 #[derive(Debug)]
@@ -39,6 +42,7 @@ pub enum ContainerRuntime {
     Docker,
     Containerd,
     CriO,
+    Podman,
 }
 
 #[derive(Error, Debug)]
@@ -53,6 +57,7 @@ impl FromStr for ContainerRuntime {
             "docker" => Ok(Self::Docker),
             "containerd" => Ok(Self::Containerd),
             "cri-o" => Ok(Self::CriO),
+            "podman" => Ok(Self::Podman),
             _ => Err(ContainerRuntimeParseError(s.to_string())),
         }
     }
@@ -64,6 +69,7 @@ impl Display for ContainerRuntime {
             ContainerRuntime::Docker => write!(f, "docker"),
             ContainerRuntime::Containerd => write!(f, "containerd"),
             ContainerRuntime::CriO => write!(f, "cri-o"),
+            ContainerRuntime::Podman => write!(f, "podman"),
         }
     }
 }
@@ -71,7 +77,7 @@ impl Display for ContainerRuntime {
 #[derive(Debug)]
 pub struct RuntimeData {
     pub pod_name: String,
-    pub pod_ips: Vec<Ipv4Addr>,
+    pub pod_ips: Vec<IpAddr>,
     pub pod_namespace: Option<String>,
     pub node_name: String,
     pub container_id: String,
@@ -128,7 +134,7 @@ impl RuntimeData {
             .filter_map(|pod_ip| {
                 pod_ip
                     .ip
-                    .parse::<Ipv4Addr>()
+                    .parse::<IpAddr>()
                     .inspect_err(|e| {
                         tracing::warn!("failed to parse pod IP {ip}: {e:?}", ip = pod_ip.ip);
                     })
@@ -273,6 +279,21 @@ where
 pub trait RuntimeDataProvider {
     #[allow(async_fn_in_trait)]
     async fn runtime_data(&self, client: &Client, namespace: Option<&str>) -> Result<RuntimeData>;
+
+    /// [`RuntimeData`] of all the ready pods of this target, used to spawn one agent per pod
+    /// (`agent.multi_pod`).
+    ///
+    /// Defaults to the single pod returned from [`RuntimeDataProvider::runtime_data`].
+    #[allow(async_fn_in_trait)]
+    async fn all_runtime_data(
+        &self,
+        client: &Client,
+        namespace: Option<&str>,
+    ) -> Result<Vec<RuntimeData>> {
+        self.runtime_data(client, namespace)
+            .await
+            .map(|runtime_data| vec![runtime_data])
+    }
 }
 
 pub trait RuntimeDataFromLabels {
@@ -296,6 +317,18 @@ where
     T: RuntimeDataFromLabels,
 {
     async fn runtime_data(&self, client: &Client, namespace: Option<&str>) -> Result<RuntimeData> {
+        self.all_runtime_data(client, namespace)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(KubeApiError::MissingRuntimeData)
+    }
+
+    async fn all_runtime_data(
+        &self,
+        client: &Client,
+        namespace: Option<&str>,
+    ) -> Result<Vec<RuntimeData>> {
         let api: Api<<Self as RuntimeDataFromLabels>::Resource> =
             get_k8s_resource_api(client, namespace);
         let resource = api.get(&self.name()).await?;
@@ -322,16 +355,20 @@ where
             ));
         }
 
-        pods.items
+        let runtime_data = pods
+            .items
             .iter()
             .filter_map(|pod| RuntimeData::from_pod(pod, self.container()).ok())
-            .next()
-            .ok_or_else(|| {
-                KubeApiError::invalid_state(
-                    &resource,
-                    "no pod matching labels is ready to be targeted",
-                )
-            })
+            .collect::<Vec<_>>();
+
+        if runtime_data.is_empty() {
+            return Err(KubeApiError::invalid_state(
+                &resource,
+                "no pod matching labels is ready to be targeted",
+            ));
+        }
+
+        Ok(runtime_data)
     }
 }
 
@@ -344,6 +381,28 @@ impl RuntimeDataProvider for Target {
             Target::Job(target) => target.runtime_data(client, namespace).await,
             Target::CronJob(target) => target.runtime_data(client, namespace).await,
             Target::StatefulSet(target) => target.runtime_data(client, namespace).await,
+            Target::Service(target) => target.runtime_data(client, namespace).await,
+            Target::ReplicaSet(target) => target.runtime_data(client, namespace).await,
+            Target::DaemonSet(target) => target.runtime_data(client, namespace).await,
+            Target::Targetless => Err(KubeApiError::MissingRuntimeData),
+        }
+    }
+
+    async fn all_runtime_data(
+        &self,
+        client: &Client,
+        namespace: Option<&str>,
+    ) -> Result<Vec<RuntimeData>> {
+        match self {
+            Target::Deployment(target) => target.all_runtime_data(client, namespace).await,
+            Target::Pod(target) => target.all_runtime_data(client, namespace).await,
+            Target::Rollout(target) => target.all_runtime_data(client, namespace).await,
+            Target::Job(target) => target.all_runtime_data(client, namespace).await,
+            Target::CronJob(target) => target.all_runtime_data(client, namespace).await,
+            Target::StatefulSet(target) => target.all_runtime_data(client, namespace).await,
+            Target::Service(target) => target.all_runtime_data(client, namespace).await,
+            Target::ReplicaSet(target) => target.all_runtime_data(client, namespace).await,
+            Target::DaemonSet(target) => target.all_runtime_data(client, namespace).await,
             Target::Targetless => Err(KubeApiError::MissingRuntimeData),
         }
     }
@@ -358,6 +417,9 @@ impl RuntimeDataProvider for ResolvedTarget<true> {
             Self::Job(target) => target.runtime_data(client, namespace).await,
             Self::CronJob(target) => target.runtime_data(client, namespace).await,
             Self::StatefulSet(target) => target.runtime_data(client, namespace).await,
+            Self::Service(target) => target.runtime_data(client, namespace).await,
+            Self::ReplicaSet(target) => target.runtime_data(client, namespace).await,
+            Self::DaemonSet(target) => target.runtime_data(client, namespace).await,
             Self::Targetless(_) => Err(KubeApiError::MissingRuntimeData),
         }
     }
@@ -365,7 +427,10 @@ impl RuntimeDataProvider for ResolvedTarget<true> {
 
 #[cfg(test)]
 mod tests {
-    use mirrord_config::target::{deployment::DeploymentTarget, job::JobTarget, pod::PodTarget};
+    use mirrord_config::target::{
+        daemon_set::DaemonSetTarget, deployment::DeploymentTarget, job::JobTarget, pod::PodTarget,
+        replica_set::ReplicaSetTarget, service::ServiceTarget,
+    };
     use rstest::rstest;
 
     use super::*;
@@ -378,6 +443,10 @@ mod tests {
     #[case("deployment/nginx-deployment/container/container-name", Target::Deployment(DeploymentTarget {deployment: "nginx-deployment".to_string(), container: Some("container-name".to_string())}))]
     #[case("job/foo", Target::Job(JobTarget { job: "foo".to_string(), container: None }))]
     #[case("job/foo/container/baz", Target::Job(JobTarget { job: "foo".to_string(), container: Some("baz".to_string()) }))]
+    #[case("service/foo", Target::Service(ServiceTarget { service: "foo".to_string(), container: None }))]
+    #[case("service/foo/container/baz", Target::Service(ServiceTarget { service: "foo".to_string(), container: Some("baz".to_string()) }))]
+    #[case("replicaset/foo", Target::ReplicaSet(ReplicaSetTarget { replica_set: "foo".to_string(), container: None }))]
+    #[case("daemonset/foo/container/baz", Target::DaemonSet(DaemonSetTarget { daemon_set: "foo".to_string(), container: Some("baz".to_string()) }))]
     fn target_parses(#[case] target: &str, #[case] expected: Target) {
         let target = target.parse::<Target>().unwrap();
         assert_eq!(target, expected)
@@ -0,0 +1,134 @@
+//! Resolves the `port` -> `targetPort` mapping of a [`Target::Service`], see
+//! [`service_port_mapping`].
+
+use k8s_openapi::{
+    api::core::v1::{Pod, Service},
+    apimachinery::pkg::util::intstr::IntOrString,
+};
+use kube::Client;
+use mirrord_config::target::Target;
+use tracing::Level;
+
+use crate::{
+    api::env::pod_from_target,
+    error::Result,
+    resolved::{ResolvedResource, ResolvedTarget},
+};
+
+/// Returns the `(port, targetPort)` pairs of the `target` service, for the ports where the two
+/// differ. Returns an empty list for other targets.
+///
+/// Named `targetPort`s are resolved with the container ports of the first pod selected by the
+/// service, and are left out when no container port has the name.
+#[tracing::instrument(level = Level::DEBUG, skip(client), ret, err)]
+pub async fn service_port_mapping(
+    client: &Client,
+    target: &Target,
+    namespace: Option<&str>,
+) -> Result<Vec<(u16, u16)>> {
+    if !matches!(target, Target::Service(..)) {
+        return Ok(vec![]);
+    }
+
+    let resolved = ResolvedTarget::new(client, target, namespace).await?;
+    let ResolvedTarget::Service(ResolvedResource { resource, .. }) = &resolved else {
+        return Ok(vec![]);
+    };
+
+    let has_named_ports = resource
+        .spec
+        .iter()
+        .flat_map(|spec| spec.ports.iter().flatten())
+        .any(|port| matches!(port.target_port, Some(IntOrString::String(..))));
+    let pod = if has_named_ports {
+        Some(pod_from_target(client, &resolved, namespace).await?)
+    } else {
+        None
+    };
+
+    Ok(port_mapping(resource, pod.as_ref()))
+}
+
+/// Maps the ports of the `service` to their `targetPort`s, resolving named ports with the
+/// container ports of the `pod`.
+fn port_mapping(service: &Service, pod: Option<&Pod>) -> Vec<(u16, u16)> {
+    let container_port = |name: &str| {
+        pod.and_then(|pod| pod.spec.as_ref())
+            .into_iter()
+            .flat_map(|spec| spec.containers.iter())
+            .flat_map(|container| container.ports.iter().flatten())
+            .find(|port| port.name.as_deref() == Some(name))
+            .map(|port| port.container_port)
+    };
+
+    service
+        .spec
+        .iter()
+        .flat_map(|spec| spec.ports.iter().flatten())
+        .filter_map(|port| {
+            let target_port = match port.target_port.as_ref()? {
+                IntOrString::Int(target_port) => *target_port,
+                IntOrString::String(name) => container_port(name)?,
+            };
+
+            let mapping = (
+                u16::try_from(port.port).ok()?,
+                u16::try_from(target_port).ok()?,
+            );
+            (mapping.0 != mapping.1).then_some(mapping)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use k8s_openapi::api::core::v1::{Container, ContainerPort, PodSpec, ServicePort, ServiceSpec};
+
+    use super::*;
+
+    fn service_port(port: i32, target_port: Option<IntOrString>) -> ServicePort {
+        ServicePort {
+            port,
+            target_port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn maps_ports_to_target_ports() {
+        let service = Service {
+            spec: Some(ServiceSpec {
+                ports: Some(vec![
+                    service_port(80, Some(IntOrString::Int(8080))),
+                    service_port(443, Some(IntOrString::String("https".to_string()))),
+                    service_port(9000, Some(IntOrString::String("missing".to_string()))),
+                    service_port(5432, Some(IntOrString::Int(5432))),
+                    service_port(6379, None),
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let pod = Pod {
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    name: "api".to_string(),
+                    ports: Some(vec![ContainerPort {
+                        name: Some("https".to_string()),
+                        container_port: 8443,
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            port_mapping(&service, Some(&pod)),
+            vec![(80, 8080), (443, 8443)]
+        );
+        assert_eq!(port_mapping(&service, None), vec![(80, 8080)]);
+    }
+}
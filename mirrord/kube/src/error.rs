@@ -57,6 +57,10 @@ pub enum KubeApiError {
         /// Should be plural name of the resource
         String,
     ),
+
+    /// The environment of the target container could not be resolved from its pod spec.
+    #[error("failed to read the environment from the pod spec: {0}")]
+    PodSpecEnv(String),
 }
 
 impl KubeApiError {
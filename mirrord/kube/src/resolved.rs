@@ -1,9 +1,9 @@
 use std::collections::BTreeMap;
 
 use k8s_openapi::api::{
-    apps::v1::{Deployment, StatefulSet},
+    apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
     batch::v1::{CronJob, Job},
-    core::v1::Pod,
+    core::v1::{Pod, Service},
 };
 use kube::{Client, Resource, ResourceExt};
 use mirrord_config::{feature::network::incoming::ConcurrentSteal, target::Target};
@@ -13,13 +13,19 @@ use super::{
     api::{kubernetes::get_k8s_resource_api, runtime::RuntimeData},
     error::KubeApiError,
 };
-use crate::api::{kubernetes::rollout::Rollout, runtime::RuntimeDataFromLabels};
+use crate::api::{
+    kubernetes::rollout::Rollout,
+    runtime::{RuntimeDataFromLabels, RuntimeDataProvider},
+};
 
 pub mod cron_job;
+pub mod daemon_set;
 pub mod deployment;
 pub mod job;
 pub mod pod;
+pub mod replica_set;
 pub mod rollout;
+pub mod service;
 pub mod stateful_set;
 
 /// Helper struct for resolving user-provided [`Target`] to Kubernetes resources.
@@ -39,6 +45,12 @@ pub enum ResolvedTarget<const CHECKED: bool> {
     Job(ResolvedResource<Job>),
     CronJob(ResolvedResource<CronJob>),
     StatefulSet(ResolvedResource<StatefulSet>),
+    ReplicaSet(ResolvedResource<ReplicaSet>),
+    DaemonSet(ResolvedResource<DaemonSet>),
+
+    /// [`Service`] does not own any pods, we target one of the ready pods matched by its
+    /// selector.
+    Service(ResolvedResource<Service>),
 
     /// [`Pod`] is a special case, in that it does not implement [`RuntimeDataFromLabels`],
     /// and instead we implement a `runtime_data` method directly in its
@@ -84,6 +96,15 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::StatefulSet(ResolvedResource { resource, .. }) => {
                 resource.metadata.name.as_deref()
             }
+            ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => {
+                resource.metadata.name.as_deref()
+            }
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }) => {
+                resource.metadata.name.as_deref()
+            }
+            ResolvedTarget::Service(ResolvedResource { resource, .. }) => {
+                resource.metadata.name.as_deref()
+            }
             ResolvedTarget::Targetless(_) => None,
         }
     }
@@ -96,6 +117,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::Job(ResolvedResource { resource, .. }) => resource.name_any(),
             ResolvedTarget::CronJob(ResolvedResource { resource, .. }) => resource.name_any(),
             ResolvedTarget::StatefulSet(ResolvedResource { resource, .. }) => resource.name_any(),
+            ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => resource.name_any(),
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }) => resource.name_any(),
+            ResolvedTarget::Service(ResolvedResource { resource, .. }) => resource.name_any(),
             ResolvedTarget::Targetless(..) => "targetless".to_string(),
         }
     }
@@ -120,6 +144,15 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::StatefulSet(ResolvedResource { resource, .. }) => {
                 resource.metadata.namespace.as_deref()
             }
+            ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => {
+                resource.metadata.namespace.as_deref()
+            }
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }) => {
+                resource.metadata.namespace.as_deref()
+            }
+            ResolvedTarget::Service(ResolvedResource { resource, .. }) => {
+                resource.metadata.namespace.as_deref()
+            }
             ResolvedTarget::Targetless(namespace) => Some(namespace),
         }
     }
@@ -137,6 +170,13 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::StatefulSet(ResolvedResource { resource, .. }) => {
                 resource.metadata.labels
             }
+            ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => {
+                resource.metadata.labels
+            }
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }) => {
+                resource.metadata.labels
+            }
+            ResolvedTarget::Service(ResolvedResource { resource, .. }) => resource.metadata.labels,
             ResolvedTarget::Targetless(_) => None,
         }
     }
@@ -149,6 +189,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::Job(_) => "job",
             ResolvedTarget::CronJob(_) => "cronjob",
             ResolvedTarget::StatefulSet(_) => "statefulset",
+            ResolvedTarget::ReplicaSet(_) => "replicaset",
+            ResolvedTarget::DaemonSet(_) => "daemonset",
+            ResolvedTarget::Service(_) => "service",
             ResolvedTarget::Targetless(_) => "targetless",
         }
     }
@@ -160,6 +203,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             | ResolvedTarget::Job(ResolvedResource { container, .. })
             | ResolvedTarget::CronJob(ResolvedResource { container, .. })
             | ResolvedTarget::StatefulSet(ResolvedResource { container, .. })
+            | ResolvedTarget::ReplicaSet(ResolvedResource { container, .. })
+            | ResolvedTarget::DaemonSet(ResolvedResource { container, .. })
+            | ResolvedTarget::Service(ResolvedResource { container, .. })
             | ResolvedTarget::Pod(ResolvedResource { container, .. }) => container.as_deref(),
             ResolvedTarget::Targetless(..) => None,
         }
@@ -173,6 +219,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             | ResolvedTarget::Job(ResolvedResource { container, .. })
             | ResolvedTarget::CronJob(ResolvedResource { container, .. })
             | ResolvedTarget::StatefulSet(ResolvedResource { container, .. })
+            | ResolvedTarget::ReplicaSet(ResolvedResource { container, .. })
+            | ResolvedTarget::DaemonSet(ResolvedResource { container, .. })
+            | ResolvedTarget::Service(ResolvedResource { container, .. })
             | ResolvedTarget::Pod(ResolvedResource { container, .. }) => container.as_deref(),
             ResolvedTarget::Targetless(..) => None,
         }
@@ -210,6 +259,19 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
                 .as_ref()
                 .and_then(|spec| spec.template.spec.as_ref())
                 .map(|pod_spec| pod_spec.containers.len()),
+            ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => resource
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.template.as_ref())
+                .and_then(|pod_template| pod_template.spec.as_ref())
+                .map(|pod_spec| pod_spec.containers.len()),
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }) => resource
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.template.spec.as_ref())
+                .map(|pod_spec| pod_spec.containers.len()),
+            // The pods selected by a service may come from different workloads.
+            ResolvedTarget::Service(..) => None,
             ResolvedTarget::CronJob(ResolvedResource { resource, .. }) => resource
                 .spec
                 .as_ref()
@@ -286,6 +348,33 @@ impl ResolvedTarget<false> {
                         container: target.container.clone(),
                     })
                 }),
+            Target::Service(target) => get_k8s_resource_api::<Service>(client, namespace)
+                .get(&target.service)
+                .await
+                .map(|resource| {
+                    ResolvedTarget::Service(ResolvedResource {
+                        resource,
+                        container: target.container.clone(),
+                    })
+                }),
+            Target::ReplicaSet(target) => get_k8s_resource_api::<ReplicaSet>(client, namespace)
+                .get(&target.replica_set)
+                .await
+                .map(|resource| {
+                    ResolvedTarget::ReplicaSet(ResolvedResource {
+                        resource,
+                        container: target.container.clone(),
+                    })
+                }),
+            Target::DaemonSet(target) => get_k8s_resource_api::<DaemonSet>(client, namespace)
+                .get(&target.daemon_set)
+                .await
+                .map(|resource| {
+                    ResolvedTarget::DaemonSet(ResolvedResource {
+                        resource,
+                        container: target.container.clone(),
+                    })
+                }),
             Target::Pod(target) => get_k8s_resource_api::<Pod>(client, namespace)
                 .get(&target.pod)
                 .await
@@ -309,7 +398,10 @@ impl ResolvedTarget<false> {
     ///    and the target container, if specified, is found in the spec
     /// 2. [`ResolvedTarget::Pod`] - passes target-readiness check, see [`RuntimeData::from_pod`].
     /// 3. [`ResolvedTarget::Job`] - error, as this is `copy_target` exclusive
-    /// 4. [`ResolvedTarget::Targetless`] - no check
+    /// 4. [`ResolvedTarget::ReplicaSet`] or [`ResolvedTarget::DaemonSet`] - has available pods and
+    ///    the target container, if specified, is found in the spec
+    /// 5. [`ResolvedTarget::Service`] - selects at least one pod that is ready to be targeted
+    /// 6. [`ResolvedTarget::Targetless`] - no check
     #[tracing::instrument(level = Level::DEBUG, skip(client), ret, err)]
     pub async fn assert_valid_mirrord_target(
         self,
@@ -447,6 +539,92 @@ impl ResolvedTarget<false> {
                 }))
             }
 
+            ResolvedTarget::ReplicaSet(ResolvedResource {
+                resource,
+                container,
+            }) => {
+                let available = resource
+                    .status
+                    .as_ref()
+                    .ok_or_else(|| KubeApiError::missing_field(&resource, ".status"))?
+                    .available_replicas
+                    .unwrap_or_default(); // Field can be missing when there are no replicas
+
+                if available <= 0 {
+                    return Err(KubeApiError::invalid_state(
+                        &resource,
+                        "no available replicas",
+                    ));
+                }
+
+                if let Some(container) = &container {
+                    // verify that the container exists
+                    resource
+                        .spec
+                        .as_ref()
+                        .ok_or_else(|| KubeApiError::missing_field(&resource, ".spec"))?
+                        .template
+                        .as_ref()
+                        .and_then(|template| template.spec.as_ref())
+                        .ok_or_else(|| KubeApiError::missing_field(&resource, ".spec.template.spec"))?
+                        .containers
+                        .iter()
+                        .find(|c| c.name == *container)
+                        .ok_or_else(|| KubeApiError::invalid_state(&resource, format_args!("specified pod template does not contain target container `{container}`")))?;
+                }
+
+                Ok(ResolvedTarget::ReplicaSet(ResolvedResource {
+                    resource,
+                    container,
+                }))
+            }
+
+            ResolvedTarget::DaemonSet(ResolvedResource {
+                resource,
+                container,
+            }) => {
+                let available = resource
+                    .status
+                    .as_ref()
+                    .ok_or_else(|| KubeApiError::missing_field(&resource, ".status"))?
+                    .number_available
+                    .unwrap_or_default(); // Field can be missing when there are no pods
+
+                if available <= 0 {
+                    return Err(KubeApiError::invalid_state(&resource, "no available pods"));
+                }
+
+                if let Some(container) = &container {
+                    // verify that the container exists
+                    resource
+                        .spec
+                        .as_ref()
+                        .ok_or_else(|| KubeApiError::missing_field(&resource, ".spec"))?
+                        .template
+                        .spec
+                        .as_ref()
+                        .ok_or_else(|| KubeApiError::missing_field(&resource, ".spec.template.spec"))?
+                        .containers
+                        .iter()
+                        .find(|c| c.name == *container)
+                        .ok_or_else(|| KubeApiError::invalid_state(&resource, format_args!("specified pod template does not contain target container `{container}`")))?;
+                }
+
+                Ok(ResolvedTarget::DaemonSet(ResolvedResource {
+                    resource,
+                    container,
+                }))
+            }
+
+            ResolvedTarget::Service(service) => {
+                // A service has no pod template, so we check that it selects a pod that can be
+                // targeted (which also verifies the container, if specified).
+                let namespace = service.resource.metadata.namespace.clone();
+                service.runtime_data(client, namespace.as_deref()).await?;
+
+                Ok(ResolvedTarget::Service(service))
+            }
+
             ResolvedTarget::Targetless(namespace) => {
                 // no check needed here
                 Ok(ResolvedTarget::Targetless(namespace))
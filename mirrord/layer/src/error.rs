@@ -4,7 +4,7 @@ use errno::set_errno;
 use ignore_codes::*;
 use libc::{c_char, hostent, DIR, FILE};
 use mirrord_config::config::ConfigError;
-use mirrord_protocol::{ResponseError, SerializationError};
+use mirrord_protocol::{BlockedAction, ResponseError, SerializationError};
 #[cfg(target_os = "macos")]
 use mirrord_sip::SipError;
 use thiserror::Error;
@@ -75,7 +75,7 @@ pub(crate) enum HookError {
     #[error("mirrord-layer: SIP patch failed with error `{0}`!")]
     FailedSipPatch(#[from] SipError),
 
-    #[error("mirrord-layer: IPv6 can't be used with mirrord")]
+    #[error("mirrord-layer: IPv6 sockets require `feature.network.ipv6` to be enabled")]
     SocketUnsuportedIpv6,
 
     // `From` implemented below, not with `#[from]` so that when new variants of
@@ -221,6 +221,14 @@ impl From<HookError> for i64 {
             HookError::SocketUnsuportedIpv6 => {
                 info!("{fail}")
             }
+            HookError::ResponseError(
+                ref err @ ResponseError::Forbidden {
+                    blocked_action: BlockedAction::FileAccess(..) | BlockedAction::Outgoing(..),
+                    ..
+                },
+            ) => {
+                error!("{err}")
+            }
             HookError::ProxyError(ref err) => {
                 graceful_exit!(
                     r"Proxy error, connectivity issue or a bug.
@@ -276,6 +284,11 @@ impl From<HookError> for i64 {
                 ResponseError::PortAlreadyStolen(_port) => libc::EINVAL,
                 ResponseError::NotImplemented => libc::EINVAL,
                 ResponseError::StripPrefix(_) => libc::EINVAL,
+                // The application can continue without the blocked file or connection.
+                ResponseError::Forbidden {
+                    blocked_action: BlockedAction::FileAccess(..) | BlockedAction::Outgoing(..),
+                    ..
+                } => libc::EACCES,
                 err @ ResponseError::Forbidden { .. } => {
                     graceful_exit!(
                         "Stopping mirrord run. Please adjust your mirrord configuration.\n{err}"
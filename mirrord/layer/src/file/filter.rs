@@ -1,169 +1,47 @@
-/// Controls which files are ignored (opened locally) by mirrord file operations.
-///
-/// There are 2 ways of setting this up:
-///
-/// 1. no configuration (default): will bypass file operations for file paths and types that
-///    match [`generate_local_set`];
-///
-/// 2. Using the overrides for `read_only`, `read_write` and `local`.
-use std::env;
+//! Applies the [`FileFilter`] decisions to the file operations, see [`FileFilterExt`].
 
-use mirrord_config::{
-    feature::fs::{FsConfig, FsModeConfig},
-    util::VecOrSingle,
+pub use mirrord_config::feature::fs::filter::FileFilter;
+use mirrord_config::feature::fs::{
+    filter::{FileAccess, FileFilterRule},
+    FsModeConfig,
 };
-use regex::{RegexSet, RegexSetBuilder};
 
 use crate::{
     detour::{Bypass, Detour},
     error::HookError,
 };
 
-mod not_found_by_default;
-mod read_local_by_default;
-mod read_remote_by_default;
-
-/// List of files that mirrord should use locally, as they probably exist only in the local user
-/// machine, or are system configuration files (that could break the process if we used the remote
-/// version).
-///
-/// You most likely do **NOT** want to include any of these, but if have a reason to do so, then
-/// setting any of the overrides - `MIRRORD_FILE_X_PATTERN` allows you to override this list.
-fn generate_local_set() -> RegexSet {
-    // To handle the problem of injecting `open` and friends into project runners (like in a call to
-    // `node app.js`, or `cargo run app`), we're ignoring files from the current working directory.
-    read_local_by_default::regex_set_builder()
-        .case_insensitive(true)
-        .build()
-        .expect("Building local path regex set failed")
-}
-
-/// List of files that mirrord should use remotely read only
-fn generate_remote_ro_set() -> RegexSet {
-    let patterns = read_remote_by_default::PATHS;
-    RegexSetBuilder::new(patterns)
-        .case_insensitive(true)
-        .build()
-        .expect("Building remote readonly path regex set failed")
-}
-
-fn generate_not_found_set() -> RegexSet {
-    let Ok(home) = env::var("HOME") else {
-        tracing::warn!("Unable to resolve $HOME directory, generating empty not-found set");
-        return Default::default();
-    };
-
-    let home_clean = regex::escape(home.trim_end_matches('/'));
-
-    let patterns = not_found_by_default::PATHS
-        .into_iter()
-        .map(|cloud_dir| format!("^{home_clean}/{cloud_dir}"));
-
-    RegexSetBuilder::new(patterns)
-        .case_insensitive(true)
-        .build()
-        .expect("Building not found path regex set failed")
-}
-
-#[derive(Debug)]
-pub struct FileFilter {
-    read_only: RegexSet,
-    read_write: RegexSet,
-    local: RegexSet,
-    not_found: RegexSet,
-    default_local: RegexSet,
-    default_remote_ro: RegexSet,
-    default_not_found: RegexSet,
-    mode: FsModeConfig,
-}
-
-impl FileFilter {
-    fn make_regex_set(patterns: Option<VecOrSingle<String>>) -> Result<RegexSet, regex::Error> {
-        RegexSetBuilder::new(patterns.as_deref().map(<[_]>::to_vec).unwrap_or_default())
-            .case_insensitive(true)
-            .build()
-    }
-
-    /// Initializes a `FileFilter` based on the user configuration.
-    ///
-    /// The filter first checks if the user specified any include/exclude regexes. (This will be
-    /// removed) If path matches include, it continues to check if the path has specific
-    /// behavior, if not, it checks if the path matches the default exclude list.
-    /// If not, it does the default behavior set by user (default is read only remote).
-    #[mirrord_layer_macro::instrument(level = "trace")]
-    pub fn new(fs_config: FsConfig) -> Self {
-        let FsConfig {
-            read_write,
-            read_only,
-            local,
-            mode,
-            not_found,
-            ..
-        } = fs_config;
-
-        let read_write =
-            Self::make_regex_set(read_write).expect("building read-write regex set failed");
-        let read_only =
-            Self::make_regex_set(read_only).expect("building read-only regex set failed");
-        let local = Self::make_regex_set(local).expect("building local path regex set failed");
-        let not_found =
-            Self::make_regex_set(not_found).expect("building not-found regex set failed");
-
-        let default_local = generate_local_set();
-        let default_remote_ro = generate_remote_ro_set();
-        let default_not_found = generate_not_found_set();
-
-        Self {
-            read_only,
-            read_write,
-            local,
-            not_found,
-            default_local,
-            default_remote_ro,
-            default_not_found,
-            mode,
-        }
-    }
-
+/// [`FileFilter`] extension.
+pub trait FileFilterExt {
     /// Checks if `text` matches the regex held by the initialized variant of `FileFilter`,
     /// and the whether the path is queried for write converting the result a `Detour`.
     ///
     /// `op` is used to lazily initialize a `Bypass` case.
-    pub fn continue_or_bypass_with<F>(&self, text: &str, write: bool, op: F) -> Detour<()>
+    fn continue_or_bypass_with<F>(&self, text: &str, write: bool, op: F) -> Detour<()>
+    where
+        F: FnOnce() -> Bypass;
+}
+
+impl FileFilterExt for FileFilter {
+    fn continue_or_bypass_with<F>(&self, text: &str, write: bool, op: F) -> Detour<()>
     where
         F: FnOnce() -> Bypass,
     {
-        match self.mode {
-            FsModeConfig::Local => Detour::Bypass(op()),
-            _ if self.not_found.is_match(text) => Detour::Error(HookError::FileNotFound),
-            _ if self.read_write.is_match(text) => Detour::Success(()),
-            _ if self.read_only.is_match(text) => {
-                if write {
-                    Detour::Bypass(op())
-                } else {
-                    Detour::Success(())
-                }
+        match self.check(text, write) {
+            (FileAccess::Remote, _) => Detour::Success(()),
+            (FileAccess::NotFound, _) => Detour::Error(HookError::FileNotFound),
+            (FileAccess::Local, FileFilterRule::Mode(FsModeConfig::Read)) => {
+                Detour::Bypass(Bypass::ReadOnly(text.into()))
             }
-            _ if self.local.is_match(text) => Detour::Bypass(op()),
-            _ if self.default_not_found.is_match(text) => Detour::Error(HookError::FileNotFound),
-            _ if self.default_remote_ro.is_match(text) && !write => Detour::Success(()),
-            _ if self.default_local.is_match(text) => Detour::Bypass(op()),
-            FsModeConfig::LocalWithOverrides => Detour::Bypass(op()),
-            FsModeConfig::Write => Detour::Success(()),
-            FsModeConfig::Read if write => Detour::Bypass(Bypass::ReadOnly(text.into())),
-            FsModeConfig::Read => Detour::Success(()),
+            (FileAccess::Local, _) => Detour::Bypass(op()),
         }
     }
 }
 
-impl Default for FileFilter {
-    fn default() -> Self {
-        Self::new(FsConfig::default())
-    }
-}
-
 #[cfg(test)]
 mod tests {
+    use std::env;
+
     use mirrord_config::{feature::fs::FsConfig, util::VecOrSingle};
     use rstest::*;
 
@@ -446,13 +324,6 @@ mod tests {
         assert_eq!(res.kind(), expected);
     }
 
-    /// Sanity test for empty [`RegexSet`] behaviour.
-    #[test]
-    fn empty_regex_set() {
-        let set = FileFilter::make_regex_set(None).unwrap();
-        assert!(!set.is_match("/path/to/some/file"));
-    }
-
     /// Return path to the $HOME directory without trailing slash.
     fn clean_home() -> String {
         env::var("HOME").unwrap().trim_end_matches('/').into()
@@ -14,14 +14,15 @@ use std::{
 
 use errno::{set_errno, Errno};
 use libc::{
-    self, c_char, c_int, c_void, dirent, iovec, off_t, size_t, ssize_t, stat, statfs, AT_EACCESS,
-    AT_FDCWD, DIR, EINVAL, O_DIRECTORY, O_RDONLY,
+    self, c_char, c_int, c_void, dirent, gid_t, iovec, off_t, size_t, ssize_t, stat, statfs,
+    timespec, uid_t, AT_EACCESS, AT_FDCWD, AT_SYMLINK_NOFOLLOW, DIR, EINVAL, O_DIRECTORY, O_RDONLY,
 };
 #[cfg(target_os = "linux")]
 use libc::{dirent64, stat64, statx, EBADF, ENOENT, ENOTDIR};
 use mirrord_layer_macro::{hook_fn, hook_guard_fn};
 use mirrord_protocol::file::{
-    FsMetadataInternal, MetadataInternal, ReadFileResponse, ReadLinkFileResponse, WriteFileResponse,
+    FileTimeInternal, FsMetadataInternal, MetadataInternal, ReadFileResponse, ReadLinkFileResponse,
+    WriteFileResponse,
 };
 #[cfg(target_os = "linux")]
 use mirrord_protocol::ResponseError::{NotDirectory, NotFound};
@@ -1038,6 +1039,25 @@ pub(crate) unsafe extern "C" fn preadv_detour(
         .unwrap_or_bypass_with(|_| FN_PREADV(fd, iovecs, iovec_count, offset))
 }
 
+/// Logic of [`readlink_detour`], also used for Go's `readlinkat` syscall.
+///
+/// Like [`libc::readlink`], does not append a null byte, and truncates the path to
+/// `buffer_size`.
+pub(crate) unsafe fn readlink_logic(
+    raw_path: *const c_char,
+    out_buffer: *mut c_char,
+    buffer_size: size_t,
+) -> Detour<ssize_t> {
+    read_link(raw_path.checked_into()).map(|ReadLinkFileResponse { path }| {
+        let path_bytes = path.as_os_str().as_bytes();
+        let length = path_bytes.len().min(buffer_size);
+
+        ptr::copy(path_bytes.as_ptr(), out_buffer.cast(), length);
+
+        ssize_t::try_from(length).unwrap()
+    })
+}
+
 /// Hook for [`libc::readlink`].
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn readlink_detour(
@@ -1045,21 +1065,10 @@ pub(crate) unsafe extern "C" fn readlink_detour(
     out_buffer: *mut c_char,
     buffer_size: size_t,
 ) -> ssize_t {
-    read_link(raw_path.checked_into())
-        .map(|ReadLinkFileResponse { path }| {
-            let path_bytes = path.as_os_str().as_bytes();
-
-            let path_ptr = path_bytes.as_ptr();
-            let out_buffer = out_buffer.cast();
-
-            ptr::copy(path_ptr, out_buffer, buffer_size);
-
-            ssize_t::try_from(path_bytes.len().min(buffer_size)).unwrap()
-        })
-        .unwrap_or_bypass_with(|bypass| {
-            let raw_path = update_ptr_from_bypass(raw_path, &bypass);
-            FN_READLINK(raw_path, out_buffer, buffer_size)
-        })
+    readlink_logic(raw_path, out_buffer, buffer_size).unwrap_or_bypass_with(|bypass| {
+        let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+        FN_READLINK(raw_path, out_buffer, buffer_size)
+    })
 }
 
 /// Hook for `libc::mkdir`.
@@ -1088,6 +1097,210 @@ pub(crate) unsafe extern "C" fn mkdirat_detour(
         })
 }
 
+/// Hook for `libc::unlink`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn unlink_detour(pathname: *const c_char) -> c_int {
+    unlink(pathname.checked_into())
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(pathname, &bypass);
+            FN_UNLINK(raw_path)
+        })
+}
+
+/// Hook for `libc::unlinkat`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn unlinkat_detour(
+    dirfd: c_int,
+    pathname: *const c_char,
+    flags: c_int,
+) -> c_int {
+    unlinkat(dirfd, pathname.checked_into(), flags)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(pathname, &bypass);
+            FN_UNLINKAT(dirfd, raw_path, flags)
+        })
+}
+
+/// Hook for `libc::rmdir`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn rmdir_detour(pathname: *const c_char) -> c_int {
+    rmdir(pathname.checked_into())
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(pathname, &bypass);
+            FN_RMDIR(raw_path)
+        })
+}
+
+/// Hook for `libc::rename`.
+///
+/// When bypassing, we call the original function with the paths we got, as the bypass might refer
+/// to either of them.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn rename_detour(
+    old_path: *const c_char,
+    new_path: *const c_char,
+) -> c_int {
+    rename(old_path.checked_into(), new_path.checked_into())
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| FN_RENAME(old_path, new_path))
+}
+
+/// Hook for `libc::truncate`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn truncate_detour(path: *const c_char, length: off_t) -> c_int {
+    truncate(path.checked_into(), length)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(path, &bypass);
+            FN_TRUNCATE(raw_path, length)
+        })
+}
+
+/// Hook for `libc::ftruncate`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn ftruncate_detour(fd: c_int, length: off_t) -> c_int {
+    ftruncate(fd, length)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| FN_FTRUNCATE(fd, length))
+}
+
+/// Hook for `libc::chmod`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn chmod_detour(path: *const c_char, mode: u32) -> c_int {
+    chmod(path.checked_into(), mode)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(path, &bypass);
+            FN_CHMOD(raw_path, mode)
+        })
+}
+
+/// Hook for `libc::fchmod`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn fchmod_detour(fd: c_int, mode: u32) -> c_int {
+    fchmod(fd, mode)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| FN_FCHMOD(fd, mode))
+}
+
+/// Converts the `-1` that `chown` uses for "don't change this id" into `None`.
+fn chown_id(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+/// Hook for `libc::chown`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn chown_detour(
+    path: *const c_char,
+    owner: uid_t,
+    group: gid_t,
+) -> c_int {
+    chown(path.checked_into(), chown_id(owner), chown_id(group))
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(path, &bypass);
+            FN_CHOWN(raw_path, owner, group)
+        })
+}
+
+/// Hook for `libc::fchown`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn fchown_detour(fd: c_int, owner: uid_t, group: gid_t) -> c_int {
+    fchown(fd, chown_id(owner), chown_id(group))
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| FN_FCHOWN(fd, owner, group))
+}
+
+/// Converts the `times` argument of `utimensat` and `futimens` into the access and modification
+/// [`FileTimeInternal`]s.
+///
+/// A null `times` means both timestamps are set to the current time.
+unsafe fn file_times_from_raw(times: *const timespec) -> (FileTimeInternal, FileTimeInternal) {
+    let convert = |time: &timespec| match time.tv_nsec {
+        libc::UTIME_NOW => FileTimeInternal::Now,
+        libc::UTIME_OMIT => FileTimeInternal::Omit,
+        nanoseconds => FileTimeInternal::Set {
+            seconds: time.tv_sec,
+            nanoseconds,
+        },
+    };
+
+    if times.is_null() {
+        (FileTimeInternal::Now, FileTimeInternal::Now)
+    } else {
+        let [access_time, modification_time] = &*times.cast::<[timespec; 2]>();
+        (convert(access_time), convert(modification_time))
+    }
+}
+
+/// Hook for `libc::utimensat`.
+///
+/// On Linux `pathname` may be null, in which case the timestamps of `dirfd` itself are changed.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn utimensat_detour(
+    dirfd: c_int,
+    pathname: *const c_char,
+    times: *const timespec,
+    flags: c_int,
+) -> c_int {
+    let (access_time, modification_time) = file_times_from_raw(times);
+    let path = (!pathname.is_null()).then(|| pathname.checked_into());
+
+    utimensat(
+        dirfd,
+        path,
+        access_time,
+        modification_time,
+        flags & AT_SYMLINK_NOFOLLOW == 0,
+    )
+    .map(|()| 0)
+    .unwrap_or_bypass_with(|bypass| {
+        let raw_path = update_ptr_from_bypass(pathname, &bypass);
+        FN_UTIMENSAT(dirfd, raw_path, times, flags)
+    })
+}
+
+/// Hook for `libc::futimens`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn futimens_detour(fd: c_int, times: *const timespec) -> c_int {
+    let (access_time, modification_time) = file_times_from_raw(times);
+
+    utimensat(fd, None, access_time, modification_time, true)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| FN_FUTIMENS(fd, times))
+}
+
+/// Hook for `libc::symlink`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn symlink_detour(
+    target: *const c_char,
+    link_path: *const c_char,
+) -> c_int {
+    symlink(target.checked_into(), link_path.checked_into())
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_link_path = update_ptr_from_bypass(link_path, &bypass);
+            FN_SYMLINK(target, raw_link_path)
+        })
+}
+
+/// Hook for `libc::link`.
+///
+/// When bypassing, we call the original function with the paths we got, as the bypass might refer
+/// to either of them.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn link_detour(
+    old_path: *const c_char,
+    new_path: *const c_char,
+) -> c_int {
+    link(old_path.checked_into(), new_path.checked_into())
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| FN_LINK(old_path, new_path))
+}
+
 /// Convenience function to setup file hooks (`x_detour`) with `frida_gum`.
 pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager) {
     replace!(hook_manager, "open", open_detour, FnOpen, FN_OPEN);
@@ -1172,6 +1385,59 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager) {
         FN_MKDIRAT
     );
 
+    replace!(hook_manager, "unlink", unlink_detour, FnUnlink, FN_UNLINK);
+    replace!(
+        hook_manager,
+        "unlinkat",
+        unlinkat_detour,
+        FnUnlinkat,
+        FN_UNLINKAT
+    );
+    replace!(hook_manager, "rmdir", rmdir_detour, FnRmdir, FN_RMDIR);
+    replace!(hook_manager, "rename", rename_detour, FnRename, FN_RENAME);
+
+    replace!(
+        hook_manager,
+        "truncate",
+        truncate_detour,
+        FnTruncate,
+        FN_TRUNCATE
+    );
+    replace!(
+        hook_manager,
+        "ftruncate",
+        ftruncate_detour,
+        FnFtruncate,
+        FN_FTRUNCATE
+    );
+
+    replace!(hook_manager, "chmod", chmod_detour, FnChmod, FN_CHMOD);
+    replace!(hook_manager, "fchmod", fchmod_detour, FnFchmod, FN_FCHMOD);
+    replace!(hook_manager, "chown", chown_detour, FnChown, FN_CHOWN);
+    replace!(hook_manager, "fchown", fchown_detour, FnFchown, FN_FCHOWN);
+    replace!(
+        hook_manager,
+        "utimensat",
+        utimensat_detour,
+        FnUtimensat,
+        FN_UTIMENSAT
+    );
+    replace!(
+        hook_manager,
+        "futimens",
+        futimens_detour,
+        FnFutimens,
+        FN_FUTIMENS
+    );
+    replace!(
+        hook_manager,
+        "symlink",
+        symlink_detour,
+        FnSymlink,
+        FN_SYMLINK
+    );
+    replace!(hook_manager, "link", link_detour, FnLink, FN_LINK);
+
     replace!(hook_manager, "lseek", lseek_detour, FnLseek, FN_LSEEK);
 
     replace!(hook_manager, "write", write_detour, FnWrite, FN_WRITE);
@@ -4,12 +4,15 @@ use std::{env, ffi::CString, io::SeekFrom, os::unix::io::RawFd, path::PathBuf};
 
 #[cfg(target_os = "linux")]
 use libc::{c_char, statx, statx_timestamp};
-use libc::{c_int, iovec, unlink, AT_FDCWD};
+use libc::{c_int, iovec, AT_FDCWD};
 use mirrord_protocol::{
     file::{
-        MakeDirAtRequest, MakeDirRequest, OpenFileRequest, OpenFileResponse, OpenOptionsInternal,
-        ReadFileResponse, ReadLinkFileRequest, ReadLinkFileResponse, SeekFileResponse,
-        WriteFileResponse, XstatFsResponse, XstatResponse,
+        ChmodRequest, ChownRequest, FchmodRequest, FchownRequest, FileTimeInternal,
+        FtruncateRequest, LinkRequest, MakeDirAtRequest, MakeDirRequest, OpenFileRequest,
+        OpenFileResponse, OpenOptionsInternal, ReadFileResponse, ReadLinkFileRequest,
+        ReadLinkFileResponse, RemoveDirRequest, RenameRequest, SeekFileResponse, SymlinkRequest,
+        TruncateRequest, UnlinkAtRequest, UnlinkRequest, UtimensatRequest, WriteFileResponse,
+        XstatFsResponse, XstatResponse,
     },
     ResponseError,
 };
@@ -23,6 +26,7 @@ use crate::{
     common,
     detour::{Bypass, Detour},
     error::{HookError, HookResult as Result},
+    file::filter::FileFilterExt,
 };
 
 /// 1 Megabyte. Large read requests can lead to timeouts.
@@ -157,7 +161,7 @@ fn create_local_fake_file(remote_fd: u64) -> Detour<RawFd> {
         close_remote_file_on_failure(remote_fd)?;
         Detour::Error(HookError::LocalFileCreation(remote_fd, error.0))
     } else {
-        unsafe { unlink(file_path_ptr) };
+        unsafe { libc::unlink(file_path_ptr) };
         Detour::Success(local_file_fd)
     }
 }
@@ -387,6 +391,317 @@ pub(crate) fn mkdirat(dirfd: RawFd, pathname: Detour<PathBuf>, mode: u32) -> Det
     }
 }
 
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn unlink(pathname: Detour<PathBuf>) -> Detour<()> {
+    let pathname = pathname?;
+
+    check_relative_paths!(pathname);
+
+    let path = remap_path!(pathname);
+
+    ensure_not_ignored!(path, true);
+
+    let unlink = UnlinkRequest { pathname: path };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(unlink)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn unlinkat(dirfd: RawFd, pathname: Detour<PathBuf>, flags: c_int) -> Detour<()> {
+    let pathname: PathBuf = pathname?;
+
+    if pathname.is_absolute() || dirfd == AT_FDCWD {
+        let path = remap_path!(pathname);
+
+        if flags & libc::AT_REMOVEDIR != 0 {
+            rmdir(Detour::Success(path))
+        } else {
+            unlink(Detour::Success(path))
+        }
+    } else {
+        // Relative path requires special handling, we must identify the relative part (relative to
+        // what).
+        let remote_fd = get_remote_fd(dirfd)?;
+
+        let unlink = UnlinkAtRequest {
+            dirfd: remote_fd,
+            pathname: pathname.clone(),
+            flags: u32::try_from(flags)?,
+        };
+
+        // `NotImplemented` error here means that the protocol doesn't support it.
+        match common::make_proxy_request_with_response(unlink)? {
+            Ok(response) => Detour::Success(response),
+            Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+            Err(fail) => Detour::Error(fail.into()),
+        }
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn rmdir(pathname: Detour<PathBuf>) -> Detour<()> {
+    let pathname = pathname?;
+
+    check_relative_paths!(pathname);
+
+    let path = remap_path!(pathname);
+
+    ensure_not_ignored!(path, true);
+
+    let rmdir = RemoveDirRequest { pathname: path };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(rmdir)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Renames `old_path` to `new_path` in the remote pod.
+///
+/// Both paths must be handled remotely, otherwise we bypass and let the rename happen locally.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn rename(old_path: Detour<PathBuf>, new_path: Detour<PathBuf>) -> Detour<()> {
+    let old_path = old_path?;
+    let new_path = new_path?;
+
+    check_relative_paths!(old_path);
+    check_relative_paths!(new_path);
+
+    let old_path = remap_path!(old_path);
+    let new_path = remap_path!(new_path);
+
+    ensure_not_ignored!(old_path, true);
+    ensure_not_ignored!(new_path, true);
+
+    let rename = RenameRequest { old_path, new_path };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(rename)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn truncate(path: Detour<PathBuf>, length: i64) -> Detour<()> {
+    let path = path?;
+
+    check_relative_paths!(path);
+
+    let path = remap_path!(path);
+
+    ensure_not_ignored!(path, true);
+
+    let truncate = TruncateRequest {
+        path,
+        length: u64::try_from(length)?,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(truncate)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn ftruncate(local_fd: RawFd, length: i64) -> Detour<()> {
+    let remote_fd = get_remote_fd(local_fd)?;
+
+    let ftruncate = FtruncateRequest {
+        fd: remote_fd,
+        length: u64::try_from(length)?,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(ftruncate)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn chmod(path: Detour<PathBuf>, mode: u32) -> Detour<()> {
+    let path = path?;
+
+    check_relative_paths!(path);
+
+    let path = remap_path!(path);
+
+    ensure_not_ignored!(path, true);
+
+    let chmod = ChmodRequest { path, mode };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(chmod)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn fchmod(local_fd: RawFd, mode: u32) -> Detour<()> {
+    let remote_fd = get_remote_fd(local_fd)?;
+
+    let fchmod = FchmodRequest {
+        fd: remote_fd,
+        mode,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(fchmod)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn chown(path: Detour<PathBuf>, owner: Option<u32>, group: Option<u32>) -> Detour<()> {
+    let path = path?;
+
+    check_relative_paths!(path);
+
+    let path = remap_path!(path);
+
+    ensure_not_ignored!(path, true);
+
+    let chown = ChownRequest { path, owner, group };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(chown)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn fchown(local_fd: RawFd, owner: Option<u32>, group: Option<u32>) -> Detour<()> {
+    let remote_fd = get_remote_fd(local_fd)?;
+
+    let fchown = FchownRequest {
+        fd: remote_fd,
+        owner,
+        group,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(fchown)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Changes the timestamps of a remote file.
+///
+/// When `pathname` is `None`, the timestamps of `dirfd` itself are changed (this is how we handle
+/// `futimens`).
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn utimensat(
+    dirfd: RawFd,
+    pathname: Option<Detour<PathBuf>>,
+    access_time: FileTimeInternal,
+    modification_time: FileTimeInternal,
+    follow_symlink: bool,
+) -> Detour<()> {
+    let (dirfd, pathname) = match pathname {
+        None => (Some(get_remote_fd(dirfd)?), None),
+        Some(pathname) => {
+            let pathname = pathname?;
+
+            if pathname.is_absolute() || dirfd == AT_FDCWD {
+                check_relative_paths!(pathname);
+
+                let path = remap_path!(pathname);
+
+                ensure_not_ignored!(path, true);
+
+                (None, Some(path))
+            } else {
+                // Relative path requires special handling, we must identify the relative part
+                // (relative to what).
+                (Some(get_remote_fd(dirfd)?), Some(pathname))
+            }
+        }
+    };
+
+    let utimensat = UtimensatRequest {
+        dirfd,
+        pathname,
+        access_time,
+        modification_time,
+        follow_symlink,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(utimensat)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Creates a symlink at `link_path` in the remote pod.
+///
+/// `target` is not checked nor remapped, as it's stored in the link as is.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn symlink(target: Detour<PathBuf>, link_path: Detour<PathBuf>) -> Detour<()> {
+    let target = target?;
+    let link_path = link_path?;
+
+    check_relative_paths!(link_path);
+
+    let link_path = remap_path!(link_path);
+
+    ensure_not_ignored!(link_path, true);
+
+    let symlink = SymlinkRequest { target, link_path };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(symlink)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn link(old_path: Detour<PathBuf>, new_path: Detour<PathBuf>) -> Detour<()> {
+    let old_path = old_path?;
+    let new_path = new_path?;
+
+    check_relative_paths!(old_path);
+    check_relative_paths!(new_path);
+
+    let old_path = remap_path!(old_path);
+    let new_path = remap_path!(new_path);
+
+    ensure_not_ignored!(old_path, true);
+    ensure_not_ignored!(new_path, true);
+
+    let link = LinkRequest { old_path, new_path };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(link)? {
+        Ok(response) => Detour::Success(response),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
 pub(crate) fn pwrite(local_fd: RawFd, buffer: &[u8], offset: u64) -> Detour<WriteFileResponse> {
     let remote_fd = get_remote_fd(local_fd)?;
     trace!("pwrite: local_fd {local_fd}");
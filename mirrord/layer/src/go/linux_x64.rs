@@ -8,11 +8,13 @@ use crate::{
 };
 /*
  * Reference for which syscalls are managed by the handlers:
- * SYS_openat: Syscall6
+ * SYS_openat, SYS_openat2, SYS_pread64, SYS_pwrite64, SYS_newfstatat, SYS_statx,
+ * SYS_readlinkat: Syscall6
  * SYS_read, SYS_write, SYS_lseek, SYS_faccessat: Syscall
  *
  * SYS_socket, SYS_bind, SYS_listen, SYS_accept, SYS_close: Syscall
- * SYS_accept4: Syscall6
+ * SYS_getsockname, SYS_getpeername, SYS_sendmsg, SYS_recvmsg: Syscall
+ * SYS_accept4, SYS_sendto, SYS_recvfrom: Syscall6
  *
  * SYS_getdents64: Syscall on go 1.18, Syscall6 on go 1.19.
  */
@@ -322,6 +324,10 @@ unsafe extern "C" fn c_abi_syscall_handler(
         libc::SYS_connect => connect_detour(param1 as _, param2 as _, param3 as _) as i64,
         libc::SYS_accept => accept_detour(param1 as _, param2 as _, param3 as _) as i64,
         libc::SYS_close => close_detour(param1 as _) as i64,
+        libc::SYS_getsockname => getsockname_detour(param1 as _, param2 as _, param3 as _) as i64,
+        libc::SYS_getpeername => getpeername_detour(param1 as _, param2 as _, param3 as _) as i64,
+        libc::SYS_sendmsg => sendmsg_detour(param1 as _, param2 as _, param3 as _) as i64,
+        libc::SYS_recvmsg => recvmsg_detour(param1 as _, param2 as _, param3 as _) as i64,
 
         _ if crate::setup().fs_config().is_active() => match syscall {
             libc::SYS_read => read_detour(param1 as _, param2 as _, param3 as _) as i64,
@@ -5,7 +5,11 @@
 use errno::errno;
 use tracing::trace;
 
-use crate::{close_detour, file::hooks::*, socket::hooks::*};
+use crate::{
+    close_detour,
+    file::{hooks::*, ops::statx_logic},
+    socket::hooks::*,
+};
 
 #[cfg_attr(
     all(target_os = "linux", target_arch = "x86_64"),
@@ -43,6 +47,27 @@ unsafe extern "C" fn c_abi_syscall6_handler(
         libc::SYS_accept => accept_detour(param1 as _, param2 as _, param3 as _) as i64,
         libc::SYS_close => close_detour(param1 as _) as i64,
         libc::SYS_connect => connect_detour(param1 as _, param2 as _, param3 as _) as i64,
+        libc::SYS_getsockname => getsockname_detour(param1 as _, param2 as _, param3 as _) as i64,
+        libc::SYS_getpeername => getpeername_detour(param1 as _, param2 as _, param3 as _) as i64,
+        // Used by Go's pure resolver for DNS over UDP.
+        libc::SYS_sendto => send_to_detour(
+            param1 as _,
+            param2 as _,
+            param3 as _,
+            param4 as _,
+            param5 as _,
+            param6 as _,
+        ) as i64,
+        libc::SYS_recvfrom => recv_from_detour(
+            param1 as _,
+            param2 as _,
+            param3 as _,
+            param4 as _,
+            param5 as _,
+            param6 as _,
+        ) as i64,
+        libc::SYS_sendmsg => sendmsg_detour(param1 as _, param2 as _, param3 as _) as i64,
+        libc::SYS_recvmsg => recvmsg_detour(param1 as _, param2 as _, param3 as _) as i64,
 
         _ if crate::setup().fs_config().is_active() => {
             match syscall {
@@ -76,30 +101,26 @@ unsafe extern "C" fn c_abi_syscall6_handler(
                 //   SYS_NEWFSTATAT (262)
                 // - SYS_lstat: maps to fstatat with AT_FDCWD and AT_SYMLINK_NOFOLLOW in go - no
                 //   additional hook needed
-                // - SYS_statx: not supported in go
+                // - SYS_statx will use statx_logic, used by some libraries (e.g. golang.org/x/sys)
                 libc::SYS_newfstatat => {
                     fstatat_logic(param1 as _, param2 as _, param3 as _, param4 as _)
                         .unwrap_or_bypass_with(|_| {
-                            let (Ok(result) | Err(result)) = syscalls::syscall!(
-                                syscalls::Sysno::from(syscall as i32),
-                                param1,
-                                param2,
-                                param3,
-                                param4,
-                                param5,
-                                param6
-                            )
-                            .map(|success| success as i64)
-                            .map_err(|fail| {
-                                let raw_errno = fail.into_raw();
-                                errno::set_errno(errno::Errno(raw_errno));
-
-                                -(raw_errno as i64)
-                            });
-                            result as i32
+                            bypass_syscall6(syscall, param1, param2, param3, param4, param5, param6)
+                                as i32
                         })
                         .into()
                 }
+                libc::SYS_statx => statx_logic(
+                    param1 as _,
+                    param2 as _,
+                    param3 as _,
+                    param4 as _,
+                    param5 as _,
+                )
+                .unwrap_or_bypass_with(|_| {
+                    bypass_syscall6(syscall, param1, param2, param3, param4, param5, param6) as i32
+                })
+                .into(),
                 libc::SYS_fstat => fstat_detour(param1 as _, param2 as _) as i64,
                 libc::SYS_fsync => fsync_detour(param1 as _) as i64,
                 libc::SYS_fdatasync => fsync_detour(param1 as _) as i64,
@@ -107,52 +128,41 @@ unsafe extern "C" fn c_abi_syscall6_handler(
                     openat_detour(param1 as _, param2 as _, param3 as _, param4 as libc::c_int)
                         as i64
                 }
+                // `open_how.resolve` flags can't be honored for remote files, so we intercept
+                // only calls that don't set any.
+                libc::SYS_openat2
+                    if (param3 as *const libc::open_how)
+                        .as_ref()
+                        .is_some_and(|how| how.resolve == 0) =>
+                {
+                    let how = &*(param3 as *const libc::open_how);
+                    openat_detour(
+                        param1 as _,
+                        param2 as _,
+                        how.flags as _,
+                        how.mode as libc::c_int,
+                    ) as i64
+                }
                 libc::SYS_getdents64 => {
                     getdents64_detour(param1 as _, param2 as _, param3 as _) as i64
                 }
+                // `os.Readlink` in Go maps to `readlinkat` with `AT_FDCWD`, paths relative to
+                // other descriptors are not intercepted.
+                libc::SYS_readlinkat if param1 == libc::AT_FDCWD as i64 => {
+                    readlink_logic(param2 as _, param3 as _, param4 as _).unwrap_or_bypass_with(
+                        |_| {
+                            bypass_syscall6(syscall, param1, param2, param3, param4, param5, param6)
+                                as _
+                        },
+                    ) as i64
+                }
                 #[cfg(all(target_os = "linux", not(target_arch = "aarch64")))]
                 libc::SYS_mkdir => mkdir_detour(param1 as _, param2 as _) as i64,
                 libc::SYS_mkdirat => mkdirat_detour(param1 as _, param2 as _, param3 as _) as i64,
-                _ => {
-                    let (Ok(result) | Err(result)) = syscalls::syscall!(
-                        syscalls::Sysno::from(syscall as i32),
-                        param1,
-                        param2,
-                        param3,
-                        param4,
-                        param5,
-                        param6
-                    )
-                    .map(|success| success as i64)
-                    .map_err(|fail| {
-                        let raw_errno = fail.into_raw();
-                        errno::set_errno(errno::Errno(raw_errno));
-
-                        -(raw_errno as i64)
-                    });
-                    result
-                }
+                _ => bypass_syscall6(syscall, param1, param2, param3, param4, param5, param6),
             }
         }
-        _ => {
-            let (Ok(result) | Err(result)) = syscalls::syscall!(
-                syscalls::Sysno::from(syscall as i32),
-                param1,
-                param2,
-                param3,
-                param4,
-                param5,
-                param6
-            )
-            .map(|success| success as i64)
-            .map_err(|fail| {
-                let raw_errno = fail.into_raw();
-                errno::set_errno(errno::Errno(raw_errno));
-
-                -(raw_errno as i64)
-            });
-            result
-        }
+        _ => bypass_syscall6(syscall, param1, param2, param3, param4, param5, param6),
     };
 
     if syscall_result.is_negative() {
@@ -162,3 +172,32 @@ unsafe extern "C" fn c_abi_syscall6_handler(
         syscall_result
     }
 }
+
+/// Makes the syscall without any interception, setting `errno` on failure.
+unsafe fn bypass_syscall6(
+    syscall: i64,
+    param1: i64,
+    param2: i64,
+    param3: i64,
+    param4: i64,
+    param5: i64,
+    param6: i64,
+) -> i64 {
+    let (Ok(result) | Err(result)) = syscalls::syscall!(
+        syscalls::Sysno::from(syscall as i32),
+        param1,
+        param2,
+        param3,
+        param4,
+        param5,
+        param6
+    )
+    .map(|success| success as i64)
+    .map_err(|fail| {
+        let raw_errno = fail.into_raw();
+        errno::set_errno(errno::Errno(raw_errno));
+
+        -(raw_errno as i64)
+    });
+    result
+}
@@ -5,20 +5,14 @@ use mirrord_config::{
     feature::{
         env::EnvConfig,
         fs::FsConfig,
-        network::{
-            incoming::{
-                http_filter::{HttpFilterConfig, InnerFilter},
-                IncomingConfig,
-            },
-            outgoing::OutgoingConfig,
-        },
+        network::{incoming::IncomingConfig, outgoing::OutgoingConfig},
     },
     target::Target,
     LayerConfig,
 };
-use mirrord_intproxy_protocol::PortSubscription;
+use mirrord_intproxy_protocol::{http_filter::steal_http_filter, PortSubscription};
 use mirrord_protocol::{
-    tcp::{Filter, HttpFilter, StealType},
+    tcp::{HttpFilter, StealType},
     Port,
 };
 use regex::RegexSet;
@@ -135,6 +129,10 @@ impl LayerSetup {
         self.config.feature.network.dns.enabled
     }
 
+    pub fn ipv6_enabled(&self) -> bool {
+        self.config.feature.network.ipv6
+    }
+
     pub fn targetless(&self) -> bool {
         self.config
             .target
@@ -227,74 +225,13 @@ impl IncomingMode {
 
         let ports = { http_filter_config.ports.iter().copied().collect() };
 
-        // Matching all fields to make this check future-proof.
-        let filter = match http_filter_config {
-            HttpFilterConfig {
-                path_filter: Some(path),
-                header_filter: None,
-                all_of: None,
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::Filter(HttpFilter::Path(
-                Filter::new(path.into()).expect("invalid filter expression"),
-            )),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: Some(header),
-                all_of: None,
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::Filter(HttpFilter::Header(
-                Filter::new(header.into()).expect("invalid filter expression"),
-            )),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: None,
-                all_of: Some(filters),
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::Filter(Self::make_composite_filter(true, filters)),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: None,
-                all_of: None,
-                any_of: Some(filters),
-                ports: _ports,
-            } => StealHttpFilter::Filter(Self::make_composite_filter(false, filters)),
-
-            HttpFilterConfig {
-                path_filter: None,
-                header_filter: None,
-                all_of: None,
-                any_of: None,
-                ports: _ports,
-            } => StealHttpFilter::None,
-
-            _ => panic!("multiple HTTP filters specified, this is a bug"),
-        };
+        let filter = steal_http_filter(http_filter_config)
+            .map(StealHttpFilter::Filter)
+            .unwrap_or(StealHttpFilter::None);
 
         Self::Steal(StealHttpSettings { filter, ports })
     }
 
-    fn make_composite_filter(all: bool, filters: &[InnerFilter]) -> HttpFilter {
-        let filters = filters
-            .iter()
-            .map(|filter| match filter {
-                InnerFilter::Path { path } => {
-                    HttpFilter::Path(Filter::new(path.clone()).expect("invalid filter expression"))
-                }
-                InnerFilter::Header { header } => HttpFilter::Header(
-                    Filter::new(header.clone()).expect("invalid filter expression"),
-                ),
-            })
-            .collect();
-
-        HttpFilter::Composite { all, filters }
-    }
-
     /// Returns [`PortSubscription`] request to be used for the given port.
     pub fn subscription(&self, port: Port) -> PortSubscription {
         let Self::Steal(steal) = self else {
@@ -2,7 +2,7 @@
 //! absolute minimum
 use std::{
     collections::HashMap,
-    net::{SocketAddr, ToSocketAddrs},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
     os::unix::io::RawFd,
     str::FromStr,
     sync::{Arc, LazyLock, Mutex},
@@ -14,12 +14,13 @@ use hashbrown::hash_set::HashSet;
 use hooks::FN_FCNTL;
 use libc::{c_int, sockaddr, socklen_t};
 use mirrord_config::feature::network::{
-    filter::{AddressFilter, ProtocolAndAddressFilter, ProtocolFilter},
+    filter::{ProtocolAndAddressFilter, ProtocolFilter},
     outgoing::{OutgoingConfig, OutgoingFilterConfig},
 };
-use mirrord_intproxy_protocol::{NetProtocol, PortUnsubscribe};
+use mirrord_intproxy_protocol::{NetProtocol, PortUnsubscribe, UdpPortUnsubscribe};
 use mirrord_protocol::{
-    outgoing::SocketAddress, DnsLookupError, ResolveErrorKindInternal, ResponseError,
+    dns::AddressFamily, outgoing::SocketAddress, DnsLookupError, ResolveErrorKindInternal,
+    ResponseError,
 };
 use socket2::SockAddr;
 use tracing::warn;
@@ -33,6 +34,7 @@ use crate::{
 
 #[cfg(target_os = "macos")]
 mod apple_dnsinfo;
+mod dns_message;
 pub(crate) mod dns_selector;
 pub(super) mod hooks;
 pub(crate) mod ops;
@@ -224,16 +226,43 @@ impl UserSocket {
     /// Inform internal proxy about closing a listening port.
     #[mirrord_layer_macro::instrument(level = "trace", fields(pid = std::process::id()), ret)]
     pub(crate) fn close(&self) {
-        if let Self {
-            state: SocketState::Listening(bound),
-            kind: SocketKind::Tcp(..),
-            ..
-        } = self
-        {
-            let _ = common::make_proxy_request_no_response(PortUnsubscribe {
-                port: bound.requested_address.port(),
-                listening_on: bound.address,
-            });
+        match self {
+            Self {
+                state: SocketState::Listening(bound),
+                kind: SocketKind::Tcp(..),
+                ..
+            } => {
+                let _ = common::make_proxy_request_no_response(PortUnsubscribe {
+                    port: bound.requested_address.port(),
+                    listening_on: bound.address,
+                });
+            }
+            Self {
+                state: SocketState::Listening(bound),
+                kind: SocketKind::Udp(..),
+                ..
+            } => {
+                let port = bound.requested_address.port();
+                let mapped_port = crate::setup()
+                    .incoming_config()
+                    .port_mapping
+                    .get_by_left(&port)
+                    .copied()
+                    .unwrap_or(port);
+
+                let _ = common::make_proxy_request_no_response(UdpPortUnsubscribe {
+                    port: mapped_port,
+                    listening_on: bound.address,
+                });
+
+                if let Ok(mut peers) = ops::UDP_PEERS.lock() {
+                    peers.retain(|(listener_address, _), _| *listener_address != bound.address);
+                }
+                if let Ok(mut peers) = ops::STOLEN_UDP_PEERS.lock() {
+                    peers.retain(|(listener_address, _), _| *listener_address != bound.address);
+                }
+            }
+            _ => {}
         }
     }
 }
@@ -344,8 +373,15 @@ impl OutgoingSelector {
             Self::Remote(filters) => (filters, false),
         };
 
+        let protocol = match protocol {
+            NetProtocol::Stream => ProtocolFilter::Tcp,
+            NetProtocol::Datagrams => ProtocolFilter::Udp,
+        };
         for filter in filters {
-            if !filter.matches(address, protocol, selector_is_local)? {
+            let matches = filter.matches_with(address, protocol, |name| {
+                resolve_filter_name(name, address.port(), selector_is_local)
+            })?;
+            if !matches {
                 continue;
             }
 
@@ -402,88 +438,47 @@ impl OutgoingSelector {
     }
 }
 
-/// [`ProtocolAndAddressFilter`] extension.
-trait ProtocolAndAddressFilterExt {
-    /// Matches the outgoing connection request (given as [[`SocketAddr`], [`NetProtocol`]] pair)
-    /// against this filter.
-    ///
-    /// # Note on DNS resolution
-    ///
-    /// This method may require a DNS resolution (when [`ProtocolAndAddressFilter::address`] is
-    /// [`AddressFilter::Name`]). If remote DNS is disabled or `force_local_dns`
-    /// flag is used, the method uses local resolution [`ToSocketAddrs`]. Otherwise, it uses
-    /// remote resolution [`remote_getaddrinfo`].
-    fn matches(
-        &self,
-        address: SocketAddr,
-        protocol: NetProtocol,
-        force_local_dns: bool,
-    ) -> HookResult<bool>;
-}
-
-impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
-    fn matches(
-        &self,
-        address: SocketAddr,
-        protocol: NetProtocol,
-        force_local_dns: bool,
-    ) -> HookResult<bool> {
-        if let (ProtocolFilter::Tcp, NetProtocol::Datagrams)
-        | (ProtocolFilter::Udp, NetProtocol::Stream) = (self.protocol, protocol)
-        {
-            return Ok(false);
+/// Resolves the name from an outgoing filter, for [`ProtocolAndAddressFilter::matches_with`].
+///
+/// If remote DNS is disabled or `force_local_dns` flag is used, the name is resolved locally with
+/// [`ToSocketAddrs`]. Otherwise, it uses remote resolution [`remote_getaddrinfo`].
+fn resolve_filter_name(name: &str, port: u16, force_local_dns: bool) -> HookResult<Vec<IpAddr>> {
+    if crate::setup().remote_dns_enabled() && !force_local_dns {
+        let family = if crate::setup().ipv6_enabled() {
+            AddressFamily::Both
+        } else {
+            AddressFamily::Ipv4Only
         };
 
-        let port = self.address.port();
-        if port != 0 && port != address.port() {
-            return Ok(false);
+        match remote_getaddrinfo(name.to_string(), family) {
+            Ok(res) => Ok(res.into_iter().map(|(_, ip)| ip).collect()),
+            Err(HookError::ResponseError(ResponseError::DnsLookup(DnsLookupError {
+                kind: ResolveErrorKindInternal::NoRecordsFound(..),
+            }))) => Ok(vec![]),
+            Err(e) => {
+                tracing::error!(error = ?e, "Remote resolution of OutgoingFilter failed");
+                Err(e)
+            }
         }
+    } else {
+        let _guard = DetourGuard::new();
 
-        match &self.address {
-            AddressFilter::Name(name, port) => {
-                let resolved_ips = if crate::setup().remote_dns_enabled() && !force_local_dns {
-                    match remote_getaddrinfo(name.to_string()) {
-                        Ok(res) => res.into_iter().map(|(_, ip)| ip).collect(),
-                        Err(HookError::ResponseError(ResponseError::DnsLookup(
-                            DnsLookupError {
-                                kind: ResolveErrorKindInternal::NoRecordsFound(..),
-                            },
-                        ))) => vec![],
-                        Err(e) => {
-                            tracing::error!(error = ?e, "Remote resolution of OutgoingFilter failed");
-                            return Err(e);
-                        }
-                    }
+        match (name, port).to_socket_addrs() {
+            Ok(addresses) => Ok(addresses.map(|addr| addr.ip()).collect()),
+            Err(e) => {
+                let as_string = e.to_string();
+                if as_string.contains("Temporary failure in name resolution")
+                    || as_string.contains("nodename nor servname provided, or not known")
+                {
+                    // There is no special `ErrorKind` for case when no records are
+                    // found. We catch this case based
+                    // on error message.
+                    Ok(vec![])
                 } else {
-                    let _guard = DetourGuard::new();
-
-                    match (name.as_str(), *port).to_socket_addrs() {
-                        Ok(addresses) => addresses.map(|addr| addr.ip()).collect(),
-                        Err(e) => {
-                            let as_string = e.to_string();
-                            if as_string.contains("Temporary failure in name resolution")
-                                || as_string
-                                    .contains("nodename nor servname provided, or not known")
-                            {
-                                // There is no special `ErrorKind` for case when no records are
-                                // found. We catch this case based
-                                // on error message.
-                                vec![]
-                            } else {
-                                tracing::error!(error = ?e, "Local resolution of OutgoingFilter failed");
-                                return Err(e.into());
-                            }
-                        }
-                    }
-                };
-
-                Ok(resolved_ips.into_iter().any(|ip| ip == address.ip()))
-            }
-            AddressFilter::Socket(addr) => {
-                Ok(addr.ip().is_unspecified() || addr.ip() == address.ip())
+                    tracing::error!(error = ?e, "Local resolution of OutgoingFilter failed");
+                    Err(e.into())
+                }
             }
-            AddressFilter::Subnet(net, _) => Ok(net.contains(&address.ip())),
-            AddressFilter::Port(..) => Ok(true),
         }
     }
 }
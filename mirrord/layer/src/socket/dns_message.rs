@@ -0,0 +1,165 @@
+//! Minimal DNS wire format support for the hooks that expose raw DNS data to the user, see
+//! [`res_query`](super::ops::res_query) and [`getnameinfo`](super::ops::getnameinfo).
+
+use std::{fmt::Write, net::IpAddr};
+
+use mirrord_protocol::dns::DnsRecord;
+
+/// DNS class of all records that we resolve remotely (`IN`).
+pub(super) const CLASS_IN: u16 = 1;
+
+/// Numeric type of `PTR` records.
+pub(super) const RECORD_TYPE_PTR: u16 = 12;
+
+/// Header flags of the messages built in [`build_response`]: a response (`QR`) to a recursive query
+/// (`RD`, `RA`), without errors.
+const RESPONSE_FLAGS: u16 = 0x8180;
+
+/// Appends the given domain name to the `message`, as uncompressed labels.
+///
+/// Returns [`None`] if the name is not valid in the DNS wire format.
+fn encode_name(name: &str, message: &mut Vec<u8>) -> Option<()> {
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|l| !l.is_empty())
+    {
+        let length = u8::try_from(label.len()).ok().filter(|len| *len < 64)?;
+        message.push(length);
+        message.extend_from_slice(label.as_bytes());
+    }
+
+    message.push(0);
+
+    Some(())
+}
+
+/// Builds a DNS response message with a single question (`name`, `record_type`) and the given
+/// `records` in the answer section, like the one returned from `res_query`.
+///
+/// Returns [`None`] if any of the names is not valid in the DNS wire format.
+pub(super) fn build_response(
+    name: &str,
+    record_type: u16,
+    records: &[DnsRecord],
+) -> Option<Vec<u8>> {
+    let answers = u16::try_from(records.len()).ok()?;
+
+    let mut message = Vec::with_capacity(512);
+    // Header: ID, flags, QDCOUNT, ANCOUNT, NSCOUNT, ARCOUNT.
+    for field in [0, RESPONSE_FLAGS, 1, answers, 0, 0] {
+        message.extend_from_slice(&field.to_be_bytes());
+    }
+
+    encode_name(name, &mut message)?;
+    message.extend_from_slice(&record_type.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    for record in records {
+        encode_name(&record.name, &mut message)?;
+        message.extend_from_slice(&record.record_type.to_be_bytes());
+        message.extend_from_slice(&CLASS_IN.to_be_bytes());
+        message.extend_from_slice(&record.ttl.to_be_bytes());
+        message.extend_from_slice(&u16::try_from(record.data.len()).ok()?.to_be_bytes());
+        message.extend_from_slice(&record.data);
+    }
+
+    Some(message)
+}
+
+/// Decodes an uncompressed domain name from the record data (e.g. of a `PTR` record).
+///
+/// The returned name has no trailing `.`.
+pub(super) fn decode_name(data: &[u8]) -> Option<String> {
+    let mut labels = Vec::new();
+    let mut rest = data;
+
+    loop {
+        let (length, tail) = rest.split_first()?;
+        if *length == 0 {
+            break;
+        }
+
+        let label = tail.get(..usize::from(*length))?;
+        labels.push(std::str::from_utf8(label).ok()?);
+        rest = tail.get(usize::from(*length)..)?;
+    }
+
+    Some(labels.join("."))
+}
+
+/// Returns the fully qualified name used in reverse (`PTR`) lookups of the given address, e.g.
+/// `4.3.2.1.in-addr.arpa.` for `1.2.3.4`.
+pub(super) fn reverse_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(address) => {
+            let [a, b, c, d] = address.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa.")
+        }
+        IpAddr::V6(address) => {
+            let mut name = String::with_capacity(73);
+            for byte in address.octets().iter().rev() {
+                let _ = write!(name, "{:x}.{:x}.", byte & 0xf, byte >> 4);
+            }
+            name.push_str("ip6.arpa.");
+            name
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_names() {
+        assert_eq!(
+            reverse_name("10.0.1.2".parse().unwrap()),
+            "2.1.0.10.in-addr.arpa."
+        );
+        assert_eq!(
+            reverse_name("2001:db8::1".parse().unwrap()),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+        );
+    }
+
+    #[test]
+    fn names_round_trip() {
+        let mut data = Vec::new();
+        encode_name("my-service.default.svc.cluster.local.", &mut data).unwrap();
+        assert_eq!(data.first(), Some(&10));
+        assert_eq!(
+            decode_name(&data).as_deref(),
+            Some("my-service.default.svc.cluster.local")
+        );
+
+        assert!(encode_name(&"a".repeat(64), &mut Vec::new()).is_none());
+        assert!(decode_name(&[3, b'a', b'b']).is_none());
+    }
+
+    #[test]
+    fn response_with_srv_record() {
+        // Priority 10, weight 5, port 8080, target `a.b`.
+        let srv_data = vec![0, 10, 0, 5, 0x1f, 0x90, 1, b'a', 1, b'b', 0];
+        let records = [DnsRecord {
+            name: "_http._tcp.b.".to_string(),
+            record_type: 33,
+            ttl: 30,
+            data: srv_data.clone(),
+        }];
+
+        let message = build_response("_http._tcp.b", 33, &records).unwrap();
+
+        let question = [
+            5, b'_', b'h', b't', b't', b'p', 4, b'_', b't', b'c', b'p', 1, b'b', 0,
+        ];
+        let mut expected = vec![0, 0, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+        expected.extend_from_slice(&question);
+        expected.extend_from_slice(&[0, 33, 0, 1]);
+        expected.extend_from_slice(&question);
+        expected.extend_from_slice(&[0, 33, 0, 1, 0, 0, 0, 30, 0, 11]);
+        expected.extend_from_slice(&srv_data);
+
+        assert_eq!(message, expected);
+    }
+}
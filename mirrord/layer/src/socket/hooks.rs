@@ -1,5 +1,5 @@
 use alloc::ffi::CString;
-use core::{cmp, ffi::CStr};
+use core::{cmp, ffi::CStr, ptr};
 use std::{
     collections::HashSet,
     os::unix::io::RawFd,
@@ -14,7 +14,11 @@ use mirrord_layer_macro::{hook_fn, hook_guard_fn};
 #[cfg(target_os = "macos")]
 use super::apple_dnsinfo::*;
 use super::ops::*;
-use crate::{detour::DetourGuard, hooks::HookManager, replace};
+use crate::{
+    detour::{Detour, DetourGuard},
+    hooks::HookManager,
+    replace,
+};
 
 /// Here we keep addr infos that we allocated so we'll know when to use the original
 /// freeaddrinfo function and when to use our implementation
@@ -70,7 +74,7 @@ pub(super) unsafe extern "C" fn _connect_nocancel_detour(
 }
 
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn getpeername_detour(
+pub(crate) unsafe extern "C" fn getpeername_detour(
     sockfd: RawFd,
     address: *mut sockaddr,
     address_len: *mut socklen_t,
@@ -328,9 +332,122 @@ unsafe extern "C" fn freeaddrinfo_detour(addrinfo: *mut libc::addrinfo) {
     }
 }
 
+/// Turns the raw pointer parameters into Rust types and calls `ops::res_query`.
+#[hook_guard_fn]
+unsafe extern "C" fn res_query_detour(
+    raw_name: *const c_char,
+    class: c_int,
+    record_type: c_int,
+    answer: *mut u8,
+    answer_length: c_int,
+) -> c_int {
+    let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+
+    res_query(
+        rawish_name,
+        class,
+        record_type,
+        answer,
+        answer_length,
+        false,
+    )
+    .unwrap_or_bypass_with(|_| FN_RES_QUERY(raw_name, class, record_type, answer, answer_length))
+}
+
+/// Same as [`res_query_detour`], but the agent applies the search domains to the name.
+#[hook_guard_fn]
+unsafe extern "C" fn res_search_detour(
+    raw_name: *const c_char,
+    class: c_int,
+    record_type: c_int,
+    answer: *mut u8,
+    answer_length: c_int,
+) -> c_int {
+    let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+
+    res_query(rawish_name, class, record_type, answer, answer_length, true).unwrap_or_bypass_with(
+        |_| FN_RES_SEARCH(raw_name, class, record_type, answer, answer_length),
+    )
+}
+
+/// `resolv.h` in glibc defines `res_query` as a macro for `__res_query`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn __res_query_detour(
+    raw_name: *const c_char,
+    class: c_int,
+    record_type: c_int,
+    answer: *mut u8,
+    answer_length: c_int,
+) -> c_int {
+    let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+
+    res_query(
+        rawish_name,
+        class,
+        record_type,
+        answer,
+        answer_length,
+        false,
+    )
+    .unwrap_or_bypass_with(|_| FN___RES_QUERY(raw_name, class, record_type, answer, answer_length))
+}
+
+/// `resolv.h` in glibc defines `res_search` as a macro for `__res_search`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn __res_search_detour(
+    raw_name: *const c_char,
+    class: c_int,
+    record_type: c_int,
+    answer: *mut u8,
+    answer_length: c_int,
+) -> c_int {
+    let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+
+    res_query(rawish_name, class, record_type, answer, answer_length, true).unwrap_or_bypass_with(
+        |_| FN___RES_SEARCH(raw_name, class, record_type, answer, answer_length),
+    )
+}
+
+/// Resolves the host name with `ops::getnameinfo`, then calls the original function to fill in the
+/// service name.
+#[hook_guard_fn]
+unsafe extern "C" fn getnameinfo_detour(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host: *mut c_char,
+    host_length: socklen_t,
+    service: *mut c_char,
+    service_length: socklen_t,
+    flags: c_int,
+) -> c_int {
+    match getnameinfo(raw_address, address_length, host, host_length, flags) {
+        Detour::Success(0) if !service.is_null() && service_length > 0 => FN_GETNAMEINFO(
+            raw_address,
+            address_length,
+            ptr::null_mut(),
+            0,
+            service,
+            service_length,
+            flags,
+        ),
+        Detour::Success(result) => result,
+        _ => FN_GETNAMEINFO(
+            raw_address,
+            address_length,
+            host,
+            host_length,
+            service,
+            service_length,
+            flags,
+        ),
+    }
+}
+
 /// Not a faithful reproduction of what [`libc::recvmsg`] is supposed to do, see [`recv_from`].
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn recv_from_detour(
+pub(crate) unsafe extern "C" fn recv_from_detour(
     sockfd: i32,
     out_buffer: *mut c_void,
     buffer_length: size_t,
@@ -364,7 +481,7 @@ pub(super) unsafe extern "C" fn recv_from_detour(
 
 /// Not a faithful reproduction of what [`libc::sendto`] is supposed to do, see [`send_to`].
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn send_to_detour(
+pub(crate) unsafe extern "C" fn send_to_detour(
     sockfd: RawFd,
     raw_message: *const c_void,
     message_length: size_t,
@@ -401,14 +518,14 @@ pub(super) unsafe extern "C" fn send_to_detour(
 ///
 /// TODO(alex): We are ignoring the control message header [`libc::cmsghdr`].
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn recvmsg_detour(
+pub(crate) unsafe extern "C" fn recvmsg_detour(
     sockfd: i32,
     message_header: *mut libc::msghdr,
     flags: c_int,
 ) -> ssize_t {
     let recvmsg_result = FN_RECVMSG(sockfd, message_header, flags);
 
-    if recvmsg_result == -1 {
+    if recvmsg_result == -1 || (*message_header).msg_name.is_null() {
         recvmsg_result
     } else {
         // Fills the address, similar to how `recv_from` works.
@@ -422,11 +539,43 @@ pub(super) unsafe extern "C" fn recvmsg_detour(
     }
 }
 
+/// Not a faithful reproduction of what [`libc::recvmmsg`] is supposed to do, see [`recv_from`].
+///
+/// Fills the addresses of all of the received messages, like [`recvmsg_detour`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn recvmmsg_detour(
+    sockfd: i32,
+    message_vector: *mut libc::mmsghdr,
+    vector_length: libc::c_uint,
+    flags: c_int,
+    timeout: *mut libc::timespec,
+) -> c_int {
+    let recvmmsg_result = FN_RECVMMSG(sockfd, message_vector, vector_length, flags, timeout);
+
+    if recvmmsg_result > 0 {
+        let messages = std::slice::from_raw_parts_mut(message_vector, recvmmsg_result as usize);
+        for message in messages
+            .iter_mut()
+            .filter(|message| !message.msg_hdr.msg_name.is_null())
+        {
+            let _ = recv_from(
+                sockfd,
+                message.msg_len as isize,
+                message.msg_hdr.msg_name as *mut _,
+                &mut message.msg_hdr.msg_namelen,
+            );
+        }
+    }
+
+    recvmmsg_result
+}
+
 /// Not a faithful reproduction of what [`libc::sendmsg`] is supposed to do, see [`sendmsg`].
 //
 // TODO(alex): We are ignoring the control message header `libc::cmsghdr`.
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn sendmsg_detour(
+pub(crate) unsafe extern "C" fn sendmsg_detour(
     sockfd: RawFd,
     message_header: *const libc::msghdr,
     flags: c_int,
@@ -571,6 +720,14 @@ pub(crate) unsafe fn enable_socket_hooks(
 
     #[cfg(target_os = "linux")]
     {
+        replace!(
+            hook_manager,
+            "recvmmsg",
+            recvmmsg_detour,
+            FnRecvmmsg,
+            FN_RECVMMSG
+        );
+
         // Here we replace a function of libuv and not libc, so we pass None as the .
         replace!(
             hook_manager,
@@ -624,6 +781,49 @@ pub(crate) unsafe fn enable_socket_hooks(
             FnFreeaddrinfo,
             FN_FREEADDRINFO
         );
+
+        replace!(
+            hook_manager,
+            "getnameinfo",
+            getnameinfo_detour,
+            FnGetnameinfo,
+            FN_GETNAMEINFO
+        );
+
+        replace!(
+            hook_manager,
+            "res_query",
+            res_query_detour,
+            FnRes_query,
+            FN_RES_QUERY
+        );
+
+        replace!(
+            hook_manager,
+            "res_search",
+            res_search_detour,
+            FnRes_search,
+            FN_RES_SEARCH
+        );
+
+        #[cfg(target_os = "linux")]
+        {
+            replace!(
+                hook_manager,
+                "__res_query",
+                __res_query_detour,
+                Fn__res_query,
+                FN___RES_QUERY
+            );
+
+            replace!(
+                hook_manager,
+                "__res_search",
+                __res_search_detour,
+                Fn__res_search,
+                FN___RES_SEARCH
+            );
+        }
         #[cfg(target_os = "macos")]
         {
             replace!(
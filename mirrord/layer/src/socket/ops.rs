@@ -11,18 +11,20 @@ use std::{
     path::PathBuf,
     ptr::{self, copy_nonoverlapping},
     sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
 };
 
 use errno::set_errno;
-use libc::{c_int, c_void, hostent, sockaddr, socklen_t, AF_UNIX};
-use mirrord_config::feature::network::incoming::{IncomingConfig, IncomingMode};
+use libc::{c_char, c_int, c_void, hostent, sockaddr, socklen_t, AF_UNIX};
+use mirrord_config::feature::network::incoming::{IgnoredPort, IncomingMode, IncomingPort};
 use mirrord_intproxy_protocol::{
     ConnMetadataRequest, ConnMetadataResponse, NetProtocol, OutgoingConnectRequest,
-    OutgoingConnectResponse, PortSubscribe,
+    OutgoingConnectResponse, PortSubscribe, UdpPortSubscribe,
 };
 use mirrord_protocol::{
-    dns::{GetAddrInfoRequest, LookupRecord},
+    dns::{AddressFamily, DnsQueryRequest, DnsRecord, GetAddrInfoRequestV2, LookupRecord},
     file::{OpenFileResponse, OpenOptionsInternal, ReadFileResponse},
+    DnsLookupError, ResolveErrorKindInternal, ResponseError,
 };
 use nix::sys::socket::{sockopt, SockaddrIn, SockaddrIn6, SockaddrLike, SockaddrStorage};
 use socket2::SockAddr;
@@ -47,6 +49,39 @@ use crate::{
 pub(super) static REMOTE_DNS_REVERSE_MAPPING: LazyLock<Mutex<HashMap<IpAddr, String>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// How long we use the entries of [`UDP_PEERS`], before asking the internal proxy again.
+///
+/// The internal proxy closes the socket that delivers the datagrams from a peer after the peer is
+/// idle for a while, and the address can be bound again for another peer. This is much shorter,
+/// so we don't use stale entries.
+const UDP_PEER_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// How long we use the entries of [`STOLEN_UDP_PEERS`] after the last datagram or reply.
+///
+/// Shorter than the time after which the internal proxy closes the socket of an idle peer, see
+/// [`UDP_PEER_CACHE_TTL`].
+const STOLEN_UDP_PEER_TTL: Duration = Duration::from_secs(30);
+
+/// Socket addresses keyed by the address of a listening socket and another socket address, with the
+/// time they were last used. See [`UDP_PEERS`] and [`STOLEN_UDP_PEERS`].
+type UdpPeers = HashMap<(SocketAddr, SocketAddr), (SocketAddr, Instant)>;
+
+/// Original peers of the mirrored and stolen UDP datagrams, keyed by the address of the receiving
+/// listening socket and the address of the internal proxy socket that delivered the datagram.
+///
+/// Caches the [`ConnMetadataRequest`] results in [`recv_from`] for [`UDP_PEER_CACHE_TTL`], so that
+/// we don't ask the internal proxy about every datagram.
+pub(super) static UDP_PEERS: LazyLock<Mutex<UdpPeers>> = LazyLock::new(Default::default);
+
+/// Addresses of the internal proxy sockets that deliver stolen UDP datagrams, keyed by the address
+/// of the receiving listening socket and the original remote peer, with the time they were last
+/// used.
+///
+/// Filled in [`recv_from`] and used in [`send_dns_patch`], so that the replies sent by the user
+/// application reach the internal proxy, which sends them back to the original peer. Entries are
+/// used for [`STOLEN_UDP_PEER_TTL`].
+pub(super) static STOLEN_UDP_PEERS: LazyLock<Mutex<UdpPeers>> = LazyLock::new(Default::default);
+
 /// Hostname initialized from the agent with [`gethostname`].
 pub(crate) static HOSTNAME: OnceLock<CString> = OnceLock::new();
 
@@ -129,7 +164,7 @@ pub(super) fn socket(domain: c_int, type_: c_int, protocol: c_int) -> Detour<Raw
         Ok(())
     }?;
 
-    if domain == libc::AF_INET6 {
+    if domain == libc::AF_INET6 && !crate::setup().ipv6_enabled() {
         return Detour::Error(HookError::SocketUnsuportedIpv6);
     }
 
@@ -176,35 +211,6 @@ fn bind_similar_address(sockfd: c_int, requested_address: &SocketAddr) -> Detour
     }
 }
 
-/// Checks if given TCP port needs to be ignored
-/// based on http_filter/ports logic
-fn is_ignored_tcp_port(addr: &SocketAddr, config: &IncomingConfig) -> bool {
-    let mapped_port = crate::setup()
-        .incoming_config()
-        .port_mapping
-        .get_by_left(&addr.port())
-        .copied()
-        .unwrap_or_else(|| addr.port());
-    let http_filter_used = config.mode == IncomingMode::Steal && config.http_filter.is_filter_set();
-
-    // this is a bit weird but it makes more sense configured ports are the remote port
-    // and not the local, so the check is done on the mapped port
-    // see https://github.com/metalbear-co/mirrord/issues/2397
-    let not_a_filtered_port = !config.http_filter.ports.contains(&mapped_port);
-
-    let not_stolen_with_filter = !http_filter_used || not_a_filtered_port;
-
-    // Unfiltered ports were specified and the requested port is not one of them, or an HTTP filter
-    // is set and no unfiltered ports were specified.
-    let not_whitelisted = config
-        .ports
-        .as_ref()
-        .map(|ports| !ports.contains(&mapped_port))
-        .unwrap_or(http_filter_used);
-
-    is_ignored_port(addr) || (not_stolen_with_filter && not_whitelisted)
-}
-
 /// If the socket is not found in [`SOCKETS`], bypass.
 /// Otherwise, if it's not an ignored port, bind (possibly with a fallback to random port) and
 /// update socket state in [`SOCKETS`]. If it's an ignored port, remove the socket from [`SOCKETS`].
@@ -231,19 +237,26 @@ pub(super) fn bind(
             })?
     };
 
-    // we don't use `is_localhost` here since unspecified means to listen
-    // on all IPs.
-    if incoming_config.ignore_localhost && requested_address.ip().is_loopback() {
-        return Detour::Bypass(Bypass::IgnoreLocalhost(requested_port));
-    }
-
     // To handle #1458, we don't ignore port `0` for UDP.
-    if (matches!(socket.kind, SocketKind::Tcp(_)))
-        && is_ignored_tcp_port(&requested_address, incoming_config)
-        || crate::setup().is_debugger_port(&requested_address)
-        || incoming_config.ignore_ports.contains(&requested_port)
-    {
-        Err(Bypass::Port(requested_address.port()))?;
+    let ignored = match socket.kind {
+        SocketKind::Tcp(_) => match incoming_config.tcp_port(requested_address) {
+            IncomingPort::Ignored(ignored) => Some(ignored),
+            IncomingPort::Remote { .. } => None,
+        },
+        _ => incoming_config.ignored_address(requested_address),
+    };
+
+    match ignored {
+        // we don't use `is_localhost` here since unspecified means to listen
+        // on all IPs.
+        Some(IgnoredPort::Localhost) => {
+            return Detour::Bypass(Bypass::IgnoreLocalhost(requested_port));
+        }
+        Some(..) => Err(Bypass::Port(requested_port))?,
+        None if crate::setup().is_debugger_port(&requested_address) => {
+            Err(Bypass::Port(requested_port))?
+        }
+        None => {}
     }
 
     // Check that the domain matches the requested address.
@@ -327,10 +340,16 @@ pub(super) fn bind(
     .and_then(|(_, address)| address.as_socket())
     .bypass(Bypass::AddressConversion)?;
 
-    Arc::get_mut(&mut socket).unwrap().state = SocketState::Bound(Bound {
+    let bound = Bound {
         requested_address,
         address,
-    });
+    };
+    Arc::get_mut(&mut socket).unwrap().state =
+        if socket.kind.is_udp() && subscribe_udp_port(sockfd, &bound) {
+            SocketState::Listening(bound)
+        } else {
+            SocketState::Bound(bound)
+        };
 
     SOCKETS.lock()?.insert(sockfd, socket);
 
@@ -340,6 +359,57 @@ pub(super) fn bind(
     Detour::Success(0)
 }
 
+/// Subscribe to the agent on the real port of a UDP socket, when mirroring incoming traffic.
+/// Datagrams received from the agent on the real port will later be sent to the local address of
+/// the socket.
+///
+/// Returns whether the port was subscribed. If it was not, the socket stays bound only locally.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+fn subscribe_udp_port(sockfd: RawFd, bound: &Bound) -> bool {
+    let setup = crate::setup();
+    let incoming_config = setup.incoming_config();
+
+    // Port `0` is most likely a client socket, see #1458.
+    if incoming_config.mode == IncomingMode::Off
+        || setup.targetless()
+        || bound.requested_address.port() == 0
+    {
+        return false;
+    }
+
+    let mapped_port = incoming_config
+        .port_mapping
+        .get_by_left(&bound.requested_address.port())
+        .copied()
+        .unwrap_or_else(|| bound.requested_address.port());
+
+    match common::make_proxy_request_with_response(UdpPortSubscribe {
+        listening_on: bound.address,
+        port: mapped_port,
+        steal: incoming_config.mode == IncomingMode::Steal,
+    }) {
+        Ok(Ok(())) => {
+            tracing::debug!(
+                "daemon subscribed udp port {}",
+                bound.requested_address.port()
+            );
+            true
+        }
+        Ok(Err(error)) => {
+            tracing::warn!(
+                %error,
+                port = mapped_port,
+                "Failed to subscribe to incoming UDP traffic, the socket will receive only local datagrams"
+            );
+            false
+        }
+        Err(error) => {
+            error!(%error, "Failed to subscribe UDP port");
+            false
+        }
+    }
+}
+
 /// Warn the user if they are filtering HTTP, and it looks like they might have intended to also
 /// steal another port unfiltered, but didn't know they had to set `feature.network.incoming.ports`
 /// for that.
@@ -625,7 +695,8 @@ pub(super) fn connect(
                 .family()
                 .map(|family| family as i32)
                 .unwrap_or(-1);
-            if domain != libc::AF_INET && domain != libc::AF_UNIX {
+            let ipv6_domain = domain == libc::AF_INET6 && crate::setup().ipv6_enabled();
+            if domain != libc::AF_INET && domain != libc::AF_UNIX && !ipv6_domain {
                 return Detour::Bypass(Bypass::Domain(domain));
             }
             // I really hate it, but nix seems to really make this API bad :()
@@ -889,8 +960,12 @@ pub(super) fn dup<const SWITCH_MAP: bool>(fd: c_int, dup_fd: i32) -> Result<(),
 ///
 /// This function updates the mapping in [`REMOTE_DNS_REVERSE_MAPPING`].
 #[mirrord_layer_macro::instrument(level = Level::TRACE, ret, err)]
-pub(super) fn remote_getaddrinfo(node: String) -> HookResult<Vec<(String, IpAddr)>> {
-    let addr_info_list = common::make_proxy_request_with_response(GetAddrInfoRequest { node })?.0?;
+pub(super) fn remote_getaddrinfo(
+    node: String,
+    family: AddressFamily,
+) -> HookResult<Vec<(String, IpAddr)>> {
+    let addr_info_list =
+        common::make_proxy_request_with_response(GetAddrInfoRequestV2 { node, family })?.0?;
 
     let mut remote_dns_reverse_mapping = REMOTE_DNS_REVERSE_MAPPING.lock()?;
     addr_info_list.iter().for_each(|lookup| {
@@ -956,18 +1031,33 @@ pub(super) fn getaddrinfo(
 
     // TODO(alex): Use more fields from `raw_hints` to respect the user's `getaddrinfo` call.
     let libc::addrinfo {
+        ai_family,
         ai_socktype,
         ai_protocol,
         ..
     } = raw_hints;
 
+    let ipv6_enabled = crate::setup().ipv6_enabled();
+    let family = match ai_family {
+        _ if !ipv6_enabled => AddressFamily::Ipv4Only,
+        libc::AF_INET => AddressFamily::Ipv4Only,
+        libc::AF_INET6 => AddressFamily::Ipv6Only,
+        _ => AddressFamily::Both,
+    };
+
     // Some apps (gRPC on Python) use `::` to listen on all interfaces, and usually that just means
-    // resolve on unspecified. So we just return that in IpV4 because we don't support ipv6.
+    // resolve on unspecified. Without IPv6 support we return that in IPv4.
     let resolved_addr = if node == "::" {
+        let unspecified = if ipv6_enabled {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        };
+
         // name is "" because that's what happens in real flow.
-        vec![("".to_string(), IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+        vec![("".to_string(), unspecified)]
     } else {
-        remote_getaddrinfo(node.clone())?
+        remote_getaddrinfo(node.clone(), family)?
     };
 
     let mut managed_addr_info = MANAGED_ADDRINFO.lock()?;
@@ -1066,7 +1156,8 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
 
     crate::setup().dns_selector().check_query(&name, 0)?;
 
-    let hosts_and_ips = remote_getaddrinfo(name.clone())?;
+    // `gethostbyname` only ever returns IPv4 addresses.
+    let hosts_and_ips = remote_getaddrinfo(name.clone(), AddressFamily::Ipv4Only)?;
 
     // We could `unwrap` here, as this would have failed on the previous conversion.
     let host_name = CString::new(name)?;
@@ -1130,6 +1221,183 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
     Detour::Success(std::ptr::addr_of!(GETHOSTBYNAME_HOSTENT) as _)
 }
 
+/// Handles the remote communication part of [`res_query`] and [`getnameinfo`], resolving records of
+/// the given type through the agent.
+///
+/// With `search`, the agent applies the search domains from the target's `resolv.conf` to the
+/// `name`, see [`DnsQueryRequest::search`].
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+fn remote_dns_query(name: String, record_type: u16, search: bool) -> Detour<Vec<DnsRecord>> {
+    let request = DnsQueryRequest {
+        name,
+        record_type,
+        search,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(request)?.0 {
+        Ok(records) => Detour::Success(records),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Resolves records of any type (e.g. `SRV`, `TXT`, `MX`) through the agent, and writes the DNS
+/// response message to the `answer` buffer, like the original `res_query` and `res_search` do.
+///
+/// With `search` set to `false` (`res_query`), the `raw_name` is treated as fully qualified.
+/// Otherwise (`res_search`), the agent applies the search domains from the target's `resolv.conf`.
+///
+/// Returns the length of the written message, truncated to `answer_length`, or `-1` with `h_errno`
+/// set (see [`set_h_errno`]) if no records were found.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret, skip(answer))]
+pub(super) fn res_query(
+    raw_name: Option<&CStr>,
+    class: c_int,
+    record_type: c_int,
+    answer: *mut u8,
+    answer_length: c_int,
+    search: bool,
+) -> Detour<c_int> {
+    let name = raw_name
+        .bypass(Bypass::NullNode)?
+        .to_str()
+        .map_err(|_| Bypass::CStrConversion)?;
+
+    let record_type = u16::try_from(record_type).map_err(|_| Bypass::NotImplemented)?;
+    if class != c_int::from(dns_message::CLASS_IN) || answer.is_null() || answer_length <= 0 {
+        Detour::Bypass(Bypass::NotImplemented)?;
+    }
+
+    crate::setup().dns_selector().check_query(name, 0)?;
+
+    let records = match remote_dns_query(name.to_owned(), record_type, search) {
+        Detour::Success(records) => records,
+        Detour::Bypass(bypass) => return Detour::Bypass(bypass),
+        Detour::Error(error) => {
+            set_h_errno(h_errno_for(&error));
+            return Detour::Error(error);
+        }
+    };
+    if records.is_empty() {
+        set_h_errno(NO_DATA);
+        return Detour::Success(-1);
+    }
+
+    let message = dns_message::build_response(name, record_type, &records)
+        .ok_or(Bypass::AddressConversion)?;
+    let written = message.len().min(answer_length as usize);
+    unsafe { copy_nonoverlapping(message.as_ptr(), answer, written) };
+
+    Detour::Success(written as c_int)
+}
+
+/// `h_errno` value: the name does not exist.
+const HOST_NOT_FOUND: c_int = 1;
+/// `h_errno` value: temporary failure, e.g. the DNS server did not respond.
+const TRY_AGAIN: c_int = 2;
+/// `h_errno` value: unrecoverable failure.
+const NO_RECOVERY: c_int = 3;
+/// `h_errno` value: the name exists, but has no records of the requested type.
+const NO_DATA: c_int = 4;
+
+/// Sets `h_errno`, which the callers of `res_query` and `res_search` check on failure.
+fn set_h_errno(value: c_int) {
+    #[cfg(target_os = "linux")]
+    extern "C" {
+        fn __h_errno_location() -> *mut c_int;
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        *__h_errno_location() = value
+    };
+
+    #[cfg(target_os = "macos")]
+    extern "C" {
+        static mut h_errno: c_int;
+    }
+    #[cfg(target_os = "macos")]
+    unsafe {
+        h_errno = value
+    };
+}
+
+/// Picks the `h_errno` value for the failed remote query, based on the DNS response code.
+fn h_errno_for(error: &HookError) -> c_int {
+    const NOERROR: u16 = 0;
+    const NXDOMAIN: u16 = 3;
+
+    match error {
+        HookError::ResponseError(ResponseError::DnsLookup(DnsLookupError { kind })) => match kind {
+            ResolveErrorKindInternal::NoRecordsFound(NXDOMAIN) => HOST_NOT_FOUND,
+            ResolveErrorKindInternal::NoRecordsFound(NOERROR) => NO_DATA,
+            ResolveErrorKindInternal::NoRecordsFound(..)
+            | ResolveErrorKindInternal::NoConnections
+            | ResolveErrorKindInternal::Timeout => TRY_AGAIN,
+            _ => NO_RECOVERY,
+        },
+        _ => TRY_AGAIN,
+    }
+}
+
+/// Resolves the name of the given address with a remote `PTR` query, and writes it to the `host`
+/// buffer, like the original `getnameinfo` does.
+///
+/// Bypasses when the user asked for a numeric host (`NI_NUMERICHOST`) or when the remote lookup
+/// fails, unless the name is required (`NI_NAMEREQD`). The service part is always left for the
+/// original `getnameinfo`.
+///
+/// Returns `0` on success, or one of the `EAI_*` codes.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret, skip(raw_address, host))]
+pub(super) fn getnameinfo(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host: *mut c_char,
+    host_length: socklen_t,
+    flags: c_int,
+) -> Detour<c_int> {
+    if host.is_null() || host_length == 0 || flags & libc::NI_NUMERICHOST != 0 {
+        Detour::Bypass(Bypass::EmptyBuffer)?;
+    }
+
+    let address = SocketAddr::try_from_raw(raw_address, address_length)?;
+    crate::setup()
+        .dns_selector()
+        .check_query(&address.ip().to_string(), address.port())?;
+
+    let reverse_name = dns_message::reverse_name(address.ip());
+    let name = match remote_dns_query(reverse_name, dns_message::RECORD_TYPE_PTR, false) {
+        Detour::Success(records) => records
+            .iter()
+            .filter(|record| record.record_type == dns_message::RECORD_TYPE_PTR)
+            .find_map(|record| dns_message::decode_name(&record.data)),
+        Detour::Bypass(bypass) => return Detour::Bypass(bypass),
+        Detour::Error(..) => None,
+    };
+
+    let Some(mut name) = name else {
+        if flags & libc::NI_NAMEREQD != 0 {
+            return Detour::Success(libc::EAI_NONAME);
+        }
+
+        return Detour::Bypass(Bypass::EmptyOption);
+    };
+
+    if flags & libc::NI_NOFQDN != 0 {
+        name.truncate(name.find('.').unwrap_or(name.len()));
+    }
+
+    let name = CString::new(name)?;
+    let name = name.as_bytes_with_nul();
+    if name.len() > host_length as usize {
+        return Detour::Success(libc::EAI_OVERFLOW);
+    }
+
+    unsafe { copy_nonoverlapping(name.as_ptr().cast(), host, name.len()) };
+
+    Detour::Success(0)
+}
+
 /// Resolve hostname from remote host with caching for the result
 #[mirrord_layer_macro::instrument(level = "trace")]
 pub(super) fn gethostname() -> Detour<&'static CString> {
@@ -1189,6 +1457,15 @@ pub(super) fn read_remote_resolv_conf() -> Detour<Vec<u8>> {
 /// When the socket is in a [`Connected`] state, we call [`fill_address`] with its `remote_address`,
 /// instead of letting whatever came in `raw_source` through.
 ///
+/// ## Mirrored and stolen UDP traffic
+///
+/// When the socket is [`SocketState::Listening`], the datagrams are sent by the internal proxy
+/// (see [`subscribe_udp_port`]), and we use [`ConnMetadataRequest`] to retrieve the address of the
+/// original peer. The results are cached in [`UDP_PEERS`].
+///
+/// When stealing, we also remember the internal proxy socket in [`STOLEN_UDP_PEERS`], so that the
+/// replies can be sent back to the original peer.
+///
 /// See [`send_to`] for more information.
 #[mirrord_layer_macro::instrument(level = "trace", ret, skip(raw_source, source_length))]
 pub(super) fn recv_from(
@@ -1197,17 +1474,71 @@ pub(super) fn recv_from(
     raw_source: *mut sockaddr,
     source_length: *mut socklen_t,
 ) -> Detour<isize> {
-    SOCKETS
+    // Nowhere to put the address (e.g. `recvmsg` with a null `msg_name`).
+    if raw_source.is_null() || source_length.is_null() {
+        return Detour::Bypass(Bypass::EmptyOption);
+    }
+
+    let state = SOCKETS
         .lock()?
         .get(&sockfd)
-        .and_then(|socket| match &socket.state {
-            SocketState::Connected(Connected { remote_address, .. }) => {
-                Some(remote_address.clone())
+        .map(|socket| socket.state.clone());
+
+    let address: SockAddr = match state {
+        Some(SocketState::Connected(Connected { remote_address, .. })) => {
+            remote_address.try_into()?
+        }
+        // Mirrored and stolen datagrams come from the internal proxy, ask it about the original
+        // peer.
+        Some(SocketState::Listening(Bound {
+            address: listener_address,
+            ..
+        })) => {
+            let peer_address = SocketAddr::try_from_raw(raw_source, unsafe { *source_length })?;
+            let now = Instant::now();
+
+            let cached = UDP_PEERS
+                .lock()?
+                .get(&(listener_address, peer_address))
+                .filter(|(_, fetched_at)| now.duration_since(*fetched_at) < UDP_PEER_CACHE_TTL)
+                .map(|(remote_source, _)| *remote_source);
+
+            let remote_source = match cached {
+                Some(remote_source) => remote_source,
+                None => {
+                    let ConnMetadataResponse { remote_source, .. } =
+                        common::make_proxy_request_with_response(ConnMetadataRequest {
+                            listener_address,
+                            peer_address,
+                        })?;
+
+                    let mut peers = UDP_PEERS.lock()?;
+                    peers.retain(|_, (_, fetched_at)| {
+                        now.duration_since(*fetched_at) < UDP_PEER_CACHE_TTL
+                    });
+                    peers.insert((listener_address, peer_address), (remote_source, now));
+
+                    remote_source
+                }
+            };
+
+            if crate::setup().incoming_config().mode == IncomingMode::Steal {
+                let mut stolen_peers = STOLEN_UDP_PEERS.lock()?;
+                let previous =
+                    stolen_peers.insert((listener_address, remote_source), (peer_address, now));
+                if previous.is_none() {
+                    stolen_peers.retain(|_, (_, used_at)| {
+                        now.duration_since(*used_at) < STOLEN_UDP_PEER_TTL
+                    });
+                }
             }
-            _ => None,
-        })
-        .map(SocketAddress::try_into)?
-        .map(|address| fill_address(raw_source, source_length, address))??;
+
+            remote_source.into()
+        }
+        _ => Detour::Bypass(Bypass::EmptyOption)?,
+    };
+
+    fill_address(raw_source, source_length, address)?;
 
     errno::set_errno(errno::Errno(0));
     Detour::Success(recv_from_result)
@@ -1220,6 +1551,24 @@ fn send_dns_patch(
     user_socket_info: Arc<UserSocket>,
     destination: SocketAddr,
 ) -> Detour<SockAddr> {
+    // Replies to stolen datagrams go back through the internal proxy, see [`recv_from`].
+    if let SocketState::Listening(Bound { address, .. }) = &user_socket_info.state {
+        let now = Instant::now();
+        let stolen_peer = STOLEN_UDP_PEERS
+            .lock()?
+            .get_mut(&(*address, destination))
+            .filter(|(_, used_at)| now.duration_since(*used_at) < STOLEN_UDP_PEER_TTL)
+            .map(|(peer_address, used_at)| {
+                *used_at = now;
+                *peer_address
+            });
+
+        if let Some(peer_address) = stolen_peer {
+            SOCKETS.lock()?.insert(sockfd, user_socket_info);
+            return Detour::Success(SockAddr::from(peer_address));
+        }
+    }
+
     let mut sockets = SOCKETS.lock()?;
     // We want to keep holding this socket.
     sockets.insert(sockfd, user_socket_info);
@@ -1234,6 +1583,10 @@ fn send_dns_patch(
             SocketState::Bound(Bound {
                 requested_address,
                 address,
+            })
+            | SocketState::Listening(Bound {
+                requested_address,
+                address,
             }) => {
                 // Special case for port `0`, see `getsockname`.
                 if requested_address.port() == 0 {
@@ -23,6 +23,7 @@ use mirrord_kube::{
 };
 use mirrord_progress::Progress;
 use mirrord_protocol::{ClientMessage, DaemonMessage};
+use policy::ClientPolicy;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::{Receiver, Sender};
@@ -42,6 +43,7 @@ use crate::{
 mod conn_wrapper;
 mod discovery;
 pub mod error;
+pub mod policy;
 mod upgrade;
 
 /// State of client's [`Certificate`] the should be attached to some operator requests.
@@ -113,6 +115,9 @@ pub struct OperatorSession {
     /// Version of [`mirrord_protocol`] used by the operator.
     /// Used to create [`ConnectionWrapper`].
     pub operator_protocol_version: Option<Version>,
+    /// Parts of the policies that apply to the target and are enforced by the client.
+    #[serde(default)]
+    pub policy: ClientPolicy,
 }
 
 impl fmt::Debug for OperatorSession {
@@ -126,6 +131,7 @@ impl fmt::Debug for OperatorSession {
                 &self.operator_license_fingerprint,
             )
             .field("operator_protocol_version", &self.operator_protocol_version)
+            .field("policy", &self.policy)
             .finish()
     }
 }
@@ -565,6 +571,15 @@ impl OperatorApi<PreparedClientCert> {
             .supported_features()
             .contains(&NewOperatorFeature::ProxyApi);
 
+        let policy = ClientPolicy::fetch(
+            self.client(),
+            &target,
+            target
+                .namespace()
+                .unwrap_or_else(|| self.target_namespace(layer_config)),
+        )
+        .await?;
+
         let is_empty_deployment = target.empty_deployment();
         let (connect_url, session_id) = if layer_config.feature.copy_target.enabled
             // use copy_target for splitting queues
@@ -661,6 +676,7 @@ impl OperatorApi<PreparedClientCert> {
                 .protocol_version
                 .as_ref()
                 .and_then(|version| version.parse().ok()),
+            policy,
         };
 
         let mut connection_subtask = progress.subtask("connecting to the target");
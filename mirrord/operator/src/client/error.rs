@@ -16,6 +16,7 @@ pub enum OperatorOperation {
     GettingStatus,
     SessionManagement,
     ListingTargets,
+    FetchingPolicies,
 }
 
 impl fmt::Display for OperatorOperation {
@@ -28,6 +29,7 @@ impl fmt::Display for OperatorOperation {
             Self::GettingStatus => "getting status",
             Self::SessionManagement => "session management",
             Self::ListingTargets => "listing targets",
+            Self::FetchingPolicies => "fetching mirrord policies",
         };
 
         f.write_str(as_str)
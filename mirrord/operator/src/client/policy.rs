@@ -0,0 +1,512 @@
+//! Parts of the mirrord policies that are enforced by the client, see [`ClientPolicy`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    net::IpAddr,
+    path::{Component, Path, PathBuf},
+};
+
+use ipnet::IpNet;
+use kube::{api::ListParams, Api, Client, ResourceExt};
+use mirrord_auth::credential_store::UserIdentity;
+use mirrord_kube::resolved::ResolvedTarget;
+use mirrord_protocol::{
+    file::BlockedPath,
+    outgoing::SocketAddress,
+    tcp::{RequiredHeaderFilter, StealType},
+    BlockedAction, ResponseError,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::Level;
+use wildmatch::WildMatch;
+
+use super::error::{OperatorApiError, OperatorApiResult, OperatorOperation};
+use crate::crd::{
+    label_selector::LabelSelector,
+    policy::{
+        EnvPolicy, FsPolicy, HttpFilterPolicy, MirrordClusterPolicy, MirrordPolicy, NetworkPolicy,
+    },
+};
+
+/// A single pattern taken from a policy, together with the name of that policy.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PolicyPattern {
+    pattern: String,
+    policy: String,
+}
+
+/// Restrictions from the `fs`, `network.outgoing`, `env` and `httpFilter` sections of all
+/// [`MirrordPolicy`]s and [`MirrordClusterPolicy`]s that apply to the target.
+///
+/// The operator cannot see the remote files accessed, outgoing connections made and env vars
+/// fetched by the user application, so these sections are enforced by the mirrord client:
+/// 1. The CLI fetches the policies when creating a new operator session ([`ClientPolicy::fetch`]);
+/// 2. The policy is passed to the internal proxy inside of the
+///    [`OperatorSession`](super::OperatorSession);
+/// 3. The internal proxy responds to blocked requests with [`ResponseError::Forbidden`].
+///
+/// The `fs` restrictions are also sent to the agent ([`FsPolicyRequest`]), which checks them
+/// against the remote paths with symlinks resolved, and hides the blocked paths from directory
+/// listings. The same goes for the `httpFilter` restrictions
+/// ([`LayerTcpSteal::RequiredHeaderFilters`]), which the agent checks on every steal
+/// subscription.
+///
+/// [`FsPolicyRequest`]: mirrord_protocol::file::FsPolicyRequest
+/// [`LayerTcpSteal::RequiredHeaderFilters`]: mirrord_protocol::tcp::LayerTcpSteal::RequiredHeaderFilters
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientPolicy {
+    /// Patterns of remote paths that cannot be accessed.
+    fs_block: Vec<PolicyPattern>,
+    /// CIDRs of remote destinations that cannot be reached.
+    outgoing_block: Vec<PolicyPattern>,
+    /// Patterns of env var names that are not fetched from the target.
+    env_exclude: Vec<PolicyPattern>,
+    /// HTTP header filters that must be included in every steal filter.
+    steal_header_filter: Vec<PolicyPattern>,
+}
+
+impl ClientPolicy {
+    /// Lists the [`MirrordPolicy`]s in the target's `namespace` and all [`MirrordClusterPolicy`]s,
+    /// and merges the ones that apply to the given `target`.
+    ///
+    /// Fails when the policies cannot be listed (e.g. the user is not allowed to list them), as
+    /// we cannot enforce restrictions we don't know about. Missing policy CRDs mean that there are
+    /// no policies.
+    #[tracing::instrument(level = Level::DEBUG, skip(client, target), ret, err)]
+    pub async fn fetch<const CHECKED: bool>(
+        client: &Client,
+        target: &ResolvedTarget<CHECKED>,
+        namespace: &str,
+    ) -> OperatorApiResult<Self> {
+        let target_paths = target_paths(target);
+        let labels = target.clone().into_labels().unwrap_or_default();
+
+        let mut policy = Self::default();
+
+        list_policies(Api::<MirrordPolicy>::namespaced(client.clone(), namespace))
+            .await?
+            .into_iter()
+            .filter(|found| {
+                applies(
+                    found.spec.target_path.as_deref(),
+                    found.spec.selector.as_ref(),
+                    &target_paths,
+                    &labels,
+                )
+            })
+            .for_each(|found| {
+                let name = found.name_any();
+                policy.add(
+                    &name,
+                    &found.spec.fs,
+                    &found.spec.network,
+                    &found.spec.env,
+                    &found.spec.http_filter,
+                )
+            });
+
+        list_policies(Api::<MirrordClusterPolicy>::all(client.clone()))
+            .await?
+            .into_iter()
+            .filter(|found| {
+                applies(
+                    found.spec.target_path.as_deref(),
+                    found.spec.selector.as_ref(),
+                    &target_paths,
+                    &labels,
+                )
+            })
+            .for_each(|found| {
+                let name = found.name_any();
+                policy.add(
+                    &name,
+                    &found.spec.fs,
+                    &found.spec.network,
+                    &found.spec.env,
+                    &found.spec.http_filter,
+                )
+            });
+
+        if let Some(user) = UserIdentity::load().name {
+            policy.set_user(&user);
+        }
+
+        Ok(policy)
+    }
+
+    /// Replaces `{user}` in the required header filters with the given name.
+    fn set_user(&mut self, user: &str) {
+        for required in &mut self.steal_header_filter {
+            required.pattern = required.pattern.replace("{user}", user.trim());
+        }
+    }
+
+    /// Adds the restrictions of one policy.
+    fn add(
+        &mut self,
+        name: &str,
+        fs: &FsPolicy,
+        network: &NetworkPolicy,
+        env: &EnvPolicy,
+        http_filter: &HttpFilterPolicy,
+    ) {
+        let patterns = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| PolicyPattern {
+                    pattern: pattern.clone(),
+                    policy: name.to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        self.fs_block.extend(patterns(&fs.block));
+        self.outgoing_block
+            .extend(patterns(&network.outgoing.block));
+        self.env_exclude.extend(patterns(&env.exclude));
+        self.steal_header_filter
+            .extend(patterns(http_filter.header_filter.as_slice()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fs_block.is_empty()
+            && self.outgoing_block.is_empty()
+            && self.env_exclude.is_empty()
+            && self.steal_header_filter.is_empty()
+    }
+
+    /// Returns [`ResponseError::Forbidden`] if stealing with the given [`StealType`] is blocked,
+    /// because the filter does not include one of the required HTTP header filters.
+    pub fn check_steal(&self, steal_type: &StealType) -> Result<(), ResponseError> {
+        self.required_header_filters()
+            .iter()
+            .try_for_each(|required| required.check(steal_type))
+    }
+
+    /// Returns the HTTP header filters required in every steal filter, for the agent to enforce
+    /// them.
+    pub fn required_header_filters(&self) -> Vec<RequiredHeaderFilter> {
+        self.steal_header_filter
+            .iter()
+            .map(|required| RequiredHeaderFilter {
+                header_filter: required.pattern.clone(),
+                policy: required.policy.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns [`ResponseError::Forbidden`] if accessing the remote file at the given `path` is
+    /// blocked.
+    ///
+    /// The path is normalized before matching, so that `..` cannot be used to escape a pattern.
+    /// Symlinks are not resolved here, this is done by the agent (see [`Self::blocked_paths`]).
+    pub fn check_file_access(&self, path: &Path) -> Result<(), ResponseError> {
+        let normalized = normalize_path(path);
+        let normalized = normalized.to_string_lossy();
+
+        match self
+            .fs_block
+            .iter()
+            .find(|blocked| WildMatch::new(&blocked.pattern).matches(&normalized))
+        {
+            Some(blocked) => Err(ResponseError::Forbidden {
+                blocked_action: BlockedAction::FileAccess(path.to_path_buf()),
+                policy_name: Some(blocked.policy.clone()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns [`ResponseError::Forbidden`] if any remote paths are blocked.
+    ///
+    /// Used for relative paths that cannot be resolved, e.g. because they are relative to an
+    /// unknown directory descriptor.
+    pub fn check_unresolved_file_access(&self, path: &Path) -> Result<(), ResponseError> {
+        match self.fs_block.first() {
+            Some(blocked) => Err(ResponseError::Forbidden {
+                blocked_action: BlockedAction::FileAccess(path.to_path_buf()),
+                policy_name: Some(blocked.policy.clone()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the patterns of blocked remote paths, for the agent to enforce them.
+    pub fn blocked_paths(&self) -> Vec<BlockedPath> {
+        self.fs_block
+            .iter()
+            .map(|blocked| BlockedPath {
+                pattern: blocked.pattern.clone(),
+                policy: blocked.policy.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns [`ResponseError::Forbidden`] if outgoing traffic to the given `address` is
+    /// blocked. Unix addresses are never blocked.
+    pub fn check_outgoing(&self, address: &SocketAddress) -> Result<(), ResponseError> {
+        let SocketAddress::Ip(ip_address) = address else {
+            return Ok(());
+        };
+
+        match self
+            .outgoing_block
+            .iter()
+            .find(|blocked| net_contains(&blocked.pattern, ip_address.ip()))
+        {
+            Some(blocked) => Err(ResponseError::Forbidden {
+                blocked_action: BlockedAction::Outgoing(address.clone()),
+                policy_name: Some(blocked.policy.clone()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes env vars excluded by the policies from the given map.
+    ///
+    /// Returns the names of the removed vars.
+    pub fn filter_env(&self, env: &mut HashMap<String, String>) -> Vec<String> {
+        if self.env_exclude.is_empty() {
+            return Default::default();
+        }
+
+        let patterns = self
+            .env_exclude
+            .iter()
+            .map(|excluded| WildMatch::new(&excluded.pattern))
+            .collect::<Vec<_>>();
+
+        let mut removed = Vec::new();
+        env.retain(|name, _| {
+            let excluded = patterns.iter().any(|pattern| pattern.matches(name));
+            if excluded {
+                removed.push(name.clone());
+            }
+            !excluded
+        });
+
+        removed
+    }
+}
+
+/// Lists all policies of one kind. A missing CRD (404) means that there are no policies.
+async fn list_policies<K>(api: Api<K>) -> OperatorApiResult<Vec<K>>
+where
+    K: Clone + DeserializeOwned + fmt::Debug,
+{
+    match api.list(&ListParams::default()).await {
+        Ok(list) => Ok(list.items),
+        Err(kube::Error::Api(response)) if response.code == 404 => Ok(vec![]),
+        Err(error) => Err(OperatorApiError::KubeError {
+            error,
+            operation: OperatorOperation::FetchingPolicies,
+        }),
+    }
+}
+
+/// Paths under which the `target` can be matched by the `targetPath` of a policy, e.g.
+/// `deploy/my-deploy` and `deployment/my-deploy/container/my-container`.
+fn target_paths<const CHECKED: bool>(target: &ResolvedTarget<CHECKED>) -> Vec<String> {
+    let Some(name) = target.name() else {
+        return vec![target.type_().to_string()];
+    };
+
+    let mut types = vec![target.type_()];
+    if let ResolvedTarget::Deployment(..) = target {
+        types.push("deploy");
+    }
+
+    types
+        .into_iter()
+        .flat_map(|type_| {
+            let path = format!("{type_}/{name}");
+            let with_container = target
+                .container()
+                .map(|container| format!("{path}/container/{container}"));
+            std::iter::once(path).chain(with_container)
+        })
+        .collect()
+}
+
+/// Whether a policy with the given `targetPath` and `selector` applies to the target.
+fn applies(
+    target_path: Option<&str>,
+    selector: Option<&LabelSelector>,
+    target_paths: &[String],
+    labels: &BTreeMap<String, String>,
+) -> bool {
+    let path_matches = target_path.map_or(true, |pattern| {
+        let pattern = WildMatch::new(pattern);
+        target_paths.iter().any(|path| pattern.matches(path))
+    });
+
+    path_matches && selector.map_or(true, |selector| selector.matches(labels))
+}
+
+/// Whether the network given as a CIDR or a single IP contains the `ip`.
+///
+/// Invalid entries are logged and ignored.
+fn net_contains(network: &str, ip: IpAddr) -> bool {
+    if let Ok(network) = network.parse::<IpNet>() {
+        return network.contains(&ip);
+    }
+
+    match network.parse::<IpAddr>() {
+        Ok(single) => single == ip,
+        Err(..) => {
+            tracing::warn!(
+                network,
+                "Invalid entry in `network.outgoing.block` of a policy"
+            );
+            false
+        }
+    }
+}
+
+/// Lexically resolves `.` and `..` components of the path, without accessing the filesystem.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use mirrord_protocol::tcp::{Filter, HttpFilter};
+    use rstest::rstest;
+
+    use super::*;
+
+    fn policy() -> ClientPolicy {
+        let mut policy = ClientPolicy::default();
+        policy.add(
+            "security",
+            &FsPolicy {
+                block: vec!["/var/run/secrets/*".into()],
+            },
+            &NetworkPolicy {
+                outgoing: crate::crd::policy::OutgoingPolicy {
+                    block: vec!["10.0.0.0/16".into(), "192.168.1.1".into()],
+                },
+            },
+            &EnvPolicy {
+                exclude: vec!["*_PASSWORD".into()],
+            },
+            &HttpFilterPolicy {
+                header_filter: Some("X-Mirrord-User: {user}".into()),
+            },
+        );
+        policy.set_user("me");
+        policy
+    }
+
+    #[rstest]
+    #[case("/var/run/secrets/kubernetes.io/serviceaccount/token", false)]
+    #[case("/var/run/../run/secrets/token", false)]
+    #[case("/var/run/secrets", true)]
+    #[case("/etc/hosts", true)]
+    fn file_access(#[case] path: &str, #[case] allowed: bool) {
+        let result = policy().check_file_access(Path::new(path));
+        assert_eq!(result.is_ok(), allowed, "{result:?}");
+
+        if let Err(ResponseError::Forbidden { policy_name, .. }) = result {
+            assert_eq!(policy_name.as_deref(), Some("security"));
+        }
+    }
+
+    #[test]
+    fn unresolved_file_access() {
+        assert!(policy()
+            .check_unresolved_file_access(Path::new("token"))
+            .is_err());
+        assert!(ClientPolicy::default()
+            .check_unresolved_file_access(Path::new("token"))
+            .is_ok());
+    }
+
+    #[rstest]
+    #[case("10.0.12.1:5432", false)]
+    #[case("192.168.1.1:80", false)]
+    #[case("192.168.1.2:80", true)]
+    #[case("10.1.0.1:5432", true)]
+    fn outgoing(#[case] address: SocketAddr, #[case] allowed: bool) {
+        assert_eq!(
+            policy().check_outgoing(&SocketAddress::Ip(address)).is_ok(),
+            allowed
+        );
+    }
+
+    #[test]
+    fn env() {
+        let mut env = HashMap::from([
+            ("DB_PASSWORD".to_string(), "secret".to_string()),
+            ("DB_HOST".to_string(), "db".to_string()),
+        ]);
+
+        let removed = policy().filter_env(&mut env);
+
+        assert_eq!(removed, ["DB_PASSWORD"]);
+        assert_eq!(env.keys().collect::<Vec<_>>(), ["DB_HOST"]);
+    }
+
+    fn header(value: &str) -> HttpFilter {
+        HttpFilter::Header(Filter::new(value.into()).unwrap())
+    }
+
+    #[rstest]
+    #[case(StealType::All(80), false)]
+    #[case(StealType::FilteredHttp(80, Filter::new("X-Mirrord-User: me".into()).unwrap()), true)]
+    #[case(StealType::FilteredHttpEx(80, header("X-Mirrord-User: you")), false)]
+    #[case(StealType::FilteredHttpEx(80, HttpFilter::Path(Filter::new("/api".into()).unwrap())), false)]
+    #[case(
+        StealType::FilteredHttpEx(80, HttpFilter::Composite {
+            all: true,
+            filters: vec![HttpFilter::Path(Filter::new("/api".into()).unwrap()), header("X-Mirrord-User: me")],
+        }),
+        true
+    )]
+    #[case(
+        StealType::FilteredHttpEx(80, HttpFilter::Composite {
+            all: false,
+            filters: vec![HttpFilter::Path(Filter::new("/api".into()).unwrap()), header("X-Mirrord-User: me")],
+        }),
+        false
+    )]
+    fn steal(#[case] steal_type: StealType, #[case] allowed: bool) {
+        let result = policy().check_steal(&steal_type);
+        assert_eq!(result.is_ok(), allowed, "{result:?}");
+    }
+
+    #[rstest]
+    #[case(Some("deploy/my-*"), true)]
+    #[case(Some("deployment/my-deploy/container/*"), true)]
+    #[case(Some("pod/*"), false)]
+    #[case(None, true)]
+    fn target_path(#[case] pattern: Option<&str>, #[case] expected: bool) {
+        let paths = [
+            "deployment/my-deploy",
+            "deployment/my-deploy/container/app",
+            "deploy/my-deploy",
+            "deploy/my-deploy/container/app",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            applies(pattern, None, &paths, &Default::default()),
+            expected
+        );
+    }
+}
@@ -63,6 +63,9 @@ impl TargetCrd {
             Target::Job(target) => ("job", &target.job, &target.container),
             Target::CronJob(target) => ("cronjob", &target.cron_job, &target.container),
             Target::StatefulSet(target) => ("statefulset", &target.stateful_set, &target.container),
+            Target::Service(target) => ("service", &target.service, &target.container),
+            Target::ReplicaSet(target) => ("replicaset", &target.replica_set, &target.container),
+            Target::DaemonSet(target) => ("daemonset", &target.daemon_set, &target.container),
             Target::Targetless => return TARGETLESS_TARGET_NAME.to_string(),
         };
 
@@ -25,6 +25,59 @@ pub enum BlockedFeature {
     Unknown,
 }
 
+/// Remote file access blocked by a policy.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+pub struct FsPolicy {
+    /// Patterns of remote paths that cannot be accessed, e.g. `/var/run/secrets/**`.
+    ///
+    /// `?` matches exactly one occurrence of any character and `*` matches arbitrary many
+    /// (including zero) occurrences of any character, `/` included.
+    #[serde(default)]
+    pub block: Vec<String>,
+}
+
+/// Network access blocked by a policy.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+pub struct NetworkPolicy {
+    /// Outgoing traffic blocked by this policy.
+    #[serde(default)]
+    pub outgoing: OutgoingPolicy,
+}
+
+/// Outgoing traffic blocked by a policy.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+pub struct OutgoingPolicy {
+    /// Remote destinations to which connections cannot be made, as CIDRs (`10.0.0.0/16`) or
+    /// single IP addresses (`10.0.0.1`).
+    #[serde(default)]
+    pub block: Vec<String>,
+}
+
+/// Remote environment variables hidden by a policy.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+pub struct EnvPolicy {
+    /// Patterns of environment variable names that are not fetched from the target, e.g.
+    /// `*_PASSWORD`.
+    ///
+    /// `?` matches exactly one occurrence of any character and `*` matches arbitrary many
+    /// (including zero) occurrences of any character.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Requirements for the HTTP filters used when stealing traffic, imposed by a policy.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpFilterPolicy {
+    /// When set, traffic can be stolen only with an HTTP filter that includes a header filter
+    /// equal to this value, e.g. `X-Mirrord-User: {user}`. `{user}` is replaced with the name of
+    /// the user running mirrord.
+    ///
+    /// The header filter can be used alone, or inside of an `all_of` filter. In an `any_of`
+    /// filter, every clause must be such a header filter. Stealing without a filter is blocked.
+    pub header_filter: Option<String>,
+}
+
 /// Custom resource for policies that limit what mirrord features users can use.
 ///
 /// This policy applies only to resources living in the same namespace.
@@ -51,6 +104,22 @@ pub struct MirrordPolicySpec {
     // TODO: make the k8s list type be set/map to prevent duplicates.
     /// List of features and operations blocked by this policy.
     pub block: Vec<BlockedFeature>,
+
+    /// File operations blocked by this policy.
+    #[serde(default)]
+    pub fs: FsPolicy,
+
+    /// Network operations blocked by this policy.
+    #[serde(default)]
+    pub network: NetworkPolicy,
+
+    /// Environment variables hidden by this policy.
+    #[serde(default)]
+    pub env: EnvPolicy,
+
+    /// Requirements for the HTTP filters used when stealing traffic.
+    #[serde(default)]
+    pub http_filter: HttpFilterPolicy,
 }
 
 /// Custom cluster-wide resource for policies that limit what mirrord features users can use.
@@ -78,6 +147,22 @@ pub struct MirrordClusterPolicySpec {
     // TODO: make the k8s list type be set/map to prevent duplicates.
     /// List of features and operations blocked by this policy.
     pub block: Vec<BlockedFeature>,
+
+    /// File operations blocked by this policy.
+    #[serde(default)]
+    pub fs: FsPolicy,
+
+    /// Network operations blocked by this policy.
+    #[serde(default)]
+    pub network: NetworkPolicy,
+
+    /// Environment variables hidden by this policy.
+    #[serde(default)]
+    pub env: EnvPolicy,
+
+    /// Requirements for the HTTP filters used when stealing traffic.
+    #[serde(default)]
+    pub http_filter: HttpFilterPolicy,
 }
 
 #[test]
@@ -938,6 +938,16 @@ impl OperatorClusterUserRole {
                     verbs: vec!["deletecollection".to_owned(), "delete".to_owned()],
                     ..Default::default()
                 },
+                // The client enforces parts of the policies, so it has to read them.
+                PolicyRule {
+                    api_groups: Some(vec![MirrordPolicy::group(&()).into_owned()]),
+                    resources: Some(vec![
+                        MirrordPolicy::plural(&()).into_owned(),
+                        MirrordClusterPolicy::plural(&()).into_owned(),
+                    ]),
+                    verbs: vec!["get".to_owned(), "list".to_owned()],
+                    ..Default::default()
+                },
             ]),
             ..Default::default()
         };
@@ -12,7 +12,10 @@ use mirrord_macros::protocol_break;
 use semver::VersionReq;
 
 use crate::{
-    dns::{GetAddrInfoRequest, GetAddrInfoResponse},
+    dns::{
+        DnsQueryRequest, DnsQueryResponse, GetAddrInfoRequest, GetAddrInfoRequestV2,
+        GetAddrInfoResponse,
+    },
     file::*,
     outgoing::{
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
@@ -20,6 +23,7 @@ use crate::{
     },
     pause::DaemonPauseTarget,
     tcp::{DaemonTcp, LayerTcp, LayerTcpSteal},
+    udp::{DaemonUdp, LayerUdp},
     vpn::{ClientVpn, ServerVpn},
     ResponseError,
 };
@@ -85,6 +89,21 @@ pub enum FileRequest {
     ReadDirBatch(ReadDirBatchRequest),
     MakeDir(MakeDirRequest),
     MakeDirAt(MakeDirAtRequest),
+    Unlink(UnlinkRequest),
+    UnlinkAt(UnlinkAtRequest),
+    Rename(RenameRequest),
+    RemoveDir(RemoveDirRequest),
+    Ftruncate(FtruncateRequest),
+    Truncate(TruncateRequest),
+    Chmod(ChmodRequest),
+    Fchmod(FchmodRequest),
+    Chown(ChownRequest),
+    Fchown(FchownRequest),
+    Utimensat(UtimensatRequest),
+    Symlink(SymlinkRequest),
+    Link(LinkRequest),
+    Reopen(ReopenFileRequest),
+    FsPolicy(FsPolicyRequest),
 }
 
 /// Minimal mirrord-protocol version that allows `ClientMessage::ReadyForLogs` message.
@@ -108,6 +127,16 @@ pub enum ClientMessage {
     SwitchProtocolVersion(#[bincode(with_serde)] semver::Version),
     ReadyForLogs,
     Vpn(ClientVpn),
+    /// Same as [`ClientMessage::GetAddrInfoRequest`], but allows resolving IPv6 addresses.
+    ///
+    /// Agent responds with [`DaemonMessage::GetAddrInfoResponse`].
+    GetAddrInfoRequestV2(GetAddrInfoRequestV2),
+    /// Incoming UDP traffic, see [`LayerUdp`].
+    Udp(LayerUdp),
+    /// Query for DNS records of any type.
+    ///
+    /// Agent responds with [`DaemonMessage::DnsQueryResponse`].
+    DnsQuery(DnsQueryRequest),
 }
 
 /// Type alias for `Result`s that should be returned from mirrord-agent to mirrord-layer.
@@ -130,6 +159,19 @@ pub enum FileResponse {
     ReadLink(RemoteResult<ReadLinkFileResponse>),
     ReadDirBatch(RemoteResult<ReadDirBatchResponse>),
     MakeDir(RemoteResult<()>),
+    /// Response to both [`FileRequest::Unlink`] and [`FileRequest::UnlinkAt`].
+    Unlink(RemoteResult<()>),
+    Rename(RemoteResult<()>),
+    RemoveDir(RemoteResult<()>),
+    /// Response to both [`FileRequest::Ftruncate`] and [`FileRequest::Truncate`].
+    Truncate(RemoteResult<()>),
+    /// Response to both [`FileRequest::Chmod`] and [`FileRequest::Fchmod`].
+    Chmod(RemoteResult<()>),
+    /// Response to both [`FileRequest::Chown`] and [`FileRequest::Fchown`].
+    Chown(RemoteResult<()>),
+    Utimensat(RemoteResult<()>),
+    Symlink(RemoteResult<()>),
+    Link(RemoteResult<()>),
 }
 
 /// `-agent` --> `-layer` messages.
@@ -153,6 +195,9 @@ pub enum DaemonMessage {
     PauseTarget(DaemonPauseTarget),
     SwitchProtocolVersionResponse(#[bincode(with_serde)] semver::Version),
     Vpn(ServerVpn),
+    /// Incoming UDP traffic, see [`DaemonUdp`].
+    Udp(DaemonUdp),
+    DnsQueryResponse(DnsQueryResponse),
 }
 
 pub struct ProtocolCodec<I, O> {
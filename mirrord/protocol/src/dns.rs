@@ -1,12 +1,21 @@
 extern crate alloc;
 use core::ops::Deref;
-use std::net::IpAddr;
+use std::{net::IpAddr, sync::LazyLock};
 
 use bincode::{Decode, Encode};
 use hickory_resolver::{lookup_ip::LookupIp, proto::rr::resource::RecordParts};
+use semver::VersionReq;
 
 use crate::RemoteResult;
 
+/// Minimal mirrord-protocol version that allows [`GetAddrInfoRequestV2`].
+pub static ADDRINFO_V2_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.14.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`DnsQueryRequest`].
+pub static DNS_QUERY_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.23.0".parse().expect("Bad Identifier"));
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct LookupRecord {
     pub name: String,
@@ -73,3 +82,81 @@ impl Deref for GetAddrInfoResponse {
 pub struct GetAddrInfoRequest {
     pub node: String,
 }
+
+/// Which address families the agent should resolve in a [`GetAddrInfoRequestV2`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum AddressFamily {
+    /// Only `A` records, same as the old [`GetAddrInfoRequest`].
+    #[default]
+    Ipv4Only,
+    /// Only `AAAA` records.
+    Ipv6Only,
+    /// Both `A` and `AAAA` records.
+    Both,
+}
+
+/// Triggered by the `mirrord-layer` hook of `getaddrinfo_detour`.
+///
+/// Unlike [`GetAddrInfoRequest`], carries the address family requested by the user, which allows
+/// the agent to return IPv6 addresses.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetAddrInfoRequestV2 {
+    pub node: String,
+    pub family: AddressFamily,
+}
+
+impl From<GetAddrInfoRequest> for GetAddrInfoRequestV2 {
+    /// Old requests only ever resolved IPv4 addresses.
+    fn from(GetAddrInfoRequest { node }: GetAddrInfoRequest) -> Self {
+        Self {
+            node,
+            family: AddressFamily::Ipv4Only,
+        }
+    }
+}
+
+impl From<GetAddrInfoRequestV2> for GetAddrInfoRequest {
+    /// Drops the [`AddressFamily`], for agents that don't support [`ADDRINFO_V2_VERSION`].
+    fn from(GetAddrInfoRequestV2 { node, .. }: GetAddrInfoRequestV2) -> Self {
+        Self { node }
+    }
+}
+
+/// Triggered by the `mirrord-layer` hooks of `res_query`, `res_search` and `getnameinfo`.
+///
+/// Unlike [`GetAddrInfoRequestV2`], allows querying records of any type (e.g. `SRV`, `TXT`, `MX`
+/// or `PTR`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct DnsQueryRequest {
+    /// Queried domain name.
+    pub name: String,
+    /// Numeric record type, as in the DNS wire format (e.g. `33` for `SRV`).
+    pub record_type: u16,
+    /// Whether the agent applies the search domains configured in the target's `resolv.conf` to
+    /// the `name`, like `res_search`. Otherwise the `name` is treated as fully qualified, like in
+    /// `res_query`. A name that ends with `.` is always fully qualified.
+    pub search: bool,
+}
+
+/// Single resource record returned in a [`DnsQueryResponse`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct DnsRecord {
+    /// Owner name of the record.
+    pub name: String,
+    /// Numeric record type, as in the DNS wire format.
+    pub record_type: u16,
+    pub ttl: u32,
+    /// Record data in the DNS wire format. Domain names in the data are never compressed.
+    pub data: Vec<u8>,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct DnsQueryResponse(pub RemoteResult<Vec<DnsRecord>>);
+
+impl Deref for DnsQueryResponse {
+    type Target = RemoteResult<Vec<DnsRecord>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
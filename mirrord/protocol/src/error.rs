@@ -2,7 +2,7 @@ use std::{
     fmt::{self, Formatter},
     io,
     net::AddrParseError,
-    path::StripPrefixError,
+    path::{PathBuf, StripPrefixError},
     sync::LazyLock,
 };
 
@@ -94,6 +94,22 @@ pub static MIRROR_BLOCK_VERSION: LazyLock<VersionReq> =
 pub enum BlockedAction {
     Steal(StealType),
     Mirror(Port),
+    /// Accessing the remote file with the given path.
+    ///
+    /// Enforced by the mirrord client and by the agent (see
+    /// [`FsPolicyRequest`](crate::file::FsPolicyRequest)), based on the `fs` section of the policy.
+    FileAccess(PathBuf),
+    /// Making an outgoing connection or sending outgoing datagrams to the given address.
+    ///
+    /// Enforced by the mirrord client, based on the `network.outgoing` section of the policy.
+    Outgoing(SocketAddress),
+    /// Stealing traffic with a filter that does not include the required HTTP header filter.
+    ///
+    /// Enforced by the mirrord client, based on the `httpFilter` section of the policy.
+    StealWithoutHeaderFilter {
+        steal_type: StealType,
+        header_filter: String,
+    },
 }
 
 /// Determines how a blocked action will be displayed to the user in an error.
@@ -118,6 +134,22 @@ impl fmt::Display for BlockedAction {
             BlockedAction::Mirror(port) => {
                 write!(f, "Mirroring traffic from port {port}")
             }
+            BlockedAction::FileAccess(path) => {
+                write!(f, "Accessing remote file {}", path.display())
+            }
+            BlockedAction::Outgoing(address) => {
+                write!(f, "Outgoing traffic to {address}")
+            }
+            BlockedAction::StealWithoutHeaderFilter {
+                steal_type,
+                header_filter,
+            } => {
+                write!(
+                    f,
+                    "{} without the HTTP header filter `{header_filter}`",
+                    BlockedAction::Steal(steal_type.clone())
+                )
+            }
         }
     }
 }
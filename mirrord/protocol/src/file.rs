@@ -25,6 +25,32 @@ pub static READDIR_BATCH_VERSION: LazyLock<VersionReq> =
 pub static MKDIR_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.13.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`UnlinkRequest`], [`UnlinkAtRequest`],
+/// [`RenameRequest`] and [`RemoveDirRequest`].
+pub static REMOVE_RENAME_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.15.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`FtruncateRequest`] and [`TruncateRequest`].
+pub static TRUNCATE_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.15.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`ChmodRequest`], [`FchmodRequest`],
+/// [`ChownRequest`], [`FchownRequest`] and [`UtimensatRequest`].
+pub static SET_METADATA_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.16.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`SymlinkRequest`] and [`LinkRequest`].
+pub static LINK_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.16.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`ReopenFileRequest`].
+pub static REOPEN_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.21.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`FsPolicyRequest`].
+pub static FS_POLICY_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.22.0".parse().expect("Bad Identifier"));
+
 /// Internal version of Metadata across operating system (macOS, Linux)
 /// Only mutual attributes
 #[derive(Encode, Decode, Debug, PartialEq, Clone, Copy, Eq, Default)]
@@ -282,6 +308,158 @@ pub struct MakeDirAtRequest {
     pub mode: u32,
 }
 
+/// `unlink` request, removes the file (or symbolic link) at `pathname`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct UnlinkRequest {
+    pub pathname: PathBuf,
+}
+
+/// `unlinkat` request, with `pathname` relative to the remote directory `dirfd`.
+///
+/// `flags` may contain `AT_REMOVEDIR`, which turns this request into `rmdir`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct UnlinkAtRequest {
+    pub dirfd: u64,
+    pub pathname: PathBuf,
+    pub flags: u32,
+}
+
+/// `rename` request, moves `old_path` to `new_path`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct RenameRequest {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+}
+
+/// `rmdir` request, removes the empty directory at `pathname`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct RemoveDirRequest {
+    pub pathname: PathBuf,
+}
+
+/// `ftruncate` request, changes the size of the remote file `fd` to `length`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FtruncateRequest {
+    pub fd: u64,
+    pub length: u64,
+}
+
+/// `truncate` request, changes the size of the file at `path` to `length`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct TruncateRequest {
+    pub path: PathBuf,
+    pub length: u64,
+}
+
+/// `chmod` request, changes the permission bits of the file at `path`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ChmodRequest {
+    pub path: PathBuf,
+    pub mode: u32,
+}
+
+/// `fchmod` request, changes the permission bits of the remote file `fd`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FchmodRequest {
+    pub fd: u64,
+    pub mode: u32,
+}
+
+/// `chown` request, changes the owner and group of the file at `path`.
+///
+/// `None` leaves the id unchanged (`-1` in `libc`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ChownRequest {
+    pub path: PathBuf,
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+}
+
+/// `fchown` request, changes the owner and group of the remote file `fd`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FchownRequest {
+    pub fd: u64,
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+}
+
+/// A file timestamp, as passed to `utimensat`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileTimeInternal {
+    /// `UTIME_NOW`, set the timestamp to the current time.
+    Now,
+    /// `UTIME_OMIT`, leave the timestamp unchanged.
+    Omit,
+    Set {
+        seconds: i64,
+        nanoseconds: i64,
+    },
+}
+
+/// `utimensat` (and `futimens`) request.
+///
+/// - `pathname` absolute: `dirfd` is ignored;
+/// - `pathname` relative: it's relative to the remote directory `dirfd`;
+/// - `pathname` is `None`: the timestamps of the remote file `dirfd` itself are changed.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct UtimensatRequest {
+    pub dirfd: Option<u64>,
+    pub pathname: Option<PathBuf>,
+    pub access_time: FileTimeInternal,
+    pub modification_time: FileTimeInternal,
+    /// Whether to follow a symlink at `pathname` (no `AT_SYMLINK_NOFOLLOW`).
+    pub follow_symlink: bool,
+}
+
+/// `symlink` request, creates a symbolic link at `link_path` that points to `target`.
+///
+/// `target` is stored in the link as is, it's not resolved.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct SymlinkRequest {
+    pub target: PathBuf,
+    pub link_path: PathBuf,
+}
+
+/// `link` request, creates a hard link at `new_path` to the file at `old_path`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct LinkRequest {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+}
+
+/// Opens the file at `path` under the given `fd`, and moves the file offset to `position`.
+///
+/// Sent by the intproxy after it makes a new agent connection, to restore files that the layer
+/// opened through the previous connection. Answered with [`FileResponse::Open`].
+///
+/// [`FileResponse::Open`]: crate::FileResponse::Open
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ReopenFileRequest {
+    pub fd: u64,
+    pub path: PathBuf,
+    pub open_options: OpenOptionsInternal,
+    pub position: u64,
+}
+
+/// Remote paths blocked by the mirrord policies, see [`BlockedPath`].
+///
+/// Sent by the intproxy before other file requests. The agent rejects requests for the blocked
+/// paths with [`ResponseError::Forbidden`](crate::ResponseError::Forbidden), and hides them from
+/// directory listings. Not answered.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FsPolicyRequest {
+    pub block: Vec<BlockedPath>,
+}
+
+/// A pattern of remote paths blocked by a mirrord policy.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct BlockedPath {
+    /// Glob pattern matched against the whole path, after the symlinks are resolved.
+    pub pattern: String,
+    /// Name of the policy that blocks the path.
+    pub policy: String,
+}
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct ReadLimitedFileRequest {
     pub remote_fd: u64,
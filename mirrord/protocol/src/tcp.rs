@@ -25,7 +25,10 @@ use tokio::sync::mpsc::Receiver;
 use tokio_stream::wrappers::ReceiverStream;
 use tracing::{error, Level};
 
-use crate::{body_chunks::BodyExt as _, ConnectionId, Port, RemoteResult, RequestId};
+use crate::{
+    body_chunks::BodyExt as _, BlockedAction, ConnectionId, Port, RemoteResult, RequestId,
+    ResponseError,
+};
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct NewTcpConnection {
@@ -157,6 +160,59 @@ pub enum HttpFilter {
         /// Filters to use
         filters: Vec<HttpFilter>,
     },
+    /// Filter by method ("POST"), case-insensitive
+    Method(String),
+    /// Filter by query parameters, matched against each `key=value` pair ("tenant=foo")
+    Query(Filter),
+    /// Filter by body (boxed, so that it doesn't bloat this enum)
+    Body(Box<HttpBodyFilter>),
+}
+
+/// Describes how [`HttpFilter::Body`] inspects the request body.
+///
+/// Only bodies that fit in the agent's buffer are inspected, bigger bodies never match.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum HttpBodyFilter {
+    /// Regex matched against the whole body
+    Regex(Filter),
+    /// JSONPath `query` evaluated on the JSON body, stringified results are matched against
+    /// the regex
+    Json { query: String, matches: Filter },
+}
+
+impl Display for HttpBodyFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpBodyFilter::Regex(filter) => write!(f, "{filter}"),
+            HttpBodyFilter::Json { query, matches } => write!(f, "json {query}={matches}"),
+        }
+    }
+}
+
+impl HttpFilter {
+    /// Whether every request matched by this filter is also matched by the header filter
+    /// `required`.
+    pub fn includes_header_filter(&self, required: &str) -> bool {
+        match self {
+            HttpFilter::Header(header) => header.to_string() == required,
+            HttpFilter::Composite { all: true, filters } => filters
+                .iter()
+                .any(|filter| filter.includes_header_filter(required)),
+            HttpFilter::Composite {
+                all: false,
+                filters,
+            } => {
+                !filters.is_empty()
+                    && filters
+                        .iter()
+                        .all(|filter| filter.includes_header_filter(required))
+            }
+            HttpFilter::Path(..)
+            | HttpFilter::Method(..)
+            | HttpFilter::Query(..)
+            | HttpFilter::Body(..) => false,
+        }
+    }
 }
 
 impl Display for HttpFilter {
@@ -164,6 +220,9 @@ impl Display for HttpFilter {
         match self {
             HttpFilter::Header(filter) => write!(f, "header={filter}"),
             HttpFilter::Path(filter) => write!(f, "path={filter}"),
+            HttpFilter::Method(method) => write!(f, "method={method}"),
+            HttpFilter::Query(filter) => write!(f, "query={filter}"),
+            HttpFilter::Body(filter) => write!(f, "body={filter}"),
             HttpFilter::Composite { all, filters } => match all {
                 true => {
                     write!(f, "all of ")?;
@@ -216,6 +275,46 @@ impl StealType {
         | StealType::FilteredHttp(port, ..)) = self;
         *port
     }
+
+    /// Whether every request stolen with this subscription is also matched by the header filter
+    /// `required`.
+    pub fn includes_header_filter(&self, required: &str) -> bool {
+        match self {
+            StealType::All(..) => false,
+            StealType::FilteredHttp(_, filter) => filter.to_string() == required,
+            StealType::FilteredHttpEx(_, filter) => filter.includes_header_filter(required),
+        }
+    }
+}
+
+/// Minimal mirrord-protocol version that allows [`LayerTcpSteal::RequiredHeaderFilters`].
+pub static STEAL_POLICY_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.22.0".parse().expect("Bad Identifier"));
+
+/// An HTTP header filter required by a mirrord policy in every steal subscription.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct RequiredHeaderFilter {
+    pub header_filter: String,
+    /// Name of the policy that requires the filter.
+    pub policy: String,
+}
+
+impl RequiredHeaderFilter {
+    /// Returns [`ResponseError::Forbidden`] if the `steal_type` does not include this header
+    /// filter.
+    pub fn check(&self, steal_type: &StealType) -> RemoteResult<()> {
+        if steal_type.includes_header_filter(&self.header_filter) {
+            return Ok(());
+        }
+
+        Err(ResponseError::Forbidden {
+            blocked_action: BlockedAction::StealWithoutHeaderFilter {
+                steal_type: steal_type.clone(),
+                header_filter: self.header_filter.clone(),
+            },
+            policy_name: Some(self.policy.clone()),
+        })
+    }
 }
 
 /// Messages related to Steal Tcp handler from client.
@@ -228,6 +327,12 @@ pub enum LayerTcpSteal {
     HttpResponse(HttpResponse<Vec<u8>>),
     HttpResponseFramed(HttpResponse<InternalHttpBody>),
     HttpResponseChunked(ChunkedResponse),
+    /// Header filters required by the mirrord policies, see [`RequiredHeaderFilter`].
+    ///
+    /// Sent by the intproxy before the subscriptions. The agent rejects
+    /// [`LayerTcpSteal::PortSubscribe`]s that do not include all of these filters, and UDP steal
+    /// subscriptions if there are any. Not answered.
+    RequiredHeaderFilters(Vec<RequiredHeaderFilter>),
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -458,6 +563,11 @@ pub static HTTP_FILTERED_UPGRADE_VERSION: LazyLock<VersionReq> =
 pub static HTTP_COMPOSITE_FILTER_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.11.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`HttpFilter::Method`], [`HttpFilter::Query`] and
+/// [`HttpFilter::Body`]
+pub static HTTP_METHOD_QUERY_BODY_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.17.0".parse().expect("Bad Identifier"));
+
 /// Protocol break - on version 2, please add source port, dest/src IP to the message
 /// so we can avoid losing this information.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
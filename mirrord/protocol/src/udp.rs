@@ -0,0 +1,97 @@
+//! Messages related to the incoming UDP traffic feature.
+
+use std::{fmt, net::IpAddr, sync::LazyLock};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+use crate::{Port, RemoteResult};
+
+/// Minimal mirrord-protocol version that allows [`LayerUdp`] and [`DaemonUdp`] messages.
+pub static UDP_MIRROR_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.18.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerUdp::StealPortSubscribe`],
+/// [`LayerUdp::StealPortUnsubscribe`] and [`LayerUdp::StealReply`].
+pub static UDP_STEAL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.19.0".parse().expect("Bad Identifier"));
+
+/// A single UDP datagram sent to a subscribed port of the target.
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct UdpDatagram {
+    /// Address of the peer that sent this datagram.
+    pub remote_address: IpAddr,
+    /// Port of the peer that sent this datagram.
+    pub source_port: Port,
+    /// Address of the target that received this datagram.
+    pub local_address: IpAddr,
+    /// Subscribed port of the target that received this datagram.
+    pub destination_port: Port,
+    /// Payload of this datagram.
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Debug for UdpDatagram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpDatagram")
+            .field("remote_address", &self.remote_address)
+            .field("source_port", &self.source_port)
+            .field("local_address", &self.local_address)
+            .field("destination_port", &self.destination_port)
+            .field("bytes (length)", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// A reply to a stolen [`UdpDatagram`], sent from the client.
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct UdpReply {
+    /// Address of the peer that sent the stolen datagram.
+    pub remote_address: IpAddr,
+    /// Port of the peer that sent the stolen datagram.
+    pub remote_port: Port,
+    /// Stolen port of the target, the reply is sent from this port.
+    pub port: Port,
+    /// Payload of this reply.
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Debug for UdpReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpReply")
+            .field("remote_address", &self.remote_address)
+            .field("remote_port", &self.remote_port)
+            .field("port", &self.port)
+            .field("bytes (length)", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// Messages related to incoming UDP traffic, sent from the client.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum LayerUdp {
+    /// Start receiving copies of the datagrams sent to the given port.
+    ///
+    /// Agent responds with [`DaemonUdp::SubscribeResult`].
+    PortSubscribe(Port),
+    /// Stop receiving datagrams sent to the given port.
+    PortUnsubscribe(Port),
+    /// Start stealing the datagrams sent to the given port. Stolen datagrams no longer reach the
+    /// target.
+    ///
+    /// Agent responds with [`DaemonUdp::SubscribeResult`].
+    StealPortSubscribe(Port),
+    /// Stop stealing datagrams sent to the given port.
+    StealPortUnsubscribe(Port),
+    /// Send a reply to the peer that sent a stolen datagram.
+    StealReply(UdpReply),
+}
+
+/// Messages related to incoming UDP traffic, sent from the agent.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum DaemonUdp {
+    /// Response to [`LayerUdp::PortSubscribe`] or [`LayerUdp::StealPortSubscribe`].
+    SubscribeResult(RemoteResult<Port>),
+    /// A datagram was sent to one of the subscribed (mirrored or stolen) ports.
+    Datagram(UdpDatagram),
+}